@@ -1,6 +1,3 @@
-use std::str::FromStr;
-
-use domain::base::name::Dname;
 use domain::resolv::StubResolver;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -9,12 +6,7 @@ use tokio_native_tls::native_tls::TlsConnector;
 #[tokio::main]
 async fn main() {
     let resolver = StubResolver::new();
-    let addr = match resolver
-        .lookup_host(
-            &Dname::<Vec<u8>>::from_str("www.rust-lang.org").unwrap(),
-        )
-        .await
-    {
+    let addr = match resolver.lookup_host("www.rust-lang.org").await {
         Ok(addr) => addr,
         Err(err) => {
             eprintln!("DNS query failed: {}", err);
@@ -0,0 +1,40 @@
+//! Parses arbitrary data as a DNS message.
+//!
+//! Parsing may fail but must never panic or loop. The target walks over
+//! all questions and records, parses the record data of all known types,
+//! and formats and composes everything it successfully parsed.
+#![no_main]
+
+use domain::base::name::ParsedDname;
+use domain::base::{Compose, Message};
+use domain::rdata::AllRecordData;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let msg = match Message::from_octets(data) {
+        Ok(msg) => msg,
+        Err(_) => return,
+    };
+    let mut buf = Vec::new();
+    for question in msg.question() {
+        let question = match question {
+            Ok(question) => question,
+            Err(_) => return,
+        };
+        let _ = question.to_string();
+        question.compose(&mut buf).unwrap();
+    }
+    for item in msg.iter() {
+        let record = match item {
+            Ok((record, _)) => record,
+            Err(_) => return,
+        };
+        if let Ok(Some(record)) =
+            record.into_record::<AllRecordData<_, ParsedDname<_>>>()
+        {
+            let _ = record.to_string();
+            record.compose(&mut buf).unwrap();
+        }
+    }
+    let _ = msg.opt();
+});
@@ -0,0 +1,566 @@
+//! A compact CBOR encoding of DNS messages.
+//!
+//! This module provides an experimental encoding of DNS messages in CBOR
+//! for use over constrained transports such as CoAP. It is modelled after
+//! the work on a CBOR format for DNS messages in the IETF CORE working
+//! group but is not wire compatible with any of its drafts. The format may
+//! change as this work progresses.
+//!
+//! A message is converted into its CBOR encoding via [`encode_message`]
+//! and back via [`decode_message`]. The conversion keeps all information
+//! of the message except for name compression, so that decoding the
+//! encoding of a message results in the same message with all domain names
+//! uncompressed.
+//!
+//! # The Encoding
+//!
+//! In CDDL, the encoding is defined as follows:
+//!
+//! ```text
+//! message    = [id: uint, flags: uint, questions: [* question],
+//!               ? answer: [* record], ? authority: [* record],
+//!               ? additional: [* record]]
+//! question   = [name, type: uint, ? class: uint]
+//! record     = [? name, type: uint, ? class: uint, ttl: uint, rdata: bstr]
+//! name       = [* label]
+//! label      = tstr / bstr
+//! ```
+//!
+//! The `flags` are the second 16 bit word of the message header containing
+//! all flags, the opcode, and the response code. The header counts are
+//! given implicitly by the length of the sections. Trailing empty record
+//! sections are left out.
+//!
+//! Domain names are arrays of their labels without the root label. Labels
+//! that are valid UTF-8 are encoded as text strings, all others as byte
+//! strings. The class of questions and records is left out if it is IN.
+//!
+//! The owner name of a record is left out if it is the same as that of the
+//! previous record. For the first record, the name is compared to the name
+//! of the first question instead. Record data is given in its uncompressed
+//! wire format.
+//!
+//! Only definite length items are used and accepted.
+//!
+//! [`encode_message`]: fn.encode_message.html
+//! [`decode_message`]: fn.decode_message.html
+#![cfg(feature = "std")]
+
+use super::header::Header;
+use super::iana::{Class, Rtype};
+use super::message::{Message, RecordSection};
+use super::message_builder::MessageBuilder;
+use super::name::{Dname, ParsedDname, ToDname, ToLabelIter};
+use super::octets::{Compose, OctetsRef, ParseError};
+use super::question::Question;
+use super::rdata::UnknownRecordData;
+use super::record::Record;
+use crate::rdata::AllRecordData;
+use core::convert::TryFrom;
+use core::str;
+use std::vec::Vec;
+
+//------------ CBOR Major Types ----------------------------------------------
+
+const UINT: u8 = 0;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+
+//------------ encode_message ------------------------------------------------
+
+/// Encodes a message in CBOR.
+///
+/// Returns an error if the message cannot be parsed.
+pub fn encode_message<Octets>(
+    msg: &Message<Octets>,
+) -> Result<Vec<u8>, ParseError>
+where
+    Octets: AsRef<[u8]>,
+    for<'a> &'a Octets: OctetsRef,
+{
+    let mut questions = Vec::new();
+    let mut count = 0;
+    for question in msg.question() {
+        let question = question?;
+        let long = question.qclass() != Class::In;
+        push_head(&mut questions, ARRAY, if long { 3 } else { 2 });
+        push_name(&mut questions, question.qname());
+        push_head(&mut questions, UINT, question.qtype().to_int().into());
+        if long {
+            push_head(
+                &mut questions,
+                UINT,
+                question.qclass().to_int().into(),
+            );
+        }
+        count += 1;
+    }
+
+    let mut prev = msg.first_question().map(|q| q.qname().to_vec());
+    let mut sections = Vec::new();
+    let mut section = Some(msg.answer()?);
+    while let Some(records) = section {
+        sections.push(encode_section(records, &mut prev)?);
+        section = records.next_section()?;
+    }
+    while sections.last().map(|s| s.0 == 0).unwrap_or(false) {
+        sections.pop();
+    }
+
+    let mut target = Vec::new();
+    let header = msg.as_slice();
+    push_head(&mut target, ARRAY, 3 + sections.len() as u64);
+    push_head(&mut target, UINT, read_u16(&header[..2]).into());
+    push_head(&mut target, UINT, read_u16(&header[2..4]).into());
+    push_head(&mut target, ARRAY, count);
+    target.extend_from_slice(&questions);
+    for (count, records) in sections {
+        push_head(&mut target, ARRAY, count);
+        target.extend_from_slice(&records);
+    }
+    Ok(target)
+}
+
+/// Encodes the records of a section.
+///
+/// Returns the number of records and their encoding. The owner name of
+/// the last record is kept in `prev`.
+fn encode_section<Ref: OctetsRef>(
+    section: RecordSection<Ref>,
+    prev: &mut Option<Dname<Vec<u8>>>,
+) -> Result<(u64, Vec<u8>), ParseError> {
+    let mut target = Vec::new();
+    let mut count = 0;
+    for record in section {
+        let record = match record?
+            .into_record::<AllRecordData<_, ParsedDname<_>>>()?
+        {
+            Some(record) => record,
+            None => return Err(ParseError::form_error("bad record")),
+        };
+        let owner = record.owner().to_vec();
+        let same = prev
+            .as_ref()
+            .map(|prev| prev.as_slice() == owner.as_slice())
+            .unwrap_or(false);
+        let long = record.class() != Class::In;
+        push_head(&mut target, ARRAY, 3 + u64::from(!same) + u64::from(long));
+        if !same {
+            push_name(&mut target, &owner);
+        }
+        push_head(&mut target, UINT, record.rtype().to_int().into());
+        if long {
+            push_head(&mut target, UINT, record.class().to_int().into());
+        }
+        push_head(&mut target, UINT, record.ttl().into());
+        let mut data = Vec::new();
+        record
+            .data()
+            .compose(&mut data)
+            .map_err(|_| ParseError::form_error("bad record data"))?;
+        push_head(&mut target, BYTES, data.len() as u64);
+        target.extend_from_slice(&data);
+        *prev = Some(owner);
+        count += 1;
+    }
+    Ok((count, target))
+}
+
+/// Reads a 16 bit integer in network byte order.
+fn read_u16(octets: &[u8]) -> u16 {
+    u16::from_be_bytes([octets[0], octets[1]])
+}
+
+/// Appends the head of a data item.
+fn push_head(target: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        target.push(major | value as u8)
+    } else if let Ok(value) = u8::try_from(value) {
+        target.push(major | 24);
+        target.push(value);
+    } else if let Ok(value) = u16::try_from(value) {
+        target.push(major | 25);
+        target.extend_from_slice(&value.to_be_bytes());
+    } else if let Ok(value) = u32::try_from(value) {
+        target.push(major | 26);
+        target.extend_from_slice(&value.to_be_bytes());
+    } else {
+        target.push(major | 27);
+        target.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Appends a domain name.
+fn push_name<'a, N: ToLabelIter<'a> + ?Sized>(
+    target: &mut Vec<u8>,
+    name: &'a N,
+) {
+    let count = name.iter_labels().filter(|label| !label.is_root()).count();
+    push_head(target, ARRAY, count as u64);
+    for label in name.iter_labels() {
+        if label.is_root() {
+            break;
+        }
+        let major = if str::from_utf8(label.as_slice()).is_ok() {
+            TEXT
+        } else {
+            BYTES
+        };
+        push_head(target, major, label.len() as u64);
+        target.extend_from_slice(label.as_slice());
+    }
+}
+
+//------------ decode_message ------------------------------------------------
+
+/// Decodes a message from its CBOR encoding.
+///
+/// Returns an error if `data` isn’t a valid encoding or the resulting
+/// message would be too long.
+pub fn decode_message(data: &[u8]) -> Result<Message<Vec<u8>>, ParseError> {
+    let mut decoder = Decoder { data, pos: 0 };
+    let len = decoder.array()?;
+    if !(3..=6).contains(&len) {
+        return Err(ParseError::form_error("bad message array"));
+    }
+    let mut header = [0u8; 12];
+    header[..2].copy_from_slice(&decoder.u16()?.to_be_bytes());
+    header[2..4].copy_from_slice(&decoder.u16()?.to_be_bytes());
+
+    let mut questions = Vec::new();
+    for _ in 0..decoder.array()? {
+        questions.push(decoder.question()?);
+    }
+    let mut prev = questions.first().map(|q: &Question<_>| q.qname().clone());
+    let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+    for section in sections.iter_mut().take(len - 3) {
+        for _ in 0..decoder.array()? {
+            section.push(decoder.record(&mut prev)?);
+        }
+    }
+    if decoder.pos != data.len() {
+        return Err(ParseError::form_error("trailing data"));
+    }
+
+    let mut msg = MessageBuilder::new_vec();
+    *msg.header_mut() = *Header::for_message_slice(&header);
+    let mut msg = msg.question();
+    for question in questions {
+        msg.push(question).map_err(too_long)?;
+    }
+    let [answer, authority, additional] = sections;
+    let mut msg = msg.answer();
+    for record in answer {
+        msg.push(record).map_err(too_long)?;
+    }
+    let mut msg = msg.authority();
+    for record in authority {
+        msg.push(record).map_err(too_long)?;
+    }
+    let mut msg = msg.additional();
+    for record in additional {
+        msg.push(record).map_err(too_long)?;
+    }
+    Ok(msg.into_message())
+}
+
+type DecodedRecord = Record<Dname<Vec<u8>>, UnknownRecordData<Vec<u8>>>;
+
+fn too_long<E>(_: E) -> ParseError {
+    ParseError::form_error("message too long")
+}
+
+//------------ Decoder -------------------------------------------------------
+
+/// A decoder for the CBOR items used by the encoding.
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    /// Decodes the head of an item and returns its major type and value.
+    fn head(&mut self) -> Result<(u8, u64), ParseError> {
+        let first = *self.data.get(self.pos).ok_or(ParseError::ShortInput)?;
+        self.pos += 1;
+        let len = match first & 0x1F {
+            info @ 0..=23 => return Ok((first >> 5, info.into())),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err(ParseError::form_error("unsupported item")),
+        };
+        let mut value = 0;
+        for &octet in self.take(len)? {
+            value = value << 8 | u64::from(octet)
+        }
+        Ok((first >> 5, value))
+    }
+
+    /// Takes the next `len` octets.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(ParseError::ShortInput)?;
+        let res = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(res)
+    }
+
+    /// Decodes an item of the given major type and returns its value.
+    fn expect(&mut self, major: u8) -> Result<u64, ParseError> {
+        match self.head()? {
+            (found, value) if found == major => Ok(value),
+            _ => Err(ParseError::form_error("unexpected item")),
+        }
+    }
+
+    /// Decodes the head of an array and returns its length.
+    fn array(&mut self) -> Result<usize, ParseError> {
+        let len = self.expect(ARRAY)?;
+        // Every item takes at least one octet, which limits the length.
+        if len > (self.data.len() - self.pos) as u64 {
+            return Err(ParseError::ShortInput);
+        }
+        Ok(len as usize)
+    }
+
+    /// Decodes a string of the given major type.
+    fn string(&mut self, major: u8) -> Result<&'a [u8], ParseError> {
+        let len = self.expect(major)?;
+        let len = usize::try_from(len).map_err(|_| ParseError::ShortInput)?;
+        self.take(len)
+    }
+
+    fn u16(&mut self) -> Result<u16, ParseError> {
+        u16::try_from(self.expect(UINT)?)
+            .map_err(|_| ParseError::form_error("value too large"))
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseError> {
+        u32::try_from(self.expect(UINT)?)
+            .map_err(|_| ParseError::form_error("value too large"))
+    }
+
+    /// Decodes a domain name.
+    fn name(&mut self) -> Result<Dname<Vec<u8>>, ParseError> {
+        let mut octets = Vec::new();
+        for _ in 0..self.array()? {
+            let major = self.data.get(self.pos).map(|first| first >> 5);
+            let label = match major {
+                Some(TEXT) => self.string(TEXT)?,
+                _ => self.string(BYTES)?,
+            };
+            if label.is_empty() || label.len() > 63 {
+                return Err(ParseError::form_error("bad label"));
+            }
+            octets.push(label.len() as u8);
+            octets.extend_from_slice(label);
+        }
+        octets.push(0);
+        Dname::from_octets(octets)
+            .map_err(|_| ParseError::form_error("bad name"))
+    }
+
+    /// Decodes a question.
+    fn question(&mut self) -> Result<Question<Dname<Vec<u8>>>, ParseError> {
+        let len = self.array()?;
+        if len != 2 && len != 3 {
+            return Err(ParseError::form_error("bad question array"));
+        }
+        let qname = self.name()?;
+        let qtype = Rtype::from_int(self.u16()?);
+        let qclass = if len == 3 {
+            Class::from_int(self.u16()?)
+        } else {
+            Class::In
+        };
+        Ok(Question::new(qname, qtype, qclass))
+    }
+
+    /// Decodes a record.
+    ///
+    /// If the record has no owner name, `prev` is used. Afterwards, `prev`
+    /// is the owner of the record.
+    fn record(
+        &mut self,
+        prev: &mut Option<Dname<Vec<u8>>>,
+    ) -> Result<DecodedRecord, ParseError> {
+        let len = self.array()?;
+        if !(3..=5).contains(&len) {
+            return Err(ParseError::form_error("bad record array"));
+        }
+        let has_name =
+            self.data.get(self.pos).map(|first| first >> 5) == Some(ARRAY);
+        let owner = if has_name {
+            self.name()?
+        } else {
+            prev.clone()
+                .ok_or_else(|| ParseError::form_error("missing owner"))?
+        };
+        let rtype = Rtype::from_int(self.u16()?);
+        let class = match len - usize::from(has_name) {
+            3 => Class::In,
+            4 => Class::from_int(self.u16()?),
+            _ => return Err(ParseError::form_error("bad record array")),
+        };
+        let ttl = self.u32()?;
+        let data = self.string(BYTES)?;
+        if data.len() > usize::from(u16::MAX) {
+            return Err(ParseError::form_error("long record data"));
+        }
+        *prev = Some(owner.clone());
+        Ok(Record::new(
+            owner,
+            class,
+            ttl,
+            UnknownRecordData::from_octets(rtype, data.into()),
+        ))
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::rdata::{Aaaa, Cname, Mx, Ns, A};
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn query() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(0x1234);
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((name("example.com"), Rtype::Aaaa)).unwrap();
+        let msg = msg.into_message();
+
+        let cbor = encode_message(&msg).unwrap();
+        assert_eq!(
+            cbor,
+            b"\x83\x19\x12\x34\x19\x01\x00\x81\x82\
+              \x82\x67example\x63com\x18\x1c"
+                .as_ref()
+        );
+        let decoded = decode_message(&cbor).unwrap();
+        assert_eq!(decoded.as_slice(), msg.as_slice());
+    }
+
+    #[test]
+    fn response() {
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((name("www.example.com"), Rtype::A)).unwrap();
+        let mut msg = MessageBuilder::new_vec()
+            .start_answer(&query.into_message(), Rcode::NoError)
+            .unwrap();
+        msg.push((
+            name("www.example.com"),
+            300,
+            Cname::new(name("example.com")),
+        ))
+        .unwrap();
+        msg.push((name("example.com"), 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        msg.push((name("example.com"), 300, A::from_octets(192, 0, 2, 2)))
+            .unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            name("example.com"),
+            Class::Ch,
+            60,
+            Ns::new(name("ns.example.com")),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            Dname::from_octets(b"\x03n\xffs\x07example\x03com\x00".to_vec())
+                .unwrap(),
+            60,
+            Aaaa::from_str("2001:db8::53").unwrap(),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+
+        let cbor = encode_message(&msg).unwrap();
+        let decoded = decode_message(&cbor).unwrap();
+        assert_eq!(decoded.as_slice(), msg.as_slice());
+
+        // The second A record and the NS record have no owner, the NS
+        // record has a class.
+        let answer = decoded.answer().unwrap();
+        assert_eq!(answer.count(), 3);
+        assert!(cbor.windows(5).any(|w| w == b"\x83\x01\x19\x01\x2c"));
+        assert!(cbor.windows(3).any(|w| w == b"\x84\x02\x03"));
+    }
+
+    #[test]
+    fn compressed() {
+        // Compressed names are encoded uncompressed.
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((name("example.com"), Rtype::Mx)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            name("example.com"),
+            300,
+            Mx::new(10, name("mx.example.com")),
+        ))
+        .unwrap();
+        let plain = msg.into_message();
+
+        let mut msg = MessageBuilder::from_target(
+            crate::base::StaticCompressor::new(Vec::new()),
+        )
+        .unwrap()
+        .question();
+        msg.push((name("example.com"), Rtype::Mx)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((
+            name("example.com"),
+            300,
+            Mx::new(10, name("mx.example.com")),
+        ))
+        .unwrap();
+        let compressed = msg.finish().into_target();
+        assert!(compressed.len() < plain.as_slice().len());
+
+        let cbor = encode_message(&Message::from_octets(compressed).unwrap())
+            .unwrap();
+        assert_eq!(
+            decode_message(&cbor).unwrap().as_slice(),
+            plain.as_slice()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(decode_message(b"").is_err());
+        assert!(decode_message(b"\x82\x00\x00").is_err());
+        // Indefinite length array.
+        assert!(decode_message(b"\x9f\x00\x00\x80\xff").is_err());
+        // Trailing data.
+        assert!(decode_message(b"\x83\x00\x00\x80\x00").is_err());
+        // Empty label.
+        assert!(decode_message(b"\x83\x00\x00\x81\x82\x81\x60\x01").is_err());
+        // Record without owner and without question.
+        assert!(
+            decode_message(b"\x84\x00\x00\x80\x81\x83\x01\x00\x40").is_err()
+        );
+        // Huge array length.
+        assert!(decode_message(
+            b"\x83\x00\x00\x9b\xff\xff\xff\xff\xff\xff\xff\xff"
+        )
+        .is_err());
+        assert!(decode_message(b"\x83\x00\x00\x80").is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+//! Sources for the current time.
+//!
+//! Several parts of the crate need to know the current time: the answer
+//! cache ages TTLs and expires entries, and the zone signer determines the
+//! validity period of signatures and the SOA serial from it. Instead of
+//! asking the system directly, these use a [`Clock`]. Normally, this is
+//! the [`SystemClock`]. Tests can use a [`MockClock`] instead and move
+//! time forward at will, making time-dependent behaviour deterministic.
+//!
+//! [`Clock`]: trait.Clock.html
+//! [`SystemClock`]: struct.SystemClock.html
+//! [`MockClock`]: struct.MockClock.html
+#![cfg(feature = "std")]
+
+use super::time32::Time32;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+//------------ Clock ---------------------------------------------------------
+
+/// A source for the current time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+
+    /// Returns the current time in seconds since the Unix epoch.
+    ///
+    /// Times before the epoch are returned as zero.
+    fn unix_secs(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+
+    /// Returns the current time as a `Time32`.
+    fn time32(&self) -> Time32 {
+        Time32::from(self.now())
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
+
+//------------ SystemClock ---------------------------------------------------
+
+/// A clock using the system’s wall clock time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+//------------ MockClock -----------------------------------------------------
+
+/// A clock that only moves when told to.
+///
+/// Clones of a mock clock share their time, so a test can keep a clone
+/// around to advance the time of a clock it handed to the code under test.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    /// Creates a new clock starting at the given time.
+    pub fn new(now: SystemTime) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Creates a new clock starting at the given seconds since the epoch.
+    pub fn from_unix_secs(secs: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Sets the clock to the given time.
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mock_clock() {
+        let clock = MockClock::from_unix_secs(1_600_000_000);
+        let shared: Arc<dyn Clock> = Arc::new(clock.clone());
+        assert_eq!(shared.unix_secs(), 1_600_000_000);
+        assert_eq!(shared.time32(), Time32::from_int(1_600_000_000));
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(shared.unix_secs(), 1_600_000_030);
+        clock.set(UNIX_EPOCH);
+        assert_eq!(shared.unix_secs(), 0);
+    }
+}
@@ -23,7 +23,7 @@ use super::octets::{
     Compose, OctetsBuilder, Parse, ParseError, Parser, ShortBuf,
 };
 use core::convert::TryInto;
-use core::mem;
+use core::{fmt, mem};
 
 //------------ Header --------------------------------------------------
 
@@ -290,6 +290,32 @@ impl Header {
         self.inner[3] = self.inner[3] & 0xF0 | (rcode.to_int() & 0x0F);
     }
 
+    /// Returns all the flags of the header.
+    pub fn flags(self) -> Flags {
+        Flags {
+            qr: self.qr(),
+            aa: self.aa(),
+            tc: self.tc(),
+            rd: self.rd(),
+            ra: self.ra(),
+            z: self.z(),
+            ad: self.ad(),
+            cd: self.cd(),
+        }
+    }
+
+    /// Sets all the flags of the header.
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.set_qr(flags.qr);
+        self.set_aa(flags.aa);
+        self.set_tc(flags.tc);
+        self.set_rd(flags.rd);
+        self.set_ra(flags.ra);
+        self.set_z(flags.z);
+        self.set_ad(flags.ad);
+        self.set_cd(flags.cd);
+    }
+
     //--- Internal helpers
 
     /// Returns the value of the bit at the given position.
@@ -311,6 +337,176 @@ impl Header {
     }
 }
 
+/// # Fluent Construction
+///
+/// These methods set a field and return the header, which allows creating
+/// a header in a single expression:
+///
+/// ```
+/// use domain::base::header::Header;
+/// use domain::base::iana::Opcode;
+///
+/// let header = Header::new()
+///     .with_random_id()
+///     .with_opcode(Opcode::Query)
+///     .with_rd(true)
+///     .with_ad(true);
+/// assert!(header.rd() && header.ad() && !header.cd());
+/// ```
+///
+impl Header {
+    /// Returns the header with the ID field set to `value`.
+    pub fn with_id(mut self, value: u16) -> Self {
+        self.set_id(value);
+        self
+    }
+
+    /// Returns the header with the ID field set to a random number.
+    pub fn with_random_id(mut self) -> Self {
+        self.set_random_id();
+        self
+    }
+
+    /// Returns the header with the QR bit set to `set`.
+    pub fn with_qr(mut self, set: bool) -> Self {
+        self.set_qr(set);
+        self
+    }
+
+    /// Returns the header with the opcode set to `opcode`.
+    pub fn with_opcode(mut self, opcode: Opcode) -> Self {
+        self.set_opcode(opcode);
+        self
+    }
+
+    /// Returns the header with the AA bit set to `set`.
+    pub fn with_aa(mut self, set: bool) -> Self {
+        self.set_aa(set);
+        self
+    }
+
+    /// Returns the header with the TC bit set to `set`.
+    pub fn with_tc(mut self, set: bool) -> Self {
+        self.set_tc(set);
+        self
+    }
+
+    /// Returns the header with the RD bit set to `set`.
+    pub fn with_rd(mut self, set: bool) -> Self {
+        self.set_rd(set);
+        self
+    }
+
+    /// Returns the header with the RA bit set to `set`.
+    pub fn with_ra(mut self, set: bool) -> Self {
+        self.set_ra(set);
+        self
+    }
+
+    /// Returns the header with the reserved bit set to `set`.
+    pub fn with_z(mut self, set: bool) -> Self {
+        self.set_z(set);
+        self
+    }
+
+    /// Returns the header with the AD bit set to `set`.
+    pub fn with_ad(mut self, set: bool) -> Self {
+        self.set_ad(set);
+        self
+    }
+
+    /// Returns the header with the CD bit set to `set`.
+    pub fn with_cd(mut self, set: bool) -> Self {
+        self.set_cd(set);
+        self
+    }
+
+    /// Returns the header with the RCODE field set to `rcode`.
+    pub fn with_rcode(mut self, rcode: Rcode) -> Self {
+        self.set_rcode(rcode);
+        self
+    }
+
+    /// Returns the header with all flags set to those in `flags`.
+    pub fn with_flags(mut self, flags: Flags) -> Self {
+        self.set_flags(flags);
+        self
+    }
+}
+
+//------------ Flags ---------------------------------------------------------
+
+/// The flags of a message header.
+///
+/// This type collects all the single-bit fields of a [`Header`] into
+/// a plain value. It can be retrieved via [`Header::flags`] and applied via
+/// [`Header::set_flags`]. See the respective methods of [`Header`] for the
+/// meaning of each flag.
+///
+/// The `Display` implementation prints the names of the flags that are
+/// set, separated by spaces, in the same way as `dig` does.
+///
+/// [`Header`]: struct.Header.html
+/// [`Header::flags`]: struct.Header.html#method.flags
+/// [`Header::set_flags`]: struct.Header.html#method.set_flags
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Flags {
+    /// The QR bit: the message is a response.
+    pub qr: bool,
+
+    /// The AA bit: the response is an authoritative answer.
+    pub aa: bool,
+
+    /// The TC bit: the message was truncated.
+    pub tc: bool,
+
+    /// The RD bit: recursion is desired.
+    pub rd: bool,
+
+    /// The RA bit: recursion is available.
+    pub ra: bool,
+
+    /// The reserved Z bit.
+    pub z: bool,
+
+    /// The AD bit: the data is authentic.
+    pub ad: bool,
+
+    /// The CD bit: checking is disabled.
+    pub cd: bool,
+}
+
+impl Flags {
+    /// Creates a new value with all flags unset.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sep = "";
+        for &(set, name) in &[
+            (self.qr, "qr"),
+            (self.aa, "aa"),
+            (self.tc, "tc"),
+            (self.rd, "rd"),
+            (self.ra, "ra"),
+            (self.z, "z"),
+            (self.ad, "ad"),
+            (self.cd, "cd"),
+        ] {
+            if set {
+                write!(f, "{}{}", sep, name)?;
+                sep = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
 //------------ HeaderCounts -------------------------------------------------
 
 /// The section count part of the header section of a DNS message.
@@ -920,4 +1116,41 @@ mod test {
         assert!(c.inc_arcount().is_ok());
         assert!(c.inc_arcount().is_err());
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn flags() {
+        use std::string::ToString;
+
+        let header = Header::new()
+            .with_id(0x1234)
+            .with_opcode(Opcode::Notify)
+            .with_aa(true)
+            .with_cd(true)
+            .with_rcode(Rcode::Refused);
+        assert_eq!(header.id(), 0x1234);
+        assert_eq!(header.opcode(), Opcode::Notify);
+        assert_eq!(header.rcode(), Rcode::Refused);
+        let flags = header.flags();
+        assert_eq!(
+            flags,
+            Flags {
+                aa: true,
+                cd: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(flags.to_string(), "aa cd");
+
+        let header = header.with_flags(Flags {
+            qr: true,
+            tc: true,
+            z: true,
+            ..Default::default()
+        });
+        assert!(header.qr() && header.tc() && header.z());
+        assert!(!header.aa() && !header.cd());
+        assert_eq!(header.id(), 0x1234);
+        assert_eq!(header.rcode(), Rcode::Refused);
+    }
 }
@@ -25,6 +25,7 @@
 //! re-exported here. This is mostly so we can have associated types like
 //! `FromStrError` without having to resort to devilishly long names.
 
+pub use self::cert::CertType;
 pub use self::class::Class;
 pub use self::digestalg::DigestAlg;
 pub use self::exterr::ExtendedErrorCode;
@@ -33,11 +34,13 @@ pub use self::opcode::Opcode;
 pub use self::opt::OptionCode;
 pub use self::rcode::{OptRcode, Rcode, TsigRcode};
 pub use self::rtype::Rtype;
-pub use self::secalg::SecAlg;
+pub use self::secalg::{Requirement, SecAlg};
+pub use self::zonemd::{ZonemdAlg, ZonemdScheme};
 
 #[macro_use]
 mod macros;
 
+pub mod cert;
 pub mod class;
 pub mod digestalg;
 pub mod exterr;
@@ -47,3 +50,4 @@ pub mod opt;
 pub mod rcode;
 pub mod rtype;
 pub mod secalg;
+pub mod zonemd;
@@ -520,7 +520,7 @@ impl OptRcode {
             NotZone => 10,
             BadVers => 16,
             BadCookie => 23,
-            Int(value) => value & 0x0F,
+            Int(value) => value & 0x0FFF,
         }
     }
 
@@ -532,7 +532,7 @@ impl OptRcode {
     /// Returns the two parts of an extended rcode value.
     pub fn to_parts(self) -> (Rcode, u8) {
         let res = self.to_int();
-        (Rcode::from_int(res as u8), (res >> 8) as u8)
+        (Rcode::from_int(res as u8), (res >> 4) as u8)
     }
 
     /// Returns the rcode part of the extended rcode.
@@ -544,6 +544,13 @@ impl OptRcode {
     pub fn ext(self) -> u8 {
         self.to_parts().1
     }
+
+    /// Returns whether the rcode can be expressed without an OPT record.
+    ///
+    /// This is true if the upper eight bits of the rcode are zero.
+    pub fn is_basic(self) -> bool {
+        self.ext() == 0
+    }
 }
 
 //--- From
@@ -566,6 +573,43 @@ impl From<Rcode> for OptRcode {
     }
 }
 
+impl From<OptRcode> for Rcode {
+    /// Converts the extended rcode into the part that goes into the header.
+    fn from(value: OptRcode) -> Rcode {
+        value.rcode()
+    }
+}
+
+//--- PartialEq and Eq
+
+impl cmp::PartialEq for OptRcode {
+    fn eq(&self, other: &OptRcode) -> bool {
+        self.to_int() == other.to_int()
+    }
+}
+
+impl cmp::PartialEq<u16> for OptRcode {
+    fn eq(&self, other: &u16) -> bool {
+        self.to_int() == *other
+    }
+}
+
+impl cmp::PartialEq<OptRcode> for u16 {
+    fn eq(&self, other: &OptRcode) -> bool {
+        *self == other.to_int()
+    }
+}
+
+impl cmp::Eq for OptRcode {}
+
+//--- Hash
+
+impl hash::Hash for OptRcode {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.to_int().hash(state)
+    }
+}
+
 //--- Display
 
 impl fmt::Display for OptRcode {
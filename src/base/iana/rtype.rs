@@ -299,6 +299,16 @@ int_enum! {
     /// See draft-wessels-dns-zone-digest.
     (Zonemd => 63, b"ZONEMD")
 
+    /// General-purpose service binding.
+    ///
+    /// See RFC 9460.
+    (Svcb => 64, b"SVCB")
+
+    /// Service binding for HTTPS.
+    ///
+    /// See RFC 9460.
+    (Https => 65, b"HTTPS")
+
     /// SPF.
     ///
     /// RFC 7208.
@@ -405,6 +415,11 @@ int_enum! {
     /// See draft-durand-doa-over-dns.
     (Doa => 259, b"DOA")
 
+    /// Automatic Multicast Tunneling Relay.
+    ///
+    /// See RFC 8777.
+    (Amtrelay => 260, b"AMTRELAY")
+
     /// DNSSEC trust authorities.
     (Ta => 32768, b"TA")
 
@@ -415,3 +430,33 @@ int_enum! {
 }
 
 int_enum_str_with_prefix!(Rtype, "TYPE", b"TYPE", u16, "unknown record type");
+
+impl Rtype {
+    /// Returns whether names in record data of this type may be compressed.
+    ///
+    /// Following [RFC 3597], section 4, name compression is only allowed
+    /// for the names embedded in the record data of the well-known types
+    /// defined in [RFC 1035]: CNAME, MB, MD, MF, MG, MINFO, MR, MX, NS, PTR,
+    /// and SOA. Names in record data of all other types, including the
+    /// types defined later such as SRV or SVCB, must never be compressed
+    /// since a receiver that doesn’t know the type can’t decompress them.
+    ///
+    /// [RFC 1035]: https://tools.ietf.org/html/rfc1035
+    /// [RFC 3597]: https://tools.ietf.org/html/rfc3597
+    pub fn allows_rdata_compression(self) -> bool {
+        matches!(
+            self,
+            Rtype::Cname
+                | Rtype::Mb
+                | Rtype::Md
+                | Rtype::Mf
+                | Rtype::Mg
+                | Rtype::Minfo
+                | Rtype::Mr
+                | Rtype::Mx
+                | Rtype::Ns
+                | Rtype::Ptr
+                | Rtype::Soa
+        )
+    }
+}
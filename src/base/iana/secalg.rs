@@ -117,3 +117,107 @@ int_enum! {
 }
 
 int_enum_str_with_decimal!(SecAlg, u8, "unknown algorithm");
+
+impl SecAlg {
+    /// Returns the requirement level for using the algorithm for signing.
+    ///
+    /// The levels are taken from RFC 8624, section 3.1. Returns `None` for
+    /// values that aren’t listed there, i.e., unassigned values and those
+    /// that don’t identify an actual zone signing algorithm.
+    pub fn signing_requirement(self) -> Option<Requirement> {
+        match self.to_int() {
+            1 | 3 | 6 | 12 => Some(Requirement::MustNot),
+            5 | 7 | 10 => Some(Requirement::NotRecommended),
+            14 | 16 => Some(Requirement::May),
+            15 => Some(Requirement::Recommended),
+            8 | 13 => Some(Requirement::Must),
+            _ => None,
+        }
+    }
+
+    /// Returns the requirement level for supporting the algorithm when
+    /// validating.
+    ///
+    /// The levels are taken from RFC 8624, section 3.1. Returns `None` for
+    /// values that aren’t listed there, i.e., unassigned values and those
+    /// that don’t identify an actual zone signing algorithm.
+    pub fn validation_requirement(self) -> Option<Requirement> {
+        match self.to_int() {
+            1 | 3 | 6 => Some(Requirement::MustNot),
+            12 | 16 => Some(Requirement::May),
+            14 | 15 => Some(Requirement::Recommended),
+            5 | 7 | 8 | 10 | 13 => Some(Requirement::Must),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the algorithm should not be used for signing anymore.
+    ///
+    /// This is the case if the signing requirement is either MUST NOT or
+    /// NOT RECOMMENDED.
+    pub fn is_deprecated(self) -> bool {
+        matches!(
+            self.signing_requirement(),
+            Some(Requirement::MustNot) | Some(Requirement::NotRecommended)
+        )
+    }
+}
+
+//------------ Requirement ---------------------------------------------------
+
+/// The requirement level for implementing an algorithm.
+///
+/// The levels are those of RFC 2119 as used by RFC 8624 in its
+/// recommendations for DNSSEC algorithm support. They are ordered from the
+/// weakest to the strongest requirement.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Requirement {
+    /// The algorithm MUST NOT be implemented.
+    MustNot,
+
+    /// The algorithm is NOT RECOMMENDED.
+    NotRecommended,
+
+    /// The algorithm MAY be implemented.
+    May,
+
+    /// The algorithm is RECOMMENDED.
+    Recommended,
+
+    /// The algorithm MUST be implemented.
+    Must,
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requirements() {
+        assert_eq!(
+            SecAlg::RsaMd5.signing_requirement(),
+            Some(Requirement::MustNot)
+        );
+        assert_eq!(
+            SecAlg::Int(13).signing_requirement(),
+            Some(Requirement::Must)
+        );
+        assert_eq!(
+            SecAlg::EccGost.validation_requirement(),
+            Some(Requirement::May)
+        );
+        assert_eq!(
+            SecAlg::Ed25519.validation_requirement(),
+            Some(Requirement::Recommended)
+        );
+        assert_eq!(
+            SecAlg::Ed448.validation_requirement(),
+            Some(Requirement::May)
+        );
+        assert_eq!(SecAlg::PrivateDns.signing_requirement(), None);
+        assert!(SecAlg::RsaSha1.is_deprecated());
+        assert!(!SecAlg::Ed25519.is_deprecated());
+    }
+}
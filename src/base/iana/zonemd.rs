@@ -0,0 +1,53 @@
+//! ZONEMD scheme and hash algorithm numbers.
+
+//------------ ZonemdScheme --------------------------------------------------
+
+int_enum! {
+    /// ZONEMD scheme numbers.
+    ///
+    /// These numbers are used in the ZONEMD resource record to specify how
+    /// the zone data is collected for calculating the digest.
+    ///
+    /// For the currently registered values see the [IANA registration].
+    ///
+    /// [IANA registration]: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#zonemd-schemes
+    =>
+    ZonemdScheme, u8;
+
+    /// Specifies that the SIMPLE scheme is used.
+    ///
+    /// The scheme is described in [RFC 8976]. The digest is calculated over
+    /// the entire zone in canonical order.
+    ///
+    /// [RFC 8976]: https://tools.ietf.org/html/rfc8976
+    (Simple => 1, b"SIMPLE")
+}
+
+int_enum_str_decimal!(ZonemdScheme, u8);
+
+//------------ ZonemdAlg -----------------------------------------------------
+
+int_enum! {
+    /// ZONEMD hash algorithm numbers.
+    ///
+    /// These numbers are used in the ZONEMD resource record to specify the
+    /// hash algorithm used for calculating the digest.
+    ///
+    /// For the currently registered values see the [IANA registration].
+    ///
+    /// [IANA registration]: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#zonemd-hash-algorithms
+    =>
+    ZonemdAlg, u8;
+
+    /// Specifies that the SHA-384 hash function is used.
+    ///
+    /// Implementation of this function is mandatory.
+    (Sha384 => 1, b"SHA384")
+
+    /// Specifies that the SHA-512 hash function is used.
+    ///
+    /// Implementation of this function is optional.
+    (Sha512 => 2, b"SHA512")
+}
+
+int_enum_str_decimal!(ZonemdAlg, u8);
@@ -10,10 +10,10 @@
 //!
 //! [`Message`]: struct.Message.html
 
-use super::header::{Header, HeaderCounts, HeaderSection};
-use super::iana::{Class, Rcode, Rtype};
+use super::header::{Flags, Header, HeaderCounts, HeaderSection};
+use super::iana::{Class, Opcode, OptRcode, Rcode, Rtype};
 use super::message_builder::{AdditionalBuilder, AnswerBuilder};
-use super::name::ParsedDname;
+use super::name::{Label, ParsedDname};
 use super::octets::{
     OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser, ShortBuf,
 };
@@ -247,6 +247,16 @@ impl<Octets: AsRef<[u8]>> Message<Octets> {
         *HeaderSection::for_message_slice(&self.as_slice())
     }
 
+    /// Returns the flags of the message header.
+    pub fn flags(&self) -> Flags {
+        self.header().flags()
+    }
+
+    /// Returns the opcode of the message header.
+    pub fn opcode(&self) -> Opcode {
+        self.header().opcode()
+    }
+
     /// Returns whether the rcode of the header is NoError.
     pub fn no_error(&self) -> bool {
         self.header().rcode() == Rcode::NoError
@@ -256,6 +266,21 @@ impl<Octets: AsRef<[u8]>> Message<Octets> {
     pub fn is_error(&self) -> bool {
         self.header().rcode() != Rcode::NoError
     }
+
+    /// Returns the rcode of the message header.
+    ///
+    /// This is only the rcode from the header. Use [`opt_rcode`] to also
+    /// consider the extended rcode of the OPT record.
+    ///
+    /// [`opt_rcode`]: #method.opt_rcode
+    pub fn rcode(&self) -> Rcode {
+        self.header().rcode()
+    }
+
+    /// Returns whether the rcode of the header is NXDomain.
+    pub fn is_nxdomain(&self) -> bool {
+        self.header().rcode() == Rcode::NXDomain
+    }
 }
 
 /// # Access to Sections
@@ -343,6 +368,36 @@ where
         Ok((question, answer, authority, additional))
     }
 
+    /// Calls `op` with the type and TTL position of each record.
+    ///
+    /// The position of the TTL field is relative to the start of the
+    /// message. The record’s RDLEN field follows four octets later. This
+    /// allows modifying these fields in place.
+    #[cfg(any(feature = "resolv", feature = "server"))]
+    pub(crate) fn for_each_ttl_field<F>(
+        &self,
+        mut op: F,
+    ) -> Result<(), ParseError>
+    where
+        F: FnMut(Rtype, usize),
+    {
+        let mut section = self.answer()?;
+        loop {
+            while let Some(record) = section.next() {
+                let record = record?;
+                // The record ends with TTL, RDLEN, and the record data.
+                op(
+                    record.rtype(),
+                    section.pos() - usize::from(record.rdlen()) - 6,
+                );
+            }
+            section = match section.next_section()? {
+                Some(section) => section,
+                None => return Ok(()),
+            }
+        }
+    }
+
     /// Returns an iterator over the records in the message.
     ///
     /// The iterator’s item is a pair of a [`ParsedRecord`] and the
@@ -368,22 +423,98 @@ where
     /// Returns whether this is the answer to some other message.
     ///
     /// The method checks whether the ID fields of the headers are the same,
-    /// whether the QR flag is set in this message, and whether the questions
-    /// are the same.
+    /// whether the QR flag is set in this message, whether the opcodes are
+    /// the same, and whether the questions are the same. See
+    /// [`answers_question`] for a version that reports why the message
+    /// isn’t an answer.
+    ///
+    /// [`answers_question`]: #method.answers_question
     pub fn is_answer<Other>(&self, query: &Message<Other>) -> bool
     where
         Other: AsRef<[u8]>,
         for<'o> &'o Other: OctetsRef,
     {
-        if !self.header().qr()
-            || self.header().id() != query.header().id()
-            || self.header_counts().qdcount()
-                != query.header_counts().qdcount()
-        {
-            false
-        } else {
-            self.question() == query.question()
+        self.answers_question(query).is_ok()
+    }
+
+    /// Checks whether this message is the answer to a query.
+    ///
+    /// The message is an answer if it has the QR flag set, its ID and
+    /// opcode are the same as those of `query`, and it echoes the questions
+    /// of `query`. The domain names of the questions are compared
+    /// case-insensitively.
+    ///
+    /// If the message isn’t an answer, the error tells why.
+    pub fn answers_question<Other>(
+        &self,
+        query: &Message<Other>,
+    ) -> Result<(), AnswerMismatch>
+    where
+        Other: AsRef<[u8]>,
+        for<'o> &'o Other: OctetsRef,
+    {
+        self.check_answer(query, false)
+    }
+
+    /// Checks whether this message is the answer to a query, including case.
+    ///
+    /// This is the same as [`answers_question`] except that the domain
+    /// names of the questions need to be echoed with exactly the same case.
+    /// This is necessary for checking answers to queries that randomize
+    /// the case of their question names as a defense against spoofing, a
+    /// technique known as ‘DNS 0x20.’
+    ///
+    /// [`answers_question`]: #method.answers_question
+    pub fn answers_question_exact<Other>(
+        &self,
+        query: &Message<Other>,
+    ) -> Result<(), AnswerMismatch>
+    where
+        Other: AsRef<[u8]>,
+        for<'o> &'o Other: OctetsRef,
+    {
+        self.check_answer(query, true)
+    }
+
+    fn check_answer<Other>(
+        &self,
+        query: &Message<Other>,
+        exact_case: bool,
+    ) -> Result<(), AnswerMismatch>
+    where
+        Other: AsRef<[u8]>,
+        for<'o> &'o Other: OctetsRef,
+    {
+        let header = self.header();
+        if !header.qr() {
+            return Err(AnswerMismatch::NotAnswer);
         }
+        if header.id() != query.header().id() {
+            return Err(AnswerMismatch::Id);
+        }
+        if header.opcode() != query.header().opcode() {
+            return Err(AnswerMismatch::Opcode);
+        }
+        if self.header_counts().qdcount() != query.header_counts().qdcount() {
+            return Err(AnswerMismatch::QuestionCount);
+        }
+        for (answer, query) in self.question().zip(query.question()) {
+            let answer = answer.map_err(|_| AnswerMismatch::Malformed)?;
+            let query = query.map_err(|_| AnswerMismatch::Malformed)?;
+            if answer != query {
+                return Err(AnswerMismatch::Question);
+            }
+            if exact_case
+                && !answer
+                    .qname()
+                    .iter()
+                    .map(Label::as_slice)
+                    .eq(query.qname().iter().map(Label::as_slice))
+            {
+                return Err(AnswerMismatch::QnameCase);
+            }
+        }
+        Ok(())
     }
 
     /// Returns the first question, if there is any.
@@ -484,6 +615,79 @@ where
         None
     }
 
+    /// Returns the chain of names leading to the canonical name.
+    ///
+    /// The returned names start with the question name and then follow
+    /// the CNAME records in the answer section, ending with the canonical
+    /// name as returned by [`canonical_name`]. If the question name isn’t
+    /// an alias, it is the only name.
+    ///
+    /// If the message doesn’t have a question, if there is a parse error, or
+    /// if there is a CNAME loop the method returns `None`.
+    ///
+    /// [`canonical_name`]: #method.canonical_name
+    #[cfg(feature = "std")]
+    pub fn canonical_chain(
+        &self,
+    ) -> Option<std::vec::Vec<ParsedDname<&Octets>>> {
+        let mut chain = std::vec![self.first_question()?.into_qname()];
+        let answer = self.answer().ok()?.limit_to::<Cname<_>>();
+        for _ in 0..self.header_counts().ancount() + 1 {
+            let name = chain.last().unwrap();
+            let next = answer
+                .clone()
+                .filter_map(Result::ok)
+                .find(|record| record.owner() == name);
+            match next {
+                Some(record) => chain.push(*record.data().cname()),
+                None => return Some(chain),
+            }
+        }
+        None
+    }
+
+    /// Returns an iterator over the answer records of the given type.
+    ///
+    /// Records of other types and records that fail to parse are skipped.
+    /// If the answer section can’t be found, the iterator is empty.
+    pub fn answers<'s, Data>(
+        &'s self,
+    ) -> impl Iterator<Item = Record<ParsedDname<&'s Octets>, Data>> + 's
+    where
+        Data: ParseRecordData<&'s Octets> + 's,
+    {
+        self.answer()
+            .ok()
+            .into_iter()
+            .flat_map(RecordSection::limit_to::<Data>)
+            .filter_map(Result::ok)
+    }
+
+    /// Returns the first answer record of the given type, if there is any.
+    ///
+    /// Records that fail to parse are skipped.
+    pub fn first_answer<'s, Data>(
+        &'s self,
+    ) -> Option<Record<ParsedDname<&'s Octets>, Data>>
+    where
+        Data: ParseRecordData<&'s Octets> + 's,
+    {
+        self.answers().next()
+    }
+
+    /// Returns the smallest TTL of the records in the answer section.
+    ///
+    /// This considers all records, including the CNAME records leading to
+    /// the canonical name. Returns `None` if there are no records or the
+    /// answer section can’t be parsed.
+    pub fn min_ttl(&self) -> Option<u32> {
+        self.answer()
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|record| record.ttl())
+            .min()
+    }
+
     /// Returns the OPT record from the message, if there is one.
     pub fn opt(&self) -> Option<OptRecord<<&Octets as OctetsRef>::Range>> {
         match self.additional() {
@@ -495,6 +699,17 @@ where
         }
     }
 
+    /// Returns the extended rcode of the message.
+    ///
+    /// The rcode is assembled from the header and, if present, the OPT
+    /// record. If there is no OPT record, only the header rcode is used.
+    pub fn opt_rcode(&self) -> OptRcode {
+        match self.opt() {
+            Some(opt) => opt.rcode(self.header()),
+            None => self.header().rcode().into(),
+        }
+    }
+
     /// Returns the last additional record from the message.
     ///
     /// The method tries to parse the last record of the additional section
@@ -1096,6 +1311,52 @@ impl fmt::Display for CopyRecordsError {
 #[cfg(feature = "std")]
 impl std::error::Error for CopyRecordsError {}
 
+//------------ AnswerMismatch ------------------------------------------------
+
+/// The reason why a message isn’t the answer to a query.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnswerMismatch {
+    /// The QR flag isn’t set, i.e., the message is a query.
+    NotAnswer,
+
+    /// The message ID is different.
+    Id,
+
+    /// The opcode is different.
+    Opcode,
+
+    /// The number of questions is different.
+    QuestionCount,
+
+    /// A question is different.
+    Question,
+
+    /// A question name is the same but differs in case.
+    QnameCase,
+
+    /// The question section of either message couldn’t be parsed.
+    Malformed,
+}
+
+//--- Display and Error
+
+impl fmt::Display for AnswerMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            AnswerMismatch::NotAnswer => "message is not an answer",
+            AnswerMismatch::Id => "message ID mismatch",
+            AnswerMismatch::Opcode => "opcode mismatch",
+            AnswerMismatch::QuestionCount => "question count mismatch",
+            AnswerMismatch::Question => "question mismatch",
+            AnswerMismatch::QnameCase => "question name case mismatch",
+            AnswerMismatch::Malformed => "malformed question section",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AnswerMismatch {}
+
 //============ Testing =======================================================
 
 #[cfg(test)]
@@ -1195,6 +1456,79 @@ mod test {
         assert!(msg.as_message().canonical_name().is_none());
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn answer_helpers() {
+        use crate::rdata::A;
+
+        let name = |s| Dname::vec_from_str(s).unwrap();
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_rcode(Rcode::NXDomain);
+        let mut msg = msg.question();
+        msg.push((name("www.example.com."), Rtype::A)).unwrap();
+        let msg_ref = msg.as_message();
+        assert_eq!(msg_ref.rcode(), Rcode::NXDomain);
+        assert!(msg_ref.is_nxdomain());
+        assert!(msg_ref.first_answer::<A>().is_none());
+        assert_eq!(msg_ref.min_ttl(), None);
+        assert_eq!(
+            msg_ref.canonical_chain().unwrap(),
+            [name("www.example.com.")]
+        );
+
+        let mut msg = msg.answer();
+        msg.header_mut().set_rcode(Rcode::NoError);
+        msg.push((
+            name("www.example.com."),
+            3600,
+            Cname::new(name("cdn.example.net.")),
+        ))
+        .unwrap();
+        msg.push((
+            name("cdn.example.net."),
+            60,
+            A::from_octets(192, 0, 2, 1),
+        ))
+        .unwrap();
+        msg.push((
+            name("cdn.example.net."),
+            300,
+            A::from_octets(192, 0, 2, 2),
+        ))
+        .unwrap();
+        let msg_ref = msg.as_message();
+        assert!(!msg_ref.is_nxdomain());
+        let addrs: Vec<_> = msg_ref
+            .answers::<A>()
+            .map(|record| record.data().addr())
+            .collect();
+        assert_eq!(
+            addrs,
+            [
+                A::from_octets(192, 0, 2, 1).addr(),
+                A::from_octets(192, 0, 2, 2).addr()
+            ]
+        );
+        assert_eq!(
+            msg_ref.first_answer::<Cname<_>>().unwrap().data().cname(),
+            &name("cdn.example.net.")
+        );
+        assert_eq!(msg_ref.min_ttl(), Some(60));
+        assert_eq!(
+            msg_ref.canonical_chain().unwrap(),
+            [name("www.example.com."), name("cdn.example.net.")]
+        );
+
+        // CNAME loop.
+        msg.push((
+            name("cdn.example.net."),
+            3600,
+            Cname::new(name("www.example.com.")),
+        ))
+        .unwrap();
+        assert!(msg.as_message().canonical_chain().is_none());
+    }
+
     #[test]
     #[cfg(feature = "std")]
     fn message_iterator() {
@@ -1234,4 +1568,192 @@ mod test {
             assert_eq!(0, msg.header_counts().arcount());
         }
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unknown_round_trip() {
+        use crate::base::iana::{Class, OptionCode};
+        use crate::base::opt::UnknownOptData;
+        use crate::base::rdata::UnknownRecordData;
+        use crate::base::record::Record;
+        use crate::rdata::A;
+
+        let owner = Dname::vec_from_str("example.com.").unwrap();
+        let unknown = [
+            UnknownRecordData::from_octets(
+                Rtype::Int(65280),
+                b"\x01\x02\x03".to_vec(),
+            ),
+            UnknownRecordData::from_octets(Rtype::Int(65281), Vec::new()),
+        ];
+        let build = |with_a: bool| {
+            let mut msg = MessageBuilder::new_vec().answer();
+            for data in &unknown {
+                msg.push(Record::new(
+                    &owner,
+                    Class::Int(0xff01),
+                    3600,
+                    data.clone(),
+                ))
+                .unwrap();
+            }
+            if with_a {
+                msg.push((&owner, 3600, A::from_octets(192, 0, 2, 1)))
+                    .unwrap();
+            }
+            let mut msg = msg.additional();
+            msg.opt(|opt| {
+                opt.set_dnssec_ok(true);
+                opt.push_raw_option(OptionCode::Int(65001), |target| {
+                    target.append_slice(b"abc")
+                })?;
+                opt.push_raw_option(OptionCode::Int(65002), |_| Ok(()))
+            })
+            .unwrap();
+            msg.into_message()
+        };
+        let msg = build(true);
+
+        // Copy everything but the A record into a new message, going
+        // through both the generic and the all-types record data.
+        let copy = msg
+            .copy_records(MessageBuilder::new_vec().answer(), |rr| {
+                if rr.rtype() == Rtype::A {
+                    return None;
+                }
+                rr.into_record::<AllRecordData<_, ParsedDname<_>>>()
+                    .unwrap()
+            })
+            .unwrap()
+            .into_message();
+
+        let answer: Vec<_> = copy
+            .answer()
+            .unwrap()
+            .limit_to::<UnknownRecordData<_>>()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(answer.len(), 2);
+        for (record, data) in answer.iter().zip(unknown.iter()) {
+            assert_eq!(record.owner(), &owner);
+            assert_eq!(record.class(), Class::Int(0xff01));
+            assert_eq!(record.data(), data);
+            assert_eq!(record.data().rtype(), data.rtype());
+        }
+
+        let opt = copy.opt().unwrap();
+        assert!(opt.dnssec_ok());
+        let options: Vec<_> = opt
+            .as_opt()
+            .iter::<UnknownOptData<_>>()
+            .map(|opt| {
+                let opt = opt.unwrap();
+                (opt.code(), opt.as_slice().to_vec())
+            })
+            .collect();
+        assert_eq!(
+            options,
+            [
+                (OptionCode::Int(65001), b"abc".to_vec()),
+                (OptionCode::Int(65002), Vec::new()),
+            ]
+        );
+
+        // The copy is identical to a message built without the A record.
+        assert_eq!(copy.as_slice(), build(false).as_slice());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn answers_question() {
+        use crate::base::iana::Opcode;
+
+        fn query(qname: &str, id: u16) -> Message<Vec<u8>> {
+            let mut msg = MessageBuilder::new_vec();
+            msg.header_mut().set_id(id);
+            let mut msg = msg.question();
+            msg.push((Dname::vec_from_str(qname).unwrap(), Rtype::A))
+                .unwrap();
+            msg.into_message()
+        }
+
+        fn answer(qname: &str, id: u16) -> Message<Vec<u8>> {
+            let mut msg = query(qname, id);
+            msg.header_mut().set_qr(true);
+            msg
+        }
+
+        let request = query("wWw.ExAmple.com.", 12);
+        assert_eq!(
+            answer("wWw.ExAmple.com.", 12).answers_question(&request),
+            Ok(())
+        );
+        assert_eq!(
+            answer("wWw.ExAmple.com.", 12).answers_question_exact(&request),
+            Ok(())
+        );
+        assert_eq!(
+            answer("www.example.com.", 12).answers_question(&request),
+            Ok(())
+        );
+        assert!(answer("www.example.com.", 12).is_answer(&request));
+        assert_eq!(
+            answer("www.example.com.", 12).answers_question_exact(&request),
+            Err(AnswerMismatch::QnameCase)
+        );
+        assert_eq!(
+            query("wWw.ExAmple.com.", 12).answers_question(&request),
+            Err(AnswerMismatch::NotAnswer)
+        );
+        assert_eq!(
+            answer("wWw.ExAmple.com.", 13).answers_question(&request),
+            Err(AnswerMismatch::Id)
+        );
+        assert_eq!(
+            answer("mail.example.com.", 12).answers_question(&request),
+            Err(AnswerMismatch::Question)
+        );
+        let mut other = answer("wWw.ExAmple.com.", 12);
+        other.header_mut().set_opcode(Opcode::Notify);
+        assert_eq!(
+            other.answers_question(&request),
+            Err(AnswerMismatch::Opcode)
+        );
+        let mut other = MessageBuilder::new_vec();
+        other.header_mut().set_id(12);
+        other.header_mut().set_qr(true);
+        assert_eq!(
+            other.into_message().answers_question(&request),
+            Err(AnswerMismatch::QuestionCount)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parse_corpus() {
+        use std::{fs, path::Path};
+
+        fn walk(msg: Message<&[u8]>) -> Result<(), ParseError> {
+            for question in msg.question() {
+                question?;
+            }
+            for item in msg.iter() {
+                let (record, _) = item?;
+                record.into_record::<AllRecordData<_, ParsedDname<_>>>()?;
+            }
+            Ok(())
+        }
+
+        // The messages in the fuzz corpus whose name starts with `valid-`
+        // must parse, all others must fail somewhere.
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fuzz/corpus/parse_message");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            let res = walk(Message::from_octets(data.as_slice()).unwrap());
+            let name = path.file_name().unwrap().to_str().unwrap();
+            assert_eq!(res.is_ok(), name.starts_with("valid-"), "{}", name);
+        }
+    }
 }
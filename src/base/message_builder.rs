@@ -164,6 +164,9 @@ use std::vec::Vec;
 #[derive(Clone, Debug)]
 pub struct MessageBuilder<Target> {
     target: Target,
+
+    /// The maximum length of the message in octets for pushing elements.
+    limit: usize,
 }
 
 /// # Creating Message Builders
@@ -180,7 +183,10 @@ impl<Target: OctetsBuilder> MessageBuilder<Target> {
     pub fn from_target(mut target: Target) -> Result<Self, ShortBuf> {
         target.truncate(0);
         target.append_slice(HeaderSection::new().as_slice())?;
-        Ok(MessageBuilder { target })
+        Ok(MessageBuilder {
+            target,
+            limit: usize::MAX,
+        })
     }
 }
 
@@ -252,6 +258,54 @@ impl<Target: OctetsBuilder> MessageBuilder<Target> {
         Ok(builder.answer())
     }
 
+    /// Starts creating an answer by copying the questions verbatim.
+    ///
+    /// This is like [`start_answer`] except that the question section of
+    /// `msg` is copied as is rather than parsed and composed anew, which
+    /// avoids any work on the question names. Since the question section
+    /// starts at the same position in both messages, compression pointers
+    /// within it stay valid. However, the question names are not available
+    /// for compressing the names of records added later.
+    ///
+    /// If a question of `msg` cannot be parsed, only the questions before
+    /// it are copied.
+    ///
+    /// [`start_answer`]: #method.start_answer
+    pub fn start_answer_verbatim<Octets>(
+        mut self,
+        msg: &Message<Octets>,
+        rcode: Rcode,
+    ) -> Result<AnswerBuilder<Target>, ShortBuf>
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        {
+            let header = self.header_mut();
+            header.set_id(msg.header().id());
+            header.set_qr(true);
+            header.set_opcode(msg.header().opcode());
+            header.set_rd(msg.header().rd());
+            header.set_rcode(rcode);
+        }
+        let mut questions = msg.question();
+        let start = questions.pos();
+        let mut end = start;
+        let mut count = 0;
+        while let Some(Ok(_)) = questions.next() {
+            end = questions.pos();
+            count += 1;
+        }
+        self.push(
+            |target| target.append_slice(&msg.as_slice()[start..end]),
+            |counts| {
+                counts.set_qdcount(count);
+                Ok(())
+            },
+        )?;
+        Ok(self.answer())
+    }
+
     /// Creates an AXFR request for the given domain.
     ///
     /// Sets a random ID, pushes the domain and the AXFR record type into
@@ -280,6 +334,26 @@ impl<Target: OctetsBuilder> MessageBuilder<Target> {
         Header::for_message_slice_mut(self.target.as_mut())
     }
 
+    /// Returns the builder with the message header replaced by `header`.
+    ///
+    /// Together with the fluent methods of [`Header`], this allows setting
+    /// up the header when creating the builder:
+    ///
+    /// ```
+    /// use domain::base::{Header, MessageBuilder};
+    ///
+    /// let msg = MessageBuilder::new_vec()
+    ///     .with_header(Header::new().with_id(12).with_rd(true));
+    /// assert_eq!(msg.header().id(), 12);
+    /// assert!(msg.header().rd());
+    /// ```
+    ///
+    /// [`Header`]: ../header/struct.Header.html
+    pub fn with_header(mut self, header: Header) -> Self {
+        *self.header_mut() = header;
+        self
+    }
+
     /// Return the current value of the message header counts.
     pub fn counts(&self) -> HeaderCounts {
         *HeaderCounts::for_message_slice(self.target.as_ref())
@@ -291,6 +365,66 @@ impl<Target: OctetsBuilder> MessageBuilder<Target> {
     }
 }
 
+/// # Limiting the Message Size
+///
+impl<Target: OctetsBuilder> MessageBuilder<Target> {
+    /// Limits the length of the message to `limit` octets.
+    ///
+    /// Once a limit is set, pushing a question, record, or EDNS option
+    /// that would make the message longer than the limit fails with an
+    /// error and leaves the message unchanged. This allows filling a
+    /// message up to the maximum size the receiver is able to process.
+    ///
+    /// The limit does not affect data that is already part of the message.
+    pub fn set_push_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Removes a limit previously set via `set_push_limit`.
+    pub fn clear_push_limit(&mut self) {
+        self.limit = usize::MAX;
+    }
+
+    /// Returns the current push limit if there is one.
+    pub fn push_limit(&self) -> Option<usize> {
+        if self.limit == usize::MAX {
+            None
+        } else {
+            Some(self.limit)
+        }
+    }
+
+    /// Pushes an element to the message.
+    ///
+    /// The element is composed via `push` and the section count increased
+    /// via `inc`. If either fails or the message grows beyond the push
+    /// limit, the message is truncated back to where it was and an error
+    /// is returned.
+    fn push<Push, Inc>(
+        &mut self,
+        push: Push,
+        inc: Inc,
+    ) -> Result<(), ShortBuf>
+    where
+        Push: FnOnce(&mut Target) -> Result<(), ShortBuf>,
+        Inc: FnOnce(&mut HeaderCounts) -> Result<(), ShortBuf>,
+    {
+        let pos = self.target.len();
+        let res = push(&mut self.target).and_then(|_| {
+            if self.target.len() > self.limit {
+                Err(ShortBuf)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = res.and_then(|_| inc(self.counts_mut())) {
+            self.target.truncate(pos);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
 /// # Conversions
 ///
 impl<Target: OctetsBuilder> MessageBuilder<Target> {
@@ -491,12 +625,10 @@ impl<Target: OctetsBuilder> QuestionBuilder<Target> {
         &mut self,
         question: impl AsQuestion,
     ) -> Result<(), ShortBuf> {
-        let pos = self.as_target().len();
-        question.compose_question(self.as_target_mut())?;
-        self.counts_mut().inc_qdcount().map_err(|err| {
-            self.as_target_mut().truncate(pos);
-            err
-        })
+        self.as_builder_mut().push(
+            |target| question.compose_question(target),
+            |counts| counts.inc_qdcount(),
+        )
     }
 }
 
@@ -725,12 +857,10 @@ impl<Target: OctetsBuilder> AnswerBuilder<Target> {
     /// ```
     ///
     pub fn push(&mut self, record: impl AsRecord) -> Result<(), ShortBuf> {
-        let pos = self.as_target().len();
-        record.compose_record(self.as_target_mut())?;
-        self.counts_mut().inc_ancount().map_err(|err| {
-            self.as_target_mut().truncate(pos);
-            err
-        })
+        self.as_builder_mut().push(
+            |target| record.compose_record(target),
+            |counts| counts.inc_ancount(),
+        )
     }
 }
 
@@ -959,12 +1089,10 @@ impl<Target: OctetsBuilder> AuthorityBuilder<Target> {
     /// ).unwrap();
     /// ```
     pub fn push(&mut self, record: impl AsRecord) -> Result<(), ShortBuf> {
-        let pos = self.as_target().len();
-        record.compose_record(self.as_target_mut())?;
-        self.counts_mut().inc_nscount().map_err(|err| {
-            self.as_target_mut().truncate(pos);
-            err
-        })
+        self.as_builder_mut().push(
+            |target| record.compose_record(target),
+            |counts| counts.inc_nscount(),
+        )
     }
 }
 
@@ -1200,12 +1328,10 @@ impl<Target: OctetsBuilder> AdditionalBuilder<Target> {
     /// ).unwrap();
     /// ```
     pub fn push(&mut self, record: impl AsRecord) -> Result<(), ShortBuf> {
-        let pos = self.as_target().len();
-        record.compose_record(self.as_target_mut())?;
-        self.counts_mut().inc_arcount().map_err(|err| {
-            self.as_target_mut().truncate(pos);
-            err
-        })
+        self.as_builder_mut().push(
+            |target| record.compose_record(target),
+            |counts| counts.inc_arcount(),
+        )
     }
 
     /// Appends and builds an OPT record.
@@ -1458,20 +1584,13 @@ impl<'a, Target: OctetsBuilder> OptBuilder<'a, Target> {
         let start = additional.as_target().as_ref().len();
         let arcount = additional.counts().arcount();
 
-        let err = additional
-            .as_target_mut()
-            .append_all(|target| {
+        additional.as_builder_mut().push(
+            |target| {
                 OptHeader::default().compose(target)?;
                 0u16.compose(target)
-            })
-            .is_err();
-        if err {
-            return Err(ShortBuf);
-        }
-        if additional.counts_mut().inc_arcount().is_err() {
-            additional.as_target_mut().truncate(start);
-            return Err(ShortBuf);
-        }
+            },
+            |counts| counts.inc_arcount(),
+        )?;
 
         Ok(OptBuilder {
             additional,
@@ -1509,7 +1628,9 @@ impl<'a, Target: OctetsBuilder> OptBuilder<'a, Target> {
         let len = self.as_target().as_ref().len()
             - self.start
             - (mem::size_of::<OptHeader>() + 2);
-        if len > usize::from(u16::max_value()) {
+        if len > usize::from(u16::MAX)
+            || self.as_target().as_ref().len() > self.additional.limit
+        {
             self.as_target_mut().truncate(pos);
             return Err(ShortBuf);
         }
@@ -1747,6 +1868,9 @@ pub struct StaticCompressor<Target> {
 
     /// The number of entries in `entries`.
     len: usize,
+
+    /// Whether to compress names in record data of a type.
+    rdata_policy: fn(Rtype) -> bool,
 }
 
 impl<Target> StaticCompressor<Target> {
@@ -1756,9 +1880,21 @@ impl<Target> StaticCompressor<Target> {
             target,
             entries: Default::default(),
             len: 0,
+            rdata_policy: Rtype::allows_rdata_compression,
         }
     }
 
+    /// Overrides whether names in record data of a type are compressed.
+    ///
+    /// By default, the policy of [`Rtype::allows_rdata_compression`] is
+    /// used. The function should only deviate from it when talking to
+    /// peers known to require a different policy.
+    ///
+    /// [`Rtype::allows_rdata_compression`]: ../iana/rtype/enum.Rtype.html#method.allows_rdata_compression
+    pub fn set_rdata_policy(&mut self, policy: fn(Rtype) -> bool) {
+        self.rdata_policy = policy
+    }
+
     /// Returns a reference to the underlying octets builder.
     pub fn as_target(&self) -> &Target {
         &self.target
@@ -1836,6 +1972,10 @@ impl<Target: AsMut<[u8]>> AsMut<[u8]> for StaticCompressor<Target> {
 impl<Target: OctetsBuilder> OctetsBuilder for StaticCompressor<Target> {
     type Octets = Target::Octets;
 
+    fn compress_rdata(&self, rtype: Rtype) -> bool {
+        (self.rdata_policy)(rtype)
+    }
+
     fn append_slice(&mut self, slice: &[u8]) -> Result<(), ShortBuf> {
         self.target.append_slice(slice)
     }
@@ -1917,6 +2057,9 @@ pub struct TreeCompressor<Target> {
 
     /// The topmost node of our tree.
     start: Node,
+
+    /// Whether to compress names in record data of a type.
+    rdata_policy: fn(Rtype) -> bool,
 }
 
 /// A node in our tree.
@@ -1958,9 +2101,21 @@ impl<Target> TreeCompressor<Target> {
         TreeCompressor {
             target,
             start: Default::default(),
+            rdata_policy: Rtype::allows_rdata_compression,
         }
     }
 
+    /// Overrides whether names in record data of a type are compressed.
+    ///
+    /// By default, the policy of [`Rtype::allows_rdata_compression`] is
+    /// used. The function should only deviate from it when talking to
+    /// peers known to require a different policy.
+    ///
+    /// [`Rtype::allows_rdata_compression`]: ../iana/rtype/enum.Rtype.html#method.allows_rdata_compression
+    pub fn set_rdata_policy(&mut self, policy: fn(Rtype) -> bool) {
+        self.rdata_policy = policy
+    }
+
     /// Returns a reference to the underlying octets builder.
     pub fn as_target(&self) -> &Target {
         &self.target
@@ -2045,6 +2200,10 @@ impl<Target: AsMut<[u8]>> AsMut<[u8]> for TreeCompressor<Target> {
 impl<Target: OctetsBuilder> OctetsBuilder for TreeCompressor<Target> {
     type Octets = Target::Octets;
 
+    fn compress_rdata(&self, rtype: Rtype) -> bool {
+        (self.rdata_policy)(rtype)
+    }
+
     fn append_slice(&mut self, slice: &[u8]) -> Result<(), ShortBuf> {
         self.target.append_slice(slice)
     }
@@ -2202,6 +2361,31 @@ mod test {
         assert_eq!(opts.next(), Some(Ok(nsid)));
     }
 
+    #[test]
+    fn push_limit() {
+        let name = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        let mut msg = MessageBuilder::new_vec();
+        msg.set_push_limit(12 + 17 + 27);
+        assert_eq!(msg.push_limit(), Some(56));
+        let mut msg = msg.question();
+        msg.push((&name, Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+
+        // One A record with an uncompressed name fits, a second doesn’t.
+        msg.push((&name, 86400, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        assert!(msg
+            .push((&name, 86400, A::from_octets(192, 0, 2, 2)))
+            .is_err());
+        assert_eq!(msg.counts().ancount(), 1);
+        assert_eq!(msg.as_slice().len(), 56);
+
+        msg.clear_push_limit();
+        msg.push((&name, 86400, A::from_octets(192, 0, 2, 2)))
+            .unwrap();
+        assert_eq!(msg.counts().ancount(), 2);
+    }
+
     fn create_compressed<T: OctetsBuilder + AsRef<[u8]>>(target: T) -> T {
         let mut msg = MessageBuilder::from_target(target).unwrap().question();
         msg.header_mut().set_rcode(Rcode::NXDomain);
@@ -2232,6 +2416,55 @@ mod test {
         msg.finish()
     }
 
+    #[test]
+    fn rdata_policy() {
+        use crate::rdata::rfc6672::Dname as DnameData;
+        use crate::rdata::Mx;
+
+        fn create<T: OctetsBuilder + AsRef<[u8]>>(target: T) -> T {
+            let name = |s| Dname::<Vec<u8>>::from_str(s).unwrap();
+            let mut msg =
+                MessageBuilder::from_target(target).unwrap().question();
+            msg.push((name("example."), Rtype::A)).unwrap();
+            let mut msg = msg.answer();
+            msg.push((
+                name("example."),
+                3600,
+                DnameData::new(name("a.example.")),
+            ))
+            .unwrap();
+            msg.push((
+                name("example."),
+                3600,
+                Mx::new(10, name("mx.example.")),
+            ))
+            .unwrap();
+            msg.finish()
+        }
+
+        fn contains(msg: &[u8], part: &[u8]) -> bool {
+            msg.windows(part.len()).any(|window| window == part)
+        }
+
+        // DNAME is never compressed but MX is.
+        let msg = create(StaticCompressor::new(Vec::new()));
+        assert!(contains(msg.as_ref(), b"\x01a\x07example\x00"));
+        assert!(contains(msg.as_ref(), b"\x02mx\xc0\x0c"));
+        let msg = create(TreeCompressor::new(Vec::new()));
+        assert!(contains(msg.as_ref(), b"\x01a\x07example\x00"));
+        assert!(contains(msg.as_ref(), b"\x02mx\xc0\x0c"));
+
+        // The policy can be overridden.
+        let mut target = TreeCompressor::new(Vec::new());
+        target.set_rdata_policy(|_| true);
+        let msg = create(target);
+        assert!(contains(msg.as_ref(), b"\x01a\xc0\x0c"));
+        let mut target = StaticCompressor::new(Vec::new());
+        target.set_rdata_policy(|_| false);
+        let msg = create(target);
+        assert!(contains(msg.as_ref(), b"\x02mx\x07example\x00"));
+    }
+
     #[test]
     fn compressor() {
         // An example negative response to `example. NS` with an SOA to test various compressed name situations.
@@ -45,6 +45,8 @@
 //! sections to look at the questions and resource records. Similarly,
 //! a [`MessageBuilder`] takes a bytes vector (or creates one for you) and
 //! has functionality to build the sections of the message step-by-step.
+//! For constrained transports, the [cbor](cbor/index.html) module provides
+//! an experimental, more compact CBOR encoding of messages.
 //!
 //!
 //! # Types for DNS Data
@@ -59,7 +61,10 @@
 //! * [name](name/index.html) for domain names,
 //! * [opt](opt/index.html) for the record data of OPT records used in EDNS,
 //! * [question](question/index.html) for questions,
-//! * [serial](serial/index.html) for serial numbers of zones, and
+//! * [serial](serial/index.html) for serial numbers of zones,
+//! * [time32](time32/index.html) for points in time such as the validity
+//!   period of RRSIG records, and [clock](clock/index.html) for sources of
+//!   the current time,
 //! * [record](record/index.html) for DNS resource records including record
 //!   data,
 //! * [rdata](rdata/index.html) for all the individual record types.
@@ -94,7 +99,7 @@
 
 pub use self::charstr::CharStr;
 pub use self::cmp::CanonicalOrd;
-pub use self::header::{Header, HeaderCounts, HeaderSection};
+pub use self::header::{Flags, Header, HeaderCounts, HeaderSection};
 pub use self::iana::Rtype;
 pub use self::message::{Message, QuestionSection, RecordSection};
 #[cfg(feature = "std")]
@@ -111,11 +116,16 @@ pub use self::octets::{Compose, Parser, ShortBuf};
 pub use self::question::Question;
 pub use self::rdata::{ParseRecordData, RecordData, UnknownRecordData};
 pub use self::record::{ParsedRecord, Record, RecordHeader};
+#[cfg(feature = "std")]
+pub use self::response::ResponseBuilder;
 pub use self::serial::Serial;
+pub use self::time32::Time32;
 
 //--- Modules
 
+pub mod cbor;
 pub mod charstr;
+pub mod clock;
 pub mod cmp;
 pub mod header;
 pub mod iana;
@@ -128,5 +138,7 @@ pub mod opt;
 pub mod question;
 pub mod rdata;
 pub mod record;
+pub mod response;
 pub mod serial;
 pub mod str;
+pub mod time32;
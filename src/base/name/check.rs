@@ -0,0 +1,476 @@
+//! Checking host names given as text.
+//!
+//! Host names entered by users or read from configuration usually need to
+//! be checked before use. The functions in this module do so without
+//! building a domain name and report where in the input a problem was
+//! found.
+//!
+//! [`check_host_name`] checks that a name follows the rules for host names
+//! of [RFC 952] and [RFC 1123]: labels consist only of ASCII letters,
+//! digits, and hyphens – this is often called the _LDH rule_ – and don’t
+//! start or end with a hyphen, labels are at most 63 and names at most 253
+//! characters long. A single trailing dot is allowed.
+//!
+//! With the `idna` feature, [`host_name_to_ascii`] accepts internationalized
+//! names, processes them according to [UTS 46], and returns their ASCII
+//! form in which labels with non-ASCII characters are encoded as A-labels
+//! starting with `xn--`. The length limits apply to this ASCII form.
+//!
+//! For mail addresses, [`check_mail_domain`] and [`mail_domain_to_ascii`]
+//! apply the same checks to the part after the last `@`.
+//!
+//! All functions report errors as a [`HostNameError`] that contains the
+//! kind of the error and its position as a byte offset into the input.
+//!
+//! [`check_host_name`]: fn.check_host_name.html
+//! [`host_name_to_ascii`]: fn.host_name_to_ascii.html
+//! [`check_mail_domain`]: fn.check_mail_domain.html
+//! [`mail_domain_to_ascii`]: fn.mail_domain_to_ascii.html
+//! [`HostNameError`]: struct.HostNameError.html
+//! [RFC 952]: https://tools.ietf.org/html/rfc952
+//! [RFC 1123]: https://tools.ietf.org/html/rfc1123
+//! [UTS 46]: https://www.unicode.org/reports/tr46/
+
+use core::fmt;
+#[cfg(all(feature = "idna", feature = "std"))]
+use std::string::String;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum length of a label.
+const MAX_LABEL_LEN: usize = 63;
+
+/// The maximum length of a name without the trailing dot.
+///
+/// This is the 255 octets of an absolute name in wire format minus the
+/// length octet of the first label and the root label.
+const MAX_NAME_LEN: usize = 253;
+
+//------------ check_host_name -----------------------------------------------
+
+/// Checks that a string is a valid host name.
+///
+/// The name may end in a dot. See the [module documentation] for the rules
+/// applied.
+///
+/// [module documentation]: index.html
+pub fn check_host_name(name: &str) -> Result<(), HostNameError> {
+    check_ldh_name(name, 0)
+}
+
+/// Checks the domain of a mail address.
+///
+/// The domain is the part of `addr` after the last `@`. It is checked via
+/// [`check_host_name`]. The local part before the `@` is only checked for
+/// being present. Error positions are relative to the start of `addr`.
+///
+/// [`check_host_name`]: fn.check_host_name.html
+pub fn check_mail_domain(addr: &str) -> Result<(), HostNameError> {
+    let at = split_mail_addr(addr)?;
+    check_ldh_name(&addr[at + 1..], at + 1)
+}
+
+//------------ host_name_to_ascii --------------------------------------------
+
+/// Converts an internationalized host name into its ASCII form.
+///
+/// The name is processed according to UTS 46 using non-transitional
+/// processing and the STD3 rules. Labels that contain only ASCII
+/// characters are checked like [`check_host_name`] does and converted to
+/// lowercase. Other labels are converted into A-labels. The ideographic
+/// full stops `。`, `．`, and `｡` are accepted as label separators and
+/// replaced with dots. A trailing separator is kept as a dot.
+///
+/// Errors found in a label are reported at the label’s start. Errors that
+/// concern the name as a whole, such as violations of the bidi rule across
+/// labels, are reported at position zero.
+///
+/// This function is only available with the `idna` feature.
+///
+/// [`check_host_name`]: fn.check_host_name.html
+#[cfg(all(feature = "idna", feature = "std"))]
+pub fn host_name_to_ascii(name: &str) -> Result<String, HostNameError> {
+    idna_to_ascii(name, 0)
+}
+
+/// Converts the domain of a mail address into its ASCII form.
+///
+/// The domain is the part of `addr` after the last `@`. It is converted
+/// via [`host_name_to_ascii`] and the complete address with the converted
+/// domain is returned. The local part is left as is. Error positions are
+/// relative to the start of `addr`.
+///
+/// This function is only available with the `idna` feature.
+///
+/// [`host_name_to_ascii`]: fn.host_name_to_ascii.html
+#[cfg(all(feature = "idna", feature = "std"))]
+pub fn mail_domain_to_ascii(addr: &str) -> Result<String, HostNameError> {
+    let at = split_mail_addr(addr)?;
+    let domain = idna_to_ascii(&addr[at + 1..], at + 1)?;
+    let mut res = String::with_capacity(at + 1 + domain.len());
+    res.push_str(&addr[..=at]);
+    res.push_str(&domain);
+    Ok(res)
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the position of the `@` separating a mail address.
+fn split_mail_addr(addr: &str) -> Result<usize, HostNameError> {
+    match addr.rfind('@') {
+        Some(0) => {
+            Err(HostNameError::new(HostNameErrorKind::EmptyLocalPart, 0))
+        }
+        Some(at) => Ok(at),
+        None => {
+            Err(HostNameError::new(HostNameErrorKind::MissingAt, addr.len()))
+        }
+    }
+}
+
+/// Checks an ASCII host name starting at `offset` in the input.
+fn check_ldh_name(name: &str, offset: usize) -> Result<(), HostNameError> {
+    let body = strip_root(name, offset, '.')?;
+    let mut pos = offset;
+    for label in body.split('.') {
+        check_ldh_label(label, pos)?;
+        pos += label.len() + 1;
+    }
+    if body.len() > MAX_NAME_LEN {
+        return Err(HostNameError::new(
+            HostNameErrorKind::LongName,
+            offset + MAX_NAME_LEN,
+        ));
+    }
+    Ok(())
+}
+
+/// Removes a single trailing separator from a name.
+///
+/// Fails if the name is empty or only consists of the separator.
+fn strip_root(
+    name: &str,
+    offset: usize,
+    sep: char,
+) -> Result<&str, HostNameError> {
+    let body = name.strip_suffix(sep).unwrap_or(name);
+    if body.is_empty() {
+        Err(HostNameError::new(HostNameErrorKind::EmptyName, offset))
+    } else {
+        Ok(body)
+    }
+}
+
+/// Checks that a label follows the LDH rule.
+fn check_ldh_label(label: &str, pos: usize) -> Result<(), HostNameError> {
+    if label.is_empty() {
+        return Err(HostNameError::new(HostNameErrorKind::EmptyLabel, pos));
+    }
+    if let Some((idx, ch)) = label
+        .char_indices()
+        .find(|&(_, ch)| !ch.is_ascii_alphanumeric() && ch != '-')
+    {
+        return Err(HostNameError::new(
+            HostNameErrorKind::IllegalCharacter(ch),
+            pos + idx,
+        ));
+    }
+    if label.starts_with('-') {
+        return Err(HostNameError::new(
+            HostNameErrorKind::LeadingHyphen,
+            pos,
+        ));
+    }
+    if label.ends_with('-') {
+        return Err(HostNameError::new(
+            HostNameErrorKind::TrailingHyphen,
+            pos + label.len() - 1,
+        ));
+    }
+    if label.len() > MAX_LABEL_LEN {
+        return Err(HostNameError::new(HostNameErrorKind::LongLabel, pos));
+    }
+    Ok(())
+}
+
+/// Converts an internationalized name starting at `offset` in the input.
+#[cfg(all(feature = "idna", feature = "std"))]
+fn idna_to_ascii(name: &str, offset: usize) -> Result<String, HostNameError> {
+    fn is_sep(ch: char) -> bool {
+        matches!(ch, '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}')
+    }
+
+    let config = idna::Config::default()
+        .use_std3_ascii_rules(true)
+        .check_hyphens(true);
+    let (body, absolute) = match name.char_indices().last() {
+        Some((idx, ch)) if is_sep(ch) => (&name[..idx], true),
+        _ => (name, false),
+    };
+    if body.is_empty() {
+        return Err(HostNameError::new(HostNameErrorKind::EmptyName, offset));
+    }
+
+    let mut res = String::with_capacity(body.len() + 1);
+    let mut pos = offset;
+    for label in body.split(is_sep) {
+        if label.is_ascii() {
+            check_ldh_label(label, pos)?;
+            // Hyphens in the third and fourth position are reserved for
+            // A-labels.
+            if label.get(2..4) == Some("--")
+                && !label[..2].eq_ignore_ascii_case("xn")
+            {
+                return Err(HostNameError::new(HostNameErrorKind::Idna, pos));
+            }
+        }
+        let ascii = config
+            .to_ascii(label)
+            .map_err(|_| HostNameError::new(HostNameErrorKind::Idna, pos))?;
+        if ascii.len() > MAX_LABEL_LEN {
+            return Err(HostNameError::new(
+                HostNameErrorKind::LongLabel,
+                pos,
+            ));
+        }
+        if !res.is_empty() {
+            res.push('.');
+        }
+        res.push_str(&ascii);
+        if res.len() > MAX_NAME_LEN {
+            return Err(HostNameError::new(HostNameErrorKind::LongName, pos));
+        }
+        pos += label.len();
+        pos += body[pos - offset..]
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+    }
+
+    // Checks spanning labels such as the bidi rule need the whole name.
+    if config.to_ascii(&res).is_err() {
+        return Err(HostNameError::new(HostNameErrorKind::Idna, offset));
+    }
+    if absolute {
+        res.push('.');
+    }
+    Ok(res)
+}
+
+//------------ HostNameError -------------------------------------------------
+
+/// A host name failed a check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HostNameError {
+    /// What went wrong.
+    kind: HostNameErrorKind,
+
+    /// The byte offset in the input where it went wrong.
+    pos: usize,
+}
+
+impl HostNameError {
+    fn new(kind: HostNameErrorKind, pos: usize) -> Self {
+        HostNameError { kind, pos }
+    }
+
+    /// Returns the kind of error.
+    pub fn kind(&self) -> HostNameErrorKind {
+        self.kind
+    }
+
+    /// Returns the position of the error as a byte offset into the input.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for HostNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.kind, self.pos)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HostNameError {}
+
+//------------ HostNameErrorKind ---------------------------------------------
+
+/// The kind of problem found in a host name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum HostNameErrorKind {
+    /// The name is empty or only consists of the root label.
+    EmptyName,
+
+    /// A label other than the final root label is empty.
+    EmptyLabel,
+
+    /// A label is longer than 63 octets.
+    LongLabel,
+
+    /// The name is longer than 253 octets without the trailing dot.
+    LongName,
+
+    /// A label contains a character other than a letter, digit, or hyphen.
+    IllegalCharacter(char),
+
+    /// A label starts with a hyphen.
+    LeadingHyphen,
+
+    /// A label ends with a hyphen.
+    TrailingHyphen,
+
+    /// A label or the name is not valid according to UTS 46.
+    ///
+    /// This includes invalid A-labels and labels with hyphens in both the
+    /// third and fourth position that aren’t A-labels.
+    Idna,
+
+    /// A mail address doesn’t contain an `@`.
+    MissingAt,
+
+    /// A mail address has an empty local part.
+    EmptyLocalPart,
+}
+
+//--- Display
+
+impl fmt::Display for HostNameErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HostNameErrorKind::EmptyName => f.write_str("empty name"),
+            HostNameErrorKind::EmptyLabel => f.write_str("empty label"),
+            HostNameErrorKind::LongLabel => {
+                f.write_str("label length limit exceeded")
+            }
+            HostNameErrorKind::LongName => {
+                f.write_str("name length limit exceeded")
+            }
+            HostNameErrorKind::IllegalCharacter(ch) => {
+                write!(f, "illegal character '{}'", ch)
+            }
+            HostNameErrorKind::LeadingHyphen => {
+                f.write_str("label starts with a hyphen")
+            }
+            HostNameErrorKind::TrailingHyphen => {
+                f.write_str("label ends with a hyphen")
+            }
+            HostNameErrorKind::Idna => f.write_str("invalid IDNA label"),
+            HostNameErrorKind::MissingAt => f.write_str("missing '@'"),
+            HostNameErrorKind::EmptyLocalPart => {
+                f.write_str("empty local part")
+            }
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+
+    fn err(kind: HostNameErrorKind, pos: usize) -> Result<(), HostNameError> {
+        Err(HostNameError::new(kind, pos))
+    }
+
+    #[test]
+    fn host_name() {
+        use self::HostNameErrorKind::*;
+
+        assert_eq!(check_host_name("www.example.com"), Ok(()));
+        assert_eq!(check_host_name("www.example.com."), Ok(()));
+        assert_eq!(check_host_name("xn--bcher-kva.example"), Ok(()));
+        assert_eq!(check_host_name("3com.EXAMPLE"), Ok(()));
+        assert_eq!(check_host_name(""), err(EmptyName, 0));
+        assert_eq!(check_host_name("."), err(EmptyName, 0));
+        assert_eq!(check_host_name("www..example"), err(EmptyLabel, 4));
+        assert_eq!(check_host_name(".example"), err(EmptyLabel, 0));
+        assert_eq!(
+            check_host_name("www.exa_mple"),
+            err(IllegalCharacter('_'), 7)
+        );
+        assert_eq!(
+            check_host_name("www.bücher"),
+            err(IllegalCharacter('ü'), 5)
+        );
+        assert_eq!(check_host_name("www.-example"), err(LeadingHyphen, 4));
+        assert_eq!(check_host_name("www-.example"), err(TrailingHyphen, 3));
+
+        let long = "a".repeat(64);
+        assert_eq!(check_host_name(&long[..63]), Ok(()));
+        assert_eq!(
+            check_host_name(&format!("www.{}", long)),
+            err(LongLabel, 4)
+        );
+        let label = "a".repeat(63);
+        let name = format!("{0}.{0}.{0}.{1}", label, &label[..61]);
+        assert_eq!(check_host_name(&name), Ok(()));
+        assert_eq!(
+            check_host_name(&format!("{}a", name)),
+            err(LongName, 253)
+        );
+
+        assert_eq!(check_mail_domain("user@example.com"), Ok(()));
+        assert_eq!(check_mail_domain("a@b@example.com"), Ok(()));
+        assert_eq!(
+            check_mail_domain("user@exa mple.com"),
+            err(IllegalCharacter(' '), 8)
+        );
+        assert_eq!(check_mail_domain("user"), err(MissingAt, 4));
+        assert_eq!(check_mail_domain("@example.com"), err(EmptyLocalPart, 0));
+        assert_eq!(check_mail_domain("user@"), err(EmptyName, 5));
+    }
+
+    #[test]
+    #[cfg(all(feature = "idna", feature = "std"))]
+    fn idna() {
+        use self::HostNameErrorKind::*;
+
+        assert_eq!(
+            host_name_to_ascii("www.Bücher.example").unwrap(),
+            "www.xn--bcher-kva.example"
+        );
+        assert_eq!(
+            host_name_to_ascii("bücher。example。").unwrap(),
+            "xn--bcher-kva.example."
+        );
+        assert_eq!(
+            host_name_to_ascii("xn--bcher-kva.example").unwrap(),
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(
+            host_name_to_ascii("www.exa_mple").unwrap_err(),
+            HostNameError::new(IllegalCharacter('_'), 7)
+        );
+        assert_eq!(
+            host_name_to_ascii("bücher..example").unwrap_err(),
+            HostNameError::new(EmptyLabel, 8)
+        );
+        assert_eq!(
+            host_name_to_ascii("www.ab--cd.example").unwrap_err(),
+            HostNameError::new(Idna, 4)
+        );
+        assert_eq!(
+            host_name_to_ascii("www.xn--a.example").unwrap_err(),
+            HostNameError::new(Idna, 4)
+        );
+        assert_eq!(
+            host_name_to_ascii(&format!("{}ü.example", "a".repeat(60)))
+                .unwrap_err(),
+            HostNameError::new(LongLabel, 0)
+        );
+        assert_eq!(
+            mail_domain_to_ascii("jörg@bücher.example").unwrap(),
+            "jörg@xn--bcher-kva.example"
+        );
+        assert_eq!(
+            mail_domain_to_ascii("user@b_ücher.example").unwrap_err(),
+            HostNameError::new(Idna, 5)
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Alternative ways to display domain names.
+//!
+//! The `Display` implementation of the domain name types produces the
+//! common display format which omits the trailing dot of absolute names.
+//! Where a different rendering is needed, a name can be wrapped into one
+//! of the types of this module which implement `Display` differently:
+//!
+//! * [`Presentation`] produces the presentation format used in zone files
+//!   with a trailing dot for absolute names and a single dot for the root.
+//! * [`Unicode`] decodes A-labels – labels starting with `xn--` – into
+//!   their Unicode form for showing internationalized names to people. It
+//!   needs the `idna` feature.
+//! * [`Lossless`] shows every label with its length, so that the exact
+//!   octets of the name can be seen, which is useful in debug logs.
+//!
+//! All wrappers work with any type that provides its labels via
+//! [`ToLabelIter`], i.e., all domain name types of this crate.
+//!
+//! [`Presentation`]: struct.Presentation.html
+//! [`Unicode`]: struct.Unicode.html
+//! [`Lossless`]: struct.Lossless.html
+//! [`ToLabelIter`]: trait.ToLabelIter.html
+
+use super::label::Label;
+use super::traits::ToLabelIter;
+use core::fmt;
+
+//------------ Presentation --------------------------------------------------
+
+/// Displays a domain name in presentation format.
+///
+/// Labels are separated by dots and special characters are escaped as in
+/// the name’s regular `Display` output. Absolute names end in a dot and
+/// the root name is displayed as a single dot.
+#[derive(Clone, Copy, Debug)]
+pub struct Presentation<'a, N: ?Sized>(&'a N);
+
+impl<'a, N: ?Sized> Presentation<'a, N> {
+    /// Creates a wrapper for displaying `name`.
+    pub fn new(name: &'a N) -> Self {
+        Presentation(name)
+    }
+}
+
+impl<'a, N: ToLabelIter<'a> + ?Sized> fmt::Display for Presentation<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_labels(self.0, f, fmt::Display::fmt)
+    }
+}
+
+//------------ Unicode -------------------------------------------------------
+
+/// Displays a domain name with A-labels converted to Unicode.
+///
+/// Labels starting with `xn--` are decoded and checked according to
+/// UTS 46. If this succeeds, the label is displayed in its Unicode form.
+/// All other labels, including invalid A-labels, are displayed as in
+/// [`Presentation`] which this type otherwise follows.
+///
+/// Since the output may contain characters that look alike, it should
+/// only be used where names are shown to people and not where they are
+/// processed further.
+///
+/// This type is only available with the `idna` feature.
+///
+/// [`Presentation`]: struct.Presentation.html
+#[cfg(all(feature = "idna", feature = "std"))]
+#[derive(Clone, Copy, Debug)]
+pub struct Unicode<'a, N: ?Sized>(&'a N);
+
+#[cfg(all(feature = "idna", feature = "std"))]
+impl<'a, N: ?Sized> Unicode<'a, N> {
+    /// Creates a wrapper for displaying `name`.
+    pub fn new(name: &'a N) -> Self {
+        Unicode(name)
+    }
+}
+
+#[cfg(all(feature = "idna", feature = "std"))]
+impl<'a, N: ToLabelIter<'a> + ?Sized> fmt::Display for Unicode<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_labels(self.0, f, |label, f| match decode_a_label(label) {
+            Some(label) => f.write_str(&label),
+            None => fmt::Display::fmt(label, f),
+        })
+    }
+}
+
+/// Returns the Unicode form of a valid A-label.
+#[cfg(all(feature = "idna", feature = "std"))]
+fn decode_a_label(label: &Label) -> Option<std::string::String> {
+    let label = core::str::from_utf8(label.as_slice()).ok()?;
+    if !label.get(..4)?.eq_ignore_ascii_case("xn--") {
+        return None;
+    }
+    let (res, status) = idna::Config::default()
+        .use_std3_ascii_rules(true)
+        .check_hyphens(true)
+        .to_unicode(label);
+    if status.is_err() || res.contains('.') {
+        return None;
+    }
+    Some(res)
+}
+
+//------------ Lossless ------------------------------------------------------
+
+/// Displays a domain name showing the length of every label.
+///
+/// Each label, including the root label, is displayed as its length in
+/// parentheses followed by its content. Printable ASCII characters are
+/// shown as is, except for backslashes and parentheses which are escaped
+/// with a backslash. All other octets are escaped as a backslash followed
+/// by three decimal digits. The name `www.example.com.` thus becomes
+/// `(3)www(7)example(3)com(0)`.
+///
+/// Unlike the other formats, this shows the structure of the name even if
+/// labels contain dots and tells absolute and relative names apart at a
+/// glance.
+#[derive(Clone, Copy, Debug)]
+pub struct Lossless<'a, N: ?Sized>(&'a N);
+
+impl<'a, N: ?Sized> Lossless<'a, N> {
+    /// Creates a wrapper for displaying `name`.
+    pub fn new(name: &'a N) -> Self {
+        Lossless(name)
+    }
+}
+
+impl<'a, N: ToLabelIter<'a> + ?Sized> fmt::Display for Lossless<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for label in self.0.iter_labels() {
+            write!(f, "({})", label.len())?;
+            for &ch in label.iter() {
+                if ch == b'\\' || ch == b'(' || ch == b')' {
+                    write!(f, "\\{}", ch as char)?;
+                } else if !(0x20..0x7F).contains(&ch) {
+                    write!(f, "\\{:03}", ch)?;
+                } else {
+                    write!(f, "{}", ch as char)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Formats the labels of a name separated by dots.
+///
+/// The root label becomes a trailing dot or, if it is the only label, a
+/// single dot.
+fn fmt_labels<'a, N, F>(
+    name: &'a N,
+    f: &mut fmt::Formatter,
+    mut fmt_label: F,
+) -> fmt::Result
+where
+    N: ToLabelIter<'a> + ?Sized,
+    F: FnMut(&Label, &mut fmt::Formatter) -> fmt::Result,
+{
+    let mut first = true;
+    for label in name.iter_labels() {
+        if label.is_root() {
+            return f.write_str(".");
+        }
+        if !first {
+            f.write_str(".")?;
+        }
+        first = false;
+        fmt_label(label, f)?;
+    }
+    Ok(())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::name::{Dname, RelativeDname};
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn presentation() {
+        assert_eq!(
+            name("www.example.com").display_presentation().to_string(),
+            "www.example.com."
+        );
+        assert_eq!(Dname::root_vec().display_presentation().to_string(), ".");
+        assert_eq!(
+            name("a\\.b.ex\\032ample")
+                .display_presentation()
+                .to_string(),
+            "a\\.b.ex\\ ample."
+        );
+        let relative =
+            RelativeDname::from_octets(b"\x03www".to_vec()).unwrap();
+        assert_eq!(Presentation::new(&relative).to_string(), "www");
+    }
+
+    #[test]
+    fn lossless() {
+        assert_eq!(
+            name("www.example.com").display_lossless().to_string(),
+            "(3)www(7)example(3)com(0)"
+        );
+        assert_eq!(
+            name("a\\.b.x\\(\\000").display_lossless().to_string(),
+            "(3)a.b(3)x\\(\\000(0)"
+        );
+        let relative =
+            RelativeDname::from_octets(b"\x03www".to_vec()).unwrap();
+        assert_eq!(Lossless::new(&relative).to_string(), "(3)www");
+    }
+
+    #[test]
+    #[cfg(feature = "idna")]
+    fn unicode() {
+        assert_eq!(
+            name("www.xn--bcher-kva.example")
+                .display_unicode()
+                .to_string(),
+            "www.bücher.example."
+        );
+        assert_eq!(
+            name("XN--BCHER-KVA.example").display_unicode().to_string(),
+            "bücher.example."
+        );
+        // Invalid A-labels are kept as they are.
+        assert_eq!(
+            name("xn--a.example").display_unicode().to_string(),
+            "xn--a.example."
+        );
+        assert_eq!(Dname::root_vec().display_unicode().to_string(), ".");
+    }
+}
@@ -4,6 +4,9 @@ use super::super::octets::{
     OctetsFrom, OctetsRef, Parse, ParseError, Parser, ShortBuf,
 };
 use super::builder::{DnameBuilder, FromStrError};
+#[cfg(all(feature = "idna", feature = "std"))]
+use super::display::Unicode;
+use super::display::{Lossless, Presentation};
 use super::label::{Label, LabelTypeError, SplitLabelError};
 use super::relative::{DnameIter, RelativeDname};
 use super::traits::{ToDname, ToLabelIter};
@@ -254,6 +257,33 @@ impl<Octets: AsRef<[u8]> + ?Sized> Dname<Octets> {
     }
 }
 
+/// # Alternative Display Formats
+///
+/// The `Display` implementation omits the trailing dot. These methods
+/// return wrappers that display the name differently.
+impl<Octets: AsRef<[u8]> + ?Sized> Dname<Octets> {
+    /// Returns a value displaying the name in presentation format.
+    ///
+    /// The output includes the trailing dot and shows the root name as a
+    /// single dot.
+    pub fn display_presentation(&self) -> Presentation<'_, Self> {
+        Presentation::new(self)
+    }
+
+    /// Returns a value displaying the name with A-labels in Unicode.
+    ///
+    /// This is only available with the `idna` feature.
+    #[cfg(all(feature = "idna", feature = "std"))]
+    pub fn display_unicode(&self) -> Unicode<'_, Self> {
+        Unicode::new(self)
+    }
+
+    /// Returns a value displaying the name with all label lengths.
+    pub fn display_lossless(&self) -> Lossless<'_, Self> {
+        Lossless::new(self)
+    }
+}
+
 /// # Working with Labels
 ///
 /// All methods that split the name or cut off parts on the left side are
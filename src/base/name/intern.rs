@@ -0,0 +1,142 @@
+//! A pool for sharing the storage of identical domain names.
+#![cfg(all(feature = "std", feature = "bytes"))]
+
+use super::dname::Dname;
+use super::traits::ToDname;
+use bytes::Bytes;
+use std::collections::HashSet;
+
+//------------ NamePool ------------------------------------------------------
+
+/// A pool of domain names sharing their storage.
+///
+/// Large zones contain the same owner names over and over again: every
+/// record of every RRset at a node and the signatures and NSEC records
+/// added during signing all carry a copy of the node’s name. When names
+/// are stored as [`Dname<Bytes>`], the pool can be used to make all these
+/// copies share the same octets.
+///
+/// The [`intern`] method returns a name that refers to the pool’s copy of
+/// the given name, adding a copy if the pool doesn’t have one yet. Names
+/// are compared octet by octet, so names that only differ in case are kept
+/// separately. The pool keeps statistics on how many octets have been
+/// saved which can be retrieved via [`stats`].
+///
+/// [`Dname<Bytes>`]: struct.Dname.html
+/// [`intern`]: #method.intern
+/// [`stats`]: #method.stats
+#[derive(Clone, Debug, Default)]
+pub struct NamePool {
+    /// The names in the pool.
+    names: HashSet<Bytes>,
+
+    /// The statistics of the pool.
+    stats: PoolStats,
+}
+
+impl NamePool {
+    /// Creates a new, empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of distinct names in the pool.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns whether the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the statistics of the pool.
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    /// Returns a name sharing its storage with the pool’s copy of `name`.
+    pub fn intern<N: ToDname + ?Sized>(&mut self, name: &N) -> Dname<Bytes> {
+        let name = name.to_cow();
+        let slice = name.as_slice();
+        self.stats.requests += 1;
+        self.stats.requested_octets += slice.len();
+        let octets = match self.names.get(slice) {
+            Some(octets) => octets.clone(),
+            None => {
+                let octets = Bytes::copy_from_slice(slice);
+                self.stats.stored_octets += octets.len();
+                self.names.insert(octets.clone());
+                octets
+            }
+        };
+        unsafe { Dname::from_octets_unchecked(octets) }
+    }
+
+    /// Removes all names from the pool and resets its statistics.
+    ///
+    /// Names previously returned by the pool stay valid.
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.stats = PoolStats::default();
+    }
+}
+
+//------------ PoolStats -----------------------------------------------------
+
+/// Statistics of a name pool.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PoolStats {
+    /// The number of names interned.
+    pub requests: usize,
+
+    /// The total number of octets of all interned names.
+    pub requested_octets: usize,
+
+    /// The number of octets of the distinct names kept by the pool.
+    pub stored_octets: usize,
+}
+
+impl PoolStats {
+    /// Returns the number of octets saved by sharing names.
+    ///
+    /// This is the difference between the octets all interned names would
+    /// occupy if stored separately and the octets actually stored. It
+    /// doesn’t include the per-name overhead of the pool itself or of the
+    /// `Bytes` values.
+    pub fn saved_octets(&self) -> usize {
+        self.requested_octets - self.stored_octets
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+    use std::vec::Vec;
+
+    #[test]
+    fn intern() {
+        let mut pool = NamePool::new();
+        let www = Dname::<Vec<u8>>::from_str("www.example.").unwrap();
+        let first = pool.intern(&www);
+        let second = pool.intern(&www.to_bytes());
+        let other =
+            pool.intern(&Dname::<Vec<u8>>::from_str("WWW.example.").unwrap());
+        assert_eq!(first, www);
+        assert_eq!(first.as_slice().as_ptr(), second.as_slice().as_ptr());
+        assert_ne!(first.as_slice().as_ptr(), other.as_slice().as_ptr());
+        assert_eq!(pool.len(), 2);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                requests: 3,
+                requested_octets: 39,
+                stored_octets: 26,
+            }
+        );
+        assert_eq!(pool.stats().saved_octets(), 13);
+    }
+}
@@ -47,21 +47,49 @@
 //! Alternatively, you can use [`DnameBuilder`] to construct a name manually
 //! from individual labels.
 //!
+//! When keeping many copies of the same names, such as the owner names in a
+//! large zone, a [`NamePool`] lets identical names share their octets.
+//!
+//! Host names given as text, for instance by users, can be checked for
+//! following the rules for host names via [`check_host_name`]. With the
+//! `idna` feature, internationalized names can be checked and converted via
+//! [`host_name_to_ascii`].
+//!
+//! Besides their regular `Display` output, names can be displayed in
+//! presentation format via [`Presentation`], with labels in Unicode via
+//! [`Unicode`], or with all label lengths via [`Lossless`].
+//!
 //! [`Bytes`]: ../../../bytes/struct.Bytes.html
+//! [`check_host_name`]: fn.check_host_name.html
+//! [`host_name_to_ascii`]: fn.host_name_to_ascii.html
+//! [`Lossless`]: struct.Lossless.html
 //! [`Dname`]: struct.Dname.html
 //! [`DnameBuilder`]: struct.DnameBuilder.html
 //! [`FromStr`]: ../../../std/str/trait.FromStr.html
+//! [`NamePool`]: struct.NamePool.html
 //! [`ParsedDname`]: struct.ParsedDname.html
+//! [`Presentation`]: struct.Presentation.html
 //! [`RelativeDname`]: struct.RelativeDname.html
 //! [`ToDname`]: trait.ToDname.html
 //! [`ToRelativeDname`]: trait.ToRelativeDname.html
 //! [`UncertainDname`]: enum.UncertainDname.html
+//! [`Unicode`]: struct.Unicode.html
 
 pub use self::builder::{
     DnameBuilder, FromStrError, PushError, PushNameError,
 };
 pub use self::chain::{Chain, ChainIter, LongChainError, UncertainChainIter};
+pub use self::check::{
+    check_host_name, check_mail_domain, HostNameError, HostNameErrorKind,
+};
+#[cfg(all(feature = "idna", feature = "std"))]
+pub use self::check::{host_name_to_ascii, mail_domain_to_ascii};
+#[cfg(all(feature = "idna", feature = "std"))]
+pub use self::display::Unicode;
+pub use self::display::{Lossless, Presentation};
 pub use self::dname::{Dname, DnameError};
+#[cfg(all(feature = "std", feature = "bytes"))]
+pub use self::intern::{NamePool, PoolStats};
 pub use self::label::{
     Label, LabelTypeError, LongLabelError, OwnedLabel, SliceLabelsIter,
     SplitLabelError,
@@ -70,6 +98,10 @@ pub use self::parsed::{ParsedDname, ParsedDnameIter, ParsedSuffixIter};
 pub use self::relative::{
     DnameIter, RelativeDname, RelativeDnameError, StripSuffixError,
 };
+pub use self::reverse::{
+    addr_from_reverse_name, classless_reverse_name, prefix_from_reverse_name,
+    reverse_name, reverse_prefix_name, ReverseNameError,
+};
 pub use self::traits::{
     ToDname, ToEitherDname, ToLabelIter, ToRelativeDname,
 };
@@ -77,9 +109,13 @@ pub use self::uncertain::UncertainDname;
 
 mod builder;
 mod chain;
+mod check;
+mod display;
 mod dname;
+mod intern;
 mod label;
 mod parsed;
 mod relative;
+mod reverse;
 mod traits;
 mod uncertain;
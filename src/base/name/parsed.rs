@@ -5,8 +5,8 @@
 
 use super::super::cmp::CanonicalOrd;
 use super::super::octets::{
-    Compose, FormError, OctetsBuilder, OctetsRef, Parse, ParseError, Parser,
-    ShortBuf,
+    Compose, EmptyBuilder, FormError, FromBuilder, OctetsBuilder, OctetsFrom,
+    OctetsRef, Parse, ParseError, Parser, ShortBuf,
 };
 use super::dname::Dname;
 use super::label::{Label, LabelTypeError};
@@ -198,6 +198,20 @@ impl<Ref: AsRef<[u8]>> From<Dname<Ref>> for ParsedDname<Ref> {
     }
 }
 
+//--- OctetsFrom
+
+impl<Octets, Ref> OctetsFrom<ParsedDname<Ref>> for Dname<Octets>
+where
+    Ref: AsRef<[u8]>,
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    /// Assembles the labels of a parsed name into an uncompressed name.
+    fn octets_from(source: ParsedDname<Ref>) -> Result<Self, ShortBuf> {
+        source.to_dname().map_err(|_| ShortBuf)
+    }
+}
+
 //--- PartialEq and Eq
 
 impl<Ref, N> PartialEq<N> for ParsedDname<Ref>
@@ -316,6 +330,15 @@ where
         let mut len = 0;
         let mut compressed = false;
 
+        // Compression pointers must point to an earlier occurrence of a
+        // name. Since every name or suffix pointed to ends either in the
+        // root label or in a pointer to an even earlier position, we can
+        // require each pointer to point to before the start of the
+        // sequence of labels it appears in. This rules out loops as well as
+        // forward pointers right away rather than only after running into
+        // the pointer limit above.
+        let mut start = tmp.pos();
+
         loop {
             match LabelType::parse(&mut tmp)? {
                 LabelType::Normal(0) => {
@@ -341,6 +364,10 @@ where
                             ParsedDnameError::ExcessiveCompression.into()
                         );
                     }
+                    if pos >= start {
+                        return Err(ParsedDnameError::InvalidPointer.into());
+                    }
+                    start = pos;
                     if ptrs == 0 {
                         parser.seek(tmp.pos()).unwrap();
                     }
@@ -624,6 +651,9 @@ pub enum ParsedDnameError {
 
     /// Too many compression pointers.
     ExcessiveCompression,
+
+    /// A compression pointer doesn’t point to an earlier name.
+    InvalidPointer,
 }
 
 impl fmt::Display for ParsedDnameError {
@@ -651,6 +681,9 @@ impl From<ParsedDnameError> for FormError {
             ParsedDnameError::ExcessiveCompression => {
                 FormError::new("too many compression pointers")
             }
+            ParsedDnameError::InvalidPointer => {
+                FormError::new("invalid compression pointer")
+            }
         }
     }
 }
@@ -1012,7 +1045,7 @@ mod test {
         let mut parser = p(b"\x03www\xc0\xee12", 0);
         assert_eq!(
             ParsedDname::parse(&mut parser.clone()),
-            Err(ParseError::ShortInput)
+            Err(ParsedDnameError::InvalidPointer.into())
         );
         assert_eq!(ParsedDname::skip(&mut parser), Ok(()));
         assert_eq!(parser.remaining(), 2);
@@ -1064,6 +1097,44 @@ mod test {
         assert!(ParsedDname::parse(&mut parser.clone()).is_err());
         assert_eq!(ParsedDname::skip(&mut parser), Ok(()));
         assert_eq!(parser.remaining(), 2);
+
+        // Forward pointer
+        let parser = p(b"\x03www\xc0\x06\x03com\0", 0);
+        assert_eq!(
+            ParsedDname::parse(&mut parser.clone()),
+            Err(ParsedDnameError::InvalidPointer.into())
+        );
+
+        // Loop through a label
+        let parser = p(b"\x03com\0\x03www\xc0\x05", 5);
+        assert_eq!(
+            ParsedDname::parse(&mut parser.clone()),
+            Err(ParsedDnameError::InvalidPointer.into())
+        );
+
+        // A pointer to an earlier name is fine, but a pointer found there
+        // needs to point further back still.
+        let parser = p(b"\x07example\xc0\x10\x03www\xc0\x00\x03com\0", 10);
+        assert_eq!(
+            ParsedDname::parse(&mut parser.clone()),
+            Err(ParsedDnameError::InvalidPointer.into())
+        );
+
+        // Excessive indirection
+        let mut buf = Vec::from(&b"\0"[..]);
+        for _ in 0..128 {
+            let target = buf.len().saturating_sub(2) as u16;
+            buf.extend_from_slice(&(0xC000 | target).to_be_bytes());
+        }
+        let mut parser = Parser::from_ref(buf.as_slice());
+        parser.advance(buf.len() - 2).unwrap();
+        assert_eq!(
+            ParsedDname::parse(&mut parser.clone()),
+            Err(ParsedDnameError::ExcessiveCompression.into())
+        );
+        let mut parser = Parser::from_ref(buf.as_slice());
+        parser.advance(buf.len() - 4).unwrap();
+        assert_eq!(ParsedDname::parse(&mut parser).unwrap().len(), 1);
     }
 
     #[test]
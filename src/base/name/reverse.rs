@@ -0,0 +1,490 @@
+//! Domain names for reverse lookups.
+//!
+//! This is a private module. Its public items are re-exported by the parent.
+
+use super::super::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use super::super::octets::{
+    EmptyBuilder, FromBuilder, OctetsBuilder, ShortBuf,
+};
+use super::builder::{DnameBuilder, PushError};
+use super::dname::Dname;
+use super::label::Label;
+use super::traits::ToDname;
+use core::fmt;
+
+//------------ reverse_name --------------------------------------------------
+
+/// Returns the domain name used for reverse lookups of an IP address.
+///
+/// For IPv4 addresses, this is the name below `in-addr.arpa.` described
+/// in section 3.5 of [RFC 1035]. For IPv6 addresses, it is the name below
+/// `ip6.arpa.` described in section 2.5 of [RFC 3596].
+///
+/// The function only fails if the octets sequence is too short for the
+/// name.
+///
+/// [RFC 1035]: https://tools.ietf.org/html/rfc1035
+/// [RFC 3596]: https://tools.ietf.org/html/rfc3596
+pub fn reverse_name<Octets>(addr: IpAddr) -> Result<Dname<Octets>, ShortBuf>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    let len = match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    reverse_prefix_name(addr, len).map_err(|_| ShortBuf)
+}
+
+//------------ reverse_prefix_name -------------------------------------------
+
+/// Returns the reverse lookup domain name for an address prefix.
+///
+/// This is the name of the zone that can be delegated for all addresses
+/// covered by the prefix given via the address `addr` and the prefix length
+/// `len` in bits. Any bits of `addr` beyond the prefix length are ignored.
+///
+/// Since each label covers eight bits for IPv4 and four bits for IPv6
+/// addresses, the prefix length must be a multiple of these. For IPv4
+/// prefixes in between, [`classless_reverse_name`] can be used.
+///
+/// [`classless_reverse_name`]: fn.classless_reverse_name.html
+pub fn reverse_prefix_name<Octets>(
+    addr: IpAddr,
+    len: u8,
+) -> Result<Dname<Octets>, ReverseNameError>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    let mut res = DnameBuilder::<<Octets as FromBuilder>::Builder>::new();
+    match addr {
+        IpAddr::V4(addr) => {
+            if len > 32 || len & 0x07 != 0 {
+                return Err(ReverseNameError::BadPrefixLen);
+            }
+            let octets = addr.octets();
+            for &octet in octets[..usize::from(len / 8)].iter().rev() {
+                append_decimal(&mut res, octet)?;
+            }
+            res.append_label(b"in-addr")?;
+        }
+        IpAddr::V6(addr) => {
+            if len > 128 || len & 0x03 != 0 {
+                return Err(ReverseNameError::BadPrefixLen);
+            }
+            let octets = addr.octets();
+            for nibble in (0..usize::from(len / 4)).rev() {
+                let octet = octets[nibble / 2];
+                let value = if nibble % 2 == 0 { octet >> 4 } else { octet };
+                res.append_label(&[hexdigit(value)])?;
+            }
+            res.append_label(b"ip6")?;
+        }
+    }
+    res.append_label(b"arpa")?;
+    Ok(res.into_dname()?)
+}
+
+//------------ classless_reverse_name ----------------------------------------
+
+/// Returns the name for a classless in-addr.arpa delegation.
+///
+/// [RFC 2317] describes how reverse lookups for IPv4 prefixes longer than
+/// 24 bits can be delegated by placing CNAME records for each address
+/// into the zone for the enclosing /24 pointing to names in a delegated
+/// child zone. This function returns the name of this child zone for the
+/// prefix given by `addr` and `len`.
+///
+/// The name follows the suggestion of the RFC: the first label consists of
+/// the last octet of the first address of the prefix and the prefix length
+/// separated by a slash. For instance, for the prefix 192.0.2.128/25, the
+/// name is `128/25.2.0.192.in-addr.arpa.` The name of the CNAME target for
+/// an address is this name with the address’s last octet prepended.
+///
+/// The prefix length must be between 25 and 32.
+///
+/// [RFC 2317]: https://tools.ietf.org/html/rfc2317
+pub fn classless_reverse_name<Octets>(
+    addr: Ipv4Addr,
+    len: u8,
+) -> Result<Dname<Octets>, ReverseNameError>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    if len <= 24 || len > 32 {
+        return Err(ReverseNameError::BadPrefixLen);
+    }
+    let octets = addr.octets();
+    let mut res = DnameBuilder::<<Octets as FromBuilder>::Builder>::new();
+    append_decimal_digits(&mut res, octets[3] & (0xFF << (32 - len)))?;
+    res.push(b'/')?;
+    append_decimal_digits(&mut res, len)?;
+    res.end_label();
+    for &octet in octets[..3].iter().rev() {
+        append_decimal(&mut res, octet)?;
+    }
+    res.append_label(b"in-addr")?;
+    res.append_label(b"arpa")?;
+    Ok(res.into_dname()?)
+}
+
+//------------ prefix_from_reverse_name --------------------------------------
+
+/// Returns the address prefix described by a reverse lookup domain name.
+///
+/// This reverses [`reverse_name`], [`reverse_prefix_name`], and
+/// [`classless_reverse_name`]. It returns the address and prefix length
+/// if `name` is `in-addr.arpa.` or `ip6.arpa.` or a name below them that
+/// describes an address prefix. Bits beyond the prefix length will be
+/// zero. For the names of classless delegations, both the dash and the
+/// slash are accepted as separator between first address and prefix
+/// length.
+///
+/// Returns `None` if the name isn’t a reverse lookup name.
+///
+/// [`reverse_name`]: fn.reverse_name.html
+/// [`reverse_prefix_name`]: fn.reverse_prefix_name.html
+/// [`classless_reverse_name`]: fn.classless_reverse_name.html
+pub fn prefix_from_reverse_name<N: ToDname + ?Sized>(
+    name: &N,
+) -> Option<(IpAddr, u8)> {
+    let mut labels = name.iter_labels().rev();
+    if !labels.next()?.is_root()
+        || labels.next()? != Label::from_slice(b"arpa").unwrap()
+    {
+        return None;
+    }
+    let family = labels.next()?;
+    if family == Label::from_slice(b"in-addr").unwrap() {
+        let mut octets = [0u8; 4];
+        let mut len = 0;
+        while let Some(label) = labels.next() {
+            if len == 24 {
+                if let Some((first, prefix)) = parse_classless(label) {
+                    octets[3] = first;
+                    return match labels.next() {
+                        None => Some((Ipv4Addr::from(octets).into(), prefix)),
+                        Some(label) => {
+                            octets[3] = parse_decimal(label)?;
+                            if labels.next().is_some()
+                                || octets[3] & (0xFF << (32 - prefix))
+                                    != first
+                            {
+                                return None;
+                            }
+                            Some((Ipv4Addr::from(octets).into(), 32))
+                        }
+                    };
+                }
+            }
+            if len == 32 {
+                return None;
+            }
+            octets[usize::from(len / 8)] = parse_decimal(label)?;
+            len += 8;
+        }
+        Some((Ipv4Addr::from(octets).into(), len))
+    } else if family == Label::from_slice(b"ip6").unwrap() {
+        let mut octets = [0u8; 16];
+        let mut len = 0;
+        for label in labels {
+            if len == 128 {
+                return None;
+            }
+            let value = parse_hexdigit(label)?;
+            let octet = &mut octets[usize::from(len / 8)];
+            if len % 8 == 0 {
+                *octet = value << 4
+            } else {
+                *octet |= value
+            }
+            len += 4;
+        }
+        Some((Ipv6Addr::from(octets).into(), len))
+    } else {
+        None
+    }
+}
+
+//------------ addr_from_reverse_name ----------------------------------------
+
+/// Returns the IP address described by a reverse lookup domain name.
+///
+/// This is the reverse of [`reverse_name`]. Returns `None` if `name` is not
+/// a reverse lookup name or if it only describes a prefix rather than a
+/// single address.
+///
+/// [`reverse_name`]: fn.reverse_name.html
+pub fn addr_from_reverse_name<N: ToDname + ?Sized>(
+    name: &N,
+) -> Option<IpAddr> {
+    match prefix_from_reverse_name(name)? {
+        (addr @ IpAddr::V4(_), 32) | (addr @ IpAddr::V6(_), 128) => {
+            Some(addr)
+        }
+        _ => None,
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Appends a label with the decimal representation of `value`.
+fn append_decimal<Builder: OctetsBuilder>(
+    builder: &mut DnameBuilder<Builder>,
+    value: u8,
+) -> Result<(), PushError> {
+    append_decimal_digits(builder, value)?;
+    builder.end_label();
+    Ok(())
+}
+
+/// Appends the decimal digits of `value` to the current label.
+fn append_decimal_digits<Builder: OctetsBuilder>(
+    builder: &mut DnameBuilder<Builder>,
+    value: u8,
+) -> Result<(), PushError> {
+    if value >= 100 {
+        builder.push(b'0' + value / 100)?;
+    }
+    if value >= 10 {
+        builder.push(b'0' + (value / 10) % 10)?;
+    }
+    builder.push(b'0' + value % 10)
+}
+
+/// Returns the hex digit for the lower four bits of `value`.
+fn hexdigit(value: u8) -> u8 {
+    b"0123456789abcdef"[usize::from(value & 0x0F)]
+}
+
+/// Parses a label consisting of a decimal number between 0 and 255.
+///
+/// Leading zeros are not allowed.
+fn parse_decimal(label: &Label) -> Option<u8> {
+    parse_decimal_slice(label.as_slice())
+}
+
+/// Parses a slice consisting of a decimal number between 0 and 255.
+fn parse_decimal_slice(slice: &[u8]) -> Option<u8> {
+    if slice.is_empty()
+        || slice.len() > 3
+        || (slice.len() > 1 && slice[0] == b'0')
+    {
+        return None;
+    }
+    let mut res = 0u16;
+    for &ch in slice {
+        if !ch.is_ascii_digit() {
+            return None;
+        }
+        res = res * 10 + u16::from(ch - b'0');
+    }
+    if res > 255 {
+        None
+    } else {
+        Some(res as u8)
+    }
+}
+
+/// Parses a label of a classless delegation into first octet and length.
+fn parse_classless(label: &Label) -> Option<(u8, u8)> {
+    let slice = label.as_slice();
+    let pos = slice.iter().position(|&ch| ch == b'/' || ch == b'-')?;
+    let first = parse_decimal_slice(&slice[..pos])?;
+    let len = parse_decimal_slice(&slice[pos + 1..])?;
+    if len <= 24 || len > 32 || first & !(0xFF << (32 - len)) != 0 {
+        return None;
+    }
+    Some((first, len))
+}
+
+/// Parses a label consisting of a single hex digit.
+fn parse_hexdigit(label: &Label) -> Option<u8> {
+    match label.as_slice() {
+        [ch] => (*ch as char).to_digit(16).map(|value| value as u8),
+        _ => None,
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ ReverseNameError ----------------------------------------------
+
+/// An error happened while creating a reverse lookup domain name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReverseNameError {
+    /// The prefix length is not permitted.
+    BadPrefixLen,
+
+    /// The buffer is too short to contain the name.
+    ShortBuf,
+}
+
+//--- From
+
+impl From<PushError> for ReverseNameError {
+    fn from(_: PushError) -> Self {
+        // The labels are always short and the name never exceeds the
+        // limit, so the only possible error is a short buffer.
+        ReverseNameError::ShortBuf
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for ReverseNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReverseNameError::BadPrefixLen => {
+                f.write_str("invalid prefix length")
+            }
+            ReverseNameError::ShortBuf => ShortBuf.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReverseNameError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn reverse_name() {
+        assert_eq!(
+            super::reverse_name::<Vec<u8>>(addr("192.0.2.1"))
+                .unwrap()
+                .to_string(),
+            "1.2.0.192.in-addr.arpa"
+        );
+        assert_eq!(
+            super::reverse_name::<Vec<u8>>(addr("2001:db8::9af"))
+                .unwrap()
+                .to_string(),
+            "f.a.9.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+             0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn reverse_prefix_name() {
+        assert_eq!(
+            super::reverse_prefix_name::<Vec<u8>>(addr("192.0.2.1"), 24)
+                .unwrap(),
+            name("2.0.192.in-addr.arpa")
+        );
+        assert_eq!(
+            super::reverse_prefix_name::<Vec<u8>>(addr("10.1.2.3"), 0)
+                .unwrap(),
+            name("in-addr.arpa")
+        );
+        assert_eq!(
+            super::reverse_prefix_name::<Vec<u8>>(addr("2001:db8::"), 36)
+                .unwrap(),
+            name("0.8.b.d.0.1.0.0.2.ip6.arpa")
+        );
+        assert_eq!(
+            super::reverse_prefix_name::<Vec<u8>>(addr("192.0.2.1"), 25),
+            Err(ReverseNameError::BadPrefixLen)
+        );
+        assert_eq!(
+            super::reverse_prefix_name::<Vec<u8>>(addr("2001:db8::"), 130),
+            Err(ReverseNameError::BadPrefixLen)
+        );
+    }
+
+    #[test]
+    fn classless_reverse_name() {
+        assert_eq!(
+            super::classless_reverse_name::<Vec<u8>>(
+                Ipv4Addr::new(192, 0, 2, 130),
+                25
+            )
+            .unwrap()
+            .to_string(),
+            "128/25.2.0.192.in-addr.arpa"
+        );
+        assert_eq!(
+            super::classless_reverse_name::<Vec<u8>>(
+                Ipv4Addr::new(192, 0, 2, 130),
+                24
+            ),
+            Err(ReverseNameError::BadPrefixLen)
+        );
+    }
+
+    #[test]
+    fn prefix_from_reverse_name() {
+        for &(s, prefix, len) in &[
+            ("1.2.0.192.in-addr.arpa", "192.0.2.1", 32),
+            ("2.0.192.IN-ADDR.ARPA", "192.0.2.0", 24),
+            ("in-addr.arpa", "0.0.0.0", 0),
+            ("128/25.2.0.192.in-addr.arpa", "192.0.2.128", 25),
+            ("64-26.2.0.192.in-addr.arpa", "192.0.2.64", 26),
+            ("130.128/25.2.0.192.in-addr.arpa", "192.0.2.130", 32),
+            ("0.8.b.d.0.1.0.0.2.ip6.arpa", "2001:db8::", 36),
+            (
+                "f.a.9.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+                 0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa",
+                "2001:db8::9af",
+                128,
+            ),
+        ] {
+            assert_eq!(
+                super::prefix_from_reverse_name(&name(s)),
+                Some((addr(prefix), len)),
+                "{}",
+                s
+            );
+        }
+        for s in &[
+            "example.com",
+            "arpa",
+            "1.1.2.0.192.in-addr.arpa",
+            "01.2.0.192.in-addr.arpa",
+            "256.2.0.192.in-addr.arpa",
+            "129/25.2.0.192.in-addr.arpa",
+            "1.128/25.2.0.192.in-addr.arpa",
+            "10.2.0.in-addr.arpa.example",
+            "g.ip6.arpa",
+            "10.ip6.arpa",
+        ] {
+            assert_eq!(
+                super::prefix_from_reverse_name(&name(s)),
+                None,
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn addr_from_reverse_name() {
+        assert_eq!(
+            super::addr_from_reverse_name(&name("1.2.0.192.in-addr.arpa")),
+            Some(addr("192.0.2.1"))
+        );
+        assert_eq!(
+            super::addr_from_reverse_name(&name("2.0.192.in-addr.arpa")),
+            None
+        );
+    }
+}
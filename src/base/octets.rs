@@ -142,6 +142,7 @@
 //! [`Parser`]: struct.Parser.html
 //! [`ShortBuf`]: struct.ShortBuf.html
 
+use super::iana::Rtype;
 use super::name::ToDname;
 use super::net::{Ipv4Addr, Ipv6Addr};
 #[cfg(feature = "bytes")]
@@ -497,6 +498,41 @@ pub trait OctetsBuilder: AsRef<[u8]> + AsMut<[u8]> + Sized {
         }
     }
 
+    /// Returns whether names in record data of `rtype` are compressed.
+    ///
+    /// This is consulted when composing a record. If it returns `false`,
+    /// all names in the record data are appended uncompressed even if the
+    /// record data type asks for compression via
+    /// [`append_compressed_dname`].
+    ///
+    /// The default implementation follows the policy of
+    /// [`Rtype::allows_rdata_compression`]. Builders that implement name
+    /// compression may allow overriding it.
+    ///
+    /// [`append_compressed_dname`]: #method.append_compressed_dname
+    /// [`Rtype::allows_rdata_compression`]: ../iana/rtype/enum.Rtype.html#method.allows_rdata_compression
+    fn compress_rdata(&self, rtype: Rtype) -> bool {
+        rtype.allows_rdata_compression()
+    }
+
+    /// Appends record data, compressing names only if allowed.
+    ///
+    /// Whether names embedded in the record data are compressed is
+    /// determined by [`compress_rdata`] for the record type `rtype`.
+    ///
+    /// [`compress_rdata`]: #method.compress_rdata
+    fn append_rdata<D: Compose + ?Sized>(
+        &mut self,
+        rtype: Rtype,
+        data: &D,
+    ) -> Result<(), ShortBuf> {
+        if self.compress_rdata(rtype) {
+            data.compose(self)
+        } else {
+            data.compose(&mut Uncompressed(self))
+        }
+    }
+
     /// Prepends some appended data with its length as a `u16`.
     ///
     /// The method will append the data being added via the closure `op` to
@@ -532,6 +568,40 @@ pub trait OctetsBuilder: AsRef<[u8]> + AsMut<[u8]> + Sized {
     }
 }
 
+//------------ Uncompressed --------------------------------------------------
+
+/// An octets builder wrapper that never compresses names.
+struct Uncompressed<'a, Target>(&'a mut Target);
+
+impl<'a, Target: AsRef<[u8]>> AsRef<[u8]> for Uncompressed<'a, Target> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl<'a, Target: AsMut<[u8]>> AsMut<[u8]> for Uncompressed<'a, Target> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut()
+    }
+}
+
+impl<'a, Target: OctetsBuilder> OctetsBuilder for Uncompressed<'a, Target> {
+    type Octets = &'a [u8];
+
+    fn append_slice(&mut self, slice: &[u8]) -> Result<(), ShortBuf> {
+        self.0.append_slice(slice)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn freeze(self) -> Self::Octets {
+        let target: &'a Target = self.0;
+        target.as_ref()
+    }
+}
+
 #[cfg(feature = "std")]
 impl OctetsBuilder for Vec<u8> {
     type Octets = Self;
@@ -834,4 +834,26 @@ mod test {
         assert_eq!(Some(Ok(nsid)), opt.iter::<opt::Nsid<_>>().next());
         assert_eq!(Some(Ok(cookie)), opt.iter::<opt::Cookie>().next());
     }
+
+    #[test]
+    fn opt_rcode() {
+        let msg = {
+            let mut mb = MessageBuilder::new_vec().additional();
+            mb.opt(|mb| {
+                mb.set_rcode(OptRcode::BadVers);
+                Ok(())
+            })
+            .unwrap();
+            mb.into_message()
+        };
+        assert_eq!(msg.header().rcode(), crate::base::iana::Rcode::NoError);
+        assert_eq!(msg.opt_rcode(), OptRcode::BadVers);
+        assert_eq!(OptRcode::BadVers.to_parts(), (0.into(), 1));
+        assert_eq!(OptRcode::from_int(0x0AB3).ext(), 0xAB);
+        assert_eq!(OptRcode::from_int(0x0AB3).to_int(), 0x0AB3);
+        assert!(!OptRcode::BadCookie.is_basic());
+
+        let msg = MessageBuilder::new_vec().into_message();
+        assert_eq!(msg.opt_rcode(), OptRcode::NoError);
+    }
 }
@@ -1,7 +1,17 @@
 //! EDNS Options from RFC 7830
+//!
+//! Besides the padding option itself, this module provides the padding
+//! strategies of [RFC 8467] via [`PaddingPolicy`] and a way to add padding
+//! to an already composed message via [`pad_message`].
+//!
+//! [RFC 8467]: https://tools.ietf.org/html/rfc8467
+//! [`PaddingPolicy`]: enum.PaddingPolicy.html
+//! [`pad_message`]: fn.pad_message.html
 
+use core::convert::TryFrom;
 use rand::random;
-use super::super::iana::OptionCode;
+use super::super::iana::{OptionCode, Rtype};
+use super::super::message::Message;
 use super::super::message_builder::OptBuilder;
 use super::super::octets::{
     Compose, OctetsBuilder, Parse, ParseError, Parser, ShortBuf
@@ -96,3 +106,269 @@ impl CodeOptData for Padding {
     const CODE: OptionCode = OptionCode::Padding;
 }
 
+
+//------------ PaddingPolicy -------------------------------------------------
+
+/// A strategy for choosing the amount of padding added to a message.
+///
+/// The strategies are those described in [RFC 8467]. The RFC recommends
+/// block-length padding with a block size of 128 octets for queries and
+/// 468 octets for responses which are available as [`QUERY`] and
+/// [`RESPONSE`], respectively. The default policy is not to pad at all.
+///
+/// Padding only helps on encrypted transports and must not be used on
+/// unencrypted ones.
+///
+/// [RFC 8467]: https://tools.ietf.org/html/rfc8467
+/// [`QUERY`]: #associatedconstant.QUERY
+/// [`RESPONSE`]: #associatedconstant.RESPONSE
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PaddingPolicy {
+    /// Messages are not padded.
+    None,
+
+    /// Messages are padded to the next multiple of the block size.
+    ///
+    /// A block size of zero is treated as one, i.e., only an empty padding
+    /// option is added.
+    BlockLength(u16),
+
+    /// Messages are padded to the given size.
+    ///
+    /// Messages that already are at least this long only get an empty
+    /// padding option.
+    Maximal(u16),
+
+    /// Messages are padded by a random length up to the given value.
+    Random(u16),
+}
+
+impl PaddingPolicy {
+    /// The policy recommended by RFC 8467 for queries.
+    pub const QUERY: Self = PaddingPolicy::BlockLength(128);
+
+    /// The policy recommended by RFC 8467 for responses.
+    pub const RESPONSE: Self = PaddingPolicy::BlockLength(468);
+
+    /// Returns the length of the padding for a message of the given length.
+    ///
+    /// The length is that of the padding option’s data. The message length
+    /// is that before adding the padding option, the four octets of the
+    /// option header are taken into account. Returns `None` if the message
+    /// should not be padded.
+    pub fn padding_len(self, msg_len: usize) -> Option<u16> {
+        let len = msg_len.checked_add(4)?;
+        let padding = match self {
+            PaddingPolicy::None => return None,
+            PaddingPolicy::BlockLength(block) => {
+                let block = usize::from(block.max(1));
+                (block - len % block) % block
+            }
+            PaddingPolicy::Maximal(size) => {
+                usize::from(size).saturating_sub(len)
+            }
+            PaddingPolicy::Random(max) => {
+                (random::<u32>() % (u32::from(max) + 1)) as usize
+            }
+        };
+        u16::try_from(padding).ok()
+    }
+}
+
+
+//--- Default
+
+#[allow(clippy::derivable_impls)] // #[default] needs Rust 1.62.
+impl Default for PaddingPolicy {
+    fn default() -> Self {
+        PaddingPolicy::None
+    }
+}
+
+
+//------------ pad_message ---------------------------------------------------
+
+/// Adds a padding option to a composed message.
+///
+/// The amount of padding is determined by `policy`. The padding is only
+/// added if the padded message is at most `limit` octets long.
+///
+/// Since the OPT record is updated in place, it needs to be the last
+/// record of the message, i.e., the message must not be signed. A message
+/// that already contains a padding option is left alone.
+///
+/// Returns whether padding was added.
+pub fn pad_message<Target>(
+    target: &mut Target,
+    policy: PaddingPolicy,
+    limit: usize
+) -> bool
+where Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]> {
+    let start = match padding_opt_start(target.as_ref()) {
+        Some(start) => start,
+        None => return false
+    };
+    let len = target.as_ref().len();
+    let padding = match policy.padding_len(len) {
+        Some(padding) => padding,
+        None => return false
+    };
+    if len + 4 + usize::from(padding) > limit {
+        return false
+    }
+
+    // The record data length is after the root owner name, type, class,
+    // and TTL.
+    let rdlen_pos = start + 9;
+    let rdlen = u16::from_be_bytes([
+        target.as_ref()[rdlen_pos], target.as_ref()[rdlen_pos + 1]
+    ]);
+    let rdlen = match rdlen.checked_add(padding).and_then(|rdlen| {
+        rdlen.checked_add(4)
+    }) {
+        Some(rdlen) => rdlen,
+        None => return false
+    };
+    let res = target.append_all(|target| {
+        OptionCode::Padding.compose(target)?;
+        padding.compose(target)?;
+        Padding::new(padding, PaddingMode::Zero).compose(target)
+    });
+    if res.is_err() {
+        return false
+    }
+    target.as_mut()[rdlen_pos..rdlen_pos + 2].copy_from_slice(
+        &rdlen.to_be_bytes()
+    );
+    true
+}
+
+/// Returns the start of an OPT record that can receive padding.
+///
+/// This is the case if the last record of the message is an OPT record
+/// with the root as its owner that ends with the message and doesn’t
+/// contain a padding option yet.
+fn padding_opt_start(msg: &[u8]) -> Option<usize> {
+    let msg = Message::from_octets(msg).ok()?;
+    let mut section = msg.additional().ok()?;
+    let mut last = None;
+    loop {
+        let pos = section.pos();
+        match section.next() {
+            Some(Ok(record)) => last = Some((pos, record.rtype())),
+            Some(Err(_)) => return None,
+            None => break
+        }
+    }
+    let (start, rtype) = last?;
+    if rtype != Rtype::Opt || section.pos() != msg.as_slice().len()
+        || msg.as_slice()[start] != 0
+    {
+        return None
+    }
+    let opt = msg.opt()?;
+    if opt.iter::<Padding>().next().is_some() {
+        return None
+    }
+    Some(start)
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+    use core::str::FromStr;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+
+    fn query(qname: &str, opt: bool) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str(qname).unwrap(), Rtype::A
+        )).unwrap();
+        let mut msg = msg.additional();
+        if opt {
+            msg.opt(|_| Ok(())).unwrap();
+        }
+        msg.finish()
+    }
+
+    fn padding(msg: &[u8]) -> Option<u16> {
+        let msg = Message::from_octets(msg).unwrap();
+        msg.opt()?.iter::<Padding>().next().map(|opt| opt.unwrap().len())
+    }
+
+    #[test]
+    fn padding_len() {
+        assert_eq!(PaddingPolicy::None.padding_len(50), None);
+        assert_eq!(PaddingPolicy::QUERY.padding_len(50), Some(74));
+        assert_eq!(PaddingPolicy::QUERY.padding_len(124), Some(0));
+        assert_eq!(PaddingPolicy::QUERY.padding_len(125), Some(127));
+        assert_eq!(PaddingPolicy::RESPONSE.padding_len(500), Some(432));
+        assert_eq!(PaddingPolicy::BlockLength(0).padding_len(50), Some(0));
+        assert_eq!(PaddingPolicy::Maximal(100).padding_len(50), Some(46));
+        assert_eq!(PaddingPolicy::Maximal(100).padding_len(200), Some(0));
+        for _ in 0..100 {
+            assert!(PaddingPolicy::Random(10).padding_len(50).unwrap() <= 10);
+        }
+        assert_eq!(PaddingPolicy::Random(0).padding_len(50), Some(0));
+    }
+
+    #[test]
+    fn block_length_buckets() {
+        for &(qname, bucket) in &[
+            ("a.example", 128),
+            ("www.example.com", 128),
+            (
+                "a-rather-long-label-to-push-the-query-beyond-the-first\
+                 -bucket.and-another-one-because-one-label-is-not-quite\
+                 -enough.example.com", 256
+            ),
+        ] {
+            let mut msg = query(qname, true);
+            let orig_len = msg.len();
+            assert!(pad_message(&mut msg, PaddingPolicy::QUERY, 0xFFFF));
+            assert_eq!(msg.len(), bucket);
+            assert_eq!(
+                padding(&msg), Some((bucket - orig_len - 4) as u16)
+            );
+            let parsed = Message::from_octets(msg.as_slice()).unwrap();
+            assert_eq!(parsed.header_counts().arcount(), 1);
+            assert_eq!(parsed.additional().unwrap().count(), 1);
+
+            // Padding twice doesn’t do anything.
+            assert!(!pad_message(&mut msg, PaddingPolicy::QUERY, 0xFFFF));
+            assert_eq!(msg.len(), bucket);
+        }
+
+        let mut msg = query("www.example.com", true);
+        assert!(pad_message(&mut msg, PaddingPolicy::RESPONSE, 0xFFFF));
+        assert_eq!(msg.len(), 468);
+    }
+
+    #[test]
+    fn not_padded() {
+        // No OPT record.
+        let mut msg = query("www.example.com", false);
+        let len = msg.len();
+        assert!(!pad_message(&mut msg, PaddingPolicy::QUERY, 0xFFFF));
+        assert_eq!(msg.len(), len);
+
+        // Padding would exceed the limit.
+        let mut msg = query("www.example.com", true);
+        let len = msg.len();
+        assert!(!pad_message(&mut msg, PaddingPolicy::QUERY, 127));
+        assert_eq!(msg.len(), len);
+        assert!(pad_message(&mut msg, PaddingPolicy::QUERY, 128));
+        assert_eq!(msg.len(), 128);
+
+        // No padding policy.
+        let mut msg = query("www.example.com", true);
+        assert!(!pad_message(&mut msg, PaddingPolicy::None, 0xFFFF));
+        assert_eq!(msg.len(), len);
+    }
+}
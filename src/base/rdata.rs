@@ -25,8 +25,8 @@
 use super::cmp::CanonicalOrd;
 use super::iana::Rtype;
 use super::octets::{
-    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
-    ShortBuf,
+    Compose, EmptyBuilder, FromBuilder, OctetsBuilder, OctetsFrom, OctetsRef,
+    Parse, ParseError, Parser, ShortBuf,
 };
 #[cfg(feature = "master")]
 use crate::master::scan::{
@@ -54,6 +54,31 @@ pub trait RecordData: Compose + Sized {
     /// This is a method rather than an associated function to allow one
     /// type to be used for several real record types.
     fn rtype(&self) -> Rtype;
+
+    /// Returns the record data in canonical wire format.
+    ///
+    /// The canonical form is used when signing and validating records. It
+    /// is the uncompressed wire format with all domain names embedded in
+    /// the data of the record types listed in [RFC 4034, section 6.2], as
+    /// amended by [RFC 6840, section 5.1], converted to lowercase. Names in
+    /// the data of all other record types, including NSEC, are left as
+    /// they are.
+    ///
+    /// The method relies on the record data’s implementation of
+    /// [`Compose::compose_canonical`].
+    ///
+    /// [RFC 4034, section 6.2]: https://tools.ietf.org/html/rfc4034#section-6.2
+    /// [RFC 6840, section 5.1]: https://tools.ietf.org/html/rfc6840#section-5.1
+    /// [`Compose::compose_canonical`]: ../octets/trait.Compose.html#method.compose_canonical
+    fn canonicalize<Octets>(&self) -> Result<Octets, ShortBuf>
+    where
+        Octets: FromBuilder,
+        <Octets as FromBuilder>::Builder: EmptyBuilder,
+    {
+        let mut builder = Octets::Builder::empty();
+        self.compose_canonical(&mut builder)?;
+        Ok(builder.freeze())
+    }
 }
 
 //------------ ParseRecordData -----------------------------------------------
@@ -189,7 +214,15 @@ impl UnknownRecordData<Bytes> {
         rtype: Rtype,
         scanner: &mut Scanner<C>,
     ) -> Result<Self, ScanError> {
-        scanner.skip_literal("\\#")?;
+        scanner.skip_generic_marker()?;
+        Self::scan_data(rtype, scanner)
+    }
+
+    /// Scans the record data following the `\#` marker.
+    pub(crate) fn scan_data<C: CharSource>(
+        rtype: Rtype,
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
         let mut len = u16::scan(scanner)? as usize;
         let mut res = BytesMut::with_capacity(len);
         while len > 0 {
@@ -200,7 +233,7 @@ impl UnknownRecordData<Bytes> {
                         return Err(SyntaxError::LongGenericData);
                     }
                     let ch = symbol.into_digit(16)? as u8;
-                    if let Some(ch1) = *first {
+                    if let Some(ch1) = first.take() {
                         res.put_u8(ch1 << 4 | ch);
                         *len -= 1;
                     } else {
@@ -247,7 +280,8 @@ where
     Other: AsRef<[u8]>,
 {
     fn eq(&self, other: &UnknownRecordData<Other>) -> bool {
-        self.data.as_ref().eq(other.data.as_ref())
+        self.rtype == other.rtype
+            && self.data.as_ref().eq(other.data.as_ref())
     }
 }
 
@@ -265,6 +299,10 @@ where
         &self,
         other: &UnknownRecordData<Other>,
     ) -> Option<Ordering> {
+        match self.rtype.partial_cmp(&other.rtype) {
+            Some(Ordering::Equal) => {}
+            res => return res,
+        }
         self.data.as_ref().partial_cmp(other.data.as_ref())
     }
 }
@@ -276,12 +314,20 @@ where
     Other: AsRef<[u8]>,
 {
     fn canonical_cmp(&self, other: &UnknownRecordData<Other>) -> Ordering {
+        match self.rtype.cmp(&other.rtype) {
+            Ordering::Equal => {}
+            res => return res,
+        }
         self.data.as_ref().cmp(other.data.as_ref())
     }
 }
 
 impl<Octets: AsRef<[u8]>> Ord for UnknownRecordData<Octets> {
     fn cmp(&self, other: &Self) -> Ordering {
+        match self.rtype.cmp(&other.rtype) {
+            Ordering::Equal => {}
+            res => return res,
+        }
         self.data.as_ref().cmp(other.data.as_ref())
     }
 }
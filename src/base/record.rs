@@ -131,6 +131,11 @@ impl<Name, Data> Record<Name, Data> {
         &self.owner
     }
 
+    /// Sets the record’s owner domain name.
+    pub fn set_owner(&mut self, owner: Name) {
+        self.owner = owner
+    }
+
     /// Returns the record type.
     pub fn rtype(&self) -> Rtype
     where
@@ -337,7 +342,9 @@ impl<N: ToDname, D: RecordData> Compose for Record<N, D> {
             self.data.rtype().compose(target)?;
             self.class.compose(target)?;
             self.ttl.compose(target)?;
-            target.u16_len_prefixed(|target| self.data.compose(target))
+            target.u16_len_prefixed(|target| {
+                target.append_rdata(self.data.rtype(), &self.data)
+            })
         })
     }
 
@@ -437,7 +444,9 @@ pub trait AsRecord {
             self.data().rtype().compose(target)?;
             self.class().compose(target)?;
             self.ttl().compose(target)?;
-            target.u16_len_prefixed(|target| self.data().compose(target))
+            target.u16_len_prefixed(|target| {
+                target.append_rdata(self.data().rtype(), self.data())
+            })
         })
     }
 
@@ -0,0 +1,506 @@
+//! Building responses to DNS requests.
+//!
+//! This module provides the [`ResponseBuilder`], a helper for servers that
+//! assembles a response from the records for each section and takes care
+//! of the tedious parts of getting a response right: it copies the
+//! relevant parts of the request, includes address records for the names
+//! referred to by NS, MX, and SRV records, honours the size limit of the
+//! transport, and sets the TC bit if the response doesn’t fit.
+//!
+//! [`ResponseBuilder`]: struct.ResponseBuilder.html
+#![cfg(feature = "std")]
+
+use super::header::Header;
+use super::iana::{Rcode, Rtype};
+use super::message::Message;
+use super::message_builder::{
+    AdditionalBuilder, MessageBuilder, TreeCompressor,
+};
+use super::name::{Dname, ToDname};
+use super::octets::{OctetsRef, ShortBuf};
+use super::question::Question;
+use super::rdata::RecordData;
+use super::record::Record;
+use crate::rdata::{Mx, Ns, Srv};
+use std::vec::Vec;
+
+//------------ Constants -----------------------------------------------------
+
+/// The maximum size of a response over UDP without EDNS.
+const MIN_UDP_SIZE: u16 = 512;
+
+/// The UDP payload size we advertise by default.
+///
+/// This is the value recommended by the DNS Flag Day 2020.
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// The size of an OPT record without any options.
+const OPT_SIZE: usize = 11;
+
+//------------ ResponseBuilder -----------------------------------------------
+
+/// A builder for the response to a request.
+///
+/// The builder is created from the request via [`new`]. It copies the
+/// message ID, opcode, and the RD and CD flags from the request and sets
+/// the QR flag. The question section of the request is repeated in the
+/// response. Other header fields such as the AA flag or the response code
+/// can be changed via [`header_mut`] and [`set_rcode`].
+///
+/// Records are added to the answer, authority, and additional sections via
+/// [`push_answer`], [`push_authority`], and [`push_additional`]. In
+/// addition, address records can be given to the builder via
+/// [`push_address`]. These are only included in the additional section if
+/// their owner is the target of an NS, MX, or SRV record in the answer or
+/// authority sections.
+///
+/// With [`set_minimal`], the builder produces minimal responses: the
+/// authority section is left out of positive answers and addresses are
+/// only included as glue for referrals, i.e., if the answer section is
+/// empty.
+///
+/// The size of the response is limited to what the requester can receive.
+/// By default, this is 512 octets or, if the request contains an OPT
+/// record, the smaller of the UDP payload size advertised by the requester
+/// and our own payload size set via [`set_udp_payload_size`]. For stream
+/// transports, a larger limit can be set via [`set_max_size`]. If the
+/// records of the answer or authority sections don’t fit, the response
+/// will only contain the question and have its TC flag set so the
+/// requester can retry via TCP. Records of the additional section that
+/// don’t fit are silently dropped.
+///
+/// [`new`]: #method.new
+/// [`header_mut`]: #method.header_mut
+/// [`set_rcode`]: #method.set_rcode
+/// [`push_answer`]: #method.push_answer
+/// [`push_authority`]: #method.push_authority
+/// [`push_additional`]: #method.push_additional
+/// [`push_address`]: #method.push_address
+/// [`set_minimal`]: #method.set_minimal
+/// [`set_udp_payload_size`]: #method.set_udp_payload_size
+/// [`set_max_size`]: #method.set_max_size
+#[derive(Clone, Debug)]
+pub struct ResponseBuilder<N, D> {
+    /// The header of the response.
+    header: Header,
+
+    /// The questions of the request.
+    question: Vec<Question<Dname<Vec<u8>>>>,
+
+    /// The EDNS information of the request if it had an OPT record.
+    edns: Option<RequestEdns>,
+
+    /// The UDP payload size we advertise.
+    udp_payload_size: u16,
+
+    /// The maximum size of the response if explicitly set.
+    max_size: Option<usize>,
+
+    /// Whether to produce minimal responses.
+    minimal: bool,
+
+    /// The records for the answer section.
+    answer: Vec<Record<N, D>>,
+
+    /// The records for the authority section.
+    authority: Vec<Record<N, D>>,
+
+    /// The records for the additional section.
+    additional: Vec<Record<N, D>>,
+
+    /// Address records that may be added to the additional section.
+    addresses: Vec<Record<N, D>>,
+}
+
+/// The EDNS information of a request.
+#[derive(Clone, Copy, Debug)]
+struct RequestEdns {
+    /// The UDP payload size advertised by the requester.
+    udp_payload_size: u16,
+
+    /// Whether the requester set the DO bit.
+    dnssec_ok: bool,
+}
+
+impl<N: ToDname, D: RecordData> ResponseBuilder<N, D> {
+    /// Creates a new response builder for the given request.
+    ///
+    /// Questions of the request that fail to parse are skipped.
+    pub fn new<Octets>(request: &Message<Octets>) -> Self
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let mut header = Header::new();
+        header.set_id(request.header().id());
+        header.set_qr(true);
+        header.set_opcode(request.header().opcode());
+        header.set_rd(request.header().rd());
+        header.set_cd(request.header().cd());
+        ResponseBuilder {
+            header,
+            question: request
+                .question()
+                .filter_map(Result::ok)
+                .map(|question| {
+                    Question::new(
+                        question.qname().to_vec(),
+                        question.qtype(),
+                        question.qclass(),
+                    )
+                })
+                .collect(),
+            edns: request.opt().map(|opt| RequestEdns {
+                udp_payload_size: opt.udp_payload_size(),
+                dnssec_ok: opt.dnssec_ok(),
+            }),
+            udp_payload_size: DEFAULT_UDP_PAYLOAD_SIZE,
+            max_size: None,
+            minimal: false,
+            answer: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+            addresses: Vec::new(),
+        }
+    }
+
+    /// Returns the header of the response.
+    pub fn header(&self) -> Header {
+        self.header
+    }
+
+    /// Returns a mutable reference to the header of the response.
+    ///
+    /// The TC flag will be overwritten when the response is finished.
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    /// Sets the response code.
+    pub fn set_rcode(&mut self, rcode: Rcode) {
+        self.header.set_rcode(rcode)
+    }
+
+    /// Sets whether the builder produces minimal responses.
+    pub fn set_minimal(&mut self, minimal: bool) {
+        self.minimal = minimal
+    }
+
+    /// Sets the UDP payload size advertised in the response.
+    ///
+    /// This is only relevant if the request contained an OPT record.
+    pub fn set_udp_payload_size(&mut self, size: u16) {
+        self.udp_payload_size = size
+    }
+
+    /// Sets the maximum size of the response.
+    ///
+    /// This overrides the size limit derived from the request. It should
+    /// be used for responses sent over stream transports.
+    pub fn set_max_size(&mut self, size: usize) {
+        self.max_size = Some(size)
+    }
+
+    /// Returns the maximum size of the response.
+    pub fn max_size(&self) -> usize {
+        if let Some(size) = self.max_size {
+            return size;
+        }
+        match self.edns {
+            Some(edns) => usize::from(
+                edns.udp_payload_size
+                    .min(self.udp_payload_size)
+                    .max(MIN_UDP_SIZE),
+            ),
+            None => usize::from(MIN_UDP_SIZE),
+        }
+    }
+
+    /// Adds a record to the answer section.
+    pub fn push_answer(&mut self, record: Record<N, D>) {
+        self.answer.push(record)
+    }
+
+    /// Adds a record to the authority section.
+    pub fn push_authority(&mut self, record: Record<N, D>) {
+        self.authority.push(record)
+    }
+
+    /// Adds a record to the additional section.
+    ///
+    /// The record is always included if there is space left.
+    pub fn push_additional(&mut self, record: Record<N, D>) {
+        self.additional.push(record)
+    }
+
+    /// Adds an address record that may be included automatically.
+    ///
+    /// The record is only added to the additional section if its owner is
+    /// the target of an NS, MX, or SRV record in the answer or authority
+    /// sections of the response and, for minimal responses, only if the
+    /// response is a referral. Records other than A and AAAA records are
+    /// ignored.
+    pub fn push_address(&mut self, record: Record<N, D>) {
+        self.addresses.push(record)
+    }
+
+    /// Finishes the response and returns it.
+    ///
+    /// The method only fails if not even the question fits into the
+    /// maximum size of the response.
+    pub fn finish(self) -> Result<Message<Vec<u8>>, ShortBuf> {
+        let max_size = self.max_size();
+        let limit = if self.edns.is_some() {
+            max_size.saturating_sub(OPT_SIZE)
+        } else {
+            max_size
+        };
+
+        let mut builder =
+            MessageBuilder::from_target(TreeCompressor::new(Vec::new()))?;
+        *builder.header_mut() = self.header;
+        builder.header_mut().set_tc(false);
+        builder.set_push_limit(limit);
+
+        let mut builder = builder.question();
+        for question in &self.question {
+            builder.push(question)?;
+        }
+
+        let mut builder = builder.answer();
+        let mut truncated = false;
+        for record in &self.answer {
+            if builder.push(record).is_err() {
+                truncated = true;
+                break;
+            }
+        }
+
+        let mut builder = builder.authority();
+        if !truncated && !self.omit_authority() {
+            for record in &self.authority {
+                if builder.push(record).is_err() {
+                    truncated = true;
+                    break;
+                }
+            }
+        }
+
+        let mut builder = if truncated {
+            let mut builder = builder.question();
+            builder.header_mut().set_tc(true);
+            builder.additional()
+        } else {
+            let mut builder = builder.additional();
+            for record in &self.additional {
+                if builder.push(record).is_err() {
+                    break;
+                }
+            }
+            self.push_addresses(&mut builder);
+            builder
+        };
+
+        if let Some(edns) = self.edns {
+            builder.set_push_limit(max_size);
+            builder.opt(|opt| {
+                opt.set_udp_payload_size(self.udp_payload_size);
+                opt.set_dnssec_ok(edns.dnssec_ok);
+                Ok(())
+            })?;
+        }
+
+        Ok(builder.into_message())
+    }
+
+    /// Returns whether the authority section should be left out.
+    fn omit_authority(&self) -> bool {
+        self.minimal
+            && !self.answer.is_empty()
+            && self.header.rcode() == Rcode::NoError
+    }
+
+    /// Adds the address records for the targets in the response.
+    fn push_addresses(
+        &self,
+        builder: &mut AdditionalBuilder<TreeCompressor<Vec<u8>>>,
+    ) {
+        if self.addresses.is_empty()
+            || (self.minimal && builder.counts().ancount() > 0)
+        {
+            return;
+        }
+        let targets = additional_targets(builder.as_message());
+        for record in &self.addresses {
+            if record.rtype() != Rtype::A && record.rtype() != Rtype::Aaaa {
+                continue;
+            }
+            if !targets.iter().any(|name| name.name_eq(record.owner())) {
+                continue;
+            }
+            if builder.push(record).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the names referred to by the answer and authority sections.
+///
+/// These are the targets of NS, MX, and SRV records.
+fn additional_targets(msg: Message<&[u8]>) -> Vec<Dname<Vec<u8>>> {
+    let mut res = Vec::new();
+    let mut section = msg.answer().ok();
+    for _ in 0..2 {
+        let records = match section {
+            Some(records) => records,
+            None => break,
+        };
+        for record in records.flatten() {
+            if let Ok(Some(record)) = record.to_record::<Ns<_>>() {
+                res.push(record.data().nsdname().to_vec());
+            } else if let Ok(Some(record)) = record.to_record::<Mx<_>>() {
+                res.push(record.data().exchange().to_vec());
+            } else if let Ok(Some(record)) = record.to_record::<Srv<_>>() {
+                res.push(record.data().target().to_vec());
+            }
+        }
+        section = records.next_section().ok().and_then(|section| section);
+    }
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::{AllRecordData, Soa, A};
+    use crate::utils::testing::{name, record};
+    use core::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+    type Data = AllRecordData<Vec<u8>, Name>;
+
+    fn request(qname: &str, qtype: Rtype, edns: bool) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(4711);
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((name::<Vec<u8>>(qname), qtype)).unwrap();
+        let mut msg = msg.additional();
+        if edns {
+            msg.opt(|opt| {
+                opt.set_udp_payload_size(4096);
+                opt.set_dnssec_ok(true);
+                Ok(())
+            })
+            .unwrap();
+        }
+        msg.into_message()
+    }
+
+    fn ns(target: &str) -> Data {
+        Ns::new(name::<Vec<u8>>(target)).into()
+    }
+
+    fn a(addr: &str) -> Data {
+        A::from_str(addr).unwrap().into()
+    }
+
+    #[test]
+    fn referral_with_glue() {
+        let req = request("www.sub.example.com", Rtype::A, false);
+        let mut resp = ResponseBuilder::<Name, Data>::new(&req);
+        resp.set_minimal(true);
+        resp.push_authority(record(
+            "sub.example.com",
+            3600,
+            ns("ns.sub.example.com"),
+        ));
+        resp.push_address(record("ns.sub.example.com", 3600, a("192.0.2.1")));
+        resp.push_address(record(
+            "ns.other.example.com",
+            3600,
+            a("192.0.2.2"),
+        ));
+        let resp = resp.finish().unwrap();
+
+        assert_eq!(resp.header().id(), 4711);
+        assert!(resp.header().qr());
+        assert!(resp.header().rd());
+        assert!(!resp.header().tc());
+        let counts = resp.header_counts();
+        assert_eq!(counts.qdcount(), 1);
+        assert_eq!(counts.ancount(), 0);
+        assert_eq!(counts.nscount(), 1);
+        assert_eq!(counts.arcount(), 1);
+    }
+
+    #[test]
+    fn minimal_positive_answer() {
+        let req = request("example.com", Rtype::Ns, true);
+        let mut resp = ResponseBuilder::<Name, Data>::new(&req);
+        resp.push_answer(record("example.com", 3600, ns("ns.example.com")));
+        resp.push_authority(record(
+            "example.com",
+            3600,
+            ns("ns.example.com"),
+        ));
+        resp.push_address(record("ns.example.com", 3600, a("192.0.2.1")));
+
+        let mut minimal = resp.clone();
+        minimal.set_minimal(true);
+        let minimal = minimal.finish().unwrap();
+        assert_eq!(minimal.header_counts().nscount(), 0);
+        // Only the OPT record.
+        assert_eq!(minimal.header_counts().arcount(), 1);
+        assert!(minimal.opt().unwrap().dnssec_ok());
+
+        let full = resp.finish().unwrap();
+        assert_eq!(full.header_counts().nscount(), 1);
+        assert_eq!(full.header_counts().arcount(), 2);
+    }
+
+    #[test]
+    fn truncation() {
+        let req = request("example.com", Rtype::A, false);
+        let mut resp = ResponseBuilder::<Name, Data>::new(&req);
+        for i in 0..40u8 {
+            resp.push_answer(record(
+                "example.com",
+                3600,
+                A::from_octets(192, 0, 2, i).into(),
+            ));
+        }
+        resp.push_authority(record(
+            "example.com",
+            3600,
+            Soa::new(
+                name("ns.example.com"),
+                name("hostmaster.example.com"),
+                1.into(),
+                3600,
+                600,
+                86400,
+                300,
+            )
+            .into(),
+        ));
+        let mut tcp = resp.clone();
+        let resp = resp.finish().unwrap();
+        assert!(resp.header().tc());
+        assert!(resp.as_slice().len() <= 512);
+        assert_eq!(resp.header_counts().qdcount(), 1);
+        assert_eq!(resp.header_counts().ancount(), 0);
+        assert_eq!(resp.header_counts().nscount(), 0);
+
+        tcp.set_max_size(usize::from(u16::max_value()));
+        let tcp = tcp.finish().unwrap();
+        assert!(!tcp.header().tc());
+        assert_eq!(tcp.header_counts().ancount(), 40);
+        assert_eq!(tcp.header_counts().nscount(), 1);
+    }
+}
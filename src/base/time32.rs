@@ -0,0 +1,447 @@
+//! Points in time expressed in 32 bits.
+//!
+//! Some DNS data, most notably the inception and expiration times of RRSIG
+//! records, contains points in time as a 32 bit number of seconds since the
+//! Unix epoch. Because these values wrap around every 136 years, they have
+//! to be compared using the serial number arithmetic of [RFC 1982]. This
+//! module provides the type [`Time32`] that implements these rules and can
+//! be converted from and into more common representations of time.
+//!
+//! [RFC 1982]: https://tools.ietf.org/html/rfc1982
+//! [`Time32`]: struct.Time32.html
+
+use super::cmp::CanonicalOrd;
+use super::octets::{
+    Compose, OctetsBuilder, Parse, ParseError, Parser, ShortBuf,
+};
+use super::serial::Serial;
+#[cfg(feature = "master")]
+use crate::master::scan::{
+    CharSource, Scan, ScanError, Scanner, SyntaxError,
+};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, TimeZone, Utc};
+use core::{cmp, fmt, str};
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+//------------ Time32 --------------------------------------------------------
+
+/// A point in time as a 32 bit number of seconds since the Unix epoch.
+///
+/// The type is used for the inception and expiration times of RRSIG
+/// records as defined in [RFC 4034]. Since the value wraps around, it only
+/// implements a partial ordering following the serial number arithmetic of
+/// RFC 1982: a value is considered to be before another if it is less than
+/// 2^31 seconds before it, modulo 2^32. For the same reason, conversions
+/// into a full point in time need a reference time and pick the point in
+/// time closest to it.
+///
+/// In the presentation format, the time is given in UTC either in the form
+/// `YYYYMMDDHHmmSS` or as the decimal number of seconds. The [`Display`]
+/// implementation uses the former while the [`FromStr`] implementation
+/// accepts both.
+///
+/// [RFC 4034]: https://tools.ietf.org/html/rfc4034
+/// [`Display`]: #impl-Display
+/// [`FromStr`]: #impl-FromStr
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Time32(u32);
+
+impl Time32 {
+    /// Creates a value from the raw number of seconds.
+    pub fn from_int(secs: u32) -> Self {
+        Time32(secs)
+    }
+
+    /// Returns the current time.
+    #[cfg(feature = "std")]
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// Returns the raw number of seconds.
+    pub fn into_int(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the time `secs` seconds after `self`.
+    ///
+    /// As with serial numbers, only up to `2^31 - 1` seconds can be added.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `secs` is greater than `2^31 - 1`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, secs: u32) -> Self {
+        assert!(secs <= 0x7FFF_FFFF);
+        Time32(self.0.wrapping_add(secs))
+    }
+
+    /// Returns the time `secs` seconds before `self`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `secs` is greater than `2^31 - 1`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn sub(self, secs: u32) -> Self {
+        assert!(secs <= 0x7FFF_FFFF);
+        Time32(self.0.wrapping_sub(secs))
+    }
+
+    /// Returns the number of seconds from `earlier` to `self`.
+    ///
+    /// Returns `None` if `earlier` isn’t actually before or equal to
+    /// `self`.
+    pub fn secs_since(self, earlier: Time32) -> Option<u32> {
+        match earlier.partial_cmp(&self) {
+            Some(cmp::Ordering::Less) | Some(cmp::Ordering::Equal) => {
+                Some(self.0.wrapping_sub(earlier.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the number of seconds since the epoch closest to `reference`.
+    ///
+    /// Of all the points in time represented by `self`, the one closest to
+    /// `reference` is chosen. The result is never before the epoch.
+    #[cfg(any(feature = "std", feature = "chrono"))]
+    fn resolve(self, reference: i64) -> i64 {
+        let diff = i64::from(self.0.wrapping_sub(reference as u32) as i32);
+        cmp::max(reference + diff, 0)
+    }
+
+    /// Converts the value into a system time.
+    ///
+    /// Since the value only expresses a point in time modulo 2^32 seconds,
+    /// the time closest to `reference`, normally the current time, is
+    /// returned.
+    #[cfg(feature = "std")]
+    pub fn to_system_time(self, reference: SystemTime) -> SystemTime {
+        let reference = match reference.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(_) => 0,
+        };
+        UNIX_EPOCH + Duration::from_secs(self.resolve(reference) as u64)
+    }
+
+    /// Converts the value into a date and time.
+    ///
+    /// Like with [`to_system_time`], the point in time closest to
+    /// `reference` is returned.
+    ///
+    /// [`to_system_time`]: #method.to_system_time
+    #[cfg(feature = "chrono")]
+    pub fn to_datetime(self, reference: DateTime<Utc>) -> DateTime<Utc> {
+        let secs = self.resolve(cmp::max(reference.timestamp(), 0));
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+}
+
+//--- From and FromStr
+
+impl From<u32> for Time32 {
+    fn from(secs: u32) -> Self {
+        Time32(secs)
+    }
+}
+
+impl From<Time32> for u32 {
+    fn from(time: Time32) -> u32 {
+        time.0
+    }
+}
+
+impl From<Time32> for Serial {
+    fn from(time: Time32) -> Serial {
+        Serial(time.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<SystemTime> for Time32 {
+    /// Converts a system time into its 32 bit value.
+    ///
+    /// Times before the epoch are converted into the epoch.
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => Time32(duration.as_secs() as u32),
+            Err(_) => Time32(0),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<T: TimeZone> From<DateTime<T>> for Time32 {
+    fn from(time: DateTime<T>) -> Self {
+        Time32(cmp::max(time.timestamp(), 0) as u32)
+    }
+}
+
+impl str::FromStr for Time32 {
+    type Err = Time32Error;
+
+    /// Parses a time from its presentation format.
+    ///
+    /// The string can either be in the form `YYYYMMDDHHmmSS` or contain
+    /// the decimal number of seconds. Since the former is ambiguous with a
+    /// number of seconds, the latter must not be longer than 10 digits as
+    /// required by RFC 4034.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|ch| ch.is_ascii_digit()) {
+            return Err(Time32Error(()));
+        }
+        if s.len() <= 10 {
+            return s.parse().map(Time32).map_err(|_| Time32Error(()));
+        }
+        if s.len() != 14 {
+            return Err(Time32Error(()));
+        }
+        let num = |start: usize, end: usize| -> u32 {
+            s[start..end].parse().unwrap()
+        };
+        let (year, month, day) = (num(0, 4), num(4, 6), num(6, 8));
+        let (hour, minute, second) = (num(8, 10), num(10, 12), num(12, 14));
+        if year < 1970
+            || !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return Err(Time32Error(()));
+        }
+        let secs = days_from_civil(year, month, day) * 86400
+            + u64::from(hour * 3600 + minute * 60 + second);
+
+        // Times after 2106 wrap around.
+        Ok(Time32(secs as u32))
+    }
+}
+
+//--- PartialOrd and CanonicalOrd
+
+impl cmp::PartialOrd for Time32 {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Serial(self.0).partial_cmp(&Serial(other.0))
+    }
+}
+
+impl CanonicalOrd for Time32 {
+    fn canonical_cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+//--- Parse and Compose
+
+impl<T: AsRef<[u8]>> Parse<T> for Time32 {
+    fn parse(parser: &mut Parser<T>) -> Result<Self, ParseError> {
+        u32::parse(parser).map(Time32)
+    }
+
+    fn skip(parser: &mut Parser<T>) -> Result<(), ParseError> {
+        u32::skip(parser)
+    }
+}
+
+impl Compose for Time32 {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        self.0.compose(target)
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Time32 {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        scanner.scan_string_phrase(|res| {
+            str::FromStr::from_str(&res).map_err(SyntaxError::content)
+        })
+    }
+}
+
+impl fmt::Display for Time32 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.0 / 86400);
+        let secs = self.0 % 86400;
+        write!(
+            f,
+            "{:04}{:02}{:02}{:02}{:02}{:02}",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ Time32Error ---------------------------------------------------
+
+/// A string didn’t contain a valid time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Time32Error(());
+
+impl fmt::Display for Time32Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid time value")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Time32Error {}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts days since the Unix epoch into year, month, and day.
+///
+/// This is the algorithm described by Howard Hinnant in
+/// <http://howardhinnant.github.io/date_algorithms.html>, simplified for
+/// days after the epoch.
+pub(crate) fn civil_from_days(days: u32) -> (u32, u32, u32) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts year, month, and day into days since the Unix epoch.
+///
+/// This is the inverse of [`civil_from_days`], again simplified for dates
+/// after the epoch.
+///
+/// [`civil_from_days`]: fn.civil_from_days.html
+fn days_from_civil(year: u32, month: u32, day: u32) -> u64 {
+    let year = u64::from(if month <= 2 { year - 1 } else { year });
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = u64::from(if month > 2 { month - 3 } else { month + 9 });
+    let doy = (153 * mp + 2) / 5 + u64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Returns the number of days in the given month.
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 => {
+            (days_from_civil(year, 3, 1) - days_from_civil(year, 2, 1)) as u32
+        }
+        _ => 31,
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "std")]
+    use core::str::FromStr;
+    #[cfg(feature = "std")]
+    use std::string::ToString;
+
+    #[test]
+    fn calendar() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(18628), (2021, 1, 1));
+        assert_eq!(days_from_civil(2000, 2, 29), 11016);
+        assert_eq!(days_from_civil(2021, 1, 1), 18628);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn presentation() {
+        let time = Time32(1_600_000_000);
+        assert_eq!(time.to_string(), "20200913122640");
+        assert_eq!(Time32::from_str("20200913122640"), Ok(time));
+        assert_eq!(Time32::from_str("1600000000"), Ok(time));
+        assert_eq!(Time32::from_str("19700101000000"), Ok(Time32(0)));
+        assert_eq!(Time32::from_str("20000229000000"), Ok(Time32(951782400)));
+        assert_eq!(Time32(u32::MAX).to_string(), "21060207062815");
+        assert_eq!(
+            Time32::from_str("21060207062816"),
+            Ok(Time32(0)),
+            "times after 2106 wrap around"
+        );
+
+        for bad in &[
+            "",
+            "4294967296",
+            "20200913122",
+            "2020091312264",
+            "20201313122640",
+            "20210229000000",
+            "20200431000000",
+            "20200913242640",
+            "19691231235959",
+            "2020-09-13",
+        ] {
+            assert!(Time32::from_str(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn comparison() {
+        assert!(Time32(10) < Time32(20));
+        assert!(Time32(u32::MAX - 10) < Time32(10));
+        assert_eq!(Time32(1).partial_cmp(&Time32(0x8000_0001)), None);
+        assert_eq!(Time32(20).secs_since(Time32(10)), Some(10));
+        assert_eq!(Time32(5).secs_since(Time32(u32::MAX)), Some(6));
+        assert_eq!(Time32(10).secs_since(Time32(20)), None);
+        assert_eq!(Time32(u32::MAX).add(1), Time32(0));
+        assert_eq!(Time32(0).sub(1), Time32(u32::MAX));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn system_time() {
+        let secs = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(Time32::from(secs(1_600_000_000)), Time32(1_600_000_000));
+        assert_eq!(
+            Time32(1_600_000_000).to_system_time(secs(1_500_000_000)),
+            secs(1_600_000_000)
+        );
+
+        // After the wrap-around in 2106, small values are in the future.
+        assert_eq!(
+            Time32(100).to_system_time(secs(0xFFFF_FF00)),
+            secs(0x1_0000_0064)
+        );
+        assert_eq!(
+            Time32(0xFFFF_FF00).to_system_time(secs(0x1_0000_0064)),
+            secs(0xFFFF_FF00)
+        );
+
+        // Times never end up before the epoch.
+        assert_eq!(Time32(0xFFFF_FF00).to_system_time(secs(10)), secs(0));
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn datetime() {
+        let date = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        assert_eq!(Time32::from(date), Time32(1_600_000_000));
+        assert_eq!(Time32(1_600_000_000).to_datetime(Utc::now()), date);
+    }
+}
@@ -0,0 +1,760 @@
+//! Generating query load against a server.
+//!
+//! This module helps with load-testing DNS servers, for instance those
+//! built with the [server] module. The function [`run_load`] sends queries
+//! to a server at a fixed rate for a given time and collects the outcome
+//! of each of them into a [`LoadReport`] which provides counters as well
+//! as latency percentiles.
+//!
+//! What is sent and how is described by a [`LoadConfig`]. Queries go out
+//! over UDP or TCP and are spread over a number of sockets or connections.
+//! Over TCP, queries are pipelined, i.e., a query doesn’t wait for the
+//! answer to the previous one. The query names are produced by a
+//! [`QnamePattern`] which can, for example, generate names below a zone
+//! that are unlikely to be cached.
+//!
+//! Queries are sent on schedule regardless of whether answers arrive. A
+//! query counts as timed out if there is no answer within the configured
+//! timeout. If the generator itself falls behind – because the machine is
+//! too slow or writing to a TCP connection blocks – queries are sent as
+//! fast as possible until it has caught up. Comparing the achieved rate
+//! given by [`LoadReport::qps`] with the configured one shows whether
+//! this happened.
+//!
+//! This module requires the `bench` feature.
+//!
+//! [server]: ../server/index.html
+//! [`run_load`]: fn.run_load.html
+//! [`LoadConfig`]: struct.LoadConfig.html
+//! [`LoadReport`]: struct.LoadReport.html
+//! [`LoadReport::qps`]: struct.LoadReport.html#method.qps
+//! [`QnamePattern`]: enum.QnamePattern.html
+#![cfg(feature = "bench")]
+
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, DnameBuilder};
+use crate::resolv::stub::conf::Transport;
+use bytes::Bytes;
+use futures::future::{join_all, pending};
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::vec::Vec;
+use std::{fmt, io};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, sleep_until, Instant};
+
+//------------ LoadConfig ----------------------------------------------------
+
+/// The configuration of a load test.
+#[derive(Clone, Debug)]
+pub struct LoadConfig {
+    /// The address of the server to send queries to.
+    pub server: SocketAddr,
+
+    /// The transport to use.
+    pub transport: Transport,
+
+    /// The pattern for the query names.
+    pub qnames: QnamePattern,
+
+    /// The record type to ask for.
+    ///
+    /// Defaults to A.
+    pub qtype: Rtype,
+
+    /// The number of queries to send per second.
+    ///
+    /// Must not be zero. Defaults to 100.
+    pub qps: u32,
+
+    /// How long to send queries for.
+    ///
+    /// Defaults to ten seconds.
+    pub duration: Duration,
+
+    /// How long to wait for an answer.
+    ///
+    /// Defaults to two seconds.
+    pub timeout: Duration,
+
+    /// The number of UDP sockets or TCP connections to use.
+    ///
+    /// Queries are distributed evenly over all of them. Must not be zero.
+    /// Defaults to one.
+    pub connections: usize,
+
+    /// Whether to set the RD bit in queries.
+    ///
+    /// Defaults to `true`.
+    pub recursion_desired: bool,
+}
+
+impl LoadConfig {
+    /// Creates a new configuration with default values.
+    pub fn new(
+        server: SocketAddr,
+        transport: Transport,
+        qnames: QnamePattern,
+    ) -> Self {
+        LoadConfig {
+            server,
+            transport,
+            qnames,
+            qtype: Rtype::A,
+            qps: 100,
+            duration: Duration::from_secs(10),
+            timeout: Duration::from_secs(2),
+            connections: 1,
+            recursion_desired: true,
+        }
+    }
+
+    /// Returns the number of queries sent for this configuration.
+    pub fn queries(&self) -> u64 {
+        (self.duration.as_secs_f64() * f64::from(self.qps)).round() as u64
+    }
+
+    /// Creates the query with the given sequence number and message ID.
+    fn query(&self, seq: u64, id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(id);
+        msg.header_mut().set_rd(self.recursion_desired);
+        let mut msg = msg.question();
+        msg.push((self.qnames.name(seq), self.qtype)).unwrap();
+        msg.finish()
+    }
+}
+
+//------------ QnamePattern --------------------------------------------------
+
+/// How query names are chosen.
+#[derive(Clone, Debug)]
+pub enum QnamePattern {
+    /// All queries are for the same name.
+    Fixed(Dname<Bytes>),
+
+    /// The names of the list are used in turn.
+    ///
+    /// An empty list is treated like the root name.
+    List(Vec<Dname<Bytes>>),
+
+    /// A label of random letters and digits is prepended to a zone.
+    ///
+    /// The label has the given length, which should be between 1 and 63.
+    /// Since the names are unlikely to repeat, this defeats caches.
+    RandomLabel {
+        /// The zone the names are in.
+        zone: Dname<Bytes>,
+
+        /// The length of the random label.
+        len: u8,
+    },
+
+    /// A label with the query’s sequence number is prepended to a zone.
+    ///
+    /// The names are `q0`, `q1`, and so on below the zone.
+    Sequence(Dname<Bytes>),
+}
+
+impl QnamePattern {
+    /// Returns the name for the query with the given sequence number.
+    ///
+    /// If a name would become too long, the zone is used instead.
+    pub fn name(&self, seq: u64) -> Dname<Bytes> {
+        match *self {
+            QnamePattern::Fixed(ref name) => name.clone(),
+            QnamePattern::List(ref names) => {
+                if names.is_empty() {
+                    Dname::root_bytes()
+                } else {
+                    names[(seq % names.len() as u64) as usize].clone()
+                }
+            }
+            QnamePattern::RandomLabel { ref zone, len } => {
+                const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+                let mut rng = rand::thread_rng();
+                let label = (0..len.max(1))
+                    .map(|_| CHARS[rng.gen_range(0, CHARS.len())])
+                    .collect::<Vec<_>>();
+                Self::prepend(&label, zone)
+            }
+            QnamePattern::Sequence(ref zone) => {
+                Self::prepend(format!("q{}", seq).as_bytes(), zone)
+            }
+        }
+    }
+
+    /// Prepends a label to a zone, falling back to the zone.
+    fn prepend(label: &[u8], zone: &Dname<Bytes>) -> Dname<Bytes> {
+        let mut builder = DnameBuilder::new_bytes();
+        if builder.append_label(label).is_err() {
+            return zone.clone();
+        }
+        builder.append_origin(zone).unwrap_or_else(|_| zone.clone())
+    }
+}
+
+//------------ run_load ------------------------------------------------------
+
+/// Runs a load test.
+///
+/// Opens the sockets or connections given by `config`, sends the queries,
+/// and waits for the answers. An error is returned if the configuration
+/// is invalid or the sockets or connections can’t be opened. Errors while
+/// sending individual queries are counted in the report instead.
+pub async fn run_load(config: &LoadConfig) -> Result<LoadReport, io::Error> {
+    if config.qps == 0 || config.connections == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "rate and number of connections must not be zero",
+        ));
+    }
+    let mut senders = Vec::with_capacity(config.connections);
+    let mut receivers = Vec::with_capacity(config.connections);
+    for _ in 0..config.connections {
+        let (sender, receiver) = open(config).await?;
+        senders.push(sender);
+        receivers.push(receiver);
+    }
+
+    let collector = Mutex::new(Collector::new(config.timeout));
+    let start = Instant::now();
+    let send = async {
+        let interval = Duration::from_secs(1) / config.qps;
+        let count = senders.len() as u64;
+        let mut next = start;
+        for seq in 0..config.queries() {
+            sleep_until(next).await;
+            next += interval;
+            let idx = (seq % count) as usize;
+            let id = (seq / count) as u16;
+            let query = config.query(seq, id);
+            collector.lock().unwrap().start(idx, id);
+            if senders[idx].send(&query).await.is_err() {
+                collector.lock().unwrap().error(idx, id);
+            }
+        }
+        let duration = start.elapsed();
+        sleep(config.timeout).await;
+        duration
+    };
+    let receive = async {
+        join_all(
+            receivers
+                .into_iter()
+                .enumerate()
+                .map(|(idx, receiver)| receive(idx, receiver, &collector)),
+        )
+        .await;
+        // Keep going until all queries have been sent.
+        pending::<()>().await
+    };
+    let duration = tokio::select! {
+        duration = send => duration,
+        _ = receive => unreachable!(),
+    };
+    Ok(collector.into_inner().unwrap().finish(duration))
+}
+
+/// Receives answers until the connection is closed.
+async fn receive(
+    idx: usize,
+    mut receiver: Receiver,
+    collector: &Mutex<Collector>,
+) {
+    while let Ok(Some(answer)) = receiver.recv().await {
+        collector.lock().unwrap().answer(idx, &answer)
+    }
+}
+
+/// Opens a socket or connection to the server.
+async fn open(config: &LoadConfig) -> Result<(Sender, Receiver), io::Error> {
+    match config.transport {
+        Transport::Udp => {
+            let local: SocketAddr = if config.server.is_ipv4() {
+                ([0u8; 4], 0).into()
+            } else {
+                ([0u16; 8], 0).into()
+            };
+            let sock = UdpSocket::bind(local).await?;
+            sock.connect(config.server).await?;
+            let sock = Arc::new(sock);
+            Ok((Sender::Udp(sock.clone()), Receiver::Udp(sock)))
+        }
+        Transport::Tcp => {
+            let (read, write) =
+                TcpStream::connect(config.server).await?.into_split();
+            Ok((Sender::Tcp(write), Receiver::Tcp(read)))
+        }
+    }
+}
+
+//------------ Sender and Receiver -------------------------------------------
+
+/// The sending side of a socket or connection.
+enum Sender {
+    Udp(Arc<UdpSocket>),
+    Tcp(OwnedWriteHalf),
+}
+
+impl Sender {
+    async fn send(&mut self, query: &[u8]) -> Result<(), io::Error> {
+        match *self {
+            Sender::Udp(ref sock) => sock.send(query).await.map(|_| ()),
+            Sender::Tcp(ref mut sock) => {
+                let mut buf = Vec::with_capacity(query.len() + 2);
+                buf.extend_from_slice(&(query.len() as u16).to_be_bytes());
+                buf.extend_from_slice(query);
+                sock.write_all(&buf).await
+            }
+        }
+    }
+}
+
+/// The receiving side of a socket or connection.
+enum Receiver {
+    Udp(Arc<UdpSocket>),
+    Tcp(OwnedReadHalf),
+}
+
+impl Receiver {
+    /// Receives the next message.
+    ///
+    /// Returns `Ok(None)` if a TCP connection was closed.
+    async fn recv(&mut self) -> Result<Option<Vec<u8>>, io::Error> {
+        match *self {
+            Receiver::Udp(ref sock) => {
+                let mut buf = vec![0; 0xFFFF];
+                let len = sock.recv(&mut buf).await?;
+                buf.truncate(len);
+                Ok(Some(buf))
+            }
+            Receiver::Tcp(ref mut sock) => {
+                let len = match sock.read_u16().await {
+                    Ok(len) => len,
+                    Err(err)
+                        if err.kind() == io::ErrorKind::UnexpectedEof =>
+                    {
+                        return Ok(None)
+                    }
+                    Err(err) => return Err(err),
+                };
+                let mut buf = vec![0; usize::from(len)];
+                sock.read_exact(&mut buf).await?;
+                Ok(Some(buf))
+            }
+        }
+    }
+}
+
+//------------ Collector -----------------------------------------------------
+
+/// Collects the outcome of queries while a load test is running.
+#[derive(Debug)]
+struct Collector {
+    /// The timeout for answers.
+    timeout: Duration,
+
+    /// The queries waiting for an answer by connection and message ID.
+    pending: HashMap<(usize, u16), Instant>,
+
+    /// The report being assembled.
+    report: LoadReport,
+}
+
+impl Collector {
+    fn new(timeout: Duration) -> Self {
+        Collector {
+            timeout,
+            pending: HashMap::new(),
+            report: LoadReport::default(),
+        }
+    }
+
+    /// Records that a query is about to be sent.
+    fn start(&mut self, idx: usize, id: u16) {
+        self.report.sent += 1;
+        // If the ID is still in use, that query will never be matched.
+        if self.pending.insert((idx, id), Instant::now()).is_some() {
+            self.report.timed_out += 1;
+        }
+    }
+
+    /// Records that sending a query failed.
+    fn error(&mut self, idx: usize, id: u16) {
+        self.pending.remove(&(idx, id));
+        self.report.errors += 1;
+    }
+
+    /// Records a message received on a connection.
+    fn answer(&mut self, idx: usize, data: &[u8]) {
+        let msg = match Message::from_octets(data) {
+            Ok(msg) if msg.header().qr() => msg,
+            _ => {
+                self.report.unexpected += 1;
+                return;
+            }
+        };
+        let started = match self.pending.remove(&(idx, msg.header().id())) {
+            Some(started) => started,
+            None => {
+                self.report.unexpected += 1;
+                return;
+            }
+        };
+        let latency = started.elapsed();
+        if latency > self.timeout {
+            self.report.timed_out += 1;
+            return;
+        }
+        self.report.latencies.push(latency);
+        *self.report.rcodes.entry(msg.header().rcode()).or_insert(0) += 1;
+        if msg.header().tc() {
+            self.report.truncated += 1
+        }
+    }
+
+    /// Finishes collecting, counting all outstanding queries as timed out.
+    fn finish(mut self, duration: Duration) -> LoadReport {
+        self.report.timed_out += self.pending.len() as u64;
+        self.report.duration = duration;
+        self.report.latencies.sort_unstable();
+        self.report
+    }
+}
+
+//------------ LoadReport ----------------------------------------------------
+
+/// The outcome of a load test.
+///
+/// The `Display` implementation prints a short summary.
+#[derive(Clone, Debug, Default)]
+pub struct LoadReport {
+    /// The number of queries sent.
+    sent: u64,
+
+    /// The number of queries without an answer within the timeout.
+    timed_out: u64,
+
+    /// The number of queries that couldn’t be sent.
+    errors: u64,
+
+    /// The number of answers with the TC bit set.
+    truncated: u64,
+
+    /// The number of messages received that didn’t answer a query.
+    unexpected: u64,
+
+    /// The number of answers per response code.
+    rcodes: BTreeMap<Rcode, u64>,
+
+    /// The latencies of all answers in increasing order.
+    latencies: Vec<Duration>,
+
+    /// The time it took to send all queries.
+    duration: Duration,
+}
+
+impl LoadReport {
+    /// Returns the number of queries sent.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Returns the number of queries answered within the timeout.
+    pub fn answered(&self) -> u64 {
+        self.latencies.len() as u64
+    }
+
+    /// Returns the number of queries not answered within the timeout.
+    pub fn timed_out(&self) -> u64 {
+        self.timed_out
+    }
+
+    /// Returns the number of queries that couldn’t be sent.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Returns the number of answers that were truncated.
+    pub fn truncated(&self) -> u64 {
+        self.truncated
+    }
+
+    /// Returns the number of received messages that didn’t answer a query.
+    ///
+    /// This includes answers arriving after their query has timed out.
+    pub fn unexpected(&self) -> u64 {
+        self.unexpected
+    }
+
+    /// Returns the number of answers with the given response code.
+    pub fn rcode_count(&self, rcode: Rcode) -> u64 {
+        self.rcodes.get(&rcode).copied().unwrap_or(0)
+    }
+
+    /// Returns the response codes of answers and how often they occurred.
+    pub fn rcodes(&self) -> impl Iterator<Item = (Rcode, u64)> + '_ {
+        self.rcodes.iter().map(|(rcode, count)| (*rcode, *count))
+    }
+
+    /// Returns the time it took to send all queries.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the rate at which queries were actually sent.
+    pub fn qps(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+        if secs > 0. {
+            self.sent as f64 / secs
+        } else {
+            0.
+        }
+    }
+
+    /// Returns the latencies of all answers in increasing order.
+    pub fn latencies(&self) -> &[Duration] {
+        &self.latencies
+    }
+
+    /// Returns the given percentile of the latencies.
+    ///
+    /// The percentile is determined using the nearest-rank method, i.e.,
+    /// it is the smallest latency such that at least `percent` percent of
+    /// all latencies are less than or equal to it. Values of `percent`
+    /// outside of 0 to 100 are clamped. Returns `None` if there were no
+    /// answers.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let len = self.latencies.len();
+        let rank = if percent >= 100. {
+            len
+        } else {
+            (percent / 100. * len as f64).ceil() as usize
+        };
+        Some(self.latencies[rank.max(1) - 1])
+    }
+
+    /// Returns the mean latency of all answers.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let sum: Duration = self.latencies.iter().sum();
+        Some(sum / self.latencies.len() as u32)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "sent {} queries in {:.3}s ({:.1} qps)",
+            self.sent,
+            self.duration.as_secs_f64(),
+            self.qps()
+        )?;
+        write!(
+            f,
+            "answered {}, timed out {}, errors {}, truncated {}",
+            self.answered(),
+            self.timed_out,
+            self.errors,
+            self.truncated
+        )?;
+        for (rcode, count) in self.rcodes() {
+            write!(f, ", {} {}", rcode, count)?;
+        }
+        writeln!(f)?;
+        match (self.mean(), self.latencies.last()) {
+            (Some(mean), Some(max)) => write!(
+                f,
+                "latency mean {:?}, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+                mean,
+                self.percentile(50.).unwrap(),
+                self.percentile(90.).unwrap(),
+                self.percentile(99.).unwrap(),
+                max
+            ),
+            _ => write!(f, "no answers"),
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::run;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn name(s: &str) -> Dname<Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    /// Turns a query into an NXDOMAIN answer.
+    fn answer(query: &mut [u8]) {
+        query[2] |= 0x80;
+        query[3] = (query[3] & 0xF0) | 3;
+    }
+
+    /// Answers every UDP query except for each `drop`th one.
+    async fn udp_server(sock: UdpSocket, drop: usize) {
+        let mut buf = vec![0; 512];
+        let mut count = 0;
+        loop {
+            let (len, addr) = sock.recv_from(&mut buf).await.unwrap();
+            count += 1;
+            if drop != 0 && count % drop == 0 {
+                continue;
+            }
+            answer(&mut buf[..len]);
+            sock.send_to(&buf[..len], addr).await.unwrap();
+        }
+    }
+
+    /// Accepts `count` TCP connections and answers all their queries.
+    async fn tcp_server(listener: tokio::net::TcpListener, count: usize) {
+        let mut connections = Vec::new();
+        for _ in 0..count {
+            connections.push(listener.accept().await.unwrap().0);
+        }
+        join_all(connections.into_iter().map(|sock| async move {
+            let (mut read, mut write) = sock.into_split();
+            while let Ok(len) = read.read_u16().await {
+                let mut buf = vec![0; usize::from(len) + 2];
+                buf[..2].copy_from_slice(&len.to_be_bytes());
+                read.read_exact(&mut buf[2..]).await.unwrap();
+                answer(&mut buf[2..]);
+                write.write_all(&buf).await.unwrap();
+            }
+        }))
+        .await;
+        pending::<()>().await
+    }
+
+    #[test]
+    fn qname_patterns() {
+        let zone = name("example.com");
+        assert_eq!(QnamePattern::Fixed(zone.clone()).name(7), zone);
+        let list = QnamePattern::List(vec![name("a.example"), name("b")]);
+        assert_eq!(list.name(0), name("a.example"));
+        assert_eq!(list.name(3), name("b"));
+        assert_eq!(
+            QnamePattern::List(Vec::new()).name(1),
+            Dname::root_bytes()
+        );
+        assert_eq!(
+            QnamePattern::Sequence(zone.clone()).name(42),
+            name("q42.example.com")
+        );
+        let random = QnamePattern::RandomLabel {
+            zone: zone.clone(),
+            len: 12,
+        };
+        let first = random.name(0);
+        assert!(first.ends_with(&zone));
+        assert_eq!(first.len(), zone.len() + 13);
+        assert_ne!(first, random.name(0));
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut report = LoadReport::default();
+        assert_eq!(report.percentile(50.), None);
+        assert_eq!(report.mean(), None);
+        report.latencies =
+            (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        let ms = |ms| Some(Duration::from_millis(ms));
+        assert_eq!(report.percentile(0.), ms(1));
+        assert_eq!(report.percentile(50.), ms(50));
+        assert_eq!(report.percentile(90.), ms(90));
+        assert_eq!(report.percentile(99.5), ms(100));
+        assert_eq!(report.percentile(150.), ms(100));
+        assert_eq!(report.mean(), Some(Duration::from_micros(50500)));
+    }
+
+    #[test]
+    fn udp_load() {
+        run(async {
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut config = LoadConfig::new(
+                sock.local_addr().unwrap(),
+                Transport::Udp,
+                QnamePattern::Sequence(name("example.com")),
+            );
+            config.qps = 400;
+            config.duration = Duration::from_millis(250);
+            config.timeout = Duration::from_millis(200);
+            config.connections = 2;
+            assert_eq!(config.queries(), 100);
+
+            let report = tokio::select! {
+                report = run_load(&config) => report.unwrap(),
+                _ = udp_server(sock, 10) => unreachable!(),
+            };
+            assert_eq!(report.sent(), 100);
+            assert_eq!(report.answered(), 90);
+            assert_eq!(report.timed_out(), 10);
+            assert_eq!(report.rcode_count(Rcode::NXDomain), 90);
+            assert_eq!(report.errors(), 0);
+            assert!(report.duration() >= Duration::from_millis(240));
+            assert!(report.percentile(50.) <= report.percentile(99.));
+            assert!(report.to_string().contains("answered 90"));
+        })
+    }
+
+    #[test]
+    fn tcp_load() {
+        run(async {
+            let listener =
+                tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut config = LoadConfig::new(
+                listener.local_addr().unwrap(),
+                Transport::Tcp,
+                QnamePattern::Fixed(name("example.com")),
+            );
+            config.qps = 200;
+            config.duration = Duration::from_millis(200);
+            config.timeout = Duration::from_millis(500);
+            config.connections = 3;
+
+            let report = tokio::select! {
+                report = run_load(&config) => report.unwrap(),
+                _ = tcp_server(listener, 3) => unreachable!(),
+            };
+            assert_eq!(report.sent(), 40);
+            assert_eq!(report.answered(), 40);
+            assert_eq!(report.timed_out(), 0);
+            assert_eq!(report.unexpected(), 0);
+        })
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut config = LoadConfig::new(
+            "127.0.0.1:53".parse().unwrap(),
+            Transport::Udp,
+            QnamePattern::Fixed(Dname::root_bytes()),
+        );
+        config.qps = 0;
+        assert_eq!(
+            run(run_load(&config)).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
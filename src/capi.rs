@@ -0,0 +1,916 @@
+//! A C API for the core functionality.
+//!
+//! This module exposes a small set of operations through functions with C
+//! linkage so that applications written in other languages can embed the
+//! crate. All functions are prefixed with `domain_` and only use types
+//! with a stable C representation.
+//!
+//! The following operations are available:
+//!
+//! * converting domain names between their presentation and wire formats
+//!   via [`domain_name_from_str`] and [`domain_name_to_str`],
+//! * creating query messages via [`domain_query_new`],
+//! * parsing messages via [`domain_message_parse`] and inspecting the
+//!   result through the returned [`ParsedMessage`] handle,
+//! * looking up the addresses of a host via [`domain_lookup_host`] using
+//!   the blocking [`BlockingResolver`] handle.
+//!
+//! Functions that can fail return a [`Status`]. Results are written into
+//! buffers provided by the caller. In addition to the buffer, these
+//! functions take a pointer to a `size_t` that receives the length of the
+//! result. If the buffer is too small, the length is still written and
+//! [`Status::ShortBuf`] is returned, so the caller can retry with a large
+//! enough buffer.
+//!
+//! Handles are created by a `_new` or `_parse` function and must be
+//! released via the matching `_free` function. Pointers returned as part
+//! of the information about a handle remain valid until it is released.
+//!
+//! The functions are exported from the library when it is linked into a
+//! `staticlib` or `cdylib` crate, which can simply re-export this module.
+//! The declarations for a C header follow directly from the definitions
+//! here: `Status` and `Section` are `int` sized enums, `bool` is C’s
+//! `_Bool`, and `usize` is `size_t`.
+//!
+//! This module requires the `capi` feature.
+//!
+//! [`domain_name_from_str`]: fn.domain_name_from_str.html
+//! [`domain_name_to_str`]: fn.domain_name_to_str.html
+//! [`domain_query_new`]: fn.domain_query_new.html
+//! [`domain_message_parse`]: fn.domain_message_parse.html
+//! [`domain_lookup_host`]: fn.domain_lookup_host.html
+//! [`ParsedMessage`]: struct.ParsedMessage.html
+//! [`BlockingResolver`]: struct.BlockingResolver.html
+//! [`Status`]: enum.Status.html
+//! [`Status::ShortBuf`]: enum.Status.html#variant.ShortBuf
+#![cfg(feature = "capi")]
+
+use crate::base::iana::{Class, Rtype};
+use crate::base::message::{self, Message};
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::Compose;
+use crate::rdata::AllRecordData;
+use crate::resolv::lookup::error::LookupError;
+use crate::resolv::stub::StubResolver;
+use core::str::FromStr;
+use core::{ptr, slice};
+use std::boxed::Box;
+use std::ffi::CStr;
+use std::net::IpAddr;
+use std::os::raw::c_char;
+use std::string::ToString;
+use std::vec::Vec;
+use tokio::runtime;
+
+//------------ Status --------------------------------------------------------
+
+/// The result of a function of the C API.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    /// The function succeeded.
+    Ok = 0,
+
+    /// A pointer argument was null.
+    NullPointer = 1,
+
+    /// An argument was invalid.
+    ///
+    /// This happens if a string isn’t valid UTF-8 or a domain name is
+    /// malformed.
+    InvalidArgument = 2,
+
+    /// The provided buffer was too small for the result.
+    ShortBuf = 3,
+
+    /// A message could not be parsed.
+    ParseError = 4,
+
+    /// An index was beyond the number of available items.
+    OutOfRange = 5,
+
+    /// The looked up name does not exist or has no addresses.
+    NotFound = 6,
+
+    /// The lookup failed for some other reason.
+    LookupFailed = 7,
+}
+
+//------------ Domain Names --------------------------------------------------
+
+/// Converts a domain name from presentation format into wire format.
+///
+/// The name is given as the NUL-terminated string `name`. It is always
+/// treated as an absolute name, whether it ends in a dot or not. The wire
+/// format is written to `buf` which has room for `buf_len` octets. Its
+/// length is written to `out_len`.
+///
+/// # Safety
+///
+/// `name` must point to a NUL-terminated string, `buf` must be valid for
+/// writing `buf_len` octets, and `out_len` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn domain_name_from_str(
+    name: *const c_char,
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> Status {
+    let name = match c_str(name) {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    let name = match Dname::<Vec<u8>>::from_str(name) {
+        Ok(name) => name,
+        Err(_) => return Status::InvalidArgument,
+    };
+    copy_out(name.as_slice(), buf, buf_len, out_len)
+}
+
+/// Converts a domain name from wire format into presentation format.
+///
+/// The absolute name in wire format is given by `name` and `name_len`. It
+/// must not be compressed. The presentation format including a trailing
+/// dot is written as a NUL-terminated string to `buf` which has room for
+/// `buf_len` octets. The length of the string without the terminating NUL
+/// is written to `out_len`.
+///
+/// # Safety
+///
+/// `name` must be valid for reading `name_len` octets, `buf` must be
+/// valid for writing `buf_len` octets, and `out_len` must be valid for
+/// writing.
+#[no_mangle]
+pub unsafe extern "C" fn domain_name_to_str(
+    name: *const u8,
+    name_len: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> Status {
+    if name.is_null() {
+        return Status::NullPointer;
+    }
+    let name = match Dname::from_slice(slice::from_raw_parts(name, name_len))
+    {
+        Ok(name) => name,
+        Err(_) => return Status::InvalidArgument,
+    };
+    let mut res = name.display_presentation().to_string().into_bytes();
+    let len = res.len();
+    res.push(0);
+    let status = copy_out(&res, buf as *mut u8, buf_len, out_len);
+    if !out_len.is_null() {
+        *out_len = len
+    }
+    status
+}
+
+//------------ Queries -------------------------------------------------------
+
+/// Creates a query message.
+///
+/// The query asks for records of type `qtype` and class `qclass` for the
+/// name given as the NUL-terminated string `qname`. The message ID is set
+/// to `id` and the RD bit is set if `recursion_desired` is true.
+///
+/// The message is written to `buf` which has room for `buf_len` octets.
+/// Its length is written to `out_len`.
+///
+/// # Safety
+///
+/// `qname` must point to a NUL-terminated string, `buf` must be valid for
+/// writing `buf_len` octets, and `out_len` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn domain_query_new(
+    qname: *const c_char,
+    qtype: u16,
+    qclass: u16,
+    id: u16,
+    recursion_desired: bool,
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> Status {
+    let qname = match c_str(qname) {
+        Ok(qname) => qname,
+        Err(status) => return status,
+    };
+    let qname = match Dname::<Vec<u8>>::from_str(qname) {
+        Ok(qname) => qname,
+        Err(_) => return Status::InvalidArgument,
+    };
+    let mut msg = MessageBuilder::new_vec();
+    msg.header_mut().set_id(id);
+    msg.header_mut().set_rd(recursion_desired);
+    let mut msg = msg.question();
+    if msg
+        .push((qname, Rtype::from_int(qtype), Class::from_int(qclass)))
+        .is_err()
+    {
+        return Status::InvalidArgument;
+    }
+    copy_out(msg.as_slice(), buf, buf_len, out_len)
+}
+
+//------------ Parsed Messages -----------------------------------------------
+
+/// A parsed DNS message.
+///
+/// The handle is created via [`domain_message_parse`] and must be released
+/// via [`domain_message_free`]. All names in the message are stored
+/// uncompressed, as is the record data of all record types known to this
+/// crate.
+///
+/// [`domain_message_parse`]: fn.domain_message_parse.html
+/// [`domain_message_free`]: fn.domain_message_free.html
+#[derive(Clone, Debug)]
+pub struct ParsedMessage {
+    header: HeaderInfo,
+    questions: Vec<OwnedQuestion>,
+    records: Vec<OwnedRecord>,
+}
+
+#[derive(Clone, Debug)]
+struct OwnedQuestion {
+    qname: Vec<u8>,
+    qtype: u16,
+    qclass: u16,
+}
+
+#[derive(Clone, Debug)]
+struct OwnedRecord {
+    section: Section,
+    owner: Vec<u8>,
+    rtype: u16,
+    class: u16,
+    ttl: u32,
+    data: Vec<u8>,
+}
+
+impl ParsedMessage {
+    fn from_message(msg: Message<&[u8]>) -> Option<Self> {
+        let counts = msg.header_counts();
+        let flags = msg.as_slice();
+        let header = HeaderInfo {
+            id: msg.header().id(),
+            flags: u16::from_be_bytes([flags[2], flags[3]]),
+            opcode: msg.opcode().to_int(),
+            rcode: msg.opt_rcode().to_int(),
+            qdcount: counts.qdcount(),
+            ancount: counts.ancount(),
+            nscount: counts.nscount(),
+            arcount: counts.arcount(),
+        };
+        let mut questions = Vec::new();
+        for question in msg.question() {
+            let question = question.ok()?;
+            questions.push(OwnedQuestion {
+                qname: question.qname().to_vec().into_octets(),
+                qtype: question.qtype().to_int(),
+                qclass: question.qclass().to_int(),
+            })
+        }
+        let mut records = Vec::new();
+        for item in msg.iter() {
+            let (record, section) = item.ok()?;
+            let record = record.to_record::<AllRecordData<_, _>>().ok()??;
+            let mut data = Vec::new();
+            record.data().compose(&mut data).ok()?;
+            records.push(OwnedRecord {
+                section: section.into(),
+                owner: record.owner().to_vec().into_octets(),
+                rtype: record.rtype().to_int(),
+                class: record.class().to_int(),
+                ttl: record.ttl(),
+                data,
+            })
+        }
+        Some(ParsedMessage {
+            header,
+            questions,
+            records,
+        })
+    }
+}
+
+/// The header of a parsed message.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeaderInfo {
+    /// The message ID.
+    pub id: u16,
+
+    /// The second 16 bit word of the header.
+    ///
+    /// This contains the QR, AA, TC, RD, RA, Z, AD, and CD flags as well as
+    /// the opcode and the lower four bits of the response code.
+    pub flags: u16,
+
+    /// The opcode.
+    pub opcode: u8,
+
+    /// The full response code including the bits from the OPT record.
+    pub rcode: u16,
+
+    /// The number of entries in the question section.
+    pub qdcount: u16,
+
+    /// The number of records in the answer section.
+    pub ancount: u16,
+
+    /// The number of records in the authority section.
+    pub nscount: u16,
+
+    /// The number of records in the additional section.
+    pub arcount: u16,
+}
+
+/// A question of a parsed message.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct QuestionInfo {
+    /// The uncompressed query name in wire format.
+    pub qname: *const u8,
+
+    /// The length of the query name.
+    pub qname_len: usize,
+
+    /// The query type.
+    pub qtype: u16,
+
+    /// The query class.
+    pub qclass: u16,
+}
+
+/// The section of a message a record appears in.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Section {
+    /// The answer section.
+    Answer = 1,
+
+    /// The authority section.
+    Authority = 2,
+
+    /// The additional section.
+    Additional = 3,
+}
+
+impl From<message::Section> for Section {
+    fn from(section: message::Section) -> Self {
+        match section {
+            message::Section::Answer => Section::Answer,
+            message::Section::Authority => Section::Authority,
+            message::Section::Additional => Section::Additional,
+        }
+    }
+}
+
+/// A resource record of a parsed message.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RecordInfo {
+    /// The section the record appears in.
+    pub section: Section,
+
+    /// The uncompressed owner name in wire format.
+    pub owner: *const u8,
+
+    /// The length of the owner name.
+    pub owner_len: usize,
+
+    /// The record type.
+    pub rtype: u16,
+
+    /// The record class.
+    pub class: u16,
+
+    /// The TTL of the record.
+    pub ttl: u32,
+
+    /// The record data in wire format.
+    pub data: *const u8,
+
+    /// The length of the record data.
+    pub data_len: usize,
+}
+
+/// Parses a DNS message.
+///
+/// The message is given by `data` and `len`. If parsing succeeds, a handle
+/// to the parsed message is written to `out`. It must be released via
+/// [`domain_message_free`].
+///
+/// # Safety
+///
+/// `data` must be valid for reading `len` octets and `out` must be valid
+/// for writing.
+///
+/// [`domain_message_free`]: fn.domain_message_free.html
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_parse(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut ParsedMessage,
+) -> Status {
+    if data.is_null() || out.is_null() {
+        return Status::NullPointer;
+    }
+    let msg = match Message::from_octets(slice::from_raw_parts(data, len)) {
+        Ok(msg) => msg,
+        Err(_) => return Status::ParseError,
+    };
+    match ParsedMessage::from_message(msg) {
+        Some(msg) => {
+            *out = Box::into_raw(Box::new(msg));
+            Status::Ok
+        }
+        None => Status::ParseError,
+    }
+}
+
+/// Releases a parsed message.
+///
+/// # Safety
+///
+/// `msg` must be null or a handle returned by [`domain_message_parse`]
+/// that hasn’t been released yet.
+///
+/// [`domain_message_parse`]: fn.domain_message_parse.html
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_free(msg: *mut ParsedMessage) {
+    if !msg.is_null() {
+        drop(Box::from_raw(msg))
+    }
+}
+
+/// Writes the header of a parsed message to `out`.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle and `out` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_header(
+    msg: *const ParsedMessage,
+    out: *mut HeaderInfo,
+) -> Status {
+    if msg.is_null() || out.is_null() {
+        return Status::NullPointer;
+    }
+    *out = (*msg).header;
+    Status::Ok
+}
+
+/// Returns the number of questions of a parsed message.
+///
+/// # Safety
+///
+/// `msg` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_question_count(
+    msg: *const ParsedMessage,
+) -> usize {
+    msg.as_ref().map(|msg| msg.questions.len()).unwrap_or(0)
+}
+
+/// Writes the question with the given index to `out`.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle and `out` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_question(
+    msg: *const ParsedMessage,
+    index: usize,
+    out: *mut QuestionInfo,
+) -> Status {
+    if msg.is_null() || out.is_null() {
+        return Status::NullPointer;
+    }
+    let question = match (&*msg).questions.get(index) {
+        Some(question) => question,
+        None => return Status::OutOfRange,
+    };
+    *out = QuestionInfo {
+        qname: question.qname.as_ptr(),
+        qname_len: question.qname.len(),
+        qtype: question.qtype,
+        qclass: question.qclass,
+    };
+    Status::Ok
+}
+
+/// Returns the number of records in all sections of a parsed message.
+///
+/// # Safety
+///
+/// `msg` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_record_count(
+    msg: *const ParsedMessage,
+) -> usize {
+    msg.as_ref().map(|msg| msg.records.len()).unwrap_or(0)
+}
+
+/// Writes the record with the given index to `out`.
+///
+/// The records of all three sections are numbered consecutively in the
+/// order they appear in the message.
+///
+/// # Safety
+///
+/// `msg` must be a valid handle and `out` must be valid for writing.
+#[no_mangle]
+pub unsafe extern "C" fn domain_message_record(
+    msg: *const ParsedMessage,
+    index: usize,
+    out: *mut RecordInfo,
+) -> Status {
+    if msg.is_null() || out.is_null() {
+        return Status::NullPointer;
+    }
+    let record = match (&*msg).records.get(index) {
+        Some(record) => record,
+        None => return Status::OutOfRange,
+    };
+    *out = RecordInfo {
+        section: record.section,
+        owner: record.owner.as_ptr(),
+        owner_len: record.owner.len(),
+        rtype: record.rtype,
+        class: record.class,
+        ttl: record.ttl,
+        data: record.data.as_ptr(),
+        data_len: record.data.len(),
+    };
+    Status::Ok
+}
+
+//------------ Host Lookups --------------------------------------------------
+
+/// A resolver performing blocking lookups.
+///
+/// The handle is created via [`domain_resolver_new`] using the system’s
+/// resolver configuration and must be released via
+/// [`domain_resolver_free`]. It must not be used by more than one thread
+/// at the same time.
+///
+/// [`domain_resolver_new`]: fn.domain_resolver_new.html
+/// [`domain_resolver_free`]: fn.domain_resolver_free.html
+#[derive(Debug)]
+pub struct BlockingResolver {
+    runtime: runtime::Runtime,
+    resolver: StubResolver,
+}
+
+/// An address of a host.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HostAddr {
+    /// The address family, either 4 or 6.
+    pub family: u8,
+
+    /// The address in network byte order.
+    ///
+    /// IPv4 addresses only use the first four octets. The remaining octets
+    /// are zero.
+    pub addr: [u8; 16],
+}
+
+impl From<IpAddr> for HostAddr {
+    fn from(addr: IpAddr) -> Self {
+        let mut res = HostAddr::default();
+        match addr {
+            IpAddr::V4(addr) => {
+                res.family = 4;
+                res.addr[..4].copy_from_slice(&addr.octets());
+            }
+            IpAddr::V6(addr) => {
+                res.family = 6;
+                res.addr = addr.octets();
+            }
+        }
+        res
+    }
+}
+
+/// Creates a new resolver using the system’s configuration.
+///
+/// Returns a null pointer if the resolver could not be created.
+#[no_mangle]
+pub extern "C" fn domain_resolver_new() -> *mut BlockingResolver {
+    let runtime =
+        match runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_) => return ptr::null_mut(),
+        };
+    Box::into_raw(Box::new(BlockingResolver {
+        runtime,
+        resolver: StubResolver::new(),
+    }))
+}
+
+/// Releases a resolver.
+///
+/// # Safety
+///
+/// `resolver` must be null or a handle returned by
+/// [`domain_resolver_new`] that hasn’t been released yet.
+///
+/// [`domain_resolver_new`]: fn.domain_resolver_new.html
+#[no_mangle]
+pub unsafe extern "C" fn domain_resolver_free(
+    resolver: *mut BlockingResolver,
+) {
+    if !resolver.is_null() {
+        drop(Box::from_raw(resolver))
+    }
+}
+
+/// Looks up the addresses of a host.
+///
+/// The host name is given as the NUL-terminated string `name`. It is
+/// processed using the search list of the resolver configuration. The
+/// function blocks until the lookup has finished.
+///
+/// The addresses are written to `addrs` which has room for `addrs_len`
+/// addresses. The number of addresses found is written to `out_count`. If
+/// there are more than fit into `addrs`, the first `addrs_len` addresses
+/// are written and [`Status::ShortBuf`] is returned.
+///
+/// # Safety
+///
+/// `resolver` must be a valid handle, `name` must point to a
+/// NUL-terminated string, `addrs` must be valid for writing `addrs_len`
+/// addresses, and `out_count` must be valid for writing.
+///
+/// [`Status::ShortBuf`]: enum.Status.html#variant.ShortBuf
+#[no_mangle]
+pub unsafe extern "C" fn domain_lookup_host(
+    resolver: *const BlockingResolver,
+    name: *const c_char,
+    addrs: *mut HostAddr,
+    addrs_len: usize,
+    out_count: *mut usize,
+) -> Status {
+    if resolver.is_null() || (addrs.is_null() && addrs_len > 0) {
+        return Status::NullPointer;
+    }
+    if out_count.is_null() {
+        return Status::NullPointer;
+    }
+    let name = match c_str(name) {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    let resolver = &*resolver;
+    let found: Result<Vec<IpAddr>, _> = resolver.runtime.block_on(async {
+        resolver
+            .resolver
+            .search_host(name)
+            .await
+            .map(|found| found.iter().collect())
+    });
+    let found = match found {
+        Ok(found) => found,
+        Err(LookupError::BadName) => return Status::InvalidArgument,
+        Err(err) if err.is_negative() => return Status::NotFound,
+        Err(_) => return Status::LookupFailed,
+    };
+    *out_count = found.len();
+    if found.is_empty() {
+        return Status::NotFound;
+    }
+    for (i, addr) in found.iter().take(addrs_len).enumerate() {
+        *addrs.add(i) = (*addr).into();
+    }
+    if found.len() > addrs_len {
+        Status::ShortBuf
+    } else {
+        Status::Ok
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts a C string into a `str`.
+unsafe fn c_str<'a>(s: *const c_char) -> Result<&'a str, Status> {
+    if s.is_null() {
+        return Err(Status::NullPointer);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Status::InvalidArgument)
+}
+
+/// Copies `data` into a buffer provided by the caller.
+unsafe fn copy_out(
+    data: &[u8],
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> Status {
+    if out_len.is_null() || (buf.is_null() && buf_len > 0) {
+        return Status::NullPointer;
+    }
+    *out_len = data.len();
+    if data.len() > buf_len {
+        return Status::ShortBuf;
+    }
+    ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len());
+    Status::Ok
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn name_conversion() {
+        let name = CString::new("www.example.com").unwrap();
+        let mut buf = [0u8; 255];
+        let mut len = 0;
+        unsafe {
+            assert_eq!(
+                domain_name_from_str(
+                    name.as_ptr(),
+                    buf.as_mut_ptr(),
+                    4,
+                    &mut len
+                ),
+                Status::ShortBuf
+            );
+            assert_eq!(len, 17);
+            assert_eq!(
+                domain_name_from_str(
+                    name.as_ptr(),
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len
+                ),
+                Status::Ok
+            );
+        }
+        assert_eq!(&buf[..len], b"\x03www\x07example\x03com\x00");
+
+        let mut text = [0 as c_char; 64];
+        let mut text_len = 0;
+        unsafe {
+            assert_eq!(
+                domain_name_to_str(
+                    buf.as_ptr(),
+                    len,
+                    text.as_mut_ptr(),
+                    text.len(),
+                    &mut text_len
+                ),
+                Status::Ok
+            );
+            assert_eq!(
+                CStr::from_ptr(text.as_ptr()).to_str().unwrap(),
+                "www.example.com."
+            );
+        }
+        assert_eq!(text_len, 16);
+
+        let bad = CString::new("a..b").unwrap();
+        unsafe {
+            assert_eq!(
+                domain_name_from_str(
+                    bad.as_ptr(),
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len
+                ),
+                Status::InvalidArgument
+            );
+            assert_eq!(
+                domain_name_from_str(
+                    ptr::null(),
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len
+                ),
+                Status::NullPointer
+            );
+        }
+    }
+
+    #[test]
+    fn query_and_parse() {
+        let name = CString::new("example.com").unwrap();
+        let mut buf = [0u8; 512];
+        let mut len = 0;
+        unsafe {
+            assert_eq!(
+                domain_query_new(
+                    name.as_ptr(),
+                    Rtype::Aaaa.to_int(),
+                    Class::In.to_int(),
+                    0x1234,
+                    true,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len
+                ),
+                Status::Ok
+            );
+        }
+
+        // Turn the query into an answer with one record.
+        let query = Message::from_octets(&buf[..len]).unwrap();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, crate::base::iana::Rcode::NoError)
+            .unwrap();
+        answer
+            .push((
+                Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+                3600,
+                crate::rdata::Aaaa::from_str("2001:db8::1").unwrap(),
+            ))
+            .unwrap();
+        let answer = answer.finish();
+
+        let mut msg = ptr::null_mut();
+        let mut header = HeaderInfo::default();
+        let mut question = QuestionInfo {
+            qname: ptr::null(),
+            qname_len: 0,
+            qtype: 0,
+            qclass: 0,
+        };
+        unsafe {
+            assert_eq!(
+                domain_message_parse(answer.as_ptr(), answer.len(), &mut msg),
+                Status::Ok
+            );
+            assert_eq!(domain_message_header(msg, &mut header), Status::Ok);
+            assert_eq!(header.id, 0x1234);
+            assert_eq!(header.flags & 0x8100, 0x8100); // QR and RD
+            assert_eq!(header.rcode, 0);
+            assert_eq!(header.qdcount, 1);
+            assert_eq!(header.ancount, 1);
+
+            assert_eq!(domain_message_question_count(msg), 1);
+            assert_eq!(
+                domain_message_question(msg, 0, &mut question),
+                Status::Ok
+            );
+            assert_eq!(
+                slice::from_raw_parts(question.qname, question.qname_len),
+                b"\x07example\x03com\x00"
+            );
+            assert_eq!(question.qtype, 28);
+
+            assert_eq!(domain_message_record_count(msg), 1);
+            let mut record = RecordInfo {
+                section: Section::Additional,
+                owner: ptr::null(),
+                owner_len: 0,
+                rtype: 0,
+                class: 0,
+                ttl: 0,
+                data: ptr::null(),
+                data_len: 0,
+            };
+            assert_eq!(
+                domain_message_record(msg, 0, &mut record),
+                Status::Ok
+            );
+            assert_eq!(record.section, Section::Answer);
+            assert_eq!(
+                slice::from_raw_parts(record.owner, record.owner_len),
+                b"\x07example\x03com\x00"
+            );
+            assert_eq!(record.rtype, 28);
+            assert_eq!(record.ttl, 3600);
+            assert_eq!(
+                slice::from_raw_parts(record.data, record.data_len),
+                &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+            );
+            assert_eq!(
+                domain_message_record(msg, 1, &mut record),
+                Status::OutOfRange
+            );
+            domain_message_free(msg);
+
+            assert_eq!(
+                domain_message_parse(answer.as_ptr(), 5, &mut msg),
+                Status::ParseError
+            );
+        }
+    }
+
+    #[test]
+    fn host_addr() {
+        let addr = HostAddr::from(IpAddr::from([192, 0, 2, 1]));
+        assert_eq!(addr.family, 4);
+        assert_eq!(&addr.addr[..4], &[192, 0, 2, 1]);
+        assert_eq!(&addr.addr[4..], &[0; 12]);
+    }
+}
@@ -19,8 +19,17 @@
 //!
 //! Currently, there are the following modules:
 //!
+//! * [bench]: generating query load for testing servers,
+//! * [capi]: a C API for embedding the crate into applications written
+//!   in other languages,
+//! * [localzone]: answering queries from locally configured zones and
+//!   records,
 //! * [master]: reading and writing of master files – also known as zone
 //!   files –, i.e., the textual representation of DNS data.
+//! * [metrics]: counters and histograms for operational monitoring,
+//! * [python]: Python bindings for names, messages, and the resolver,
+//! * [querylog]: structured per-query logs with privacy controls,
+//! * [server]: building blocks for DNS servers,
 //! * [sign]: support for DNSSEC signing,
 //! * [tsig]: support for securing DNS transactions with TSIG records,
 //! * [validate]: support for DNSSEC validation.
@@ -40,14 +49,33 @@
 //! the crate and to keep the amount of compiled code and dependencies small
 //! if these parts are not required.
 //!
+//! * `bench`: a load generator for testing servers. This will enable the
+//!   [bench] module and the `resolv` feature.
 //! * `bytes`: enables using the types `Bytes` and `BytesMut` from the
 //!    [bytes] crate as octet sequences.
+//! * `capi`: a C API for the core functionality. This will enable the
+//!   [capi] module and the `resolv-sync` feature.
 //! * `chrono`: adds the [chrono] crate as a dependency. This adds support
 //!   for generating serial numbers from time stamps.
+//! * `flate2`: adds the [flate2] crate as a dependency. This adds support
+//!   for writing gzip compressed zones in the [sign] module.
+//! * `hyper`: adds the [hyper] crate as a dependency. This adds support
+//!   for serving DNS over HTTPS connections via HTTP/2 and HTTP/1.1 in the
+//!   [server] module.
+//! * `idna`: adds the [idna] crate as a dependency. This adds support for
+//!   checking and converting internationalized host names. It needs the
+//!   `std` feature, too.
 //! * `master`: master file (also known as zone file) parsing and
 //!   construction. This will enable the [master] module and currently
 //!   enables the `bytes`, `chrono`, and `std` features.
+//! * `metrics`: metrics of resolvers and servers for monitoring. This will
+//!   enable the [metrics] module and requires the `std` feature.
+//! * `python`: Python bindings via the [pyo3] crate. This will enable the
+//!   [python] module and the `resolv-sync` feature.
 //! * `ring`: enables crypto functionality via the [ring] crate.
+//! * `server`: building blocks for DNS servers. This will enable the
+//!   [server] module as well as the `bytes`, `futures`, `std`, `tokio`,
+//!   and `libc` features.
 //! * `sign`: basic DNSSEC signing support. This will enable the [sign]
 //!   module and requires the `std` feature. Note that this will not directly
 //!   enable actually signing. For that you will also need to pick a crypto
@@ -63,18 +91,32 @@
 //! * `validate`: basic DNSSEC validation support. This feature enables the
 //!   [validate] module and currently also enables the `std` and `ring`
 //!   features.
+//! * `zstd`: adds the [zstd] crate as a dependency. This adds support for
+//!   writing zstd compressed zones in the [sign] module.
 //!
 //! [base]: base/index.html
+//! [bench]: bench/index.html
+//! [capi]: capi/index.html
+//! [localzone]: localzone/index.html
 //! [master]: master/index.html
+//! [metrics]: metrics/index.html
+//! [python]: python/index.html
+//! [querylog]: querylog/index.html
 //! [rdata]: rdata/index.html
+//! [server]: server/index.html
 //! [sign]: sign/index.html
 //! [tsig]: tsig/index.html
 //! [validate]: valiate/index.html
 //! [bytes]: https://docs.rs/bytes/
 //! [domain-resolv]: https://docs.rs/domain-resolv/
 //! [chrono]: https://docs.rs/chrono/
+//! [flate2]: https://docs.rs/flate2/
+//! [hyper]: https://docs.rs/hyper/
+//! [idna]: https://docs.rs/idna/
+//! [pyo3]: https://docs.rs/pyo3/
 //! [ring]: https://docs.rs/ring/
 //! [smallvec]: https://docs.rs/smallvec/
+//! [zstd]: https://docs.rs/zstd/
 
 #![no_std]
 
@@ -87,9 +129,16 @@ extern crate std;
 extern crate core;
 
 pub mod base;
+pub mod bench;
+pub mod capi;
+pub mod localzone;
 pub mod master;
+pub mod metrics;
+pub mod python;
+pub mod querylog;
 pub mod rdata;
 pub mod resolv;
+pub mod server;
 pub mod sign;
 pub mod test;
 pub mod tsig;
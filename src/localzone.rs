@@ -0,0 +1,700 @@
+//! Answering queries from locally configured data.
+//!
+//! Both resolvers and servers sometimes need to answer queries for
+//! certain names themselves rather than sending them upstream or looking
+//! them up in the zones they serve. The [`LocalZones`] type collects such
+//! local data. It consists of local zones and local records.
+//!
+//! A local zone is a domain name and a [`ZoneKind`] that determines how
+//! queries for the name and all names below it are answered if there is
+//! no local data for the query name. A static zone answers with NXDOMAIN,
+//! a redirect zone with the data of its apex, a refusing zone with
+//! REFUSED, and a denying zone drops the query. A transparent zone lets
+//! the query through for normal processing. Local records are always
+//! answered. Adding a record for a name outside of any local zone creates
+//! a transparent zone for the name.
+//!
+//! [`LocalZones::rfc6303`] creates local zones for the address spaces
+//! listed in RFC 6303 whose reverse mapping zones should be served locally
+//! rather than cause queries to the root and `arpa` servers. They are
+//! static zones with the SOA and NS records suggested by the RFC. Reverse
+//! queries for the loopback addresses are answered with `localhost.`.
+//!
+//! The stub resolver uses local zones via
+//! [`ResolvOptions::local_zones`]. For servers, [`LocalZones`] implements
+//! the [`Service`] trait.
+//!
+//! [`LocalZones`]: struct.LocalZones.html
+//! [`LocalZones::rfc6303`]: struct.LocalZones.html#method.rfc6303
+//! [`ZoneKind`]: enum.ZoneKind.html
+//! [`ResolvOptions::local_zones`]: ../resolv/stub/conf/struct.ResolvOptions.html#structfield.local_zones
+//! [`Service`]: ../server/service/trait.Service.html
+#![cfg(feature = "std")]
+
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message_builder::AnswerBuilder;
+#[cfg(feature = "server")]
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{Compose, OctetsBuilder, ShortBuf};
+use crate::base::rdata::{RecordData, UnknownRecordData};
+use crate::base::record::{AsRecord, Record};
+use crate::rdata::{Ns, Ptr, Soa};
+#[cfg(feature = "server")]
+use crate::server::service::{PooledBuf, Request, Service};
+use core::str::FromStr;
+use std::collections::HashMap;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The TTL of the records of the RFC 6303 zones.
+const RFC6303_TTL: u32 = 10800;
+
+/// The zones from RFC 6303 as well as 100.64.0.0/10 from RFC 6598.
+const RFC6303_ZONES: &[&str] = &[
+    // RFC 1122 and RFC 5735
+    "0.in-addr.arpa",
+    "127.in-addr.arpa",
+    "254.169.in-addr.arpa",
+    "2.0.192.in-addr.arpa",
+    "100.51.198.in-addr.arpa",
+    "113.0.203.in-addr.arpa",
+    "255.255.255.255.in-addr.arpa",
+    // RFC 1918
+    "10.in-addr.arpa",
+    "16.172.in-addr.arpa",
+    "17.172.in-addr.arpa",
+    "18.172.in-addr.arpa",
+    "19.172.in-addr.arpa",
+    "20.172.in-addr.arpa",
+    "21.172.in-addr.arpa",
+    "22.172.in-addr.arpa",
+    "23.172.in-addr.arpa",
+    "24.172.in-addr.arpa",
+    "25.172.in-addr.arpa",
+    "26.172.in-addr.arpa",
+    "27.172.in-addr.arpa",
+    "28.172.in-addr.arpa",
+    "29.172.in-addr.arpa",
+    "30.172.in-addr.arpa",
+    "31.172.in-addr.arpa",
+    "168.192.in-addr.arpa",
+    // RFC 4291
+    "0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+     ip6.arpa",
+    "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+     ip6.arpa",
+    "8.e.f.ip6.arpa",
+    "9.e.f.ip6.arpa",
+    "a.e.f.ip6.arpa",
+    "b.e.f.ip6.arpa",
+    // RFC 4193
+    "d.f.ip6.arpa",
+    // RFC 3849
+    "8.b.d.0.1.0.0.2.ip6.arpa",
+];
+
+/// The loopback reverse names answered with `localhost.`.
+const LOOPBACK_NAMES: &[&str] = &[
+    "1.0.0.127.in-addr.arpa",
+    "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+     ip6.arpa",
+];
+
+//------------ LocalZones ----------------------------------------------------
+
+/// A collection of local zones and records.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Clone, Debug, Default)]
+pub struct LocalZones {
+    /// The zones by their lowercase apex in wire format.
+    zones: HashMap<Vec<u8>, ZoneKind>,
+
+    /// The records by their lowercase owner in wire format.
+    records: HashMap<Vec<u8>, Vec<LocalRecord>>,
+}
+
+/// A local record.
+///
+/// The record data is kept in wire format.
+pub type LocalRecord = Record<Dname<Vec<u8>>, UnknownRecordData<Vec<u8>>>;
+
+impl LocalZones {
+    /// Creates an empty collection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a collection with the zones from RFC 6303.
+    ///
+    /// In addition to the zones listed in RFC 6303, this includes the
+    /// reverse zones for the shared address space 100.64.0.0/10 from RFC
+    /// 6598. Each zone is a static zone with an SOA and NS record at its
+    /// apex. The reverse names of the IPv4 and IPv6 loopback addresses
+    /// have a PTR record for `localhost.`.
+    pub fn rfc6303() -> Self {
+        let mut res = Self::new();
+        let localhost = name("localhost");
+        let shared =
+            (64..128).map(|octet| format!("{}.100.in-addr.arpa", octet));
+        let zones = RFC6303_ZONES.iter().map(|zone| String::from(*zone));
+        for zone in zones.chain(shared) {
+            let apex = name(&zone);
+            res.add_zone(&apex, ZoneKind::Static);
+            let soa = Soa::new(
+                apex.clone(),
+                name("nobody.invalid"),
+                1.into(),
+                3600,
+                1200,
+                604800,
+                10800,
+            );
+            // Composing into a vec can’t fail.
+            res.add_record((&apex, Class::In, RFC6303_TTL, soa))
+                .unwrap();
+            res.add_record((
+                &apex,
+                Class::In,
+                RFC6303_TTL,
+                Ns::new(apex.clone()),
+            ))
+            .unwrap();
+        }
+        for owner in LOOPBACK_NAMES {
+            res.add_record((
+                name(owner),
+                Class::In,
+                RFC6303_TTL,
+                Ptr::new(localhost.clone()),
+            ))
+            .unwrap();
+        }
+        res
+    }
+
+    /// Returns whether there are no zones and records.
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty() && self.records.is_empty()
+    }
+
+    /// Adds a local zone.
+    ///
+    /// If there already is a zone with this apex, its kind is replaced.
+    pub fn add_zone(&mut self, apex: &impl ToDname, kind: ZoneKind) {
+        self.zones.insert(key(apex), kind);
+    }
+
+    /// Removes a local zone.
+    ///
+    /// Records for names within the zone are kept. Returns whether there
+    /// was a zone with this apex.
+    pub fn remove_zone(&mut self, apex: &impl ToDname) -> bool {
+        self.zones.remove(&key(apex)).is_some()
+    }
+
+    /// Adds a local record.
+    ///
+    /// Only records of class IN are answered. If the owner of the record
+    /// is not within a local zone, a transparent zone is created for it.
+    ///
+    /// Returns an error if the record data cannot be composed.
+    pub fn add_record(
+        &mut self,
+        record: impl AsRecord,
+    ) -> Result<(), ShortBuf> {
+        let mut data = Vec::new();
+        record.data().compose(&mut data)?;
+        let owner = record.owner().to_vec();
+        let owner_key = key(&owner);
+        if self.find_zone(&owner_key).is_none() {
+            self.zones.insert(owner_key.clone(), ZoneKind::Transparent);
+        }
+        self.records.entry(owner_key).or_default().push(Record::new(
+            owner,
+            record.class(),
+            record.ttl(),
+            UnknownRecordData::from_octets(record.data().rtype(), data),
+        ));
+        Ok(())
+    }
+
+    /// Removes all local records for a name.
+    ///
+    /// Returns the number of records removed.
+    pub fn remove_records(&mut self, owner: &impl ToDname) -> usize {
+        self.records
+            .remove(&key(owner))
+            .map_or(0, |records| records.len())
+    }
+
+    /// Determines the local answer to a query.
+    ///
+    /// Returns `None` if the query isn’t answered locally and should be
+    /// processed normally.
+    pub fn lookup(
+        &self,
+        qname: &impl ToDname,
+        qtype: Rtype,
+        qclass: Class,
+    ) -> Option<LocalAnswer<'_>> {
+        if qclass != Class::In {
+            return None;
+        }
+        let qkey = key(qname);
+        let (apex, kind) = self.find_zone(&qkey)?;
+        if let Some(records) = self.records.get(&qkey) {
+            return Some(LocalAnswer::Records(
+                self.select(records, qtype, apex, false),
+            ));
+        }
+        match kind {
+            ZoneKind::Transparent => {
+                if self.has_descendants(&qkey) {
+                    Some(LocalAnswer::Records(
+                        self.negative(apex, Rcode::NoError),
+                    ))
+                } else {
+                    None
+                }
+            }
+            ZoneKind::Static => {
+                let rcode = if self.has_descendants(&qkey) {
+                    Rcode::NoError
+                } else {
+                    Rcode::NXDomain
+                };
+                Some(LocalAnswer::Records(self.negative(apex, rcode)))
+            }
+            ZoneKind::Redirect => {
+                let records = self.records.get(apex)?;
+                Some(LocalAnswer::Records(
+                    self.select(records, qtype, apex, true),
+                ))
+            }
+            ZoneKind::Refuse => Some(LocalAnswer::Refuse),
+            ZoneKind::Deny => Some(LocalAnswer::Drop),
+        }
+    }
+
+    /// Returns the closest zone enclosing the name with the given key.
+    fn find_zone(&self, key: &[u8]) -> Option<(&[u8], ZoneKind)> {
+        let mut pos = 0;
+        loop {
+            if let Some((apex, kind)) = self.zones.get_key_value(&key[pos..])
+            {
+                return Some((apex, *kind));
+            }
+            let len = usize::from(*key.get(pos)?);
+            if len == 0 {
+                return None;
+            }
+            pos += len + 1;
+        }
+    }
+
+    /// Returns whether there are records for names below the given key.
+    fn has_descendants(&self, key: &[u8]) -> bool {
+        self.records
+            .keys()
+            .any(|other| other.len() > key.len() && is_below(other, key))
+    }
+
+    /// Selects the records of `qtype` from the records of a name.
+    fn select<'a>(
+        &'a self,
+        records: &'a [LocalRecord],
+        qtype: Rtype,
+        apex: &[u8],
+        redirect: bool,
+    ) -> LocalRecords<'a> {
+        let mut answer: Vec<_> = records
+            .iter()
+            .filter(|record| {
+                record.class() == Class::In
+                    && (qtype == Rtype::Any || record.rtype() == qtype)
+            })
+            .collect();
+        if answer.is_empty() {
+            answer = records
+                .iter()
+                .filter(|record| {
+                    record.class() == Class::In
+                        && record.rtype() == Rtype::Cname
+                })
+                .collect();
+        }
+        if answer.is_empty() {
+            return self.negative(apex, Rcode::NoError);
+        }
+        LocalRecords {
+            rcode: Rcode::NoError,
+            answer,
+            authority: Vec::new(),
+            redirect,
+        }
+    }
+
+    /// Creates a negative answer with the zone’s SOA record if it has one.
+    fn negative(&self, apex: &[u8], rcode: Rcode) -> LocalRecords<'_> {
+        let authority = self
+            .records
+            .get(apex)
+            .map(|records| {
+                records
+                    .iter()
+                    .filter(|record| record.rtype() == Rtype::Soa)
+                    .collect()
+            })
+            .unwrap_or_default();
+        LocalRecords {
+            rcode,
+            answer: Vec::new(),
+            authority,
+            redirect: false,
+        }
+    }
+}
+
+//--- Service
+
+#[cfg(feature = "server")]
+impl Service for LocalZones {
+    /// Answers a query from the local zones.
+    ///
+    /// Returns `None` both if the query isn’t answered locally and if it
+    /// is to be dropped. Use [`lookup`] to tell the two cases apart.
+    ///
+    /// [`lookup`]: #method.lookup
+    fn call(
+        &self,
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf> {
+        let msg = request.message();
+        if msg.header().qr() {
+            return None;
+        }
+        let question = msg.first_question()?;
+        let qname = question.qname();
+        let answer =
+            self.lookup(&qname, question.qtype(), question.qclass())?;
+        let builder =
+            builder.start_answer_verbatim(msg, answer.rcode()?).ok()?;
+        answer.compose(&qname, builder).ok()
+    }
+}
+
+//------------ ZoneKind ------------------------------------------------------
+
+/// How queries within a local zone without local data are answered.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ZoneKind {
+    /// Answer with NXDOMAIN or NODATA for empty non-terminals.
+    Static,
+
+    /// Process the query normally.
+    Transparent,
+
+    /// Answer with the records at the apex of the zone.
+    Redirect,
+
+    /// Answer with REFUSED.
+    Refuse,
+
+    /// Don’t answer at all.
+    Deny,
+}
+
+//------------ LocalAnswer ---------------------------------------------------
+
+/// The local answer to a query.
+#[derive(Clone, Debug)]
+pub enum LocalAnswer<'a> {
+    /// Answer with the given records.
+    Records(LocalRecords<'a>),
+
+    /// Answer with REFUSED.
+    Refuse,
+
+    /// Don’t answer.
+    Drop,
+}
+
+impl<'a> LocalAnswer<'a> {
+    /// Returns the response code of the answer.
+    ///
+    /// Returns `None` if the query should be dropped.
+    pub fn rcode(&self) -> Option<Rcode> {
+        match *self {
+            LocalAnswer::Records(ref records) => Some(records.rcode()),
+            LocalAnswer::Refuse => Some(Rcode::Refused),
+            LocalAnswer::Drop => None,
+        }
+    }
+
+    /// Adds the records of the answer to a response.
+    ///
+    /// The response should have been started with the answer’s
+    /// [`rcode`]. Records with the apex of a redirect zone as their owner
+    /// are added with `qname` as their owner instead.
+    ///
+    /// [`rcode`]: #method.rcode
+    pub fn compose<Target: OctetsBuilder>(
+        &self,
+        qname: &impl ToDname,
+        builder: AnswerBuilder<Target>,
+    ) -> Result<Target, ShortBuf> {
+        match *self {
+            LocalAnswer::Records(ref records) => {
+                records.compose(qname, builder)
+            }
+            _ => Ok(builder.finish()),
+        }
+    }
+}
+
+//------------ LocalRecords --------------------------------------------------
+
+/// The records of a local answer.
+#[derive(Clone, Debug)]
+pub struct LocalRecords<'a> {
+    /// The response code.
+    rcode: Rcode,
+
+    /// The records for the answer section.
+    answer: Vec<&'a LocalRecord>,
+
+    /// The records for the authority section.
+    authority: Vec<&'a LocalRecord>,
+
+    /// Whether the answer records are to be renamed to the query name.
+    redirect: bool,
+}
+
+impl<'a> LocalRecords<'a> {
+    /// Returns the response code.
+    pub fn rcode(&self) -> Rcode {
+        self.rcode
+    }
+
+    /// Returns the records for the answer section.
+    pub fn answer(&self) -> &[&'a LocalRecord] {
+        &self.answer
+    }
+
+    /// Returns the records for the authority section.
+    pub fn authority(&self) -> &[&'a LocalRecord] {
+        &self.authority
+    }
+
+    /// Adds the records to a response.
+    fn compose<Target: OctetsBuilder>(
+        &self,
+        qname: &impl ToDname,
+        mut builder: AnswerBuilder<Target>,
+    ) -> Result<Target, ShortBuf> {
+        for record in &self.answer {
+            let data = UnknownRecordData::from_octets(
+                record.rtype(),
+                record.data().data().as_slice(),
+            );
+            if self.redirect {
+                builder.push((qname, record.class(), record.ttl(), data))?;
+            } else {
+                builder.push((
+                    record.owner(),
+                    record.class(),
+                    record.ttl(),
+                    data,
+                ))?;
+            }
+        }
+        let mut builder = builder.authority();
+        for record in &self.authority {
+            builder.push(*record)?;
+        }
+        Ok(builder.finish())
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// Returns the lowercase wire format of a name.
+fn key<N: ToDname + ?Sized>(name: &N) -> Vec<u8> {
+    let mut res = Vec::new();
+    for label in name.iter_labels() {
+        res.push(label.len() as u8);
+        res.extend(label.as_slice().iter().map(u8::to_ascii_lowercase));
+    }
+    res
+}
+
+/// Returns whether the name `key` is at or below the name `suffix`.
+///
+/// Both names are given in wire format.
+fn is_below(key: &[u8], suffix: &[u8]) -> bool {
+    let mut pos = 0;
+    while key.len() - pos >= suffix.len() {
+        if &key[pos..] == suffix {
+            return true;
+        }
+        match key.get(pos) {
+            Some(&0) | None => return false,
+            Some(&len) => pos += usize::from(len) + 1,
+        }
+    }
+    false
+}
+
+/// Converts a string into a name.
+///
+/// This is only used with the static names of this module.
+fn name(s: &str) -> Dname<Vec<u8>> {
+    Dname::from_str(s).unwrap()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::rdata::A;
+    use std::net::Ipv4Addr;
+    use std::string::ToString;
+
+    fn lookup(
+        zones: &LocalZones,
+        qname: &str,
+        qtype: Rtype,
+    ) -> Option<(Rcode, usize, usize)> {
+        let answer = zones.lookup(&name(qname), qtype, Class::In)?;
+        let rcode = answer.rcode()?;
+        match answer {
+            LocalAnswer::Records(records) => Some((
+                rcode,
+                records.answer().len(),
+                records.authority().len(),
+            )),
+            _ => Some((rcode, 0, 0)),
+        }
+    }
+
+    #[test]
+    fn rfc6303() {
+        let zones = LocalZones::rfc6303();
+        assert_eq!(
+            lookup(&zones, "1.0.168.192.in-addr.arpa", Rtype::Ptr),
+            Some((Rcode::NXDomain, 0, 1))
+        );
+        assert_eq!(
+            lookup(&zones, "168.192.IN-ADDR.ARPA", Rtype::Soa),
+            Some((Rcode::NoError, 1, 0))
+        );
+        assert_eq!(
+            lookup(&zones, "1.0.0.127.in-addr.arpa", Rtype::Ptr),
+            Some((Rcode::NoError, 1, 0))
+        );
+        assert_eq!(
+            lookup(&zones, "9.8.100.100.in-addr.arpa", Rtype::Ptr),
+            Some((Rcode::NXDomain, 0, 1))
+        );
+        assert_eq!(lookup(&zones, "1.0.0.8.in-addr.arpa", Rtype::Ptr), None);
+        assert_eq!(lookup(&zones, "1.100.in-addr.arpa", Rtype::Ptr), None);
+    }
+
+    #[test]
+    fn local_data() {
+        let mut zones = LocalZones::new();
+        zones
+            .add_record((
+                name("www.example.com"),
+                Class::In,
+                60,
+                A::new(Ipv4Addr::new(192, 0, 2, 1)),
+            ))
+            .unwrap();
+        zones
+            .add_record((
+                name("a.b.example.org"),
+                Class::In,
+                60,
+                A::new(Ipv4Addr::new(192, 0, 2, 2)),
+            ))
+            .unwrap();
+        zones.add_zone(&name("example.org"), ZoneKind::Static);
+        zones.add_zone(&name("ads.example"), ZoneKind::Redirect);
+        zones
+            .add_record((
+                name("ads.example"),
+                Class::In,
+                60,
+                A::new(Ipv4Addr::new(127, 0, 0, 1)),
+            ))
+            .unwrap();
+        zones.add_zone(&name("refused.example"), ZoneKind::Refuse);
+        zones.add_zone(&name("denied.example"), ZoneKind::Deny);
+
+        // Transparent zone for the local record.
+        assert_eq!(
+            lookup(&zones, "www.example.com", Rtype::A),
+            Some((Rcode::NoError, 1, 0))
+        );
+        assert_eq!(
+            lookup(&zones, "www.example.com", Rtype::Aaaa),
+            Some((Rcode::NoError, 0, 0))
+        );
+        assert_eq!(lookup(&zones, "mail.example.com", Rtype::A), None);
+
+        // Static zone with an empty non-terminal.
+        assert_eq!(
+            lookup(&zones, "b.example.org", Rtype::A),
+            Some((Rcode::NoError, 0, 0))
+        );
+        assert_eq!(
+            lookup(&zones, "c.example.org", Rtype::A),
+            Some((Rcode::NXDomain, 0, 0))
+        );
+
+        assert_eq!(
+            lookup(&zones, "x.refused.example", Rtype::A),
+            Some((Rcode::Refused, 0, 0))
+        );
+        assert_eq!(lookup(&zones, "denied.example", Rtype::A), None);
+        assert!(matches!(
+            zones.lookup(&name("denied.example"), Rtype::A, Class::In),
+            Some(LocalAnswer::Drop)
+        ));
+
+        // Redirect renames the apex records.
+        let query = {
+            let mut msg = MessageBuilder::new_vec().question();
+            msg.push((name("tracker.ads.example"), Rtype::A)).unwrap();
+            msg.into_message()
+        };
+        let qname = name("tracker.ads.example");
+        let answer = zones.lookup(&qname, Rtype::A, Class::In).unwrap();
+        let builder = MessageBuilder::new_vec()
+            .start_answer(&query, answer.rcode().unwrap())
+            .unwrap();
+        let response =
+            Message::from_octets(answer.compose(&qname, builder).unwrap())
+                .unwrap();
+        let record = response
+            .answer()
+            .unwrap()
+            .limit_to::<A>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.owner().to_string(), "tracker.ads.example");
+        assert_eq!(record.data().addr(), Ipv4Addr::new(127, 0, 0, 1));
+    }
+}
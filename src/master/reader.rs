@@ -131,6 +131,7 @@ impl fmt::Display for ReaderItem {
 mod test {
     use super::*;
     use crate::master::scan::ScanError;
+    use std::vec::Vec;
 
     #[test]
     fn print() {
@@ -172,4 +173,280 @@ $INCLUDE <SUBSYS>ISI-MAILBOXES.TXT"[..],
             }
         }
     }
+
+    fn read(zone: &str) -> Result<Vec<MasterRecord>, ScanError> {
+        Reader::new(zone)
+            .filter_map(|item| match item {
+                Ok(ReaderItem::Record(record)) => Some(Ok(record)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generic_data() {
+        use crate::base::iana::Rtype;
+        use crate::base::octets::Compose;
+        use crate::base::rdata::UnknownRecordData;
+        use crate::rdata::{MasterRecordData, Mx, A};
+        use std::str::FromStr;
+        use std::string::ToString;
+
+        let records = read(
+            "$ORIGIN example.\n\
+             @ 3600 IN TYPE65280 \\# 3 abcdef\n\
+             @ 3600 IN type65281 \\# 0\n\
+             @ 3600 CLASS65281 TYPE65282 \\# 4 ( 0102\n 0304 )\n\
+             @ 3600 IN A \\# 4 c0000201\n\
+             @ 3600 IN MX \\# 16 000a046d61696c076578616d706c6500\n",
+        )
+        .unwrap();
+        type Data = MasterRecordData<Bytes, Dname<Bytes>>;
+        let unknown = |rtype, data: &[u8]| {
+            Data::Other(UnknownRecordData::from_octets(
+                Rtype::Int(rtype),
+                Bytes::copy_from_slice(data),
+            ))
+        };
+        assert_eq!(records[0].data(), &unknown(65280, b"\xab\xcd\xef"));
+        assert_eq!(records[1].data(), &unknown(65281, b""));
+        assert_eq!(records[2].class(), Class::Int(65281));
+        assert_eq!(records[2].data(), &unknown(65282, b"\x01\x02\x03\x04"));
+        assert_eq!(records[3].data(), &Data::A(A::from_octets(192, 0, 2, 1)));
+        assert_eq!(
+            records[4].data(),
+            &Data::Mx(Mx::new(10, Dname::from_str("mail.example.").unwrap()))
+        );
+
+        // Writing and reading again results in the same records and the
+        // unknown data composes to the original octets.
+        for record in &records {
+            let text = record.to_string();
+            let again = read(&text).unwrap();
+            assert_eq!(again.len(), 1, "{}", text);
+            assert_eq!(again[0].class(), record.class(), "{}", text);
+            assert_eq!(again[0].data(), record.data(), "{}", text);
+        }
+        let mut wire = Vec::new();
+        records[0].data().compose(&mut wire).unwrap();
+        assert_eq!(wire, b"\xab\xcd\xef");
+
+        // The length has to match the data and data for known types has to
+        // parse completely.
+        assert!(read("example. 3600 IN TYPE65280 \\# 4 abcdef\n").is_err());
+        assert!(read("example. 3600 IN TYPE65280 \\# 2 abcdef\n").is_err());
+        assert!(read("example. 3600 IN TYPE65280 abcdef\n").is_err());
+        assert!(read("example. 3600 IN A \\# 5 c000020101\n").is_err());
+    }
+
+    #[test]
+    fn historic_types() {
+        use crate::base::iana::{CertType, DigestAlg, SecAlg};
+        use crate::rdata::{Cert, Dlv, Kx, MasterRecordData, Ta};
+        use std::str::FromStr;
+
+        let records = read(
+            "$ORIGIN example.\n\
+             @ 3600 IN KX 10 KX.Example.\n\
+             @ 3600 IN CERT PGP 0 0 ( aGVs\n bG8= )\n\
+             @ 3600 IN CERT 65000 12345 RSASHA256 AAEC\n\
+             @ 3600 IN DHCID AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA=\n\
+             @ 3600 IN DLV 60485 5 1 2bb183af5f22588179a53b0a98631fad1a292118\n\
+             @ 3600 IN TA 60485 RSASHA1 1 2BB183AF5F22588179A53B0A98631FAD1A292118\n",
+        )
+        .unwrap();
+        type Data = MasterRecordData<Bytes, Dname<Bytes>>;
+        let digest = Bytes::from_static(
+            b"\x2b\xb1\x83\xaf\x5f\x22\x58\x81\x79\xa5\
+              \x3b\x0a\x98\x63\x1f\xad\x1a\x29\x21\x18",
+        );
+        assert_eq!(
+            records[0].data(),
+            &Data::Kx(Kx::new(10, Dname::from_str("kx.example.").unwrap()))
+        );
+        assert_eq!(
+            records[1].data(),
+            &Data::Cert(Cert::new(
+                CertType::Pgp,
+                0,
+                SecAlg::Int(0),
+                Bytes::from_static(b"hello")
+            ))
+        );
+        assert_eq!(
+            records[2].data(),
+            &Data::Cert(Cert::new(
+                CertType::Int(65000),
+                12345,
+                SecAlg::RsaSha256,
+                Bytes::from_static(b"\x00\x01\x02")
+            ))
+        );
+        match records[3].data() {
+            Data::Dhcid(dhcid) => {
+                assert_eq!(dhcid.data().len(), 35);
+                assert_eq!(&dhcid.data()[..3], b"\x00\x02\x01");
+            }
+            data => panic!("{:?}", data),
+        }
+        assert_eq!(
+            records[4].data(),
+            &Data::Dlv(Dlv::new(
+                60485,
+                SecAlg::RsaSha1,
+                DigestAlg::Sha1,
+                digest.clone()
+            ))
+        );
+        assert_eq!(
+            records[5].data(),
+            &Data::Ta(Ta::new(
+                60485,
+                SecAlg::RsaSha1,
+                DigestAlg::Sha1,
+                digest
+            ))
+        );
+        check_round_trip(&records);
+    }
+
+    #[test]
+    fn gateway_types() {
+        use crate::base::net::{Ipv4Addr, Ipv6Addr};
+        use crate::rdata::rfc4025::Gateway;
+        use crate::rdata::{Amtrelay, Ipseckey, MasterRecordData};
+        use std::str::FromStr;
+
+        let records = read(
+            "$ORIGIN example.\n\
+             @ 3600 IN IPSECKEY 10 0 2 . AQNRU3mG7TVTO2BkR47usntb102uFJtu\n\
+             @ 3600 IN IPSECKEY ( 10 1 2 192.0.2.38\n AQNRU3mG7TVTO2BkR47u )\n\
+             @ 3600 IN IPSECKEY 10 2 2 2001:0DB8:0:8002::2000:1 AQNRU3mG\n\
+             @ 3600 IN IPSECKEY 10 3 2 gw.example. AQNRU3mG\n\
+             @ 3600 IN IPSECKEY 10 1 0 192.0.2.3\n\
+             @ 3600 IN AMTRELAY 10 0 0 .\n\
+             @ 3600 IN AMTRELAY 10 0 1 203.0.113.15\n\
+             @ 3600 IN AMTRELAY 10 1 2 2001:db8::15\n\
+             @ 3600 IN AMTRELAY 128 1 3 amtrelays\n",
+        )
+        .unwrap();
+        type Data = MasterRecordData<Bytes, Dname<Bytes>>;
+        let name = |s| Dname::from_str(s).unwrap();
+        let key = Bytes::from_static(b"\x01\x03\x51\x53\x79\x86");
+        match records[0].data() {
+            Data::Ipseckey(data) => {
+                assert_eq!(data.precedence(), 10);
+                assert_eq!(data.gateway_type(), 0);
+                assert_eq!(data.algorithm(), 2);
+                assert_eq!(data.public_key().len(), 24);
+            }
+            data => panic!("{:?}", data),
+        }
+        match records[1].data() {
+            Data::Ipseckey(data) => {
+                assert_eq!(
+                    data.gateway(),
+                    &Gateway::<Dname<Bytes>>::Ipv4(Ipv4Addr::new(
+                        192, 0, 2, 38
+                    ))
+                );
+                assert_eq!(data.public_key().len(), 15);
+            }
+            data => panic!("{:?}", data),
+        }
+        assert_eq!(
+            records[2].data(),
+            &Data::Ipseckey(Ipseckey::new(
+                10,
+                2,
+                Gateway::Ipv6(Ipv6Addr::new(
+                    0x2001, 0xdb8, 0, 0x8002, 0, 0, 0x2000, 1
+                )),
+                key.clone()
+            ))
+        );
+        assert_eq!(
+            records[3].data(),
+            &Data::Ipseckey(Ipseckey::new(
+                10,
+                2,
+                Gateway::Name(name("gw.example.")),
+                key
+            ))
+        );
+        assert_eq!(
+            records[4].data(),
+            &Data::Ipseckey(Ipseckey::new(
+                10,
+                0,
+                Gateway::Ipv4(Ipv4Addr::new(192, 0, 2, 3)),
+                Bytes::new()
+            ))
+        );
+        assert_eq!(
+            records[5].data(),
+            &Data::Amtrelay(Amtrelay::new(10, false, Gateway::None))
+        );
+        assert_eq!(
+            records[6].data(),
+            &Data::Amtrelay(Amtrelay::new(
+                10,
+                false,
+                Gateway::Ipv4(Ipv4Addr::new(203, 0, 113, 15))
+            ))
+        );
+        assert_eq!(
+            records[7].data(),
+            &Data::Amtrelay(Amtrelay::new(
+                10,
+                true,
+                Gateway::Ipv6(Ipv6Addr::new(
+                    0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x15
+                ))
+            ))
+        );
+        assert_eq!(
+            records[8].data(),
+            &Data::Amtrelay(Amtrelay::new(
+                128,
+                true,
+                Gateway::Name(name("amtrelays.example."))
+            ))
+        );
+        check_round_trip(&records);
+
+        assert!(read("example. 3600 IN IPSECKEY 10 4 2 . AQNR").is_err());
+        assert!(read("example. 3600 IN AMTRELAY 10 2 0 .").is_err());
+        assert!(read("example. 3600 IN AMTRELAY 10 0 1 .").is_err());
+        assert!(read("example. 3600 IN IPSECKEY \\# 3 0a0402").is_err());
+    }
+
+    /// Checks that records read back from their presentation format.
+    ///
+    /// This checks both the regular format and the generic format of
+    /// RFC 3597.
+    fn check_round_trip(records: &[MasterRecord]) {
+        use crate::base::octets::Compose;
+        use std::string::ToString;
+
+        for record in records {
+            let text = record.to_string();
+            let again = read(&text).unwrap();
+            assert_eq!(again[0].data(), record.data(), "{}", text);
+
+            let mut wire = Vec::new();
+            record.data().compose(&mut wire).unwrap();
+            let mut generic = std::format!(
+                "example. 3600 IN {} \\# {} ",
+                record.rtype(),
+                wire.len()
+            );
+            for ch in &wire {
+                generic.push_str(&std::format!("{:02x}", ch));
+            }
+            let again = read(&generic).unwrap();
+            assert_eq!(again[0].data(), record.data(), "{}", generic);
+        }
+    }
 }
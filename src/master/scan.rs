@@ -458,6 +458,35 @@ impl<C: CharSource> Scanner<C> {
             },
         )
     }
+
+    /// Skips over the marker for record data in generic format.
+    ///
+    /// [RFC 3597] allows giving the data of any record as a word `\#`
+    /// followed by the data length and the data encoded in hex. Because
+    /// the marker is an escape sequence, it can’t be matched via
+    /// [`skip_literal`].
+    ///
+    /// [RFC 3597]: https://tools.ietf.org/html/rfc3597
+    /// [`skip_literal`]: #method.skip_literal
+    pub fn skip_generic_marker(&mut self) -> Result<(), ScanError> {
+        self.scan_word(
+            false,
+            |seen, symbol| match symbol {
+                Symbol::SimpleEscape('#') if !*seen => {
+                    *seen = true;
+                    Ok(())
+                }
+                _ => Err(SyntaxError::Expected("\\#".into())),
+            },
+            |seen| {
+                if seen {
+                    Ok(())
+                } else {
+                    Err(SyntaxError::Expected("\\#".into()))
+                }
+            },
+        )
+    }
 }
 
 /// # Complex Scanning
@@ -0,0 +1,659 @@
+//! Metrics for operational monitoring.
+//!
+//! This module provides counters and histograms that the resolver and
+//! server components update as they process queries. The collected values
+//! can be written in the [Prometheus text exposition format] for scraping
+//! by a monitoring system or read individually via the methods of the
+//! metric types for feeding them into some other metrics system.
+//!
+//! There are two collections of metrics: [`ResolverMetrics`] for the stub
+//! resolver and [`ServerMetrics`] for servers. A resolver starts updating
+//! its metrics once they have been attached via
+//! [`StubResolver::set_metrics`]. Similarly, the [`Proxy`] updates the
+//! server metrics attached via [`Proxy::set_metrics`]. Other servers can
+//! update the server metrics themselves.
+//!
+//! The building blocks [`Counter`], [`CounterVec`], and [`Histogram`] can
+//! be used for additional metrics.
+//!
+//! This module requires the `metrics` feature.
+//!
+//! [Prometheus text exposition format]: https://prometheus.io/docs/instrumenting/exposition_formats/
+//! [`ResolverMetrics`]: struct.ResolverMetrics.html
+//! [`ServerMetrics`]: struct.ServerMetrics.html
+//! [`Counter`]: struct.Counter.html
+//! [`CounterVec`]: struct.CounterVec.html
+//! [`Histogram`]: struct.Histogram.html
+//! [`StubResolver::set_metrics`]: ../resolv/stub/struct.StubResolver.html#method.set_metrics
+//! [`Proxy`]: ../server/proxy/struct.Proxy.html
+//! [`Proxy::set_metrics`]: ../server/proxy/struct.Proxy.html#method.set_metrics
+#![cfg(feature = "metrics")]
+
+use crate::base::iana::{Rcode, Rtype};
+use core::convert::TryFrom;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use std::collections::BTreeMap;
+use std::string::{String, ToString};
+use std::sync::Mutex;
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The upper bounds of the latency histogram buckets in seconds.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+    2.5, 5.0, 10.0,
+];
+
+//------------ ResolverMetrics -----------------------------------------------
+
+/// Metrics of a stub resolver.
+///
+/// Queries are counted by their query type and the response code of the
+/// answer. Queries that didn’t result in an answer at all are counted as
+/// failures instead. The latency is only observed for queries that were
+/// sent to upstream servers.
+#[derive(Debug)]
+pub struct ResolverMetrics {
+    /// The queries by query type and response code.
+    queries: CounterVec,
+
+    /// The queries that failed.
+    failures: CounterVec,
+
+    /// The time it took to receive an answer from upstream.
+    latency: Histogram,
+
+    /// The queries answered from the cache.
+    cache_hits: Counter,
+
+    /// The queries that had to be sent upstream despite a cache.
+    cache_misses: Counter,
+}
+
+impl ResolverMetrics {
+    /// Creates a new value with all metrics at zero.
+    pub fn new() -> Self {
+        ResolverMetrics {
+            queries: CounterVec::new(&["qtype", "rcode"]),
+            failures: CounterVec::new(&["qtype"]),
+            latency: Histogram::new(LATENCY_BUCKETS),
+            cache_hits: Counter::new(),
+            cache_misses: Counter::new(),
+        }
+    }
+
+    /// Records an answer for a query of type `qtype`.
+    ///
+    /// If the answer was received from upstream, `latency` is the time it
+    /// took.
+    pub fn record_answer(
+        &self,
+        qtype: Rtype,
+        rcode: Rcode,
+        latency: Option<Duration>,
+    ) {
+        self.queries.inc(&[&qtype, &rcode]);
+        if let Some(latency) = latency {
+            self.latency.observe(latency);
+        }
+    }
+
+    /// Records a query of type `qtype` that failed.
+    pub fn record_failure(&self, qtype: Rtype) {
+        self.failures.inc(&[&qtype]);
+    }
+
+    /// Records a cache lookup.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        if hit {
+            self.cache_hits.inc()
+        } else {
+            self.cache_misses.inc()
+        }
+    }
+
+    /// Returns the queries by query type and response code.
+    pub fn queries(&self) -> &CounterVec {
+        &self.queries
+    }
+
+    /// Returns the failed queries by query type.
+    pub fn failures(&self) -> &CounterVec {
+        &self.failures
+    }
+
+    /// Returns the latency of upstream queries.
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    /// Returns the number of queries answered from the cache.
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.get()
+    }
+
+    /// Returns the number of cache lookups that didn’t find an answer.
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses.get()
+    }
+
+    /// Returns the share of cache lookups that found an answer.
+    ///
+    /// Returns `None` if there haven’t been any lookups yet.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let hits = self.cache_hits();
+        let total = hits + self.cache_misses();
+        if total == 0 {
+            None
+        } else {
+            Some(hits as f64 / total as f64)
+        }
+    }
+
+    /// Writes the metrics in Prometheus text exposition format.
+    pub fn write_prometheus<W: fmt::Write>(
+        &self,
+        target: &mut W,
+    ) -> fmt::Result {
+        self.queries.write_prometheus(
+            target,
+            "domain_resolver_queries_total",
+            "Queries answered by the resolver.",
+        )?;
+        self.failures.write_prometheus(
+            target,
+            "domain_resolver_failures_total",
+            "Queries the resolver failed to answer.",
+        )?;
+        self.latency.write_prometheus(
+            target,
+            "domain_resolver_upstream_latency_seconds",
+            "Time until an answer was received from upstream.",
+        )?;
+        self.cache_hits.write_prometheus(
+            target,
+            "domain_resolver_cache_hits_total",
+            "Queries answered from the cache.",
+        )?;
+        self.cache_misses.write_prometheus(
+            target,
+            "domain_resolver_cache_misses_total",
+            "Cache lookups that did not find an answer.",
+        )
+    }
+}
+
+//--- Default
+
+impl Default for ResolverMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ ServerMetrics -------------------------------------------------
+
+/// Metrics of a server.
+///
+/// Queries are counted by the transport they were received over, their
+/// query type, and the response code of the response. Zone transfers are
+/// counted by their type and whether they succeeded.
+#[derive(Debug)]
+pub struct ServerMetrics {
+    /// The queries by transport, query type, and response code.
+    queries: CounterVec,
+
+    /// The time it took to produce a response.
+    latency: Histogram,
+
+    /// The zone transfers by type and result.
+    transfers: CounterVec,
+}
+
+impl ServerMetrics {
+    /// Creates a new value with all metrics at zero.
+    pub fn new() -> Self {
+        ServerMetrics {
+            queries: CounterVec::new(&["transport", "qtype", "rcode"]),
+            latency: Histogram::new(LATENCY_BUCKETS),
+            transfers: CounterVec::new(&["qtype", "result"]),
+        }
+    }
+
+    /// Records a response to a query.
+    ///
+    /// The `transport` is used as the label value for the transport, for
+    /// instance a [`Transport`] of the server module.
+    ///
+    /// [`Transport`]: ../server/acl/enum.Transport.html
+    pub fn record_query(
+        &self,
+        transport: &dyn fmt::Display,
+        qtype: Rtype,
+        rcode: Rcode,
+        latency: Duration,
+    ) {
+        self.queries.inc(&[transport, &qtype, &rcode]);
+        self.latency.observe(latency);
+    }
+
+    /// Records a zone transfer of type `qtype`, i.e., AXFR or IXFR.
+    pub fn record_transfer(&self, qtype: Rtype, success: bool) {
+        let result = if success { "success" } else { "failure" };
+        self.transfers.inc(&[&qtype, &result]);
+    }
+
+    /// Returns the queries by transport, query type, and response code.
+    pub fn queries(&self) -> &CounterVec {
+        &self.queries
+    }
+
+    /// Returns the time it took to produce responses.
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    /// Returns the zone transfers by type and result.
+    pub fn transfers(&self) -> &CounterVec {
+        &self.transfers
+    }
+
+    /// Writes the metrics in Prometheus text exposition format.
+    pub fn write_prometheus<W: fmt::Write>(
+        &self,
+        target: &mut W,
+    ) -> fmt::Result {
+        self.queries.write_prometheus(
+            target,
+            "domain_server_queries_total",
+            "Queries answered by the server.",
+        )?;
+        self.latency.write_prometheus(
+            target,
+            "domain_server_latency_seconds",
+            "Time it took to produce a response.",
+        )?;
+        self.transfers.write_prometheus(
+            target,
+            "domain_server_transfers_total",
+            "Zone transfers served.",
+        )
+    }
+}
+
+//--- Default
+
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ Counter -------------------------------------------------------
+
+/// A monotonically increasing counter.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    /// Creates a new counter starting at zero.
+    pub fn new() -> Self {
+        Counter(AtomicU64::new(0))
+    }
+
+    /// Increases the counter by one.
+    pub fn inc(&self) {
+        self.add(1)
+    }
+
+    /// Increases the counter by `value`.
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Returns the current value of the counter.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Writes the counter in Prometheus text exposition format.
+    pub fn write_prometheus<W: fmt::Write>(
+        &self,
+        target: &mut W,
+        name: &str,
+        help: &str,
+    ) -> fmt::Result {
+        write_header(target, name, help, "counter")?;
+        writeln!(target, "{} {}", name, self.get())
+    }
+}
+
+//------------ CounterVec ----------------------------------------------------
+
+/// A set of counters distinguished by label values.
+///
+/// The names of the labels are given when creating the value. Each
+/// distinct combination of values for these labels has its own counter
+/// which is created when it is first increased.
+#[derive(Debug)]
+pub struct CounterVec {
+    /// The names of the labels.
+    labels: &'static [&'static str],
+
+    /// The counters by label values.
+    counters: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    /// Creates a new, empty set of counters with the given label names.
+    pub fn new(labels: &'static [&'static str]) -> Self {
+        CounterVec {
+            labels,
+            counters: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the label names.
+    pub fn labels(&self) -> &'static [&'static str] {
+        self.labels
+    }
+
+    /// Increases the counter for the given label values by one.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the number of values differs from the number
+    /// of labels.
+    pub fn inc(&self, values: &[&dyn fmt::Display]) {
+        self.add(values, 1)
+    }
+
+    /// Increases the counter for the given label values by `value`.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the number of values differs from the number
+    /// of labels.
+    pub fn add(&self, values: &[&dyn fmt::Display], value: u64) {
+        assert_eq!(values.len(), self.labels.len());
+        let key = values.iter().map(|value| value.to_string()).collect();
+        *self.counters.lock().unwrap().entry(key).or_insert(0) += value;
+    }
+
+    /// Returns the value of the counter for the given label values.
+    pub fn get(&self, values: &[&str]) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(key, _)| {
+                key.iter().map(String::as_str).eq(values.iter().copied())
+            })
+            .map(|(_, count)| *count)
+            .unwrap_or(0)
+    }
+
+    /// Returns the sum of all counters.
+    pub fn total(&self) -> u64 {
+        self.counters.lock().unwrap().values().sum()
+    }
+
+    /// Writes the counters in Prometheus text exposition format.
+    pub fn write_prometheus<W: fmt::Write>(
+        &self,
+        target: &mut W,
+        name: &str,
+        help: &str,
+    ) -> fmt::Result {
+        write_header(target, name, help, "counter")?;
+        for (values, count) in self.counters.lock().unwrap().iter() {
+            target.write_str(name)?;
+            write_labels(target, self.labels, values, None)?;
+            writeln!(target, " {}", count)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Histogram -----------------------------------------------------
+
+/// A histogram of durations.
+///
+/// Observations are counted in buckets with fixed upper bounds given in
+/// seconds. In addition, the histogram keeps the number and sum of all
+/// observations.
+#[derive(Debug)]
+pub struct Histogram {
+    /// The upper bounds of the buckets in seconds.
+    bounds: &'static [f64],
+
+    /// The number of observations per bucket.
+    ///
+    /// Contrary to the exposition format, these aren’t cumulative. There is
+    /// one extra bucket at the end for observations above the last bound.
+    buckets: Vec<AtomicU64>,
+
+    /// The sum of all observations in nanoseconds.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    /// Creates a new histogram with the given bucket bounds in seconds.
+    ///
+    /// The bounds must be in increasing order.
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds an observation.
+    pub fn observe(&self, value: Duration) {
+        let secs = value.as_secs_f64();
+        let idx = self
+            .bounds
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(
+            u64::try_from(value.as_nanos()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Returns the number of observations.
+    pub fn count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns the sum of all observations.
+    pub fn sum(&self) -> Duration {
+        Duration::from_nanos(self.sum.load(Ordering::Relaxed))
+    }
+
+    /// Returns the upper bounds and cumulative counts of the buckets.
+    ///
+    /// The last bucket for observations above all bounds is not included.
+    /// Its cumulative count is that returned by [`count`].
+    ///
+    /// [`count`]: #method.count
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        let mut total = 0;
+        self.bounds
+            .iter()
+            .zip(&self.buckets)
+            .map(move |(bound, count)| {
+                total += count.load(Ordering::Relaxed);
+                (*bound, total)
+            })
+    }
+
+    /// Writes the histogram in Prometheus text exposition format.
+    pub fn write_prometheus<W: fmt::Write>(
+        &self,
+        target: &mut W,
+        name: &str,
+        help: &str,
+    ) -> fmt::Result {
+        write_header(target, name, help, "histogram")?;
+        for (bound, count) in self.buckets() {
+            write!(target, "{}_bucket", name)?;
+            write_labels(target, &[], &[], Some(&bound))?;
+            writeln!(target, " {}", count)?;
+        }
+        writeln!(target, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count())?;
+        writeln!(target, "{}_sum {}", name, self.sum().as_secs_f64())?;
+        writeln!(target, "{}_count {}", name, self.count())
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// Writes the HELP and TYPE lines of a metric.
+fn write_header<W: fmt::Write>(
+    target: &mut W,
+    name: &str,
+    help: &str,
+    kind: &str,
+) -> fmt::Result {
+    writeln!(target, "# HELP {} {}", name, help)?;
+    writeln!(target, "# TYPE {} {}", name, kind)
+}
+
+/// Writes the label set of a sample.
+///
+/// If `le` is given, it is added as the bucket bound of a histogram.
+fn write_labels<W: fmt::Write>(
+    target: &mut W,
+    labels: &[&str],
+    values: &[String],
+    le: Option<&f64>,
+) -> fmt::Result {
+    if labels.is_empty() && le.is_none() {
+        return Ok(());
+    }
+    target.write_char('{')?;
+    let mut first = true;
+    for (label, value) in labels.iter().zip(values) {
+        if !first {
+            target.write_char(',')?;
+        }
+        first = false;
+        write!(target, "{}=\"", label)?;
+        for ch in value.chars() {
+            match ch {
+                '\\' => target.write_str("\\\\")?,
+                '"' => target.write_str("\\\"")?,
+                '\n' => target.write_str("\\n")?,
+                ch => target.write_char(ch)?,
+            }
+        }
+        target.write_char('"')?;
+    }
+    if let Some(le) = le {
+        if !first {
+            target.write_char(',')?;
+        }
+        write!(target, "le=\"{}\"", le)?;
+    }
+    target.write_char('}')
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolver_metrics() {
+        let metrics = ResolverMetrics::new();
+        assert_eq!(metrics.cache_hit_rate(), None);
+        metrics.record_cache_lookup(false);
+        metrics.record_answer(
+            Rtype::A,
+            Rcode::NoError,
+            Some(Duration::from_millis(3)),
+        );
+        metrics.record_cache_lookup(true);
+        metrics.record_answer(Rtype::A, Rcode::NoError, None);
+        metrics.record_answer(
+            Rtype::Aaaa,
+            Rcode::NXDomain,
+            Some(Duration::from_secs(20)),
+        );
+        metrics.record_failure(Rtype::Mx);
+
+        assert_eq!(metrics.cache_hit_rate(), Some(0.5));
+        assert_eq!(metrics.queries().get(&["A", "NOERROR"]), 2);
+        assert_eq!(metrics.queries().total(), 3);
+        assert_eq!(metrics.failures().get(&["MX"]), 1);
+        assert_eq!(metrics.latency().count(), 2);
+        assert_eq!(metrics.latency().sum(), Duration::from_millis(20_003));
+
+        let mut text = String::new();
+        metrics.write_prometheus(&mut text).unwrap();
+        assert!(text.contains(
+            "# TYPE domain_resolver_queries_total counter\n\
+             domain_resolver_queries_total{qtype=\"A\",rcode=\"NOERROR\"} 2\n\
+             domain_resolver_queries_total{qtype=\"AAAA\",rcode=\"NXDOMAIN\"} 1\n"
+        ));
+        assert!(text.contains(
+            "domain_resolver_upstream_latency_seconds_bucket{le=\"0.0025\"} 0\n\
+             domain_resolver_upstream_latency_seconds_bucket{le=\"0.005\"} 1\n"
+        ));
+        assert!(text.contains(
+            "domain_resolver_upstream_latency_seconds_bucket{le=\"10\"} 1\n\
+             domain_resolver_upstream_latency_seconds_bucket{le=\"+Inf\"} 2\n\
+             domain_resolver_upstream_latency_seconds_sum 20.003\n\
+             domain_resolver_upstream_latency_seconds_count 2\n"
+        ));
+        assert!(text.contains("domain_resolver_cache_hits_total 1\n"));
+    }
+
+    #[test]
+    fn server_metrics() {
+        let metrics = ServerMetrics::new();
+        metrics.record_query(
+            &"udp",
+            Rtype::A,
+            Rcode::NoError,
+            Duration::from_micros(100),
+        );
+        metrics.record_transfer(Rtype::Axfr, true);
+        metrics.record_transfer(Rtype::Ixfr, false);
+        assert_eq!(metrics.queries().get(&["udp", "A", "NOERROR"]), 1);
+        assert_eq!(metrics.transfers().get(&["IXFR", "failure"]), 1);
+
+        let mut text = String::new();
+        metrics.write_prometheus(&mut text).unwrap();
+        assert!(text.contains(
+            "domain_server_queries_total\
+             {transport=\"udp\",qtype=\"A\",rcode=\"NOERROR\"} 1\n"
+        ));
+        assert!(text.contains(
+            "domain_server_transfers_total\
+             {qtype=\"AXFR\",result=\"success\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn escape_labels() {
+        let counters = CounterVec::new(&["name"]);
+        counters.inc(&[&"a\"b\\c\nd"]);
+        let mut text = String::new();
+        counters
+            .write_prometheus(&mut text, "test", "Test.")
+            .unwrap();
+        assert!(text.ends_with("test{name=\"a\\\"b\\\\c\\nd\"} 1\n"));
+    }
+}
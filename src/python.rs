@@ -0,0 +1,645 @@
+//! Python bindings.
+//!
+//! This module provides a Python extension module named `domain` via the
+//! [PyO3] crate. It makes domain names, messages, and the stub resolver
+//! available to Python code:
+//!
+//! * [`Dname`] is an absolute domain name that can be created from its
+//!   presentation or wire format.
+//! * [`Message`] is a DNS message. It can be parsed from its wire format
+//!   or created as a query. Its questions and records are returned as
+//!   [`Question`] and [`Record`] objects.
+//! * [`StubResolver`] is a blocking stub resolver using the system’s
+//!   configuration. It releases the global interpreter lock while waiting
+//!   for answers.
+//!
+//! Record types and classes can be given as mnemonics, e.g., `"AAAA"`, or
+//! as integers. Malformed arguments or messages raise `ValueError` and
+//! failed lookups raise `OSError`.
+//!
+//! In order to build the extension module, create a `cdylib` crate that
+//! depends on this crate with the `python` feature and re-exports
+//! [`domain_module`]. When building it, enable the `extension-module`
+//! feature of PyO3.
+//!
+//! This module requires the `python` feature.
+//!
+//! [PyO3]: https://pyo3.rs/
+//! [`Dname`]: struct.Dname.html
+//! [`Message`]: struct.Message.html
+//! [`Question`]: struct.Question.html
+//! [`Record`]: struct.Record.html
+//! [`StubResolver`]: struct.StubResolver.html
+//! [`domain_module`]: fn.domain_module.html
+#![cfg(feature = "python")]
+// The code generated by PyO3 for functions returning `PyResult` triggers
+// this lint.
+#![allow(clippy::useless_conversion)]
+
+use crate::base::iana::{Class, Rtype};
+use crate::base::message::{Message as BaseMessage, RecordSection};
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname as BaseDname, ToDname};
+use crate::base::octets::Compose;
+use crate::rdata::AllRecordData;
+use crate::resolv::stub::StubResolver as BaseResolver;
+use bytes::Bytes;
+use core::str::FromStr;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::collections::hash_map::DefaultHasher;
+use std::format;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use tokio::runtime;
+
+//------------ Dname ---------------------------------------------------------
+
+/// An absolute domain name.
+#[pyclass(module = "domain")]
+#[derive(Clone, Debug)]
+pub struct Dname(BaseDname<Bytes>);
+
+#[pymethods]
+impl Dname {
+    /// Creates a name from its presentation format.
+    #[new]
+    fn new(name: &str) -> PyResult<Self> {
+        if name == "." {
+            return Ok(Dname(BaseDname::root_bytes()));
+        }
+        BaseDname::from_str(name)
+            .map(Dname)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Creates a name from its uncompressed wire format.
+    #[staticmethod]
+    fn from_wire(data: &[u8]) -> PyResult<Self> {
+        BaseDname::from_octets(Bytes::copy_from_slice(data))
+            .map(Dname)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Returns the wire format of the name.
+    fn to_wire<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.0.as_slice())
+    }
+
+    /// Returns whether the name is the root name.
+    fn is_root(&self) -> bool {
+        self.0.is_root()
+    }
+
+    /// Returns the labels of the name excluding the root label.
+    fn labels(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|label| !label.is_root())
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Returns the name with its first label removed.
+    ///
+    /// Returns `None` for the root name.
+    fn parent(&self) -> Option<Self> {
+        let mut name = self.0.clone();
+        if name.parent() {
+            Some(Dname(name))
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the name is equal to or below `base`.
+    fn ends_with(&self, base: &Self) -> bool {
+        self.0.ends_with(&base.0)
+    }
+
+    fn __str__(&self) -> String {
+        self.0.display_presentation().to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Dname('{}')", self.0.display_presentation())
+    }
+
+    fn __richcmp__(
+        &self,
+        other: &Self,
+        op: CompareOp,
+        py: Python,
+    ) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.0 == other.0).into_py(py),
+            CompareOp::Ne => (self.0 != other.0).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+//------------ Question ------------------------------------------------------
+
+/// A question of a message.
+#[pyclass(module = "domain", get_all)]
+#[derive(Clone, Debug)]
+pub struct Question {
+    /// The query name.
+    qname: Dname,
+
+    /// The mnemonic of the query type.
+    qtype: String,
+
+    /// The mnemonic of the query class.
+    qclass: String,
+}
+
+#[pymethods]
+impl Question {
+    fn __repr__(&self) -> String {
+        format!(
+            "<Question {} {} {}>",
+            self.qname.0.display_presentation(),
+            self.qclass,
+            self.qtype
+        )
+    }
+}
+
+//------------ Record --------------------------------------------------------
+
+/// A resource record of a message.
+#[pyclass(module = "domain")]
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The owner name.
+    #[pyo3(get)]
+    owner: Dname,
+
+    /// The mnemonic of the record type.
+    #[pyo3(get)]
+    rtype: String,
+
+    /// The mnemonic of the record class.
+    #[pyo3(get)]
+    rclass: String,
+
+    /// The TTL in seconds.
+    #[pyo3(get)]
+    ttl: u32,
+
+    /// The record data in presentation format.
+    #[pyo3(get)]
+    data: String,
+
+    /// The record data in wire format.
+    wire_data: Vec<u8>,
+}
+
+#[pymethods]
+impl Record {
+    /// Returns the record data in uncompressed wire format.
+    fn wire_data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.wire_data)
+    }
+
+    fn __str__(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            self.owner.0.display_presentation(),
+            self.ttl,
+            self.rclass,
+            self.rtype,
+            self.data
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Record {}>", self.__str__())
+    }
+}
+
+//------------ Message -------------------------------------------------------
+
+/// A DNS message.
+#[pyclass(module = "domain")]
+#[derive(Clone)]
+pub struct Message(BaseMessage<Bytes>);
+
+#[pymethods]
+impl Message {
+    /// Creates a message from its wire format.
+    #[staticmethod]
+    fn from_wire(data: &[u8]) -> PyResult<Self> {
+        BaseMessage::from_octets(Bytes::copy_from_slice(data))
+            .map(Message)
+            .map_err(|_| PyValueError::new_err("short message"))
+    }
+
+    /// Creates a query message.
+    ///
+    /// The query type defaults to A and the class to IN. If no ID is
+    /// given, a random ID is used.
+    #[staticmethod]
+    #[pyo3(
+        signature = (qname, qtype = None, qclass = None, id = None, rd = true)
+    )]
+    fn query(
+        qname: &Bound<'_, PyAny>,
+        qtype: Option<&Bound<'_, PyAny>>,
+        qclass: Option<&Bound<'_, PyAny>>,
+        id: Option<u16>,
+        rd: bool,
+    ) -> PyResult<Self> {
+        let question =
+            (name_arg(qname)?, rtype_arg(qtype)?, class_arg(qclass)?);
+        let mut msg = MessageBuilder::new_bytes();
+        match id {
+            Some(id) => msg.header_mut().set_id(id),
+            None => msg.header_mut().set_random_id(),
+        }
+        msg.header_mut().set_rd(rd);
+        let mut msg = msg.question();
+        msg.push(question)
+            .map_err(|_| PyValueError::new_err("message too long"))?;
+        Ok(Message(msg.into_message()))
+    }
+
+    /// Returns the wire format of the message.
+    fn to_wire<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.0.as_slice())
+    }
+
+    /// The message ID.
+    #[getter]
+    fn id(&self) -> u16 {
+        self.0.header().id()
+    }
+
+    /// The mnemonic of the opcode.
+    #[getter]
+    fn opcode(&self) -> String {
+        self.0.opcode().to_string()
+    }
+
+    /// The mnemonic of the response code including the OPT record bits.
+    #[getter]
+    fn rcode(&self) -> String {
+        self.0.opt_rcode().to_string()
+    }
+
+    /// Whether the message is a response.
+    #[getter]
+    fn qr(&self) -> bool {
+        self.0.header().qr()
+    }
+
+    /// Whether the answer is authoritative.
+    #[getter]
+    fn aa(&self) -> bool {
+        self.0.header().aa()
+    }
+
+    /// Whether the message was truncated.
+    #[getter]
+    fn tc(&self) -> bool {
+        self.0.header().tc()
+    }
+
+    /// Whether recursion is desired.
+    #[getter]
+    fn rd(&self) -> bool {
+        self.0.header().rd()
+    }
+
+    /// Whether recursion is available.
+    #[getter]
+    fn ra(&self) -> bool {
+        self.0.header().ra()
+    }
+
+    /// Whether the answer has been authenticated.
+    #[getter]
+    fn ad(&self) -> bool {
+        self.0.header().ad()
+    }
+
+    /// Whether checking has been disabled.
+    #[getter]
+    fn cd(&self) -> bool {
+        self.0.header().cd()
+    }
+
+    /// Returns the questions of the message.
+    fn question(&self) -> PyResult<Vec<Question>> {
+        let mut res = Vec::new();
+        for question in self.0.question() {
+            let question = question.map_err(parse_err)?;
+            res.push(Question {
+                qname: Dname(question.qname().to_dname().map_err(parse_err)?),
+                qtype: question.qtype().to_string(),
+                qclass: question.qclass().to_string(),
+            })
+        }
+        Ok(res)
+    }
+
+    /// Returns the records of the answer section.
+    fn answer(&self) -> PyResult<Vec<Record>> {
+        records(self.0.answer().map_err(parse_err)?)
+    }
+
+    /// Returns the records of the authority section.
+    fn authority(&self) -> PyResult<Vec<Record>> {
+        records(self.0.authority().map_err(parse_err)?)
+    }
+
+    /// Returns the records of the additional section.
+    fn additional(&self) -> PyResult<Vec<Record>> {
+        records(self.0.additional().map_err(parse_err)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Message id={} opcode={} rcode={}>",
+            self.id(),
+            self.opcode(),
+            self.rcode()
+        )
+    }
+}
+
+//------------ StubResolver --------------------------------------------------
+
+/// A blocking stub resolver using the system’s configuration.
+#[pyclass(module = "domain")]
+#[derive(Debug)]
+pub struct StubResolver {
+    runtime: runtime::Runtime,
+    resolver: BaseResolver,
+}
+
+#[pymethods]
+impl StubResolver {
+    #[new]
+    fn new() -> PyResult<Self> {
+        Ok(StubResolver {
+            runtime: runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+            resolver: BaseResolver::new(),
+        })
+    }
+
+    /// Sends a query and returns the answer.
+    ///
+    /// The query type defaults to A and the class to IN.
+    #[pyo3(signature = (qname, qtype = None, qclass = None))]
+    fn query(
+        &self,
+        py: Python,
+        qname: &Bound<'_, PyAny>,
+        qtype: Option<&Bound<'_, PyAny>>,
+        qclass: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Message> {
+        let question =
+            (name_arg(qname)?, rtype_arg(qtype)?, class_arg(qclass)?);
+        py.allow_threads(|| {
+            self.runtime.block_on(self.resolver.query(question))
+        })
+        .map(|answer| Message(answer.into_message()))
+        .map_err(|err| PyOSError::new_err(err.to_string()))
+    }
+
+    /// Returns the addresses of a host.
+    ///
+    /// The host name is processed using the search list.
+    fn lookup_host(&self, py: Python, name: &str) -> PyResult<Vec<String>> {
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                self.resolver.search_host(name).await.map(|found| {
+                    found.iter().map(|a| a.to_string()).collect()
+                })
+            })
+        })
+        .map_err(|err| PyOSError::new_err(err.to_string()))
+    }
+
+    /// Returns the host names of an address.
+    fn lookup_addr(&self, py: Python, addr: &str) -> PyResult<Vec<String>> {
+        let addr = IpAddr::from_str(addr)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        py.allow_threads(|| {
+            self.runtime.block_on(async {
+                self.resolver.lookup_addr(addr).await.map(|found| {
+                    found.iter().map(|name| format!("{}.", name)).collect()
+                })
+            })
+        })
+        .map_err(|err| PyOSError::new_err(err.to_string()))
+    }
+}
+
+//------------ Module --------------------------------------------------------
+
+/// Initializes the `domain` Python module.
+#[pymodule]
+#[pyo3(name = "domain")]
+pub fn domain_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Dname>()?;
+    m.add_class::<Question>()?;
+    m.add_class::<Record>()?;
+    m.add_class::<Message>()?;
+    m.add_class::<StubResolver>()?;
+    Ok(())
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts a `Dname` or string argument into a name.
+fn name_arg(arg: &Bound<'_, PyAny>) -> PyResult<BaseDname<Bytes>> {
+    match arg.extract::<Dname>() {
+        Ok(name) => Ok(name.0),
+        Err(_) => Dname::new(arg.extract()?).map(|name| name.0),
+    }
+}
+
+/// Converts an optional mnemonic or integer argument into a record type.
+fn rtype_arg(arg: Option<&Bound<'_, PyAny>>) -> PyResult<Rtype> {
+    match arg {
+        None => Ok(Rtype::A),
+        Some(arg) => match arg.extract::<u16>() {
+            Ok(value) => Ok(Rtype::from_int(value)),
+            Err(_) => Rtype::from_str(arg.extract()?)
+                .map_err(|_| PyValueError::new_err("unknown record type")),
+        },
+    }
+}
+
+/// Converts an optional mnemonic or integer argument into a class.
+fn class_arg(arg: Option<&Bound<'_, PyAny>>) -> PyResult<Class> {
+    match arg {
+        None => Ok(Class::In),
+        Some(arg) => match arg.extract::<u16>() {
+            Ok(value) => Ok(Class::from_int(value)),
+            Err(_) => Class::from_str(arg.extract()?)
+                .map_err(|_| PyValueError::new_err("unknown class")),
+        },
+    }
+}
+
+/// Converts the records of a section into Python records.
+fn records(section: RecordSection<&Bytes>) -> PyResult<Vec<Record>> {
+    let mut res = Vec::new();
+    for record in section {
+        let record = record
+            .map_err(parse_err)?
+            .into_record::<AllRecordData<_, _>>()
+            .map_err(parse_err)?
+            .ok_or_else(|| PyValueError::new_err("malformed record"))?;
+        let mut wire_data = Vec::new();
+        record
+            .data()
+            .compose(&mut wire_data)
+            .map_err(|_| PyValueError::new_err("malformed record"))?;
+        res.push(Record {
+            owner: Dname(record.owner().to_dname().map_err(parse_err)?),
+            rtype: record.rtype().to_string(),
+            rclass: record.class().to_string(),
+            ttl: record.ttl(),
+            data: record.data().to_string(),
+            wire_data,
+        })
+    }
+    Ok(res)
+}
+
+/// Converts an error into a Python `ValueError`.
+fn parse_err<E: ToString>(err: E) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    fn run(code: &str) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let m = PyModule::new_bound(py, "domain").unwrap();
+            domain_module(&m).unwrap();
+            let locals = PyDict::new_bound(py);
+            locals.set_item("domain", m).unwrap();
+            if let Err(err) = py.run_bound(code, None, Some(&locals)) {
+                err.print(py);
+                panic!("Python code failed");
+            }
+        })
+    }
+
+    #[test]
+    fn dname() {
+        run(r#"
+name = domain.Dname("www.Example.com")
+assert str(name) == "www.Example.com."
+assert name.labels() == ["www", "Example", "com"]
+assert name.to_wire() == b"\x03www\x07Example\x03com\x00"
+assert domain.Dname.from_wire(name.to_wire()) == name
+assert name == domain.Dname("www.example.com.")
+assert hash(name) == hash(domain.Dname("WWW.EXAMPLE.COM"))
+assert name.parent() == domain.Dname("example.com")
+assert name.ends_with(domain.Dname("com"))
+assert domain.Dname(".").is_root()
+assert domain.Dname(".").parent() is None
+try:
+    domain.Dname("a..b")
+    assert False
+except ValueError:
+    pass
+"#);
+    }
+
+    #[test]
+    fn message() {
+        run(r#"
+query = domain.Message.query("example.com", "AAAA", id=0x1234)
+assert query.id == 0x1234
+assert query.rd and not query.qr
+assert query.opcode == "QUERY"
+assert query.rcode == "NOERROR"
+question = query.question()
+assert len(question) == 1
+assert question[0].qname == domain.Dname("example.com")
+assert question[0].qtype == "AAAA"
+assert question[0].qclass == "IN"
+assert query.answer() == []
+
+parsed = domain.Message.from_wire(query.to_wire())
+assert parsed.id == 0x1234
+assert parsed.question()[0].qtype == "AAAA"
+
+query = domain.Message.query(domain.Dname("example.com"), 15, rd=False)
+assert not query.rd
+assert query.question()[0].qtype == "MX"
+try:
+    domain.Message.query("example.com", "BOGUS")
+    assert False
+except ValueError:
+    pass
+"#);
+    }
+
+    #[test]
+    fn records() {
+        use crate::base::iana::Rcode;
+        use crate::rdata::{Aaaa, Cname};
+
+        let mut query = MessageBuilder::new_vec().question();
+        let www = BaseDname::<Vec<u8>>::from_str("www.example.com").unwrap();
+        let target = BaseDname::<Vec<u8>>::from_str("example.com").unwrap();
+        query.push((&www, Rtype::Aaaa)).unwrap();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query.into_message(), Rcode::NoError)
+            .unwrap();
+        answer.push((&www, 300, Cname::new(&target))).unwrap();
+        answer
+            .push((&target, 300, Aaaa::from_str("2001:db8::1").unwrap()))
+            .unwrap();
+        let wire = answer.finish();
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let msg = Message::from_wire(&wire).unwrap();
+            assert!(msg.qr());
+            let records = msg.answer().unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(
+                records[0].__str__(),
+                "www.example.com. 300 IN CNAME example.com."
+            );
+            assert_eq!(
+                records[0].wire_data(py).as_bytes(),
+                b"\x07example\x03com\x00"
+            );
+            assert_eq!(
+                records[1].__str__(),
+                "example.com. 300 IN AAAA 2001:db8::1"
+            );
+            assert!(msg.authority().unwrap().is_empty());
+        })
+    }
+}
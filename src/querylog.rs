@@ -0,0 +1,856 @@
+//! Structured query logging.
+//!
+//! This module allows resolvers and servers to log every query they
+//! process as a structured record. Each [`QueryLogEntry`] describes one
+//! query: who asked whom, what was asked, and what the outcome was. A
+//! [`QueryLog`] encodes entries either as JSON – one object per line – or
+//! as a sequence of CBOR maps ([RFC 8742]) and hands them to a [`Sink`].
+//!
+//! Query logs are sensitive data. Before an entry is encoded, the log
+//! applies its [`Redaction`] which can truncate client addresses to a
+//! prefix, pseudonymize query names via a keyed hash, and omit fields
+//! altogether.
+//!
+//! Logs are written into any [`Sink`]. The [`FileSink`] provided here
+//! appends to a file and supports rotation: either by size or when
+//! [`QueryLog::rotate`] is called, for instance after an external tool
+//! has moved the file. A hook can be attached that is called with the path
+//! of each rotated file, e.g., for compressing or shipping it.
+//!
+//! This module requires the `std` feature.
+//!
+//! [RFC 8742]: https://tools.ietf.org/html/rfc8742
+//! [`QueryLogEntry`]: struct.QueryLogEntry.html
+//! [`QueryLog`]: struct.QueryLog.html
+//! [`QueryLog::rotate`]: struct.QueryLog.html#method.rotate
+//! [`Redaction`]: struct.Redaction.html
+//! [`Sink`]: trait.Sink.html
+//! [`FileSink`]: struct.FileSink.html
+#![cfg(feature = "std")]
+
+use crate::base::iana::{Class, OptRcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::OctetsRef;
+use core::fmt;
+use core::time::Duration;
+use std::boxed::Box;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::string::{String, ToString};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+//------------ QueryLogEntry -------------------------------------------------
+
+/// The log entry for a single query.
+///
+/// All fields are public so that entries can be assembled from whatever
+/// information is available. The [`from_request`] and [`set_response`]
+/// methods fill in the fields that can be taken from the messages.
+///
+/// In the server role, `client` is the address the request was received
+/// from and `server` the local address. In the resolver role, `server` is
+/// the upstream server the query was sent to, if known.
+///
+/// [`from_request`]: #method.from_request
+/// [`set_response`]: #method.set_response
+#[derive(Clone, Debug)]
+pub struct QueryLogEntry {
+    /// The time the query was received or sent.
+    pub time: SystemTime,
+
+    /// Whether the query was processed by a resolver or a server.
+    pub role: Role,
+
+    /// The address of the client.
+    pub client: Option<SocketAddr>,
+
+    /// The address of the server.
+    pub server: Option<SocketAddr>,
+
+    /// The transport protocol used, e.g., `"udp"`.
+    pub transport: Option<String>,
+
+    /// The message ID of the request.
+    pub id: u16,
+
+    /// The query name.
+    pub qname: Dname<Vec<u8>>,
+
+    /// The query type.
+    pub qtype: Rtype,
+
+    /// The query class.
+    pub qclass: Class,
+
+    /// The response code if there was a response.
+    pub rcode: Option<OptRcode>,
+
+    /// The number of records in the answer section of the response.
+    pub answer_count: Option<u16>,
+
+    /// The time it took to produce or receive the response.
+    pub duration: Option<Duration>,
+}
+
+impl QueryLogEntry {
+    /// Creates an entry for the first question of a request.
+    ///
+    /// The time is set to now. Returns `None` if the request doesn’t have a
+    /// question.
+    pub fn from_request<Octets>(
+        role: Role,
+        request: &Message<Octets>,
+    ) -> Option<Self>
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let question = request.first_question()?;
+        Some(QueryLogEntry {
+            time: SystemTime::now(),
+            role,
+            client: None,
+            server: None,
+            transport: None,
+            id: request.header().id(),
+            qname: question.qname().to_vec(),
+            qtype: question.qtype(),
+            qclass: question.qclass(),
+            rcode: None,
+            answer_count: None,
+            duration: None,
+        })
+    }
+
+    /// Fills in the fields describing the response.
+    pub fn set_response<Octets>(&mut self, response: &Message<Octets>)
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        self.rcode = Some(response.opt_rcode());
+        self.answer_count = Some(response.header_counts().ancount());
+    }
+}
+
+//------------ Role ----------------------------------------------------------
+
+/// The role of the component that processed a query.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Role {
+    /// A resolver sending a query upstream.
+    Resolver,
+
+    /// A server answering a query from a client.
+    Server,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Role::Resolver => "resolver",
+            Role::Server => "server",
+        })
+    }
+}
+
+//------------ Field ---------------------------------------------------------
+
+/// A field of a log entry.
+///
+/// The name of a field as returned by [`name`] is used as the key in the
+/// encoded entry.
+///
+/// [`name`]: #method.name
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Field {
+    /// The time of the query in seconds since the Unix epoch.
+    Time,
+
+    /// The role of the logging component.
+    Role,
+
+    /// The client address.
+    Client,
+
+    /// The server address.
+    Server,
+
+    /// The transport protocol.
+    Transport,
+
+    /// The message ID of the request.
+    Id,
+
+    /// The query name.
+    Qname,
+
+    /// The query type.
+    Qtype,
+
+    /// The query class.
+    Qclass,
+
+    /// The response code.
+    Rcode,
+
+    /// The number of records in the answer section.
+    AnswerCount,
+
+    /// The time it took to produce or receive the response in seconds.
+    Duration,
+}
+
+impl Field {
+    /// Returns the name of the field.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Time => "time",
+            Field::Role => "role",
+            Field::Client => "client",
+            Field::Server => "server",
+            Field::Transport => "transport",
+            Field::Id => "id",
+            Field::Qname => "qname",
+            Field::Qtype => "qtype",
+            Field::Qclass => "qclass",
+            Field::Rcode => "rcode",
+            Field::AnswerCount => "ancount",
+            Field::Duration => "duration",
+        }
+    }
+}
+
+//------------ Redaction -----------------------------------------------------
+
+/// Rules for removing sensitive information from log entries.
+///
+/// The default value keeps everything.
+#[derive(Clone, Debug)]
+pub struct Redaction {
+    /// The number of leading bits of client IPv4 addresses to keep.
+    pub ipv4_prefix: u8,
+
+    /// The number of leading bits of client IPv6 addresses to keep.
+    pub ipv6_prefix: u8,
+
+    /// Whether to keep the port of client addresses.
+    pub client_port: bool,
+
+    /// How to treat query names.
+    pub qname: QnameRedaction,
+
+    /// The fields to leave out entirely.
+    pub omit: Vec<Field>,
+}
+
+impl Redaction {
+    /// Creates a value that keeps everything.
+    pub fn new() -> Self {
+        Redaction {
+            ipv4_prefix: 32,
+            ipv6_prefix: 128,
+            client_port: true,
+            qname: QnameRedaction::Keep,
+            omit: Vec::new(),
+        }
+    }
+
+    /// Creates a value suitable for privacy-preserving logs.
+    ///
+    /// Client addresses are truncated to /24 for IPv4 and /48 for IPv6
+    /// and their ports are dropped. Query names are hashed with `key`. The
+    /// message ID is omitted.
+    pub fn strict(key: [u8; 16]) -> Self {
+        Redaction {
+            ipv4_prefix: 24,
+            ipv6_prefix: 48,
+            client_port: false,
+            qname: QnameRedaction::Hash(key),
+            omit: vec![Field::Id],
+        }
+    }
+
+    /// Returns whether the field should be included.
+    fn includes(&self, field: Field) -> bool {
+        !self.omit.contains(&field)
+    }
+
+    /// Returns the client address as it should be logged.
+    fn client(&self, addr: SocketAddr) -> String {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => {
+                let mask = prefix_mask(self.ipv4_prefix, 32) as u32;
+                IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask))
+            }
+            IpAddr::V6(ip) => {
+                let mask = prefix_mask(self.ipv6_prefix, 128);
+                IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask))
+            }
+        };
+        if self.client_port {
+            SocketAddr::new(ip, addr.port()).to_string()
+        } else {
+            ip.to_string()
+        }
+    }
+
+    /// Returns the query name as it should be logged.
+    fn qname(&self, qname: &Dname<Vec<u8>>) -> String {
+        match self.qname {
+            QnameRedaction::Keep => qname.to_string(),
+            QnameRedaction::Hash(ref key) => {
+                format!("{:016x}", hash_qname(key, qname))
+            }
+        }
+    }
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a mask with the leading `prefix` of `bits` bits set.
+fn prefix_mask(prefix: u8, bits: u32) -> u128 {
+    let prefix = u32::from(prefix).min(bits);
+    if prefix == 0 {
+        0
+    } else {
+        (!0u128 << (128 - prefix)) >> (128 - bits)
+    }
+}
+
+/// Hashes a query name case-insensitively with SipHash-2-4.
+#[allow(deprecated)] // SipHasher is deprecated but the only keyed hasher.
+fn hash_qname(key: &[u8; 16], qname: &Dname<Vec<u8>>) -> u64 {
+    use core::hash::{Hasher, SipHasher};
+
+    let mut k0 = [0u8; 8];
+    let mut k1 = [0u8; 8];
+    k0.copy_from_slice(&key[..8]);
+    k1.copy_from_slice(&key[8..]);
+    let mut hasher = SipHasher::new_with_keys(
+        u64::from_le_bytes(k0),
+        u64::from_le_bytes(k1),
+    );
+    for &ch in qname.as_slice() {
+        hasher.write_u8(ch.to_ascii_lowercase());
+    }
+    hasher.finish()
+}
+
+//------------ QnameRedaction ------------------------------------------------
+
+/// How query names are treated in logs.
+#[derive(Clone, Debug)]
+pub enum QnameRedaction {
+    /// Query names are logged as they are.
+    Keep,
+
+    /// Query names are replaced by a keyed hash.
+    ///
+    /// The hash is calculated with SipHash-2-4 over the lowercase wire
+    /// format of the name and logged as 16 hexadecimal digits. Identical
+    /// names thus can still be correlated within logs using the same key
+    /// while the names themselves cannot easily be recovered.
+    Hash([u8; 16]),
+}
+
+//------------ Format --------------------------------------------------------
+
+/// The encoding of log entries.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Format {
+    /// Each entry is a JSON object on a line of its own.
+    Json,
+
+    /// Each entry is a CBOR map. The log is a CBOR sequence.
+    Cbor,
+}
+
+//------------ QueryLog ------------------------------------------------------
+
+/// A query log.
+///
+/// The log can be shared between the tasks or threads processing queries.
+/// Entries are written to the sink one at a time.
+pub struct QueryLog<S> {
+    /// The encoding to use.
+    format: Format,
+
+    /// The redaction rules.
+    redaction: Redaction,
+
+    /// The sink to write to.
+    sink: Mutex<S>,
+}
+
+impl<S: Sink> QueryLog<S> {
+    /// Creates a new log writing to `sink`.
+    pub fn new(format: Format, redaction: Redaction, sink: S) -> Self {
+        QueryLog {
+            format,
+            redaction,
+            sink: Mutex::new(sink),
+        }
+    }
+
+    /// Returns the format of the log.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the redaction rules of the log.
+    pub fn redaction(&self) -> &Redaction {
+        &self.redaction
+    }
+
+    /// Adds an entry to the log.
+    pub fn log(&self, entry: &QueryLogEntry) -> Result<(), io::Error> {
+        let data = self.encode(entry);
+        self.sink.lock().unwrap().write_entry(&data)
+    }
+
+    /// Rotates the sink.
+    ///
+    /// What this means is up to the sink. For a [`FileSink`], the current
+    /// file is closed and renamed and a new file is started.
+    ///
+    /// [`FileSink`]: struct.FileSink.html
+    pub fn rotate(&self) -> Result<(), io::Error> {
+        self.sink.lock().unwrap().rotate()
+    }
+
+    /// Flushes the sink.
+    pub fn flush(&self) -> Result<(), io::Error> {
+        self.sink.lock().unwrap().flush()
+    }
+
+    /// Returns the sink, consuming the log.
+    pub fn into_sink(self) -> S {
+        self.sink.into_inner().unwrap()
+    }
+
+    /// Encodes an entry after applying the redaction rules.
+    pub fn encode(&self, entry: &QueryLogEntry) -> Vec<u8> {
+        let values = self.values(entry);
+        match self.format {
+            Format::Json => encode_json(&values),
+            Format::Cbor => encode_cbor(&values),
+        }
+    }
+
+    /// Returns the fields and values to log for an entry.
+    fn values(&self, entry: &QueryLogEntry) -> Vec<(Field, Value)> {
+        let mut res = Vec::new();
+        let time = entry
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        res.push((Field::Time, Value::Float(time)));
+        res.push((Field::Role, Value::Text(entry.role.to_string())));
+        if let Some(client) = entry.client {
+            res.push((
+                Field::Client,
+                Value::Text(self.redaction.client(client)),
+            ));
+        }
+        if let Some(server) = entry.server {
+            res.push((Field::Server, Value::Text(server.to_string())));
+        }
+        if let Some(ref transport) = entry.transport {
+            res.push((Field::Transport, Value::Text(transport.clone())));
+        }
+        res.push((Field::Id, Value::Uint(entry.id.into())));
+        res.push((
+            Field::Qname,
+            Value::Text(self.redaction.qname(&entry.qname)),
+        ));
+        res.push((Field::Qtype, Value::Text(entry.qtype.to_string())));
+        res.push((Field::Qclass, Value::Text(entry.qclass.to_string())));
+        if let Some(rcode) = entry.rcode {
+            res.push((Field::Rcode, Value::Text(rcode.to_string())));
+        }
+        if let Some(count) = entry.answer_count {
+            res.push((Field::AnswerCount, Value::Uint(count.into())));
+        }
+        if let Some(duration) = entry.duration {
+            res.push((Field::Duration, Value::Float(duration.as_secs_f64())));
+        }
+        res.retain(|(field, _)| self.redaction.includes(*field));
+        res
+    }
+}
+
+impl<S> fmt::Debug for QueryLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QueryLog")
+            .field("format", &self.format)
+            .field("redaction", &self.redaction)
+            .finish()
+    }
+}
+
+//------------ Value ---------------------------------------------------------
+
+/// The value of a field in an encoded entry.
+enum Value {
+    Text(String),
+    Uint(u64),
+    Float(f64),
+}
+
+/// Encodes the values as a JSON object followed by a line feed.
+fn encode_json(values: &[(Field, Value)]) -> Vec<u8> {
+    let mut res = String::from("{");
+    for (idx, (field, value)) in values.iter().enumerate() {
+        if idx > 0 {
+            res.push(',');
+        }
+        res.push('"');
+        res.push_str(field.name());
+        res.push_str("\":");
+        match *value {
+            Value::Text(ref text) => {
+                res.push('"');
+                for ch in text.chars() {
+                    match ch {
+                        '"' => res.push_str("\\\""),
+                        '\\' => res.push_str("\\\\"),
+                        ch if (ch as u32) < 0x20 => {
+                            res.push_str(&format!("\\u{:04x}", ch as u32))
+                        }
+                        ch => res.push(ch),
+                    }
+                }
+                res.push('"');
+            }
+            Value::Uint(value) => res.push_str(&value.to_string()),
+            Value::Float(value) => res.push_str(&value.to_string()),
+        }
+    }
+    res.push_str("}\n");
+    res.into_bytes()
+}
+
+/// Encodes the values as a CBOR map with text keys.
+fn encode_cbor(values: &[(Field, Value)]) -> Vec<u8> {
+    fn head(target: &mut Vec<u8>, major: u8, value: u64) {
+        let major = major << 5;
+        if value < 24 {
+            target.push(major | value as u8);
+        } else if value <= u64::from(u8::MAX) {
+            target.push(major | 24);
+            target.push(value as u8);
+        } else if value <= u64::from(u16::MAX) {
+            target.push(major | 25);
+            target.extend_from_slice(&(value as u16).to_be_bytes());
+        } else if value <= u64::from(u32::MAX) {
+            target.push(major | 26);
+            target.extend_from_slice(&(value as u32).to_be_bytes());
+        } else {
+            target.push(major | 27);
+            target.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn text(target: &mut Vec<u8>, text: &str) {
+        head(target, 3, text.len() as u64);
+        target.extend_from_slice(text.as_bytes());
+    }
+
+    let mut res = Vec::new();
+    head(&mut res, 5, values.len() as u64);
+    for (field, value) in values {
+        text(&mut res, field.name());
+        match *value {
+            Value::Text(ref value) => text(&mut res, value),
+            Value::Uint(value) => head(&mut res, 0, value),
+            Value::Float(value) => {
+                res.push(0xfb);
+                res.extend_from_slice(&value.to_bits().to_be_bytes());
+            }
+        }
+    }
+    res
+}
+
+//------------ Sink ----------------------------------------------------------
+
+/// A destination for encoded log entries.
+pub trait Sink {
+    /// Writes an encoded entry.
+    fn write_entry(&mut self, entry: &[u8]) -> Result<(), io::Error>;
+
+    /// Rotates the destination.
+    ///
+    /// The default implementation does nothing.
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    /// Flushes any buffered data.
+    ///
+    /// The default implementation does nothing.
+    fn flush(&mut self) -> Result<(), io::Error> {
+        Ok(())
+    }
+}
+
+impl Sink for Vec<u8> {
+    fn write_entry(&mut self, entry: &[u8]) -> Result<(), io::Error> {
+        self.extend_from_slice(entry);
+        Ok(())
+    }
+}
+
+//------------ FileSink ------------------------------------------------------
+
+/// A sink appending to a file with support for rotation.
+///
+/// When the file is rotated, it is renamed by appending a dot and the
+/// smallest number for which no file exists yet and a new file is started.
+/// If a hook has been set via [`set_rotate_hook`], it is called with the
+/// path of the renamed file.
+///
+/// [`set_rotate_hook`]: #method.set_rotate_hook
+pub struct FileSink {
+    /// The path of the current file.
+    path: PathBuf,
+
+    /// The current file.
+    file: BufWriter<File>,
+
+    /// The number of octets in the current file.
+    size: u64,
+
+    /// The size after which the file is rotated.
+    max_size: Option<u64>,
+
+    /// The hook called after rotating.
+    hook: Option<RotateHook>,
+}
+
+/// The hook called with the path of a rotated file.
+type RotateHook = Box<dyn FnMut(&Path) + Send>;
+
+impl FileSink {
+    /// Opens the file at `path` for appending.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, io::Error> {
+        let path = path.into();
+        let (file, size) = Self::open_file(&path)?;
+        Ok(FileSink {
+            path,
+            file,
+            size,
+            max_size: None,
+            hook: None,
+        })
+    }
+
+    /// Rotates the file once it has reached `max_size` octets.
+    pub fn set_max_size(&mut self, max_size: Option<u64>) {
+        self.max_size = max_size
+    }
+
+    /// Sets a hook to be called with the path of each rotated file.
+    pub fn set_rotate_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(&Path) + Send + 'static,
+    {
+        self.hook = Some(Box::new(hook))
+    }
+
+    /// Returns the path of the current file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn open_file(path: &Path) -> Result<(BufWriter<File>, u64), io::Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok((BufWriter::new(file), size))
+    }
+}
+
+impl Sink for FileSink {
+    fn write_entry(&mut self, entry: &[u8]) -> Result<(), io::Error> {
+        self.file.write_all(entry)?;
+        self.size += entry.len() as u64;
+        match self.max_size {
+            Some(max_size) if self.size >= max_size => self.rotate(),
+            _ => Ok(()),
+        }
+    }
+
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        self.file.flush()?;
+        let mut idx = 1;
+        let rotated = loop {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(format!(".{}", idx));
+            let rotated = PathBuf::from(rotated);
+            if !rotated.exists() {
+                break rotated;
+            }
+            idx += 1;
+        };
+        fs::rename(&self.path, &rotated)?;
+        let (file, size) = Self::open_file(&self.path)?;
+        self.file = file;
+        self.size = size;
+        if let Some(ref mut hook) = self.hook {
+            hook(&rotated)
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        self.file.flush()
+    }
+}
+
+impl fmt::Debug for FileSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileSink")
+            .field("path", &self.path)
+            .field("size", &self.size)
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::message_builder::MessageBuilder;
+    use core::str::FromStr;
+    use std::sync::Arc;
+
+    fn entry() -> QueryLogEntry {
+        let mut request = MessageBuilder::new_vec();
+        request.header_mut().set_id(0x1234);
+        let mut request = request.question();
+        request
+            .push((
+                Dname::<Vec<u8>>::from_str("Example.COM").unwrap(),
+                Rtype::A,
+            ))
+            .unwrap();
+        let request = request.into_message();
+        let mut response = MessageBuilder::new_vec()
+            .start_answer(&request, Rcode::NXDomain)
+            .unwrap()
+            .into_message();
+        response.header_mut().set_qr(true);
+
+        let mut entry =
+            QueryLogEntry::from_request(Role::Server, &request).unwrap();
+        entry.time = UNIX_EPOCH + Duration::from_millis(1_600_000_000_500);
+        entry.client = Some("192.0.2.77:5353".parse().unwrap());
+        entry.transport = Some("udp".into());
+        entry.duration = Some(Duration::from_millis(2));
+        entry.set_response(&response);
+        entry
+    }
+
+    #[test]
+    fn json() {
+        let log = QueryLog::new(Format::Json, Redaction::new(), Vec::new());
+        log.log(&entry()).unwrap();
+        assert_eq!(
+            String::from_utf8(log.into_sink()).unwrap(),
+            "{\"time\":1600000000.5,\"role\":\"server\",\
+             \"client\":\"192.0.2.77:5353\",\"transport\":\"udp\",\
+             \"id\":4660,\"qname\":\"Example.COM\",\"qtype\":\"A\",\
+             \"qclass\":\"IN\",\"rcode\":\"NXDOMAIN\",\"ancount\":0,\
+             \"duration\":0.002}\n"
+        );
+    }
+
+    #[test]
+    fn redaction() {
+        let key = *b"0123456789abcdef";
+        let log = QueryLog::new(Format::Json, Redaction::strict(key), vec![]);
+        let mut entry = entry();
+        let first = String::from_utf8(log.encode(&entry)).unwrap();
+        assert!(first.contains("\"client\":\"192.0.2.0\""));
+        assert!(!first.contains("\"id\""));
+        assert!(!first.contains("example"));
+
+        // Hashes ignore case but depend on the key.
+        entry.qname = Dname::from_str("example.com").unwrap();
+        assert_eq!(first, String::from_utf8(log.encode(&entry)).unwrap());
+        let other = QueryLog::new(
+            Format::Json,
+            Redaction::strict(*b"fedcba9876543210"),
+            vec![],
+        );
+        assert_ne!(first, String::from_utf8(other.encode(&entry)).unwrap());
+
+        let mut redaction = Redaction::strict(key);
+        redaction.ipv6_prefix = 32;
+        entry.client = Some("[2001:db8:1234::1]:53".parse().unwrap());
+        let log = QueryLog::new(Format::Json, redaction, vec![]);
+        let encoded = String::from_utf8(log.encode(&entry)).unwrap();
+        assert!(encoded.contains("\"client\":\"2001:db8::\""));
+    }
+
+    #[test]
+    fn cbor() {
+        let mut redaction = Redaction::new();
+        redaction.omit = vec![
+            Field::Time,
+            Field::Role,
+            Field::Client,
+            Field::Transport,
+            Field::Qname,
+            Field::Qclass,
+            Field::Rcode,
+            Field::AnswerCount,
+            Field::Duration,
+        ];
+        let log = QueryLog::new(Format::Cbor, redaction, Vec::new());
+        assert_eq!(
+            log.encode(&entry()),
+            b"\xa2\x62id\x19\x12\x34\x65qtype\x61A"
+        );
+    }
+
+    #[test]
+    fn file_rotation() {
+        let dir = std::env::temp_dir()
+            .join(format!("domain-querylog-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("query.log");
+        let rotated = Arc::new(Mutex::new(Vec::new()));
+        let mut sink = FileSink::open(&path).unwrap();
+        sink.set_max_size(Some(300));
+        {
+            let rotated = rotated.clone();
+            sink.set_rotate_hook(move |path| {
+                rotated.lock().unwrap().push(path.to_path_buf())
+            });
+        }
+        let log = QueryLog::new(Format::Json, Redaction::new(), sink);
+        log.log(&entry()).unwrap();
+        assert!(rotated.lock().unwrap().is_empty());
+        log.log(&entry()).unwrap();
+        assert_eq!(*rotated.lock().unwrap(), [dir.join("query.log.1")]);
+        log.log(&entry()).unwrap();
+        log.flush().unwrap();
+        assert!(fs::metadata(&path).unwrap().len() > 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -330,6 +330,18 @@ macro_rules! rdata_types {
                         where C: $crate::master::scan::CharSource {
                 use $crate::master::scan::Scan;
 
+                // RFC 3597 allows data of known types in generic format, too.
+                if let Ok(()) = scanner.skip_generic_marker() {
+                    let data = $crate::base::rdata::UnknownRecordData
+                        ::scan_data(rtype, scanner)?;
+                    return Self::from_generic(data).map_err(|err| {
+                        $crate::master::scan::ScanError::Syntax(
+                            $crate::master::scan::SyntaxError::content(err),
+                            scanner.pos()
+                        )
+                    })
+                }
+
                 match rtype {
                     $( $( $(
                         $crate::base::iana::Rtype::$mtype => {
@@ -343,6 +355,34 @@ macro_rules! rdata_types {
                     }
                 }
             }
+
+            /// Converts record data in generic format into its actual type.
+            ///
+            /// Data for unknown record types is kept in generic format.
+            fn from_generic(
+                data: $crate::base::rdata::UnknownRecordData<bytes::Bytes>
+            ) -> Result<Self, $crate::base::octets::ParseError> {
+                use $crate::base::octets::{OctetsFrom, ParseError, Parser};
+                use $crate::base::rdata::ParseRecordData;
+
+                let mut parser = Parser::from_ref(data.data());
+                let res = MasterRecordData::<
+                    bytes::Bytes, ParsedDname<&bytes::Bytes>
+                >::parse_data(data.rtype(), &mut parser)?;
+                if parser.remaining() != 0 {
+                    return Err(ParseError::form_error(
+                        "trailing data in generic record data"
+                    ))
+                }
+                match res {
+                    Some(res) => {
+                        Self::octets_from(res).map_err(|_| {
+                            ParseError::ShortInput
+                        })
+                    }
+                    None => Ok(MasterRecordData::Other(data)),
+                }
+            }
         }
 
         impl<O, N> core::fmt::Display for MasterRecordData<O, N>
@@ -28,13 +28,23 @@
 mod macros;
 
 pub mod rfc1035;
+pub mod rfc2230;
 pub mod rfc2782;
 pub mod rfc2845;
 pub mod rfc3596;
+pub mod rfc4025;
 pub mod rfc4034;
+pub mod rfc4398;
+pub mod rfc4431;
+pub mod rfc4701;
 pub mod rfc5155;
 pub mod rfc6672;
 pub mod rfc7344;
+pub mod rfc8777;
+pub mod rfc8976;
+pub mod txt;
+
+use core::fmt;
 
 // The rdata_types! macro (defined in self::macros) reexports the record data
 // types here and creates the MasterRecordData and AllRecordData enums
@@ -73,6 +83,11 @@ rdata_types! {
             Null<O>,
         }
     }
+    rfc2230::{
+        master {
+            Kx<N>,
+        }
+    }
     rfc2782::{
         master {
             Srv<N>,
@@ -88,6 +103,11 @@ rdata_types! {
             Aaaa,
         }
     }
+    rfc4025::{
+        master {
+            Ipseckey<O, N>,
+        }
+    }
     rfc4034::{
         master {
             Dnskey<O>,
@@ -96,6 +116,22 @@ rdata_types! {
             Ds<O>,
         }
     }
+    rfc4398::{
+        master {
+            Cert<O>,
+        }
+    }
+    rfc4431::{
+        master {
+            Dlv<O>,
+            Ta<O>,
+        }
+    }
+    rfc4701::{
+        master {
+            Dhcid<O>,
+        }
+    }
     rfc6672::{
         master {
             Dname<N>,
@@ -113,4 +149,164 @@ rdata_types! {
             Cds<O>,
         }
     }
+    rfc8777::{
+        master {
+            Amtrelay<N>,
+        }
+    }
+    rfc8976::{
+        master {
+            Zonemd<O>,
+        }
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ BuildError ----------------------------------------------------
+
+/// Record data assembled via a builder is invalid.
+///
+/// The error is returned by the `build` methods of the record data builders
+/// such as [`SoaBuilder`], [`SrvBuilder`], and [`RrsigBuilder`].
+///
+/// [`SoaBuilder`]: rfc1035/struct.SoaBuilder.html
+/// [`SrvBuilder`]: rfc2782/struct.SrvBuilder.html
+/// [`RrsigBuilder`]: rfc4034/struct.RrsigBuilder.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BuildError {
+    /// A description of the problem.
+    msg: &'static str,
+}
+
+impl BuildError {
+    /// Creates a new error with the given message.
+    pub(crate) fn new(msg: &'static str) -> Self {
+        BuildError { msg }
+    }
+
+    /// Returns a description of the problem.
+    pub fn msg(self) -> &'static str {
+        self.msg
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.msg)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rtype, SecAlg};
+    use crate::base::name::Dname as Name;
+    use crate::base::rdata::RecordData;
+    use crate::base::serial::Serial;
+    use crate::base::time32::Time32;
+    use std::vec::Vec;
+
+    type Data = MasterRecordData<Vec<u8>, Name<Vec<u8>>>;
+
+    fn name(s: &str) -> Name<Vec<u8>> {
+        s.parse().unwrap()
+    }
+
+    fn canonical(data: Data) -> Vec<u8> {
+        data.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn canonicalize() {
+        // Names embedded in the types listed in RFC 4034, section 6.2 are
+        // converted to lowercase.
+        let wire = b"\x04mail\x07example\x03com\x00";
+        let upper = "MAIL.Example.COM";
+
+        for data in vec![
+            Data::from(Ns::new(name(upper))),
+            Cname::new(name(upper)).into(),
+            Ptr::new(name(upper)).into(),
+            Dname::new(name(upper)).into(),
+        ] {
+            assert_eq!(canonical(data), wire.as_ref());
+        }
+
+        assert_eq!(
+            canonical(Mx::new(10, name(upper)).into()),
+            b"\x00\x0a\x04mail\x07example\x03com\x00".as_ref()
+        );
+        assert_eq!(
+            canonical(Kx::new(10, name(upper)).into()),
+            b"\x00\x0a\x04mail\x07example\x03com\x00".as_ref()
+        );
+        assert_eq!(
+            canonical(Srv::new(1, 2, 53, name("NS.Example.")).into()),
+            b"\x00\x01\x00\x02\x00\x35\x02ns\x07example\x00".as_ref()
+        );
+        assert_eq!(
+            canonical(
+                Minfo::new(name("RMail.Example."), name("EMail.Example."))
+                    .into()
+            ),
+            b"\x05rmail\x07example\x00\x05email\x07example\x00".as_ref()
+        );
+        assert_eq!(
+            canonical(
+                Soa::new(
+                    name("NS.Example."),
+                    name("HostMaster.Example."),
+                    Serial(1),
+                    2,
+                    3,
+                    4,
+                    5,
+                )
+                .into()
+            ),
+            b"\x02ns\x07example\x00\x0ahostmaster\x07example\x00\
+              \x00\x00\x00\x01\x00\x00\x00\x02\x00\x00\x00\x03\
+              \x00\x00\x00\x04\x00\x00\x00\x05"
+                .as_ref()
+        );
+        assert_eq!(
+            canonical(
+                Rrsig::new(
+                    Rtype::A,
+                    SecAlg::Ed25519,
+                    2,
+                    3600,
+                    Time32::from_int(2),
+                    Time32::from_int(1),
+                    12345,
+                    name("Example."),
+                    vec![0xab],
+                )
+                .into()
+            ),
+            b"\x00\x01\x0f\x02\x00\x00\x0e\x10\x00\x00\x00\x02\
+              \x00\x00\x00\x01\x30\x39\x07example\x00\xab"
+                .as_ref()
+        );
+
+        // RFC 6840 removed NSEC from the list, so its next name keeps its
+        // case.
+        let mut types = rfc4034::RtypeBitmap::<Vec<u8>>::builder();
+        types.add(Rtype::A).unwrap();
+        assert_eq!(
+            canonical(
+                Nsec::new(name("WWW.Example."), types.finalize()).into()
+            ),
+            b"\x03WWW\x07Example\x00\x00\x01\x40".as_ref()
+        );
+    }
 }
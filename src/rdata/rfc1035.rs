@@ -4,6 +4,7 @@
 //!
 //! [RFC 1035]: https://tools.ietf.org/html/rfc1035
 
+use super::BuildError;
 use crate::base::charstr::CharStr;
 use crate::base::cmp::CanonicalOrd;
 use crate::base::iana::Rtype;
@@ -1266,6 +1267,108 @@ impl<N> RtypeRecordData for Soa<N> {
     const RTYPE: Rtype = Rtype::Soa;
 }
 
+//------------ SoaBuilder ---------------------------------------------------
+
+/// A builder for Soa record data.
+///
+/// The builder starts out with the two names and sensible defaults for the
+/// numeric fields: a serial of 1, a refresh interval of one day, a retry
+/// interval of two hours, an expire time of 1000 hours, and a minimum of
+/// one hour, as recommended in RIPE-203. These can be changed via the
+/// various `with_*` methods.
+///
+/// When the data is finally built via [`build`], the intervals are checked
+/// for consistency: the retry interval must not be longer than the refresh
+/// interval and the expire time must be at least as long as refresh and
+/// retry intervals combined.
+///
+/// [`build`]: #method.build
+#[derive(Clone, Debug)]
+pub struct SoaBuilder<N> {
+    mname: N,
+    rname: N,
+    serial: Serial,
+    refresh: u32,
+    retry: u32,
+    expire: u32,
+    minimum: u32,
+}
+
+impl<N> Soa<N> {
+    /// Returns a builder for Soa record data with the given names.
+    pub fn builder(mname: N, rname: N) -> SoaBuilder<N> {
+        SoaBuilder::new(mname, rname)
+    }
+}
+
+impl<N> SoaBuilder<N> {
+    /// Creates a new builder with the given names.
+    pub fn new(mname: N, rname: N) -> Self {
+        SoaBuilder {
+            mname,
+            rname,
+            serial: Serial(1),
+            refresh: 86400,
+            retry: 7200,
+            expire: 3_600_000,
+            minimum: 3600,
+        }
+    }
+
+    /// Returns the builder with the serial number set to `serial`.
+    pub fn with_serial(mut self, serial: Serial) -> Self {
+        self.serial = serial;
+        self
+    }
+
+    /// Returns the builder with the refresh interval set to `refresh`.
+    pub fn with_refresh(mut self, refresh: u32) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Returns the builder with the retry interval set to `retry`.
+    pub fn with_retry(mut self, retry: u32) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Returns the builder with the expire time set to `expire`.
+    pub fn with_expire(mut self, expire: u32) -> Self {
+        self.expire = expire;
+        self
+    }
+
+    /// Returns the builder with the minimum TTL set to `minimum`.
+    pub fn with_minimum(mut self, minimum: u32) -> Self {
+        self.minimum = minimum;
+        self
+    }
+
+    /// Builds the record data after checking the intervals.
+    pub fn build(self) -> Result<Soa<N>, BuildError> {
+        if self.retry > self.refresh {
+            return Err(BuildError::new(
+                "retry interval longer than refresh interval",
+            ));
+        }
+        if self.expire < self.refresh.saturating_add(self.retry) {
+            return Err(BuildError::new(
+                "expire time shorter than refresh and retry intervals",
+            ));
+        }
+        Ok(Soa::new(
+            self.mname,
+            self.rname,
+            self.serial,
+            self.refresh,
+            self.retry,
+            self.expire,
+            self.minimum,
+        ))
+    }
+}
+
 //------------ Txt ----------------------------------------------------------
 
 /// Txt record data.
@@ -1623,6 +1726,42 @@ mod test {
         assert_eq!(minfo.emailbx(), minfo_bytes.emailbx());
     }
 
+    #[test]
+    fn soa_builder() {
+        use crate::base::name::Dname;
+
+        let name = |s: &str| -> Dname<Vec<u8>> { s.parse().unwrap() };
+        let soa = Soa::builder(name("ns.example"), name("mail.example"))
+            .with_serial(Serial(2021010101))
+            .with_retry(3600)
+            .build()
+            .unwrap();
+        assert_eq!(
+            soa,
+            Soa::new(
+                name("ns.example"),
+                name("mail.example"),
+                Serial(2021010101),
+                86400,
+                3600,
+                3_600_000,
+                3600
+            )
+        );
+
+        let err = |builder: SoaBuilder<&str>| builder.build().unwrap_err();
+        assert_eq!(
+            err(Soa::builder("a", "b").with_retry(90000)),
+            BuildError::new("retry interval longer than refresh interval")
+        );
+        assert_eq!(
+            err(Soa::builder("a", "b").with_expire(86400)),
+            BuildError::new(
+                "expire time shorter than refresh and retry intervals"
+            )
+        );
+    }
+
     #[test]
     fn txt_from_slice() {
         let short = b"01234";
@@ -0,0 +1,178 @@
+//! Record data from [RFC 2230]: KX records.
+//!
+//! This RFC defines the KX record type.
+//!
+//! [RFC 2230]: https://tools.ietf.org/html/rfc2230
+
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::Rtype;
+use crate::base::name::{ParsedDname, ToDname};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+#[cfg(feature = "master")]
+use crate::master::scan::{CharSource, Scan, ScanError, Scanner};
+use core::cmp::Ordering;
+use core::fmt;
+
+//------------ Kx -----------------------------------------------------------
+
+/// Kx record data.
+///
+/// The Kx record specifies a host willing to act as a key exchange for the
+/// owner name.
+///
+/// The Kx record type is defined in RFC 2230, section 3.1.
+#[derive(Clone, Debug, Hash)]
+pub struct Kx<N> {
+    preference: u16,
+    exchanger: N,
+}
+
+impl<N> Kx<N> {
+    /// Creates a new Kx record data from the components.
+    pub fn new(preference: u16, exchanger: N) -> Self {
+        Kx {
+            preference,
+            exchanger,
+        }
+    }
+
+    /// The preference for this record.
+    ///
+    /// Defines an order if there are several Kx records for the same owner.
+    /// Lower values are preferred.
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    /// The name of the host that is the key exchanger.
+    pub fn exchanger(&self) -> &N {
+        &self.exchanger
+    }
+}
+
+//--- OctetsFrom
+
+impl<Name, SrcName> OctetsFrom<Kx<SrcName>> for Kx<Name>
+where
+    Name: OctetsFrom<SrcName>,
+{
+    fn octets_from(source: Kx<SrcName>) -> Result<Self, ShortBuf> {
+        Ok(Kx::new(
+            source.preference,
+            Name::octets_from(source.exchanger)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<N, NN> PartialEq<Kx<NN>> for Kx<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn eq(&self, other: &Kx<NN>) -> bool {
+        self.preference == other.preference
+            && self.exchanger.name_eq(&other.exchanger)
+    }
+}
+
+impl<N: ToDname> Eq for Kx<N> {}
+
+//--- PartialOrd, Ord, and CanonicalOrd
+
+impl<N, NN> PartialOrd<Kx<NN>> for Kx<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn partial_cmp(&self, other: &Kx<NN>) -> Option<Ordering> {
+        match self.preference.partial_cmp(&other.preference) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        Some(self.exchanger.name_cmp(&other.exchanger))
+    }
+}
+
+impl<N: ToDname> Ord for Kx<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.preference.cmp(&other.preference) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.exchanger.name_cmp(&other.exchanger)
+    }
+}
+
+impl<N: ToDname, NN: ToDname> CanonicalOrd<Kx<NN>> for Kx<N> {
+    fn canonical_cmp(&self, other: &Kx<NN>) -> Ordering {
+        match self.preference.cmp(&other.preference) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.exchanger.lowercase_composed_cmp(&other.exchanger)
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Kx<ParsedDname<Ref>> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        Ok(Self::new(u16::parse(parser)?, ParsedDname::parse(parser)?))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        u16::skip(parser)?;
+        ParsedDname::skip(parser)
+    }
+}
+
+impl<N: ToDname> Compose for Kx<N> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|target| {
+            self.preference.compose(target)?;
+            self.exchanger.compose(target)
+        })
+    }
+
+    fn compose_canonical<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|target| {
+            self.preference.compose(target)?;
+            self.exchanger.compose_canonical(target)
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl<N: Scan> Scan for Kx<N> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        Ok(Self::new(u16::scan(scanner)?, N::scan(scanner)?))
+    }
+}
+
+impl<N: fmt::Display> fmt::Display for Kx<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}.", self.preference, self.exchanger)
+    }
+}
+
+//--- RtypeRecordData
+
+impl<N> RtypeRecordData for Kx<N> {
+    const RTYPE: Rtype = Rtype::Kx;
+}
@@ -4,6 +4,7 @@
 //!
 //! [RFC 2782]: https://tools.ietf.org/html/rfc2782
 
+use super::BuildError;
 use crate::base::cmp::CanonicalOrd;
 use crate::base::iana::Rtype;
 use crate::base::name::{ParsedDname, ToDname};
@@ -230,3 +231,108 @@ impl<N: fmt::Display> fmt::Display for Srv<N> {
         )
     }
 }
+
+//------------ SrvBuilder ----------------------------------------------------
+
+/// A builder for Srv record data.
+///
+/// The builder starts out with the target and priority, weight, and port
+/// all set to zero. These can be changed via the various `with_*` methods.
+///
+/// A target of the root name means that the service is not available at
+/// the domain. When the data is finally built via [`build`], it is
+/// therefore checked that a port is given if and only if the target isn’t
+/// the root name.
+///
+/// [`build`]: #method.build
+#[derive(Clone, Debug)]
+pub struct SrvBuilder<N> {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: N,
+}
+
+impl<N> Srv<N> {
+    /// Returns a builder for Srv record data with the given target.
+    pub fn builder(target: N) -> SrvBuilder<N> {
+        SrvBuilder::new(target)
+    }
+}
+
+impl<N> SrvBuilder<N> {
+    /// Creates a new builder with the given target.
+    pub fn new(target: N) -> Self {
+        SrvBuilder {
+            priority: 0,
+            weight: 0,
+            port: 0,
+            target,
+        }
+    }
+
+    /// Returns the builder with the priority set to `priority`.
+    pub fn with_priority(mut self, priority: u16) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Returns the builder with the weight set to `weight`.
+    pub fn with_weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Returns the builder with the port set to `port`.
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Builds the record data after checking target and port.
+    pub fn build(self) -> Result<Srv<N>, BuildError>
+    where
+        N: ToDname,
+    {
+        let is_root = self.target.iter_labels().next().unwrap().is_root();
+        if is_root && self.port != 0 {
+            return Err(BuildError::new("port given for root target"));
+        }
+        if !is_root && self.port == 0 {
+            return Err(BuildError::new("missing port"));
+        }
+        Ok(Srv::new(self.priority, self.weight, self.port, self.target))
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use std::vec::Vec;
+
+    #[test]
+    fn srv_builder() {
+        let name = |s: &str| -> Dname<Vec<u8>> { s.parse().unwrap() };
+        let srv = Srv::builder(name("sip.example"))
+            .with_priority(10)
+            .with_weight(60)
+            .with_port(5060)
+            .build()
+            .unwrap();
+        assert_eq!(srv, Srv::new(10, 60, 5060, name("sip.example")));
+        assert!(Srv::builder(Dname::root_vec()).build().is_ok());
+
+        assert_eq!(
+            Srv::builder(Dname::root_vec()).with_port(80).build(),
+            Err(BuildError::new("port given for root target"))
+        );
+        assert_eq!(
+            Srv::builder(name("sip.example")).build(),
+            Err(BuildError::new("missing port"))
+        );
+    }
+}
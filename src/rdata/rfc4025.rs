@@ -0,0 +1,551 @@
+//! Record data from [RFC 4025]: IPSECKEY records.
+//!
+//! This RFC defines the IPSECKEY record type for storing keying material
+//! for use with IPsec.
+//!
+//! [RFC 4025]: https://tools.ietf.org/html/rfc4025
+
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::Rtype;
+#[cfg(feature = "master")]
+use crate::base::name::Dname;
+use crate::base::name::{ParsedDname, ToDname};
+use crate::base::net::{Ipv4Addr, Ipv6Addr};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+#[cfg(feature = "master")]
+use crate::master::scan::{
+    CharSource, Scan, ScanError, Scanner, SyntaxError,
+};
+use crate::utils::base64;
+#[cfg(feature = "master")]
+use bytes::Bytes;
+use core::cmp::Ordering;
+use core::{fmt, hash};
+#[cfg(feature = "master")]
+use std::string::String;
+
+//------------ Ipseckey ------------------------------------------------------
+
+/// IPSECKEY record data.
+///
+/// The IPSECKEY record provides a public key for use with IPsec together
+/// with the gateway to send IPsec traffic for the owner name to. The
+/// gateway can be missing or given as an IPv4 address, an IPv6 address, or
+/// a domain name. Likewise, the public key can be missing, in which case
+/// the record only provides the gateway.
+///
+/// The IPSECKEY record type is defined in RFC 4025, section 2.
+#[derive(Clone)]
+pub struct Ipseckey<Octets, Name> {
+    precedence: u8,
+    algorithm: u8,
+    gateway: Gateway<Name>,
+    public_key: Octets,
+}
+
+impl<Octets, Name> Ipseckey<Octets, Name> {
+    /// Creates new IPSECKEY record data from its components.
+    pub fn new(
+        precedence: u8,
+        algorithm: u8,
+        gateway: Gateway<Name>,
+        public_key: Octets,
+    ) -> Self {
+        Ipseckey {
+            precedence,
+            algorithm,
+            gateway,
+            public_key,
+        }
+    }
+
+    /// Returns the precedence of the record.
+    ///
+    /// If there are several IPSECKEY records for the same owner, the
+    /// gateways should be tried in order of increasing precedence.
+    pub fn precedence(&self) -> u8 {
+        self.precedence
+    }
+
+    /// Returns the type of the gateway as it appears in the wire format.
+    pub fn gateway_type(&self) -> u8 {
+        self.gateway.gateway_type()
+    }
+
+    /// Returns the algorithm of the public key.
+    ///
+    /// A value of 0 means that there is no key. The values 1 and 2 are
+    /// for DSA and RSA keys, respectively.
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+
+    /// Returns the gateway.
+    pub fn gateway(&self) -> &Gateway<Name> {
+        &self.gateway
+    }
+
+    /// Returns the public key.
+    ///
+    /// The key is empty if the record doesn’t contain one.
+    pub fn public_key(&self) -> &Octets {
+        &self.public_key
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets, Name, SrcName>
+    OctetsFrom<Ipseckey<SrcOctets, SrcName>> for Ipseckey<Octets, Name>
+where
+    Octets: OctetsFrom<SrcOctets>,
+    Name: OctetsFrom<SrcName>,
+{
+    fn octets_from(
+        source: Ipseckey<SrcOctets, SrcName>,
+    ) -> Result<Self, ShortBuf> {
+        Ok(Ipseckey::new(
+            source.precedence,
+            source.algorithm,
+            Gateway::octets_from(source.gateway)?,
+            Octets::octets_from(source.public_key)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<N, NN, O, OO> PartialEq<Ipseckey<OO, NN>> for Ipseckey<O, N>
+where
+    N: ToDname,
+    NN: ToDname,
+    O: AsRef<[u8]>,
+    OO: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Ipseckey<OO, NN>) -> bool {
+        self.precedence == other.precedence
+            && self.algorithm == other.algorithm
+            && self.gateway == other.gateway
+            && self.public_key.as_ref() == other.public_key.as_ref()
+    }
+}
+
+impl<Octets, Name> Eq for Ipseckey<Octets, Name>
+where
+    Octets: AsRef<[u8]>,
+    Name: ToDname,
+{
+}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<N, NN, O, OO> PartialOrd<Ipseckey<OO, NN>> for Ipseckey<O, N>
+where
+    N: ToDname,
+    NN: ToDname,
+    O: AsRef<[u8]>,
+    OO: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Ipseckey<OO, NN>) -> Option<Ordering> {
+        match self.precedence.partial_cmp(&other.precedence) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.gateway_type().partial_cmp(&other.gateway_type()) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.algorithm.partial_cmp(&other.algorithm) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.gateway.partial_cmp(&other.gateway) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        self.public_key
+            .as_ref()
+            .partial_cmp(other.public_key.as_ref())
+    }
+}
+
+impl<N, NN, O, OO> CanonicalOrd<Ipseckey<OO, NN>> for Ipseckey<O, N>
+where
+    N: ToDname,
+    NN: ToDname,
+    O: AsRef<[u8]>,
+    OO: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Ipseckey<OO, NN>) -> Ordering {
+        match self.precedence.cmp(&other.precedence) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.gateway_type().cmp(&other.gateway_type()) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.gateway.canonical_cmp(&other.gateway) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.public_key.as_ref().cmp(other.public_key.as_ref())
+    }
+}
+
+impl<O: AsRef<[u8]>, N: ToDname> Ord for Ipseckey<O, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<O: AsRef<[u8]>, N: hash::Hash> hash::Hash for Ipseckey<O, N> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.precedence.hash(state);
+        self.algorithm.hash(state);
+        self.gateway.hash(state);
+        self.public_key.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Ipseckey<Ref::Range, ParsedDname<Ref>> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let precedence = u8::parse(parser)?;
+        let gateway_type = u8::parse(parser)?;
+        let algorithm = u8::parse(parser)?;
+        let gateway = Gateway::parse(gateway_type, parser)?;
+        let len = parser.remaining();
+        let public_key = parser.parse_octets(len)?;
+        Ok(Self::new(precedence, algorithm, gateway, public_key))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        u8::skip(parser)?;
+        let gateway_type = u8::parse(parser)?;
+        u8::skip(parser)?;
+        Gateway::skip(gateway_type, parser)?;
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>, Name: ToDname> Compose for Ipseckey<Octets, Name> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.precedence.compose(buf)?;
+            self.gateway_type().compose(buf)?;
+            self.algorithm.compose(buf)?;
+            self.gateway.compose(buf)?;
+            buf.append_slice(self.public_key.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Ipseckey<Bytes, Dname<Bytes>> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        let precedence = u8::scan(scanner)?;
+        let gateway_type = u8::scan(scanner)?;
+        let algorithm = u8::scan(scanner)?;
+        let gateway = Gateway::scan(gateway_type, scanner)?;
+
+        // The public key is optional, so we need to check whether there is
+        // anything left in the entry.
+        let public_key = match scanner.scan_base64_phrases(Ok) {
+            Ok(key) => key,
+            Err(ScanError::Syntax(SyntaxError::UnexpectedNewline, _))
+            | Err(ScanError::Syntax(SyntaxError::UnexpectedEof, _)) => {
+                Bytes::new()
+            }
+            Err(err) => return Err(err),
+        };
+        Ok(Self::new(precedence, algorithm, gateway, public_key))
+    }
+}
+
+impl<Octets, Name> fmt::Display for Ipseckey<Octets, Name>
+where
+    Octets: AsRef<[u8]>,
+    Name: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.precedence,
+            self.gateway_type(),
+            self.algorithm,
+            self.gateway
+        )?;
+        if !self.public_key.as_ref().is_empty() {
+            f.write_str(" ")?;
+            base64::display(&self.public_key, f)?;
+        }
+        Ok(())
+    }
+}
+
+//--- Debug
+
+impl<Octets, Name> fmt::Debug for Ipseckey<Octets, Name>
+where
+    Octets: AsRef<[u8]>,
+    Name: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Ipseckey")
+            .field("precedence", &self.precedence)
+            .field("algorithm", &self.algorithm)
+            .field("gateway", &self.gateway)
+            .field("public_key", &self.public_key.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets, Name> RtypeRecordData for Ipseckey<Octets, Name> {
+    const RTYPE: Rtype = Rtype::Ipseckey;
+}
+
+//------------ Gateway -------------------------------------------------------
+
+/// The gateway of an IPSECKEY record.
+///
+/// The wire format of the gateway depends on the gateway type given
+/// separately in the record data. The type is therefore not part of the
+/// wire format of this type itself but can be derived via the
+/// [`gateway_type`] method.
+///
+/// The same encoding is used for the relay of AMTRELAY records.
+///
+/// Domain names are never compressed in the wire format.
+///
+/// [`gateway_type`]: #method.gateway_type
+#[derive(Clone, Debug, Hash)]
+pub enum Gateway<N> {
+    /// There is no gateway.
+    None,
+
+    /// The gateway is given as an IPv4 address.
+    Ipv4(Ipv4Addr),
+
+    /// The gateway is given as an IPv6 address.
+    Ipv6(Ipv6Addr),
+
+    /// The gateway is given as a domain name.
+    Name(N),
+}
+
+impl<N> Gateway<N> {
+    /// Returns the gateway type used for this gateway in the wire format.
+    pub fn gateway_type(&self) -> u8 {
+        match *self {
+            Gateway::None => 0,
+            Gateway::Ipv4(_) => 1,
+            Gateway::Ipv6(_) => 2,
+            Gateway::Name(_) => 3,
+        }
+    }
+}
+
+impl<Ref: OctetsRef> Gateway<ParsedDname<Ref>> {
+    /// Parses a gateway of the given type from the beginning of a parser.
+    pub fn parse(
+        gateway_type: u8,
+        parser: &mut Parser<Ref>,
+    ) -> Result<Self, ParseError> {
+        match gateway_type {
+            0 => Ok(Gateway::None),
+            1 => Ipv4Addr::parse(parser).map(Gateway::Ipv4),
+            2 => Ipv6Addr::parse(parser).map(Gateway::Ipv6),
+            3 => ParsedDname::parse(parser).map(Gateway::Name),
+            _ => Err(ParseError::form_error("unknown gateway type")),
+        }
+    }
+
+    /// Skips over a gateway of the given type.
+    pub fn skip(
+        gateway_type: u8,
+        parser: &mut Parser<Ref>,
+    ) -> Result<(), ParseError> {
+        match gateway_type {
+            0 => Ok(()),
+            1 => Ipv4Addr::skip(parser),
+            2 => Ipv6Addr::skip(parser),
+            3 => ParsedDname::skip(parser),
+            _ => Err(ParseError::form_error("unknown gateway type")),
+        }
+    }
+}
+
+#[cfg(feature = "master")]
+impl Gateway<Dname<Bytes>> {
+    /// Scans a gateway of the given type.
+    ///
+    /// A missing gateway is represented by a single dot.
+    pub fn scan<C: CharSource>(
+        gateway_type: u8,
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        match gateway_type {
+            0 => scanner.skip_literal(".").map(|_| Gateway::None),
+            1 => scanner
+                .scan_string_phrase(|res| {
+                    core::str::FromStr::from_str(&res).map_err(Into::into)
+                })
+                .map(Gateway::Ipv4),
+            2 => scanner
+                .scan_string_phrase(|res| {
+                    core::str::FromStr::from_str(&res).map_err(Into::into)
+                })
+                .map(Gateway::Ipv6),
+            3 => Dname::scan(scanner).map(Gateway::Name),
+            _ => Err(ScanError::Syntax(
+                SyntaxError::Expected(String::from("gateway type 0 to 3")),
+                scanner.pos(),
+            )),
+        }
+    }
+}
+
+//--- OctetsFrom
+
+impl<Name, SrcName> OctetsFrom<Gateway<SrcName>> for Gateway<Name>
+where
+    Name: OctetsFrom<SrcName>,
+{
+    fn octets_from(source: Gateway<SrcName>) -> Result<Self, ShortBuf> {
+        Ok(match source {
+            Gateway::None => Gateway::None,
+            Gateway::Ipv4(addr) => Gateway::Ipv4(addr),
+            Gateway::Ipv6(addr) => Gateway::Ipv6(addr),
+            Gateway::Name(name) => Gateway::Name(Name::octets_from(name)?),
+        })
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<N, NN> PartialEq<Gateway<NN>> for Gateway<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn eq(&self, other: &Gateway<NN>) -> bool {
+        match (self, other) {
+            (Gateway::None, Gateway::None) => true,
+            (Gateway::Ipv4(left), Gateway::Ipv4(right)) => left == right,
+            (Gateway::Ipv6(left), Gateway::Ipv6(right)) => left == right,
+            (Gateway::Name(left), Gateway::Name(right)) => {
+                left.name_eq(right)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<N: ToDname> Eq for Gateway<N> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+//
+// Gateways of different types are ordered by their gateway type.
+
+impl<N, NN> PartialOrd<Gateway<NN>> for Gateway<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn partial_cmp(&self, other: &Gateway<NN>) -> Option<Ordering> {
+        match (self, other) {
+            (Gateway::Ipv4(left), Gateway::Ipv4(right)) => {
+                left.partial_cmp(right)
+            }
+            (Gateway::Ipv6(left), Gateway::Ipv6(right)) => {
+                left.partial_cmp(right)
+            }
+            (Gateway::Name(left), Gateway::Name(right)) => {
+                Some(left.name_cmp(right))
+            }
+            _ => self.gateway_type().partial_cmp(&other.gateway_type()),
+        }
+    }
+}
+
+impl<N, NN> CanonicalOrd<Gateway<NN>> for Gateway<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn canonical_cmp(&self, other: &Gateway<NN>) -> Ordering {
+        match (self, other) {
+            (Gateway::Ipv4(left), Gateway::Ipv4(right)) => left.cmp(right),
+            (Gateway::Ipv6(left), Gateway::Ipv6(right)) => left.cmp(right),
+            (Gateway::Name(left), Gateway::Name(right)) => {
+                left.composed_cmp(right)
+            }
+            _ => self.gateway_type().cmp(&other.gateway_type()),
+        }
+    }
+}
+
+impl<N: ToDname> Ord for Gateway<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Gateway::Ipv4(left), Gateway::Ipv4(right)) => left.cmp(right),
+            (Gateway::Ipv6(left), Gateway::Ipv6(right)) => left.cmp(right),
+            (Gateway::Name(left), Gateway::Name(right)) => {
+                left.name_cmp(right)
+            }
+            _ => self.gateway_type().cmp(&other.gateway_type()),
+        }
+    }
+}
+
+//--- Compose
+
+impl<N: ToDname> Compose for Gateway<N> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        match *self {
+            Gateway::None => Ok(()),
+            Gateway::Ipv4(addr) => addr.compose(target),
+            Gateway::Ipv6(addr) => addr.compose(target),
+            Gateway::Name(ref name) => name.compose(target),
+        }
+    }
+}
+
+//--- Display
+
+impl<N: fmt::Display> fmt::Display for Gateway<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Gateway::None => f.write_str("."),
+            Gateway::Ipv4(addr) => write!(f, "{}", addr),
+            Gateway::Ipv6(addr) => write!(f, "{}", addr),
+            Gateway::Name(ref name) => write!(f, "{}.", name),
+        }
+    }
+}
@@ -4,6 +4,7 @@
 //!
 //! [RFC 4034]: https://tools.ietf.org/html/rfc4034
 
+use super::BuildError;
 use crate::base::cmp::CanonicalOrd;
 use crate::base::iana::{DigestAlg, Rtype, SecAlg};
 #[cfg(feature = "master")]
@@ -14,7 +15,7 @@ use crate::base::octets::{
     OctetsRef, Parse, ParseError, Parser, ShortBuf,
 };
 use crate::base::rdata::RtypeRecordData;
-use crate::base::serial::Serial;
+use crate::base::time32::Time32;
 #[cfg(feature = "master")]
 use crate::master::scan::{CharSource, Scan, ScanError, Scanner};
 use crate::utils::base64;
@@ -22,6 +23,7 @@ use crate::utils::base64;
 use bytes::{Bytes, BytesMut};
 use core::cmp::Ordering;
 use core::convert::TryInto;
+use core::iter::FromIterator;
 use core::{fmt, hash, ptr};
 #[cfg(feature = "std")]
 use std::vec::Vec;
@@ -330,8 +332,8 @@ pub struct ProtoRrsig<Name> {
     algorithm: SecAlg,
     labels: u8,
     original_ttl: u32,
-    expiration: Serial,
-    inception: Serial,
+    expiration: Time32,
+    inception: Time32,
     key_tag: u16,
     signer_name: Name,
 }
@@ -343,8 +345,8 @@ impl<Name> ProtoRrsig<Name> {
         algorithm: SecAlg,
         labels: u8,
         original_ttl: u32,
-        expiration: Serial,
-        inception: Serial,
+        expiration: Time32,
+        inception: Time32,
         key_tag: u16,
         signer_name: Name,
     ) -> Self {
@@ -360,6 +362,38 @@ impl<Name> ProtoRrsig<Name> {
         }
     }
 
+    pub fn type_covered(&self) -> Rtype {
+        self.type_covered
+    }
+
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    pub fn labels(&self) -> u8 {
+        self.labels
+    }
+
+    pub fn original_ttl(&self) -> u32 {
+        self.original_ttl
+    }
+
+    pub fn expiration(&self) -> Time32 {
+        self.expiration
+    }
+
+    pub fn inception(&self) -> Time32 {
+        self.inception
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn signer_name(&self) -> &Name {
+        &self.signer_name
+    }
+
     pub fn into_rrsig<Octets>(
         self,
         signature: Octets,
@@ -442,8 +476,8 @@ pub struct Rrsig<Octets, Name> {
     algorithm: SecAlg,
     labels: u8,
     original_ttl: u32,
-    expiration: Serial,
-    inception: Serial,
+    expiration: Time32,
+    inception: Time32,
     key_tag: u16,
     signer_name: Name,
     signature: Octets,
@@ -456,8 +490,8 @@ impl<Octets, Name> Rrsig<Octets, Name> {
         algorithm: SecAlg,
         labels: u8,
         original_ttl: u32,
-        expiration: Serial,
-        inception: Serial,
+        expiration: Time32,
+        inception: Time32,
         key_tag: u16,
         signer_name: Name,
         signature: Octets,
@@ -491,11 +525,11 @@ impl<Octets, Name> Rrsig<Octets, Name> {
         self.original_ttl
     }
 
-    pub fn expiration(&self) -> Serial {
+    pub fn expiration(&self) -> Time32 {
         self.expiration
     }
 
-    pub fn inception(&self) -> Serial {
+    pub fn inception(&self) -> Time32 {
         self.inception
     }
 
@@ -514,6 +548,10 @@ impl<Octets, Name> Rrsig<Octets, Name> {
     pub fn set_signature(&mut self, signature: Octets) {
         self.signature = signature
     }
+
+    pub fn into_signature(self) -> Octets {
+        self.signature
+    }
 }
 
 //--- OctetsFrom
@@ -692,8 +730,8 @@ impl<Ref: OctetsRef> Parse<Ref> for Rrsig<Ref::Range, ParsedDname<Ref>> {
         let algorithm = SecAlg::parse(parser)?;
         let labels = u8::parse(parser)?;
         let original_ttl = u32::parse(parser)?;
-        let expiration = Serial::parse(parser)?;
-        let inception = Serial::parse(parser)?;
+        let expiration = Time32::parse(parser)?;
+        let inception = Time32::parse(parser)?;
         let key_tag = u16::parse(parser)?;
         let signer_name = ParsedDname::parse(parser)?;
         let len = parser.remaining();
@@ -716,8 +754,8 @@ impl<Ref: OctetsRef> Parse<Ref> for Rrsig<Ref::Range, ParsedDname<Ref>> {
         SecAlg::skip(parser)?;
         u8::skip(parser)?;
         u32::skip(parser)?;
-        Serial::skip(parser)?;
-        Serial::skip(parser)?;
+        Time32::skip(parser)?;
+        Time32::skip(parser)?;
         u16::skip(parser)?;
         ParsedDname::skip(parser)?;
         parser.advance_to_end();
@@ -773,8 +811,8 @@ impl Scan for Rrsig<Bytes, Dname<Bytes>> {
             SecAlg::scan(scanner)?,
             u8::scan(scanner)?,
             u32::scan(scanner)?,
-            Serial::scan_rrsig(scanner)?,
-            Serial::scan_rrsig(scanner)?,
+            Time32::scan(scanner)?,
+            Time32::scan(scanner)?,
             u16::scan(scanner)?,
             Dname::scan(scanner)?,
             scanner.scan_base64_phrases(Ok)?,
@@ -832,6 +870,145 @@ impl<Octets, Name> RtypeRecordData for Rrsig<Octets, Name> {
     const RTYPE: Rtype = Rtype::Rrsig;
 }
 
+//------------ RrsigBuilder --------------------------------------------------
+
+/// A builder for Rrsig record data.
+///
+/// The builder starts out with the covered record type, the algorithm, and
+/// the signer name. All other fields need to be set via the various
+/// `with_*` methods before the data can be built via [`build`]. Instead of
+/// giving the number of labels directly, it can also be derived from the
+/// owner name of the signed RRset via [`with_owner`].
+///
+/// When building, the builder checks that all fields have been set, that
+/// the number of labels is possible for a domain name, and that the
+/// inception of the validity period is before its expiration.
+///
+/// [`build`]: #method.build
+/// [`with_owner`]: #method.with_owner
+#[derive(Clone, Debug)]
+pub struct RrsigBuilder<Octets, Name> {
+    type_covered: Rtype,
+    algorithm: SecAlg,
+    labels: Option<u8>,
+    original_ttl: Option<u32>,
+    validity: Option<(Time32, Time32)>,
+    key_tag: Option<u16>,
+    signer_name: Name,
+    signature: Option<Octets>,
+}
+
+impl<Octets, Name> Rrsig<Octets, Name> {
+    /// Returns a builder for Rrsig record data.
+    pub fn builder(
+        type_covered: Rtype,
+        algorithm: SecAlg,
+        signer_name: Name,
+    ) -> RrsigBuilder<Octets, Name> {
+        RrsigBuilder::new(type_covered, algorithm, signer_name)
+    }
+}
+
+impl<Octets, Name> RrsigBuilder<Octets, Name> {
+    /// Creates a new builder from the mandatory components.
+    pub fn new(
+        type_covered: Rtype,
+        algorithm: SecAlg,
+        signer_name: Name,
+    ) -> Self {
+        RrsigBuilder {
+            type_covered,
+            algorithm,
+            labels: None,
+            original_ttl: None,
+            validity: None,
+            key_tag: None,
+            signer_name,
+            signature: None,
+        }
+    }
+
+    /// Returns the builder with the number of labels set to `labels`.
+    pub fn with_labels(mut self, labels: u8) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Returns the builder with the labels derived from the owner name.
+    ///
+    /// The number of labels doesn’t include the root label and a leading
+    /// asterisk label.
+    pub fn with_owner<N: ToDname>(mut self, owner: &N) -> Self {
+        self.labels = Some(owner.rrsig_label_count());
+        self
+    }
+
+    /// Returns the builder with the original TTL set to `ttl`.
+    pub fn with_original_ttl(mut self, ttl: u32) -> Self {
+        self.original_ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the builder with the given validity period.
+    pub fn with_validity(
+        mut self,
+        inception: Time32,
+        expiration: Time32,
+    ) -> Self {
+        self.validity = Some((inception, expiration));
+        self
+    }
+
+    /// Returns the builder with the key tag set to `key_tag`.
+    pub fn with_key_tag(mut self, key_tag: u16) -> Self {
+        self.key_tag = Some(key_tag);
+        self
+    }
+
+    /// Returns the builder with the signature set to `signature`.
+    pub fn with_signature(mut self, signature: Octets) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Builds the record data after checking its components.
+    pub fn build(self) -> Result<Rrsig<Octets, Name>, BuildError> {
+        let labels = match self.labels {
+            Some(labels) if labels > 127 => {
+                return Err(BuildError::new("too many labels"))
+            }
+            Some(labels) => labels,
+            None => return Err(BuildError::new("missing labels")),
+        };
+        let original_ttl = self
+            .original_ttl
+            .ok_or_else(|| BuildError::new("missing original TTL"))?;
+        let (inception, expiration) = self
+            .validity
+            .ok_or_else(|| BuildError::new("missing validity period"))?;
+        if inception.partial_cmp(&expiration) != Some(Ordering::Less) {
+            return Err(BuildError::new("inception not before expiration"));
+        }
+        let key_tag = self
+            .key_tag
+            .ok_or_else(|| BuildError::new("missing key tag"))?;
+        let signature = self
+            .signature
+            .ok_or_else(|| BuildError::new("missing signature"))?;
+        Ok(Rrsig::new(
+            self.type_covered,
+            self.algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            self.signer_name,
+            signature,
+        ))
+    }
+}
+
 //------------ Nsec ----------------------------------------------------------
 
 #[derive(Clone)]
@@ -1256,6 +1433,23 @@ impl<Octets> RtypeRecordData for Ds<Octets> {
 
 //------------ RtypeBitmap ---------------------------------------------------
 
+/// A set of record types.
+///
+/// The set is kept in the type bitmap format defined for NSEC records in
+/// RFC 4034, section 4.1.2, which is also used by NSEC3 and CSYNC records.
+/// Apart from being part of record data, the type can be used as a compact
+/// general-purpose set of record types. In addition to checking whether a
+/// type is present via [`contains`] and iterating over the types via
+/// [`iter`], sets can be combined via [`union`], [`intersection`], and
+/// [`difference`]. A new set can be created either through a
+/// [`RtypeBitmapBuilder`] or by collecting an iterator of record types.
+///
+/// [`contains`]: #method.contains
+/// [`iter`]: #method.iter
+/// [`union`]: #method.union
+/// [`intersection`]: #method.intersection
+/// [`difference`]: #method.difference
+/// [`RtypeBitmapBuilder`]: struct.RtypeBitmapBuilder.html
 #[derive(Clone)]
 pub struct RtypeBitmap<Octets>(Octets);
 
@@ -1331,6 +1525,120 @@ impl<Octets: AsRef<[u8]>> RtypeBitmap<Octets> {
         }
         false
     }
+
+    /// Returns whether the set doesn’t contain any record types.
+    pub fn is_empty(&self) -> bool {
+        self.0.as_ref().is_empty()
+    }
+
+    /// Returns the set of record types present in either set.
+    pub fn union<Other, Target>(
+        &self,
+        other: &RtypeBitmap<Other>,
+    ) -> Result<RtypeBitmap<Target>, ShortBuf>
+    where
+        Other: AsRef<[u8]>,
+        Target: FromBuilder,
+        <Target as FromBuilder>::Builder: EmptyBuilder,
+    {
+        self.combine(other, |left, right| left | right)
+    }
+
+    /// Returns the set of record types present in both sets.
+    pub fn intersection<Other, Target>(
+        &self,
+        other: &RtypeBitmap<Other>,
+    ) -> Result<RtypeBitmap<Target>, ShortBuf>
+    where
+        Other: AsRef<[u8]>,
+        Target: FromBuilder,
+        <Target as FromBuilder>::Builder: EmptyBuilder,
+    {
+        self.combine(other, |left, right| left & right)
+    }
+
+    /// Returns the set of record types present in `self` but not in `other`.
+    pub fn difference<Other, Target>(
+        &self,
+        other: &RtypeBitmap<Other>,
+    ) -> Result<RtypeBitmap<Target>, ShortBuf>
+    where
+        Other: AsRef<[u8]>,
+        Target: FromBuilder,
+        <Target as FromBuilder>::Builder: EmptyBuilder,
+    {
+        self.combine(other, |left, right| left & !right)
+    }
+
+    /// Combines two sets window by window using `op` on each octet.
+    ///
+    /// Missing windows and octets are treated as all zeros. Windows that
+    /// end up empty are dropped and trailing zero octets are removed as
+    /// required by the encoding.
+    fn combine<Other, Target, F>(
+        &self,
+        other: &RtypeBitmap<Other>,
+        op: F,
+    ) -> Result<RtypeBitmap<Target>, ShortBuf>
+    where
+        Other: AsRef<[u8]>,
+        Target: FromBuilder,
+        <Target as FromBuilder>::Builder: EmptyBuilder,
+        F: Fn(u8, u8) -> u8,
+    {
+        let mut target = <Target as FromBuilder>::Builder::with_capacity(
+            self.as_slice().len() + other.as_slice().len(),
+        );
+        let mut left = read_window(self.as_slice());
+        let mut right = read_window(other.as_slice());
+        loop {
+            let (block, left_window, right_window) = match (left, right) {
+                (None, None) => break,
+                (Some(((block, window), rest)), None) => {
+                    left = read_window(rest);
+                    (block, window, &[][..])
+                }
+                (None, Some(((block, window), rest))) => {
+                    right = read_window(rest);
+                    (block, &[][..], window)
+                }
+                (
+                    Some(((left_block, left_window), left_rest)),
+                    Some(((right_block, right_window), right_rest)),
+                ) => match left_block.cmp(&right_block) {
+                    Ordering::Less => {
+                        left = read_window(left_rest);
+                        (left_block, left_window, &[][..])
+                    }
+                    Ordering::Greater => {
+                        right = read_window(right_rest);
+                        (right_block, &[][..], right_window)
+                    }
+                    Ordering::Equal => {
+                        left = read_window(left_rest);
+                        right = read_window(right_rest);
+                        (left_block, left_window, right_window)
+                    }
+                },
+            };
+            let mut window = [0u8; 32];
+            let mut len = 0;
+            for (idx, octet) in window.iter_mut().enumerate() {
+                *octet = op(
+                    left_window.get(idx).copied().unwrap_or(0),
+                    right_window.get(idx).copied().unwrap_or(0),
+                );
+                if *octet != 0 {
+                    len = idx + 1;
+                }
+            }
+            if len > 0 {
+                target.append_slice(&[block, len as u8])?;
+                target.append_slice(&window[..len])?;
+            }
+        }
+        Ok(RtypeBitmap(target.freeze()))
+    }
 }
 
 //--- AsRef
@@ -1403,6 +1711,27 @@ impl<O: AsRef<[u8]>> hash::Hash for RtypeBitmap<O> {
     }
 }
 
+//--- FromIterator
+
+impl<Octets> FromIterator<Rtype> for RtypeBitmap<Octets>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    /// Creates a set from the record types of an iterator.
+    ///
+    /// # Panics
+    ///
+    /// The function panics if the octets builder runs out of space.
+    fn from_iter<I: IntoIterator<Item = Rtype>>(iter: I) -> Self {
+        let mut builder = RtypeBitmapBuilder::<Octets::Builder>::new();
+        for rtype in iter {
+            builder.add(rtype).expect("record type bitmap too long")
+        }
+        builder.finalize()
+    }
+}
+
 //--- IntoIterator
 
 impl<'a, Octets: AsRef<[u8]>> IntoIterator for &'a RtypeBitmap<Octets> {
@@ -1820,6 +2149,106 @@ mod test {
         assert_eq!(types, bitmap_types);
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn rtype_bitmap_set_operations() {
+        use std::vec::Vec;
+
+        let left: RtypeBitmap<Vec<u8>> =
+            [Rtype::A, Rtype::Mx, Rtype::Caa, Rtype::Int(1234)]
+                .iter()
+                .copied()
+                .collect();
+        let right: RtypeBitmap<Vec<u8>> =
+            [Rtype::A, Rtype::Txt, Rtype::Int(1234), Rtype::Ta]
+                .iter()
+                .copied()
+                .collect();
+        assert!(left.contains(Rtype::Caa));
+        assert!(!left.contains(Rtype::Txt));
+
+        let union: RtypeBitmap<Vec<u8>> = left.union(&right).unwrap();
+        assert_eq!(
+            union.iter().collect::<Vec<_>>(),
+            [
+                Rtype::A,
+                Rtype::Mx,
+                Rtype::Txt,
+                Rtype::Caa,
+                Rtype::Int(1234),
+                Rtype::Ta
+            ]
+        );
+        let intersection: RtypeBitmap<Vec<u8>> =
+            left.intersection(&right).unwrap();
+        assert_eq!(
+            intersection.iter().collect::<Vec<_>>(),
+            [Rtype::A, Rtype::Int(1234)]
+        );
+        let difference: RtypeBitmap<Vec<u8>> =
+            left.difference(&right).unwrap();
+        assert_eq!(
+            difference.iter().collect::<Vec<_>>(),
+            [Rtype::Mx, Rtype::Caa]
+        );
+
+        // The results have to be in canonical form, i.e., the same as if
+        // built from scratch.
+        assert_eq!(difference.as_slice(), b"\x00\x02\x00\x01\x01\x01\x40");
+        let empty: RtypeBitmap<Vec<u8>> = left.difference(&left).unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.as_slice().is_empty());
+        assert!(RtypeBitmap::from_octets(empty.as_slice()).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn rrsig_builder() {
+        use crate::base::name::Dname;
+        use std::vec::Vec;
+
+        type Builder = RrsigBuilder<Vec<u8>, Dname<Vec<u8>>>;
+
+        let name = |s: &str| -> Dname<Vec<u8>> { s.parse().unwrap() };
+        let builder = || -> Builder {
+            Rrsig::builder(Rtype::A, SecAlg::EcdsaP256Sha256, name("example"))
+                .with_owner(&name("*.www.example"))
+                .with_original_ttl(3600)
+                .with_validity(Time32::from_int(1000), Time32::from_int(2000))
+                .with_key_tag(12345)
+                .with_signature(b"sig".to_vec())
+        };
+        assert_eq!(
+            builder().build().unwrap(),
+            Rrsig::new(
+                Rtype::A,
+                SecAlg::EcdsaP256Sha256,
+                2,
+                3600,
+                Time32::from_int(2000),
+                Time32::from_int(1000),
+                12345,
+                name("example"),
+                b"sig".to_vec()
+            )
+        );
+
+        let err = |builder: Builder| builder.build().unwrap_err().msg();
+        assert_eq!(err(builder().with_labels(128)), "too many labels");
+        assert_eq!(
+            err(builder().with_validity(
+                Time32::from_int(2000),
+                Time32::from_int(1000)
+            )),
+            "inception not before expiration"
+        );
+        assert_eq!(
+            err(Rrsig::builder(Rtype::A, SecAlg::RsaSha256, name("example"))
+                .with_labels(1)),
+            "missing original TTL"
+        );
+    }
+
     #[test]
     #[cfg(feature = "bytes")]
     fn dnskey_key_tag() {
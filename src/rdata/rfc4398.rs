@@ -0,0 +1,246 @@
+//! Record data from [RFC 4398]: CERT records.
+//!
+//! This RFC defines the CERT record type for storing certificates and
+//! certificate revocation lists in the DNS.
+//!
+//! [RFC 4398]: https://tools.ietf.org/html/rfc4398
+
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{CertType, Rtype, SecAlg};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+#[cfg(feature = "master")]
+use crate::master::scan::{CharSource, Scan, ScanError, Scanner};
+use crate::utils::base64;
+#[cfg(feature = "master")]
+use bytes::Bytes;
+use core::cmp::Ordering;
+use core::{fmt, hash};
+
+//------------ Cert ----------------------------------------------------------
+
+/// CERT record data.
+///
+/// The CERT record holds a certificate or certificate revocation list. The
+/// format of the data is given by the certificate type. The key tag and
+/// algorithm identify the DNSKEY the certificate relates to, if any, and
+/// are zero otherwise.
+///
+/// The CERT record type is defined in RFC 4398, section 2.
+#[derive(Clone)]
+pub struct Cert<Octets> {
+    cert_type: CertType,
+    key_tag: u16,
+    algorithm: SecAlg,
+    certificate: Octets,
+}
+
+impl<Octets> Cert<Octets> {
+    /// Creates new CERT record data from its components.
+    pub fn new(
+        cert_type: CertType,
+        key_tag: u16,
+        algorithm: SecAlg,
+        certificate: Octets,
+    ) -> Self {
+        Cert {
+            cert_type,
+            key_tag,
+            algorithm,
+            certificate,
+        }
+    }
+
+    /// Returns the format of the certificate.
+    pub fn cert_type(&self) -> CertType {
+        self.cert_type
+    }
+
+    /// Returns the key tag of the related DNSKEY.
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// Returns the algorithm of the related DNSKEY.
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    /// Returns the certificate or CRL.
+    pub fn certificate(&self) -> &Octets {
+        &self.certificate
+    }
+
+    /// Converts the record data into the certificate or CRL.
+    pub fn into_certificate(self) -> Octets {
+        self.certificate
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets> OctetsFrom<Cert<SrcOctets>> for Cert<Octets>
+where
+    Octets: OctetsFrom<SrcOctets>,
+{
+    fn octets_from(source: Cert<SrcOctets>) -> Result<Self, ShortBuf> {
+        Ok(Cert::new(
+            source.cert_type,
+            source.key_tag,
+            source.algorithm,
+            Octets::octets_from(source.certificate)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<Octets, Other> PartialEq<Cert<Other>> for Cert<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Cert<Other>) -> bool {
+        self.cert_type == other.cert_type
+            && self.key_tag == other.key_tag
+            && self.algorithm == other.algorithm
+            && self.certificate.as_ref() == other.certificate.as_ref()
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Eq for Cert<Octets> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<Octets, Other> PartialOrd<Cert<Other>> for Cert<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Cert<Other>) -> Option<Ordering> {
+        Some(self.canonical_cmp(other))
+    }
+}
+
+impl<Octets, Other> CanonicalOrd<Cert<Other>> for Cert<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Cert<Other>) -> Ordering {
+        match self.cert_type.cmp(&other.cert_type) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.key_tag.cmp(&other.key_tag) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.certificate.as_ref().cmp(other.certificate.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Ord for Cert<Octets> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<Octets: AsRef<[u8]>> hash::Hash for Cert<Octets> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.cert_type.hash(state);
+        self.key_tag.hash(state);
+        self.algorithm.hash(state);
+        self.certificate.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Cert<Ref::Range> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let len = match parser.remaining().checked_sub(5) {
+            Some(len) => len,
+            None => return Err(ParseError::ShortInput),
+        };
+        Ok(Self::new(
+            CertType::parse(parser)?,
+            u16::parse(parser)?,
+            SecAlg::parse(parser)?,
+            parser.parse_octets(len)?,
+        ))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        if parser.remaining() < 5 {
+            return Err(ParseError::ShortInput);
+        }
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Compose for Cert<Octets> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.cert_type.compose(buf)?;
+            self.key_tag.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            buf.append_slice(self.certificate.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Cert<Bytes> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        Ok(Self::new(
+            CertType::scan(scanner)?,
+            u16::scan(scanner)?,
+            SecAlg::scan(scanner)?,
+            scanner.scan_base64_phrases(Ok)?,
+        ))
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Display for Cert<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} ", self.cert_type, self.key_tag, self.algorithm)?;
+        base64::display(&self.certificate, f)
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Cert<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cert")
+            .field("cert_type", &self.cert_type)
+            .field("key_tag", &self.key_tag)
+            .field("algorithm", &self.algorithm)
+            .field("certificate", &self.certificate.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets> RtypeRecordData for Cert<Octets> {
+    const RTYPE: Rtype = Rtype::Cert;
+}
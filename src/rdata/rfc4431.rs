@@ -0,0 +1,494 @@
+//! Record data from [RFC 4431]: DLV records.
+//!
+//! This RFC defines the DLV record type for DNSSEC lookaside validation.
+//! The module also contains the TA record type which shares its format and
+//! was used for a similar purpose but has never been published as an RFC.
+//!
+//! [RFC 4431]: https://tools.ietf.org/html/rfc4431
+
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{DigestAlg, Rtype, SecAlg};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+#[cfg(feature = "master")]
+use crate::master::scan::{CharSource, Scan, ScanError, Scanner};
+#[cfg(feature = "master")]
+use bytes::Bytes;
+use core::cmp::Ordering;
+use core::{fmt, hash};
+
+//------------ Dlv ----------------------------------------------------------
+
+/// DLV record data.
+///
+/// The DLV record holds a delegation signer for a zone published in a
+/// DNSSEC lookaside validation registry rather than the parent zone. Its
+/// format is identical to that of the DS record.
+///
+/// The DLV record type is defined in RFC 4431. Lookaside validation has
+/// since been retired, so the type is mostly encountered in archived zones.
+#[derive(Clone)]
+pub struct Dlv<Octets> {
+    key_tag: u16,
+    algorithm: SecAlg,
+    digest_type: DigestAlg,
+    digest: Octets,
+}
+
+impl<Octets> Dlv<Octets> {
+    pub fn new(
+        key_tag: u16,
+        algorithm: SecAlg,
+        digest_type: DigestAlg,
+        digest: Octets,
+    ) -> Self {
+        Dlv {
+            key_tag,
+            algorithm,
+            digest_type,
+            digest,
+        }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    pub fn digest_type(&self) -> DigestAlg {
+        self.digest_type
+    }
+
+    pub fn digest(&self) -> &Octets {
+        &self.digest
+    }
+
+    pub fn into_digest(self) -> Octets {
+        self.digest
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets> OctetsFrom<Dlv<SrcOctets>> for Dlv<Octets>
+where
+    Octets: OctetsFrom<SrcOctets>,
+{
+    fn octets_from(source: Dlv<SrcOctets>) -> Result<Self, ShortBuf> {
+        Ok(Dlv::new(
+            source.key_tag,
+            source.algorithm,
+            source.digest_type,
+            Octets::octets_from(source.digest)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<Octets, Other> PartialEq<Dlv<Other>> for Dlv<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Dlv<Other>) -> bool {
+        self.key_tag == other.key_tag
+            && self.algorithm == other.algorithm
+            && self.digest_type == other.digest_type
+            && self.digest.as_ref().eq(other.digest.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Eq for Dlv<Octets> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<Octets, Other> PartialOrd<Dlv<Other>> for Dlv<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Dlv<Other>) -> Option<Ordering> {
+        match self.key_tag.partial_cmp(&other.key_tag) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.algorithm.partial_cmp(&other.algorithm) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.digest_type.partial_cmp(&other.digest_type) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        self.digest.as_ref().partial_cmp(other.digest.as_ref())
+    }
+}
+
+impl<Octets, Other> CanonicalOrd<Dlv<Other>> for Dlv<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Dlv<Other>) -> Ordering {
+        match self.key_tag.cmp(&other.key_tag) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.digest_type.cmp(&other.digest_type) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.digest.as_ref().cmp(other.digest.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Ord for Dlv<Octets> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<Octets: AsRef<[u8]>> hash::Hash for Dlv<Octets> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.key_tag.hash(state);
+        self.algorithm.hash(state);
+        self.digest_type.hash(state);
+        self.digest.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Dlv<Ref::Range> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let len = match parser.remaining().checked_sub(4) {
+            Some(len) => len,
+            None => return Err(ParseError::ShortInput),
+        };
+        Ok(Self::new(
+            u16::parse(parser)?,
+            SecAlg::parse(parser)?,
+            DigestAlg::parse(parser)?,
+            parser.parse_octets(len)?,
+        ))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        if parser.remaining() < 4 {
+            return Err(ParseError::ShortInput);
+        }
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Compose for Dlv<Octets> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.key_tag.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            self.digest_type.compose(buf)?;
+            buf.append_slice(self.digest.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Dlv<Bytes> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        Ok(Self::new(
+            u16::scan(scanner)?,
+            SecAlg::scan(scanner)?,
+            DigestAlg::scan(scanner)?,
+            scanner.scan_hex_words(Ok)?,
+        ))
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Display for Dlv<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            self.key_tag, self.algorithm, self.digest_type
+        )?;
+        for ch in self.digest.as_ref() {
+            write!(f, "{:02x}", ch)?
+        }
+        Ok(())
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Dlv<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Dlv")
+            .field("key_tag", &self.key_tag)
+            .field("algorithm", &self.algorithm)
+            .field("digest_type", &self.digest_type)
+            .field("digest", &self.digest.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets> RtypeRecordData for Dlv<Octets> {
+    const RTYPE: Rtype = Rtype::Dlv;
+}
+
+//------------ Ta -----------------------------------------------------------
+
+/// TA record data.
+///
+/// The TA record holds a trust anchor for a zone in the same format as the
+/// DS record. It was defined for deploying DNSSEC before the root zone was
+/// signed and is not specified in an RFC.
+#[derive(Clone)]
+pub struct Ta<Octets> {
+    key_tag: u16,
+    algorithm: SecAlg,
+    digest_type: DigestAlg,
+    digest: Octets,
+}
+
+impl<Octets> Ta<Octets> {
+    pub fn new(
+        key_tag: u16,
+        algorithm: SecAlg,
+        digest_type: DigestAlg,
+        digest: Octets,
+    ) -> Self {
+        Ta {
+            key_tag,
+            algorithm,
+            digest_type,
+            digest,
+        }
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    pub fn digest_type(&self) -> DigestAlg {
+        self.digest_type
+    }
+
+    pub fn digest(&self) -> &Octets {
+        &self.digest
+    }
+
+    pub fn into_digest(self) -> Octets {
+        self.digest
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets> OctetsFrom<Ta<SrcOctets>> for Ta<Octets>
+where
+    Octets: OctetsFrom<SrcOctets>,
+{
+    fn octets_from(source: Ta<SrcOctets>) -> Result<Self, ShortBuf> {
+        Ok(Ta::new(
+            source.key_tag,
+            source.algorithm,
+            source.digest_type,
+            Octets::octets_from(source.digest)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<Octets, Other> PartialEq<Ta<Other>> for Ta<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Ta<Other>) -> bool {
+        self.key_tag == other.key_tag
+            && self.algorithm == other.algorithm
+            && self.digest_type == other.digest_type
+            && self.digest.as_ref().eq(other.digest.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Eq for Ta<Octets> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<Octets, Other> PartialOrd<Ta<Other>> for Ta<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Ta<Other>) -> Option<Ordering> {
+        match self.key_tag.partial_cmp(&other.key_tag) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.algorithm.partial_cmp(&other.algorithm) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.digest_type.partial_cmp(&other.digest_type) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        self.digest.as_ref().partial_cmp(other.digest.as_ref())
+    }
+}
+
+impl<Octets, Other> CanonicalOrd<Ta<Other>> for Ta<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Ta<Other>) -> Ordering {
+        match self.key_tag.cmp(&other.key_tag) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.digest_type.cmp(&other.digest_type) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.digest.as_ref().cmp(other.digest.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Ord for Ta<Octets> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<Octets: AsRef<[u8]>> hash::Hash for Ta<Octets> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.key_tag.hash(state);
+        self.algorithm.hash(state);
+        self.digest_type.hash(state);
+        self.digest.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Ta<Ref::Range> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let len = match parser.remaining().checked_sub(4) {
+            Some(len) => len,
+            None => return Err(ParseError::ShortInput),
+        };
+        Ok(Self::new(
+            u16::parse(parser)?,
+            SecAlg::parse(parser)?,
+            DigestAlg::parse(parser)?,
+            parser.parse_octets(len)?,
+        ))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        if parser.remaining() < 4 {
+            return Err(ParseError::ShortInput);
+        }
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Compose for Ta<Octets> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.key_tag.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            self.digest_type.compose(buf)?;
+            buf.append_slice(self.digest.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Ta<Bytes> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        Ok(Self::new(
+            u16::scan(scanner)?,
+            SecAlg::scan(scanner)?,
+            DigestAlg::scan(scanner)?,
+            scanner.scan_hex_words(Ok)?,
+        ))
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Display for Ta<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ",
+            self.key_tag, self.algorithm, self.digest_type
+        )?;
+        for ch in self.digest.as_ref() {
+            write!(f, "{:02x}", ch)?
+        }
+        Ok(())
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Ta<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Ta")
+            .field("key_tag", &self.key_tag)
+            .field("algorithm", &self.algorithm)
+            .field("digest_type", &self.digest_type)
+            .field("digest", &self.digest.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets> RtypeRecordData for Ta<Octets> {
+    const RTYPE: Rtype = Rtype::Ta;
+}
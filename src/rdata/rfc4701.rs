@@ -0,0 +1,169 @@
+//! Record data from [RFC 4701]: DHCID records.
+//!
+//! This RFC defines the DHCID record type used by DHCP servers to avoid
+//! conflicts when updating the DNS on behalf of their clients.
+//!
+//! [RFC 4701]: https://tools.ietf.org/html/rfc4701
+
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::Rtype;
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+#[cfg(feature = "master")]
+use crate::master::scan::{CharSource, Scan, ScanError, Scanner};
+use crate::utils::base64;
+#[cfg(feature = "master")]
+use bytes::Bytes;
+use core::cmp::Ordering;
+use core::{fmt, hash};
+
+//------------ Dhcid ---------------------------------------------------------
+
+/// DHCID record data.
+///
+/// The DHCID record associates a DHCP client’s identity with the names
+/// the DHCP server maintains for it. The data consists of an identifier
+/// type, a digest type, and a digest of the client’s identity. Since the
+/// DNS treats the data as opaque, this type only provides access to the raw
+/// octets.
+///
+/// The DHCID record type is defined in RFC 4701, section 3.
+#[derive(Clone)]
+pub struct Dhcid<Octets> {
+    data: Octets,
+}
+
+impl<Octets> Dhcid<Octets> {
+    /// Creates new DHCID record data from the raw data.
+    pub fn new(data: Octets) -> Self {
+        Dhcid { data }
+    }
+
+    /// Returns the raw data of the record.
+    pub fn data(&self) -> &Octets {
+        &self.data
+    }
+
+    /// Converts the record data into the raw data.
+    pub fn into_data(self) -> Octets {
+        self.data
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets> OctetsFrom<Dhcid<SrcOctets>> for Dhcid<Octets>
+where
+    Octets: OctetsFrom<SrcOctets>,
+{
+    fn octets_from(source: Dhcid<SrcOctets>) -> Result<Self, ShortBuf> {
+        Octets::octets_from(source.data).map(Self::new)
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<Octets, Other> PartialEq<Dhcid<Other>> for Dhcid<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Dhcid<Other>) -> bool {
+        self.data.as_ref().eq(other.data.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Eq for Dhcid<Octets> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<Octets, Other> PartialOrd<Dhcid<Other>> for Dhcid<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Dhcid<Other>) -> Option<Ordering> {
+        self.data.as_ref().partial_cmp(other.data.as_ref())
+    }
+}
+
+impl<Octets, Other> CanonicalOrd<Dhcid<Other>> for Dhcid<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Dhcid<Other>) -> Ordering {
+        self.data.as_ref().cmp(other.data.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Ord for Dhcid<Octets> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.data.as_ref().cmp(other.data.as_ref())
+    }
+}
+
+//--- Hash
+
+impl<Octets: AsRef<[u8]>> hash::Hash for Dhcid<Octets> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.data.as_ref().hash(state)
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Dhcid<Ref::Range> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let len = parser.remaining();
+        parser.parse_octets(len).map(Self::new)
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Compose for Dhcid<Octets> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_slice(self.data.as_ref())
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Dhcid<Bytes> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        scanner.scan_base64_phrases(Ok).map(Self::new)
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Display for Dhcid<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        base64::display(&self.data, f)
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Dhcid<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Dhcid").field(&self.data.as_ref()).finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets> RtypeRecordData for Dhcid<Octets> {
+    const RTYPE: Rtype = Rtype::Dhcid;
+}
@@ -0,0 +1,247 @@
+//! Record data from [RFC 8777]: AMTRELAY records.
+//!
+//! This RFC defines the AMTRELAY record type used to discover relays for
+//! Automatic Multicast Tunneling.
+//!
+//! [RFC 8777]: https://tools.ietf.org/html/rfc8777
+
+use super::rfc4025::Gateway;
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::Rtype;
+#[cfg(feature = "master")]
+use crate::base::name::Dname;
+use crate::base::name::{ParsedDname, ToDname};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+#[cfg(feature = "master")]
+use crate::master::scan::{
+    CharSource, Scan, ScanError, Scanner, SyntaxError,
+};
+#[cfg(feature = "master")]
+use bytes::Bytes;
+use core::cmp::Ordering;
+use core::fmt;
+#[cfg(feature = "master")]
+use std::string::String;
+
+//------------ Amtrelay ------------------------------------------------------
+
+/// AMTRELAY record data.
+///
+/// The AMTRELAY record provides the address of a relay for Automatic
+/// Multicast Tunneling. The relay is encoded in the same way as the gateway
+/// of an IPSECKEY record and can be missing or given as an IPv4 address, an
+/// IPv6 address, or a domain name.
+///
+/// The AMTRELAY record type is defined in RFC 8777, section 4.
+#[derive(Clone, Debug, Hash)]
+pub struct Amtrelay<N> {
+    precedence: u8,
+    discovery_optional: bool,
+    relay: Gateway<N>,
+}
+
+impl<N> Amtrelay<N> {
+    /// Creates new AMTRELAY record data from its components.
+    pub fn new(
+        precedence: u8,
+        discovery_optional: bool,
+        relay: Gateway<N>,
+    ) -> Self {
+        Amtrelay {
+            precedence,
+            discovery_optional,
+            relay,
+        }
+    }
+
+    /// Returns the precedence of the record.
+    ///
+    /// Relays with a lower precedence are to be preferred.
+    pub fn precedence(&self) -> u8 {
+        self.precedence
+    }
+
+    /// Returns whether the discovery optional flag is set.
+    ///
+    /// If the flag is set, the gateway may send a request to the relay
+    /// directly without first sending a relay discovery message.
+    pub fn discovery_optional(&self) -> bool {
+        self.discovery_optional
+    }
+
+    /// Returns the type of the relay as it appears in the wire format.
+    pub fn relay_type(&self) -> u8 {
+        self.relay.gateway_type()
+    }
+
+    /// Returns the relay.
+    pub fn relay(&self) -> &Gateway<N> {
+        &self.relay
+    }
+
+    /// Returns the octet combining the discovery flag and the relay type.
+    fn type_octet(&self) -> u8 {
+        if self.discovery_optional {
+            self.relay_type() | 0x80
+        } else {
+            self.relay_type()
+        }
+    }
+}
+
+//--- OctetsFrom
+
+impl<Name, SrcName> OctetsFrom<Amtrelay<SrcName>> for Amtrelay<Name>
+where
+    Name: OctetsFrom<SrcName>,
+{
+    fn octets_from(source: Amtrelay<SrcName>) -> Result<Self, ShortBuf> {
+        Ok(Amtrelay::new(
+            source.precedence,
+            source.discovery_optional,
+            Gateway::octets_from(source.relay)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<N, NN> PartialEq<Amtrelay<NN>> for Amtrelay<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn eq(&self, other: &Amtrelay<NN>) -> bool {
+        self.precedence == other.precedence
+            && self.discovery_optional == other.discovery_optional
+            && self.relay == other.relay
+    }
+}
+
+impl<N: ToDname> Eq for Amtrelay<N> {}
+
+//--- PartialOrd, Ord, and CanonicalOrd
+
+impl<N, NN> PartialOrd<Amtrelay<NN>> for Amtrelay<N>
+where
+    N: ToDname,
+    NN: ToDname,
+{
+    fn partial_cmp(&self, other: &Amtrelay<NN>) -> Option<Ordering> {
+        match self.precedence.partial_cmp(&other.precedence) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        match self.type_octet().partial_cmp(&other.type_octet()) {
+            Some(Ordering::Equal) => {}
+            other => return other,
+        }
+        self.relay.partial_cmp(&other.relay)
+    }
+}
+
+impl<N: ToDname> Ord for Amtrelay<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.precedence.cmp(&other.precedence) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.type_octet().cmp(&other.type_octet()) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.relay.cmp(&other.relay)
+    }
+}
+
+impl<N: ToDname, NN: ToDname> CanonicalOrd<Amtrelay<NN>> for Amtrelay<N> {
+    fn canonical_cmp(&self, other: &Amtrelay<NN>) -> Ordering {
+        match self.precedence.cmp(&other.precedence) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.type_octet().cmp(&other.type_octet()) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.relay.canonical_cmp(&other.relay)
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Amtrelay<ParsedDname<Ref>> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let precedence = u8::parse(parser)?;
+        let type_octet = u8::parse(parser)?;
+        let relay = Gateway::parse(type_octet & 0x7F, parser)?;
+        Ok(Self::new(precedence, type_octet & 0x80 != 0, relay))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        u8::skip(parser)?;
+        let type_octet = u8::parse(parser)?;
+        Gateway::skip(type_octet & 0x7F, parser)
+    }
+}
+
+impl<N: ToDname> Compose for Amtrelay<N> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|target| {
+            self.precedence.compose(target)?;
+            self.type_octet().compose(target)?;
+            self.relay.compose(target)
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Amtrelay<Dname<Bytes>> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        let precedence = u8::scan(scanner)?;
+        let pos = scanner.pos();
+        let discovery_optional = match u8::scan(scanner)? {
+            0 => false,
+            1 => true,
+            _ => {
+                return Err(ScanError::Syntax(
+                    SyntaxError::Expected(String::from("0 or 1")),
+                    pos,
+                ))
+            }
+        };
+        let relay_type = u8::scan(scanner)?;
+        let relay = Gateway::scan(relay_type, scanner)?;
+        Ok(Self::new(precedence, discovery_optional, relay))
+    }
+}
+
+impl<N: fmt::Display> fmt::Display for Amtrelay<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.precedence,
+            u8::from(self.discovery_optional),
+            self.relay_type(),
+            self.relay
+        )
+    }
+}
+
+//--- RtypeRecordData
+
+impl<N> RtypeRecordData for Amtrelay<N> {
+    const RTYPE: Rtype = Rtype::Amtrelay;
+}
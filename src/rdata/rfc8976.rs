@@ -0,0 +1,247 @@
+//! Record data from [RFC 8976]: ZONEMD records.
+//!
+//! [RFC 8976]: https://tools.ietf.org/html/rfc8976
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Rtype, ZonemdAlg, ZonemdScheme};
+use crate::base::octets::{
+    Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
+    ShortBuf,
+};
+use crate::base::rdata::RtypeRecordData;
+use crate::base::serial::Serial;
+#[cfg(feature = "master")]
+use crate::master::scan::{CharSource, Scan, ScanError, Scanner};
+#[cfg(feature = "master")]
+use bytes::Bytes;
+use core::cmp::Ordering;
+use core::{fmt, hash};
+
+//------------ Zonemd --------------------------------------------------------
+
+/// Zonemd record data.
+///
+/// The ZONEMD record contains a message digest over the content of a zone.
+/// It is defined in RFC 8976.
+#[derive(Clone)]
+pub struct Zonemd<Octets> {
+    serial: Serial,
+    scheme: ZonemdScheme,
+    algorithm: ZonemdAlg,
+    digest: Octets,
+}
+
+impl<Octets> Zonemd<Octets> {
+    pub fn new(
+        serial: Serial,
+        scheme: ZonemdScheme,
+        algorithm: ZonemdAlg,
+        digest: Octets,
+    ) -> Self {
+        Zonemd {
+            serial,
+            scheme,
+            algorithm,
+            digest,
+        }
+    }
+
+    /// The serial of the SOA record of the zone the digest is for.
+    pub fn serial(&self) -> Serial {
+        self.serial
+    }
+
+    /// The method used for collecting the zone data for the digest.
+    pub fn scheme(&self) -> ZonemdScheme {
+        self.scheme
+    }
+
+    /// The hash algorithm used to calculate the digest.
+    pub fn algorithm(&self) -> ZonemdAlg {
+        self.algorithm
+    }
+
+    /// The digest.
+    pub fn digest(&self) -> &Octets {
+        &self.digest
+    }
+
+    pub fn into_digest(self) -> Octets {
+        self.digest
+    }
+}
+
+//--- OctetsFrom
+
+impl<Octets, SrcOctets> OctetsFrom<Zonemd<SrcOctets>> for Zonemd<Octets>
+where
+    Octets: OctetsFrom<SrcOctets>,
+{
+    fn octets_from(source: Zonemd<SrcOctets>) -> Result<Self, ShortBuf> {
+        Ok(Zonemd::new(
+            source.serial,
+            source.scheme,
+            source.algorithm,
+            Octets::octets_from(source.digest)?,
+        ))
+    }
+}
+
+//--- PartialEq and Eq
+
+impl<Octets, Other> PartialEq<Zonemd<Other>> for Zonemd<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn eq(&self, other: &Zonemd<Other>) -> bool {
+        self.serial == other.serial
+            && self.scheme == other.scheme
+            && self.algorithm == other.algorithm
+            && self.digest.as_ref().eq(other.digest.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Eq for Zonemd<Octets> {}
+
+//--- PartialOrd, CanonicalOrd, and Ord
+
+impl<Octets, Other> PartialOrd<Zonemd<Other>> for Zonemd<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn partial_cmp(&self, other: &Zonemd<Other>) -> Option<Ordering> {
+        Some(self.canonical_cmp(other))
+    }
+}
+
+impl<Octets, Other> CanonicalOrd<Zonemd<Other>> for Zonemd<Octets>
+where
+    Octets: AsRef<[u8]>,
+    Other: AsRef<[u8]>,
+{
+    fn canonical_cmp(&self, other: &Zonemd<Other>) -> Ordering {
+        // The serial is compared as an integer here since this is about
+        // the wire format rather than sequence space arithmetic.
+        match self.serial.into_int().cmp(&other.serial.into_int()) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.scheme.cmp(&other.scheme) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        match self.algorithm.cmp(&other.algorithm) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        self.digest.as_ref().cmp(other.digest.as_ref())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Ord for Zonemd<Octets> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+//--- Hash
+
+impl<Octets: AsRef<[u8]>> hash::Hash for Zonemd<Octets> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.serial.hash(state);
+        self.scheme.hash(state);
+        self.algorithm.hash(state);
+        self.digest.as_ref().hash(state);
+    }
+}
+
+//--- Parse and Compose
+
+impl<Ref: OctetsRef> Parse<Ref> for Zonemd<Ref::Range> {
+    fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
+        let len = match parser.remaining().checked_sub(6) {
+            Some(len) => len,
+            None => return Err(ParseError::ShortInput),
+        };
+        Ok(Self::new(
+            Serial::parse(parser)?,
+            ZonemdScheme::parse(parser)?,
+            ZonemdAlg::parse(parser)?,
+            parser.parse_octets(len)?,
+        ))
+    }
+
+    fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
+        if parser.remaining() < 6 {
+            return Err(ParseError::ShortInput);
+        }
+        parser.advance_to_end();
+        Ok(())
+    }
+}
+
+impl<Octets: AsRef<[u8]>> Compose for Zonemd<Octets> {
+    fn compose<T: OctetsBuilder>(
+        &self,
+        target: &mut T,
+    ) -> Result<(), ShortBuf> {
+        target.append_all(|buf| {
+            self.serial.compose(buf)?;
+            self.scheme.compose(buf)?;
+            self.algorithm.compose(buf)?;
+            buf.append_slice(self.digest.as_ref())
+        })
+    }
+}
+
+//--- Scan and Display
+
+#[cfg(feature = "master")]
+impl Scan for Zonemd<Bytes> {
+    fn scan<C: CharSource>(
+        scanner: &mut Scanner<C>,
+    ) -> Result<Self, ScanError> {
+        Ok(Self::new(
+            Serial::scan(scanner)?,
+            ZonemdScheme::scan(scanner)?,
+            ZonemdAlg::scan(scanner)?,
+            scanner.scan_hex_words(Ok)?,
+        ))
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Display for Zonemd<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} ", self.serial, self.scheme, self.algorithm)?;
+        for ch in self.digest.as_ref() {
+            write!(f, "{:02x}", ch)?
+        }
+        Ok(())
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Zonemd<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Zonemd")
+            .field("serial", &self.serial)
+            .field("scheme", &self.scheme)
+            .field("algorithm", &self.algorithm)
+            .field("digest", &self.digest.as_ref())
+            .finish()
+    }
+}
+
+//--- RtypeRecordData
+
+impl<Octets> RtypeRecordData for Zonemd<Octets> {
+    const RTYPE: Rtype = Rtype::Zonemd;
+}
+
+//------------ parsed --------------------------------------------------------
+
+pub mod parsed {
+    pub use super::Zonemd;
+}
@@ -0,0 +1,191 @@
+//! Validation tokens for the ACME dns-01 challenge.
+//!
+//! With the dns-01 challenge of the Automatic Certificate Management
+//! Environment, a client proves control over a domain by publishing a TXT
+//! record at the `_acme-challenge` subdomain. The record contains the
+//! SHA-256 digest of the key authorization for the challenge encoded in
+//! unpadded *base64url*. The challenge is defined in section 8.4 of
+//! [RFC 8555].
+//!
+//! [RFC 8555]: https://tools.ietf.org/html/rfc8555
+
+use super::{txt_string, TextError};
+use crate::rdata::rfc1035::Txt;
+use core::fmt;
+
+//------------ Dns01Token ----------------------------------------------------
+
+/// The validation token of an ACME dns-01 challenge.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Dns01Token {
+    digest: [u8; 32],
+}
+
+impl Dns01Token {
+    /// The length of the encoded token.
+    const LEN: usize = 43;
+
+    /// Creates a token from the digest of the key authorization.
+    pub fn from_digest(digest: [u8; 32]) -> Self {
+        Dns01Token { digest }
+    }
+
+    /// Creates a token for a key authorization.
+    ///
+    /// The key authorization consists of the token of the challenge and
+    /// the thumbprint of the account key separated by a period.
+    #[cfg(feature = "ring")]
+    pub fn for_key_authorization(key_authorization: &str) -> Self {
+        let digest = ring::digest::digest(
+            &ring::digest::SHA256,
+            key_authorization.as_bytes(),
+        );
+        let mut res = [0u8; 32];
+        res.copy_from_slice(digest.as_ref());
+        Dns01Token { digest: res }
+    }
+
+    /// Parses a token from a string.
+    ///
+    /// The string has to contain exactly the encoded digest.
+    pub fn parse(text: &str) -> Result<Self, TextError> {
+        if text.len() != Self::LEN {
+            return Err(TextError::new(
+                text.len().min(Self::LEN),
+                "expected 43 characters",
+            ));
+        }
+        let mut digest = [0u8; 32];
+        let mut acc = 0u32;
+        let mut bits = 0;
+        let mut out = 0;
+        for (idx, ch) in text.bytes().enumerate() {
+            let val = match decode_char(ch) {
+                Some(val) => val,
+                None => return Err(TextError::new(idx, "invalid base64url")),
+            };
+            acc = (acc << 6) | u32::from(val);
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                digest[out] = (acc >> bits) as u8;
+                out += 1;
+                acc &= (1 << bits) - 1;
+            }
+        }
+        // The remaining bits of the last character have to be zero for the
+        // encoding to be canonical.
+        if acc != 0 {
+            return Err(TextError::new(Self::LEN - 1, "invalid base64url"));
+        }
+        Ok(Dns01Token { digest })
+    }
+
+    /// Parses a token from TXT record data.
+    pub fn from_txt<Octets: AsRef<[u8]>>(
+        txt: &Txt<Octets>,
+    ) -> Result<Self, TextError> {
+        Self::parse(&txt_string(txt)?)
+    }
+
+    /// Returns the digest of the key authorization.
+    pub fn digest(&self) -> &[u8; 32] {
+        &self.digest
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Dns01Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use core::fmt::Write;
+
+        let mut acc = 0u32;
+        let mut bits = 0;
+        for &octet in self.digest.iter() {
+            acc = (acc << 8) | u32::from(octet);
+            bits += 8;
+            while bits >= 6 {
+                bits -= 6;
+                f.write_char(encode_char((acc >> bits) as u8 & 0x3F))?;
+            }
+            acc &= (1 << bits) - 1;
+        }
+        if bits > 0 {
+            f.write_char(encode_char((acc << (6 - bits)) as u8 & 0x3F))?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the value of a *base64url* character.
+fn decode_char(ch: u8) -> Option<u8> {
+    match ch {
+        b'A'..=b'Z' => Some(ch - b'A'),
+        b'a'..=b'z' => Some(ch - b'a' + 26),
+        b'0'..=b'9' => Some(ch - b'0' + 52),
+        b'-' => Some(62),
+        b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// Returns the *base64url* character for a value.
+fn encode_char(val: u8) -> char {
+    match val {
+        0..=25 => (b'A' + val) as char,
+        26..=51 => (b'a' + val - 26) as char,
+        52..=61 => (b'0' + val - 52) as char,
+        62 => '-',
+        _ => '_',
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    // The record value from the example in section 8.4 of RFC 8555.
+    const TOKEN: &str = "LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjEuX0";
+
+    #[test]
+    fn parse() {
+        let token = Dns01Token::parse(TOKEN).unwrap();
+        assert_eq!(token.to_string(), TOKEN);
+        assert_eq!(&token.digest()[..3], b"\x2e\x8a\x97");
+        assert_eq!(Dns01Token::from_digest(*token.digest()), token);
+
+        let err = |text| Dns01Token::parse(text).unwrap_err();
+        assert_eq!(err("LoqX"), TextError::new(4, "expected 43 characters"));
+        assert_eq!(
+            err("LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjEuX0="),
+            TextError::new(43, "expected 43 characters")
+        );
+        assert_eq!(
+            err("LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjE+X0"),
+            TextError::new(40, "invalid base64url")
+        );
+        assert_eq!(
+            err("LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjEuX1"),
+            TextError::new(42, "invalid base64url")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ring")]
+    fn key_authorization() {
+        assert_eq!(
+            Dns01Token::for_key_authorization(
+                "evaGxfADs6pSRb2LAv9IZf17Dt3juxGJ-PCt92wr-oA.\
+                 nP1qzpXGymHBrUEepNY9HCsQk7K8KhOyp-aNIS9zlw"
+            )
+            .to_string(),
+            "495CUnlU9RMb2G6Q4l2t1OGlekqgvweR5NDnmLaNtmU"
+        );
+    }
+}
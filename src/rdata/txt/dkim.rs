@@ -0,0 +1,201 @@
+//! DKIM key records.
+//!
+//! DomainKeys Identified Mail allows signing mail messages. The public keys
+//! for verifying the signatures are published as TXT records at the
+//! `_domainkey` subdomain. The format of these records is defined in
+//! section 3.6.1 of [RFC 6376].
+//!
+//! [RFC 6376]: https://tools.ietf.org/html/rfc6376
+
+use super::{is_wsp, txt_string, TagList, TextError};
+use crate::rdata::rfc1035::Txt;
+use crate::utils::base64;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ DkimKey -------------------------------------------------------
+
+/// The content of a DKIM key record.
+///
+/// Tags that are not defined by RFC 6376 are ignored as required by the
+/// RFC. Tags that are left out are set to their default values.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DkimKey {
+    hash_algs: Option<Vec<String>>,
+    key_type: String,
+    notes: Option<String>,
+    public_key: Vec<u8>,
+    service_types: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl DkimKey {
+    /// Parses a DKIM key record from a string.
+    pub fn parse(text: &str) -> Result<Self, TextError> {
+        Self::from_tags(&TagList::parse(text)?)
+    }
+
+    /// Parses a DKIM key record from TXT record data.
+    pub fn from_txt<Octets: AsRef<[u8]>>(
+        txt: &Txt<Octets>,
+    ) -> Result<Self, TextError> {
+        Self::parse(&txt_string(txt)?)
+    }
+
+    /// Creates a DKIM key record from a tag list.
+    pub fn from_tags(tags: &TagList) -> Result<Self, TextError> {
+        // The version tag is optional but has to be first if present.
+        for (idx, tag) in tags.iter().enumerate() {
+            if tag.name() == "v" {
+                if idx != 0 {
+                    return Err(tag.error("version tag must be first"));
+                }
+                if tag.value() != "DKIM1" {
+                    return Err(tag.error("expected 'DKIM1'"));
+                }
+            }
+        }
+        let list = |name| match tags.get(name) {
+            Some(tag) => tag
+                .list(':')
+                .map(|(item, pos)| {
+                    if item.is_empty() {
+                        Err(TextError::new(pos, "expected list item"))
+                    } else {
+                        Ok(String::from(item))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some),
+            None => Ok(None),
+        };
+        let public_key = match tags.get("p") {
+            Some(tag) => {
+                let mut decoder = base64::Decoder::new();
+                for (idx, ch) in tag.value().char_indices() {
+                    if is_wsp(ch) {
+                        continue;
+                    }
+                    decoder.push(ch).map_err(|_| {
+                        TextError::new(tag.pos() + idx, "invalid base64")
+                    })?;
+                }
+                decoder
+                    .finalize()
+                    .map_err(|_| tag.error("invalid base64"))?
+                    .to_vec()
+            }
+            None => return Err(TextError::new(0, "missing public key")),
+        };
+        Ok(DkimKey {
+            hash_algs: list("h")?,
+            key_type: tags.get("k").map_or("rsa", |tag| tag.value()).into(),
+            notes: tags.get("n").map(|tag| tag.value().into()),
+            public_key,
+            service_types: list("s")?.unwrap_or_else(|| vec!["*".into()]),
+            flags: list("t")?.unwrap_or_default(),
+        })
+    }
+
+    /// Returns the acceptable hash algorithms.
+    ///
+    /// Returns `None` if all algorithms are acceptable.
+    pub fn hash_algs(&self) -> Option<&[String]> {
+        self.hash_algs.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns the key type.
+    pub fn key_type(&self) -> &str {
+        &self.key_type
+    }
+
+    /// Returns the notes for humans if present.
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns the public key.
+    ///
+    /// The key is empty if it has been revoked.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns whether the key has been revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.public_key.is_empty()
+    }
+
+    /// Returns the service types the key applies to.
+    pub fn service_types(&self) -> &[String] {
+        &self.service_types
+    }
+
+    /// Returns the flags of the key.
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// Returns whether the domain is testing DKIM.
+    pub fn is_testing(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "y")
+    }
+
+    /// Returns whether signing domain and identity must match exactly.
+    pub fn is_strict(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "s")
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::octets::{Parse, Parser};
+
+    #[test]
+    fn parse() {
+        let key = DkimKey::parse(
+            "v=DKIM1; h=sha256; k=ed25519; t=y:s; \
+             p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=",
+        )
+        .unwrap();
+        assert_eq!(key.hash_algs(), Some(&["sha256".into()][..]));
+        assert_eq!(key.key_type(), "ed25519");
+        assert_eq!(key.public_key().len(), 32);
+        assert_eq!(key.service_types(), &["*"]);
+        assert!(key.is_testing());
+        assert!(key.is_strict());
+        assert!(!key.is_revoked());
+
+        // Keys are often split over several character strings and may
+        // contain white space.
+        let wire = b"\x1dk=ed25519; p=11qYAYKxCrfVS/7T\
+                     \x1dyWQHOg7h cvPapiMlrwIaaPcHURo=";
+        let txt = Txt::parse(&mut Parser::from_ref(&wire[..])).unwrap();
+        let split = DkimKey::from_txt(&txt).unwrap();
+        assert_eq!(split.public_key(), key.public_key());
+
+        let key = DkimKey::parse("p=").unwrap();
+        assert!(key.is_revoked());
+        assert_eq!(key.hash_algs(), None);
+        assert_eq!(key.key_type(), "rsa");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |text| DkimKey::parse(text).unwrap_err();
+        assert_eq!(err("k=rsa"), TextError::new(0, "missing public key"));
+        assert_eq!(
+            err("p=; v=DKIM1"),
+            TextError::new(6, "version tag must be first")
+        );
+        assert_eq!(err("v=DKIM2; p="), TextError::new(2, "expected 'DKIM1'"));
+        assert_eq!(err("p=ab*d"), TextError::new(4, "invalid base64"));
+        assert_eq!(
+            err("h=sha1::sha256; p="),
+            TextError::new(7, "expected list item")
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! DMARC policy records.
+//!
+//! Domain-based Message Authentication, Reporting, and Conformance allows
+//! domain owners to publish a policy for handling mail that fails SPF and
+//! DKIM checks. The policy is published as a TXT record at the `_dmarc`
+//! subdomain and is defined in [RFC 7489].
+//!
+//! [RFC 7489]: https://tools.ietf.org/html/rfc7489
+
+use super::{txt_string, TagList, TextError};
+use crate::rdata::rfc1035::Txt;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Dmarc ---------------------------------------------------------
+
+/// The content of a DMARC record.
+///
+/// Tags that are not defined by RFC 7489 are ignored as required by the
+/// RFC. Tags that are left out are set to their default values.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dmarc {
+    policy: Policy,
+    subdomain_policy: Option<Policy>,
+    dkim_alignment: Alignment,
+    spf_alignment: Alignment,
+    percent: u8,
+    failure_options: Vec<FailureOption>,
+    report_formats: Vec<String>,
+    report_interval: u32,
+    aggregate_uris: Vec<ReportUri>,
+    failure_uris: Vec<ReportUri>,
+}
+
+impl Dmarc {
+    /// Parses a DMARC record from a string.
+    ///
+    /// The string has to start with the version tag `v=DMARC1` and has to
+    /// contain a policy.
+    pub fn parse(text: &str) -> Result<Self, TextError> {
+        Self::from_tags(&TagList::parse(text)?)
+    }
+
+    /// Parses a DMARC record from TXT record data.
+    pub fn from_txt<Octets: AsRef<[u8]>>(
+        txt: &Txt<Octets>,
+    ) -> Result<Self, TextError> {
+        Self::parse(&txt_string(txt)?)
+    }
+
+    /// Creates a DMARC record from a tag list.
+    pub fn from_tags(tags: &TagList) -> Result<Self, TextError> {
+        match tags.first() {
+            Some(tag) if tag.name() == "v" => {
+                if tag.value() != "DMARC1" {
+                    return Err(tag.error("expected 'DMARC1'"));
+                }
+            }
+            _ => return Err(TextError::new(0, "expected 'v=DMARC1'")),
+        }
+        let policy = match tags.get("p") {
+            Some(tag) => Policy::from_tag(tag.value())
+                .ok_or_else(|| tag.error("invalid policy"))?,
+            None => return Err(TextError::new(0, "missing policy")),
+        };
+        let subdomain_policy = match tags.get("sp") {
+            Some(tag) => Some(
+                Policy::from_tag(tag.value())
+                    .ok_or_else(|| tag.error("invalid policy"))?,
+            ),
+            None => None,
+        };
+        let alignment = |name| match tags.get(name) {
+            Some(tag) => Alignment::from_tag(tag.value())
+                .ok_or_else(|| tag.error("invalid alignment mode")),
+            None => Ok(Alignment::Relaxed),
+        };
+        let percent = match tags.get("pct") {
+            Some(tag) => match tag.value().parse() {
+                Ok(percent) if percent <= 100 => percent,
+                _ => return Err(tag.error("invalid percentage")),
+            },
+            None => 100,
+        };
+        let failure_options = match tags.get("fo") {
+            Some(tag) => tag
+                .list(':')
+                .map(|(item, pos)| {
+                    FailureOption::from_tag(item).ok_or_else(|| {
+                        TextError::new(pos, "invalid failure option")
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            None => vec![FailureOption::All],
+        };
+        let report_formats = match tags.get("rf") {
+            Some(tag) => tag
+                .list(':')
+                .map(|(item, pos)| {
+                    if item.is_empty() {
+                        Err(TextError::new(pos, "expected report format"))
+                    } else {
+                        Ok(item.into())
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+            None => vec!["afrf".into()],
+        };
+        let report_interval = match tags.get("ri") {
+            Some(tag) => tag
+                .value()
+                .parse()
+                .map_err(|_| tag.error("invalid report interval"))?,
+            None => 86400,
+        };
+        let uris = |name| match tags.get(name) {
+            Some(tag) => tag
+                .list(',')
+                .map(|(item, pos)| ReportUri::parse(item, pos))
+                .collect(),
+            None => Ok(Vec::new()),
+        };
+        Ok(Dmarc {
+            policy,
+            subdomain_policy,
+            dkim_alignment: alignment("adkim")?,
+            spf_alignment: alignment("aspf")?,
+            percent,
+            failure_options,
+            report_formats,
+            report_interval,
+            aggregate_uris: uris("rua")?,
+            failure_uris: uris("ruf")?,
+        })
+    }
+
+    /// Returns the policy for the domain.
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Returns the policy for subdomains.
+    ///
+    /// If the record doesn’t contain a separate policy for subdomains, the
+    /// policy for the domain is returned.
+    pub fn subdomain_policy(&self) -> Policy {
+        self.subdomain_policy.unwrap_or(self.policy)
+    }
+
+    /// Returns the alignment mode for DKIM.
+    pub fn dkim_alignment(&self) -> Alignment {
+        self.dkim_alignment
+    }
+
+    /// Returns the alignment mode for SPF.
+    pub fn spf_alignment(&self) -> Alignment {
+        self.spf_alignment
+    }
+
+    /// Returns the percentage of messages the policy applies to.
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// Returns the options for generating failure reports.
+    pub fn failure_options(&self) -> &[FailureOption] {
+        &self.failure_options
+    }
+
+    /// Returns the requested formats for failure reports.
+    pub fn report_formats(&self) -> &[String] {
+        &self.report_formats
+    }
+
+    /// Returns the requested interval between aggregate reports in seconds.
+    pub fn report_interval(&self) -> u32 {
+        self.report_interval
+    }
+
+    /// Returns the addresses for sending aggregate reports to.
+    pub fn aggregate_uris(&self) -> &[ReportUri] {
+        &self.aggregate_uris
+    }
+
+    /// Returns the addresses for sending failure reports to.
+    pub fn failure_uris(&self) -> &[ReportUri] {
+        &self.failure_uris
+    }
+}
+
+//------------ Policy --------------------------------------------------------
+
+/// The requested handling of messages failing the DMARC check.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Policy {
+    /// No specific action is requested.
+    None,
+
+    /// The messages should be treated as suspicious.
+    Quarantine,
+
+    /// The messages should be rejected.
+    Reject,
+}
+
+impl Policy {
+    /// Returns the policy for the value of a `p` or `sp` tag.
+    pub fn from_tag(value: &str) -> Option<Self> {
+        if value.eq_ignore_ascii_case("none") {
+            Some(Policy::None)
+        } else if value.eq_ignore_ascii_case("quarantine") {
+            Some(Policy::Quarantine)
+        } else if value.eq_ignore_ascii_case("reject") {
+            Some(Policy::Reject)
+        } else {
+            None
+        }
+    }
+}
+
+//------------ Alignment -----------------------------------------------------
+
+/// The identifier alignment mode.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Alignment {
+    /// The organizational domains have to match.
+    Relaxed,
+
+    /// The domains have to match exactly.
+    Strict,
+}
+
+impl Alignment {
+    /// Returns the mode for the value of an `adkim` or `aspf` tag.
+    pub fn from_tag(value: &str) -> Option<Self> {
+        match value {
+            "r" | "R" => Some(Alignment::Relaxed),
+            "s" | "S" => Some(Alignment::Strict),
+            _ => None,
+        }
+    }
+}
+
+//------------ FailureOption -------------------------------------------------
+
+/// An option for generating failure reports.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FailureOption {
+    /// Report if all underlying mechanisms failed, given as `0`.
+    All,
+
+    /// Report if any underlying mechanism failed, given as `1`.
+    Any,
+
+    /// Report if the DKIM signature failed, given as `d`.
+    Dkim,
+
+    /// Report if the SPF check failed, given as `s`.
+    Spf,
+}
+
+impl FailureOption {
+    /// Returns the option for an item of the value of an `fo` tag.
+    pub fn from_tag(value: &str) -> Option<Self> {
+        match value {
+            "0" => Some(FailureOption::All),
+            "1" => Some(FailureOption::Any),
+            "d" | "D" => Some(FailureOption::Dkim),
+            "s" | "S" => Some(FailureOption::Spf),
+            _ => None,
+        }
+    }
+}
+
+//------------ ReportUri -----------------------------------------------------
+
+/// An address for sending reports to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReportUri {
+    uri: String,
+    max_size: Option<u64>,
+}
+
+impl ReportUri {
+    /// Returns the URI of the address.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the maximum size of a report in octets if given.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Parses an item of the value of an `rua` or `ruf` tag.
+    fn parse(item: &str, pos: usize) -> Result<Self, TextError> {
+        let (uri, size) = match item.rfind('!') {
+            Some(idx) => (&item[..idx], Some((&item[idx + 1..], idx + 1))),
+            None => (item, None),
+        };
+        match uri.find(':') {
+            Some(idx) if idx > 0 => {}
+            _ => return Err(TextError::new(pos, "expected URI")),
+        }
+        let max_size = match size {
+            Some((size, idx)) => {
+                let err = || TextError::new(pos + idx, "invalid size");
+                let (num, unit) =
+                    match size.find(|ch: char| !ch.is_ascii_digit()) {
+                        Some(idx) => size.split_at(idx),
+                        None => (size, ""),
+                    };
+                let shift = match unit {
+                    "" | "b" | "B" => 0,
+                    "k" | "K" => 10,
+                    "m" | "M" => 20,
+                    "g" | "G" => 30,
+                    "t" | "T" => 40,
+                    _ => return Err(err()),
+                };
+                let num: u64 = num.parse().map_err(|_| err())?;
+                if num.leading_zeros() < shift {
+                    return Err(err());
+                }
+                Some(num << shift)
+            }
+            None => None,
+        };
+        Ok(ReportUri {
+            uri: uri.into(),
+            max_size,
+        })
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let dmarc = Dmarc::parse(
+            "v=DMARC1; p=quarantine; sp=reject; adkim=s; pct=50; \
+             fo=1:d; rua=mailto:agg@example.com, \
+             mailto:agg@example.net!10m; ruf=mailto:fail@example.com; \
+             future=ignored",
+        )
+        .unwrap();
+        assert_eq!(dmarc.policy(), Policy::Quarantine);
+        assert_eq!(dmarc.subdomain_policy(), Policy::Reject);
+        assert_eq!(dmarc.dkim_alignment(), Alignment::Strict);
+        assert_eq!(dmarc.spf_alignment(), Alignment::Relaxed);
+        assert_eq!(dmarc.percent(), 50);
+        assert_eq!(
+            dmarc.failure_options(),
+            &[FailureOption::Any, FailureOption::Dkim]
+        );
+        assert_eq!(dmarc.report_formats(), &["afrf"]);
+        assert_eq!(dmarc.report_interval(), 86400);
+        let rua: Vec<_> = dmarc
+            .aggregate_uris()
+            .iter()
+            .map(|uri| (uri.uri(), uri.max_size()))
+            .collect();
+        assert_eq!(
+            rua,
+            [
+                ("mailto:agg@example.com", None),
+                ("mailto:agg@example.net", Some(10 << 20))
+            ]
+        );
+        assert_eq!(dmarc.failure_uris()[0].uri(), "mailto:fail@example.com");
+
+        let dmarc = Dmarc::parse("v=DMARC1;p=none").unwrap();
+        assert_eq!(dmarc.subdomain_policy(), Policy::None);
+        assert_eq!(dmarc.failure_options(), &[FailureOption::All]);
+        assert!(dmarc.aggregate_uris().is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let err = |text| Dmarc::parse(text).unwrap_err();
+        assert_eq!(err("p=none"), TextError::new(0, "expected 'v=DMARC1'"));
+        assert_eq!(
+            err("v=DMARC2; p=none"),
+            TextError::new(2, "expected 'DMARC1'")
+        );
+        assert_eq!(err("v=DMARC1"), TextError::new(0, "missing policy"));
+        assert_eq!(
+            err("v=DMARC1; p=drop"),
+            TextError::new(12, "invalid policy")
+        );
+        assert_eq!(
+            err("v=DMARC1; p=none; pct=101"),
+            TextError::new(22, "invalid percentage")
+        );
+        assert_eq!(
+            err("v=DMARC1; p=none; fo=0:x"),
+            TextError::new(23, "invalid failure option")
+        );
+        assert_eq!(
+            err("v=DMARC1; p=none; rua=mailto:a@b, nope"),
+            TextError::new(34, "expected URI")
+        );
+        assert_eq!(
+            err("v=DMARC1; p=none; rua=mailto:a@b!10x"),
+            TextError::new(33, "invalid size")
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Structured content of TXT records.
+//!
+//! A number of protocols publish their data in TXT records using a string
+//! grammar of their own. This module contains parsers for the more common
+//! ones that turn the text into typed values:
+//!
+//! * [spf]: Sender Policy Framework records as defined in RFC 7208,
+//! * [dkim]: DKIM key records as defined in RFC 6376,
+//! * [dmarc]: DMARC policy records as defined in RFC 7489, and
+//! * [acme]: validation tokens for the ACME dns-01 challenge as defined in
+//!   RFC 8555.
+//!
+//! Each type can be parsed either from a string via `parse` or directly from
+//! [`Txt`] record data via `from_txt`. In the latter case, the character
+//! strings of the record are joined without any separator as required by
+//! all these protocols.
+//!
+//! If parsing fails, a [`TextError`] is returned. It describes the problem
+//! and contains the position in the text where it was encountered as the
+//! octet offset from the start of the (joined) text.
+//!
+//! DKIM and DMARC both use lists of `tag=value` pairs. The [`TagList`] type
+//! provides access to such lists for other protocols using the same syntax.
+//!
+//! The module requires the `std` feature. The [dkim] module additionally
+//! requires the `bytes` feature.
+//!
+//! [spf]: spf/index.html
+//! [dkim]: dkim/index.html
+//! [dmarc]: dmarc/index.html
+//! [acme]: acme/index.html
+//! [`Txt`]: ../rfc1035/struct.Txt.html
+//! [`TextError`]: struct.TextError.html
+//! [`TagList`]: struct.TagList.html
+#![cfg(feature = "std")]
+
+pub mod acme;
+#[cfg(feature = "bytes")]
+pub mod dkim;
+pub mod dmarc;
+pub mod spf;
+
+use super::rfc1035::Txt;
+use core::fmt;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ TagList -------------------------------------------------------
+
+/// A list of tags and their values.
+///
+/// The list uses the syntax defined in section 3.2 of RFC 6376: a sequence
+/// of `tag=value` pairs separated by semicolons. White space is allowed
+/// around tags and values as well as within values. A trailing semicolon is
+/// allowed, each tag may only appear once.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TagList {
+    tags: Vec<Tag>,
+}
+
+impl TagList {
+    /// Parses a tag list from a string.
+    pub fn parse(text: &str) -> Result<Self, TextError> {
+        let mut tags = Vec::<Tag>::new();
+        let mut pos = 0;
+        for spec in text.split(';') {
+            let start = pos;
+            pos += spec.len() + 1;
+            if spec.trim_matches(is_wsp).is_empty() {
+                // Only the last spec may be empty so that there can be a
+                // trailing semicolon.
+                if pos > text.len() && !tags.is_empty() {
+                    break;
+                }
+                return Err(TextError::new(start, "expected tag"));
+            }
+            let eq = match spec.find('=') {
+                Some(eq) => eq,
+                None => return Err(TextError::new(start, "expected '='")),
+            };
+            let name = spec[..eq].trim_start_matches(is_wsp);
+            let name_pos = start + eq - name.len();
+            let name = name.trim_end_matches(is_wsp);
+            check_tag_name(name, name_pos)?;
+            let value = spec[eq + 1..].trim_start_matches(is_wsp);
+            let value_pos = start + spec.len() - value.len();
+            let value = value.trim_end_matches(is_wsp);
+            if let Some(idx) = value.find(|ch| !is_tag_value_char(ch)) {
+                return Err(TextError::new(
+                    value_pos + idx,
+                    "invalid character in tag value",
+                ));
+            }
+            if tags.iter().any(|tag| tag.name == name) {
+                return Err(TextError::new(name_pos, "duplicate tag"));
+            }
+            tags.push(Tag {
+                name: name.into(),
+                value: value.into(),
+                pos: value_pos,
+            });
+        }
+        Ok(TagList { tags })
+    }
+
+    /// Parses a tag list from TXT record data.
+    pub fn from_txt<Octets: AsRef<[u8]>>(
+        txt: &Txt<Octets>,
+    ) -> Result<Self, TextError> {
+        Self::parse(&txt_string(txt)?)
+    }
+
+    /// Returns the tag with the given name if present.
+    ///
+    /// Tag names are case sensitive.
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        self.tags.iter().find(|tag| tag.name == name)
+    }
+
+    /// Returns the first tag of the list.
+    pub fn first(&self) -> Option<&Tag> {
+        self.tags.first()
+    }
+
+    /// Returns an iterator over the tags in the order they appear.
+    pub fn iter(&self) -> core::slice::Iter<'_, Tag> {
+        self.tags.iter()
+    }
+}
+
+//--- IntoIterator
+
+impl<'a> IntoIterator for &'a TagList {
+    type Item = &'a Tag;
+    type IntoIter = core::slice::Iter<'a, Tag>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+//------------ Tag -----------------------------------------------------------
+
+/// A single tag of a tag list.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tag {
+    /// The name of the tag.
+    name: String,
+
+    /// The value of the tag without surrounding white space.
+    value: String,
+
+    /// The position of the value in the text.
+    pos: usize,
+}
+
+impl Tag {
+    /// Returns the name of the tag.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value of the tag.
+    ///
+    /// White space surrounding the value is removed but white space within
+    /// the value is kept.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the position of the value in the text.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns an error at the position of the value.
+    pub fn error(&self, msg: &'static str) -> TextError {
+        TextError::new(self.pos, msg)
+    }
+
+    /// Returns an iterator over the items of a colon-separated value.
+    ///
+    /// The items are returned with surrounding white space removed together
+    /// with their position in the text.
+    pub fn list(&self, sep: char) -> impl Iterator<Item = (&str, usize)> {
+        let mut pos = self.pos;
+        self.value.split(sep).map(move |item| {
+            let start = pos;
+            pos += item.len() + sep.len_utf8();
+            let trimmed = item.trim_start_matches(is_wsp);
+            (
+                trimmed.trim_end_matches(is_wsp),
+                start + item.len() - trimmed.len(),
+            )
+        })
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the joined character strings of a TXT record as a string.
+fn txt_string<Octets: AsRef<[u8]>>(
+    txt: &Txt<Octets>,
+) -> Result<String, TextError> {
+    let mut res = Vec::new();
+    for item in txt.iter() {
+        res.extend_from_slice(item);
+    }
+    String::from_utf8(res).map_err(|err| {
+        TextError::new(err.utf8_error().valid_up_to(), "invalid UTF-8")
+    })
+}
+
+/// Checks that a tag name is `ALPHA *ALNUMPUNC`.
+fn check_tag_name(name: &str, pos: usize) -> Result<(), TextError> {
+    let mut chars = name.char_indices();
+    match chars.next() {
+        Some((_, ch)) if ch.is_ascii_alphabetic() => {}
+        _ => return Err(TextError::new(pos, "expected tag name")),
+    }
+    for (idx, ch) in chars {
+        if !ch.is_ascii_alphanumeric() && ch != '_' {
+            return Err(TextError::new(
+                pos + idx,
+                "invalid character in tag name",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether a character is white space within tag lists.
+fn is_wsp(ch: char) -> bool {
+    ch == ' ' || ch == '\t' || ch == '\r' || ch == '\n'
+}
+
+/// Returns whether a character is allowed in a tag value.
+fn is_tag_value_char(ch: char) -> bool {
+    is_wsp(ch) || (('\x21'..='\x7e').contains(&ch) && ch != ';')
+}
+
+//============ Error Types ===================================================
+
+//------------ TextError -----------------------------------------------------
+
+/// The text of a record does not follow the expected grammar.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TextError {
+    /// The octet offset of the problem in the text.
+    pos: usize,
+
+    /// A description of the problem.
+    msg: &'static str,
+}
+
+impl TextError {
+    /// Creates a new error at the given position.
+    pub(crate) fn new(pos: usize, msg: &'static str) -> Self {
+        TextError { pos, msg }
+    }
+
+    /// Returns the position of the problem as an offset into the text.
+    pub fn pos(self) -> usize {
+        self.pos
+    }
+
+    /// Returns a description of the problem.
+    pub fn msg(self) -> &'static str {
+        self.msg
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.msg, self.pos)
+    }
+}
+
+impl std::error::Error for TextError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn tag_list() {
+        let list = TagList::parse(" v=1; a = b c ;\tx_1=;").unwrap();
+        let tags: Vec<_> = list
+            .iter()
+            .map(|tag| (tag.name(), tag.value(), tag.pos()))
+            .collect();
+        assert_eq!(tags, [("v", "1", 3), ("a", "b c", 10), ("x_1", "", 20)]);
+        assert_eq!(list.get("a").unwrap().value(), "b c");
+        assert!(list.get("A").is_none());
+
+        let tag = TagList::parse("h = a : b:c").unwrap();
+        let items: Vec<_> = tag.first().unwrap().list(':').collect();
+        assert_eq!(items, [("a", 4), ("b", 8), ("c", 10)]);
+
+        let err = |text| TagList::parse(text).unwrap_err();
+        assert_eq!(err(""), TextError::new(0, "expected tag"));
+        assert_eq!(err("a=1;;b=2"), TextError::new(4, "expected tag"));
+        assert_eq!(err("a=1; b"), TextError::new(4, "expected '='"));
+        assert_eq!(err("1=1"), TextError::new(0, "expected tag name"));
+        assert_eq!(
+            err("a-b=1"),
+            TextError::new(1, "invalid character in tag name")
+        );
+        assert_eq!(err("a=1; a=2"), TextError::new(5, "duplicate tag"));
+        assert_eq!(
+            err("a=ü"),
+            TextError::new(2, "invalid character in tag value")
+        );
+    }
+}
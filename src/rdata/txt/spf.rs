@@ -0,0 +1,742 @@
+//! Sender Policy Framework records.
+//!
+//! SPF records are TXT records that describe which hosts are authorized to
+//! send mail for a domain. They are defined in [RFC 7208].
+//!
+//! The [`Spf`] type parses the text of such a record into its terms. The
+//! domain specifications of the terms are checked to be valid macro strings
+//! but are kept unexpanded. Evaluating a record is outside the scope of
+//! this module.
+//!
+//! [RFC 7208]: https://tools.ietf.org/html/rfc7208
+//! [`Spf`]: struct.Spf.html
+
+use super::{txt_string, TextError};
+use crate::rdata::rfc1035::Txt;
+use core::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Spf -----------------------------------------------------------
+
+/// The content of an SPF record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Spf {
+    /// The terms of the record in the order they appear.
+    terms: Vec<Term>,
+}
+
+impl Spf {
+    /// Parses an SPF record from a string.
+    ///
+    /// The string has to start with the version tag `v=spf1`.
+    pub fn parse(text: &str) -> Result<Self, TextError> {
+        const VERSION: &str = "v=spf1";
+
+        let version = text.get(..VERSION.len());
+        if !matches!(version, Some(v) if v.eq_ignore_ascii_case(VERSION)) {
+            return Err(TextError::new(0, "expected 'v=spf1'"));
+        }
+        let rest = &text[VERSION.len()..];
+        if !rest.is_empty() && !rest.starts_with(' ') {
+            return Err(TextError::new(VERSION.len(), "expected space"));
+        }
+
+        let mut terms = Vec::new();
+        let mut pos = VERSION.len();
+        for term in rest.split(' ') {
+            let start = pos;
+            pos += term.len() + 1;
+            if term.is_empty() {
+                continue;
+            }
+            let term = Term::parse(term, start)?;
+            if let Term::Modifier(ref modifier) = term {
+                if modifier.is_unique()
+                    && terms.iter().any(|item| match *item {
+                        Term::Modifier(ref item) => {
+                            item.name.eq_ignore_ascii_case(&modifier.name)
+                        }
+                        _ => false,
+                    })
+                {
+                    return Err(TextError::new(start, "duplicate modifier"));
+                }
+            }
+            terms.push(term);
+        }
+        Ok(Spf { terms })
+    }
+
+    /// Parses an SPF record from TXT record data.
+    pub fn from_txt<Octets: AsRef<[u8]>>(
+        txt: &Txt<Octets>,
+    ) -> Result<Self, TextError> {
+        Self::parse(&txt_string(txt)?)
+    }
+
+    /// Returns whether the text is an SPF record.
+    ///
+    /// This only checks for the version tag. It is used to select the SPF
+    /// record among the TXT records of a domain.
+    pub fn is_spf(text: &[u8]) -> bool {
+        let version = b"v=spf1";
+        text.len() >= version.len()
+            && text[..version.len()].eq_ignore_ascii_case(version)
+            && (text.len() == version.len() || text[version.len()] == b' ')
+    }
+
+    /// Returns the terms of the record.
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    /// Returns an iterator over the directives of the record.
+    pub fn directives(&self) -> impl Iterator<Item = &Directive> {
+        self.terms.iter().filter_map(|term| match *term {
+            Term::Directive(ref directive) => Some(directive),
+            Term::Modifier(_) => None,
+        })
+    }
+
+    /// Returns the value of the modifier with the given name if present.
+    ///
+    /// The name is compared ignoring ASCII case.
+    pub fn modifier(&self, name: &str) -> Option<&str> {
+        self.terms.iter().find_map(|term| match *term {
+            Term::Modifier(ref modifier)
+                if modifier.name.eq_ignore_ascii_case(name) =>
+            {
+                Some(modifier.value.as_str())
+            }
+            _ => None,
+        })
+    }
+
+    /// Returns the domain given by the `redirect` modifier if present.
+    pub fn redirect(&self) -> Option<&str> {
+        self.modifier("redirect")
+    }
+
+    /// Returns the domain given by the `exp` modifier if present.
+    pub fn explanation(&self) -> Option<&str> {
+        self.modifier("exp")
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Spf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("v=spf1")?;
+        for term in &self.terms {
+            write!(f, " {}", term)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Term ----------------------------------------------------------
+
+/// A term of an SPF record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Term {
+    /// A directive matching the sender against a mechanism.
+    Directive(Directive),
+
+    /// A modifier providing additional information.
+    Modifier(Modifier),
+}
+
+impl Term {
+    /// Parses a term starting at the given position.
+    fn parse(term: &str, pos: usize) -> Result<Self, TextError> {
+        // A modifier starts with a name followed by an equals sign. The
+        // name can’t contain a colon or slash, so a directive with an
+        // equals sign in its domain spec is not mistaken for one.
+        if let Some(eq) = term.find('=') {
+            let name = &term[..eq];
+            let mut chars = name.chars();
+            if matches!(chars.next(), Some(ch) if ch.is_ascii_alphabetic())
+                && chars.all(|ch| {
+                    ch.is_ascii_alphanumeric()
+                        || ch == '-'
+                        || ch == '_'
+                        || ch == '.'
+                })
+            {
+                check_macro_string(&term[eq + 1..], pos + eq + 1)?;
+                return Ok(Term::Modifier(Modifier {
+                    name: name.into(),
+                    value: term[eq + 1..].into(),
+                }));
+            }
+        }
+        Directive::parse(term, pos).map(Term::Directive)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Term::Directive(ref directive) => fmt::Display::fmt(directive, f),
+            Term::Modifier(ref modifier) => fmt::Display::fmt(modifier, f),
+        }
+    }
+}
+
+//------------ Directive -----------------------------------------------------
+
+/// A directive of an SPF record.
+///
+/// A directive consists of a mechanism that is matched against the sender
+/// and a qualifier that provides the result if the mechanism matches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Directive {
+    qualifier: Qualifier,
+    mechanism: Mechanism,
+}
+
+impl Directive {
+    /// Creates a new directive from its components.
+    pub fn new(qualifier: Qualifier, mechanism: Mechanism) -> Self {
+        Directive {
+            qualifier,
+            mechanism,
+        }
+    }
+
+    /// Returns the qualifier of the directive.
+    pub fn qualifier(&self) -> Qualifier {
+        self.qualifier
+    }
+
+    /// Returns the mechanism of the directive.
+    pub fn mechanism(&self) -> &Mechanism {
+        &self.mechanism
+    }
+
+    /// Parses a directive starting at the given position.
+    fn parse(term: &str, pos: usize) -> Result<Self, TextError> {
+        let (qualifier, term, pos) =
+            match Qualifier::from_char(term.chars().next().unwrap_or(' ')) {
+                Some(qualifier) => (qualifier, &term[1..], pos + 1),
+                None => (Qualifier::Pass, term, pos),
+            };
+        Mechanism::parse(term, pos).map(|mechanism| Directive {
+            qualifier,
+            mechanism,
+        })
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.qualifier != Qualifier::Pass {
+            write!(f, "{}", self.qualifier.to_char())?;
+        }
+        fmt::Display::fmt(&self.mechanism, f)
+    }
+}
+
+//------------ Qualifier -----------------------------------------------------
+
+/// The qualifier of a directive.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Qualifier {
+    /// The sender is authorized, written as `+` or left out.
+    Pass,
+
+    /// The sender is not authorized, written as `-`.
+    Fail,
+
+    /// The sender is probably not authorized, written as `~`.
+    SoftFail,
+
+    /// No statement is made about the sender, written as `?`.
+    Neutral,
+}
+
+impl Qualifier {
+    /// Returns the qualifier for its character.
+    pub fn from_char(ch: char) -> Option<Self> {
+        match ch {
+            '+' => Some(Qualifier::Pass),
+            '-' => Some(Qualifier::Fail),
+            '~' => Some(Qualifier::SoftFail),
+            '?' => Some(Qualifier::Neutral),
+            _ => None,
+        }
+    }
+
+    /// Returns the character for the qualifier.
+    pub fn to_char(self) -> char {
+        match self {
+            Qualifier::Pass => '+',
+            Qualifier::Fail => '-',
+            Qualifier::SoftFail => '~',
+            Qualifier::Neutral => '?',
+        }
+    }
+}
+
+//------------ Mechanism -----------------------------------------------------
+
+/// The mechanism of a directive.
+///
+/// Domain specifications are kept as given in the record, i.e., with any
+/// macros unexpanded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Mechanism {
+    /// Always matches.
+    All,
+
+    /// Matches if the SPF record of the given domain matches.
+    Include(String),
+
+    /// Matches if the sender is one of the addresses of a domain.
+    A {
+        /// The domain if it differs from the current domain.
+        domain: Option<String>,
+
+        /// The prefix length for IPv4 addresses if given.
+        ip4_prefix: Option<u8>,
+
+        /// The prefix length for IPv6 addresses if given.
+        ip6_prefix: Option<u8>,
+    },
+
+    /// Matches if the sender is one of the mail exchangers of a domain.
+    Mx {
+        /// The domain if it differs from the current domain.
+        domain: Option<String>,
+
+        /// The prefix length for IPv4 addresses if given.
+        ip4_prefix: Option<u8>,
+
+        /// The prefix length for IPv6 addresses if given.
+        ip6_prefix: Option<u8>,
+    },
+
+    /// Matches if the sender’s validated host name is within a domain.
+    Ptr(Option<String>),
+
+    /// Matches if the sender is within an IPv4 network.
+    Ip4(Ipv4Addr, Option<u8>),
+
+    /// Matches if the sender is within an IPv6 network.
+    Ip6(Ipv6Addr, Option<u8>),
+
+    /// Matches if the given domain has an A record.
+    Exists(String),
+}
+
+impl Mechanism {
+    /// Parses a mechanism starting at the given position.
+    fn parse(term: &str, pos: usize) -> Result<Self, TextError> {
+        let name_end = term.find(&[':', '/'][..]);
+        let name = &term[..name_end.unwrap_or(term.len())];
+        let arg = name_end.map(|end| (&term[end..], pos + end));
+        let lower = name.to_ascii_lowercase();
+        match lower.as_str() {
+            "all" => {
+                if let Some((_, pos)) = arg {
+                    return Err(TextError::new(pos, "unexpected argument"));
+                }
+                Ok(Mechanism::All)
+            }
+            "include" => Ok(Mechanism::Include(required_domain(arg, pos)?)),
+            "exists" => Ok(Mechanism::Exists(required_domain(arg, pos)?)),
+            "ptr" => {
+                let domain = match arg {
+                    Some((arg, pos)) => Some(domain_arg(arg, pos)?),
+                    None => None,
+                };
+                Ok(Mechanism::Ptr(domain))
+            }
+            "a" | "mx" => {
+                let (domain, ip4_prefix, ip6_prefix) = match arg {
+                    Some((arg, pos)) => dual_cidr_arg(arg, pos)?,
+                    None => (None, None, None),
+                };
+                if lower == "a" {
+                    Ok(Mechanism::A {
+                        domain,
+                        ip4_prefix,
+                        ip6_prefix,
+                    })
+                } else {
+                    Ok(Mechanism::Mx {
+                        domain,
+                        ip4_prefix,
+                        ip6_prefix,
+                    })
+                }
+            }
+            "ip4" => {
+                let (addr, prefix, pos) = network_arg(arg, pos, 32)?;
+                addr.parse()
+                    .map(|addr| Mechanism::Ip4(addr, prefix))
+                    .map_err(|_| TextError::new(pos, "invalid IPv4 address"))
+            }
+            "ip6" => {
+                let (addr, prefix, pos) = network_arg(arg, pos, 128)?;
+                addr.parse()
+                    .map(|addr| Mechanism::Ip6(addr, prefix))
+                    .map_err(|_| TextError::new(pos, "invalid IPv6 address"))
+            }
+            _ => Err(TextError::new(pos, "unknown mechanism")),
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn dual_cidr(
+            f: &mut fmt::Formatter,
+            name: &str,
+            domain: &Option<String>,
+            ip4_prefix: Option<u8>,
+            ip6_prefix: Option<u8>,
+        ) -> fmt::Result {
+            f.write_str(name)?;
+            if let Some(ref domain) = *domain {
+                write!(f, ":{}", domain)?;
+            }
+            if let Some(prefix) = ip4_prefix {
+                write!(f, "/{}", prefix)?;
+            }
+            if let Some(prefix) = ip6_prefix {
+                write!(f, "//{}", prefix)?;
+            }
+            Ok(())
+        }
+
+        match *self {
+            Mechanism::All => f.write_str("all"),
+            Mechanism::Include(ref domain) => write!(f, "include:{}", domain),
+            Mechanism::A {
+                ref domain,
+                ip4_prefix,
+                ip6_prefix,
+            } => dual_cidr(f, "a", domain, ip4_prefix, ip6_prefix),
+            Mechanism::Mx {
+                ref domain,
+                ip4_prefix,
+                ip6_prefix,
+            } => dual_cidr(f, "mx", domain, ip4_prefix, ip6_prefix),
+            Mechanism::Ptr(None) => f.write_str("ptr"),
+            Mechanism::Ptr(Some(ref domain)) => write!(f, "ptr:{}", domain),
+            Mechanism::Ip4(addr, prefix) => {
+                write!(f, "ip4:{}", addr)?;
+                if let Some(prefix) = prefix {
+                    write!(f, "/{}", prefix)?;
+                }
+                Ok(())
+            }
+            Mechanism::Ip6(addr, prefix) => {
+                write!(f, "ip6:{}", addr)?;
+                if let Some(prefix) = prefix {
+                    write!(f, "/{}", prefix)?;
+                }
+                Ok(())
+            }
+            Mechanism::Exists(ref domain) => write!(f, "exists:{}", domain),
+        }
+    }
+}
+
+//------------ Modifier ------------------------------------------------------
+
+/// A modifier of an SPF record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Modifier {
+    name: String,
+    value: String,
+}
+
+impl Modifier {
+    /// Returns the name of the modifier.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value of the modifier.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns whether the modifier may appear only once in a record.
+    fn is_unique(&self) -> bool {
+        self.name.eq_ignore_ascii_case("redirect")
+            || self.name.eq_ignore_ascii_case("exp")
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses the required `:domain-spec` argument of a mechanism.
+fn required_domain(
+    arg: Option<(&str, usize)>,
+    pos: usize,
+) -> Result<String, TextError> {
+    match arg {
+        Some((arg, pos)) => domain_arg(arg, pos),
+        None => Err(TextError::new(pos, "expected domain")),
+    }
+}
+
+/// Parses a `:domain-spec` argument.
+fn domain_arg(arg: &str, pos: usize) -> Result<String, TextError> {
+    if !arg.starts_with(':') {
+        return Err(TextError::new(pos, "expected ':'"));
+    }
+    let domain = &arg[1..];
+    if domain.is_empty() {
+        return Err(TextError::new(pos + 1, "expected domain"));
+    }
+    check_macro_string(domain, pos + 1)?;
+    Ok(domain.into())
+}
+
+/// Parses a `[:domain-spec][/ip4-cidr][//ip6-cidr]` argument.
+///
+/// Since the domain spec may contain slashes, the prefix lengths are taken
+/// from the end of the argument.
+#[allow(clippy::type_complexity)]
+fn dual_cidr_arg(
+    arg: &str,
+    pos: usize,
+) -> Result<(Option<String>, Option<u8>, Option<u8>), TextError> {
+    let mut rest = arg;
+    let ip6_prefix = match split_prefix(rest, "//") {
+        Some((head, prefix)) => {
+            rest = head;
+            Some(prefix_len(prefix, pos + head.len() + 2, 128)?)
+        }
+        None => None,
+    };
+    let ip4_prefix = match split_prefix(rest, "/") {
+        Some((head, prefix)) if !head.ends_with('/') => {
+            rest = head;
+            Some(prefix_len(prefix, pos + head.len() + 1, 32)?)
+        }
+        _ => None,
+    };
+    let domain = if rest.is_empty() {
+        None
+    } else {
+        Some(domain_arg(rest, pos)?)
+    };
+    Ok((domain, ip4_prefix, ip6_prefix))
+}
+
+/// Parses a `:address[/prefix]` argument.
+fn network_arg(
+    arg: Option<(&str, usize)>,
+    pos: usize,
+    max: u8,
+) -> Result<(&str, Option<u8>, usize), TextError> {
+    let (arg, pos) = match arg {
+        Some((arg, pos)) if arg.starts_with(':') => (&arg[1..], pos + 1),
+        Some((_, pos)) => return Err(TextError::new(pos, "expected ':'")),
+        None => return Err(TextError::new(pos, "expected address")),
+    };
+    match arg.find('/') {
+        Some(slash) => Ok((
+            &arg[..slash],
+            Some(prefix_len(&arg[slash + 1..], pos + slash + 1, max)?),
+            pos,
+        )),
+        None => Ok((arg, None, pos)),
+    }
+}
+
+/// Splits off a prefix length introduced by `sep` from the end of `arg`.
+fn split_prefix<'a>(arg: &'a str, sep: &str) -> Option<(&'a str, &'a str)> {
+    let idx = arg.rfind(sep)?;
+    let prefix = &arg[idx + sep.len()..];
+    if prefix.bytes().all(|ch| ch.is_ascii_digit()) {
+        Some((&arg[..idx], prefix))
+    } else {
+        None
+    }
+}
+
+/// Parses a prefix length.
+fn prefix_len(prefix: &str, pos: usize, max: u8) -> Result<u8, TextError> {
+    // The RFC doesn’t allow leading zeros.
+    if prefix.is_empty()
+        || !prefix.bytes().all(|ch| ch.is_ascii_digit())
+        || (prefix.len() > 1 && prefix.starts_with('0'))
+    {
+        return Err(TextError::new(pos, "invalid prefix length"));
+    }
+    match prefix.parse() {
+        Ok(len) if len <= max => Ok(len),
+        _ => Err(TextError::new(pos, "prefix length too large")),
+    }
+}
+
+/// Checks that a string is a valid macro string.
+///
+/// See section 7.1 of RFC 7208 for the grammar.
+fn check_macro_string(s: &str, pos: usize) -> Result<(), TextError> {
+    let mut chars = s.char_indices().peekable();
+    while let Some((idx, ch)) = chars.next() {
+        if ch != '%' {
+            if !('\x21'..='\x7e').contains(&ch) {
+                return Err(TextError::new(pos + idx, "invalid character"));
+            }
+            continue;
+        }
+        match chars.next() {
+            Some((_, '%')) | Some((_, '_')) | Some((_, '-')) => continue,
+            Some((_, '{')) => {}
+            _ => return Err(TextError::new(pos + idx, "invalid macro")),
+        }
+        match chars.next() {
+            Some((_, ch))
+                if "slodiphcrtv".contains(ch.to_ascii_lowercase()) => {}
+            _ => return Err(TextError::new(pos + idx, "invalid macro")),
+        }
+        while let Some(&(_, ch)) = chars.peek() {
+            if !ch.is_ascii_digit() {
+                break;
+            }
+            chars.next();
+        }
+        if let Some(&(_, 'r')) | Some(&(_, 'R')) = chars.peek() {
+            chars.next();
+        }
+        loop {
+            match chars.next() {
+                Some((_, '}')) => break,
+                Some((_, ch)) if ".-+,/_=".contains(ch) => {}
+                _ => return Err(TextError::new(pos + idx, "invalid macro")),
+            }
+        }
+    }
+    Ok(())
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn parse() {
+        let spf = Spf::parse(
+            "v=spf1 +mx a:%{d}.example/24//64  -ip4:192.0.2.0/24 \
+             ip6:2001:db8::1 ~include:_spf.example ?exists:%{ir}.x \
+             ptr redirect=_spf.example -all",
+        )
+        .unwrap();
+        let directives: Vec<_> = spf
+            .directives()
+            .map(|directive| (directive.qualifier(), directive.mechanism()))
+            .collect();
+        assert_eq!(
+            directives,
+            [
+                (
+                    Qualifier::Pass,
+                    &Mechanism::Mx {
+                        domain: None,
+                        ip4_prefix: None,
+                        ip6_prefix: None
+                    }
+                ),
+                (
+                    Qualifier::Pass,
+                    &Mechanism::A {
+                        domain: Some("%{d}.example".into()),
+                        ip4_prefix: Some(24),
+                        ip6_prefix: Some(64)
+                    }
+                ),
+                (
+                    Qualifier::Fail,
+                    &Mechanism::Ip4("192.0.2.0".parse().unwrap(), Some(24))
+                ),
+                (
+                    Qualifier::Pass,
+                    &Mechanism::Ip6("2001:db8::1".parse().unwrap(), None)
+                ),
+                (
+                    Qualifier::SoftFail,
+                    &Mechanism::Include("_spf.example".into())
+                ),
+                (Qualifier::Neutral, &Mechanism::Exists("%{ir}.x".into())),
+                (Qualifier::Pass, &Mechanism::Ptr(None)),
+                (Qualifier::Fail, &Mechanism::All),
+            ]
+        );
+        assert_eq!(spf.redirect(), Some("_spf.example"));
+        assert_eq!(spf.explanation(), None);
+        assert_eq!(
+            spf.to_string(),
+            "v=spf1 mx a:%{d}.example/24//64 -ip4:192.0.2.0/24 \
+             ip6:2001:db8::1 ~include:_spf.example ?exists:%{ir}.x \
+             ptr redirect=_spf.example -all"
+        );
+        assert_eq!(Spf::parse(&spf.to_string()).unwrap(), spf);
+        assert_eq!(Spf::parse("V=SPF1").unwrap().terms(), &[]);
+
+        assert!(Spf::is_spf(b"v=spf1 -all"));
+        assert!(!Spf::is_spf(b"v=spf10 -all"));
+    }
+
+    #[test]
+    fn errors() {
+        let err = |text| Spf::parse(text).unwrap_err();
+        assert_eq!(err("v=spf2"), TextError::new(0, "expected 'v=spf1'"));
+        assert_eq!(err("v=spf1-all"), TextError::new(6, "expected space"));
+        assert_eq!(
+            err("v=spf1 mx foo -all"),
+            TextError::new(10, "unknown mechanism")
+        );
+        assert_eq!(
+            err("v=spf1 ip4:192.0.2.0/33"),
+            TextError::new(21, "prefix length too large")
+        );
+        assert_eq!(
+            err("v=spf1 ip4:192.0.2"),
+            TextError::new(11, "invalid IPv4 address")
+        );
+        assert_eq!(
+            err("v=spf1 include"),
+            TextError::new(7, "expected domain")
+        );
+        assert_eq!(
+            err("v=spf1 all:x"),
+            TextError::new(10, "unexpected argument")
+        );
+        assert_eq!(
+            err("v=spf1 exists:%{q}"),
+            TextError::new(14, "invalid macro")
+        );
+        assert_eq!(
+            err("v=spf1 redirect=a redirect=b"),
+            TextError::new(18, "duplicate modifier")
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Conditional forwarding.
+//!
+//! This module provides the [`Forwarder`], a resolver that sends queries
+//! to different sets of upstream servers depending on the zone the query
+//! name belongs to. This is commonly used to resolve names of internal
+//! zones via the servers responsible for them while sending all other
+//! queries to a general purpose resolver.
+//!
+//! Each forward zone has a [`ForwardPolicy`] which decides what happens
+//! if its upstream servers fail to provide an answer: with
+//! `ForwardPolicy::First`, the query is retried with the default resolver,
+//! with `ForwardPolicy::Only`, the failure is returned.
+//!
+//! The forwarder implements the [`Resolver`] trait and can therefore be
+//! used with all lookup functions. Servers can use [`Forwarder::forward`]
+//! to relay a request they received.
+//!
+//! [`Forwarder`]: struct.Forwarder.html
+//! [`Forwarder::forward`]: struct.Forwarder.html#method.forward
+//! [`ForwardPolicy`]: enum.ForwardPolicy.html
+//! [`Resolver`]: ../resolver/trait.Resolver.html
+
+use crate::base::message::Message;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::OctetsRef;
+use crate::base::question::Question;
+use crate::resolv::resolver::Resolver;
+use crate::resolv::stub::conf::{ResolvConf, ServerConf, Transport};
+use crate::resolv::stub::{Answer, StubResolver};
+use bytes::Bytes;
+use futures::future::FutureExt;
+use std::boxed::Box;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::vec::Vec;
+
+//------------ Forwarder -----------------------------------------------------
+
+/// A resolver forwarding queries depending on the query name.
+///
+/// Queries for names within one of the forward zones are sent to the
+/// resolver of the zone with the longest apex containing the name. All
+/// other queries are sent to the default resolver. If there is no default
+/// resolver, these queries fail.
+#[derive(Clone, Debug, Default)]
+pub struct Forwarder {
+    /// The forward zones.
+    zones: Vec<ForwardZone>,
+
+    /// The resolver for all other names.
+    default: Option<StubResolver>,
+}
+
+impl Forwarder {
+    /// Creates a new forwarder without a default resolver.
+    ///
+    /// Queries for names outside the forward zones will fail.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new forwarder using the given default resolver.
+    pub fn with_default(resolver: StubResolver) -> Self {
+        Forwarder {
+            zones: Vec::new(),
+            default: Some(resolver),
+        }
+    }
+
+    /// Adds a forward zone using the given resolver.
+    ///
+    /// If there already is a forward zone with the same apex, it is
+    /// replaced.
+    pub fn add_zone<N: ToDname>(
+        &mut self,
+        apex: &N,
+        resolver: StubResolver,
+        policy: ForwardPolicy,
+    ) {
+        let zone = ForwardZone {
+            apex: apex.to_vec(),
+            resolver,
+            policy,
+        };
+        match self
+            .zones
+            .iter_mut()
+            .find(|item| item.apex.name_eq(&zone.apex))
+        {
+            Some(item) => *item = zone,
+            None => self.zones.push(zone),
+        }
+    }
+
+    /// Adds a forward zone using the given upstream servers.
+    ///
+    /// Each server will be used over both UDP and TCP with the default
+    /// options.
+    pub fn add_zone_servers<N: ToDname>(
+        &mut self,
+        apex: &N,
+        servers: &[SocketAddr],
+        policy: ForwardPolicy,
+    ) {
+        let mut conf = ResolvConf::new();
+        for addr in servers {
+            conf.servers.push(ServerConf::new(*addr, Transport::Udp));
+            conf.servers.push(ServerConf::new(*addr, Transport::Tcp));
+        }
+        conf.finalize();
+        self.add_zone(apex, StubResolver::from_conf(conf), policy)
+    }
+
+    /// Returns the forward zone responsible for `qname`.
+    ///
+    /// This is the zone with the longest apex that `qname` is equal to or
+    /// a subdomain of.
+    pub fn zone<N: ToDname>(&self, qname: &N) -> Option<&ForwardZone> {
+        self.zones
+            .iter()
+            .filter(|zone| qname.ends_with(&zone.apex))
+            .max_by_key(|zone| zone.apex.label_count())
+    }
+
+    /// Returns an iterator over the forward zones.
+    pub fn zones(&self) -> impl Iterator<Item = &ForwardZone> {
+        self.zones.iter()
+    }
+
+    /// Returns the default resolver if there is one.
+    pub fn default_resolver(&self) -> Option<&StubResolver> {
+        self.default.as_ref()
+    }
+
+    /// Answers a question.
+    pub async fn query<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<Answer, io::Error> {
+        let question = question.into();
+        let question = Question::new(
+            question.qname().to_vec(),
+            question.qtype(),
+            question.qclass(),
+        );
+        self.query_owned(question).await
+    }
+
+    /// Forwards a request received by a server.
+    ///
+    /// The answer is determined for the first question of the request.
+    /// Note that the returned answer has its own message ID which needs to
+    /// be replaced with that of the request before returning it.
+    pub async fn forward<Octets>(
+        &self,
+        request: &Message<Octets>,
+    ) -> Result<Answer, io::Error>
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let question = match request.first_question() {
+            Some(question) => question,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "request without question",
+                ))
+            }
+        };
+        self.query(question).await
+    }
+
+    /// Answers a question with an owned name.
+    async fn query_owned(
+        &self,
+        question: Question<Dname<Vec<u8>>>,
+    ) -> Result<Answer, io::Error> {
+        let zone = match self.zone(question.qname()) {
+            Some(zone) => zone,
+            None => return self.query_default(&question).await,
+        };
+        let res = zone.resolver.query(question.clone()).await;
+        match zone.policy {
+            ForwardPolicy::Only => res,
+            ForwardPolicy::First => match res {
+                Ok(answer) if answer.is_final() => Ok(answer),
+                res if self.default.is_none() => res,
+                _ => self.query_default(&question).await,
+            },
+        }
+    }
+
+    /// Answers a question via the default resolver.
+    async fn query_default(
+        &self,
+        question: &Question<Dname<Vec<u8>>>,
+    ) -> Result<Answer, io::Error> {
+        match self.default {
+            Some(ref resolver) => resolver.query(question.clone()).await,
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no upstream for query name",
+            )),
+        }
+    }
+}
+
+impl<'a> Resolver for &'a Forwarder {
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query =
+        Pin<Box<dyn Future<Output = Result<Answer, io::Error>> + 'a>>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        let question = Question::new(
+            question.qname().to_vec(),
+            question.qtype(),
+            question.qclass(),
+        );
+        let forwarder = *self;
+        async move { forwarder.query_owned(question).await }.boxed_local()
+    }
+}
+
+//------------ ForwardZone ---------------------------------------------------
+
+/// A zone whose queries are forwarded to a specific resolver.
+#[derive(Clone, Debug)]
+pub struct ForwardZone {
+    /// The apex of the zone.
+    apex: Dname<Vec<u8>>,
+
+    /// The resolver to send queries to.
+    resolver: StubResolver,
+
+    /// What to do if the resolver fails.
+    policy: ForwardPolicy,
+}
+
+impl ForwardZone {
+    /// Returns the apex of the zone.
+    pub fn apex(&self) -> &Dname<Vec<u8>> {
+        &self.apex
+    }
+
+    /// Returns the resolver queries are forwarded to.
+    pub fn resolver(&self) -> &StubResolver {
+        &self.resolver
+    }
+
+    /// Returns the forward policy of the zone.
+    pub fn policy(&self) -> ForwardPolicy {
+        self.policy
+    }
+}
+
+//------------ ForwardPolicy -------------------------------------------------
+
+/// What to do if the upstream servers of a forward zone fail.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ForwardPolicy {
+    /// Retry the query with the default resolver.
+    ///
+    /// The default resolver is used if the upstream servers of the zone
+    /// cannot be reached or don’t provide a final answer, e.g., they
+    /// respond with SERVFAIL or REFUSED.
+    First,
+
+    /// Only ever use the upstream servers of the zone.
+    Only,
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        SocketAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn zone_selection() {
+        let mut fwd = Forwarder::new();
+        fwd.add_zone_servers(
+            &name("example.com"),
+            &[addr("192.0.2.1:53")],
+            ForwardPolicy::First,
+        );
+        fwd.add_zone_servers(
+            &name("corp.example.com"),
+            &[addr("192.0.2.2:53")],
+            ForwardPolicy::Only,
+        );
+
+        let apex = |qname| fwd.zone(&name(qname)).map(|zone| zone.apex());
+        assert_eq!(apex("www.example.com"), Some(&name("example.com")));
+        assert_eq!(apex("EXAMPLE.com"), Some(&name("example.com")));
+        assert_eq!(
+            apex("www.corp.example.com"),
+            Some(&name("corp.example.com"))
+        );
+        assert_eq!(apex("www.example.org"), None);
+        assert_eq!(apex("badexample.com"), None);
+
+        fwd.add_zone_servers(
+            &name("Example.com"),
+            &[addr("192.0.2.3:53")],
+            ForwardPolicy::Only,
+        );
+        assert_eq!(fwd.zones().count(), 2);
+        assert_eq!(
+            fwd.zone(&name("www.example.com")).unwrap().policy(),
+            ForwardPolicy::Only
+        );
+    }
+
+    #[test]
+    fn no_upstream() {
+        let fwd = Forwarder::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let err = match runtime
+            .block_on(fwd.query((name("www.example.org"), Rtype::A)))
+        {
+            Ok(_) => panic!("query succeeded"),
+            Err(err) => err,
+        };
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -2,13 +2,12 @@
 
 use crate::base::iana::Rtype;
 use crate::base::message::RecordIter;
-use crate::base::name::{Dname, DnameBuilder, ParsedDname};
+use crate::base::name::{reverse_name, ParsedDname};
 use crate::base::octets::{Octets128, OctetsRef};
 use crate::rdata::Ptr;
+use crate::resolv::lookup::error::LookupError;
 use crate::resolv::resolver::Resolver;
-use std::io;
 use std::net::IpAddr;
-use std::str::FromStr;
 
 //------------ lookup_addr ---------------------------------------------------
 
@@ -20,12 +19,16 @@ use std::str::FromStr;
 ///
 /// The value returned upon success can be turned into an iterator over
 /// host names via its `iter()` method. This is due to lifetime issues.
+///
+/// The lookup fails if the answer doesn’t contain any PTR records.
 pub async fn lookup_addr<R: Resolver>(
     resolv: &R,
     addr: IpAddr,
-) -> Result<FoundAddrs<R>, io::Error> {
-    let name = dname_from_addr(addr);
-    resolv.query((name, Rtype::Ptr)).await.map(FoundAddrs)
+) -> Result<FoundAddrs<R>, LookupError> {
+    let name = reverse_name::<Octets128>(addr).unwrap();
+    let answer = resolv.query((name, Rtype::Ptr)).await?;
+    LookupError::check(answer.as_ref(), Rtype::Ptr)?;
+    Ok(FoundAddrs(answer))
 }
 
 //------------ FoundAddrs ----------------------------------------------------
@@ -90,50 +93,3 @@ impl<Ref: OctetsRef> Iterator for FoundAddrsIter<Ref> {
         None
     }
 }
-
-//------------ Helper Functions ---------------------------------------------
-
-/// Translates an IP address into a domain name.
-fn dname_from_addr(addr: IpAddr) -> Dname<Octets128> {
-    match addr {
-        IpAddr::V4(addr) => {
-            let octets = addr.octets();
-            Dname::from_str(&format!(
-                "{}.{}.{}.{}.in-addr.arpa.",
-                octets[3], octets[2], octets[1], octets[0]
-            ))
-            .unwrap()
-        }
-        IpAddr::V6(addr) => {
-            let mut res = DnameBuilder::<Octets128>::new();
-            for &item in addr.octets().iter().rev() {
-                res.append_label(&[hexdigit(item >> 4)]).unwrap();
-                res.append_label(&[hexdigit(item)]).unwrap();
-            }
-            res.append_label(b"ip6").unwrap();
-            res.append_label(b"arpa").unwrap();
-            res.into_dname().unwrap()
-        }
-    }
-}
-
-fn hexdigit(nibble: u8) -> u8 {
-    match nibble % 0x0F {
-        0 => b'0',
-        1 => b'1',
-        2 => b'2',
-        3 => b'3',
-        4 => b'4',
-        5 => b'5',
-        6 => b'6',
-        7 => b'7',
-        8 => b'8',
-        10 => b'A',
-        11 => b'B',
-        12 => b'C',
-        13 => b'D',
-        14 => b'E',
-        15 => b'F',
-        _ => unreachable!(),
-    }
-}
@@ -0,0 +1,218 @@
+//! Resolving many names at once.
+
+use crate::base::iana::Rtype;
+use crate::base::name::ToDname;
+use crate::resolv::resolver::Resolver;
+use futures::stream::{self, Stream, StreamExt};
+use std::io;
+use std::time::Duration;
+use tokio::time::timeout;
+
+//------------ resolve_bulk --------------------------------------------------
+
+/// Resolves a sequence of names with bounded concurrency.
+///
+/// The function queries the resolver for the records of type `rtype` for
+/// each name produced by `names`. At most `options.concurrency` queries
+/// are in flight at any time and each query is abandoned if it hasn’t been
+/// answered after `options.timeout`, in which case an error of kind
+/// `TimedOut` is returned for the name.
+///
+/// The returned stream produces the name together with the outcome of its
+/// query as soon as it is available. Results are therefore not necessarily
+/// produced in the order of `names`. Names are taken from the iterator
+/// only when there is room for another query, so it is fine to pass a
+/// very long or lazily generated iterator.
+///
+/// Note that the concurrency limit only applies to this stream. If the
+/// upstream servers need protecting, consider also configuring the rate
+/// limits of the resolver.
+pub fn resolve_bulk<'a, R, I>(
+    resolver: &'a R,
+    names: I,
+    rtype: Rtype,
+    options: BulkOptions,
+) -> impl Stream<Item = (I::Item, Result<R::Answer, io::Error>)> + 'a
+where
+    R: Resolver,
+    R::Query: 'a,
+    I: IntoIterator,
+    I::IntoIter: 'a,
+    I::Item: ToDname + 'a,
+{
+    let wait = options.timeout;
+    stream::iter(names)
+        .map(move |name| {
+            let query = resolver.query((&name, rtype));
+            async move {
+                let res = match timeout(wait, query).await {
+                    Ok(res) => res,
+                    Err(_) => Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "query timed out",
+                    )),
+                };
+                (name, res)
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1))
+}
+
+//------------ BulkOptions ---------------------------------------------------
+
+/// Options for resolving names in bulk.
+///
+/// See [`resolve_bulk`] for details.
+///
+/// [`resolve_bulk`]: fn.resolve_bulk.html
+#[derive(Clone, Copy, Debug)]
+pub struct BulkOptions {
+    /// The maximum number of queries in flight.
+    ///
+    /// A value of zero is treated as one. The default is 32.
+    pub concurrency: usize,
+
+    /// The time to wait for the answer to each individual query.
+    ///
+    /// This includes all the retries a resolver may do internally. The
+    /// default is 15 seconds.
+    pub timeout: Duration,
+}
+
+impl BulkOptions {
+    /// Creates new options with the given concurrency and timeout.
+    pub fn new(concurrency: usize, timeout: Duration) -> Self {
+        BulkOptions {
+            concurrency,
+            timeout,
+        }
+    }
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        BulkOptions {
+            concurrency: 32,
+            timeout: Duration::from_secs(15),
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rcode};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::base::question::Question;
+    use crate::rdata::A;
+    use crate::utils::replay::{self, Corpus, Exchange};
+    use std::boxed::Box;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::str::FromStr;
+    use std::string::ToString;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::vec::Vec;
+    use tokio::time::sleep;
+
+    /// A resolver answering from a corpus after a delay.
+    ///
+    /// Names starting with `slow` are never answered in time. The resolver
+    /// keeps track of how many queries were in flight at most.
+    struct Delayed {
+        corpus: Arc<Corpus>,
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    impl Resolver for Delayed {
+        type Octets = Vec<u8>;
+        type Answer = replay::Answer;
+        type Query =
+            Pin<Box<dyn Future<Output = Result<replay::Answer, io::Error>>>>;
+
+        fn query<N, Q>(&self, question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            let question = question.into();
+            let slow =
+                question.qname().to_vec().to_string().starts_with("slow");
+            let res = self.corpus.query(question);
+            let running = self.running.clone();
+            let max_running = self.max_running.clone();
+            Box::pin(async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                let delay = if slow { 10_000 } else { 10 };
+                sleep(Duration::from_millis(delay)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                res.await
+            })
+        }
+    }
+
+    fn exchange(name: &str) -> Exchange {
+        let name = Dname::<Vec<u8>>::from_str(name).unwrap();
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((&name, Rtype::A)).unwrap();
+        let query = query.into_message();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        answer
+            .push((&name, Class::In, 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        Exchange::new(query, Some(answer.into_message()))
+    }
+
+    #[test]
+    fn bulk() {
+        let names = [
+            "a.example.",
+            "b.example.",
+            "c.example.",
+            "d.example.",
+            "e.example.",
+            "slow.example.",
+        ];
+        let resolver = Delayed {
+            corpus: Arc::new(
+                names[..5].iter().map(|name| exchange(name)).collect(),
+            ),
+            running: Default::default(),
+            max_running: Default::default(),
+        };
+        let names = names
+            .iter()
+            .map(|name| Dname::<Vec<u8>>::from_str(name).unwrap());
+        let options = BulkOptions::new(2, Duration::from_millis(200));
+
+        let results = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(
+                resolve_bulk(&resolver, names, Rtype::A, options)
+                    .collect::<Vec<_>>(),
+            );
+
+        assert_eq!(results.len(), 6);
+        for (name, res) in results {
+            if name.to_string().starts_with("slow") {
+                assert!(matches!(
+                    res,
+                    Err(ref err) if err.kind() == io::ErrorKind::TimedOut
+                ));
+            } else {
+                assert!(res.is_ok())
+            }
+        }
+        assert_eq!(resolver.max_running.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,340 @@
+//! DNS64 address synthesis.
+//!
+//! Hosts on an IPv6-only network behind a NAT64 gateway can reach IPv4-only
+//! hosts via IPv6 addresses that embed the IPv4 address in a
+//! network-specific prefix, Pref64::/n. DNS64 as defined in [RFC 6147]
+//! hands out such synthesized AAAA records for names that only have A
+//! records.
+//!
+//! This module allows a stub resolver to do this synthesis itself. The
+//! prefix to use is represented by [`Prefix64`]. It can either be
+//! configured explicitly or discovered via the special name
+//! `ipv4only.arpa.` as described in [RFC 7050] using
+//! [`discover_prefix64`]. The synthesized addresses for a host are then
+//! available through [`FoundHosts::iter_dns64`].
+//!
+//! [`Prefix64`]: struct.Prefix64.html
+//! [`discover_prefix64`]: fn.discover_prefix64.html
+//! [`FoundHosts::iter_dns64`]: ../host/struct.FoundHosts.html#method.iter_dns64
+//! [RFC 6147]: https://tools.ietf.org/html/rfc6147
+//! [RFC 7050]: https://tools.ietf.org/html/rfc7050
+
+use crate::base::iana::Rtype;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::OctetsRef;
+use crate::rdata::Aaaa;
+use crate::resolv::lookup::error::LookupError;
+use crate::resolv::resolver::Resolver;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Prefix64 ------------------------------------------------------
+
+/// An IPv6 prefix used to embed IPv4 addresses.
+///
+/// The prefix can have one of the lengths 32, 40, 48, 56, 64, or 96 bits
+/// defined in [RFC 6052]. The IPv4 address is embedded following the rules
+/// of section 2.2 of that RFC, skipping over the bits 64 to 71 of the IPv6
+/// address.
+///
+/// [RFC 6052]: https://tools.ietf.org/html/rfc6052
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Prefix64 {
+    /// The prefix with all bits outside of it set to zero.
+    addr: Ipv6Addr,
+
+    /// The length of the prefix in bits.
+    len: u8,
+}
+
+impl Prefix64 {
+    /// Creates a new prefix from an address and a prefix length.
+    ///
+    /// Any bits of `addr` beyond the prefix length are ignored. Returns an
+    /// error if `len` is not one of the permitted lengths.
+    pub fn new(addr: Ipv6Addr, len: u8) -> Result<Self, Prefix64Error> {
+        let octets = addr.octets();
+        let mut masked = [0u8; 16];
+        let count = usize::from(Self::checked_len(len)? / 8);
+        masked[..count].copy_from_slice(&octets[..count]);
+        Ok(Prefix64 {
+            addr: masked.into(),
+            len,
+        })
+    }
+
+    /// Returns the well-known prefix `64:ff9b::/96`.
+    pub fn well_known() -> Self {
+        Prefix64 {
+            addr: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            len: 96,
+        }
+    }
+
+    /// Returns the prefix address.
+    pub fn addr(&self) -> Ipv6Addr {
+        self.addr
+    }
+
+    /// Returns the prefix length in bits.
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns whether this is the well-known prefix.
+    pub fn is_well_known(&self) -> bool {
+        *self == Self::well_known()
+    }
+
+    /// Returns the IPv6 address for the given IPv4 address.
+    pub fn synthesize(&self, addr: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.addr.octets();
+        for (&pos, &octet) in
+            Self::positions(self.len).iter().zip(addr.octets().iter())
+        {
+            octets[pos] = octet
+        }
+        octets.into()
+    }
+
+    /// Returns the IPv4 address embedded in an IPv6 address.
+    ///
+    /// Returns `None` if `addr` is not covered by this prefix.
+    pub fn extract(&self, addr: Ipv6Addr) -> Option<Ipv4Addr> {
+        let octets = addr.octets();
+        let count = usize::from(self.len / 8);
+        if octets[..count] != self.addr.octets()[..count] {
+            return None;
+        }
+        let mut res = [0u8; 4];
+        for (target, &pos) in
+            res.iter_mut().zip(Self::positions(self.len).iter())
+        {
+            *target = octets[pos]
+        }
+        Some(res.into())
+    }
+
+    /// Determines the prefix from a synthesized address for `ipv4only.arpa`.
+    ///
+    /// The name `ipv4only.arpa.` has only the two A records 192.0.0.170
+    /// and 192.0.0.171. If a DNS64 synthesizes a AAAA record for it, the
+    /// prefix can be found by looking for one of these addresses as
+    /// described in section 3 of [RFC 7050].
+    ///
+    /// Returns `None` if neither address is embedded in `addr` or if it is
+    /// embedded more than once, making the prefix ambiguous.
+    ///
+    /// [RFC 7050]: https://tools.ietf.org/html/rfc7050
+    pub fn from_ipv4only(addr: Ipv6Addr) -> Option<Self> {
+        let mut res = None;
+        for &len in &[32, 40, 48, 56, 64, 96] {
+            let prefix = match Self::new(addr, len) {
+                Ok(prefix) => prefix,
+                Err(_) => continue,
+            };
+            if prefix.synthesize(IPV4ONLY_ADDRS[0]) != addr
+                && prefix.synthesize(IPV4ONLY_ADDRS[1]) != addr
+            {
+                continue;
+            }
+            if res.is_some() {
+                return None;
+            }
+            res = Some(prefix)
+        }
+        res
+    }
+
+    /// Checks that a prefix length is permitted.
+    fn checked_len(len: u8) -> Result<u8, Prefix64Error> {
+        match len {
+            32 | 40 | 48 | 56 | 64 | 96 => Ok(len),
+            _ => Err(Prefix64Error::BadLength),
+        }
+    }
+
+    /// Returns the octets of an IPv6 address holding the IPv4 octets.
+    fn positions(len: u8) -> [usize; 4] {
+        match len {
+            32 => [4, 5, 6, 7],
+            40 => [5, 6, 7, 9],
+            48 => [6, 7, 9, 10],
+            56 => [7, 9, 10, 11],
+            64 => [9, 10, 11, 12],
+            _ => [12, 13, 14, 15],
+        }
+    }
+}
+
+/// The IPv4 addresses of `ipv4only.arpa.`
+const IPV4ONLY_ADDRS: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+//--- Default
+
+impl Default for Prefix64 {
+    fn default() -> Self {
+        Self::well_known()
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for Prefix64 {
+    type Err = Prefix64Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let addr = Ipv6Addr::from_str(parts.next().unwrap())
+            .map_err(|_| Prefix64Error::BadAddr)?;
+        let len = match parts.next() {
+            Some(len) => {
+                u8::from_str(len).map_err(|_| Prefix64Error::BadLength)?
+            }
+            None => 96,
+        };
+        Self::new(addr, len)
+    }
+}
+
+impl fmt::Display for Prefix64 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+//------------ discover_prefix64 ---------------------------------------------
+
+/// Discovers the DNS64 prefixes used by the upstream resolvers.
+///
+/// The function queries for the AAAA records of `ipv4only.arpa.` and
+/// derives the prefixes from the synthesized addresses. If the upstream
+/// resolvers don’t perform DNS64, the returned list will be empty.
+pub async fn discover_prefix64<R: Resolver>(
+    resolver: &R,
+) -> Result<Vec<Prefix64>, LookupError>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    let qname = Dname::from_slice(b"\x08ipv4only\x04arpa\0").unwrap();
+    let answer = resolver.query((qname, Rtype::Aaaa)).await?;
+    let mut res = Vec::new();
+    let records = match answer.as_ref().answer() {
+        Ok(records) => records.limit_to::<Aaaa>(),
+        Err(_) => return Ok(res),
+    };
+    for record in records {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if !record.owner().name_eq(&qname) {
+            continue;
+        }
+        if let Some(prefix) = Prefix64::from_ipv4only(record.data().addr()) {
+            if !res.contains(&prefix) {
+                res.push(prefix)
+            }
+        }
+    }
+    Ok(res)
+}
+
+//============ Error Types ===================================================
+
+//------------ Prefix64Error -------------------------------------------------
+
+/// An error happened when creating a DNS64 prefix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Prefix64Error {
+    /// The prefix address was not a valid IPv6 address.
+    BadAddr,
+
+    /// The prefix length was not one of the permitted values.
+    BadLength,
+}
+
+//--- Display and Error
+
+impl fmt::Display for Prefix64Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Prefix64Error::BadAddr => f.write_str("invalid IPv6 prefix"),
+            Prefix64Error::BadLength => {
+                f.write_str("invalid DNS64 prefix length")
+            }
+        }
+    }
+}
+
+impl error::Error for Prefix64Error {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn synthesize_and_extract() {
+        // RFC 6052, section 2.4.
+        let v4 = Ipv4Addr::new(192, 0, 2, 33);
+        for &(prefix, addr) in &[
+            ("2001:db8::/32", "2001:db8:c000:221::"),
+            ("2001:db8:100::/40", "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::/48", "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::/56", "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::/64", "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::/96", "2001:db8:122:344::192.0.2.33"),
+            ("64:ff9b::/96", "64:ff9b::192.0.2.33"),
+        ] {
+            let prefix = Prefix64::from_str(prefix).unwrap();
+            let addr = Ipv6Addr::from_str(addr).unwrap();
+            assert_eq!(prefix.synthesize(v4), addr);
+            assert_eq!(prefix.extract(addr), Some(v4));
+        }
+        assert_eq!(
+            Prefix64::well_known()
+                .extract(Ipv6Addr::from_str("2001:db8::1").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            Prefix64::from_str("64:ff9b::").unwrap(),
+            Prefix64::well_known()
+        );
+        assert_eq!(
+            Prefix64::from_str("2001:db8::/33"),
+            Err(Prefix64Error::BadLength)
+        );
+        assert_eq!(Prefix64::from_str("foo/96"), Err(Prefix64Error::BadAddr));
+    }
+
+    #[test]
+    fn from_ipv4only() {
+        assert_eq!(
+            Prefix64::from_ipv4only(
+                Ipv6Addr::from_str("64:ff9b::192.0.0.170").unwrap()
+            ),
+            Some(Prefix64::well_known())
+        );
+        assert_eq!(
+            Prefix64::from_ipv4only(
+                Ipv6Addr::from_str("2001:db8:c000:ab::").unwrap()
+            ),
+            Some(Prefix64::from_str("2001:db8::/32").unwrap())
+        );
+        assert_eq!(
+            Prefix64::from_ipv4only(
+                Ipv6Addr::from_str("2001:db8::1").unwrap()
+            ),
+            None
+        );
+    }
+}
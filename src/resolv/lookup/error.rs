@@ -0,0 +1,304 @@
+//! The error type of lookups.
+
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use bytes::Bytes;
+use std::{error, fmt, io};
+
+//------------ LookupError ---------------------------------------------------
+
+/// A lookup has failed.
+///
+/// The variants distinguish the reasons a lookup can fail so that callers
+/// can react appropriately. Where the failure was determined from an
+/// answer, the variant carries a copy of that answer.
+pub enum LookupError {
+    /// The queried name does not exist.
+    NxDomain(Message<Bytes>),
+
+    /// The queried name exists but has no records of the requested type.
+    NoData(Message<Bytes>),
+
+    /// The upstream resolver answered with SERVFAIL.
+    ServFail(Message<Bytes>),
+
+    /// The upstream resolver refused to answer.
+    Refused(Message<Bytes>),
+
+    /// The upstream resolver answered with some other error code.
+    Rcode(Message<Bytes>),
+
+    /// No answer arrived in time.
+    Timeout,
+
+    /// Talking to the upstream resolvers failed.
+    Transport(io::Error),
+
+    /// The answer could not be parsed.
+    Malformed(Option<Message<Bytes>>),
+
+    /// The name to look up was invalid.
+    ///
+    /// This happens, for instance, if appending a search suffix makes
+    /// the name too long.
+    BadName,
+}
+
+impl LookupError {
+    /// Checks that an answer contains records of the given type.
+    ///
+    /// Returns an error if the answer has an error response code, if its
+    /// answer section is broken, or if it doesn’t contain any records of
+    /// type `rtype`.
+    pub fn check<Octets: AsRef<[u8]>>(
+        answer: &Message<Octets>,
+        rtype: Rtype,
+    ) -> Result<(), Self> {
+        match answer.header().rcode() {
+            Rcode::NoError => {}
+            Rcode::NXDomain => {
+                return Err(LookupError::NxDomain(copy(answer)))
+            }
+            Rcode::ServFail => {
+                return Err(LookupError::ServFail(copy(answer)))
+            }
+            Rcode::Refused => return Err(LookupError::Refused(copy(answer))),
+            _ => return Err(LookupError::Rcode(copy(answer))),
+        }
+        let answer_slice = answer.for_slice();
+        let section = match answer_slice.answer() {
+            Ok(section) => section,
+            Err(_) => return Err(LookupError::Malformed(Some(copy(answer)))),
+        };
+        for record in section {
+            match record {
+                Ok(record) if record.rtype() == rtype => return Ok(()),
+                Ok(_) => {}
+                Err(_) => {
+                    return Err(LookupError::Malformed(Some(copy(answer))))
+                }
+            }
+        }
+        Err(LookupError::NoData(copy(answer)))
+    }
+
+    /// Converts the result of a query into an error.
+    ///
+    /// This is used when it is already known that the query didn’t result
+    /// in records of type `rtype`. If the answer looks fine otherwise,
+    /// `NoData` is returned.
+    pub fn from_result<Answer, Octets>(
+        res: Result<Answer, io::Error>,
+        rtype: Rtype,
+    ) -> Self
+    where
+        Answer: AsRef<Message<Octets>>,
+        Octets: AsRef<[u8]>,
+    {
+        match res {
+            Ok(answer) => match Self::check(answer.as_ref(), rtype) {
+                Ok(()) => LookupError::NoData(copy(answer.as_ref())),
+                Err(err) => err,
+            },
+            Err(err) => err.into(),
+        }
+    }
+
+    /// Picks the more telling of two errors for the same name.
+    ///
+    /// An NXDOMAIN wins over everything else, NODATA loses against
+    /// everything else. Otherwise, `self` is returned.
+    pub fn merge(self, other: Self) -> Self {
+        if !self.is_nxdomain()
+            && (other.is_nxdomain()
+                || (self.is_no_data() && !other.is_no_data()))
+        {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Returns the answer that caused the error if there is one.
+    pub fn answer(&self) -> Option<&Message<Bytes>> {
+        match *self {
+            LookupError::NxDomain(ref msg)
+            | LookupError::NoData(ref msg)
+            | LookupError::ServFail(ref msg)
+            | LookupError::Refused(ref msg)
+            | LookupError::Rcode(ref msg) => Some(msg),
+            LookupError::Malformed(ref msg) => msg.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the name does not exist.
+    pub fn is_nxdomain(&self) -> bool {
+        matches!(*self, LookupError::NxDomain(_))
+    }
+
+    /// Returns whether the name exists but has no records of the type.
+    pub fn is_no_data(&self) -> bool {
+        matches!(*self, LookupError::NoData(_))
+    }
+
+    /// Returns whether the error is a definitive negative answer.
+    ///
+    /// This is the case for both NXDOMAIN and NODATA. All other errors
+    /// mean that the lookup could not be completed and a retry may
+    /// succeed.
+    pub fn is_negative(&self) -> bool {
+        self.is_nxdomain() || self.is_no_data()
+    }
+}
+
+/// Copies a message into a message atop bytes.
+fn copy<Octets: AsRef<[u8]>>(msg: &Message<Octets>) -> Message<Bytes> {
+    Message::from_octets(Bytes::copy_from_slice(msg.as_slice())).unwrap()
+}
+
+//--- From
+
+impl From<io::Error> for LookupError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::TimedOut {
+            LookupError::Timeout
+        } else {
+            LookupError::Transport(err)
+        }
+    }
+}
+
+impl From<LookupError> for io::Error {
+    fn from(err: LookupError) -> Self {
+        match err {
+            LookupError::Transport(err) => err,
+            LookupError::Timeout => {
+                io::Error::new(io::ErrorKind::TimedOut, "request timed out")
+            }
+            LookupError::NxDomain(_) | LookupError::NoData(_) => {
+                io::Error::new(io::ErrorKind::NotFound, err)
+            }
+            #[allow(clippy::io_other_error)] // io::Error::other needs 1.74.
+            err => io::Error::new(io::ErrorKind::Other, err),
+        }
+    }
+}
+
+//--- Debug
+
+impl fmt::Debug for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, msg) = match *self {
+            LookupError::NxDomain(ref msg) => ("NxDomain", msg),
+            LookupError::NoData(ref msg) => ("NoData", msg),
+            LookupError::ServFail(ref msg) => ("ServFail", msg),
+            LookupError::Refused(ref msg) => ("Refused", msg),
+            LookupError::Rcode(ref msg) => ("Rcode", msg),
+            LookupError::Timeout => return f.write_str("Timeout"),
+            LookupError::Transport(ref err) => {
+                return f.debug_tuple("Transport").field(err).finish()
+            }
+            LookupError::Malformed(ref msg) => {
+                return f
+                    .debug_tuple("Malformed")
+                    .field(&msg.as_ref().map(Message::header))
+                    .finish()
+            }
+            LookupError::BadName => return f.write_str("BadName"),
+        };
+        f.debug_tuple(name).field(&msg.header()).finish()
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LookupError::NxDomain(_) => f.write_str("no such domain"),
+            LookupError::NoData(_) => f.write_str("no records found"),
+            LookupError::ServFail(_) => f.write_str("server failure"),
+            LookupError::Refused(_) => f.write_str("query refused"),
+            LookupError::Rcode(ref msg) => {
+                write!(f, "query failed with {}", msg.header().rcode())
+            }
+            LookupError::Timeout => f.write_str("request timed out"),
+            LookupError::Transport(ref err) => err.fmt(f),
+            LookupError::Malformed(_) => f.write_str("malformed answer"),
+            LookupError::BadName => f.write_str("invalid domain name"),
+        }
+    }
+}
+
+impl error::Error for LookupError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use core::str::FromStr;
+    use std::vec::Vec;
+
+    fn answer(rcode: Rcode, with_a: bool) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_rcode(rcode);
+        let mut msg = msg.answer();
+        if with_a {
+            msg.push((
+                Dname::<Vec<u8>>::from_str("example.com.").unwrap(),
+                3600,
+                A::from_str("192.0.2.1").unwrap(),
+            ))
+            .unwrap();
+        }
+        msg.into_message()
+    }
+
+    #[test]
+    fn check() {
+        assert!(LookupError::check(&answer(Rcode::NoError, true), Rtype::A)
+            .is_ok());
+        assert!(LookupError::check(
+            &answer(Rcode::NoError, true),
+            Rtype::Aaaa
+        )
+        .unwrap_err()
+        .is_no_data());
+        assert!(LookupError::check(
+            &answer(Rcode::NXDomain, false),
+            Rtype::A
+        )
+        .unwrap_err()
+        .is_nxdomain());
+        match LookupError::check(&answer(Rcode::ServFail, false), Rtype::A) {
+            Err(LookupError::ServFail(msg)) => {
+                assert_eq!(msg.header().rcode(), Rcode::ServFail)
+            }
+            _ => panic!("expected SERVFAIL"),
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let nodata = || {
+            LookupError::check(&answer(Rcode::NoError, false), Rtype::A)
+                .unwrap_err()
+        };
+        let nxdomain = || {
+            LookupError::check(&answer(Rcode::NXDomain, false), Rtype::A)
+                .unwrap_err()
+        };
+        assert!(nodata().merge(LookupError::Timeout).answer().is_none());
+        assert!(LookupError::Timeout.merge(nxdomain()).is_nxdomain());
+        assert!(nodata().merge(nodata()).is_no_data());
+        assert!(matches!(
+            LookupError::Timeout.merge(LookupError::BadName),
+            LookupError::Timeout
+        ));
+    }
+}
@@ -2,12 +2,25 @@
 
 use crate::base::iana::Rtype;
 use crate::base::message::RecordIter;
-use crate::base::name::{ParsedDname, ToDname, ToRelativeDname};
+use crate::base::name::{
+    Dname, ParsedDname, ToDname, ToLabelIter, ToRelativeDname,
+};
 use crate::base::octets::OctetsRef;
 use crate::rdata::{Aaaa, A};
+use crate::resolv::lookup::dns64::Prefix64;
+use crate::resolv::lookup::error::LookupError;
+use crate::resolv::lookup::name::{IntoDname, IntoSearchDname, SearchDname};
 use crate::resolv::resolver::{Resolver, SearchNames};
+use crate::resolv::stub::conf::{AddrOrder, SortList};
+use bytes::Bytes;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
 use std::io;
-use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
 
 //------------ lookup_host ---------------------------------------------------
 
@@ -20,10 +33,16 @@ use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 /// IP addresses or even socket addresses. Since the lookup may determine that
 /// the host name is in fact an alias for another name, the value will also
 /// return the canonical name.
+///
+/// The lookup fails if neither query resulted in any addresses. In this
+/// case, the error describes the more telling of the two failures. It also
+/// fails with `LookupError::BadName` if `qname` cannot be converted into a
+/// domain name.
 pub async fn lookup_host<R: Resolver>(
     resolver: &R,
-    qname: impl ToDname,
-) -> Result<FoundHosts<R>, io::Error> {
+    qname: impl IntoDname,
+) -> Result<FoundHosts<R>, LookupError> {
+    let qname = qname.into_dname()?;
     let (a, aaaa) = tokio::join!(
         resolver.query((&qname, Rtype::A)),
         resolver.query((&qname, Rtype::Aaaa)),
@@ -33,20 +52,318 @@ pub async fn lookup_host<R: Resolver>(
 
 //------------ search_host ---------------------------------------------------
 
+/// Creates a future that resolves a relative host name into its addresses.
+///
+/// The future appends the suffixes of the resolver’s search list to
+/// `qname` and returns the first non-empty answer. When and whether
+/// `qname` is tried as an absolute name, too, depends on the resolver’s
+/// [`SearchMode`]. By default, this is what glibc does: if `qname`
+/// contains at least as many dots as given by the resolver’s `ndots()`,
+/// the name is first tried as an absolute name. Otherwise it is tried as
+/// an absolute name last unless it consists of a single label and the
+/// resolver’s `no_tld_query()` returns `true`.
+///
+/// If `qname` is a string ending in a dot, it is an absolute name and
+/// looked up as is without using the search list.
+///
+/// If all lookups fail, the error of the absolute lookup is returned or,
+/// if there wasn’t one, the error of the last lookup.
+///
+/// If the resolver provides a [`SearchCache`], the absolute name that
+/// produced the answer is remembered for the smallest TTL of the answer
+/// and later lookups of `qname` go straight to that name. Should the
+/// lookup of the remembered name fail, the full search is done again.
+///
+/// [`SearchMode`]: enum.SearchMode.html
+/// [`SearchCache`]: struct.SearchCache.html
 pub async fn search_host<R: Resolver + SearchNames>(
     resolver: &R,
-    qname: impl ToRelativeDname,
-) -> Result<FoundHosts<R>, io::Error> {
+    qname: impl IntoSearchDname,
+) -> Result<FoundHosts<R>, LookupError>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    let qname = match qname.into_search_dname()? {
+        SearchDname::Relative(qname) => qname,
+        SearchDname::Absolute(qname) => {
+            return lookup_host(resolver, qname).await
+        }
+    };
+    let cache = resolver.search_cache();
+    if let Some(cache) = cache {
+        if let Some(name) = cache.get(&qname) {
+            if let Ok(answer) = lookup_host(resolver, &name).await {
+                return Ok(answer);
+            }
+            cache.remove(&qname);
+        }
+    }
+    let dots = qname.iter_labels().count().saturating_sub(1);
+    let mode = resolver.search_mode();
+    let absolute_first = match mode {
+        SearchMode::Glibc | SearchMode::Musl => dots >= resolver.ndots(),
+        SearchMode::AbsoluteFirst => true,
+        SearchMode::SearchFirst => false,
+    };
+    let absolute = if absolute_first {
+        let name = (&qname).chain_root();
+        match lookup_host(resolver, &name).await {
+            Ok(answer) => {
+                return Ok(cache_found(cache, &qname, &name, answer))
+            }
+            // musl doesn’t use the search list for such names at all.
+            Err(err) if mode == SearchMode::Musl => return Err(err),
+            Err(err) => Some(err),
+        }
+    } else {
+        None
+    };
+    let mut last = LookupError::BadName;
     for suffix in resolver.search_iter() {
+        // The root suffix is taken care of by the absolute lookup.
+        if suffix.len() == 1 {
+            continue;
+        }
         if let Ok(name) = (&qname).chain(suffix) {
-            if let Ok(answer) = lookup_host(resolver, name).await {
-                if !answer.is_empty() {
-                    return Ok(answer);
+            match lookup_host(resolver, &name).await {
+                Ok(answer) => {
+                    return Ok(cache_found(cache, &qname, &name, answer))
                 }
+                // musl only continues after a negative answer.
+                Err(err)
+                    if mode == SearchMode::Musl && !err.is_negative() =>
+                {
+                    return Err(err)
+                }
+                Err(err) => last = err,
+            }
+        }
+    }
+    match absolute {
+        Some(err) => Err(err),
+        None if dots == 0
+            && resolver.no_tld_query()
+            && mode != SearchMode::Musl =>
+        {
+            Err(last)
+        }
+        None => {
+            let name = (&qname).chain_root();
+            let answer = lookup_host(resolver, &name).await?;
+            Ok(cache_found(cache, &qname, &name, answer))
+        }
+    }
+}
+
+//------------ SearchMode ----------------------------------------------------
+
+/// How a search lookup combines the search list and the absolute name.
+///
+/// The modes differ in whether and when a relative host name is tried as
+/// an absolute name. In all modes, names ending in a dot are only looked
+/// up as absolute names and never with the search list.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SearchMode {
+    /// Behave like glibc.
+    ///
+    /// Names with at least `ndots` dots are tried as absolute names first,
+    /// followed by the search list. Other names are tried with the search
+    /// list first and as absolute names last unless they consist of a
+    /// single label and `no_tld_query` is set. The search list is walked
+    /// past all failed lookups.
+    Glibc,
+
+    /// Behave like musl.
+    ///
+    /// Names with at least `ndots` dots are only tried as absolute names.
+    /// Other names are tried with the search list first and as absolute
+    /// names last. The `no_tld_query` option is ignored. Walking the
+    /// search list stops at the first lookup that fails with anything but
+    /// a negative answer.
+    Musl,
+
+    /// Always try the absolute name first, followed by the search list.
+    AbsoluteFirst,
+
+    /// Always try the search list first, followed by the absolute name.
+    ///
+    /// As with glibc, single-label names aren’t tried as absolute names if
+    /// `no_tld_query` is set.
+    SearchFirst,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] needs Rust 1.62.
+impl Default for SearchMode {
+    fn default() -> Self {
+        SearchMode::Glibc
+    }
+}
+
+/// Remembers the name that produced the answer for `qname`.
+fn cache_found<R: Resolver>(
+    cache: Option<&SearchCache>,
+    qname: &impl ToRelativeDname,
+    name: &impl ToDname,
+    answer: FoundHosts<R>,
+) -> FoundHosts<R>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    if let (Some(cache), Some(ttl)) = (cache, answer.min_ttl()) {
+        cache.insert(qname, name.to_bytes(), Duration::from_secs(ttl.into()))
+    }
+    answer
+}
+
+//------------ SearchCache ---------------------------------------------------
+
+/// A cache of the results of walking the search list.
+///
+/// The cache maps relative host names to the absolute name that produced
+/// an answer in [`search_host`]. Entries expire after the TTL given when
+/// they were inserted. Names are compared case-insensitively.
+///
+/// Resolvers make a cache available through
+/// [`SearchNames::search_cache`].
+///
+/// [`search_host`]: fn.search_host.html
+/// [`SearchNames::search_cache`]: ../../resolver/trait.SearchNames.html#method.search_cache
+#[derive(Debug)]
+pub struct SearchCache {
+    /// The entries keyed by the lowercased wire format of the names.
+    entries: Mutex<HashMap<Vec<u8>, SearchEntry>>,
+
+    /// The maximum number of entries.
+    capacity: usize,
+}
+
+impl SearchCache {
+    /// The default maximum number of entries.
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    /// Creates a new, empty cache with the default capacity.
+    pub fn new() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new, empty cache with the given maximum number of entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        SearchCache {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Returns the absolute name remembered for `qname`, if any.
+    pub fn get(&self, qname: &impl ToRelativeDname) -> Option<Dname<Bytes>> {
+        let key = Self::key(qname);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some((name, expires)) if *expires > Instant::now() => {
+                Some(name.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
             }
+            None => None,
         }
     }
-    lookup_host(resolver, qname.chain_root()).await
+
+    /// Remembers `name` as the absolute name for `qname` for `ttl`.
+    ///
+    /// If the cache is full, expired entries are dropped first. If that
+    /// doesn’t free up any space, the cache is cleared.
+    pub fn insert(
+        &self,
+        qname: &impl ToRelativeDname,
+        name: Dname<Bytes>,
+        ttl: Duration,
+    ) {
+        if self.capacity == 0 || ttl == Duration::from_secs(0) {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.retain(|_, (_, expires)| *expires > now);
+            if entries.len() >= self.capacity {
+                entries.clear();
+            }
+        }
+        entries.insert(Self::key(qname), (name, now + ttl));
+    }
+
+    /// Forgets the entry for `qname`.
+    pub fn remove(&self, qname: &impl ToRelativeDname) {
+        self.entries.lock().unwrap().remove(&Self::key(qname));
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear()
+    }
+
+    fn key(qname: &impl ToRelativeDname) -> Vec<u8> {
+        let mut key = qname.to_vec().into_octets();
+        key.make_ascii_lowercase();
+        key
+    }
+}
+
+impl Default for SearchCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The absolute name for a relative name and when it expires.
+type SearchEntry = (Dname<Bytes>, Instant);
+
+//------------ resolve -------------------------------------------------------
+
+/// Resolves a string of the form `host:port` into socket addresses.
+///
+/// This mirrors what `ToSocketAddrs` does for strings in the standard
+/// library. If `addr` is a socket address literal, such as
+/// `192.0.2.1:443` or `[2001:db8::1]:443`, it is returned without any
+/// query. Otherwise, the part before the last colon is taken as the host
+/// name which is looked up via [`lookup_host`] and the part after it as
+/// the port.
+///
+/// Fails with `LookupError::BadName` if the string doesn’t consist of a
+/// host name and a port or the host name is invalid. Otherwise, the
+/// lookup fails as described for [`lookup_host`].
+///
+/// [`lookup_host`]: fn.lookup_host.html
+pub async fn resolve<R: Resolver>(
+    resolver: &R,
+    addr: &str,
+) -> Result<Vec<SocketAddr>, LookupError>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    if let Ok(addr) = SocketAddr::from_str(addr) {
+        return Ok(vec![addr]);
+    }
+    let (host, port) = split_host_port(addr)?;
+    Ok(lookup_host(resolver, host).await?.port_iter(port).collect())
+}
+
+/// Splits a string of the form `host:port` into its two parts.
+pub(crate) fn split_host_port(
+    addr: &str,
+) -> Result<(&str, u16), LookupError> {
+    let mut parts = addr.rsplitn(2, ':');
+    let port = parts.next().and_then(|port| u16::from_str(port).ok());
+    match (parts.next(), port) {
+        (Some(host), Some(port))
+            if !host.is_empty() && !host.contains(':') =>
+        {
+            Ok((host, port))
+        }
+        _ => Err(LookupError::BadName),
+    }
 }
 
 //------------ FoundHosts ----------------------------------------------------
@@ -61,46 +378,130 @@ pub async fn search_host<R: Resolver + SearchNames>(
 /// which the addresses were found.
 #[derive(Debug)]
 pub struct FoundHosts<R: Resolver> {
-    /// The answer to the AAAA query.
-    aaaa: Result<R::Answer, io::Error>,
+    /// The answer to the AAAA query if one was received.
+    aaaa: Option<R::Answer>,
+
+    /// The reason why the AAAA query didn’t result in any addresses.
+    aaaa_err: Option<LookupError>,
+
+    /// The answer to the A query if one was received.
+    a: Option<R::Answer>,
+
+    /// The reason why the A query didn’t result in any addresses.
+    a_err: Option<LookupError>,
 
-    /// The answer to the A query.
-    a: Result<R::Answer, io::Error>,
+    /// The list for sorting the addresses.
+    sortlist: SortList,
+
+    /// The order of the addresses.
+    order: AddrOrder,
 }
 
 impl<R: Resolver> FoundHosts<R> {
+    /// Creates a new value from the results of the AAAA and A queries.
+    ///
+    /// Returns an error if neither result contains any addresses.
     pub fn new(
         aaaa: Result<R::Answer, io::Error>,
         a: Result<R::Answer, io::Error>,
-    ) -> Result<Self, io::Error> {
-        if aaaa.is_err() && a.is_err() {
-            match aaaa {
-                Err(err) => return Err(err),
-                _ => unreachable!(),
-            }
+    ) -> Result<Self, LookupError> {
+        let (aaaa, aaaa_err) = Self::check(aaaa, Rtype::Aaaa);
+        let (a, a_err) = Self::check(a, Rtype::A);
+        match (aaaa_err, a_err) {
+            (Some(aaaa_err), Some(a_err)) => Err(aaaa_err.merge(a_err)),
+            (aaaa_err, a_err) => Ok(FoundHosts {
+                aaaa,
+                aaaa_err,
+                a,
+                a_err,
+                sortlist: SortList::new(),
+                order: AddrOrder::default(),
+            }),
         }
-        Ok(FoundHosts { aaaa, a })
     }
 
-    pub fn is_empty(&self) -> bool {
-        if let Ok(ref aaaa) = self.aaaa {
-            if aaaa.as_ref().header_counts().ancount() > 0 {
-                return false;
-            }
-        }
-        if let Ok(ref a) = self.a {
-            if a.as_ref().header_counts().ancount() > 0 {
-                return false;
+    /// Splits the result of a query into the answer and an error.
+    ///
+    /// The error is present if the query didn’t result in any records of
+    /// type `rtype`.
+    fn check(
+        res: Result<R::Answer, io::Error>,
+        rtype: Rtype,
+    ) -> (Option<R::Answer>, Option<LookupError>) {
+        match res {
+            Ok(answer) => {
+                let err = LookupError::check(answer.as_ref(), rtype).err();
+                (Some(answer), err)
             }
+            Err(err) => (None, Some(err.into())),
         }
-        true
     }
 
-    /// Returns a reference to one of the answers.
+    /// Sets the list used to sort the addresses returned by `iter()`.
+    pub fn with_sortlist(mut self, sortlist: SortList) -> Self {
+        self.sortlist = sortlist;
+        self
+    }
+
+    /// Sets the order of the addresses returned by `iter()`.
+    pub fn with_order(mut self, order: AddrOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aaaa
+            .iter()
+            .chain(self.a.iter())
+            .all(|answer| answer.as_ref().header_counts().ancount() == 0)
+    }
+
+    /// Returns the answer to the AAAA query if there is one.
+    pub fn aaaa_answer(&self) -> Option<&R::Answer> {
+        self.aaaa.as_ref()
+    }
+
+    /// Returns the answer to the A query if there is one.
+    pub fn a_answer(&self) -> Option<&R::Answer> {
+        self.a.as_ref()
+    }
+
+    /// Returns why the AAAA query didn’t result in any addresses.
+    ///
+    /// Since the lookup succeeds as long as one of the two queries found
+    /// addresses, the failure of the other is not reported otherwise. The
+    /// error allows telling apart a host that simply has no IPv6 addresses
+    /// – in which case the error’s `is_negative()` is `true` – from a
+    /// failed query, for instance because of a SERVFAIL or a timeout.
+    ///
+    /// Returns `None` if the query did find addresses.
+    pub fn aaaa_error(&self) -> Option<&LookupError> {
+        self.aaaa_err.as_ref()
+    }
+
+    /// Returns why the A query didn’t result in any addresses.
+    ///
+    /// See `aaaa_error()` for details.
+    pub fn a_error(&self) -> Option<&LookupError> {
+        self.a_err.as_ref()
+    }
+
+    /// Returns whether one of the two queries has failed.
+    ///
+    /// A query that was answered but didn’t contain any addresses is not
+    /// considered to have failed.
+    pub fn is_partial(&self) -> bool {
+        self.aaaa_err
+            .iter()
+            .chain(self.a_err.iter())
+            .any(|err| !err.is_negative())
+    }
+
+    /// Returns a reference to one of the answers that contain addresses.
     fn answer(&self) -> &R::Answer {
-        match self.aaaa.as_ref() {
-            Ok(answer) => answer,
-            Err(_) => self.a.as_ref().unwrap(),
+        match (self.aaaa.as_ref(), self.aaaa_err.as_ref()) {
+            (Some(answer), None) => answer,
+            _ => self.a.as_ref().unwrap(),
         }
     }
 }
@@ -123,24 +524,93 @@ where
     }
 
     /// Returns an iterator over the IP addresses returned by the lookup.
+    ///
+    /// The addresses are returned in the order set via `with_order()`,
+    /// which defaults to the order of the answers. If a sort list has been
+    /// set via `with_sortlist()`, it is applied on top of that order.
     pub fn iter(&self) -> FoundHostsIter<&R::Octets> {
+        let mut res = self.unsorted_iter();
+        if !self.sortlist.is_empty() || self.order != AddrOrder::Answer {
+            let mut addrs = res.clone().host_addrs().collect::<Vec<_>>();
+            // IPv6 addresses come first, so we order each family on its own.
+            let split = addrs
+                .iter()
+                .position(|addr| addr.rtype() == Rtype::A)
+                .unwrap_or(addrs.len());
+            let (aaaa, a) = addrs.split_at_mut(split);
+            for family in [aaaa, a].iter_mut() {
+                match self.order {
+                    AddrOrder::Answer => {}
+                    AddrOrder::Sorted => family.sort_by_key(HostAddr::addr),
+                    AddrOrder::Shuffle => {
+                        family.shuffle(&mut rand::thread_rng())
+                    }
+                }
+            }
+            addrs.sort_by_key(|addr| self.sortlist.rank(addr.addr()));
+            res.sorted = Some(addrs.into_iter());
+        }
+        res
+    }
+
+    /// Returns an iterator over the addresses with their details.
+    ///
+    /// In addition to the address, the items provide the TTL of the
+    /// record the address was taken from and whether this was an A or
+    /// AAAA record.
+    pub fn host_addrs(&self) -> HostAddrIter<&R::Octets> {
+        self.iter().host_addrs()
+    }
+
+    /// Returns the smallest TTL of the records in the answers.
+    ///
+    /// This considers all records in the answer sections, including the
+    /// CNAME records leading to the canonical name. It thus is the time
+    /// for which the result of the lookup can be cached.
+    pub fn min_ttl(&self) -> Option<u32> {
+        self.aaaa
+            .iter()
+            .chain(self.a.iter())
+            .filter_map(|answer| answer.as_ref().min_ttl())
+            .min()
+    }
+
+    /// Returns an iterator over the addresses in the order of the answers.
+    fn unsorted_iter(&self) -> FoundHostsIter<&R::Octets> {
         FoundHostsIter {
             name: self.canonical_name(),
             aaaa: {
                 self.aaaa
                     .as_ref()
-                    .ok()
                     .and_then(|msg| msg.as_ref().answer().ok())
                     .map(|answer| answer.limit_to::<Aaaa>())
             },
             a: {
                 self.a
                     .as_ref()
-                    .ok()
                     .and_then(|msg| msg.as_ref().answer().ok())
                     .map(|answer| answer.limit_to::<A>())
             },
+            dns64: None,
+            sorted: None,
+        }
+    }
+
+    /// Returns an iterator over the IPv6 addresses for a DNS64 client.
+    ///
+    /// If the lookup found any AAAA records, the iterator returns their
+    /// addresses. Otherwise, it returns IPv6 addresses synthesized from
+    /// the addresses of the A records using `prefix` as described in
+    /// [RFC 6147]. AAAA records with IPv4-mapped addresses are ignored.
+    ///
+    /// [RFC 6147]: https://tools.ietf.org/html/rfc6147
+    pub fn iter_dns64(&self, prefix: Prefix64) -> FoundHostsIter<&R::Octets> {
+        let mut res = self.unsorted_iter();
+        res.dns64 = Some(prefix);
+        if res.clone().next().is_some() {
+            res.a = None
         }
+        res
     }
 
     /// Returns an iterator over socket addresses gained from the lookup.
@@ -164,23 +634,47 @@ pub struct FoundHostsIter<Ref: OctetsRef> {
     name: ParsedDname<Ref>,
     aaaa: Option<RecordIter<Ref, Aaaa>>,
     a: Option<RecordIter<Ref, A>>,
+    dns64: Option<Prefix64>,
+    sorted: Option<std::vec::IntoIter<HostAddr>>,
 }
 
-impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
-    type Item = IpAddr;
+impl<Ref: OctetsRef> FoundHostsIter<Ref> {
+    /// Converts the iterator into one over addresses with details.
+    pub fn host_addrs(self) -> HostAddrIter<Ref> {
+        HostAddrIter(self)
+    }
 
-    fn next(&mut self) -> Option<IpAddr> {
+    fn next_host_addr(&mut self) -> Option<HostAddr> {
+        if let Some(sorted) = self.sorted.as_mut() {
+            return sorted.next();
+        }
         while let Some(res) = self.aaaa.as_mut().and_then(Iterator::next) {
             if let Ok(record) = res {
                 if *record.owner() == self.name {
-                    return Some(record.data().addr().into());
+                    let addr = record.data().addr();
+                    if self.dns64.is_some() && is_ipv4_mapped(addr) {
+                        continue;
+                    }
+                    return Some(HostAddr {
+                        addr: addr.into(),
+                        ttl: record.ttl(),
+                        rtype: Rtype::Aaaa,
+                    });
                 }
             }
         }
         while let Some(res) = self.a.as_mut().and_then(Iterator::next) {
             if let Ok(record) = res {
                 if *record.owner() == self.name {
-                    return Some(record.data().addr().into());
+                    let addr = record.data().addr();
+                    return Some(HostAddr {
+                        addr: match self.dns64 {
+                            Some(prefix) => prefix.synthesize(addr).into(),
+                            None => addr.into(),
+                        },
+                        ttl: record.ttl(),
+                        rtype: Rtype::A,
+                    });
                 }
             }
         }
@@ -188,6 +682,69 @@ impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
     }
 }
 
+impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
+    type Item = IpAddr;
+
+    fn next(&mut self) -> Option<IpAddr> {
+        self.next_host_addr().map(|addr| addr.addr())
+    }
+}
+
+/// Returns whether an IPv6 address is an IPv4-mapped address.
+fn is_ipv4_mapped(addr: Ipv6Addr) -> bool {
+    matches!(addr.segments(), [0, 0, 0, 0, 0, 0xffff, _, _])
+}
+
+//------------ HostAddrIter --------------------------------------------------
+
+/// An iterator over the addresses of a host lookup with their details.
+#[derive(Clone, Debug)]
+pub struct HostAddrIter<Ref: OctetsRef>(FoundHostsIter<Ref>);
+
+impl<Ref: OctetsRef> Iterator for HostAddrIter<Ref> {
+    type Item = HostAddr;
+
+    fn next(&mut self) -> Option<HostAddr> {
+        self.0.next_host_addr()
+    }
+}
+
+//------------ HostAddr ------------------------------------------------------
+
+/// An address found by a host lookup.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct HostAddr {
+    /// The address.
+    addr: IpAddr,
+
+    /// The TTL of the record the address was taken from.
+    ttl: u32,
+
+    /// The type of the record the address was taken from.
+    rtype: Rtype,
+}
+
+impl HostAddr {
+    /// Returns the address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the TTL of the record the address was taken from.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// Returns the type of the record the address was taken from.
+    ///
+    /// This is either `Rtype::A` or `Rtype::Aaaa`. Note that for addresses
+    /// synthesized by `FoundHosts::iter_dns64`, this is `Rtype::A` even
+    /// though the address is an IPv6 address.
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+}
+
 //------------ FoundHostsSocketIter ------------------------------------------
 
 /// An iterator over socket addresses derived from a host lookup.
@@ -214,3 +771,484 @@ impl<Ref: OctetsRef> ToSocketAddrs for FoundHostsSocketIter<Ref> {
         Ok(self.clone())
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rcode};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::question::Question;
+    use crate::resolv::lookup::name::IntoRelativeDname;
+    use crate::utils::replay::{self, Corpus, Exchange};
+    use crate::utils::testing::run;
+    use std::string::{String, ToString};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A resolver answering from a corpus and counting its queries.
+    struct Search {
+        corpus: Corpus,
+        queries: AtomicUsize,
+        cache: SearchCache,
+        mode: SearchMode,
+        log: Mutex<Vec<String>>,
+    }
+
+    impl Search {
+        fn new(corpus: Corpus) -> Self {
+            Search {
+                corpus,
+                queries: AtomicUsize::new(0),
+                cache: SearchCache::new(),
+                mode: SearchMode::Glibc,
+                log: Mutex::new(Vec::new()),
+            }
+        }
+
+        /// Returns the names of the A queries sent since the last call.
+        fn take_log(&self) -> Vec<String> {
+            std::mem::take(&mut *self.log.lock().unwrap())
+        }
+    }
+
+    impl Resolver for Search {
+        type Octets = Vec<u8>;
+        type Answer = replay::Answer;
+        type Query = <Corpus as Resolver>::Query;
+
+        fn query<N, Q>(&self, question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            let question = question.into();
+            self.queries.fetch_add(1, Ordering::Relaxed);
+            if question.qtype() == Rtype::A {
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(question.qname().to_cow().to_string());
+            }
+            self.corpus.query(question)
+        }
+    }
+
+    impl SearchNames for Search {
+        type Name = Dname<Vec<u8>>;
+        type Iter = std::vec::IntoIter<Self::Name>;
+
+        fn search_iter(&self) -> Self::Iter {
+            vec![
+                Dname::from_str("a.example.").unwrap(),
+                Dname::from_str("b.example.").unwrap(),
+            ]
+            .into_iter()
+        }
+
+        fn search_cache(&self) -> Option<&SearchCache> {
+            Some(&self.cache)
+        }
+
+        fn search_mode(&self) -> SearchMode {
+            self.mode
+        }
+    }
+
+    fn exchange(name: &str) -> Exchange {
+        let name = Dname::<Vec<u8>>::from_str(name).unwrap();
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((&name, Rtype::A)).unwrap();
+        let query = query.into_message();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        answer
+            .push((&name, Class::In, 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        Exchange::new(query, Some(answer.into_message()))
+    }
+
+    fn nxdomain(name: &str) -> Vec<Exchange> {
+        let name = Dname::<Vec<u8>>::from_str(name).unwrap();
+        [Rtype::A, Rtype::Aaaa]
+            .iter()
+            .map(|rtype| {
+                let mut query = MessageBuilder::new_vec().question();
+                query.push((&name, *rtype)).unwrap();
+                let query = query.into_message();
+                let answer = MessageBuilder::new_vec()
+                    .start_answer(&query, Rcode::NXDomain)
+                    .unwrap();
+                Exchange::new(query, Some(answer.into_message()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn search_cache() {
+        let resolver = Search::new(
+            vec![exchange("host.b.example.")].into_iter().collect(),
+        );
+        let addrs =
+            |found: FoundHosts<Search>| found.iter().collect::<Vec<_>>();
+        let expected = vec![IpAddr::from([192, 0, 2, 1])];
+
+        // The first lookup walks the search list.
+        let found = run(search_host(&resolver, "host")).unwrap();
+        assert_eq!(addrs(found), expected);
+        assert_eq!(resolver.queries.swap(0, Ordering::Relaxed), 4);
+
+        // Later lookups go straight to the name found.
+        let found = run(search_host(&resolver, "HOST")).unwrap();
+        assert_eq!(addrs(found), expected);
+        assert_eq!(resolver.queries.swap(0, Ordering::Relaxed), 2);
+
+        // A stale entry is dropped and the search list walked again.
+        resolver.cache.insert(
+            &"host".into_relative_dname().unwrap(),
+            Dname::from_str("host.a.example.").unwrap(),
+            Duration::from_secs(300),
+        );
+        let found = run(search_host(&resolver, "host")).unwrap();
+        assert_eq!(addrs(found), expected);
+        assert_eq!(resolver.queries.swap(0, Ordering::Relaxed), 6);
+    }
+
+    #[test]
+    fn search_modes() {
+        let mut corpus: Corpus =
+            vec![exchange("host.b.example.")].into_iter().collect();
+        corpus.extend(nxdomain("host.a.example."));
+        let mut resolver = Search::new(corpus);
+        let search = |resolver: &Search, qname: &str| {
+            resolver.cache.clear();
+            let found = run(search_host(resolver, qname)).is_ok();
+            (found, resolver.take_log())
+        };
+
+        // glibc: enough dots means absolute first, trailing dot means
+        // absolute only, search list walked past failures.
+        assert_eq!(
+            search(&resolver, "host"),
+            (true, vec!["host.a.example".into(), "host.b.example".into()])
+        );
+        assert_eq!(
+            search(&resolver, "host.b.example"),
+            (true, vec!["host.b.example".into()])
+        );
+        assert_eq!(
+            search(&resolver, "host.b.example."),
+            (true, vec!["host.b.example".into()])
+        );
+        assert_eq!(
+            search(&resolver, "other"),
+            (
+                false,
+                vec![
+                    "other.a.example".into(),
+                    "other.b.example".into(),
+                    "other".into()
+                ]
+            )
+        );
+
+        // musl: enough dots means absolute only, search stops at the
+        // first failure that isn’t a negative answer.
+        resolver.mode = SearchMode::Musl;
+        assert_eq!(
+            search(&resolver, "host"),
+            (true, vec!["host.a.example".into(), "host.b.example".into()])
+        );
+        assert_eq!(
+            search(&resolver, "host.b"),
+            (false, vec!["host.b".into()])
+        );
+        assert_eq!(
+            search(&resolver, "other"),
+            (false, vec!["other.a.example".into()])
+        );
+
+        // Explicit orders ignore ndots.
+        resolver.mode = SearchMode::AbsoluteFirst;
+        assert_eq!(
+            search(&resolver, "host"),
+            (
+                true,
+                vec![
+                    "host".into(),
+                    "host.a.example".into(),
+                    "host.b.example".into()
+                ]
+            )
+        );
+        resolver.mode = SearchMode::SearchFirst;
+        assert_eq!(
+            search(&resolver, "host.b.example"),
+            (
+                true,
+                vec![
+                    "host.b.example.a.example".into(),
+                    "host.b.example.b.example".into(),
+                    "host.b.example".into()
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn addr_order() {
+        use crate::rdata::Aaaa;
+        use crate::resolv::stub::conf::SortListEntry;
+
+        let name = Dname::<Vec<u8>>::from_str("host.example.").unwrap();
+        let exchange = |rtype: Rtype, addrs: &[IpAddr]| {
+            let mut query = MessageBuilder::new_vec().question();
+            query.push((&name, rtype)).unwrap();
+            let query = query.into_message();
+            let mut answer = MessageBuilder::new_vec()
+                .start_answer(&query, Rcode::NoError)
+                .unwrap();
+            for addr in addrs {
+                match *addr {
+                    IpAddr::V4(addr) => {
+                        answer.push((&name, 300, A::new(addr))).unwrap()
+                    }
+                    IpAddr::V6(addr) => {
+                        answer.push((&name, 300, Aaaa::new(addr))).unwrap()
+                    }
+                }
+            }
+            Exchange::new(query, Some(answer.into_message()))
+        };
+        let addr = |s: &str| IpAddr::from_str(s).unwrap();
+        let resolver = Search::new(
+            vec![
+                exchange(
+                    Rtype::Aaaa,
+                    &[addr("2001:db8::2"), addr("2001:db8::1")],
+                ),
+                exchange(
+                    Rtype::A,
+                    &[
+                        addr("192.0.2.3"),
+                        addr("192.0.2.1"),
+                        addr("192.0.2.2"),
+                    ],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let lookup = |order| {
+            run(lookup_host(&resolver, &name))
+                .unwrap()
+                .with_order(order)
+                .iter()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lookup(AddrOrder::Answer),
+            [
+                addr("2001:db8::2"),
+                addr("2001:db8::1"),
+                addr("192.0.2.3"),
+                addr("192.0.2.1"),
+                addr("192.0.2.2"),
+            ]
+        );
+        let sorted = [
+            addr("2001:db8::1"),
+            addr("2001:db8::2"),
+            addr("192.0.2.1"),
+            addr("192.0.2.2"),
+            addr("192.0.2.3"),
+        ];
+        assert_eq!(lookup(AddrOrder::Sorted), sorted);
+        let mut shuffled = lookup(AddrOrder::Shuffle);
+        assert!(shuffled[..2].iter().all(IpAddr::is_ipv6));
+        shuffled[..2].sort();
+        shuffled[2..].sort();
+        assert_eq!(shuffled, sorted);
+
+        // The sort list is applied on top of the order.
+        let mut sortlist = SortList::new();
+        sortlist.push(
+            SortListEntry::from_str("192.0.2.2/255.255.255.255").unwrap(),
+        );
+        let found = run(lookup_host(&resolver, &name))
+            .unwrap()
+            .with_order(AddrOrder::Sorted)
+            .with_sortlist(sortlist);
+        assert_eq!(
+            found.iter().collect::<Vec<_>>(),
+            [
+                addr("192.0.2.2"),
+                addr("2001:db8::1"),
+                addr("2001:db8::2"),
+                addr("192.0.2.1"),
+                addr("192.0.2.3"),
+            ]
+        );
+    }
+
+    /// Creates an exchange for `rtype` answering with the given addresses.
+    ///
+    /// The addresses are given as pairs of address and TTL.
+    fn addrs_exchange(
+        name: &str,
+        rtype: Rtype,
+        rcode: Rcode,
+        addrs: &[(&str, u32)],
+    ) -> Exchange {
+        use crate::rdata::Aaaa;
+
+        let name = Dname::<Vec<u8>>::from_str(name).unwrap();
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((&name, rtype)).unwrap();
+        let query = query.into_message();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, rcode)
+            .unwrap();
+        for &(addr, ttl) in addrs {
+            match IpAddr::from_str(addr).unwrap() {
+                IpAddr::V4(addr) => {
+                    answer.push((&name, ttl, A::new(addr))).unwrap()
+                }
+                IpAddr::V6(addr) => {
+                    answer.push((&name, ttl, Aaaa::new(addr))).unwrap()
+                }
+            }
+        }
+        Exchange::new(query, Some(answer.into_message()))
+    }
+
+    #[test]
+    fn host_addrs() {
+        let resolver = Search::new(
+            vec![
+                addrs_exchange(
+                    "host.example.",
+                    Rtype::Aaaa,
+                    Rcode::NoError,
+                    &[("2001:db8::1", 120)],
+                ),
+                addrs_exchange(
+                    "host.example.",
+                    Rtype::A,
+                    Rcode::NoError,
+                    &[("192.0.2.1", 300), ("192.0.2.2", 60)],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let found = run(lookup_host(&resolver, "host.example.")).unwrap();
+
+        let qtype = |answer: Option<&replay::Answer>| {
+            answer.unwrap().as_ref().sole_question().unwrap().qtype()
+        };
+        assert_eq!(qtype(found.aaaa_answer()), Rtype::Aaaa);
+        assert_eq!(qtype(found.a_answer()), Rtype::A);
+
+        // The smallest TTL is taken from both answers.
+        assert_eq!(found.min_ttl(), Some(60));
+
+        let addrs = found
+            .host_addrs()
+            .map(|addr| (addr.addr(), addr.ttl(), addr.rtype()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addrs,
+            [
+                (IpAddr::from_str("2001:db8::1").unwrap(), 120, Rtype::Aaaa),
+                (IpAddr::from_str("192.0.2.1").unwrap(), 300, Rtype::A),
+                (IpAddr::from_str("192.0.2.2").unwrap(), 60, Rtype::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let a = addrs_exchange(
+            "host.example.",
+            Rtype::A,
+            Rcode::NoError,
+            &[("192.0.2.1", 300)],
+        );
+
+        // The AAAA query fails with SERVFAIL.
+        let resolver = Search::new(
+            vec![
+                addrs_exchange(
+                    "host.example.",
+                    Rtype::Aaaa,
+                    Rcode::ServFail,
+                    &[],
+                ),
+                a.clone(),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let found = run(lookup_host(&resolver, "host.example.")).unwrap();
+        assert!(found.is_partial());
+        assert!(matches!(found.aaaa_error(), Some(LookupError::ServFail(_))));
+        assert!(found.a_error().is_none());
+        assert_eq!(
+            found.iter().collect::<Vec<_>>(),
+            [IpAddr::from([192, 0, 2, 1])]
+        );
+
+        // There is no answer to the AAAA query at all.
+        let resolver = Search::new(vec![a.clone()].into_iter().collect());
+        let found = run(lookup_host(&resolver, "host.example.")).unwrap();
+        assert!(found.is_partial());
+        assert!(found.aaaa_answer().is_none());
+        assert!(matches!(
+            found.aaaa_error(),
+            Some(LookupError::Transport(_))
+        ));
+
+        // A host without IPv6 addresses is not a partial result.
+        let resolver = Search::new(
+            vec![
+                addrs_exchange(
+                    "host.example.",
+                    Rtype::Aaaa,
+                    Rcode::NoError,
+                    &[],
+                ),
+                a,
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let found = run(lookup_host(&resolver, "host.example.")).unwrap();
+        assert!(!found.is_partial());
+        assert!(found.aaaa_error().unwrap().is_no_data());
+    }
+
+    #[test]
+    fn split_host_port() {
+        assert_eq!(
+            super::split_host_port("example.com:443").unwrap(),
+            ("example.com", 443)
+        );
+        assert_eq!(
+            super::split_host_port("example.com.:53").unwrap(),
+            ("example.com.", 53)
+        );
+        for addr in &[
+            "example.com",
+            "example.com:",
+            "example.com:https",
+            "example.com:65536",
+            ":443",
+            "2001:db8::1:443",
+        ] {
+            assert!(super::split_host_port(addr).is_err(), "{}", addr);
+        }
+    }
+}
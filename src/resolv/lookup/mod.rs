@@ -4,9 +4,21 @@
 //! implement applications of the DNS.
 
 pub use self::addr::lookup_addr;
-pub use self::host::{lookup_host, search_host};
+pub use self::bulk::{resolve_bulk, BulkOptions};
+pub use self::dns64::{discover_prefix64, Prefix64};
+pub use self::error::LookupError;
+pub use self::host::{
+    lookup_host, resolve, search_host, SearchCache, SearchMode,
+};
+pub use self::name::{
+    IntoDname, IntoRelativeDname, IntoSearchDname, SearchDname,
+};
 pub use self::srv::lookup_srv;
 
 pub mod addr;
+pub mod bulk;
+pub mod dns64;
+pub mod error;
 pub mod host;
+pub mod name;
 pub mod srv;
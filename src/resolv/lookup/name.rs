@@ -0,0 +1,295 @@
+//! Converting values into domain names for lookups.
+//!
+//! The lookup functions accept anything that can be turned into a domain
+//! name via the traits [`IntoDname`] and [`IntoRelativeDname`]. Apart from
+//! the types implementing [`ToDname`] and [`ToRelativeDname`],
+//! respectively, these traits are implemented for strings which are
+//! parsed into names and, in case of [`IntoDname`], for IP addresses which
+//! are translated into their reverse lookup names.
+//!
+//! Host names given to a search lookup are converted via
+//! [`IntoSearchDname`]. Strings ending in a dot are taken as absolute names
+//! that are looked up as is, all other values as relative names that the
+//! search list is applied to.
+//!
+//! [`IntoDname`]: trait.IntoDname.html
+//! [`IntoRelativeDname`]: trait.IntoRelativeDname.html
+//! [`IntoSearchDname`]: trait.IntoSearchDname.html
+//! [`ToDname`]: ../../../base/name/trait.ToDname.html
+//! [`ToRelativeDname`]: ../../../base/name/trait.ToRelativeDname.html
+
+use crate::base::name::{
+    reverse_name, Dname, RelativeDname, ToDname, ToRelativeDname,
+    UncertainDname,
+};
+use crate::base::octets::Octets128;
+use crate::resolv::lookup::error::LookupError;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ IntoDname -----------------------------------------------------
+
+/// A type that can be converted into an absolute domain name.
+///
+/// The conversion happens before any query is sent. If the value does not
+/// describe a valid domain name, it fails with `LookupError::BadName`.
+///
+/// Strings are parsed as absolute names whether or not they end in a dot.
+/// IP addresses are converted into the name used for reverse lookups in
+/// `in-addr.arpa.` or `ip6.arpa.`, respectively.
+pub trait IntoDname {
+    /// The type of the domain name.
+    type Dname: ToDname;
+
+    /// Converts the value into a domain name.
+    fn into_dname(self) -> Result<Self::Dname, LookupError>;
+}
+
+impl<N: ToDname> IntoDname for N {
+    type Dname = Self;
+
+    fn into_dname(self) -> Result<Self, LookupError> {
+        Ok(self)
+    }
+}
+
+impl IntoDname for &str {
+    type Dname = Dname<Vec<u8>>;
+
+    fn into_dname(self) -> Result<Self::Dname, LookupError> {
+        Dname::from_str(self).map_err(|_| LookupError::BadName)
+    }
+}
+
+impl IntoDname for &String {
+    type Dname = Dname<Vec<u8>>;
+
+    fn into_dname(self) -> Result<Self::Dname, LookupError> {
+        self.as_str().into_dname()
+    }
+}
+
+impl IntoDname for String {
+    type Dname = Dname<Vec<u8>>;
+
+    fn into_dname(self) -> Result<Self::Dname, LookupError> {
+        self.as_str().into_dname()
+    }
+}
+
+impl IntoDname for IpAddr {
+    type Dname = Dname<Octets128>;
+
+    fn into_dname(self) -> Result<Self::Dname, LookupError> {
+        Ok(reverse_name(self).unwrap())
+    }
+}
+
+//------------ IntoRelativeDname ---------------------------------------------
+
+/// A type that can be converted into a relative domain name.
+///
+/// The conversion happens before any query is sent. If the value does not
+/// describe a valid relative domain name, it fails with
+/// `LookupError::BadName`. In particular, this is the case for strings
+/// ending in a dot since they describe an absolute name.
+pub trait IntoRelativeDname {
+    /// The type of the domain name.
+    type Dname: ToRelativeDname;
+
+    /// Converts the value into a relative domain name.
+    fn into_relative_dname(self) -> Result<Self::Dname, LookupError>;
+}
+
+impl<N: ToRelativeDname> IntoRelativeDname for N {
+    type Dname = Self;
+
+    fn into_relative_dname(self) -> Result<Self, LookupError> {
+        Ok(self)
+    }
+}
+
+impl IntoRelativeDname for &str {
+    type Dname = RelativeDname<Vec<u8>>;
+
+    fn into_relative_dname(self) -> Result<Self::Dname, LookupError> {
+        match UncertainDname::from_str(self) {
+            Ok(UncertainDname::Relative(name)) => Ok(name),
+            _ => Err(LookupError::BadName),
+        }
+    }
+}
+
+impl IntoRelativeDname for &String {
+    type Dname = RelativeDname<Vec<u8>>;
+
+    fn into_relative_dname(self) -> Result<Self::Dname, LookupError> {
+        self.as_str().into_relative_dname()
+    }
+}
+
+impl IntoRelativeDname for String {
+    type Dname = RelativeDname<Vec<u8>>;
+
+    fn into_relative_dname(self) -> Result<Self::Dname, LookupError> {
+        self.as_str().into_relative_dname()
+    }
+}
+
+//------------ IntoSearchDname -----------------------------------------------
+
+/// A type that can be converted into a host name for a search lookup.
+///
+/// The conversion happens before any query is sent. If the value does not
+/// describe a valid domain name, it fails with `LookupError::BadName`.
+///
+/// Values of types implementing [`ToRelativeDname`] are always relative.
+/// Strings are relative unless they end in a dot in which case they are
+/// absolute.
+///
+/// [`ToRelativeDname`]: ../../../base/name/trait.ToRelativeDname.html
+pub trait IntoSearchDname {
+    /// The type of a relative domain name.
+    type Dname: ToRelativeDname;
+
+    /// Converts the value into a relative or absolute domain name.
+    fn into_search_dname(
+        self,
+    ) -> Result<SearchDname<Self::Dname>, LookupError>;
+}
+
+impl<N: ToRelativeDname> IntoSearchDname for N {
+    type Dname = Self;
+
+    fn into_search_dname(self) -> Result<SearchDname<Self>, LookupError> {
+        Ok(SearchDname::Relative(self))
+    }
+}
+
+impl IntoSearchDname for &str {
+    type Dname = RelativeDname<Vec<u8>>;
+
+    fn into_search_dname(
+        self,
+    ) -> Result<SearchDname<Self::Dname>, LookupError> {
+        match UncertainDname::from_str(self) {
+            Ok(UncertainDname::Relative(name)) => {
+                Ok(SearchDname::Relative(name))
+            }
+            Ok(UncertainDname::Absolute(name)) => {
+                Ok(SearchDname::Absolute(name))
+            }
+            Err(_) => Err(LookupError::BadName),
+        }
+    }
+}
+
+impl IntoSearchDname for &String {
+    type Dname = RelativeDname<Vec<u8>>;
+
+    fn into_search_dname(
+        self,
+    ) -> Result<SearchDname<Self::Dname>, LookupError> {
+        self.as_str().into_search_dname()
+    }
+}
+
+impl IntoSearchDname for String {
+    type Dname = RelativeDname<Vec<u8>>;
+
+    fn into_search_dname(
+        self,
+    ) -> Result<SearchDname<Self::Dname>, LookupError> {
+        self.as_str().into_search_dname()
+    }
+}
+
+//------------ SearchDname ---------------------------------------------------
+
+/// A host name for a search lookup.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SearchDname<N> {
+    /// A relative name that the search list is applied to.
+    Relative(N),
+
+    /// An absolute name that is looked up as is.
+    Absolute(Dname<Vec<u8>>),
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn str_into_dname() {
+        assert_eq!(
+            "www.example.com".into_dname().unwrap(),
+            Dname::<Vec<u8>>::from_str("www.example.com.").unwrap()
+        );
+        assert_eq!(
+            "www.example.com.".to_string().into_dname().unwrap(),
+            Dname::<Vec<u8>>::from_str("www.example.com.").unwrap()
+        );
+        assert!(matches!(
+            "www..example.com".into_dname(),
+            Err(LookupError::BadName)
+        ));
+    }
+
+    #[test]
+    fn str_into_relative_dname() {
+        assert_eq!(
+            "www".into_relative_dname().unwrap(),
+            RelativeDname::from_octets(b"\x03www".to_vec()).unwrap()
+        );
+        assert!(matches!(
+            "www.example.com.".into_relative_dname(),
+            Err(LookupError::BadName)
+        ));
+    }
+
+    #[test]
+    fn str_into_search_dname() {
+        assert_eq!(
+            "www.example".into_search_dname().unwrap(),
+            SearchDname::Relative(
+                RelativeDname::from_octets(b"\x03www\x07example".to_vec())
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            "www.example.".into_search_dname().unwrap(),
+            SearchDname::Absolute(Dname::from_str("www.example.").unwrap())
+        );
+        assert!(matches!(
+            "www..example".into_search_dname(),
+            Err(LookupError::BadName)
+        ));
+    }
+
+    #[test]
+    fn addr_into_dname() {
+        assert_eq!(
+            IpAddr::from_str("192.0.2.1")
+                .unwrap()
+                .into_dname()
+                .unwrap()
+                .to_string(),
+            "1.2.0.192.in-addr.arpa"
+        );
+        assert_eq!(
+            IpAddr::from_str("2001:db8::9af")
+                .unwrap()
+                .into_dname()
+                .unwrap()
+                .to_string(),
+            "f.a.9.0.0.0.0.0.0.0.0.0.0.0.0.0.\
+             0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+}
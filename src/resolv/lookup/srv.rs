@@ -1,6 +1,8 @@
 //! Looking up SRV records.
 
+use super::error::LookupError;
 use super::host::lookup_host;
+use super::name::{IntoDname, IntoRelativeDname};
 use crate::base::iana::{Class, Rtype};
 use crate::base::message::Message;
 use crate::base::name::{Dname, ToDname, ToRelativeDname};
@@ -12,7 +14,7 @@ use futures::stream::{Stream, StreamExt};
 use rand::distributions::{Distribution, Uniform};
 use std::net::{IpAddr, SocketAddr};
 use std::vec::Vec;
-use std::{io, mem, ops};
+use std::{error, fmt, io, mem, ops};
 
 // Look up SRV record. Three outcomes:
 //
@@ -52,10 +54,12 @@ use std::{io, mem, ops};
 /// single SRV record with the root label as its target.
 pub async fn lookup_srv(
     resolver: &impl Resolver,
-    service: impl ToRelativeDname,
-    name: impl ToDname,
+    service: impl IntoRelativeDname,
+    name: impl IntoDname,
     fallback_port: u16,
 ) -> Result<Option<FoundSrvs>, SrvError> {
+    let service = service.into_relative_dname()?;
+    let name = name.into_dname()?;
     let full_name = match (&service).chain(&name) {
         Ok(name) => name,
         Err(_) => return Err(SrvError::LongName),
@@ -80,7 +84,7 @@ impl FoundSrvs {
     pub fn into_stream<R: Resolver>(
         self,
         resolver: &R,
-    ) -> impl Stream<Item = Result<ResolvedSrvItem, io::Error>> + '_
+    ) -> impl Stream<Item = Result<ResolvedSrvItem, LookupError>> + '_
     where
         R::Octets: OctetsRef,
     {
@@ -271,7 +275,7 @@ impl SrvItem {
     pub async fn resolve<R: Resolver>(
         self,
         resolver: &R,
-    ) -> Result<ResolvedSrvItem, io::Error>
+    ) -> Result<ResolvedSrvItem, LookupError>
     where
         for<'a> &'a R::Octets: OctetsRef,
     {
@@ -348,11 +352,17 @@ impl ops::Deref for ResolvedSrvItem {
 pub enum SrvError {
     LongName,
     MalformedAnswer,
-    Query(io::Error),
+    Query(LookupError),
 }
 
 impl From<io::Error> for SrvError {
     fn from(err: io::Error) -> SrvError {
+        SrvError::Query(err.into())
+    }
+}
+
+impl From<LookupError> for SrvError {
+    fn from(err: LookupError) -> SrvError {
         SrvError::Query(err)
     }
 }
@@ -362,3 +372,17 @@ impl From<ParseError> for SrvError {
         SrvError::MalformedAnswer
     }
 }
+
+//--- Display and Error
+
+impl fmt::Display for SrvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SrvError::LongName => f.write_str("name too long"),
+            SrvError::MalformedAnswer => f.write_str("malformed answer"),
+            SrvError::Query(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for SrvError {}
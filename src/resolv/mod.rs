@@ -19,6 +19,14 @@
 //!    See the [stub] module for more information on how to use the stub
 //!    resolver.
 //!
+//! *  [`Forwarder`] sends queries for names in specific zones to their own
+//!    sets of upstream resolvers and all other queries to a default
+//!    resolver. See the [forward] module for details.
+//!
+//! In addition, the [odoh] module provides sending queries via Oblivious
+//! DNS over HTTPS if the `ring` feature is enabled. Its transport doing
+//! the HTTP exchanges requires the `hyper` feature, too.
+//!
 //! The lookups implemented by the crate are generic over the particular
 //! resolver, so you can pick the resolver most suitable for your own
 //! application or even implement your own specialised resolver. All
@@ -26,15 +34,21 @@
 //! convenience, they are also available as methods on the [`Resolver`]
 //! trait.
 //!
+//! [forward]: forward/index.html
 //! [lookup]: lookup/index.html
+//! [odoh]: odoh/index.html
 //! [stub]: stub/index.html
 //! [`Resolver`]: resolver/trait.Resolver.html
 //! [`StubResolver`]: stub/struct.StubResolver.html
+//! [`Forwarder`]: forward/struct.Forwarder.html
 #![cfg(feature = "resolv")]
 
+pub use self::forward::Forwarder;
 pub use self::resolver::Resolver;
 pub use self::stub::StubResolver;
 
+pub mod forward;
 pub mod lookup;
+pub mod odoh;
 pub mod resolver;
 pub mod stub;
@@ -0,0 +1,1300 @@
+//! Oblivious DNS over HTTPS.
+//!
+//! [Oblivious DoH][RFC 9230] separates the knowledge of who is asking from
+//! the knowledge of what is being asked. The client encrypts its queries
+//! with [HPKE][RFC 9180] to the public key of a _target_ resolver and sends
+//! them via an _oblivious proxy._ The proxy sees the client’s address but
+//! not the query while the target sees the query but not the client.
+//!
+//! The [`OdohTransport`] sends queries this way. It fetches the key
+//! configurations of the target from the path [`CONFIGS_PATH`], keeps
+//! them for as long as the target allows them to be cached, and sends
+//! encrypted queries to the proxy. If the target rejects a query because
+//! it rotated its keys, the transport fetches the new configurations and
+//! tries again. The transport uses HTTP/2 via the [hyper] crate and
+//! requires the `hyper` feature. The TLS connections are opened by an
+//! [`HttpsConnector`] provided by the application.
+//!
+//! Applications that want to perform the HTTP exchanges themselves can
+//! use the message layer directly. The [`OdohClient`] keeps the key
+//! configuration of the target, encrypts queries, and provides a
+//! [`QueryContext`] for decrypting the matching response:
+//!
+//! * The target’s key configurations are fetched via GET from the path
+//!   [`CONFIGS_PATH`] on the target and handed to
+//!   [`OdohClient::set_configs`] together with their cache lifetime. When
+//!   [`OdohClient::needs_configs`] returns `true`, they need to be fetched
+//!   again.
+//! * Encrypted queries are sent via POST to the URI returned by
+//!   [`proxy_uri`] with the content type [`CONTENT_TYPE`]. If the target
+//!   rejects a query because it doesn’t know the key any more – signalled
+//!   by a 401 status –, the client should call
+//!   [`OdohClient::invalidate`] and fetch new configurations.
+//!
+//! Only the mandatory cipher suite is supported: DHKEM(X25519,
+//! HKDF-SHA256), HKDF-SHA256, and AES-128-GCM.
+//!
+//! [RFC 9230]: https://tools.ietf.org/html/rfc9230
+//! [RFC 9180]: https://tools.ietf.org/html/rfc9180
+//! [hyper]: https://docs.rs/hyper/
+//! [`HttpsConnector`]: trait.HttpsConnector.html
+//! [`OdohClient`]: struct.OdohClient.html
+//! [`OdohTransport`]: struct.OdohTransport.html
+//! [`OdohClient::set_configs`]: struct.OdohClient.html#method.set_configs
+//! [`OdohClient::needs_configs`]: struct.OdohClient.html#method.needs_configs
+//! [`OdohClient::invalidate`]: struct.OdohClient.html#method.invalidate
+//! [`QueryContext`]: struct.QueryContext.html
+//! [`CONFIGS_PATH`]: constant.CONFIGS_PATH.html
+//! [`CONTENT_TYPE`]: constant.CONTENT_TYPE.html
+//! [`proxy_uri`]: fn.proxy_uri.html
+#![cfg(feature = "ring")]
+
+use crate::base::message::Message;
+use bytes::Bytes;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM};
+use ring::agreement::{
+    agree_ephemeral, EphemeralPrivateKey, UnparsedPublicKey, X25519,
+};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use std::string::String;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{error, fmt};
+#[cfg(feature = "hyper")]
+use {
+    core::future::Future,
+    core::pin::Pin,
+    hyper::client::conn::Builder,
+    hyper::header::{HeaderMap, ACCEPT, CACHE_CONTROL, CONTENT_TYPE as CT},
+    hyper::{Body, Method, Request, StatusCode, Uri},
+    std::boxed::Box,
+    std::io,
+    std::sync::Mutex,
+    tokio::io::{AsyncRead, AsyncWrite},
+};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The media type of Oblivious DoH messages.
+pub const CONTENT_TYPE: &str = "application/oblivious-dns-message";
+
+/// The well-known path for fetching a target’s key configurations.
+pub const CONFIGS_PATH: &str = "/.well-known/odohconfigs";
+
+/// The version of the key configuration we support.
+const CONFIG_VERSION: u16 = 0x0001;
+
+/// The HPKE KEM identifier for DHKEM(X25519, HKDF-SHA256).
+const KEM_X25519_SHA256: u16 = 0x0020;
+
+/// The HPKE KDF identifier for HKDF-SHA256.
+const KDF_SHA256: u16 = 0x0001;
+
+/// The HPKE AEAD identifier for AES-128-GCM.
+const AEAD_AES128GCM: u16 = 0x0001;
+
+/// The length of an X25519 public key.
+const NPK: usize = 32;
+
+/// The output length of the hash function.
+const NH: usize = 32;
+
+/// The key length of the AEAD.
+const NK: usize = 16;
+
+/// The nonce length of the AEAD.
+const NN: usize = 12;
+
+/// The message type of a query.
+const QUERY_TYPE: u8 = 0x01;
+
+/// The message type of a response.
+const RESPONSE_TYPE: u8 = 0x02;
+
+/// The block size queries are padded to.
+const PADDING_BLOCK: usize = 128;
+
+/// The cache lifetime of key configurations without a `max-age`.
+#[cfg(feature = "hyper")]
+const DEFAULT_CONFIGS_TTL: Duration = Duration::from_secs(3600);
+
+//------------ OdohConfig ----------------------------------------------------
+
+/// A key configuration of an Oblivious DoH target.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OdohConfig {
+    /// The HPKE KEM identifier.
+    kem_id: u16,
+
+    /// The HPKE KDF identifier.
+    kdf_id: u16,
+
+    /// The HPKE AEAD identifier.
+    aead_id: u16,
+
+    /// The public key of the target.
+    public_key: Vec<u8>,
+
+    /// The key identifier derived from the configuration.
+    key_id: [u8; NH],
+}
+
+impl OdohConfig {
+    /// Creates a configuration for the given X25519 public key.
+    pub fn x25519(public_key: &[u8]) -> Result<Self, OdohError> {
+        if public_key.len() != NPK {
+            return Err(OdohError::InvalidConfig);
+        }
+        Ok(Self::from_parts(
+            KEM_X25519_SHA256,
+            KDF_SHA256,
+            AEAD_AES128GCM,
+            public_key.into(),
+        ))
+    }
+
+    fn from_parts(
+        kem_id: u16,
+        kdf_id: u16,
+        aead_id: u16,
+        public_key: Vec<u8>,
+    ) -> Self {
+        let mut res = OdohConfig {
+            kem_id,
+            kdf_id,
+            aead_id,
+            public_key,
+            key_id: [0; NH],
+        };
+        let prk = extract(b"", &[&res.contents()]);
+        expand(&prk, &[b"odoh key id"], &mut res.key_id);
+        res
+    }
+
+    /// Parses the key configurations served by a target.
+    ///
+    /// Returns all configurations in order of the target’s preference.
+    /// Configurations of unknown versions are skipped. Configurations with
+    /// an unsupported cipher suite are included but will be ignored by
+    /// [`OdohClient::set_configs`].
+    ///
+    /// [`OdohClient::set_configs`]: struct.OdohClient.html#method.set_configs
+    pub fn parse_list(data: &[u8]) -> Result<Vec<Self>, OdohError> {
+        let mut parser = Reader(data);
+        let mut list = Reader(parser.u16_prefixed()?);
+        if !parser.0.is_empty() || list.0.is_empty() {
+            return Err(OdohError::InvalidConfig);
+        }
+        let mut res = Vec::new();
+        while !list.0.is_empty() {
+            let version = list.u16()?;
+            let mut contents = Reader(list.u16_prefixed()?);
+            if version != CONFIG_VERSION {
+                continue;
+            }
+            let kem_id = contents.u16()?;
+            let kdf_id = contents.u16()?;
+            let aead_id = contents.u16()?;
+            let public_key = contents.u16_prefixed()?;
+            if public_key.is_empty() || !contents.0.is_empty() {
+                return Err(OdohError::InvalidConfig);
+            }
+            res.push(Self::from_parts(
+                kem_id,
+                kdf_id,
+                aead_id,
+                public_key.into(),
+            ));
+        }
+        Ok(res)
+    }
+
+    /// Returns whether the configuration’s cipher suite is supported.
+    pub fn is_supported(&self) -> bool {
+        self.kem_id == KEM_X25519_SHA256
+            && self.kdf_id == KDF_SHA256
+            && self.aead_id == AEAD_AES128GCM
+            && self.public_key.len() == NPK
+    }
+
+    /// Returns the public key of the target.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns the key identifier of the configuration.
+    pub fn key_id(&self) -> &[u8] {
+        &self.key_id
+    }
+
+    /// Returns the wire format of the configuration’s contents.
+    fn contents(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(8 + self.public_key.len());
+        res.extend_from_slice(&self.kem_id.to_be_bytes());
+        res.extend_from_slice(&self.kdf_id.to_be_bytes());
+        res.extend_from_slice(&self.aead_id.to_be_bytes());
+        push_u16_prefixed(&mut res, &self.public_key);
+        res
+    }
+}
+
+//------------ OdohClient ----------------------------------------------------
+
+/// The client side of Oblivious DoH for a single target.
+pub struct OdohClient {
+    /// The key configuration currently used.
+    config: Option<OdohConfig>,
+
+    /// When the configuration needs to be refreshed.
+    expires: Option<Instant>,
+
+    /// The random number generator for keys and padding.
+    rng: SystemRandom,
+}
+
+impl OdohClient {
+    /// Creates a new client without a key configuration.
+    pub fn new() -> Self {
+        OdohClient {
+            config: None,
+            expires: None,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Creates a new client using the given key configuration.
+    ///
+    /// The configuration is used until [`invalidate`] is called.
+    ///
+    /// [`invalidate`]: #method.invalidate
+    pub fn with_config(config: OdohConfig) -> Result<Self, OdohError> {
+        if !config.is_supported() {
+            return Err(OdohError::Unsupported);
+        }
+        let mut res = Self::new();
+        res.config = Some(config);
+        Ok(res)
+    }
+
+    /// Returns the key configuration currently used.
+    pub fn config(&self) -> Option<&OdohConfig> {
+        self.config.as_ref()
+    }
+
+    /// Updates the key configurations from the data served by the target.
+    ///
+    /// The first supported configuration is used until `ttl` has passed,
+    /// which should be the cache lifetime of the HTTP response. If there
+    /// is no supported configuration, the client is left unchanged.
+    pub fn set_configs(
+        &mut self,
+        data: &[u8],
+        ttl: Duration,
+    ) -> Result<(), OdohError> {
+        let config = OdohConfig::parse_list(data)?
+            .into_iter()
+            .find(OdohConfig::is_supported)
+            .ok_or(OdohError::Unsupported)?;
+        self.config = Some(config);
+        self.expires = Some(Instant::now() + ttl);
+        Ok(())
+    }
+
+    /// Returns whether the key configurations need to be fetched.
+    ///
+    /// This is the case if there is no configuration or if the current one
+    /// has expired.
+    pub fn needs_configs(&self) -> bool {
+        match (&self.config, self.expires) {
+            (None, _) => true,
+            (Some(_), Some(expires)) => expires <= Instant::now(),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Drops the current key configuration.
+    ///
+    /// This should be called if the target rejects a query because it
+    /// doesn’t know the key, typically after the target rotated its keys.
+    pub fn invalidate(&mut self) {
+        self.config = None;
+        self.expires = None;
+    }
+
+    /// Encrypts a query.
+    ///
+    /// The query is padded to a multiple of 128 octets before encryption.
+    /// Returns the message to send to the proxy and the context for
+    /// decrypting the response.
+    pub fn encrypt_query<Octs: AsRef<[u8]>>(
+        &self,
+        query: &Message<Octs>,
+    ) -> Result<(Vec<u8>, QueryContext), OdohError> {
+        let config = match self.config {
+            Some(ref config) => config,
+            None => return Err(OdohError::NoConfig),
+        };
+
+        // The plaintext with its padding.
+        let query = query.as_slice();
+        let unpadded = query.len() + 4;
+        let padding =
+            (PADDING_BLOCK - unpadded % PADDING_BLOCK) % PADDING_BLOCK;
+        let mut plaintext = Vec::with_capacity(unpadded + padding);
+        push_u16_prefixed(&mut plaintext, query);
+        push_u16_prefixed(&mut plaintext, &vec![0; padding]);
+
+        // HPKE encryption to the target’s key.
+        let private_key = EphemeralPrivateKey::generate(&X25519, &self.rng)
+            .map_err(|_| OdohError::Crypto)?;
+        let enc = private_key
+            .compute_public_key()
+            .map_err(|_| OdohError::Crypto)?;
+        let shared_secret = agree_ephemeral(
+            private_key,
+            &UnparsedPublicKey::new(&X25519, &config.public_key),
+            OdohError::Crypto,
+            |dh| Ok(kem_shared_secret(dh, enc.as_ref(), &config.public_key)),
+        )?;
+        let hpke = HpkeContext::new(&shared_secret, b"odoh query");
+        let mut aad = vec![QUERY_TYPE];
+        push_u16_prefixed(&mut aad, &config.key_id);
+        let mut encrypted = enc.as_ref().to_vec();
+        encrypted.extend_from_slice(&hpke.seal(&aad, &plaintext)?);
+
+        let mut message = vec![QUERY_TYPE];
+        push_u16_prefixed(&mut message, &config.key_id);
+        push_u16_prefixed(&mut message, &encrypted);
+
+        let mut secret = [0; NK];
+        hpke.export(b"odoh response", &mut secret);
+        Ok((message, QueryContext { secret, plaintext }))
+    }
+}
+
+impl Default for OdohClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for OdohClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OdohClient")
+            .field("config", &self.config)
+            .field("expires", &self.expires)
+            .finish()
+    }
+}
+
+//------------ QueryContext --------------------------------------------------
+
+/// The state necessary for decrypting the response to a query.
+pub struct QueryContext {
+    /// The secret exported from the query’s HPKE context.
+    secret: [u8; NK],
+
+    /// The padded query plaintext.
+    plaintext: Vec<u8>,
+}
+
+impl QueryContext {
+    /// Decrypts the response received from the proxy.
+    pub fn decrypt_response(
+        self,
+        data: &[u8],
+    ) -> Result<Message<Bytes>, OdohError> {
+        let mut reader = Reader(data);
+        if reader.u8()? != RESPONSE_TYPE {
+            return Err(OdohError::InvalidMessage);
+        }
+        let response_nonce = reader.u16_prefixed()?;
+        let encrypted = reader.u16_prefixed()?;
+        if !reader.0.is_empty() {
+            return Err(OdohError::InvalidMessage);
+        }
+        let (key, nonce) = self.response_key(response_nonce)?;
+        let mut aad = vec![RESPONSE_TYPE];
+        push_u16_prefixed(&mut aad, response_nonce);
+        let mut in_out = encrypted.to_vec();
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(aad), &mut in_out)
+            .map_err(|_| OdohError::Crypto)?;
+        let mut reader = Reader(plaintext);
+        let message = reader.u16_prefixed()?;
+        let padding = reader.u16_prefixed()?;
+        if !reader.0.is_empty() || padding.iter().any(|&octet| octet != 0) {
+            return Err(OdohError::InvalidMessage);
+        }
+        Message::from_octets(Bytes::copy_from_slice(message))
+            .map_err(|_| OdohError::InvalidMessage)
+    }
+
+    /// Derives the response key and nonce.
+    fn response_key(
+        &self,
+        response_nonce: &[u8],
+    ) -> Result<(LessSafeKey, Nonce), OdohError> {
+        let mut salt = self.plaintext.clone();
+        push_u16_prefixed(&mut salt, response_nonce);
+        let prk = extract(&salt, &[&self.secret]);
+        let mut key = [0; NK];
+        expand(&prk, &[b"odoh key"], &mut key);
+        let mut nonce = [0; NN];
+        expand(&prk, &[b"odoh nonce"], &mut nonce);
+        Ok((aead_key(&key)?, Nonce::assume_unique_for_key(nonce)))
+    }
+}
+
+impl fmt::Debug for QueryContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("QueryContext")
+    }
+}
+
+//------------ HttpsConnector ------------------------------------------------
+
+/// A type that opens HTTPS connections for the [`OdohTransport`].
+///
+/// [`OdohTransport`]: struct.OdohTransport.html
+#[cfg(feature = "hyper")]
+pub trait HttpsConnector {
+    /// The type of the established TLS stream.
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Opens a TLS connection to `port` on `host`.
+    ///
+    /// The connector should verify the server’s certificate for `host`
+    /// and offer `h2` as application protocol via ALPN.
+    fn connect(&self, host: &str, port: u16) -> ConnectFuture<Self::Stream>;
+}
+
+/// The future returned by [`HttpsConnector::connect`].
+///
+/// [`HttpsConnector::connect`]: trait.HttpsConnector.html#tymethod.connect
+#[cfg(feature = "hyper")]
+pub type ConnectFuture<S> =
+    Pin<Box<dyn Future<Output = Result<S, io::Error>> + Send>>;
+
+//------------ OdohTransport -------------------------------------------------
+
+/// Sending queries via Oblivious DoH.
+///
+/// The transport sends queries to a single target via a single proxy. A
+/// new HTTP/2 connection is opened for each exchange.
+#[cfg(feature = "hyper")]
+pub struct OdohTransport<C> {
+    /// The connector for opening HTTPS connections.
+    connector: C,
+
+    /// The URI for sending queries to the target via the proxy.
+    proxy: Uri,
+
+    /// The URI for fetching the target’s key configurations.
+    configs: Uri,
+
+    /// The client keeping the key configuration.
+    client: Mutex<OdohClient>,
+}
+
+#[cfg(feature = "hyper")]
+impl<C: HttpsConnector> OdohTransport<C> {
+    /// Creates a new transport.
+    ///
+    /// The `proxy` is the HTTPS URI of the oblivious proxy’s endpoint and
+    /// `target` the HTTPS URI of the target’s endpoint, e.g.,
+    /// `https://target.example/dns-query`.
+    pub fn new(
+        connector: C,
+        proxy: &str,
+        target: &str,
+    ) -> Result<Self, OdohError> {
+        let target = parse_https_uri(target)?;
+        let host = target.host().ok_or(OdohError::InvalidUri)?;
+        let authority = match target.port_u16() {
+            Some(port) => std::format!("{}:{}", host, port),
+            None => host.into(),
+        };
+        parse_https_uri(proxy)?;
+        let proxy =
+            parse_https_uri(&proxy_uri(proxy, &authority, target.path()))?;
+        let configs = parse_https_uri(&std::format!(
+            "https://{}{}",
+            authority,
+            CONFIGS_PATH
+        ))?;
+        Ok(OdohTransport {
+            connector,
+            proxy,
+            configs,
+            client: Mutex::new(OdohClient::new()),
+        })
+    }
+
+    /// Returns the key configuration currently used.
+    pub fn config(&self) -> Option<OdohConfig> {
+        self.client.lock().unwrap().config().cloned()
+    }
+
+    /// Sends a query and returns the response.
+    ///
+    /// The target’s key configurations are fetched first if necessary. If
+    /// the target rejects the query with status 401, the configurations
+    /// are fetched again and the query is retried once.
+    pub async fn query<Octs: AsRef<[u8]>>(
+        &self,
+        query: &Message<Octs>,
+    ) -> Result<Message<Bytes>, OdohError> {
+        let mut retried = false;
+        loop {
+            if self.client.lock().unwrap().needs_configs() {
+                self.fetch_configs().await?;
+            }
+            let (message, context) =
+                self.client.lock().unwrap().encrypt_query(query)?;
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(self.proxy.clone())
+                .header(CT, CONTENT_TYPE)
+                .header(ACCEPT, CONTENT_TYPE)
+                .body(Body::from(message))
+                .map_err(|_| OdohError::InvalidUri)?;
+            let (status, _, body) = self.exchange(request).await?;
+            match status {
+                StatusCode::OK => return context.decrypt_response(&body),
+                StatusCode::UNAUTHORIZED if !retried => {
+                    self.client.lock().unwrap().invalidate();
+                    retried = true;
+                }
+                status => return Err(OdohError::Status(status.as_u16())),
+            }
+        }
+    }
+
+    /// Fetches the target’s key configurations.
+    ///
+    /// The configurations are used for as long as the response allows
+    /// them to be cached, or an hour if it doesn’t say.
+    pub async fn fetch_configs(&self) -> Result<(), OdohError> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(self.configs.clone())
+            .body(Body::empty())
+            .map_err(|_| OdohError::InvalidUri)?;
+        let (status, headers, body) = self.exchange(request).await?;
+        if status != StatusCode::OK {
+            return Err(OdohError::Status(status.as_u16()));
+        }
+        let ttl = max_age(&headers).unwrap_or(DEFAULT_CONFIGS_TTL);
+        self.client.lock().unwrap().set_configs(&body, ttl)
+    }
+
+    /// Performs a single HTTP exchange on a new connection.
+    async fn exchange(
+        &self,
+        request: Request<Body>,
+    ) -> Result<(StatusCode, HeaderMap, Bytes), OdohError> {
+        let host = request.uri().host().ok_or(OdohError::InvalidUri)?;
+        let port = request.uri().port_u16().unwrap_or(443);
+        let stream = self
+            .connector
+            .connect(host, port)
+            .await
+            .map_err(|_| OdohError::Http)?;
+        let (mut send, conn) = Builder::new()
+            .http2_only(true)
+            .handshake::<_, Body>(stream)
+            .await
+            .map_err(|_| OdohError::Http)?;
+        tokio::spawn(conn);
+        let response = send
+            .send_request(request)
+            .await
+            .map_err(|_| OdohError::Http)?;
+        let (head, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .map_err(|_| OdohError::Http)?;
+        Ok((head.status, head.headers, body))
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl<C> fmt::Debug for OdohTransport<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OdohTransport")
+            .field("proxy", &self.proxy)
+            .field("configs", &self.configs)
+            .finish()
+    }
+}
+
+/// Parses an HTTPS URI.
+#[cfg(feature = "hyper")]
+fn parse_https_uri(s: &str) -> Result<Uri, OdohError> {
+    let uri: Uri = s.parse().map_err(|_| OdohError::InvalidUri)?;
+    if uri.scheme_str() != Some("https") || uri.host().is_none() {
+        return Err(OdohError::InvalidUri);
+    }
+    Ok(uri)
+}
+
+/// Returns the `max-age` directive of the `Cache-Control` headers.
+#[cfg(feature = "hyper")]
+fn max_age(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|directive| {
+            directive.trim().strip_prefix("max-age=")?.parse().ok()
+        })
+        .map(Duration::from_secs)
+}
+
+//------------ proxy_uri -----------------------------------------------------
+
+/// Returns the URI for sending a query to `target` via `proxy`.
+///
+/// The `proxy` is the URI of the oblivious proxy’s endpoint, the target is
+/// given through its host name and the path of its endpoint.
+pub fn proxy_uri(
+    proxy: &str,
+    target_host: &str,
+    target_path: &str,
+) -> String {
+    let mut res = String::from(proxy);
+    res.push(if proxy.contains('?') { '&' } else { '?' });
+    res.push_str("targethost=");
+    percent_encode(target_host, &mut res);
+    res.push_str("&targetpath=");
+    percent_encode(target_path, &mut res);
+    res
+}
+
+fn percent_encode(s: &str, target: &mut String) {
+    for &ch in s.as_bytes() {
+        if ch.is_ascii_alphanumeric() || b"-._~".contains(&ch) {
+            target.push(ch as char)
+        } else {
+            target.push_str(&std::format!("%{:02X}", ch))
+        }
+    }
+}
+
+//------------ HPKE ----------------------------------------------------------
+
+/// The suite ID of the KEM.
+const KEM_SUITE_ID: &[u8] = b"KEM\x00\x20";
+
+/// The suite ID of the HPKE cipher suite.
+const HPKE_SUITE_ID: &[u8] = b"HPKE\x00\x20\x00\x01\x00\x01";
+
+/// Derives the KEM shared secret from the Diffie-Hellman output.
+fn kem_shared_secret(dh: &[u8], enc: &[u8], public_key: &[u8]) -> [u8; NH] {
+    let eae_prk = labeled_extract(KEM_SUITE_ID, b"", b"eae_prk", dh);
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(public_key);
+    let mut res = [0; NH];
+    labeled_expand(
+        KEM_SUITE_ID,
+        &eae_prk,
+        b"shared_secret",
+        &kem_context,
+        &mut res,
+    );
+    res
+}
+
+/// An HPKE context in base mode for a single message.
+struct HpkeContext {
+    key: [u8; NK],
+    base_nonce: [u8; NN],
+    exporter_secret: [u8; NH],
+}
+
+impl HpkeContext {
+    /// Runs the key schedule of RFC 9180, section 5.1, in base mode.
+    fn new(shared_secret: &[u8], info: &[u8]) -> Self {
+        let psk_id_hash =
+            labeled_extract(HPKE_SUITE_ID, b"", b"psk_id_hash", b"");
+        let info_hash =
+            labeled_extract(HPKE_SUITE_ID, b"", b"info_hash", info);
+        let mut context = vec![0];
+        context.extend_from_slice(&psk_id_hash);
+        context.extend_from_slice(&info_hash);
+        let secret =
+            labeled_extract(HPKE_SUITE_ID, shared_secret, b"secret", b"");
+        let mut res = HpkeContext {
+            key: [0; NK],
+            base_nonce: [0; NN],
+            exporter_secret: [0; NH],
+        };
+        labeled_expand(
+            HPKE_SUITE_ID,
+            &secret,
+            b"key",
+            &context,
+            &mut res.key,
+        );
+        labeled_expand(
+            HPKE_SUITE_ID,
+            &secret,
+            b"base_nonce",
+            &context,
+            &mut res.base_nonce,
+        );
+        labeled_expand(
+            HPKE_SUITE_ID,
+            &secret,
+            b"exp",
+            &context,
+            &mut res.exporter_secret,
+        );
+        res
+    }
+
+    /// Encrypts the first and only message of the context.
+    fn seal(
+        &self,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, OdohError> {
+        let mut in_out = plaintext.to_vec();
+        aead_key(&self.key)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(self.base_nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| OdohError::Crypto)?;
+        Ok(in_out)
+    }
+
+    /// Exports a secret from the context.
+    fn export(&self, context: &[u8], target: &mut [u8]) {
+        labeled_expand(
+            HPKE_SUITE_ID,
+            &self.exporter_secret,
+            b"sec",
+            context,
+            target,
+        )
+    }
+}
+
+fn aead_key(key: &[u8]) -> Result<LessSafeKey, OdohError> {
+    UnboundKey::new(&AES_128_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| OdohError::Crypto)
+}
+
+fn labeled_extract(
+    suite_id: &[u8],
+    salt: &[u8],
+    label: &[u8],
+    ikm: &[u8],
+) -> [u8; NH] {
+    extract(salt, &[b"HPKE-v1", suite_id, label, ikm])
+}
+
+fn labeled_expand(
+    suite_id: &[u8],
+    prk: &[u8],
+    label: &[u8],
+    info: &[u8],
+    target: &mut [u8],
+) {
+    let len = (target.len() as u16).to_be_bytes();
+    expand(prk, &[&len, b"HPKE-v1", suite_id, label, info], target)
+}
+
+//------------ HKDF ----------------------------------------------------------
+
+/// HKDF-Extract with SHA-256 over the concatenation of `ikm`.
+fn extract(salt: &[u8], ikm: &[&[u8]]) -> [u8; NH] {
+    let mut ctx =
+        hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA256, salt));
+    for part in ikm {
+        ctx.update(part);
+    }
+    let mut res = [0; NH];
+    res.copy_from_slice(ctx.sign().as_ref());
+    res
+}
+
+/// HKDF-Expand with SHA-256 over the concatenation of `info`.
+///
+/// Fills all of `target` which must not be longer than 255 times the hash
+/// length.
+fn expand(prk: &[u8], info: &[&[u8]], target: &mut [u8]) {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, prk);
+    let mut prev: Option<hmac::Tag> = None;
+    for (i, chunk) in target.chunks_mut(NH).enumerate() {
+        let mut ctx = hmac::Context::with_key(&key);
+        if let Some(prev) = prev {
+            ctx.update(prev.as_ref());
+        }
+        for part in info {
+            ctx.update(part);
+        }
+        ctx.update(&[i as u8 + 1]);
+        let tag = ctx.sign();
+        chunk.copy_from_slice(&tag.as_ref()[..chunk.len()]);
+        prev = Some(tag);
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+fn push_u16_prefixed(target: &mut Vec<u8>, data: &[u8]) {
+    target.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    target.extend_from_slice(data);
+}
+
+/// A minimal reader for the length-prefixed structures of RFC 9230.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], OdohError> {
+        if self.0.len() < len {
+            return Err(OdohError::InvalidMessage);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, OdohError> {
+        self.take(1).map(|data| data[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, OdohError> {
+        self.take(2)
+            .map(|data| u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    fn u16_prefixed(&mut self) -> Result<&'a [u8], OdohError> {
+        let len = self.u16()?;
+        self.take(usize::from(len))
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ OdohError -----------------------------------------------------
+
+/// An error happened while processing Oblivious DoH messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OdohError {
+    /// The key configuration is malformed.
+    InvalidConfig,
+
+    /// None of the target’s key configurations is supported.
+    Unsupported,
+
+    /// There is no key configuration for the target.
+    NoConfig,
+
+    /// A message is malformed.
+    InvalidMessage,
+
+    /// Encryption or decryption failed.
+    Crypto,
+
+    /// A URI is not a valid HTTPS URI.
+    InvalidUri,
+
+    /// The HTTP exchange failed.
+    Http,
+
+    /// The server answered with an unexpected HTTP status.
+    Status(u16),
+}
+
+//--- Display and Error
+
+impl fmt::Display for OdohError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            OdohError::InvalidConfig => "invalid key configuration",
+            OdohError::Unsupported => "unsupported key configuration",
+            OdohError::NoConfig => "no key configuration",
+            OdohError::InvalidMessage => "invalid message",
+            OdohError::Crypto => "encryption failed",
+            OdohError::InvalidUri => "invalid URI",
+            OdohError::Http => "HTTP exchange failed",
+            OdohError::Status(status) => {
+                return write!(f, "unexpected HTTP status {}", status)
+            }
+        })
+    }
+}
+
+impl error::Error for OdohError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use std::str::FromStr;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn query() -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com.").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.into_message()
+    }
+
+    /// Decrypts a query and encrypts `response` as a target would.
+    fn target(
+        private_key: EphemeralPrivateKey,
+        config: &OdohConfig,
+        message: &[u8],
+        response: &[u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut reader = Reader(message);
+        assert_eq!(reader.u8().unwrap(), QUERY_TYPE);
+        assert_eq!(reader.u16_prefixed().unwrap(), config.key_id());
+        let encrypted = reader.u16_prefixed().unwrap();
+        let (enc, ciphertext) = encrypted.split_at(NPK);
+        let shared_secret = agree_ephemeral(
+            private_key,
+            &UnparsedPublicKey::new(&X25519, enc),
+            (),
+            |dh| Ok(kem_shared_secret(dh, enc, config.public_key())),
+        )
+        .unwrap();
+        let hpke = HpkeContext::new(&shared_secret, b"odoh query");
+        let mut aad = vec![QUERY_TYPE];
+        push_u16_prefixed(&mut aad, config.key_id());
+        let mut plaintext = ciphertext.to_vec();
+        let len = aead_key(&hpke.key)
+            .unwrap()
+            .open_in_place(
+                Nonce::assume_unique_for_key(hpke.base_nonce),
+                Aad::from(aad),
+                &mut plaintext,
+            )
+            .unwrap()
+            .len();
+        plaintext.truncate(len);
+
+        let mut secret = [0; NK];
+        hpke.export(b"odoh response", &mut secret);
+        let context = QueryContext {
+            secret,
+            plaintext: plaintext.clone(),
+        };
+        let response_nonce = [0x42; 16];
+        let (key, nonce) = context.response_key(&response_nonce).unwrap();
+        let mut in_out = Vec::new();
+        push_u16_prefixed(&mut in_out, response);
+        push_u16_prefixed(&mut in_out, &[0; 7]);
+        let mut aad = vec![RESPONSE_TYPE];
+        push_u16_prefixed(&mut aad, &response_nonce);
+        key.seal_in_place_append_tag(nonce, Aad::from(aad), &mut in_out)
+            .unwrap();
+        let mut res = vec![RESPONSE_TYPE];
+        push_u16_prefixed(&mut res, &response_nonce);
+        push_u16_prefixed(&mut res, &in_out);
+        (plaintext, res)
+    }
+
+    #[test]
+    fn round_trip() {
+        let rng = SystemRandom::new();
+        let private_key =
+            EphemeralPrivateKey::generate(&X25519, &rng).unwrap();
+        let public_key = private_key.compute_public_key().unwrap();
+
+        let mut configs = vec![0, 0];
+        configs.extend_from_slice(&[0x00, 0x02, 0x00, 0x02, 0xFF, 0xFF]);
+        configs.extend_from_slice(&CONFIG_VERSION.to_be_bytes());
+        let mut contents = vec![0x00, 0x20, 0x00, 0x01, 0x00, 0x01];
+        push_u16_prefixed(&mut contents, public_key.as_ref());
+        push_u16_prefixed(&mut configs, &contents);
+        let len = (configs.len() - 2) as u16;
+        configs[..2].copy_from_slice(&len.to_be_bytes());
+
+        let mut client = OdohClient::new();
+        assert!(client.needs_configs());
+        client
+            .set_configs(&configs, Duration::from_secs(3600))
+            .unwrap();
+        assert!(!client.needs_configs());
+        let config = client.config().unwrap().clone();
+        assert_eq!(config.public_key(), public_key.as_ref());
+
+        let query = query();
+        let (message, context) = client.encrypt_query(&query).unwrap();
+        let mut response = query.as_slice().to_vec();
+        response[2] |= 0x80;
+        let (plaintext, response_message) =
+            target(private_key, &config, &message, &response);
+        assert_eq!(plaintext.len() % PADDING_BLOCK, 0);
+        assert_eq!(
+            &plaintext[2..2 + query.as_slice().len()],
+            query.as_slice()
+        );
+
+        let answer = context.decrypt_response(&response_message).unwrap();
+        assert_eq!(answer.as_slice(), response.as_slice());
+
+        client.invalidate();
+        assert!(client.needs_configs());
+        assert_eq!(
+            client.encrypt_query(&query).unwrap_err(),
+            OdohError::NoConfig
+        );
+    }
+
+    #[test]
+    fn tampered_response() {
+        let rng = SystemRandom::new();
+        let private_key =
+            EphemeralPrivateKey::generate(&X25519, &rng).unwrap();
+        let config = OdohConfig::x25519(
+            private_key.compute_public_key().unwrap().as_ref(),
+        )
+        .unwrap();
+        let client = OdohClient::with_config(config.clone()).unwrap();
+        let query = query();
+        let (message, context) = client.encrypt_query(&query).unwrap();
+        let (_, mut response) =
+            target(private_key, &config, &message, query.as_slice());
+        let last = response.len() - 1;
+        response[last] ^= 1;
+        assert!(matches!(
+            context.decrypt_response(&response),
+            Err(OdohError::Crypto)
+        ));
+    }
+
+    #[test]
+    fn key_schedule() {
+        // RFC 9180, appendix A.1.1.
+        let hpke = HpkeContext::new(
+            &hex("fe0e18c9f024ce43799ae393c7e8fe8f\
+                  ce9d218875e8227b0187c04e7d2ea1fc"),
+            &hex("4f6465206f6e2061204772656369616e2055726e"),
+        );
+        assert_eq!(
+            hpke.key.as_ref(),
+            hex("4531685d41d65f03dc48f6b8302c05b0")
+        );
+        assert_eq!(hpke.base_nonce.as_ref(), hex("56d890e5accaaf011cff4b7d"));
+        assert_eq!(
+            hpke.exporter_secret.as_ref(),
+            hex("45ff1c2e220db587171952c0592d5f5e\
+                 be103f1561a2614e38f2ffd47e99e3f8")
+        );
+    }
+
+    /// The target and proxy answering over in-memory connections.
+    ///
+    /// The target creates a new key whenever its configurations are
+    /// fetched. Each key is only good for a single query.
+    #[cfg(feature = "hyper")]
+    #[derive(Clone, Default)]
+    struct Server(std::sync::Arc<Mutex<ServerState>>);
+
+    #[cfg(feature = "hyper")]
+    #[derive(Default)]
+    struct ServerState {
+        key: Option<(EphemeralPrivateKey, OdohConfig)>,
+        fetches: usize,
+        hosts: Vec<String>,
+    }
+
+    #[cfg(feature = "hyper")]
+    impl Server {
+        fn respond(
+            &self,
+            request: &Request<()>,
+            body: &[u8],
+        ) -> hyper::Response<Body> {
+            let mut state = self.0.lock().unwrap();
+            let response = hyper::Response::builder();
+            if request.method() == Method::GET {
+                assert_eq!(request.uri().path(), CONFIGS_PATH);
+                state.fetches += 1;
+                let rng = SystemRandom::new();
+                let key =
+                    EphemeralPrivateKey::generate(&X25519, &rng).unwrap();
+                let config = OdohConfig::x25519(
+                    key.compute_public_key().unwrap().as_ref(),
+                )
+                .unwrap();
+                let mut entry = CONFIG_VERSION.to_be_bytes().to_vec();
+                push_u16_prefixed(&mut entry, &config.contents());
+                let mut configs = Vec::new();
+                push_u16_prefixed(&mut configs, &entry);
+                state.key = Some((key, config));
+                return response
+                    .header(CACHE_CONTROL, "public, max-age=600")
+                    .body(configs.into())
+                    .unwrap();
+            }
+            assert_eq!(request.method(), Method::POST);
+            assert_eq!(
+                request.uri().path_and_query().unwrap().as_str(),
+                "/proxy?targethost=target.example&targetpath=%2Fdns-query"
+            );
+            assert_eq!(request.headers()[CT], CONTENT_TYPE);
+            let (key, config) = match state.key.take() {
+                Some(key) => key,
+                None => {
+                    return response
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::empty())
+                        .unwrap()
+                }
+            };
+            let mut answer = query().into_octets();
+            answer[2] |= 0x80;
+            let (_, body) = target(key, &config, body, &answer);
+            response.body(body.into()).unwrap()
+        }
+    }
+
+    #[cfg(feature = "hyper")]
+    impl HttpsConnector for Server {
+        type Stream = tokio::io::DuplexStream;
+
+        fn connect(
+            &self,
+            host: &str,
+            port: u16,
+        ) -> ConnectFuture<Self::Stream> {
+            use hyper::server::conn::Http;
+            use hyper::service::service_fn;
+
+            self.0
+                .lock()
+                .unwrap()
+                .hosts
+                .push(std::format!("{}:{}", host, port));
+            let (client, server) = tokio::io::duplex(4096);
+            let this = self.clone();
+            tokio::spawn(async move {
+                let service =
+                    service_fn(move |request: Request<Body>| {
+                        let this = this.clone();
+                        async move {
+                            let (head, body) = request.into_parts();
+                            let body = hyper::body::to_bytes(body).await?;
+                            Ok::<_, hyper::Error>(this.respond(
+                                &Request::from_parts(head, ()),
+                                &body,
+                            ))
+                        }
+                    });
+                Http::new()
+                    .http2_only(true)
+                    .serve_connection(server, service)
+                    .await
+            });
+            Box::pin(async move { Ok(client) })
+        }
+    }
+
+    #[cfg(feature = "hyper")]
+    #[test]
+    fn transport() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let server = Server::default();
+                let transport = OdohTransport::new(
+                    server.clone(),
+                    "https://proxy.example/proxy",
+                    "https://target.example/dns-query",
+                )
+                .unwrap();
+
+                let response = transport.query(&query()).await.unwrap();
+                assert!(response.header().qr());
+                assert_eq!(response.header().id(), query().header().id());
+                let config = transport.config().unwrap();
+
+                // The target rotated its key, so the query is rejected and
+                // repeated with the new configuration.
+                transport.query(&query()).await.unwrap();
+                assert_ne!(transport.config().unwrap(), config);
+
+                let state = server.0.lock().unwrap();
+                assert_eq!(state.fetches, 2);
+                assert_eq!(
+                    state.hosts,
+                    [
+                        "target.example:443",
+                        "proxy.example:443",
+                        "proxy.example:443",
+                        "target.example:443",
+                        "proxy.example:443",
+                    ]
+                );
+            })
+    }
+
+    #[cfg(feature = "hyper")]
+    #[test]
+    fn transport_uris() {
+        assert_eq!(
+            OdohTransport::new(
+                Server::default(),
+                "http://proxy.example/proxy",
+                "https://target.example/dns-query",
+            )
+            .unwrap_err(),
+            OdohError::InvalidUri
+        );
+        let transport = OdohTransport::new(
+            Server::default(),
+            "https://proxy.example:8443/proxy?x=1",
+            "https://target.example:8443/dns-query",
+        )
+        .unwrap();
+        assert_eq!(
+            transport.proxy,
+            "https://proxy.example:8443/proxy?x=1&\
+             targethost=target.example%3A8443&targetpath=%2Fdns-query"
+        );
+        assert_eq!(
+            transport.configs,
+            "https://target.example:8443/.well-known/odohconfigs"
+        );
+    }
+
+    #[test]
+    fn proxy_uri() {
+        assert_eq!(
+            super::proxy_uri(
+                "https://proxy.example/proxy",
+                "target.example",
+                "/dns-query"
+            ),
+            "https://proxy.example/proxy?\
+             targethost=target.example&targetpath=%2Fdns-query"
+        );
+    }
+}
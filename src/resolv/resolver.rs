@@ -3,6 +3,7 @@
 use crate::base::message::Message;
 use crate::base::name::ToDname;
 use crate::base::question::Question;
+use crate::resolv::lookup::host::{SearchCache, SearchMode};
 use futures::future::Future;
 use std::io;
 
@@ -53,4 +54,32 @@ pub trait SearchNames {
 
     /// Returns an iterator over the search suffixes.
     fn search_iter(&self) -> Self::Iter;
+
+    /// Returns the number of dots before a name is first tried as absolute.
+    ///
+    /// The default implementation returns 1.
+    fn ndots(&self) -> usize {
+        1
+    }
+
+    /// Returns whether single-label names should never be tried as absolute.
+    ///
+    /// The default implementation returns `false`.
+    fn no_tld_query(&self) -> bool {
+        false
+    }
+
+    /// Returns how the search list and the absolute name are combined.
+    ///
+    /// The default implementation returns `SearchMode::Glibc`.
+    fn search_mode(&self) -> SearchMode {
+        SearchMode::Glibc
+    }
+
+    /// Returns the cache for the results of walking the search list.
+    ///
+    /// The default implementation returns `None`, disabling caching.
+    fn search_cache(&self) -> Option<&SearchCache> {
+        None
+    }
 }
@@ -0,0 +1,1271 @@
+//! Caching answers.
+//!
+//! The stub resolver can keep the answers it receives in an
+//! [`AnswerCache`] and use them for later queries for the same question
+//! until their TTL has passed. Both positive answers and, as described in
+//! [RFC 2308], negative answers are cached.
+//!
+//! The cache can optionally be backed by a file. In this case, every
+//! answer added to the cache is appended to the file and the content of
+//! the file is loaded when the cache is opened. Entries store the wall
+//! clock time they were received, so TTLs are honored across restarts. This
+//! allows long-running daemons and devices that are only occasionally
+//! connected to start out with the answers they had before.
+//!
+//! The file is a simple log: an eight octet magic value followed by a
+//! sequence of entries, each consisting of the time the answer was
+//! received in seconds since the Unix epoch as a 64 bit integer, the time
+//! the entry is valid for in seconds as a 32 bit integer, the length of the
+//! message as a 16 bit integer, and the message itself. All integers are
+//! in network byte order. Later entries for the same question replace
+//! earlier ones. Entries that have expired are dropped whenever the file
+//! is rewritten, which happens when the cache is opened and whenever the
+//! log has grown to twice the capacity of the cache.
+//!
+//! Since answers are stored under their question only, the resolver only
+//! uses the cache for queries sent with the default flags – RD set, AD and
+//! CD cleared – and without an OPT record of their own. Other queries are
+//! always sent upstream and their answers aren’t cached.
+//!
+//! The size of the cache can be limited both by the number of entries and
+//! by the memory used by them. The memory of an entry is estimated as the
+//! length of the cached message and the question name plus the fixed size
+//! of the entry. When the cache is full, expired entries are dropped
+//! first. If that isn’t enough, entries are dropped in the order given by
+//! the cache’s [`EvictionPolicy`].
+//!
+//! The cache takes the current time from a [`Clock`]. By default, this is
+//! the system clock, but a [`MockClock`] can be used instead to test the
+//! expiration of answers and the aging of TTLs.
+//!
+//! [`AnswerCache`]: struct.AnswerCache.html
+//! [`EvictionPolicy`]: enum.EvictionPolicy.html
+//! [`Clock`]: ../../../base/clock/trait.Clock.html
+//! [`MockClock`]: ../../../base/clock/struct.MockClock.html
+//! [RFC 2308]: https://tools.ietf.org/html/rfc2308
+
+use crate::base::clock::{Clock, SystemClock};
+use crate::base::iana::{Class, Opcode, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::ParseError;
+use crate::rdata::Soa;
+use bytes::{Bytes, BytesMut};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::vec::Vec;
+use std::{cmp, fmt, mem};
+
+//------------ AnswerCache ---------------------------------------------------
+
+/// A cache for answers.
+///
+/// Answers are keyed by their question. Names are compared
+/// case-insensitively. Only answers to standard queries with a single
+/// question are cached. An answer stays in the cache for the smallest TTL of
+/// its records. For negative answers, the TTL of the SOA record in the
+/// authority section and its minimum field are considered, too. Negative
+/// answers without an SOA record are not cached. Answers other than
+/// NOERROR and NXDOMAIN as well as truncated answers are never cached.
+///
+/// When answers are taken from the cache, the TTLs of their records are
+/// reduced by the time the answer has spent in the cache.
+#[derive(Debug)]
+pub struct AnswerCache {
+    /// The actual cache.
+    inner: Mutex<Inner>,
+
+    /// The maximum number of entries.
+    capacity: usize,
+
+    /// The maximum memory used by all entries in octets, if limited.
+    memory_limit: Option<usize>,
+
+    /// The order in which entries are dropped when the cache is full.
+    eviction: EvictionPolicy,
+
+    /// The source for the current time.
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// The entries.
+    entries: HashMap<Key, Entry>,
+
+    /// The memory used by all entries in octets.
+    memory: usize,
+
+    /// A counter advanced with every use of an entry.
+    ///
+    /// This serves as the time of last use for LRU eviction since the
+    /// wall clock is too coarse for that.
+    tick: u64,
+
+    /// The file the cache is stored in, if any.
+    file: Option<CacheFile>,
+}
+
+impl AnswerCache {
+    /// The default maximum number of entries.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// Creates a new, empty in-memory cache.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_clock(capacity, SystemClock)
+    }
+
+    /// Creates a new, empty in-memory cache using the given clock.
+    pub fn with_clock(capacity: usize, clock: impl Clock + 'static) -> Self {
+        AnswerCache {
+            inner: Mutex::new(Inner::new(None)),
+            capacity,
+            memory_limit: None,
+            eviction: EvictionPolicy::default(),
+            clock: Arc::new(clock),
+        }
+    }
+
+    /// Opens a cache backed by the file at `path`.
+    ///
+    /// If the file exists, the answers stored in it that haven’t expired
+    /// yet are loaded into the cache. Otherwise, the file is created.
+    /// Content of the file that can’t be read, such as a partial entry at
+    /// its end left behind by a crash, is ignored. It is an error, however,
+    /// if the file exists but doesn’t start with the expected magic value.
+    pub fn open(
+        path: impl AsRef<Path>,
+        capacity: usize,
+    ) -> Result<Self, io::Error> {
+        Self::open_with_clock(path, capacity, SystemClock)
+    }
+
+    /// Opens a cache backed by the file at `path` using the given clock.
+    pub fn open_with_clock(
+        path: impl AsRef<Path>,
+        capacity: usize,
+        clock: impl Clock + 'static,
+    ) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let now = clock.unix_secs();
+        let mut inner = Inner::new(Some(CacheFile::new(path.into())));
+        match File::open(path) {
+            Ok(mut file) => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                for entry in CacheFile::entries(&data)? {
+                    if entry.expires() > now {
+                        if let Some(key) = Key::from_message(&entry.message) {
+                            inner.insert(key, entry);
+                        }
+                    }
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let eviction = EvictionPolicy::default();
+        inner.evict(capacity, usize::MAX, eviction, now);
+        inner.rewrite()?;
+        Ok(AnswerCache {
+            inner: Mutex::new(inner),
+            capacity,
+            memory_limit: None,
+            eviction,
+            clock: Arc::new(clock),
+        })
+    }
+
+    /// Limits the memory used by the entries to `limit` octets.
+    ///
+    /// If the cache already uses more memory, entries are dropped right
+    /// away.
+    pub fn with_memory_limit(mut self, limit: usize) -> Self {
+        self.memory_limit = Some(limit);
+        self.shrink();
+        self
+    }
+
+    /// Sets the order in which entries are dropped when the cache is full.
+    pub fn with_eviction(mut self, eviction: EvictionPolicy) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+    /// Returns the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the limit for the memory used by the entries, if any.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Returns the order in which entries are dropped.
+    pub fn eviction(&self) -> EvictionPolicy {
+        self.eviction
+    }
+
+    /// Returns the memory currently used by the entries in octets.
+    ///
+    /// Like [`len`], this includes entries that have expired but haven’t
+    /// been removed yet.
+    ///
+    /// [`len`]: #method.len
+    pub fn memory_usage(&self) -> usize {
+        self.inner.lock().unwrap().memory
+    }
+
+    /// Drops entries until the cache is within its limits.
+    fn shrink(&mut self) {
+        let now = self.now();
+        let max_memory = self.memory_limit.unwrap_or(usize::MAX);
+        self.inner.get_mut().unwrap().evict(
+            self.capacity,
+            max_memory,
+            self.eviction,
+            now,
+        );
+    }
+
+    /// Returns the number of entries currently in the cache.
+    ///
+    /// This includes entries that have expired but haven’t been removed
+    /// yet.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Returns whether the cache is currently empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the cached answer for a question, if there is one.
+    pub fn get(
+        &self,
+        qname: &impl ToDname,
+        qtype: Rtype,
+        qclass: Class,
+    ) -> Option<Message<Bytes>> {
+        self.get_at(Key::new(qname, qtype, qclass), self.now())
+    }
+
+    /// Returns the cached answer for the question of a query.
+    pub(super) fn lookup(
+        &self,
+        query: &Message<&[u8]>,
+    ) -> Option<Message<Bytes>> {
+        self.get_at(Key::from_message(query)?, self.now())
+    }
+
+    /// Returns the current time in seconds since the Unix epoch.
+    fn now(&self) -> u64 {
+        self.clock.unix_secs()
+    }
+
+    fn get_at(&self, key: Key, now: u64) -> Option<Message<Bytes>> {
+        let mut inner = self.inner.lock().unwrap();
+        let tick = inner.next_tick();
+        let entry = inner.entries.get_mut(&key)?;
+        if entry.expires() <= now {
+            inner.remove(&key);
+            return None;
+        }
+        entry.last_used = tick;
+        entry.hits = entry.hits.saturating_add(1);
+        Some(entry.aged_message(now))
+    }
+
+    /// Adds an answer to the cache.
+    ///
+    /// The answer is stored under its first question. If the answer can’t
+    /// be cached or is larger than the memory limit of the cache, nothing
+    /// happens. If the cache is full, expired entries are dropped first. If
+    /// that doesn’t free up enough space, entries are dropped according to
+    /// the cache’s eviction policy.
+    pub fn insert(&self, answer: &Message<Bytes>) {
+        self.insert_at(answer, self.now())
+    }
+
+    fn insert_at(&self, answer: &Message<Bytes>, now: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = match Key::from_message(answer) {
+            Some(key) => key,
+            None => return,
+        };
+        let ttl = match cache_ttl(answer) {
+            Some(ttl) if ttl > 0 => ttl,
+            _ => return,
+        };
+        let mut entry = Entry {
+            received: now,
+            ttl,
+            message: answer.clone(),
+            last_used: 0,
+            hits: 0,
+        };
+        let size = entry.memory_size(&key);
+        let max_memory = match self.memory_limit {
+            Some(limit) if size > limit => return,
+            Some(limit) => limit - size,
+            None => usize::MAX,
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(&key);
+        inner.evict(self.capacity - 1, max_memory, self.eviction, now);
+        entry.last_used = inner.next_tick();
+        inner.append(&entry, self.capacity);
+        inner.insert(key, entry);
+    }
+
+    /// Returns a snapshot of all entries that haven’t expired yet.
+    ///
+    /// The entries are ordered by name, type, and class. Names are
+    /// lowercased.
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        self.entries_at(self.now())
+    }
+
+    fn entries_at(&self, now: u64) -> Vec<CacheEntry> {
+        let inner = self.inner.lock().unwrap();
+        let mut res: Vec<_> = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expires() > now)
+            .map(|(key, entry)| CacheEntry::new(key, entry, now))
+            .collect();
+        res.sort_by(|left, right| {
+            (&left.qname, left.qtype, left.qclass).cmp(&(
+                &right.qname,
+                right.qtype,
+                right.qclass,
+            ))
+        });
+        res
+    }
+
+    /// Returns for how much longer the answer for a question stays cached.
+    ///
+    /// Returns `None` if there is no answer for the question in the cache.
+    pub fn remaining_ttl(
+        &self,
+        qname: &impl ToDname,
+        qtype: Rtype,
+        qclass: Class,
+    ) -> Option<Duration> {
+        self.remaining_ttl_at(Key::new(qname, qtype, qclass), self.now())
+    }
+
+    fn remaining_ttl_at(&self, key: Key, now: u64) -> Option<Duration> {
+        let inner = self.inner.lock().unwrap();
+        let entry = inner.entries.get(&key)?;
+        match entry.expires().checked_sub(now) {
+            Some(0) | None => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Removes all answers for `qname`, regardless of their type and class.
+    ///
+    /// Returns the number of entries removed.
+    pub fn purge_name(
+        &self,
+        qname: &impl ToDname,
+    ) -> Result<usize, io::Error> {
+        let qname = Key::new(qname, Rtype::A, Class::In).qname;
+        self.purge(|key| key.qname == qname)
+    }
+
+    /// Removes all answers for `suffix` and the names below it.
+    ///
+    /// Returns the number of entries removed.
+    pub fn purge_suffix(
+        &self,
+        suffix: &impl ToDname,
+    ) -> Result<usize, io::Error> {
+        self.purge(|key| key.name().ends_with(suffix))
+    }
+
+    /// Removes all answers for questions of the given type.
+    ///
+    /// Returns the number of entries removed.
+    pub fn purge_type(&self, qtype: Rtype) -> Result<usize, io::Error> {
+        self.purge(|key| key.qtype == qtype)
+    }
+
+    /// Removes the entries whose keys match `op`.
+    ///
+    /// If the cache is backed by a file and anything was removed, the file
+    /// is rewritten so the entries don’t reappear after a restart.
+    fn purge(&self, op: impl Fn(&Key) -> bool) -> Result<usize, io::Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let len = inner.entries.len();
+        inner.retain(|key, _| !op(key));
+        let removed = len - inner.entries.len();
+        if removed > 0 {
+            inner.rewrite()?;
+        }
+        Ok(removed)
+    }
+
+    /// Removes all entries.
+    ///
+    /// If the cache is backed by a file, the file is truncated, too.
+    pub fn clear(&self) -> Result<(), io::Error> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.retain(|_, _| false);
+        inner.rewrite()
+    }
+}
+
+impl Inner {
+    fn new(file: Option<CacheFile>) -> Self {
+        Inner {
+            entries: HashMap::new(),
+            memory: 0,
+            tick: 0,
+            file,
+        }
+    }
+
+    /// Returns the next value of the use counter.
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Adds an entry, replacing an existing entry for the key.
+    fn insert(&mut self, key: Key, entry: Entry) {
+        self.memory += entry.memory_size(&key);
+        if let Some(old) = self.entries.insert(key.clone(), entry) {
+            self.memory -= old.memory_size(&key);
+        }
+    }
+
+    /// Removes the entry for a key.
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.memory -= entry.memory_size(key);
+        }
+    }
+
+    /// Keeps only the entries for which `keep` returns `true`.
+    fn retain(&mut self, mut keep: impl FnMut(&Key, &Entry) -> bool) {
+        self.entries.retain(|key, entry| keep(key, entry));
+        self.memory = self
+            .entries
+            .iter()
+            .map(|(key, entry)| entry.memory_size(key))
+            .sum();
+    }
+
+    /// Returns whether the entries are within the given limits.
+    fn fits(&self, max: usize, max_memory: usize) -> bool {
+        self.entries.len() <= max && self.memory <= max_memory
+    }
+
+    /// Drops entries until there are at most `max` left using at most
+    /// `max_memory` octets.
+    fn evict(
+        &mut self,
+        max: usize,
+        max_memory: usize,
+        eviction: EvictionPolicy,
+        now: u64,
+    ) {
+        if self.fits(max, max_memory) {
+            return;
+        }
+        self.retain(|_, entry| entry.expires() > now);
+        if self.fits(max, max_memory) {
+            return;
+        }
+        let mut order: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (eviction.rank(entry), key.clone()))
+            .collect();
+        order.sort_unstable();
+        for (_, key) in order {
+            if self.fits(max, max_memory) {
+                break;
+            }
+            self.remove(&key);
+        }
+    }
+
+    /// Appends an entry to the file, rewriting the file if it got too long.
+    ///
+    /// Errors are ignored: if the file can’t be written, the cache
+    /// continues to work in memory.
+    fn append(&mut self, entry: &Entry, capacity: usize) {
+        let rewrite = match self.file {
+            Some(ref mut file) => {
+                if file.append(entry).is_err() {
+                    return;
+                }
+                file.written > capacity.saturating_mul(2)
+            }
+            None => return,
+        };
+        if rewrite {
+            let _ = self.rewrite();
+        }
+    }
+
+    /// Rewrites the file to contain exactly the current entries.
+    fn rewrite(&mut self) -> Result<(), io::Error> {
+        match self.file {
+            Some(ref mut file) => file.rewrite(self.entries.values()),
+            None => Ok(()),
+        }
+    }
+}
+
+//------------ EvictionPolicy ------------------------------------------------
+
+/// The order in which entries are dropped when the cache is full.
+///
+/// Expired entries are always dropped first. The policy only decides
+/// which of the remaining entries go.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EvictionPolicy {
+    /// Drop the entries expiring soonest.
+    ///
+    /// This is the default.
+    Ttl,
+
+    /// Drop the entries that haven’t been used for the longest time.
+    Lru,
+
+    /// Drop the entries that have been used the least often.
+    ///
+    /// Among entries used equally often, the least recently used entries
+    /// are dropped first.
+    Lfu,
+}
+
+impl EvictionPolicy {
+    /// Returns the rank of an entry. Entries with lower ranks go first.
+    fn rank(self, entry: &Entry) -> (u64, u64) {
+        match self {
+            EvictionPolicy::Ttl => (entry.expires(), entry.last_used),
+            EvictionPolicy::Lru => (entry.last_used, entry.expires()),
+            EvictionPolicy::Lfu => (entry.hits, entry.last_used),
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)] // #[default] needs Rust 1.62.
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        EvictionPolicy::Ttl
+    }
+}
+
+//------------ Key -----------------------------------------------------------
+
+/// The key of a cache entry.
+///
+/// The name is kept in lowercased wire format.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Key {
+    qname: Vec<u8>,
+    qtype: Rtype,
+    qclass: Class,
+}
+
+impl Key {
+    fn new(qname: &impl ToDname, qtype: Rtype, qclass: Class) -> Self {
+        let mut qname = qname.to_vec().into_octets();
+        qname.make_ascii_lowercase();
+        Key {
+            qname,
+            qtype,
+            qclass,
+        }
+    }
+
+    fn name(&self) -> &Dname<[u8]> {
+        Dname::from_slice(&self.qname).unwrap()
+    }
+
+    /// Returns the key for a message.
+    ///
+    /// Returns `None` if the message isn’t a standard query with exactly
+    /// one question or the answer to one.
+    fn from_message<Octets: AsRef<[u8]>>(
+        message: &Message<Octets>,
+    ) -> Option<Self> {
+        let message = message.for_slice();
+        if message.header().opcode() != Opcode::Query
+            || message.header_counts().qdcount() != 1
+        {
+            return None;
+        }
+        let question = message.first_question()?;
+        Some(Self::new(
+            question.qname(),
+            question.qtype(),
+            question.qclass(),
+        ))
+    }
+}
+
+//------------ CacheEntry ----------------------------------------------------
+
+/// Information about an entry of the cache.
+///
+/// Values of this type are returned by [`AnswerCache::entries`].
+///
+/// [`AnswerCache::entries`]: struct.AnswerCache.html#method.entries
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    qname: Dname<Bytes>,
+    qtype: Rtype,
+    qclass: Class,
+    rcode: Rcode,
+    ttl: Duration,
+    memory_size: usize,
+    hits: u64,
+}
+
+impl CacheEntry {
+    fn new(key: &Key, entry: &Entry, now: u64) -> Self {
+        CacheEntry {
+            qname: Dname::from_octets(Bytes::copy_from_slice(&key.qname))
+                .unwrap(),
+            qtype: key.qtype,
+            qclass: key.qclass,
+            rcode: entry.message.header().rcode(),
+            ttl: Duration::from_secs(entry.expires().saturating_sub(now)),
+            memory_size: entry.memory_size(key),
+            hits: entry.hits,
+        }
+    }
+
+    /// Returns the name of the question in lowercase.
+    pub fn qname(&self) -> &Dname<Bytes> {
+        &self.qname
+    }
+
+    /// Returns the type of the question.
+    pub fn qtype(&self) -> Rtype {
+        self.qtype
+    }
+
+    /// Returns the class of the question.
+    pub fn qclass(&self) -> Class {
+        self.qclass
+    }
+
+    /// Returns the response code of the cached answer.
+    ///
+    /// This allows telling negative answers for names that don’t exist
+    /// apart from others.
+    pub fn rcode(&self) -> Rcode {
+        self.rcode
+    }
+
+    /// Returns for how much longer the entry stays cached.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the estimated memory used by the entry in octets.
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    /// Returns how often the entry has been used since it was added.
+    ///
+    /// The count starts from zero for entries loaded from a file.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+}
+
+//------------ Entry ---------------------------------------------------------
+
+/// A cached answer.
+#[derive(Clone)]
+struct Entry {
+    /// When the answer was received in seconds since the Unix epoch.
+    received: u64,
+
+    /// For how many seconds the answer can be used.
+    ttl: u32,
+
+    /// The answer as it was received.
+    message: Message<Bytes>,
+
+    /// The value of the cache’s use counter when the entry was last used.
+    last_used: u64,
+
+    /// The number of times the entry has been used.
+    hits: u64,
+}
+
+impl Entry {
+    /// Returns when the entry expires in seconds since the Unix epoch.
+    fn expires(&self) -> u64 {
+        self.received.saturating_add(u64::from(self.ttl))
+    }
+
+    /// Returns the estimated memory used by the entry stored under `key`.
+    fn memory_size(&self, key: &Key) -> usize {
+        mem::size_of::<Key>()
+            + mem::size_of::<Entry>()
+            + key.qname.len()
+            + self.message.as_slice().len()
+    }
+
+    /// Returns the message with the TTLs reduced by the time spent cached.
+    fn aged_message(&self, now: u64) -> Message<Bytes> {
+        let age =
+            cmp::min(now.saturating_sub(self.received), u64::from(u32::MAX))
+                as u32;
+        if age == 0 {
+            return self.message.clone();
+        }
+        let positions = match ttl_positions(self.message.as_slice()) {
+            Ok(positions) => positions,
+            Err(_) => return self.message.clone(),
+        };
+        let mut octets = BytesMut::from(self.message.as_slice());
+        for pos in positions {
+            let field = &mut octets[pos..pos + 4];
+            let mut ttl = [0u8; 4];
+            ttl.copy_from_slice(field);
+            let ttl = u32::from_be_bytes(ttl).saturating_sub(age);
+            field.copy_from_slice(&ttl.to_be_bytes());
+        }
+        Message::from_octets(octets.freeze()).unwrap()
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("received", &self.received)
+            .field("ttl", &self.ttl)
+            .field("message", &self.message.as_slice())
+            .field("last_used", &self.last_used)
+            .field("hits", &self.hits)
+            .finish()
+    }
+}
+
+//------------ CacheFile -----------------------------------------------------
+
+/// The file backing a cache.
+#[derive(Debug)]
+struct CacheFile {
+    /// The path of the file.
+    path: PathBuf,
+
+    /// The file opened for appending.
+    ///
+    /// This is `None` until the file has been written for the first time.
+    file: Option<File>,
+
+    /// The number of entries written to the file.
+    written: usize,
+}
+
+impl CacheFile {
+    /// The magic value at the start of the file.
+    const MAGIC: &'static [u8; 8] = b"DNSCACH1";
+
+    /// The length of the header of an entry.
+    const ENTRY_HEADER_LEN: usize =
+        mem::size_of::<u64>() + mem::size_of::<u32>() + mem::size_of::<u16>();
+
+    fn new(path: PathBuf) -> Self {
+        CacheFile {
+            path,
+            file: None,
+            written: 0,
+        }
+    }
+
+    /// Returns the entries stored in `data`.
+    fn entries(mut data: &[u8]) -> Result<Vec<Entry>, io::Error> {
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !data.starts_with(Self::MAGIC) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a cache file",
+            ));
+        }
+        data = &data[Self::MAGIC.len()..];
+        let mut res = Vec::new();
+        while data.len() >= Self::ENTRY_HEADER_LEN {
+            let mut received = [0u8; 8];
+            received.copy_from_slice(&data[..8]);
+            let mut ttl = [0u8; 4];
+            ttl.copy_from_slice(&data[8..12]);
+            let len = usize::from(u16::from_be_bytes([data[12], data[13]]));
+            data = &data[Self::ENTRY_HEADER_LEN..];
+            if data.len() < len {
+                break;
+            }
+            let message = match Message::from_octets(Bytes::copy_from_slice(
+                &data[..len],
+            )) {
+                Ok(message) => message,
+                Err(_) => break,
+            };
+            data = &data[len..];
+            res.push(Entry {
+                received: u64::from_be_bytes(received),
+                ttl: u32::from_be_bytes(ttl),
+                message,
+                last_used: 0,
+                hits: 0,
+            })
+        }
+        Ok(res)
+    }
+
+    /// Appends a single entry to the file.
+    fn append(&mut self, entry: &Entry) -> Result<(), io::Error> {
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => {
+                self.rewrite(None)?;
+                self.file.as_mut().unwrap()
+            }
+        };
+        file.write_all(&Self::compose_entry(entry))?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Replaces the content of the file with the given entries.
+    ///
+    /// The new content is written to a temporary file first which then
+    /// replaces the file so that a crash leaves either the old or the new
+    /// content.
+    fn rewrite<'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = &'a Entry>,
+    ) -> Result<(), io::Error> {
+        let mut data = Vec::from(&Self::MAGIC[..]);
+        let mut written = 0;
+        for entry in entries {
+            data.extend_from_slice(&Self::compose_entry(entry));
+            written += 1;
+        }
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&data)?;
+        tmp.sync_all()?;
+        drop(tmp);
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        self.written = written;
+        Ok(())
+    }
+
+    fn compose_entry(entry: &Entry) -> Vec<u8> {
+        let message = entry.message.as_slice();
+        let mut res =
+            Vec::with_capacity(Self::ENTRY_HEADER_LEN + message.len());
+        res.extend_from_slice(&entry.received.to_be_bytes());
+        res.extend_from_slice(&entry.ttl.to_be_bytes());
+        res.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        res.extend_from_slice(message);
+        res
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether a query can be answered from and stored in the cache.
+///
+/// Answers are cached under their question only. They can thus only be
+/// used for queries that are sent the way a plain question is: a standard
+/// query with exactly one question, the RD flag set, the AD and CD flags
+/// cleared, and no records at all, in particular no OPT record with the DO
+/// flag or EDNS options.
+pub(super) fn is_cacheable_query(query: &Message<&[u8]>) -> bool {
+    let header = query.header();
+    let counts = query.header_counts();
+    header.opcode() == Opcode::Query
+        && header.rd()
+        && !header.ad()
+        && !header.cd()
+        && counts.qdcount() == 1
+        && counts.ancount() == 0
+        && counts.nscount() == 0
+        && counts.arcount() == 0
+}
+
+/// Returns for how long an answer can be cached or `None` if it can’t.
+fn cache_ttl(answer: &Message<Bytes>) -> Option<u32> {
+    let header = answer.header();
+    if header.tc() {
+        return None;
+    }
+    let negative = match header.rcode() {
+        Rcode::NXDomain => true,
+        Rcode::NoError => answer.header_counts().ancount() == 0,
+        _ => return None,
+    };
+    let mut ttl = ttl_positions(answer.as_slice())
+        .ok()?
+        .into_iter()
+        .map(|pos| {
+            let mut ttl = [0u8; 4];
+            ttl.copy_from_slice(&answer.as_slice()[pos..pos + 4]);
+            u32::from_be_bytes(ttl)
+        })
+        .min();
+    if negative {
+        let soa = answer
+            .authority()
+            .ok()?
+            .limit_to::<Soa<ParsedDname<&Bytes>>>()
+            .next()?
+            .ok()?;
+        let minimum = soa.data().minimum();
+        ttl = Some(ttl.map_or(minimum, |ttl| cmp::min(ttl, minimum)));
+    }
+    ttl
+}
+
+/// Returns the positions of the TTL fields of all records in a message.
+///
+/// OPT records are skipped since their TTL field has a different meaning.
+fn ttl_positions(octets: &[u8]) -> Result<Vec<usize>, ParseError> {
+    let mut res = Vec::new();
+    Message::from_octets(octets)
+        .map_err(|_| ParseError::ShortInput)?
+        .for_each_ttl_field(|rtype, pos| {
+            if rtype != Rtype::Opt {
+                res.push(pos)
+            }
+        })?;
+    Ok(res)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::clock::MockClock;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::rdata::A;
+    use std::format;
+    use std::str::FromStr;
+    use std::string::ToString;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn answer(qname: &str, rcode: Rcode, ttls: &[u32]) -> Message<Bytes> {
+        let qname = name(qname);
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((&qname, Rtype::A)).unwrap();
+        let mut answer = MessageBuilder::new_bytes()
+            .start_answer(&query.into_message(), rcode)
+            .unwrap();
+        for (idx, ttl) in ttls.iter().enumerate() {
+            answer
+                .push((
+                    &qname,
+                    Class::In,
+                    *ttl,
+                    A::from_octets(192, 0, 2, idx as u8),
+                ))
+                .unwrap();
+        }
+        let mut authority = answer.authority();
+        if ttls.is_empty() {
+            authority
+                .push((
+                    name("example."),
+                    Class::In,
+                    3600,
+                    Soa::new(
+                        name("ns.example."),
+                        name("hostmaster.example."),
+                        1.into(),
+                        3600,
+                        600,
+                        86400,
+                        300,
+                    ),
+                ))
+                .unwrap();
+        }
+        authority.into_message()
+    }
+
+    fn ttls(message: &Message<Bytes>) -> Vec<u32> {
+        message
+            .answer()
+            .unwrap()
+            .map(|record| record.unwrap().ttl())
+            .collect()
+    }
+
+    #[test]
+    fn get_and_expire() {
+        let cache = AnswerCache::new(10);
+        let key = || Key::new(&name("www.example."), Rtype::A, Class::In);
+        cache.insert_at(
+            &answer("www.example.", Rcode::NoError, &[300, 60]),
+            1000,
+        );
+
+        // Names are case-insensitive and TTLs are aged.
+        let found = cache
+            .get_at(
+                Key::new(&name("WWW.Example."), Rtype::A, Class::In),
+                1030,
+            )
+            .unwrap();
+        assert_eq!(ttls(&found), [270, 30]);
+
+        // The entry expires with the smallest TTL.
+        assert!(cache.get_at(key(), 1059).is_some());
+        assert!(cache.get_at(key(), 1060).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn standard_queries_only() {
+        let cache = AnswerCache::new(10);
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((name("a.example."), Rtype::A)).unwrap();
+        query.push((name("b.example."), Rtype::A)).unwrap();
+        let answer = MessageBuilder::new_bytes()
+            .start_answer(&query.into_message(), Rcode::NoError)
+            .unwrap()
+            .into_message();
+        cache.insert_at(&answer, 1000);
+        assert!(cache.is_empty());
+
+        let mut query = MessageBuilder::new_vec();
+        query.header_mut().set_opcode(Opcode::Status);
+        let mut query = query.question();
+        query.push((name("a.example."), Rtype::A)).unwrap();
+        let mut answer = MessageBuilder::new_bytes()
+            .start_answer(&query.into_message(), Rcode::NoError)
+            .unwrap();
+        answer
+            .push((name("a.example."), 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        cache.insert_at(&answer.into_message(), 1000);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn negative() {
+        let cache = AnswerCache::new(10);
+        cache.insert_at(&answer("nx.example.", Rcode::NXDomain, &[]), 1000);
+        cache.insert_at(&answer("fail.example.", Rcode::ServFail, &[]), 1000);
+        let key = |s| Key::new(&name(s), Rtype::A, Class::In);
+
+        // The SOA minimum is smaller than the SOA TTL, so it wins.
+        assert!(cache.get_at(key("nx.example."), 1299).is_some());
+        assert!(cache.get_at(key("nx.example."), 1300).is_none());
+        assert!(cache.get_at(key("fail.example."), 1000).is_none());
+    }
+
+    #[test]
+    fn evict() {
+        let cache = AnswerCache::new(2);
+        cache.insert_at(&answer("a.example.", Rcode::NoError, &[300]), 1000);
+        cache.insert_at(&answer("b.example.", Rcode::NoError, &[100]), 1000);
+        cache.insert_at(&answer("c.example.", Rcode::NoError, &[200]), 1000);
+        let key = |s| Key::new(&name(s), Rtype::A, Class::In);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get_at(key("a.example."), 1001).is_some());
+        assert!(cache.get_at(key("b.example."), 1001).is_none());
+        assert!(cache.get_at(key("c.example."), 1001).is_some());
+    }
+
+    #[test]
+    fn eviction_policies() {
+        let key = |s| Key::new(&name(s), Rtype::A, Class::In);
+        let fill = |eviction| {
+            let cache = AnswerCache::new(2).with_eviction(eviction);
+            cache.insert_at(
+                &answer("a.example.", Rcode::NoError, &[100]),
+                1000,
+            );
+            cache.insert_at(
+                &answer("b.example.", Rcode::NoError, &[300]),
+                1000,
+            );
+            cache.get_at(key("b.example."), 1001);
+            cache.get_at(key("b.example."), 1001);
+            cache.get_at(key("a.example."), 1002);
+            cache.insert_at(
+                &answer("c.example.", Rcode::NoError, &[300]),
+                1003,
+            );
+            assert_eq!(cache.len(), 2);
+            assert!(cache.get_at(key("c.example."), 1004).is_some());
+            cache
+        };
+
+        let cache = fill(EvictionPolicy::Ttl);
+        assert!(cache.get_at(key("a.example."), 1004).is_none());
+        let cache = fill(EvictionPolicy::Lru);
+        assert!(cache.get_at(key("b.example."), 1004).is_none());
+        let cache = fill(EvictionPolicy::Lfu);
+        assert!(cache.get_at(key("a.example."), 1004).is_none());
+        assert_eq!(cache.entries_at(1004)[0].hits(), 2);
+    }
+
+    #[test]
+    fn memory_limit() {
+        let cache = AnswerCache::new(10);
+        cache.insert_at(&answer("a.example.", Rcode::NoError, &[300]), 1000);
+        let size = cache.entries_at(1000)[0].memory_size();
+        assert_eq!(cache.memory_usage(), size);
+
+        let cache = cache.with_memory_limit(size * 2 + size / 2);
+        cache.insert_at(&answer("b.example.", Rcode::NoError, &[100]), 1000);
+        cache.insert_at(&answer("c.example.", Rcode::NoError, &[200]), 1000);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.memory_usage(), size * 2);
+        let key = |s| Key::new(&name(s), Rtype::A, Class::In);
+        assert!(cache.get_at(key("b.example."), 1001).is_none());
+
+        // An answer that is too large on its own isn’t cached.
+        cache.insert_at(
+            &answer("d.example.", Rcode::NoError, &[300; 50]),
+            1000,
+        );
+        assert!(cache.get_at(key("d.example."), 1001).is_none());
+        assert_eq!(cache.len(), 2);
+
+        cache.purge_name(&name("a.example.")).unwrap();
+        assert_eq!(cache.memory_usage(), size);
+        cache.clear().unwrap();
+        assert_eq!(cache.memory_usage(), 0);
+    }
+
+    #[test]
+    fn inspect_and_purge() {
+        let cache = AnswerCache::new(10);
+        for qname in &["a.example.", "b.a.example.", "ba.example.", "c.test."]
+        {
+            cache.insert_at(&answer(qname, Rcode::NoError, &[300]), 1000);
+        }
+        cache.insert_at(&answer("nx.test.", Rcode::NXDomain, &[]), 1000);
+        let key = |s| Key::new(&name(s), Rtype::A, Class::In);
+
+        let entries = cache.entries_at(1100);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.qname().to_string())
+                .collect::<Vec<_>>(),
+            [
+                "a.example",
+                "b.a.example",
+                "ba.example",
+                "c.test",
+                "nx.test"
+            ]
+        );
+        assert_eq!(entries[0].ttl(), Duration::from_secs(200));
+        assert_eq!(entries[4].rcode(), Rcode::NXDomain);
+        assert_eq!(entries[4].ttl(), Duration::from_secs(200));
+        assert_eq!(
+            cache.remaining_ttl_at(key("c.test."), 1299),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(cache.remaining_ttl_at(key("c.test."), 1300), None);
+        assert_eq!(cache.remaining_ttl_at(key("d.test."), 1000), None);
+
+        assert_eq!(cache.purge_name(&name("NX.test.")).unwrap(), 1);
+        assert_eq!(cache.purge_suffix(&name("A.example.")).unwrap(), 2);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.purge_type(Rtype::Aaaa).unwrap(), 0);
+        assert_eq!(cache.purge_type(Rtype::A).unwrap(), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn clock() {
+        let clock = MockClock::from_unix_secs(1000);
+        let cache = AnswerCache::with_clock(10, clock.clone());
+        let qname = name("www.example.");
+        cache.insert(&answer("www.example.", Rcode::NoError, &[300, 60]));
+
+        clock.advance(Duration::from_secs(30));
+        let found = cache.get(&qname, Rtype::A, Class::In).unwrap();
+        assert_eq!(ttls(&found), [270, 30]);
+        assert_eq!(
+            cache.remaining_ttl(&qname, Rtype::A, Class::In),
+            Some(Duration::from_secs(30))
+        );
+
+        clock.advance(Duration::from_secs(30));
+        assert!(cache.get(&qname, Rtype::A, Class::In).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn persist() {
+        let path = std::env::temp_dir()
+            .join(format!("domain-cache-test-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let key = |s| Key::new(&name(s), Rtype::A, Class::In);
+        let clock = MockClock::from_unix_secs;
+
+        let cache =
+            AnswerCache::open_with_clock(&path, 10, clock(1000)).unwrap();
+        cache.insert_at(&answer("a.example.", Rcode::NoError, &[300]), 1000);
+        cache.insert_at(&answer("b.example.", Rcode::NoError, &[100]), 1000);
+        cache.insert_at(&answer("a.example.", Rcode::NoError, &[500]), 1010);
+        drop(cache);
+
+        // After a restart, unexpired answers are back and TTLs are aged
+        // across the restart.
+        let cache =
+            AnswerCache::open_with_clock(&path, 10, clock(1200)).unwrap();
+        assert_eq!(cache.len(), 1);
+        let found = cache.get_at(key("a.example."), 1210).unwrap();
+        assert_eq!(ttls(&found), [300]);
+        drop(cache);
+
+        // A partial entry at the end is ignored.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"\0\0\0").unwrap();
+        drop(file);
+        let cache =
+            AnswerCache::open_with_clock(&path, 10, clock(1200)).unwrap();
+        assert_eq!(cache.len(), 1);
+        cache.clear().unwrap();
+        drop(cache);
+        let cache =
+            AnswerCache::open_with_clock(&path, 10, clock(1200)).unwrap();
+        assert!(cache.is_empty());
+
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, b"garbage!").unwrap();
+        assert!(AnswerCache::open_with_clock(&path, 10, clock(1200)).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
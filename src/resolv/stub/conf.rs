@@ -8,14 +8,20 @@
 //!
 //! Both parts are modeled along the lines of glibc’s resolver.
 
+use super::cache::EvictionPolicy;
+use super::filter::AddrFilter;
 use crate::base::name::{self, Dname};
+use crate::localzone::LocalZones;
+use crate::resolv::lookup::host::SearchMode;
 use smallvec::SmallVec;
 use std::cmp::Ordering;
 use std::default::Default;
 use std::io::Read;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::Path;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::{Path, PathBuf};
 use std::str::{self, FromStr, SplitWhitespace};
+use std::string::String;
+use std::sync::Arc;
 use std::time::Duration;
 use std::vec::Vec;
 use std::{convert, error, fmt, fs, io, ops};
@@ -33,16 +39,39 @@ pub struct ResolvOptions {
     /// Search list for host-name lookup.
     pub search: SearchList,
 
-    /// TODO Sortlist
-    /// sortlist: ??
+    /// Address sorting list for host-name lookup.
+    ///
+    /// Addresses returned by `lookup_host` and `search_host` on the stub
+    /// resolver are sorted according to this list.
+    pub sortlist: SortList,
+
+    /// The order of addresses returned by host lookups.
+    ///
+    /// By default, addresses are returned in the order of the answer,
+    /// preserving any rotation done by the server. The sort list is
+    /// applied after this order.
+    pub addr_order: AddrOrder,
 
     /// Number of dots before an initial absolute query is made.
+    ///
+    /// This option is implemented by `search_host()`.
     pub ndots: usize,
 
+    /// How the search list and the absolute name are combined.
+    ///
+    /// This option is implemented by `search_host()`. By default, it
+    /// behaves like glibc.
+    pub search_mode: SearchMode,
+
     /// Timeout to wait for a response.
+    ///
+    /// This option is copied into the servers’ request timeouts by
+    /// `ResolvConf::finalize()`.
     pub timeout: Duration,
 
     /// Number of retries before giving up.
+    ///
+    /// This option is implemented by the query.
     pub attempts: usize,
 
     /// Accept authoritative answers only.
@@ -123,11 +152,145 @@ pub struct ResolvOptions {
     /// at all.
     pub keep_tsig: bool,
 
-    /// Send each query simultaneously to all name servers.
+    /// Send each query simultaneously to several name servers.
+    ///
+    /// The query is sent to up to `blast_servers` servers and the first
+    /// final answer is used. If there is none, the query falls back to
+    /// trying the servers one by one.
     ///
-    /// This is not currently implemented. It would be a query option.
+    /// This option is implemented by the query.
     pub blast: bool,
 
+    /// The number of servers to send a query to in blast mode.
+    ///
+    /// A value of zero means all servers. Defaults to 2.
+    pub blast_servers: usize,
+
+    /// The delay between sending the query to consecutive servers.
+    ///
+    /// In blast mode, the query is sent to the first server immediately
+    /// and to each further server this much later than to the previous
+    /// one, unless an answer has arrived by then. Defaults to zero.
+    pub blast_stagger: Duration,
+
+    /// Randomize the case of the letters in query names.
+    ///
+    /// This adds entropy to UDP queries as a defense against spoofed
+    /// answers, a technique known as ‘DNS 0x20.’ Answers have to echo the
+    /// query name with exactly the same case and are ignored otherwise, so
+    /// this should only be enabled if all servers do so. The [`probe`]
+    /// module can check this. See the [`entropy`] module for the other
+    /// random values in queries.
+    ///
+    /// This option is implemented by the query.
+    ///
+    /// [`probe`]: ../probe/index.html
+    /// [`entropy`]: ../entropy/index.html
+    pub randomize_case: bool,
+
+    /// The local address to send queries to IPv4 servers from.
+    ///
+    /// If this is `None`, the system picks the address. The port should
+    /// normally be zero so that a random port is used for each query.
+    /// This can be overridden for individual servers via
+    /// [`ServerConf::bind_addr`].
+    ///
+    /// [`ServerConf::bind_addr`]: struct.ServerConf.html#structfield.bind_addr
+    pub bind_v4: Option<SocketAddrV4>,
+
+    /// The local address to send queries to IPv6 servers from.
+    ///
+    /// If this is `None`, the system picks the address. The port should
+    /// normally be zero so that a random port is used for each query. For
+    /// link-local addresses, the scope ID selects the interface. This can
+    /// be overridden for individual servers via [`ServerConf::bind_addr`].
+    ///
+    /// [`ServerConf::bind_addr`]: struct.ServerConf.html#structfield.bind_addr
+    pub bind_v6: Option<SocketAddrV6>,
+
+    /// The network interface to send queries through.
+    ///
+    /// If this is `None`, the system picks the interface based on the
+    /// routing table. Binding to an interface is only supported on Linux,
+    /// Android, and Fuchsia. On other systems, queries will fail if an
+    /// interface is given. This can be overridden for individual servers
+    /// via [`ServerConf::interface`].
+    ///
+    /// [`ServerConf::interface`]: struct.ServerConf.html#structfield.interface
+    pub interface: Option<String>,
+
+    /// The maximum rate of queries sent to all servers combined.
+    ///
+    /// If this is `None`, queries are not limited. Otherwise, queries
+    /// exceeding the rate wait until they are allowed to go out.
+    pub rate_limit: Option<RateLimit>,
+
+    /// The maximum rate of queries sent to each server.
+    ///
+    /// The limit applies separately to each server address, shared by all
+    /// transports used with that address. If this is `None`, queries are
+    /// not limited. Otherwise, queries exceeding the rate wait until they
+    /// are allowed to go out.
+    pub server_rate_limit: Option<RateLimit>,
+
+    /// Which address records to suppress.
+    ///
+    /// Queries for a suppressed record type are answered with an empty
+    /// answer without being sent upstream. See the [`filter`] module for
+    /// details. By default, no records are suppressed.
+    ///
+    /// [`filter`]: ../filter/index.html
+    pub addr_filter: AddrFilter,
+
+    /// Answer queries for special-use domain names locally.
+    ///
+    /// If enabled, queries for `localhost.` and the names below it as well
+    /// as for names below `invalid.` and `onion.` are never sent upstream.
+    /// See the [`filter`] module for details.
+    ///
+    /// [`filter`]: ../filter/index.html
+    pub special_names: bool,
+
+    /// Domain names for which queries are blocked.
+    ///
+    /// Queries for these names and the names below them are answered with
+    /// NXDOMAIN without being sent upstream.
+    pub blocked_suffixes: Vec<SearchSuffix>,
+
+    /// Local zones and records answered without going upstream.
+    ///
+    /// If present, queries are answered from the local zones where these
+    /// provide an answer. Queries that a local zone drops or refuses are
+    /// answered with REFUSED. See the [`localzone`] module for details.
+    ///
+    /// [`localzone`]: ../../../localzone/index.html
+    pub local_zones: Option<Arc<LocalZones>>,
+
+    /// The maximum number of answers to keep in the cache.
+    ///
+    /// If this is zero, answers are not cached at all. See the [`cache`]
+    /// module for how answers are cached.
+    ///
+    /// [`cache`]: ../cache/index.html
+    pub cache_size: usize,
+
+    /// The maximum memory in octets used by the answers in the cache.
+    ///
+    /// If this is `None`, the cache is only limited by `cache_size`.
+    pub cache_memory: Option<usize>,
+
+    /// The order in which answers are dropped when the cache is full.
+    pub cache_eviction: EvictionPolicy,
+
+    /// The file to keep the cache in.
+    ///
+    /// If this is `None`, the cache is only kept in memory. Otherwise,
+    /// answers are stored in the file and loaded from it when the resolver
+    /// is created so they survive restarts. The option is ignored if
+    /// `cache_size` is zero. If the file can’t be opened, the resolver
+    /// falls back to a cache in memory.
+    pub cache_file: Option<PathBuf>,
+
     /// Use bit-label format for IPv6 reverse lookups.
     ///
     /// Bit labels have been deprecated and consequently, this option is not
@@ -160,9 +323,7 @@ pub struct ResolvOptions {
 
     /// Don’t look up unqualified names as top-level-domain.
     ///
-    /// This is not currently implemented. Instead, the resolver config’s
-    /// `search` and `ndots` fields govern resolution of relative names of
-    /// all kinds.
+    /// This option is implemented by `search_host()`.
     pub no_tld_query: bool,
 }
 
@@ -171,10 +332,27 @@ impl Default for ResolvOptions {
         ResolvOptions {
             // non-flags:
             search: SearchList::new(),
-            //sortlist,
+            sortlist: SortList::new(),
+            addr_order: AddrOrder::default(),
             ndots: 1,
+            search_mode: SearchMode::default(),
             timeout: Duration::new(5, 0),
             attempts: 2,
+            blast_servers: 2,
+            blast_stagger: Duration::from_secs(0),
+            bind_v4: None,
+            bind_v6: None,
+            interface: None,
+            rate_limit: None,
+            server_rate_limit: None,
+            addr_filter: AddrFilter::None,
+            special_names: false,
+            blocked_suffixes: Vec::new(),
+            local_zones: None,
+            cache_size: 0,
+            cache_memory: None,
+            cache_eviction: EvictionPolicy::default(),
+            cache_file: None,
 
             // enabled by default:
             recurse: true,
@@ -192,6 +370,7 @@ impl Default for ResolvOptions {
             no_check_name: false,
             keep_tsig: false,
             blast: false,
+            randomize_case: false,
             use_bstring: false,
             use_ip6dotint: false,
             use_edns0: false,
@@ -236,6 +415,38 @@ impl Transport {
     }
 }
 
+//------------ RateLimit -----------------------------------------------------
+
+/// A limit for the rate of outgoing queries.
+///
+/// The limit is enforced with a token bucket: up to `burst` queries can be
+/// sent right away after a quiet period, after that queries are spread out
+/// to `qps` queries per second.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RateLimit {
+    /// The number of queries per second.
+    ///
+    /// A value of zero is treated as one.
+    pub qps: u32,
+
+    /// The number of queries that can be sent at once.
+    ///
+    /// A value of zero is treated as one.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a new limit with a burst the size of one second’s queries.
+    pub fn new(qps: u32) -> Self {
+        RateLimit { qps, burst: qps }
+    }
+
+    /// Creates a new limit with the given burst size.
+    pub fn with_burst(qps: u32, burst: u32) -> Self {
+        RateLimit { qps, burst }
+    }
+}
+
 //------------ ServerConf ----------------------------------------------------
 
 /// Configuration for one upstream DNS server.
@@ -275,8 +486,36 @@ pub struct ServerConf {
     ///
     /// This values will be announced in request if EDNS is supported by the
     /// server. It will be included both for datagram and streaming transport
-    /// but really only matters for UDP.
+    /// but really only matters for UDP. It defaults to 1232 bytes as
+    /// recommended by DNS Flag Day 2020.
+    ///
+    /// For UDP servers, this is the maximum size. The resolver reduces it
+    /// if responses seem to get lost due to fragmentation.
     pub udp_payload_size: u16,
+
+    /// The local address to send queries from.
+    ///
+    /// If this is `None`, the resolver option [`bind_v4`] or [`bind_v6`],
+    /// respectively, is used. The port should normally be zero so that a
+    /// random port is used for each query.
+    ///
+    /// [`bind_v4`]: struct.ResolvOptions.html#structfield.bind_v4
+    /// [`bind_v6`]: struct.ResolvOptions.html#structfield.bind_v6
+    pub bind_addr: Option<SocketAddr>,
+
+    /// The network interface to send queries through.
+    ///
+    /// If this is `None`, the resolver option [`interface`] is used.
+    ///
+    /// [`interface`]: struct.ResolvOptions.html#structfield.interface
+    pub interface: Option<String>,
+
+    /// A proxy to send queries through.
+    ///
+    /// Proxies are only supported for stream transports. Queries to a
+    /// server with a datagram transport and a proxy fail rather than
+    /// bypass the proxy.
+    pub proxy: Option<ProxyConf>,
 }
 
 impl ServerConf {
@@ -291,10 +530,77 @@ impl ServerConf {
             request_timeout: Duration::from_secs(2),
             recv_size: 1232,
             udp_payload_size: 1232,
+            bind_addr: None,
+            interface: None,
+            proxy: None,
+        }
+    }
+}
+
+//------------ ProxyConf -----------------------------------------------------
+
+/// The configuration of a proxy for a server.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProxyConf {
+    /// The proxy protocol.
+    pub protocol: ProxyProtocol,
+
+    /// The address of the proxy.
+    pub addr: SocketAddr,
+
+    /// The credentials for authenticating with the proxy, if required.
+    pub auth: Option<ProxyAuth>,
+}
+
+impl ProxyConf {
+    /// Creates a new proxy config without authentication.
+    pub fn new(protocol: ProxyProtocol, addr: SocketAddr) -> Self {
+        ProxyConf {
+            protocol,
+            addr,
+            auth: None,
         }
     }
 }
 
+//------------ ProxyProtocol -------------------------------------------------
+
+/// The protocol used to talk to a proxy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ProxyProtocol {
+    /// A SOCKS5 proxy as defined in RFC 1928.
+    ///
+    /// This is also the protocol used by Tor.
+    Socks5,
+
+    /// An HTTP proxy using the CONNECT method.
+    HttpConnect,
+}
+
+//------------ ProxyAuth -----------------------------------------------------
+
+/// User name and password for authenticating with a proxy.
+///
+/// For SOCKS5, the credentials are used with the method defined in RFC
+/// 1929. For HTTP, they are sent using basic authentication.
+#[derive(Clone, Eq, PartialEq)]
+pub struct ProxyAuth {
+    /// The user name.
+    pub username: String,
+
+    /// The password.
+    pub password: String,
+}
+
+impl fmt::Debug for ProxyAuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &"<hidden>")
+            .finish()
+    }
+}
+
 //------------ ResolvConf ---------------------------------------------------
 
 /// Resolver configuration.
@@ -408,8 +714,7 @@ impl ResolvConf {
                 Some("nameserver") => self.parse_nameserver(words)?,
                 Some("domain") => self.parse_domain(words)?,
                 Some("search") => self.parse_search(words)?,
-                Some("sortlist") => { /* TODO: self.parse_sortlist(words)? */
-                }
+                Some("sortlist") => self.parse_sortlist(words)?,
                 Some("options") => self.parse_options(words)?,
                 _ => return Err(Error::ParseError),
             }
@@ -456,14 +761,17 @@ impl ResolvConf {
         Ok(())
     }
 
-    /*
     fn parse_sortlist(
         &mut self,
-        _words: SplitWhitespace
+        words: SplitWhitespace,
     ) -> Result<(), Error> {
-        // XXX TODO
+        let mut sortlist = SortList::new();
+        for word in words {
+            sortlist.push(SortListEntry::from_str(word)?);
+        }
+        self.options.sortlist = sortlist;
+        Ok(())
     }
-    */
 
     #[allow(clippy::match_same_arms)]
     fn parse_options(&mut self, words: SplitWhitespace) -> Result<(), Error> {
@@ -521,6 +829,13 @@ impl fmt::Display for ResolvConf {
             }
             "\n".fmt(f)?;
         }
+        if !self.options.sortlist.is_empty() {
+            "sortlist".fmt(f)?;
+            for entry in self.options.sortlist.as_slice() {
+                write!(f, " {}", entry)?;
+            }
+            "\n".fmt(f)?;
+        }
         match self.options.search.len().cmp(&1) {
             Ordering::Equal => {
                 writeln!(f, "domain {}", self.options.search[0])?;
@@ -589,6 +904,9 @@ impl fmt::Display for ResolvConf {
         if self.options.blast {
             options.push("blast".into())
         }
+        if self.options.randomize_case {
+            options.push("randomize-case".into())
+        }
         if self.options.use_bstring {
             options.push("use-bstring".into())
         }
@@ -677,6 +995,203 @@ impl ops::Deref for SearchList {
     }
 }
 
+//------------ SortList ------------------------------------------------------
+
+/// A list of networks for sorting addresses.
+///
+/// When sorting addresses, those matching an earlier entry in the list are
+/// sorted before those matching a later entry. Addresses that don’t match
+/// any entry are sorted last. The order of addresses matching the same
+/// entry is kept.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SortList {
+    entries: Vec<SortListEntry>,
+}
+
+impl SortList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, entry: SortListEntry) {
+        self.entries.push(entry)
+    }
+
+    pub fn as_slice(&self) -> &[SortListEntry] {
+        self.as_ref()
+    }
+
+    /// Returns the position of the first entry matching `addr`.
+    ///
+    /// Returns the length of the list if no entry matches.
+    pub fn rank(&self, addr: IpAddr) -> usize {
+        self.entries
+            .iter()
+            .position(|entry| entry.matches(addr))
+            .unwrap_or(self.entries.len())
+    }
+
+    /// Sorts a slice of addresses according to the list.
+    pub fn sort(&self, addrs: &mut [IpAddr]) {
+        if !self.entries.is_empty() {
+            addrs.sort_by_key(|addr| self.rank(*addr))
+        }
+    }
+}
+
+//--- AsRef and Deref
+
+impl AsRef<[SortListEntry]> for SortList {
+    fn as_ref(&self) -> &[SortListEntry] {
+        self.entries.as_ref()
+    }
+}
+
+impl ops::Deref for SortList {
+    type Target = [SortListEntry];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+//------------ AddrOrder -----------------------------------------------------
+
+/// The order of the addresses returned by a host lookup.
+///
+/// In all orders, IPv6 addresses come before IPv4 addresses. A sort list,
+/// if present, is applied afterwards and only changes the order of
+/// addresses matching different entries.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AddrOrder {
+    /// Keep the order of the records in the answer.
+    ///
+    /// This is what `gethostbyname` does. It preserves the rotation of
+    /// records performed by many servers for load balancing.
+    Answer,
+
+    /// Sort the addresses by their value.
+    ///
+    /// The order is the same for every lookup as long as the set of
+    /// addresses doesn’t change.
+    Sorted,
+
+    /// Shuffle the addresses randomly.
+    ///
+    /// This spreads clients across all addresses even if the server
+    /// doesn’t rotate its records.
+    Shuffle,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] needs Rust 1.62.
+impl Default for AddrOrder {
+    fn default() -> Self {
+        AddrOrder::Answer
+    }
+}
+
+//------------ SortListEntry -------------------------------------------------
+
+/// A network in a sort list.
+///
+/// In the configuration file, an entry is given as an address optionally
+/// followed by a slash and either a netmask or a prefix length. If it is
+/// missing, the natural netmask of the address’ class is used for IPv4
+/// addresses as glibc does, while IPv6 addresses match only themselves.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SortListEntry {
+    /// The network address.
+    addr: IpAddr,
+
+    /// The length of the network prefix in bits.
+    prefix_len: u8,
+}
+
+impl SortListEntry {
+    /// Creates a new entry from an address and a prefix length.
+    ///
+    /// Returns `None` if the prefix length is too long for the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max {
+            None
+        } else {
+            Some(SortListEntry { addr, prefix_len })
+        }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns whether `addr` is within the network of the entry.
+    pub fn matches(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the natural prefix length of an IPv4 address.
+    fn natural_prefix_len(addr: Ipv4Addr) -> u8 {
+        match addr.octets()[0] {
+            0..=127 => 8,
+            128..=191 => 16,
+            _ => 24,
+        }
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for SortListEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.splitn(2, '/');
+        let addr = IpAddr::from_str(parts.next().unwrap())
+            .map_err(|_| Error::ParseError)?;
+        let prefix_len = match (parts.next(), addr) {
+            (None, IpAddr::V4(addr)) => Self::natural_prefix_len(addr),
+            (None, IpAddr::V6(_)) => 128,
+            (Some(mask), IpAddr::V4(_)) if mask.contains('.') => {
+                let mask = u32::from(
+                    Ipv4Addr::from_str(mask)
+                        .map_err(|_| Error::ParseError)?,
+                );
+                let ones = (!mask).leading_zeros();
+                if ones + mask.trailing_zeros() != 32 {
+                    return Err(Error::ParseError);
+                }
+                ones as u8
+            }
+            (Some(len), _) => u8::from_str(len)?,
+        };
+        Self::new(addr, prefix_len).ok_or(Error::ParseError)
+    }
+}
+
+impl fmt::Display for SortListEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
 //------------ Private Helpers -----------------------------------------------
 //
 // These are here to wrap stuff into Results.
@@ -773,4 +1288,40 @@ mod test {
         assert!(conf.options.use_vc);
         assert_eq!(conf.options.ndots, 122);
     }
+
+    #[test]
+    fn parse_sortlist() {
+        let mut conf = ResolvConf::new();
+        let data = "sortlist 130.155.160.0/255.255.240.0 130.155.0.0 \
+                    2001:db8::/32\n"
+            .to_string();
+        assert!(conf.parse(&mut io::Cursor::new(data)).is_ok());
+        let sortlist = &conf.options.sortlist;
+        assert_eq!(
+            sortlist
+                .iter()
+                .map(SortListEntry::prefix_len)
+                .collect::<Vec<_>>(),
+            [20, 16, 32]
+        );
+
+        let mut addrs: Vec<IpAddr> = vec![
+            "192.0.2.1".parse().unwrap(),
+            "130.155.1.1".parse().unwrap(),
+            "2001:db8::1".parse().unwrap(),
+            "130.155.161.1".parse().unwrap(),
+        ];
+        sortlist.sort(&mut addrs);
+        assert_eq!(
+            addrs,
+            [
+                "130.155.161.1".parse::<IpAddr>().unwrap(),
+                "130.155.1.1".parse().unwrap(),
+                "2001:db8::1".parse().unwrap(),
+                "192.0.2.1".parse().unwrap(),
+            ]
+        );
+
+        assert!(SortListEntry::from_str("10.0.0.0/255.0.255.0").is_err());
+    }
 }
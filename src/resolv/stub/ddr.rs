@@ -0,0 +1,543 @@
+//! Discovery of Designated Resolvers.
+//!
+//! [RFC 9462] allows a client that knows an upstream resolver only by its
+//! IP address to learn about encrypted endpoints – DNS over TLS, DNS over
+//! HTTPS, or DNS over QUIC – offered by the same operator. The client asks
+//! the resolver for the SVCB records of the special name
+//! `_dns.resolver.arpa.` and receives a list of _designated resolvers._
+//!
+//! The stub resolver performs this discovery for each of its configured
+//! servers through [`StubResolver::discover_designated`]. Discovery is
+//! opt-in: nothing happens unless the method is called. Since the stub
+//! resolver itself only speaks DNS over UDP and TCP, it does not switch
+//! over to the designated resolvers. Instead, the returned [`Discovery`]
+//! values describe the endpoints and how each of them needs to be verified
+//! so that an application with an encrypted transport can upgrade.
+//!
+//! The RFC describes two ways to verify a designated resolver:
+//!
+//! * With _verified discovery,_ the TLS certificate presented by the
+//!   designated resolver must – in addition to being valid for the
+//!   resolver’s name – contain the IP address of the unencrypted resolver
+//!   the discovery query was sent to. [`DesignatedResolver::verify_certificate`]
+//!   performs this check on the addresses taken from the certificate.
+//! * With _opportunistic discovery,_ the certificate doesn’t need to be
+//!   validated. This is only allowed if the unencrypted resolver has a
+//!   private or local address and the designated resolver uses that same
+//!   address.
+//!
+//! [`DesignatedResolver::verification`] tells which of the two applies.
+//!
+//! [RFC 9462]: https://tools.ietf.org/html/rfc9462
+//! [`StubResolver::discover_designated`]: ../struct.StubResolver.html#method.discover_designated
+//! [`Discovery`]: struct.Discovery.html
+//! [`DesignatedResolver::verify_certificate`]: struct.DesignatedResolver.html#method.verify_certificate
+//! [`DesignatedResolver::verification`]: struct.DesignatedResolver.html#method.verification
+
+use super::entropy::Entropy;
+use super::{Query, ServerInfo};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{Parse, ParseError, Parser};
+use crate::base::question::Question;
+use crate::base::rdata::UnknownRecordData;
+use bytes::Bytes;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The wire format of the name queried for designated resolvers.
+const RESOLVER_ARPA: &[u8] = b"\x04_dns\x08resolver\x04arpa\x00";
+
+/// The SvcParamKey values we understand.
+const KEY_MANDATORY: u16 = 0;
+const KEY_ALPN: u16 = 1;
+const KEY_NO_DEFAULT_ALPN: u16 = 2;
+const KEY_PORT: u16 = 3;
+const KEY_IPV4HINT: u16 = 4;
+const KEY_IPV6HINT: u16 = 6;
+const KEY_DOHPATH: u16 = 7;
+
+//------------ Discovery -----------------------------------------------------
+
+/// The designated resolvers of one upstream server.
+#[derive(Clone, Debug)]
+pub struct Discovery {
+    /// The address of the unencrypted resolver that was asked.
+    pub resolver: SocketAddr,
+
+    /// The designated resolvers in order of preference.
+    ///
+    /// This is empty if the resolver doesn’t designate any.
+    pub designated: Vec<DesignatedResolver>,
+}
+
+//------------ DesignatedResolver --------------------------------------------
+
+/// A designated resolver as described by a single SVCB record.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesignatedResolver {
+    /// The priority of the record. Lower values are preferred.
+    priority: u16,
+
+    /// The name of the designated resolver.
+    target: Dname<Bytes>,
+
+    /// The protocols offered by the designated resolver.
+    protocols: Vec<Protocol>,
+
+    /// The port given in the record, if any.
+    port: Option<u16>,
+
+    /// The addresses given in the record.
+    addrs: Vec<IpAddr>,
+
+    /// The URI template for DNS over HTTPS, if any.
+    dohpath: Option<String>,
+}
+
+impl DesignatedResolver {
+    /// Extracts the designated resolvers from an answer.
+    ///
+    /// The answer should be the response to a query for the SVCB records of
+    /// `_dns.resolver.arpa.` Records in alias mode, records with mandatory
+    /// parameters we don’t understand, and records without any protocol we
+    /// know are skipped. The result is ordered by priority.
+    pub fn from_answer(answer: &Message<Bytes>) -> Vec<Self> {
+        let mut res = Vec::new();
+        if answer.header().rcode() != Rcode::NoError {
+            return res;
+        }
+        let qname = Dname::from_slice(RESOLVER_ARPA).unwrap();
+        let records = match answer.answer() {
+            Ok(records) => records.limit_to::<UnknownRecordData<Bytes>>(),
+            Err(_) => return res,
+        };
+        for record in records {
+            let record = match record {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            if record.data().rtype() != Rtype::Svcb
+                || !record.owner().name_eq(&qname)
+            {
+                continue;
+            }
+            if let Ok(Some(item)) = Self::from_rdata(record.data().data()) {
+                res.push(item)
+            }
+        }
+        res.sort_by_key(|item| item.priority);
+        res
+    }
+
+    /// Parses the record data of an SVCB record.
+    ///
+    /// Returns `Ok(None)` if the record should be ignored.
+    fn from_rdata(data: &Bytes) -> Result<Option<Self>, ParseError> {
+        let mut parser = Parser::from_ref(data);
+        let priority = u16::parse(&mut parser)?;
+        let target = Dname::parse(&mut parser)?;
+        if priority == 0 {
+            // Alias mode isn’t used for DDR.
+            return Ok(None);
+        }
+        let mut res = DesignatedResolver {
+            priority,
+            target,
+            protocols: Vec::new(),
+            port: None,
+            addrs: Vec::new(),
+            dohpath: None,
+        };
+        let mut mandatory = Vec::new();
+        while parser.remaining() > 0 {
+            let key = u16::parse(&mut parser)?;
+            let len = usize::from(u16::parse(&mut parser)?);
+            let value = parser.parse_octets(len)?;
+            let value = value.as_ref();
+            match key {
+                KEY_MANDATORY => {
+                    mandatory.extend(value.chunks_exact(2).map(|chunk| {
+                        u16::from_be_bytes([chunk[0], chunk[1]])
+                    }))
+                }
+                KEY_ALPN => {
+                    let mut value = value;
+                    while let Some((&len, rest)) = value.split_first() {
+                        let len = usize::from(len);
+                        if rest.len() < len {
+                            return Err(ParseError::ShortInput);
+                        }
+                        if let Some(protocol) =
+                            Protocol::from_alpn(&rest[..len])
+                        {
+                            if !res.protocols.contains(&protocol) {
+                                res.protocols.push(protocol)
+                            }
+                        }
+                        value = &rest[len..];
+                    }
+                }
+                KEY_PORT => {
+                    if value.len() != 2 {
+                        return Err(ParseError::form_error("invalid port"));
+                    }
+                    res.port = Some(u16::from_be_bytes([value[0], value[1]]));
+                }
+                KEY_IPV4HINT => {
+                    res.addrs.extend(value.chunks_exact(4).map(|chunk| {
+                        IpAddr::from([chunk[0], chunk[1], chunk[2], chunk[3]])
+                    }))
+                }
+                KEY_IPV6HINT => {
+                    res.addrs.extend(value.chunks_exact(16).map(|chunk| {
+                        let mut addr = [0u8; 16];
+                        addr.copy_from_slice(chunk);
+                        IpAddr::from(addr)
+                    }))
+                }
+                KEY_DOHPATH => {
+                    res.dohpath =
+                        Some(String::from_utf8_lossy(value).into_owned())
+                }
+                _ => {}
+            }
+        }
+        if mandatory.iter().any(|key| !Self::is_known_key(*key)) {
+            return Ok(None);
+        }
+        if res.dohpath.is_none() {
+            res.protocols.retain(|protocol| *protocol != Protocol::Doh);
+        }
+        if res.protocols.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(res))
+    }
+
+    fn is_known_key(key: u16) -> bool {
+        matches!(
+            key,
+            KEY_ALPN
+                | KEY_NO_DEFAULT_ALPN
+                | KEY_PORT
+                | KEY_IPV4HINT
+                | KEY_IPV6HINT
+                | KEY_DOHPATH
+        )
+    }
+
+    /// Returns the priority of the designated resolver.
+    ///
+    /// Lower values are preferred.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// Returns the name of the designated resolver.
+    ///
+    /// This is the name the resolver’s TLS certificate has to be valid for.
+    pub fn target(&self) -> &Dname<Bytes> {
+        &self.target
+    }
+
+    /// Returns the protocols offered by the designated resolver.
+    pub fn protocols(&self) -> &[Protocol] {
+        &self.protocols
+    }
+
+    /// Returns the URI template for DNS over HTTPS.
+    pub fn dohpath(&self) -> Option<&str> {
+        self.dohpath.as_deref()
+    }
+
+    /// Returns the addresses of the designated resolver given as hints.
+    ///
+    /// If there are no hints, the addresses have to be looked up via the
+    /// name returned by [`target`].
+    ///
+    /// [`target`]: #method.target
+    pub fn addrs(&self) -> &[IpAddr] {
+        &self.addrs
+    }
+
+    /// Returns the socket addresses to use for `protocol`.
+    pub fn endpoints(&self, protocol: Protocol) -> Vec<SocketAddr> {
+        if !self.protocols.contains(&protocol) {
+            return Vec::new();
+        }
+        let port = self.port.unwrap_or_else(|| protocol.default_port());
+        self.addrs
+            .iter()
+            .map(|addr| SocketAddr::new(*addr, port))
+            .collect()
+    }
+
+    /// Returns how the designated resolver needs to be verified.
+    ///
+    /// The `resolver` is the address of the unencrypted resolver the
+    /// discovery query was sent to.
+    pub fn verification(&self, resolver: IpAddr) -> Verification {
+        if is_local(resolver) && self.addrs.contains(&resolver) {
+            Verification::Opportunistic
+        } else {
+            Verification::Certificate
+        }
+    }
+
+    /// Checks the addresses contained in the designated resolver’s
+    /// certificate.
+    ///
+    /// The `cert_addrs` are the IP addresses listed in the subject
+    /// alternative name extension of the certificate presented by the
+    /// designated resolver. Verification succeeds if the address of the
+    /// unencrypted resolver is among them. The certificate must
+    /// additionally have been validated for the name returned by
+    /// [`target`].
+    ///
+    /// [`target`]: #method.target
+    pub fn verify_certificate<I: IntoIterator<Item = IpAddr>>(
+        &self,
+        resolver: IpAddr,
+        cert_addrs: I,
+    ) -> bool {
+        cert_addrs.into_iter().any(|addr| addr == resolver)
+    }
+}
+
+//------------ Protocol ------------------------------------------------------
+
+/// An encrypted protocol offered by a designated resolver.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Protocol {
+    /// DNS over TLS.
+    Dot,
+
+    /// DNS over HTTPS.
+    Doh,
+
+    /// DNS over QUIC.
+    Doq,
+}
+
+impl Protocol {
+    /// Returns the protocol for an ALPN protocol identifier.
+    fn from_alpn(alpn: &[u8]) -> Option<Self> {
+        match alpn {
+            b"dot" => Some(Protocol::Dot),
+            b"h2" | b"h3" => Some(Protocol::Doh),
+            b"doq" => Some(Protocol::Doq),
+            _ => None,
+        }
+    }
+
+    /// Returns the port used if the record doesn’t specify one.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Protocol::Dot | Protocol::Doq => 853,
+            Protocol::Doh => 443,
+        }
+    }
+}
+
+//------------ Verification --------------------------------------------------
+
+/// How a designated resolver needs to be verified.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verification {
+    /// The certificate must be validated and contain the resolver address.
+    ///
+    /// See [`DesignatedResolver::verify_certificate`].
+    ///
+    /// [`DesignatedResolver::verify_certificate`]: struct.DesignatedResolver.html#method.verify_certificate
+    Certificate,
+
+    /// The designated resolver may be used without validating it.
+    Opportunistic,
+}
+
+//------------ discover ------------------------------------------------------
+
+/// Asks each of the servers for its designated resolvers.
+///
+/// Servers are asked only once per IP address. Servers that fail to
+/// answer are left out.
+pub(super) async fn discover<'a, I>(
+    servers: I,
+    entropy: &Entropy,
+) -> Vec<Discovery>
+where
+    I: Iterator<Item = &'a ServerInfo>,
+{
+    let qname = Dname::from_slice(RESOLVER_ARPA).unwrap();
+    let mut message =
+        Query::create_message(Question::new_in(qname, Rtype::Svcb));
+    let mut res: Vec<Discovery> = Vec::new();
+    for server in servers {
+        let addr = server.conf.addr;
+        if res.iter().any(|item| item.resolver.ip() == addr.ip()) {
+            continue;
+        }
+        server.prepare_message(&mut message, entropy);
+        let answer = match server.query_and_record(&message, entropy).await {
+            Ok(answer) => answer,
+            Err(_) => continue,
+        };
+        res.push(Discovery {
+            resolver: addr,
+            designated: DesignatedResolver::from_answer(answer.as_ref()),
+        })
+    }
+    res
+}
+
+/// Returns whether an address is private or local.
+fn is_local(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => is_local_v4(addr),
+        IpAddr::V6(addr) => {
+            let first = addr.segments()[0];
+            addr.is_loopback()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || is_mapped_local_v4(addr)
+        }
+    }
+}
+
+fn is_local_v4(addr: Ipv4Addr) -> bool {
+    addr.is_private() || addr.is_loopback() || addr.is_link_local()
+}
+
+fn is_mapped_local_v4(addr: Ipv6Addr) -> bool {
+    match addr.segments() {
+        [0, 0, 0, 0, 0, 0xffff, _, _] => {
+            let octets = addr.octets();
+            is_local_v4(Ipv4Addr::new(
+                octets[12], octets[13], octets[14], octets[15],
+            ))
+        }
+        _ => false,
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::octets::Compose;
+    use std::string::ToString;
+
+    fn svcb(
+        priority: u16,
+        params: &[(u16, &[u8])],
+    ) -> UnknownRecordData<Bytes> {
+        let mut data = Vec::new();
+        priority.compose(&mut data).unwrap();
+        data.extend_from_slice(b"\x03dns\x07example\x00");
+        for (key, value) in params {
+            key.compose(&mut data).unwrap();
+            (value.len() as u16).compose(&mut data).unwrap();
+            data.extend_from_slice(value);
+        }
+        UnknownRecordData::from_octets(Rtype::Svcb, data.into())
+    }
+
+    fn answer(records: Vec<UnknownRecordData<Bytes>>) -> Message<Bytes> {
+        let qname = Dname::from_slice(RESOLVER_ARPA).unwrap();
+        let mut msg = MessageBuilder::new_bytes().question().answer();
+        for data in records {
+            msg.push((qname, 300, data)).unwrap();
+        }
+        msg.into_message()
+    }
+
+    #[test]
+    fn from_answer() {
+        let res = DesignatedResolver::from_answer(&answer(vec![
+            svcb(
+                2,
+                &[
+                    (KEY_ALPN, b"\x02h2\x03foo"),
+                    (
+                        KEY_IPV6HINT,
+                        &[
+                            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0,
+                            0, 0, 0, 1,
+                        ],
+                    ),
+                    (KEY_DOHPATH, b"/dns-query{?dns}"),
+                ],
+            ),
+            svcb(
+                1,
+                &[
+                    (KEY_ALPN, b"\x03dot\x03doq"),
+                    (KEY_PORT, b"\x21\x4e"),
+                    (KEY_IPV4HINT, &[192, 168, 1, 1, 192, 0, 2, 1]),
+                ],
+            ),
+            // Alias mode.
+            svcb(0, &[]),
+            // Unknown mandatory key.
+            svcb(3, &[(KEY_MANDATORY, b"\x00\x63"), (KEY_ALPN, b"\x03dot")]),
+            // DoH without a path.
+            svcb(4, &[(KEY_ALPN, b"\x02h2")]),
+        ]));
+        assert_eq!(res.len(), 2);
+
+        let dot = &res[0];
+        assert_eq!(dot.priority(), 1);
+        assert_eq!(dot.target().to_string(), "dns.example");
+        assert_eq!(dot.protocols(), &[Protocol::Dot, Protocol::Doq]);
+        assert_eq!(
+            dot.endpoints(Protocol::Dot),
+            vec![
+                "192.168.1.1:8526".parse::<SocketAddr>().unwrap(),
+                "192.0.2.1:8526".parse().unwrap(),
+            ]
+        );
+        assert!(dot.endpoints(Protocol::Doh).is_empty());
+
+        let doh = &res[1];
+        assert_eq!(doh.protocols(), &[Protocol::Doh]);
+        assert_eq!(doh.dohpath(), Some("/dns-query{?dns}"));
+        assert_eq!(
+            doh.endpoints(Protocol::Doh),
+            vec!["[2001:db8::1]:443".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn verification() {
+        let res = DesignatedResolver::from_answer(&answer(vec![svcb(
+            1,
+            &[
+                (KEY_ALPN, b"\x03dot"),
+                (KEY_IPV4HINT, &[192, 168, 1, 1, 192, 0, 2, 1]),
+            ],
+        )]));
+        let item = &res[0];
+        let private: IpAddr = "192.168.1.1".parse().unwrap();
+        let public: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.1".parse().unwrap();
+
+        // Only a private resolver address used by the designated resolver
+        // itself allows opportunistic use.
+        assert_eq!(item.verification(private), Verification::Opportunistic);
+        assert_eq!(item.verification(public), Verification::Certificate);
+        assert_eq!(item.verification(other), Verification::Certificate);
+        assert!(is_local("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(is_local("fd00::1".parse().unwrap()));
+        assert!(!is_local("2001:db8::1".parse().unwrap()));
+
+        assert!(item.verify_certificate(public, vec![other, public]));
+        assert!(!item.verify_certificate(public, vec![other]));
+    }
+}
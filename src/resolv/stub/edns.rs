@@ -0,0 +1,192 @@
+//! Choosing the EDNS UDP payload size for upstream servers.
+//!
+//! Following [DNS Flag Day 2020], the stub resolver advertises a UDP
+//! payload size of [`DEFAULT_PAYLOAD_SIZE`] bytes by default which avoids
+//! IP fragmentation on virtually all paths. Since fragmented responses may
+//! still get lost – particularly if a larger size has been configured –,
+//! the resolver keeps track of the size that actually works for each
+//! upstream server.
+//!
+//! Lost fragments show up as timeouts for queries with large responses
+//! while queries with small responses are answered just fine. So if a
+//! server has answered at its current size but then [`DOWNGRADE_AFTER`]
+//! queries time out without a large answer in between, the size is
+//! reduced: first to [`DEFAULT_PAYLOAD_SIZE`] and then to
+//! [`MIN_PAYLOAD_SIZE`]. A server that doesn’t answer at all is left
+//! alone. After [`PROBE_INTERVAL`] has passed, the configured size is
+//! probed again.
+//!
+//! [DNS Flag Day 2020]: https://dnsflagday.net/2020/
+//! [`DEFAULT_PAYLOAD_SIZE`]: constant.DEFAULT_PAYLOAD_SIZE.html
+//! [`MIN_PAYLOAD_SIZE`]: constant.MIN_PAYLOAD_SIZE.html
+//! [`DOWNGRADE_AFTER`]: constant.DOWNGRADE_AFTER.html
+//! [`PROBE_INTERVAL`]: constant.PROBE_INTERVAL.html
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The default UDP payload size as recommended by DNS Flag Day 2020.
+pub const DEFAULT_PAYLOAD_SIZE: u16 = 1232;
+
+/// The smallest UDP payload size we ever advertise.
+pub const MIN_PAYLOAD_SIZE: u16 = 512;
+
+/// The number of timeouts after which the payload size is reduced.
+pub const DOWNGRADE_AFTER: u32 = 2;
+
+/// The time after which a reduced payload size is raised again.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(600);
+
+//------------ PayloadSize ---------------------------------------------------
+
+/// The UDP payload size to use for a single upstream server.
+#[derive(Debug)]
+pub(super) struct PayloadSize {
+    /// The configured payload size.
+    configured: u16,
+
+    /// The current state.
+    inner: Mutex<PayloadInner>,
+}
+
+#[derive(Debug)]
+struct PayloadInner {
+    /// The payload size currently in use.
+    current: u16,
+
+    /// Whether the server has answered at the current size.
+    answered: bool,
+
+    /// The number of timeouts since the last large answer.
+    timeouts: u32,
+
+    /// When to return to the configured size after a downgrade.
+    probe_at: Option<Instant>,
+}
+
+impl PayloadSize {
+    /// Creates a new value starting out with the configured size.
+    pub fn new(configured: u16) -> Self {
+        let configured = configured.max(MIN_PAYLOAD_SIZE);
+        PayloadSize {
+            configured,
+            inner: Mutex::new(PayloadInner {
+                current: configured,
+                answered: false,
+                timeouts: 0,
+                probe_at: None,
+            }),
+        }
+    }
+
+    /// Returns the payload size to advertise in the next query.
+    pub fn get(&self) -> u16 {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(probe_at) = inner.probe_at {
+            if probe_at <= Instant::now() {
+                inner.reset(self.configured);
+            }
+        }
+        inner.current
+    }
+
+    /// Records an answer of `len` octets received over UDP.
+    pub fn record_answer(&self, len: usize) {
+        let mut inner = self.inner.lock().unwrap();
+
+        // Timeouts before the first answer mean the server was unreachable
+        // rather than anything about the payload size.
+        if !inner.answered {
+            inner.answered = true;
+            inner.timeouts = 0;
+        }
+
+        // Only an answer that wouldn’t have fit into the next smaller size
+        // proves that the current size works.
+        if len > usize::from(Self::next_smaller(inner.current)) {
+            inner.timeouts = 0;
+        }
+    }
+
+    /// Records a query over UDP that timed out.
+    pub fn record_timeout(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.timeouts = inner.timeouts.saturating_add(1);
+        if inner.answered
+            && inner.timeouts >= DOWNGRADE_AFTER
+            && inner.current > MIN_PAYLOAD_SIZE
+        {
+            inner.current = Self::next_smaller(inner.current);
+            inner.answered = false;
+            inner.timeouts = 0;
+            inner.probe_at = Some(Instant::now() + PROBE_INTERVAL);
+        }
+    }
+
+    /// Returns the next smaller size to try after `size`.
+    fn next_smaller(size: u16) -> u16 {
+        if size > DEFAULT_PAYLOAD_SIZE {
+            DEFAULT_PAYLOAD_SIZE
+        } else {
+            MIN_PAYLOAD_SIZE
+        }
+    }
+}
+
+impl PayloadInner {
+    fn reset(&mut self, configured: u16) {
+        self.current = configured;
+        self.answered = false;
+        self.timeouts = 0;
+        self.probe_at = None;
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn downgrade() {
+        let size = PayloadSize::new(4096);
+        assert_eq!(size.get(), 4096);
+
+        // A server that never answered is not downgraded.
+        for _ in 0..DOWNGRADE_AFTER {
+            size.record_timeout();
+        }
+        assert_eq!(size.get(), 4096);
+
+        // Small answers and timeouts suggest lost fragments.
+        size.record_answer(100);
+        size.record_timeout();
+        size.record_answer(100);
+        assert_eq!(size.get(), 4096);
+        size.record_timeout();
+        assert_eq!(size.get(), DEFAULT_PAYLOAD_SIZE);
+
+        // A large answer shows the size works.
+        size.record_answer(1000);
+        size.record_timeout();
+        size.record_answer(1000);
+        size.record_timeout();
+        assert_eq!(size.get(), DEFAULT_PAYLOAD_SIZE);
+        size.record_timeout();
+        assert_eq!(size.get(), MIN_PAYLOAD_SIZE);
+
+        // We never go below the minimum.
+        size.record_answer(100);
+        for _ in 0..DOWNGRADE_AFTER {
+            size.record_timeout();
+        }
+        assert_eq!(size.get(), MIN_PAYLOAD_SIZE);
+
+        // Once the probe interval has passed, we go back to the start.
+        size.inner.lock().unwrap().probe_at = Some(Instant::now());
+        assert_eq!(size.get(), 4096);
+    }
+}
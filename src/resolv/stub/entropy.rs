@@ -0,0 +1,357 @@
+//! Randomness in outgoing queries.
+//!
+//! Off-path attackers trying to spoof answers to the stub resolver’s
+//! queries have to guess the values the resolver chose at random for each
+//! query. These are the message ID, the local port of UDP queries, and –
+//! if enabled via the [`randomize_case`] option – the case of the letters
+//! in the query name, a technique known as ‘DNS 0x20.’
+//!
+//! The resolver takes all these values from a [`RandomSource`]. By
+//! default, this is [`ThreadRandom`] which uses the cryptographically
+//! secure generator of the `rand` crate. Deployments with their own
+//! requirements can provide a different source via
+//! [`StubResolver::set_random_source`]. For tests, [`SeededRandom`]
+//! produces reproducible values.
+//!
+//! In order to verify the choices the resolver makes, every decision can
+//! be reported to an [`EntropyAudit`] given to
+//! [`StubResolver::set_entropy_audit`] as an [`EntropyDecision`]. The
+//! [`AuditLog`] simply keeps all decisions for later inspection.
+//!
+//! [`randomize_case`]: ../conf/struct.ResolvOptions.html#structfield.randomize_case
+//! [`RandomSource`]: trait.RandomSource.html
+//! [`ThreadRandom`]: struct.ThreadRandom.html
+//! [`SeededRandom`]: struct.SeededRandom.html
+//! [`EntropyAudit`]: trait.EntropyAudit.html
+//! [`EntropyDecision`]: enum.EntropyDecision.html
+//! [`AuditLog`]: struct.AuditLog.html
+//! [`StubResolver::set_random_source`]: ../struct.StubResolver.html#method.set_random_source
+//! [`StubResolver::set_entropy_audit`]: ../struct.StubResolver.html#method.set_entropy_audit
+
+use crate::base::name::Dname;
+use bytes::Bytes;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+//------------ RandomSource --------------------------------------------------
+
+/// A source for the random values used in queries.
+pub trait RandomSource: fmt::Debug + Send + Sync {
+    /// Fills `dest` with random octets.
+    fn fill_bytes(&self, dest: &mut [u8]);
+
+    /// Returns a random 16 bit value.
+    fn random_u16(&self) -> u16 {
+        let mut buf = [0u8; 2];
+        self.fill_bytes(&mut buf);
+        u16::from_ne_bytes(buf)
+    }
+}
+
+impl<R: RandomSource + ?Sized> RandomSource for Arc<R> {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        (**self).fill_bytes(dest)
+    }
+}
+
+//------------ ThreadRandom --------------------------------------------------
+
+/// A random source using the `rand` crate’s thread-local generator.
+///
+/// The generator is a cryptographically secure generator seeded from the
+/// operating system and regularly reseeded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ThreadRandom;
+
+impl RandomSource for ThreadRandom {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        rand::thread_rng().fill_bytes(dest)
+    }
+}
+
+//------------ SeededRandom --------------------------------------------------
+
+/// A random source producing the same values for the same seed.
+///
+/// This is useful for tests that need reproducible queries. Since the
+/// values are predictable, it must never be used otherwise.
+#[derive(Debug)]
+pub struct SeededRandom {
+    rng: Mutex<StdRng>,
+}
+
+impl SeededRandom {
+    /// Creates a new source from the given seed.
+    pub fn new(seed: u64) -> Self {
+        SeededRandom {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.rng.lock().unwrap().fill_bytes(dest)
+    }
+}
+
+//------------ EntropyDecision -----------------------------------------------
+
+/// A random choice made for an outgoing query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EntropyDecision {
+    /// The message ID of a query was chosen.
+    MessageId(u16),
+
+    /// The local port of a UDP query was chosen.
+    SourcePort {
+        /// The port the query is sent from.
+        port: u16,
+
+        /// Whether the port was left to the operating system.
+        ///
+        /// This happens if no randomly chosen port could be bound.
+        system: bool,
+    },
+
+    /// The case of the letters of a query name was randomized.
+    QnameCase {
+        /// The query name as sent.
+        qname: Dname<Bytes>,
+
+        /// The number of letters and thus random bits in the name.
+        bits: usize,
+    },
+}
+
+//------------ EntropyAudit --------------------------------------------------
+
+/// A receiver of the random choices made for outgoing queries.
+///
+/// The audit is called synchronously while the query is prepared, so it
+/// should return quickly.
+pub trait EntropyAudit: fmt::Debug + Send + Sync {
+    /// Records a decision.
+    fn record(&self, decision: &EntropyDecision);
+}
+
+impl<A: EntropyAudit + ?Sized> EntropyAudit for Arc<A> {
+    fn record(&self, decision: &EntropyDecision) {
+        (**self).record(decision)
+    }
+}
+
+//------------ AuditLog ------------------------------------------------------
+
+/// An audit keeping all decisions.
+///
+/// Clones of an audit log share their decisions, so a clone can be kept
+/// to inspect the decisions made by a resolver the log was given to.
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog {
+    decisions: Arc<Mutex<Vec<EntropyDecision>>>,
+}
+
+impl AuditLog {
+    /// Creates a new, empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the decisions recorded so far.
+    pub fn decisions(&self) -> Vec<EntropyDecision> {
+        self.decisions.lock().unwrap().clone()
+    }
+
+    /// Removes and returns the decisions recorded so far.
+    pub fn take(&self) -> Vec<EntropyDecision> {
+        std::mem::take(&mut *self.decisions.lock().unwrap())
+    }
+}
+
+impl EntropyAudit for AuditLog {
+    fn record(&self, decision: &EntropyDecision) {
+        self.decisions.lock().unwrap().push(decision.clone())
+    }
+}
+
+//------------ Entropy -------------------------------------------------------
+
+/// The randomness configuration of a resolver.
+#[derive(Clone, Debug)]
+pub(super) struct Entropy {
+    /// The source for random values.
+    random: Arc<dyn RandomSource>,
+
+    /// The audit to report decisions to if any.
+    audit: Option<Arc<dyn EntropyAudit>>,
+
+    /// Whether to randomize the case of query names.
+    randomize_case: bool,
+}
+
+impl Entropy {
+    /// The lowest port chosen for UDP queries.
+    const MIN_PORT: u16 = 1024;
+
+    /// Creates a new value using the default random source.
+    pub fn new(randomize_case: bool) -> Self {
+        Entropy {
+            random: Arc::new(ThreadRandom),
+            audit: None,
+            randomize_case,
+        }
+    }
+
+    /// Replaces the random source.
+    pub fn set_random(&mut self, random: Arc<dyn RandomSource>) {
+        self.random = random
+    }
+
+    /// Sets the audit to report decisions to.
+    pub fn set_audit(&mut self, audit: Arc<dyn EntropyAudit>) {
+        self.audit = Some(audit)
+    }
+
+    /// Returns whether query names should get random case.
+    pub fn randomizes_case(&self) -> bool {
+        self.randomize_case
+    }
+
+    /// Chooses a message ID.
+    pub fn message_id(&self) -> u16 {
+        let id = self.random.random_u16();
+        self.record(|| EntropyDecision::MessageId(id));
+        id
+    }
+
+    /// Chooses a port for a UDP query.
+    ///
+    /// The port is taken from the range of non-privileged ports.
+    pub fn source_port(&self) -> u16 {
+        loop {
+            let port = self.random.random_u16();
+            if port >= Self::MIN_PORT {
+                return port;
+            }
+        }
+    }
+
+    /// Reports the port a UDP query is sent from.
+    pub fn record_source_port(&self, port: u16, system: bool) {
+        self.record(|| EntropyDecision::SourcePort { port, system })
+    }
+
+    /// Randomizes the case of the letters in a wire-format name.
+    pub fn randomize_case(&self, name: &mut [u8]) {
+        // Names are at most 255 octets long, so 32 octets of randomness
+        // are enough for all letters.
+        let mut bits = [0u8; 32];
+        self.random.fill_bytes(&mut bits);
+        let mut count = 0;
+        for ch in name.iter_mut() {
+            // Length octets are at most 63 and thus never letters.
+            if ch.is_ascii_alphabetic() {
+                if bits[count / 8] & (1 << (count % 8)) != 0 {
+                    ch.make_ascii_uppercase()
+                } else {
+                    ch.make_ascii_lowercase()
+                }
+                count += 1;
+            }
+        }
+        self.record(|| EntropyDecision::QnameCase {
+            qname: Dname::from_octets(Bytes::copy_from_slice(name)).unwrap(),
+            bits: count,
+        })
+    }
+
+    /// Reports a decision if there is an audit.
+    ///
+    /// The decision is only created if it is needed.
+    fn record(&self, decision: impl FnOnce() -> EntropyDecision) {
+        if let Some(ref audit) = self.audit {
+            audit.record(&decision())
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::ToDname;
+    use core::str::FromStr;
+
+    #[test]
+    fn seeded() {
+        let (left, right) = (SeededRandom::new(7), SeededRandom::new(7));
+        for _ in 0..8 {
+            assert_eq!(left.random_u16(), right.random_u16());
+        }
+        let mut left = Entropy::new(false);
+        left.set_random(Arc::new(SeededRandom::new(7)));
+        let mut right = Entropy::new(false);
+        right.set_random(Arc::new(SeededRandom::new(8)));
+        let left = (0..8).map(|_| left.message_id()).collect::<Vec<_>>();
+        let right = (0..8).map(|_| right.message_id()).collect::<Vec<_>>();
+        assert_ne!(left, right);
+    }
+
+    #[test]
+    fn decisions() {
+        let log = AuditLog::new();
+        let mut entropy = Entropy::new(true);
+        entropy.set_random(Arc::new(SeededRandom::new(1)));
+        entropy.set_audit(Arc::new(log.clone()));
+
+        let id = entropy.message_id();
+        for _ in 0..100 {
+            assert!(entropy.source_port() >= Entropy::MIN_PORT);
+        }
+        entropy.record_source_port(4711, false);
+
+        let orig = Dname::<Vec<u8>>::from_str("www.example.com").unwrap();
+        let mut name = orig.as_slice().to_vec();
+        entropy.randomize_case(&mut name);
+        let name = Dname::from_octets(name).unwrap();
+        assert_eq!(name, orig);
+
+        assert_eq!(
+            log.take(),
+            [
+                EntropyDecision::MessageId(id),
+                EntropyDecision::SourcePort {
+                    port: 4711,
+                    system: false
+                },
+                EntropyDecision::QnameCase {
+                    qname: name.to_bytes(),
+                    bits: 13
+                },
+            ]
+        );
+        assert!(log.decisions().is_empty());
+    }
+
+    #[test]
+    fn case_varies() {
+        let entropy = Entropy::new(true);
+        let orig = Dname::<Vec<u8>>::from_str("www.example.com").unwrap();
+        let mut seen = Vec::new();
+        for _ in 0..8 {
+            let mut name = orig.as_slice().to_vec();
+            entropy.randomize_case(&mut name);
+            if !seen.contains(&name) {
+                seen.push(name)
+            }
+        }
+        // With 13 letters, eight identical names are next to impossible.
+        assert!(seen.len() > 1);
+    }
+}
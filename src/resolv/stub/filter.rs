@@ -0,0 +1,265 @@
+//! Filtering queries and answers.
+//!
+//! The stub resolver can be told to answer certain queries itself rather
+//! than sending them upstream. This module contains the types to
+//! configure this via [`ResolvOptions`] and the code to produce those
+//! answers.
+//!
+//! There are four kinds of filters:
+//!
+//! * [`ResolvOptions::addr_filter`] suppresses either A or AAAA records.
+//!   Queries for the suppressed type receive an empty answer. This is
+//!   useful on hosts that only have connectivity for one address family.
+//! * [`ResolvOptions::special_names`] enables answering queries for the
+//!   special-use domain names that should never be sent to the DNS. These
+//!   are `localhost.` and the names below it as defined in [RFC 6761],
+//!   which are answered with the loopback addresses, as well as the names
+//!   below `invalid.` ([RFC 6761]) and `onion.` ([RFC 7686]), which are
+//!   answered with NXDOMAIN. Names below `home.arpa.` are sent upstream
+//!   as required by section 4 of [RFC 8375] since they are resolved by
+//!   the local caching servers.
+//! * [`ResolvOptions::blocked_suffixes`] lists domain names for which,
+//!   including the names below them, NXDOMAIN is returned.
+//! * [`ResolvOptions::local_zones`] provides local zones and records from
+//!   which queries are answered. Queries dropped or refused by a local
+//!   zone are answered with REFUSED.
+//!
+//! [`ResolvOptions`]: ../conf/struct.ResolvOptions.html
+//! [`ResolvOptions::addr_filter`]: ../conf/struct.ResolvOptions.html#structfield.addr_filter
+//! [`ResolvOptions::special_names`]: ../conf/struct.ResolvOptions.html#structfield.special_names
+//! [`ResolvOptions::blocked_suffixes`]: ../conf/struct.ResolvOptions.html#structfield.blocked_suffixes
+//! [`ResolvOptions::local_zones`]: ../conf/struct.ResolvOptions.html#structfield.local_zones
+//! [RFC 6761]: https://tools.ietf.org/html/rfc6761
+//! [RFC 7686]: https://tools.ietf.org/html/rfc7686
+//! [RFC 8375]: https://tools.ietf.org/html/rfc8375
+
+use super::conf::ResolvOptions;
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::{AnswerBuilder, MessageBuilder};
+use crate::base::name::Dname;
+use crate::rdata::{Aaaa, A};
+use bytes::{Bytes, BytesMut};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+//------------ AddrFilter ----------------------------------------------------
+
+/// Which address records to suppress.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AddrFilter {
+    /// Don’t suppress any address records.
+    None,
+
+    /// Suppress AAAA records, only resolving IPv4 addresses.
+    Ipv4Only,
+
+    /// Suppress A records, only resolving IPv6 addresses.
+    Ipv6Only,
+}
+
+impl AddrFilter {
+    /// Returns whether queries for `qtype` are suppressed.
+    pub fn suppresses(self, qtype: Rtype) -> bool {
+        match self {
+            AddrFilter::None => false,
+            AddrFilter::Ipv4Only => qtype == Rtype::Aaaa,
+            AddrFilter::Ipv6Only => qtype == Rtype::A,
+        }
+    }
+}
+
+//------------ apply ---------------------------------------------------------
+
+/// The TTL of the records in answers produced by the filters.
+const TTL: u32 = 0;
+
+/// The name answered with the loopback addresses.
+const LOCALHOST: &[u8] = b"\x09localhost\x00";
+
+/// The names answered with NXDOMAIN if special names are enabled.
+const NXDOMAIN_NAMES: &[&[u8]] = &[b"\x07invalid\x00", b"\x05onion\x00"];
+
+/// Returns the answer to a query if it is handled by a filter.
+///
+/// Returns `None` if the query should be sent upstream.
+pub(super) fn apply(
+    options: &ResolvOptions,
+    query: &Message<&[u8]>,
+) -> Option<Message<Bytes>> {
+    let question = query.first_question()?;
+    let qname = question.qname();
+    let qtype = question.qtype();
+    if options
+        .blocked_suffixes
+        .iter()
+        .any(|suffix| qname.ends_with(suffix))
+    {
+        return Some(answer(query, Rcode::NXDomain, |_| ()));
+    }
+    if let Some(ref zones) = options.local_zones {
+        if let Some(local) = zones.lookup(&qname, qtype, question.qclass()) {
+            let rcode = local.rcode().unwrap_or(Rcode::Refused);
+            let mut builder = MessageBuilder::new_bytes()
+                .start_answer(query, rcode)
+                .unwrap();
+            builder.header_mut().set_ra(true);
+            let res = local
+                .compose(&qname, builder)
+                .map(|target| Message::from_octets(target.freeze()).unwrap());
+            // If the local answer doesn’t fit, answer without it.
+            return Some(
+                res.unwrap_or_else(|_| {
+                    answer(query, Rcode::ServFail, |_| ())
+                }),
+            );
+        }
+    }
+    if options.special_names {
+        if qname.ends_with(&Dname::from_octets(LOCALHOST).unwrap()) {
+            return Some(answer(query, Rcode::NoError, |builder| {
+                if question.qclass() != Class::In {
+                    return;
+                }
+                if qtype == Rtype::A {
+                    builder
+                        .push((
+                            qname,
+                            Class::In,
+                            TTL,
+                            A::new(Ipv4Addr::LOCALHOST),
+                        ))
+                        .unwrap();
+                } else if qtype == Rtype::Aaaa {
+                    builder
+                        .push((
+                            qname,
+                            Class::In,
+                            TTL,
+                            Aaaa::new(Ipv6Addr::LOCALHOST),
+                        ))
+                        .unwrap();
+                }
+            }));
+        }
+        if NXDOMAIN_NAMES.iter().any(|suffix| {
+            qname.ends_with(&Dname::from_octets(*suffix).unwrap())
+        }) {
+            return Some(answer(query, Rcode::NXDomain, |_| ()));
+        }
+    }
+    if options.addr_filter.suppresses(qtype) {
+        return Some(answer(query, Rcode::NoError, |_| ()));
+    }
+    None
+}
+
+/// Creates an answer to `query`, adding answer records via `op`.
+fn answer<F>(query: &Message<&[u8]>, rcode: Rcode, op: F) -> Message<Bytes>
+where
+    F: FnOnce(&mut AnswerBuilder<BytesMut>),
+{
+    let mut builder = MessageBuilder::new_bytes()
+        .start_answer(query, rcode)
+        .unwrap();
+    builder.header_mut().set_ra(true);
+    op(&mut builder);
+    builder.into_message()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::localzone::LocalZones;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::vec::Vec;
+
+    fn query(qname: &str, qtype: Rtype) -> Message<Vec<u8>> {
+        let mut builder = MessageBuilder::new_vec().question();
+        builder
+            .push((Dname::<Vec<u8>>::from_str(qname).unwrap(), qtype))
+            .unwrap();
+        builder.into_message()
+    }
+
+    fn run(
+        options: &ResolvOptions,
+        qname: &str,
+        qtype: Rtype,
+    ) -> Option<(Rcode, usize)> {
+        let query = query(qname, qtype);
+        apply(options, &query.for_slice()).map(|answer| {
+            (
+                answer.header().rcode(),
+                answer.header_counts().ancount().into(),
+            )
+        })
+    }
+
+    #[test]
+    fn special_names() {
+        let mut options = ResolvOptions::default();
+        assert_eq!(run(&options, "localhost.", Rtype::A), None);
+
+        options.special_names = true;
+        assert_eq!(
+            run(&options, "localhost.", Rtype::A),
+            Some((Rcode::NoError, 1))
+        );
+        assert_eq!(
+            run(&options, "foo.LOCALHOST.", Rtype::Aaaa),
+            Some((Rcode::NoError, 1))
+        );
+        assert_eq!(
+            run(&options, "localhost.", Rtype::Mx),
+            Some((Rcode::NoError, 0))
+        );
+        assert_eq!(
+            run(&options, "foo.invalid.", Rtype::A),
+            Some((Rcode::NXDomain, 0))
+        );
+        assert_eq!(
+            run(&options, "xyz.onion.", Rtype::A),
+            Some((Rcode::NXDomain, 0))
+        );
+        assert_eq!(run(&options, "printer.home.arpa.", Rtype::A), None);
+        assert_eq!(run(&options, "localhost.example.", Rtype::A), None);
+        assert_eq!(run(&options, "onion.example.", Rtype::A), None);
+    }
+
+    #[test]
+    fn addr_filter_and_blocked() {
+        let mut options = ResolvOptions::default();
+        options.addr_filter = AddrFilter::Ipv4Only;
+        options
+            .blocked_suffixes
+            .push(FromStr::from_str("ads.example.").unwrap());
+        assert_eq!(
+            run(&options, "www.example.", Rtype::Aaaa),
+            Some((Rcode::NoError, 0))
+        );
+        assert_eq!(run(&options, "www.example.", Rtype::A), None);
+        assert_eq!(
+            run(&options, "x.Ads.example.", Rtype::A),
+            Some((Rcode::NXDomain, 0))
+        );
+        assert_eq!(run(&options, "badads.example.", Rtype::A), None);
+    }
+
+    #[test]
+    fn local_zones() {
+        let mut options = ResolvOptions::default();
+        options.local_zones = Some(Arc::new(LocalZones::rfc6303()));
+        assert_eq!(
+            run(&options, "1.0.0.127.in-addr.arpa.", Rtype::Ptr),
+            Some((Rcode::NoError, 1))
+        );
+        assert_eq!(
+            run(&options, "1.1.168.192.in-addr.arpa.", Rtype::Ptr),
+            Some((Rcode::NXDomain, 0))
+        );
+        assert_eq!(run(&options, "4.3.2.1.in-addr.arpa.", Rtype::Ptr), None);
+    }
+}
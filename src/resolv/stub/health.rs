@@ -0,0 +1,221 @@
+//! Tracking the health of upstream servers.
+//!
+//! The stub resolver keeps statistics for each of its upstream servers:
+//! a smoothed round-trip time, a smoothed error rate, and the number of
+//! consecutive failures. These are used to order the servers for each
+//! query so that fast and healthy servers are tried first.
+//!
+//! A server that failed [`DOWN_AFTER`] times in a row is considered down.
+//! It is only used as a last resort until it has been successfully probed
+//! again. Probes happen either when a down server’s back-off period has
+//! passed and it is picked for a regular query, or explicitly through
+//! [`StubResolver::probe_failed`] which is intended to be run regularly in
+//! the background.
+//!
+//! [`DOWN_AFTER`]: constant.DOWN_AFTER.html
+//! [`StubResolver::probe_failed`]: ../struct.StubResolver.html#method.probe_failed
+
+use std::cmp;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The number of consecutive failures after which a server is down.
+pub const DOWN_AFTER: u32 = 3;
+
+/// The initial time to wait before probing a server that is down.
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// The maximum time to wait before probing a server that is down.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The weight of a new sample in the smoothed values in 1/8th.
+const SAMPLE_WEIGHT: u32 = 1;
+
+//------------ ServerHealth --------------------------------------------------
+
+/// A snapshot of the health of an upstream server.
+#[derive(Clone, Copy, Debug)]
+pub struct ServerHealth {
+    /// The smoothed round-trip time or `None` if there are no samples yet.
+    pub srtt: Option<Duration>,
+
+    /// The smoothed error rate between 0 and 1.
+    pub error_rate: f64,
+
+    /// The number of failures since the last successful answer.
+    pub consecutive_failures: u32,
+
+    /// Whether the server is currently considered down.
+    pub down: bool,
+}
+
+//------------ ServerStats ---------------------------------------------------
+
+/// The statistics for a single upstream server.
+#[derive(Debug, Default)]
+pub(super) struct ServerStats {
+    inner: Mutex<StatsInner>,
+}
+
+#[derive(Debug, Default)]
+struct StatsInner {
+    /// The smoothed round-trip time.
+    srtt: Option<Duration>,
+
+    /// The smoothed error rate in millionths.
+    error_rate: u32,
+
+    /// The number of failures since the last success.
+    consecutive_failures: u32,
+
+    /// The current back-off if the server is down.
+    backoff: Option<Duration>,
+
+    /// The time the server may next be probed if it is down.
+    retry_at: Option<Instant>,
+}
+
+impl ServerStats {
+    /// Records a successful answer received after `rtt`.
+    pub fn record_success(&self, rtt: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.add_rtt(rtt);
+        inner.add_error(false);
+        inner.consecutive_failures = 0;
+        inner.backoff = None;
+        inner.retry_at = None;
+    }
+
+    /// Records a failed query.
+    ///
+    /// If the failure was a timeout, `timeout` contains the time waited.
+    pub fn record_failure(&self, timeout: Option<Duration>) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(timeout) = timeout {
+            inner.add_rtt(timeout);
+        }
+        inner.add_error(true);
+        inner.consecutive_failures =
+            inner.consecutive_failures.saturating_add(1);
+        if inner.consecutive_failures >= DOWN_AFTER {
+            let backoff = match inner.backoff {
+                Some(backoff) => cmp::min(backoff * 2, MAX_BACKOFF),
+                None => INITIAL_BACKOFF,
+            };
+            inner.backoff = Some(backoff);
+            inner.retry_at = Some(Instant::now() + backoff);
+        }
+    }
+
+    /// Returns whether the server is down.
+    pub fn is_down(&self) -> bool {
+        self.inner.lock().unwrap().consecutive_failures >= DOWN_AFTER
+    }
+
+    /// Returns whether a down server is due for a probe.
+    ///
+    /// If it is, the next probe is scheduled so that only one query at a
+    /// time will be used as a probe.
+    pub fn take_probe(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        match (inner.retry_at, inner.backoff) {
+            (Some(retry_at), Some(backoff)) if retry_at <= now => {
+                inner.retry_at = Some(now + backoff);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the rank of the server for ordering.
+    ///
+    /// Lower ranks are better. Servers that are down and not due for a
+    /// probe get the worst rank, servers without an RTT sample come after
+    /// all servers with one.
+    pub fn rank(&self) -> (u8, u64) {
+        if self.is_down() && !self.take_probe() {
+            return (2, 0);
+        }
+        let inner = self.inner.lock().unwrap();
+        match inner.srtt {
+            Some(srtt) => {
+                // Penalize errors by up to five times the RTT.
+                let micros = srtt.as_micros() as u64;
+                let penalty = micros
+                    .saturating_mul(u64::from(inner.error_rate) * 4)
+                    / 1_000_000;
+                (0, micros.saturating_add(penalty))
+            }
+            None => (1, 0),
+        }
+    }
+
+    /// Returns a snapshot of the statistics.
+    pub fn health(&self) -> ServerHealth {
+        let inner = self.inner.lock().unwrap();
+        ServerHealth {
+            srtt: inner.srtt,
+            error_rate: f64::from(inner.error_rate) / 1_000_000.,
+            consecutive_failures: inner.consecutive_failures,
+            down: inner.consecutive_failures >= DOWN_AFTER,
+        }
+    }
+}
+
+impl StatsInner {
+    fn add_rtt(&mut self, rtt: Duration) {
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => {
+                (srtt * (8 - SAMPLE_WEIGHT) + rtt * SAMPLE_WEIGHT) / 8
+            }
+            None => rtt,
+        })
+    }
+
+    fn add_error(&mut self, error: bool) {
+        let sample = if error { 1_000_000 } else { 0 };
+        self.error_rate = (self.error_rate * (8 - SAMPLE_WEIGHT)
+            + sample * SAMPLE_WEIGHT)
+            / 8;
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rank() {
+        let fast = ServerStats::default();
+        let slow = ServerStats::default();
+        let unknown = ServerStats::default();
+        fast.record_success(Duration::from_millis(10));
+        slow.record_success(Duration::from_millis(15));
+        assert!(fast.rank() < slow.rank());
+        assert!(slow.rank() < unknown.rank());
+
+        // Errors make a fast server worse than a slow one.
+        fast.record_failure(None);
+        fast.record_failure(None);
+        fast.record_success(Duration::from_millis(10));
+        fast.record_failure(None);
+        assert!(fast.rank() > slow.rank());
+
+        // Three failures in a row and the server is down.
+        for _ in 0..DOWN_AFTER {
+            slow.record_failure(Some(Duration::from_secs(2)));
+        }
+        assert!(slow.is_down());
+        assert!(slow.health().down);
+        assert!(unknown.rank() < slow.rank());
+        assert!(!slow.take_probe());
+
+        slow.record_success(Duration::from_millis(100));
+        assert!(!slow.is_down());
+    }
+}
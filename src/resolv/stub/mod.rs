@@ -1,26 +1,46 @@
+use self::cache::AnswerCache;
 use self::conf::{
     ResolvConf, ResolvOptions, SearchSuffix, ServerConf, Transport,
 };
-use crate::base::iana::Rcode;
+use self::ddr::Discovery;
+use self::edns::PayloadSize;
+use self::entropy::{Entropy, EntropyAudit, RandomSource};
+use self::health::{ServerHealth, ServerStats};
+use self::ratelimit::RateLimiter;
+use self::request::Request;
+use self::stream::AnswerStream;
+use crate::base::iana::{Rcode, Rtype};
 use crate::base::message::Message;
 use crate::base::message_builder::{
     AdditionalBuilder, MessageBuilder, StreamTarget,
 };
-use crate::base::name::{ToDname, ToRelativeDname};
-use crate::base::octets::Octets512;
+use crate::base::name::{Dname, ToDname};
 use crate::base::question::Question;
+#[cfg(feature = "metrics")]
+use crate::metrics::ResolverMetrics;
 use crate::resolv::lookup::addr::{lookup_addr, FoundAddrs};
-use crate::resolv::lookup::host::{lookup_host, search_host, FoundHosts};
+use crate::resolv::lookup::dns64::{discover_prefix64, Prefix64};
+use crate::resolv::lookup::error::LookupError;
+use crate::resolv::lookup::host::{
+    lookup_host, search_host, split_host_port, FoundHosts, SearchCache,
+    SearchMode,
+};
+use crate::resolv::lookup::name::{
+    IntoDname, IntoRelativeDname, IntoSearchDname,
+};
 use crate::resolv::lookup::srv::{lookup_srv, FoundSrvs, SrvError};
 use crate::resolv::resolver::{Resolver, SearchNames};
 use bytes::Bytes;
 use futures::future::FutureExt;
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::boxed::Box;
 use std::future::Future;
 use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use std::vec::Vec;
 /// A stub resolver.
 ///
@@ -32,16 +52,28 @@ use std::vec::Vec;
 ///
 /// The main type is [`StubResolver`] that implements the [`Resolver`] trait
 /// and thus can be used with the various lookup functions.
-use std::{io, ops};
+use std::{cmp, io, ops};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpStream, UdpSocket};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
 #[cfg(feature = "resolv-sync")]
 use tokio::runtime;
-use tokio::time::timeout;
+use tokio::time::{sleep, timeout, timeout_at};
 
 //------------ Sub-modules ---------------------------------------------------
 
+pub mod cache;
 pub mod conf;
+pub mod ddr;
+pub mod edns;
+pub mod entropy;
+pub mod filter;
+pub mod health;
+pub mod probe;
+mod proxy;
+mod ratelimit;
+pub mod request;
+pub mod stream;
+pub mod trace;
 
 //------------ Module Configuration ------------------------------------------
 
@@ -79,6 +111,19 @@ pub struct StubResolver {
 
     /// Resolver options.
     options: ResolvOptions,
+
+    /// The names found by walking the search list.
+    search_cache: Arc<SearchCache>,
+
+    /// The cache for answers if caching is enabled.
+    cache: Option<Arc<AnswerCache>>,
+
+    /// The metrics to update if any.
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<ResolverMetrics>>,
+
+    /// The source of randomness for queries.
+    entropy: Entropy,
 }
 
 impl StubResolver {
@@ -89,12 +134,36 @@ impl StubResolver {
 
     /// Creates a new resolver using the given configuraiton.
     pub fn from_conf(conf: ResolvConf) -> Self {
+        let mut preferred =
+            ServerList::from_conf(&conf, |s| s.transport.is_preferred());
+        let mut stream =
+            ServerList::from_conf(&conf, |s| s.transport.is_stream());
+        ratelimit::apply(&conf, &mut [&mut preferred, &mut stream]);
+        let cache = match (conf.options.cache_size, &conf.options.cache_file)
+        {
+            (0, _) => None,
+            (size, Some(path)) => Some(
+                AnswerCache::open(path, size)
+                    .unwrap_or_else(|_| AnswerCache::new(size)),
+            ),
+            (size, None) => Some(AnswerCache::new(size)),
+        };
+        let cache = cache.map(|cache| {
+            let cache = cache.with_eviction(conf.options.cache_eviction);
+            Arc::new(match conf.options.cache_memory {
+                Some(limit) => cache.with_memory_limit(limit),
+                None => cache,
+            })
+        });
         StubResolver {
-            preferred: ServerList::from_conf(&conf, |s| {
-                s.transport.is_preferred()
-            }),
-            stream: ServerList::from_conf(&conf, |s| s.transport.is_stream()),
+            preferred,
+            stream,
+            entropy: Entropy::new(conf.options.randomize_case),
             options: conf.options,
+            search_cache: Default::default(),
+            cache,
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
 
@@ -102,6 +171,45 @@ impl StubResolver {
         &self.options
     }
 
+    /// Returns the answer cache if caching is enabled.
+    pub fn cache(&self) -> Option<&AnswerCache> {
+        self.cache.as_deref()
+    }
+
+    /// Attaches metrics to be updated by the resolver.
+    ///
+    /// The metrics are shared by all clones of the resolver made after
+    /// calling this method.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics(&mut self, metrics: Arc<ResolverMetrics>) {
+        self.metrics = Some(metrics)
+    }
+
+    /// Returns the metrics updated by the resolver if there are any.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Option<&Arc<ResolverMetrics>> {
+        self.metrics.as_ref()
+    }
+
+    /// Sets the source for the random values used in queries.
+    ///
+    /// The source is used for message IDs, UDP source ports, and the case
+    /// of query names. It is shared by all clones of the resolver made
+    /// after calling this method. See the [`entropy`] module for details.
+    ///
+    /// [`entropy`]: entropy/index.html
+    pub fn set_random_source(&mut self, source: impl RandomSource + 'static) {
+        self.entropy.set_random(Arc::new(source))
+    }
+
+    /// Sets an audit to report the random choices made for queries to.
+    ///
+    /// The audit is shared by all clones of the resolver made after
+    /// calling this method.
+    pub fn set_entropy_audit(&mut self, audit: impl EntropyAudit + 'static) {
+        self.entropy.set_audit(Arc::new(audit))
+    }
+
     pub async fn query<N: ToDname, Q: Into<Question<N>>>(
         &self,
         question: Q,
@@ -111,40 +219,244 @@ impl StubResolver {
             .await
     }
 
+    /// Starts a query whose answer is produced record by record.
+    ///
+    /// The query is sent over TCP to the first stream server that accepts
+    /// a connection. The returned stream produces the records of the
+    /// answer as the messages arrive which is useful for answers spanning
+    /// more than one message, such as zone transfers. See the [`stream`]
+    /// module for details.
+    ///
+    /// [`stream`]: stream/index.html
+    pub async fn query_stream<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<AnswerStream, io::Error> {
+        AnswerStream::start(
+            &self.stream,
+            Query::create_message(question.into()),
+            &self.entropy,
+        )
+        .await
+    }
+
+    /// Answers a question, giving up at the given deadline.
+    ///
+    /// The deadline applies to the query as a whole, including all retries
+    /// and the switch to stream transports after a truncated answer. Each
+    /// attempt is still bound by its server’s request timeout. Once the
+    /// deadline has passed, all sockets of the query are closed and an
+    /// error of kind `TimedOut` is returned. If the deadline has passed
+    /// already, no query is sent at all.
+    ///
+    /// All other query methods, including the lookups, can also be given a
+    /// deadline by wrapping them in [`tokio::time::timeout_at`]: dropping
+    /// the future returned by any of them cancels the query cleanly.
+    ///
+    /// [`tokio::time::timeout_at`]: https://docs.rs/tokio/1/tokio/time/fn.timeout_at.html
+    pub async fn query_with_deadline<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+        deadline: Instant,
+    ) -> Result<Answer, io::Error> {
+        Query::new(self)?
+            .with_deadline(deadline)
+            .run(Query::create_message(question.into()))
+            .await
+    }
+
+    /// Sends a request crafted via [`Request`].
+    ///
+    /// The request is sent to the configured servers using the same
+    /// transports and retries as regular queries. See the [`request`]
+    /// module for details.
+    ///
+    /// [`Request`]: request/struct.Request.html
+    /// [`request`]: request/index.html
+    pub async fn query_request(
+        &self,
+        request: &Request,
+    ) -> Result<Answer, io::Error> {
+        let message = request.to_message().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "request too long")
+        })?;
+        Query::new(self)?.run(message).await
+    }
+
+    /// Sends a complete request message unchanged and returns the response.
+    ///
+    /// Unlike the other query methods, this doesn’t change the content of
+    /// the message: its ID, flags, records, and OPT record including all
+    /// EDNS options are sent as given. Only compressed domain names are
+    /// sent uncompressed. The query filter and the answer cache are
+    /// bypassed.
+    /// Servers, transports, and retries are the same as for regular
+    /// queries. See the [`request`] module for details.
+    ///
+    /// An error of kind `InvalidData` is returned if the message can’t be
+    /// parsed and one of kind `InvalidInput` if it is too long.
+    ///
+    /// [`request`]: request/index.html
+    pub async fn query_raw<Octets: AsRef<[u8]>>(
+        &self,
+        message: &Message<Octets>,
+    ) -> Result<Message<Bytes>, io::Error> {
+        let message = request::copy_message(message.for_slice())?;
+        Query::new(self)?
+            .raw()
+            .run(message)
+            .await
+            .map(Answer::into_message)
+    }
+
     async fn query_message(
         &self,
         message: QueryMessage,
     ) -> Result<Answer, io::Error> {
         Query::new(self)?.run(message).await
     }
+
+    /// Returns the health of all upstream servers.
+    pub fn server_health(
+        &self,
+    ) -> Vec<(SocketAddr, Transport, ServerHealth)> {
+        self.preferred
+            .servers
+            .iter()
+            .chain(self.stream.servers.iter())
+            .map(|server| {
+                (
+                    server.conf.addr,
+                    server.conf.transport,
+                    server.stats.health(),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the UDP payload size currently used for each UDP server.
+    ///
+    /// The size starts out as the server’s configured
+    /// [`udp_payload_size`] but may be reduced if responses appear to get
+    /// lost due to fragmentation. See the [`edns`] module for details.
+    ///
+    /// [`udp_payload_size`]: conf/struct.ServerConf.html#structfield.udp_payload_size
+    /// [`edns`]: edns/index.html
+    pub fn udp_payload_sizes(&self) -> Vec<(SocketAddr, u16)> {
+        self.preferred
+            .servers
+            .iter()
+            .chain(self.stream.servers.iter())
+            .filter(|server| server.conf.transport == Transport::Udp)
+            .map(|server| (server.conf.addr, server.payload.get()))
+            .collect()
+    }
+
+    /// Discovers the designated resolvers of the upstream servers.
+    ///
+    /// Each upstream server is asked for the SVCB records of
+    /// `_dns.resolver.arpa.` as described in RFC 9462. The resolver itself
+    /// keeps using the unencrypted servers. See the [`ddr`] module for how
+    /// to make use of the result.
+    ///
+    /// [`ddr`]: ddr/index.html
+    pub async fn discover_designated(&self) -> Vec<Discovery> {
+        ddr::discover(
+            self.preferred
+                .servers
+                .iter()
+                .chain(self.stream.servers.iter()),
+            &self.entropy,
+        )
+        .await
+    }
+
+    /// Probes all upstream servers that are down and due for a probe.
+    ///
+    /// Each such server is sent a query for the SOA record of the root
+    /// zone. Any answer other than SERVFAIL will bring the server back.
+    ///
+    /// Since servers are only probed once their back-off period has
+    /// passed, this method can be called regularly, for instance every
+    /// few seconds from a task spawned for this purpose.
+    pub async fn probe_failed(&self) {
+        let servers = self
+            .preferred
+            .servers
+            .iter()
+            .chain(self.stream.servers.iter())
+            .filter(|server| {
+                server.stats.is_down() && server.stats.take_probe()
+            })
+            .collect::<Vec<_>>();
+        let mut message = Query::create_message(Question::new_in(
+            Dname::root_ref(),
+            Rtype::Soa,
+        ));
+        for server in servers {
+            server.prepare_message(&mut message, &self.entropy);
+            let _ = server.query_and_record(&message, &self.entropy).await;
+        }
+    }
 }
 
 impl StubResolver {
     pub async fn lookup_addr(
         &self,
         addr: IpAddr,
-    ) -> Result<FoundAddrs<&Self>, io::Error> {
+    ) -> Result<FoundAddrs<&Self>, LookupError> {
         lookup_addr(&self, addr).await
     }
 
     pub async fn lookup_host(
         &self,
-        qname: impl ToDname,
-    ) -> Result<FoundHosts<&Self>, io::Error> {
-        lookup_host(&self, qname).await
+        qname: impl IntoDname,
+    ) -> Result<FoundHosts<&Self>, LookupError> {
+        lookup_host(&self, qname)
+            .await
+            .map(|found| self.order_hosts(found))
     }
 
     pub async fn search_host(
         &self,
-        qname: impl ToRelativeDname,
-    ) -> Result<FoundHosts<&Self>, io::Error> {
-        search_host(&self, qname).await
+        qname: impl IntoSearchDname,
+    ) -> Result<FoundHosts<&Self>, LookupError> {
+        search_host(&self, qname)
+            .await
+            .map(|found| self.order_hosts(found))
+    }
+
+    /// Applies the address order and sort list to a host lookup result.
+    fn order_hosts<'a>(
+        &self,
+        found: FoundHosts<&'a Self>,
+    ) -> FoundHosts<&'a Self> {
+        found
+            .with_order(self.options.addr_order)
+            .with_sortlist(self.options.sortlist.clone())
+    }
+
+    pub async fn resolve(
+        &self,
+        addr: &str,
+    ) -> Result<Vec<SocketAddr>, LookupError> {
+        if let Ok(addr) = SocketAddr::from_str(addr) {
+            return Ok(vec![addr]);
+        }
+        let (host, port) = split_host_port(addr)?;
+        Ok(self.lookup_host(host).await?.port_iter(port).collect())
+    }
+
+    pub async fn discover_prefix64(
+        &self,
+    ) -> Result<Vec<Prefix64>, LookupError> {
+        discover_prefix64(&self).await
     }
 
     pub async fn lookup_srv(
         &self,
-        service: impl ToRelativeDname,
-        name: impl ToDname,
+        service: impl IntoRelativeDname,
+        name: impl IntoDname,
         fallback_port: u16,
     ) -> Result<Option<FoundSrvs>, SrvError> {
         lookup_srv(&self, service, name, fallback_port).await
@@ -225,6 +537,22 @@ impl<'a> SearchNames for &'a StubResolver {
             pos: 0,
         }
     }
+
+    fn ndots(&self) -> usize {
+        self.options().ndots
+    }
+
+    fn no_tld_query(&self) -> bool {
+        self.options().no_tld_query
+    }
+
+    fn search_mode(&self) -> SearchMode {
+        self.options().search_mode
+    }
+
+    fn search_cache(&self) -> Option<&SearchCache> {
+        Some(&self.search_cache)
+    }
 }
 
 //------------ Query ---------------------------------------------------------
@@ -250,6 +578,12 @@ pub struct Query<'a> {
     /// answer we get. (Remember, SERVFAIL is returned for a bogus answer, so
     /// you might want to know.)
     error: Result<Answer, io::Error>,
+
+    /// The time by which the query has to be finished.
+    deadline: Option<Instant>,
+
+    /// Is the message to be sent unchanged?
+    raw: bool,
 }
 
 impl<'a> Query<'a> {
@@ -275,17 +609,126 @@ impl<'a> Query<'a> {
                 io::ErrorKind::TimedOut,
                 "all timed out",
             )),
+            deadline: None,
+            raw: false,
         })
     }
 
+    /// Sets the time by which the query has to be finished.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sends the message unchanged, bypassing the filter and cache.
+    fn raw(mut self) -> Self {
+        self.raw = true;
+        self
+    }
+
+    /// Runs the query.
+    ///
+    /// Dropping the returned future cancels the query, closing all sockets
+    /// it has opened.
     pub async fn run(
+        self,
+        message: QueryMessage,
+    ) -> Result<Answer, io::Error> {
+        if !self.raw {
+            if let Some(answer) =
+                filter::apply(self.resolver.options(), &message.as_message())
+            {
+                return Ok(answer.into());
+            }
+        }
+        #[cfg(feature = "metrics")]
+        let metrics = self.resolver.metrics.clone().map(|metrics| {
+            let qtype = message
+                .as_message()
+                .first_question()
+                .map(|question| question.qtype())
+                .unwrap_or(Rtype::Int(0));
+            (metrics, qtype)
+        });
+        let cache = if self.raw
+            || !cache::is_cacheable_query(&message.as_message())
+        {
+            None
+        } else {
+            self.resolver.cache.clone()
+        };
+        if let Some(ref cache) = cache {
+            let answer = cache.lookup(&message.as_message());
+            #[cfg(feature = "metrics")]
+            if let Some((ref metrics, qtype)) = metrics {
+                metrics.record_cache_lookup(answer.is_some());
+                if let Some(ref answer) = answer {
+                    metrics.record_answer(
+                        qtype,
+                        answer.header().rcode(),
+                        None,
+                    )
+                }
+            }
+            if let Some(answer) = answer {
+                return Ok(answer.into());
+            }
+        }
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let res = self.run_deadline(message).await;
+        #[cfg(feature = "metrics")]
+        if let Some((metrics, qtype)) = metrics {
+            match res {
+                Ok(ref answer) => metrics.record_answer(
+                    qtype,
+                    answer.header().rcode(),
+                    Some(start.elapsed()),
+                ),
+                Err(_) => metrics.record_failure(qtype),
+            }
+        }
+        if let (Some(cache), Ok(answer)) = (cache, &res) {
+            cache.insert(&answer.message)
+        }
+        res
+    }
+
+    async fn run_deadline(
+        self,
+        message: QueryMessage,
+    ) -> Result<Answer, io::Error> {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return self.run_attempts(message).await,
+        };
+        if deadline <= Instant::now() {
+            return Err(Self::deadline_exceeded());
+        }
+        match timeout_at(deadline.into(), self.run_attempts(message)).await {
+            Ok(res) => res,
+            Err(_) => Err(Self::deadline_exceeded()),
+        }
+    }
+
+    fn deadline_exceeded() -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, "deadline exceeded")
+    }
+
+    async fn run_attempts(
         mut self,
         mut message: QueryMessage,
     ) -> Result<Answer, io::Error> {
+        if self.resolver.options().blast {
+            if let Some(answer) = self.run_blast(&message).await {
+                return Ok(answer);
+            }
+        }
         loop {
             match self.run_query(&mut message).await {
                 Ok(answer) => {
                     if answer.header().rcode() == Rcode::FormErr
+                        && !self.raw
                         && self.current_server().does_edns()
                     {
                         // FORMERR with EDNS: turn off EDNS and try again.
@@ -319,11 +762,52 @@ impl<'a> Query<'a> {
         }
     }
 
+    /// Races the query on several servers.
+    ///
+    /// Returns the first final answer or `None` if there wasn’t any.
+    async fn run_blast(&self, message: &QueryMessage) -> Option<Answer> {
+        let list = if self.preferred {
+            &self.resolver.preferred
+        } else {
+            &self.resolver.stream
+        };
+        let count = match self.resolver.options().blast_servers {
+            0 => list.len(),
+            count => cmp::min(count, list.len()),
+        };
+        let stagger = self.resolver.options().blast_stagger;
+        let raw = self.raw;
+        let entropy = &self.resolver.entropy;
+        let mut counter = self.counter.clone();
+        let mut queries = FuturesUnordered::new();
+        for idx in 0..count {
+            if idx > 0 && !counter.next() {
+                break;
+            }
+            let server = counter.info(list);
+            let mut message = message.clone();
+            queries.push(async move {
+                if idx > 0 {
+                    sleep(stagger * idx as u32).await;
+                }
+                if !raw {
+                    server.prepare_message(&mut message, entropy);
+                }
+                server.query_and_record(&message, entropy).await
+            });
+        }
+        while let Some(res) = queries.next().await {
+            if let Ok(answer) = res {
+                if answer.is_final() {
+                    return Some(answer);
+                }
+            }
+        }
+        None
+    }
+
     fn create_message(question: Question<impl ToDname>) -> QueryMessage {
-        let mut message = MessageBuilder::from_target(
-            StreamTarget::new(Octets512::new()).unwrap(),
-        )
-        .unwrap();
+        let mut message = MessageBuilder::new_stream_vec();
         message.header_mut().set_rd(true);
         let mut message = message.question();
         message.push(question).unwrap();
@@ -335,8 +819,11 @@ impl<'a> Query<'a> {
         message: &mut QueryMessage,
     ) -> Result<Answer, io::Error> {
         let server = self.current_server();
-        server.prepare_message(message);
-        server.query(message).await
+        let entropy = &self.resolver.entropy;
+        if !self.raw {
+            server.prepare_message(message, entropy);
+        }
+        server.query_and_record(message, entropy).await
     }
 
     fn current_server(&self) -> &ServerInfo {
@@ -394,8 +881,10 @@ impl<'a> Query<'a> {
 
 //------------ QueryMessage --------------------------------------------------
 
-// XXX This needs to be re-evaluated if we start adding OPTtions to the query.
-pub(super) type QueryMessage = AdditionalBuilder<StreamTarget<Octets512>>;
+/// The message builder used for outgoing queries.
+///
+/// Since raw requests can be of any size, the message lives in a vec.
+pub(super) type QueryMessage = AdditionalBuilder<StreamTarget<Vec<u8>>>;
 
 //------------ Answer --------------------------------------------------------
 
@@ -443,6 +932,15 @@ struct ServerInfo {
     ///
     /// We start out with assuming it does and unset it if we get a FORMERR.
     edns: Arc<AtomicBool>,
+
+    /// The health statistics of the server.
+    stats: Arc<ServerStats>,
+
+    /// The UDP payload size to advertise.
+    payload: Arc<PayloadSize>,
+
+    /// The rate limits that apply to queries sent to this server.
+    limiters: Vec<Arc<RateLimiter>>,
 }
 
 impl ServerInfo {
@@ -454,38 +952,113 @@ impl ServerInfo {
         self.edns.store(false, Ordering::Relaxed);
     }
 
-    pub fn prepare_message(&self, query: &mut QueryMessage) {
+    /// Waits until all rate limits allow sending a query to the server.
+    pub async fn wait_for_rate_limit(&self) {
+        for limiter in &self.limiters {
+            limiter.acquire().await
+        }
+    }
+
+    /// Prepares a query for sending to the server.
+    ///
+    /// Chooses a new message ID, randomizes the case of the query name
+    /// for UDP if enabled, and adds an OPT record if the server does EDNS.
+    pub fn prepare_message(
+        &self,
+        query: &mut QueryMessage,
+        entropy: &Entropy,
+    ) {
         query.rewind();
+        if entropy.randomizes_case() && self.conf.transport == Transport::Udp
+        {
+            Self::randomize_case(query, entropy);
+        }
+        query.header_mut().set_id(entropy.message_id());
         if self.does_edns() {
             query
                 .opt(|opt| {
-                    opt.set_udp_payload_size(self.conf.udp_payload_size);
+                    opt.set_udp_payload_size(self.payload.get());
                     Ok(())
                 })
                 .unwrap();
         }
     }
 
+    /// Replaces a query with one whose query name has random case.
+    ///
+    /// Only queries with exactly one question are changed.
+    fn randomize_case(query: &mut QueryMessage, entropy: &Entropy) {
+        let message = query.as_message();
+        let question = match message.sole_question() {
+            Ok(question) => question,
+            Err(_) => return,
+        };
+        let mut qname = question.qname().to_vec().into_octets();
+        entropy.randomize_case(&mut qname);
+        let qname = Dname::from_octets(qname).unwrap();
+        let header = message.header();
+        let mut new = Query::create_message(Question::new(
+            qname,
+            question.qtype(),
+            question.qclass(),
+        ));
+        *new.header_mut() = header;
+        *query = new;
+    }
+
+    /// Sends a query and updates the health statistics.
+    ///
+    /// Timeouts, transport errors, and SERVFAIL answers count as failures.
+    /// For UDP queries with EDNS, answers and timeouts are also used to
+    /// adjust the payload size.
+    pub async fn query_and_record(
+        &self,
+        query: &QueryMessage,
+        entropy: &Entropy,
+    ) -> Result<Answer, io::Error> {
+        self.wait_for_rate_limit().await;
+        let start = Instant::now();
+        let res = self.query(query, entropy).await;
+        if self.conf.transport == Transport::Udp && self.does_edns() {
+            match res {
+                Ok(ref answer) => {
+                    self.payload.record_answer(answer.as_slice().len())
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::TimedOut => {
+                    self.payload.record_timeout()
+                }
+                _ => {}
+            }
+        }
+        match res {
+            Ok(ref answer) if answer.header().rcode() != Rcode::ServFail => {
+                self.stats.record_success(start.elapsed())
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::TimedOut => {
+                self.stats.record_failure(Some(start.elapsed()))
+            }
+            _ => self.stats.record_failure(None),
+        }
+        res
+    }
+
     pub async fn query(
         &self,
         query: &QueryMessage,
+        entropy: &Entropy,
     ) -> Result<Answer, io::Error> {
         let res = match self.conf.transport {
             Transport::Udp => {
                 timeout(
                     self.conf.request_timeout,
-                    Self::udp_query(
-                        query,
-                        self.conf.addr,
-                        self.conf.recv_size,
-                    ),
+                    Self::udp_query(query, &self.conf, entropy),
                 )
                 .await
             }
             Transport::Tcp => {
                 timeout(
                     self.conf.request_timeout,
-                    Self::tcp_query(query, self.conf.addr),
+                    Self::tcp_query(query, &self.conf),
                 )
                 .await
             }
@@ -502,9 +1075,9 @@ impl ServerInfo {
 
     pub async fn tcp_query(
         query: &QueryMessage,
-        addr: SocketAddr,
+        conf: &ServerConf,
     ) -> Result<Answer, io::Error> {
-        let mut sock = TcpStream::connect(&addr).await?;
+        let mut sock = Self::tcp_connect(conf).await?;
         sock.write_all(query.as_target().as_stream_slice()).await?;
 
         // This loop can be infinite because we have a timeout on this whole
@@ -529,13 +1102,23 @@ impl ServerInfo {
         }
     }
 
+    /// Sends a query over UDP.
+    ///
+    /// If the case of query names is randomized, answers have to echo the
+    /// query name exactly and are ignored otherwise.
     pub async fn udp_query(
         query: &QueryMessage,
-        addr: SocketAddr,
-        recv_size: usize,
+        conf: &ServerConf,
+        entropy: &Entropy,
     ) -> Result<Answer, io::Error> {
-        let sock = Self::udp_bind(addr.is_ipv4()).await?;
-        sock.connect(addr).await?;
+        if conf.proxy.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "proxies are not supported for UDP",
+            ));
+        }
+        let sock = Self::udp_bind(conf, entropy).await?;
+        sock.connect(conf.addr).await?;
         let sent = sock.send(query.as_target().as_dgram_slice()).await?;
         if sent != query.as_target().as_dgram_slice().len() {
             return Err(io::Error::new(
@@ -544,7 +1127,7 @@ impl ServerInfo {
             ));
         }
         loop {
-            let mut buf = vec![0; recv_size]; // XXX use uninit'ed mem here.
+            let mut buf = vec![0; conf.recv_size]; // XXX use uninit'ed mem here.
             let len = sock.recv(&mut buf).await?;
             buf.truncate(len);
 
@@ -553,23 +1136,48 @@ impl ServerInfo {
                 Ok(answer) => answer,
                 Err(_) => continue,
             };
-            if !answer.is_answer(&query.as_message()) {
+            let matches = if entropy.randomizes_case() {
+                answer.answers_question_exact(&query.as_message()).is_ok()
+            } else {
+                answer.is_answer(&query.as_message())
+            };
+            if !matches {
                 continue;
             }
             return Ok(answer.into());
         }
     }
 
-    async fn udp_bind(v4: bool) -> Result<UdpSocket, io::Error> {
+    /// Binds a UDP socket for a query.
+    ///
+    /// Unless the configuration asks for a specific port, a random port is
+    /// chosen. If none of a number of random ports can be bound, the port
+    /// is left to the operating system.
+    async fn udp_bind(
+        conf: &ServerConf,
+        entropy: &Entropy,
+    ) -> Result<UdpSocket, io::Error> {
+        let mut local = Self::local_addr(conf, conf.addr)?;
+        let random = local.port() == 0;
         let mut i = 0;
         loop {
-            let local: SocketAddr = if v4 {
-                ([0u8; 4], 0).into()
-            } else {
-                ([0u16; 8], 0).into()
-            };
+            let system = random && i == RETRY_RANDOM_PORT;
+            if random {
+                local.set_port(if system { 0 } else { entropy.source_port() })
+            }
             match UdpSocket::bind(&local).await {
-                Ok(sock) => return Ok(sock),
+                Ok(sock) => {
+                    if let Some(ref interface) = conf.interface {
+                        Self::udp_bind_device(&sock, interface)?;
+                    }
+                    if random {
+                        entropy.record_source_port(
+                            sock.local_addr()?.port(),
+                            system,
+                        );
+                    }
+                    return Ok(sock);
+                }
                 Err(err) => {
                     if i == RETRY_RANDOM_PORT {
                         return Err(err);
@@ -580,13 +1188,127 @@ impl ServerInfo {
             }
         }
     }
+
+    async fn tcp_connect(conf: &ServerConf) -> Result<TcpStream, io::Error> {
+        let proxy = match conf.proxy {
+            Some(ref proxy) => proxy,
+            None => return Self::tcp_connect_to(conf, conf.addr).await,
+        };
+        let mut sock = Self::tcp_connect_to(conf, proxy.addr).await?;
+        proxy::handshake(&mut sock, proxy, conf.addr).await?;
+        Ok(sock)
+    }
+
+    /// Opens a TCP connection to `addr` using the local address and
+    /// interface given in `conf`.
+    async fn tcp_connect_to(
+        conf: &ServerConf,
+        addr: SocketAddr,
+    ) -> Result<TcpStream, io::Error> {
+        if conf.bind_addr.is_none() && conf.interface.is_none() {
+            return TcpStream::connect(&addr).await;
+        }
+        let sock = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        if let Some(ref interface) = conf.interface {
+            Self::tcp_bind_device(&sock, interface)?;
+        }
+        sock.bind(Self::local_addr(conf, addr)?)?;
+        sock.connect(addr).await
+    }
+
+    /// Returns the local address to bind a socket for `conf` to.
+    ///
+    /// The socket will be connected to `remote`.
+    fn local_addr(
+        conf: &ServerConf,
+        remote: SocketAddr,
+    ) -> Result<SocketAddr, io::Error> {
+        match conf.bind_addr {
+            Some(addr) if addr.is_ipv4() != remote.is_ipv4() => {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "bind address of wrong address family",
+                ))
+            }
+            Some(addr) => Ok(addr),
+            None if remote.is_ipv4() => Ok(([0u8; 4], 0).into()),
+            None => Ok(([0u16; 8], 0).into()),
+        }
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "linux"
+    ))]
+    fn udp_bind_device(
+        sock: &UdpSocket,
+        interface: &str,
+    ) -> Result<(), io::Error> {
+        sock.bind_device(Some(interface.as_bytes()))
+    }
+
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "linux"
+    )))]
+    fn udp_bind_device(
+        _sock: &UdpSocket,
+        _interface: &str,
+    ) -> Result<(), io::Error> {
+        Err(Self::bind_device_unsupported())
+    }
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "linux"
+    ))]
+    fn tcp_bind_device(
+        sock: &TcpSocket,
+        interface: &str,
+    ) -> Result<(), io::Error> {
+        sock.bind_device(Some(interface.as_bytes()))
+    }
+
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "linux"
+    )))]
+    fn tcp_bind_device(
+        _sock: &TcpSocket,
+        _interface: &str,
+    ) -> Result<(), io::Error> {
+        Err(Self::bind_device_unsupported())
+    }
+
+    #[cfg(not(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "linux"
+    )))]
+    fn bind_device_unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Other,
+            "binding to an interface is not supported on this system",
+        )
+    }
 }
 
 impl From<ServerConf> for ServerInfo {
     fn from(conf: ServerConf) -> Self {
         ServerInfo {
-            conf,
             edns: Arc::new(AtomicBool::new(true)),
+            stats: Arc::new(ServerStats::default()),
+            payload: Arc::new(PayloadSize::new(conf.udp_payload_size)),
+            limiters: Vec::new(),
+            conf,
         }
     }
 }
@@ -625,7 +1347,24 @@ impl ServerList {
                 conf.servers
                     .iter()
                     .filter(|f| filter(*f))
-                    .map(Into::into)
+                    .map(|server| {
+                        let mut server = server.clone();
+                        let options = &conf.options;
+                        if server.bind_addr.is_none() {
+                            server.bind_addr = match server.addr {
+                                SocketAddr::V4(_) => {
+                                    options.bind_v4.map(Into::into)
+                                }
+                                SocketAddr::V6(_) => {
+                                    options.bind_v6.map(Into::into)
+                                }
+                            }
+                        }
+                        if server.interface.is_none() {
+                            server.interface = options.interface.clone();
+                        }
+                        server.into()
+                    })
                     .collect()
             },
             start: Arc::new(AtomicUsize::new(0)),
@@ -636,8 +1375,13 @@ impl ServerList {
         self.servers.is_empty()
     }
 
+    /// Returns a counter for trying the servers of the list.
+    ///
+    /// If `rotate` is `true`, the servers are used round-robin and only
+    /// servers that are down are moved to the end. Otherwise, the servers
+    /// are ordered by their health.
     pub fn counter(&self, rotate: bool) -> ServerListCounter {
-        let res = ServerListCounter::new(self);
+        let res = ServerListCounter::new(self, !rotate);
         if rotate {
             self.rotate()
         }
@@ -674,30 +1418,49 @@ impl ops::Deref for ServerList {
 
 #[derive(Clone, Debug)]
 struct ServerListCounter {
-    cur: usize,
-    end: usize,
+    /// The indexes of the servers in the order they should be tried.
+    order: Vec<usize>,
+
+    /// The current position in `order`.
+    pos: usize,
 }
 
 impl ServerListCounter {
-    fn new(list: &ServerList) -> Self {
+    /// Creates a new counter for the list.
+    ///
+    /// The counter starts at the list’s start position. Servers that are
+    /// down are moved to the end. If `ranked` is `true`, the remaining
+    /// servers are additionally ordered by their health.
+    fn new(list: &ServerList, ranked: bool) -> Self {
         if list.servers.is_empty() {
-            return ServerListCounter { cur: 0, end: 0 };
+            return ServerListCounter {
+                order: Vec::new(),
+                pos: 0,
+            };
         }
 
         // We modulo the start value here to prevent hick-ups towards the
         // end of usize’s range.
-        let start = list.start.load(Ordering::Relaxed) % list.servers.len();
-        ServerListCounter {
-            cur: start,
-            end: start + list.servers.len(),
-        }
+        let len = list.servers.len();
+        let start = list.start.load(Ordering::Relaxed) % len;
+        let mut order: Vec<_> =
+            (start..start + len).map(|idx| idx % len).collect();
+        order.sort_by_cached_key(|&idx| {
+            let (class, score) = list.servers[idx].stats.rank();
+            if ranked {
+                (class, score)
+            } else {
+                (if class == 2 { 2 } else { 0 }, 0)
+            }
+        });
+        ServerListCounter { order, pos: 0 }
     }
 
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> bool {
-        let next = self.cur + 1;
-        if next < self.end {
-            self.cur = next;
+        let next = self.pos + 1;
+        if next < self.order.len() {
+            self.pos = next;
             true
         } else {
             false
@@ -705,7 +1468,7 @@ impl ServerListCounter {
     }
 
     pub fn info<'a>(&self, list: &'a ServerList) -> &'a ServerInfo {
-        &list[self.cur % list.servers.len()]
+        &list[self.order[self.pos]]
     }
 }
 
@@ -721,7 +1484,7 @@ impl<'a> ServerListIter<'a> {
     fn new(list: &'a ServerList) -> Self {
         ServerListIter {
             servers: list,
-            counter: ServerListCounter::new(list),
+            counter: ServerListCounter::new(list, false),
         }
     }
 }
@@ -772,3 +1535,424 @@ impl<'a> Iterator for SearchIter<'a> {
         }
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::run;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    /// Runs a TCP server that reads one query and never answers.
+    ///
+    /// Returns the resolver using the server and a future resolving once
+    /// the client has closed the connection.
+    async fn silent_server() -> (StubResolver, impl Future<Output = ()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut conf = ResolvConf::new();
+        let mut server =
+            ServerConf::new(listener.local_addr().unwrap(), Transport::Tcp);
+        server.request_timeout = Duration::from_secs(60);
+        conf.servers.push(server);
+        let closed = async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            // This only returns once the client has closed the connection.
+            sock.read_to_end(&mut buf).await.unwrap();
+        };
+        (StubResolver::from_conf(conf), closed)
+    }
+
+    #[test]
+    fn cancel_on_drop() {
+        run(async {
+            let (resolver, closed) = silent_server().await;
+            let query = async {
+                let res = timeout(
+                    Duration::from_millis(100),
+                    resolver.query((Dname::root_ref(), Rtype::Soa)),
+                )
+                .await;
+                assert!(res.is_err());
+            };
+            futures::join!(query, closed);
+        })
+    }
+
+    #[test]
+    fn deadline() {
+        run(async {
+            let (resolver, closed) = silent_server().await;
+            let query = async {
+                let start = Instant::now();
+                let res = resolver
+                    .query_with_deadline(
+                        (Dname::root_ref(), Rtype::Soa),
+                        start + Duration::from_millis(100),
+                    )
+                    .await;
+                assert!(matches!(
+                    res,
+                    Err(ref err) if err.kind() == io::ErrorKind::TimedOut
+                ));
+                assert!(start.elapsed() < Duration::from_secs(30));
+            };
+            futures::join!(query, closed);
+
+            // A deadline in the past doesn’t send anything.
+            let res = resolver
+                .query_with_deadline(
+                    (Dname::root_ref(), Rtype::Soa),
+                    Instant::now(),
+                )
+                .await;
+            assert!(matches!(
+                res,
+                Err(ref err) if err.kind() == io::ErrorKind::TimedOut
+            ));
+        })
+    }
+
+    /// Runs a UDP server answering all queries with `rcode` after `delay`.
+    ///
+    /// Returns the server’s address and the times queries arrived at.
+    async fn mock_udp(
+        rcode: Rcode,
+        delay: Duration,
+    ) -> (SocketAddr, Arc<std::sync::Mutex<Vec<Instant>>>) {
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = sock.local_addr().unwrap();
+        let arrived = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let res = arrived.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0; 512];
+            loop {
+                let (len, peer) = sock.recv_from(&mut buf).await.unwrap();
+                arrived.lock().unwrap().push(Instant::now());
+                let query = Message::from_octets(&buf[..len]).unwrap();
+                let answer = MessageBuilder::new_vec()
+                    .start_answer(&query, rcode)
+                    .unwrap()
+                    .finish();
+                sleep(delay).await;
+                let _ = sock.send_to(&answer, peer).await;
+            }
+        });
+        (addr, res)
+    }
+
+    /// Creates a resolver blasting queries to `count` of the UDP servers.
+    fn blaster(
+        servers: &[SocketAddr],
+        count: usize,
+        stagger: Duration,
+    ) -> StubResolver {
+        let mut conf = ResolvConf::new();
+        for addr in servers {
+            let mut server = ServerConf::new(*addr, Transport::Udp);
+            server.request_timeout = Duration::from_secs(2);
+            conf.servers.push(server);
+        }
+        conf.options.attempts = 1;
+        conf.options.blast = true;
+        conf.options.blast_servers = count;
+        conf.options.blast_stagger = stagger;
+        StubResolver::from_conf(conf)
+    }
+
+    #[test]
+    fn blast_first_wins() {
+        run(async {
+            // The first server is slow, the second one answers right away.
+            let (slow, _) =
+                mock_udp(Rcode::NoError, Duration::from_millis(1500)).await;
+            let (fast, _) =
+                mock_udp(Rcode::NXDomain, Duration::from_secs(0)).await;
+            let resolver = blaster(&[slow, fast], 2, Duration::from_secs(0));
+            let start = Instant::now();
+            let answer = resolver
+                .query((Dname::root_ref(), Rtype::Soa))
+                .await
+                .unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NXDomain);
+            assert!(start.elapsed() < Duration::from_millis(1000));
+        })
+    }
+
+    #[test]
+    fn blast_stagger() {
+        run(async {
+            let stagger = Duration::from_millis(200);
+
+            // A non-final answer from the first server doesn’t stop the
+            // query from going to the second one after the stagger delay.
+            let (first, first_at) =
+                mock_udp(Rcode::ServFail, Duration::from_secs(0)).await;
+            let (second, second_at) =
+                mock_udp(Rcode::NoError, Duration::from_secs(0)).await;
+            let resolver = blaster(&[first, second], 2, stagger);
+            let answer = resolver
+                .query((Dname::root_ref(), Rtype::Soa))
+                .await
+                .unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NoError);
+            let first_at = first_at.lock().unwrap()[0];
+            let second_at = second_at.lock().unwrap()[0];
+            assert!(second_at.duration_since(first_at) >= stagger);
+
+            // A final answer arriving before the delay has passed means the
+            // second server never sees the query.
+            let (first, _) =
+                mock_udp(Rcode::NoError, Duration::from_secs(0)).await;
+            let (second, second_at) =
+                mock_udp(Rcode::NoError, Duration::from_secs(0)).await;
+            let resolver = blaster(&[first, second], 2, stagger);
+            resolver
+                .query((Dname::root_ref(), Rtype::Soa))
+                .await
+                .unwrap();
+            sleep(stagger * 2).await;
+            assert!(second_at.lock().unwrap().is_empty());
+        })
+    }
+
+    #[test]
+    fn blast_fallback() {
+        run(async {
+            // Both racers fail, the query then goes through all servers in
+            // turn and finds the third one.
+            let (first, first_at) =
+                mock_udp(Rcode::ServFail, Duration::from_secs(0)).await;
+            let (second, second_at) =
+                mock_udp(Rcode::ServFail, Duration::from_secs(0)).await;
+            let (third, third_at) =
+                mock_udp(Rcode::NoError, Duration::from_secs(0)).await;
+            let resolver =
+                blaster(&[first, second, third], 2, Duration::from_secs(0));
+            let answer = resolver
+                .query((Dname::root_ref(), Rtype::Soa))
+                .await
+                .unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NoError);
+            assert!(!first_at.lock().unwrap().is_empty());
+            assert!(!second_at.lock().unwrap().is_empty());
+            assert_eq!(third_at.lock().unwrap().len(), 1);
+        })
+    }
+
+    #[test]
+    fn bind_addr() {
+        let mut conf = ResolvConf::new();
+        conf.servers.push(ServerConf::new(
+            "192.0.2.1:53".parse().unwrap(),
+            Transport::Udp,
+        ));
+        conf.servers.push(ServerConf::new(
+            "[2001:db8::1]:53".parse().unwrap(),
+            Transport::Udp,
+        ));
+        let mut own =
+            ServerConf::new("192.0.2.2:53".parse().unwrap(), Transport::Tcp);
+        own.bind_addr = Some("198.51.100.2:0".parse().unwrap());
+        own.interface = Some("eth1".into());
+        conf.servers.push(own);
+        conf.options.bind_v4 = Some("198.51.100.1:0".parse().unwrap());
+        conf.options.bind_v6 = Some("[fe80::1%2]:0".parse().unwrap());
+        conf.options.interface = Some("eth0".into());
+
+        let list = ServerList::from_conf(&conf, |_| true);
+        let local = |idx: usize| {
+            let conf = &list.servers[idx].conf;
+            ServerInfo::local_addr(conf, conf.addr).unwrap()
+        };
+        assert_eq!(local(0), "198.51.100.1:0".parse().unwrap());
+        match local(1) {
+            SocketAddr::V6(addr) => assert_eq!(addr.scope_id(), 2),
+            _ => panic!("expected IPv6 address"),
+        }
+        assert_eq!(local(2), "198.51.100.2:0".parse().unwrap());
+        assert_eq!(list.servers[0].conf.interface.as_deref(), Some("eth0"));
+        assert_eq!(list.servers[2].conf.interface.as_deref(), Some("eth1"));
+
+        // A bind address of the wrong family is rejected.
+        let mut wrong =
+            ServerConf::new("192.0.2.1:53".parse().unwrap(), Transport::Udp);
+        wrong.bind_addr = Some("[2001:db8::2]:0".parse().unwrap());
+        assert!(ServerInfo::local_addr(&wrong, wrong.addr).is_err());
+    }
+
+    #[test]
+    fn query_raw() {
+        use crate::base::iana::OptionCode;
+        use crate::base::octets::OctetsBuilder;
+
+        run(async {
+            // A UDP server echoing the request back as a response.
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut conf = ResolvConf::new();
+            conf.servers.push(ServerConf::new(
+                sock.local_addr().unwrap(),
+                Transport::Udp,
+            ));
+            let resolver = StubResolver::from_conf(conf);
+            let server = async move {
+                let mut buf = vec![0; 512];
+                let (len, addr) = sock.recv_from(&mut buf).await.unwrap();
+                buf.truncate(len);
+                let request = buf.clone();
+                buf[2] |= 0x80;
+                sock.send_to(&buf, addr).await.unwrap();
+                request
+            };
+
+            let mut request = MessageBuilder::new_vec();
+            request.header_mut().set_id(0x1234);
+            request.header_mut().set_cd(true);
+            let mut request = request.question();
+            request.push((Dname::root_ref(), Rtype::Soa)).unwrap();
+            let mut request = request.additional();
+            request
+                .opt(|opt| {
+                    opt.set_udp_payload_size(1400);
+                    opt.set_dnssec_ok(true);
+                    opt.push_raw_option(OptionCode::Int(65001), |target| {
+                        target.append_slice(b"diag")
+                    })
+                })
+                .unwrap();
+            let request = request.into_message();
+
+            let (response, sent) =
+                futures::join!(resolver.query_raw(&request), server);
+            assert_eq!(sent.as_slice(), request.as_slice());
+            let response = response.unwrap();
+            assert!(response.header().qr());
+            assert_eq!(response.header().id(), 0x1234);
+            assert_eq!(&response.as_slice()[3..], &request.as_slice()[3..]);
+        })
+    }
+
+    #[test]
+    fn cache_flags() {
+        use crate::rdata::A;
+
+        run(async {
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut conf = ResolvConf::new();
+            conf.servers.push(ServerConf::new(
+                sock.local_addr().unwrap(),
+                Transport::Udp,
+            ));
+            conf.options.cache_size = 16;
+            let resolver = StubResolver::from_conf(conf);
+            let qname = Dname::<Vec<u8>>::from_str("www.example.").unwrap();
+
+            // The answer to a query with the RD flag set.
+            let mut answer = MessageBuilder::new_bytes();
+            answer.header_mut().set_qr(true);
+            answer.header_mut().set_rd(true);
+            let mut answer = answer.question();
+            answer.push((&qname, Rtype::A)).unwrap();
+            let mut answer = answer.answer();
+            answer
+                .push((&qname, 3600, A::from_octets(192, 0, 2, 1)))
+                .unwrap();
+            resolver.cache().unwrap().insert(&answer.into_message());
+
+            let request = Request::new((&qname, Rtype::A));
+            let cached = resolver.query_request(&request).await.unwrap();
+            assert_eq!(cached.header_counts().ancount(), 1);
+
+            // Without the RD flag, the request has to go to the server.
+            let server = tokio::spawn(async move {
+                let mut buf = vec![0; 512];
+                let (len, addr) = sock.recv_from(&mut buf).await.unwrap();
+                buf.truncate(len);
+                let request = buf.clone();
+                buf[2] |= 0x80;
+                sock.send_to(&buf, addr).await.unwrap();
+                request
+            });
+            let request = request.with_rd(false);
+            let response = resolver.query_request(&request).await.unwrap();
+            assert_eq!(response.header_counts().ancount(), 0);
+            assert!(!response.header().rd());
+            let sent = server.await.unwrap();
+            assert!(!Message::from_octets(sent).unwrap().header().rd());
+        })
+    }
+
+    #[test]
+    fn entropy() {
+        use self::entropy::{AuditLog, EntropyDecision, SeededRandom};
+
+        /// Answers queries by echoing them, possibly lowercasing them.
+        async fn echo(sock: UdpSocket, lowercase: bool) -> (Vec<u8>, u16) {
+            let mut buf = vec![0; 512];
+            let (len, addr) = sock.recv_from(&mut buf).await.unwrap();
+            buf.truncate(len);
+            let request = buf.clone();
+            buf[2] |= 0x80;
+            if lowercase {
+                buf[12..].make_ascii_lowercase();
+            }
+            sock.send_to(&buf, addr).await.unwrap();
+            (request, addr.port())
+        }
+
+        async fn audited(sock: &UdpSocket) -> (StubResolver, AuditLog) {
+            let mut conf = ResolvConf::new();
+            let mut server =
+                ServerConf::new(sock.local_addr().unwrap(), Transport::Udp);
+            server.request_timeout = Duration::from_millis(200);
+            conf.servers.push(server);
+            conf.options.attempts = 1;
+            conf.options.randomize_case = true;
+            let mut resolver = StubResolver::from_conf(conf);
+            let log = AuditLog::new();
+            resolver.set_random_source(SeededRandom::new(42));
+            resolver.set_entropy_audit(log.clone());
+            (resolver, log)
+        }
+
+        run(async {
+            let qname =
+                Dname::<Vec<u8>>::from_str("www.example.com").unwrap();
+
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let (resolver, log) = audited(&sock).await;
+            let (answer, (request, port)) = futures::join!(
+                resolver.query((&qname, Rtype::A)),
+                echo(sock, false)
+            );
+            let answer = answer.unwrap();
+            let request = Message::from_octets(request).unwrap();
+            let sent = request.sole_question().unwrap().qname().to_bytes();
+            assert_eq!(sent, qname);
+            assert_eq!(answer.header().id(), request.header().id());
+            let decisions = log.take();
+            assert!(decisions.contains(&EntropyDecision::MessageId(
+                request.header().id()
+            )));
+            assert!(decisions.contains(&EntropyDecision::SourcePort {
+                port,
+                system: false
+            }));
+            assert!(decisions.contains(&EntropyDecision::QnameCase {
+                qname: sent,
+                bits: 13
+            }));
+
+            // Answers that don’t keep the case are ignored.
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let (resolver, _) = audited(&sock).await;
+            let (answer, _) = futures::join!(
+                resolver.query((&qname, Rtype::A)),
+                echo(sock, true)
+            );
+            assert_eq!(answer.err().unwrap().kind(), io::ErrorKind::TimedOut);
+        })
+    }
+}
@@ -0,0 +1,671 @@
+//! Probing servers for their capabilities.
+//!
+//! When debugging resolution problems, it is often helpful to know which
+//! protocol features an authoritative server supports and whether it
+//! handles them correctly. The function [`probe_server`] sends a number of
+//! queries for the SOA record of a zone served by the server, each one
+//! exercising a specific feature, and collects the results in a
+//! [`Capabilities`] value. This is similar to what the EDNS compliance
+//! tester of ISC does.
+//!
+//! The individual probes are listed in the [`Probe`] enum. For each of
+//! them, the [`Outcome`] tells whether the server handled it correctly,
+//! indicated that it doesn’t support the feature in an acceptable way,
+//! responded incorrectly, or not at all.
+//!
+//! [`probe_server`]: fn.probe_server.html
+//! [`Capabilities`]: struct.Capabilities.html
+//! [`Probe`]: enum.Probe.html
+//! [`Outcome`]: enum.Outcome.html
+
+use super::conf::{ServerConf, Transport};
+use super::edns::DEFAULT_PAYLOAD_SIZE;
+use super::entropy::Entropy;
+use super::{QueryMessage, ServerInfo};
+use crate::base::iana::{OptRcode, OptionCode, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{OctetsBuilder, OctetsRef};
+use crate::base::opt::UnknownOptData;
+use bytes::Bytes;
+use futures::future::join_all;
+use std::fmt;
+use std::vec::Vec;
+
+//------------ probe_server --------------------------------------------------
+
+/// Probes a server for its capabilities.
+///
+/// The server is described by `conf`. Its transport is ignored: all probes
+/// are sent via UDP except for [`Probe::Tcp`]. All other settings,
+/// including the request timeout, are used for each probe. The server
+/// should be authoritative for `zone` since the probes ask for its SOA
+/// record and expect an authoritative answer.
+///
+/// All probes are sent at the same time.
+///
+/// [`Probe::Tcp`]: enum.Probe.html#variant.Tcp
+pub async fn probe_server<N: ToDname>(
+    conf: &ServerConf,
+    zone: &N,
+) -> Capabilities {
+    let udp = ServerInfo::from(ServerConf {
+        transport: Transport::Udp,
+        ..conf.clone()
+    });
+    let tcp = ServerInfo::from(ServerConf {
+        transport: Transport::Tcp,
+        ..conf.clone()
+    });
+    let entropy = Entropy::new(false);
+    let entropy = &entropy;
+    let cookie = rand::random();
+    let answers = join_all(Probe::ALL.iter().map(|&probe| {
+        let server = if probe == Probe::Tcp { &tcp } else { &udp };
+        let message = probe.message(zone, cookie);
+        async move {
+            let answer = server.query(&message, entropy).await;
+            (message, answer)
+        }
+    }))
+    .await;
+
+    let mut res = Capabilities::new();
+    for (&probe, (message, answer)) in Probe::ALL.iter().zip(answers) {
+        let outcome = match answer {
+            Ok(answer) => {
+                let answer = answer.into_message();
+                res.record_data(probe, &answer);
+                probe.evaluate(&message.as_message(), &answer, cookie)
+            }
+            Err(_) => Outcome::NoAnswer,
+        };
+        res.set_outcome(probe, outcome);
+    }
+    res
+}
+
+//------------ Probe ---------------------------------------------------------
+
+/// A single probe sent to a server.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Probe {
+    /// A plain DNS query without EDNS.
+    Plain,
+
+    /// A query with EDNS version 0.
+    ///
+    /// The server should answer with an OPT record of version 0 or, if it
+    /// doesn’t support EDNS, with a FORMERR or NOTIMP response without one.
+    Edns,
+
+    /// A query with EDNS version 1.
+    ///
+    /// A server supporting EDNS should answer with BADVERS and an OPT
+    /// record of the highest version it supports.
+    EdnsVersion,
+
+    /// A query with the DO bit set.
+    ///
+    /// A server supporting DNSSEC copies the bit into its response.
+    Dnssec,
+
+    /// A plain DNS query over TCP.
+    Tcp,
+
+    /// A query with a DNS cookie as defined in RFC 7873.
+    ///
+    /// A server supporting cookies responds with the client cookie followed
+    /// by its server cookie.
+    Cookie,
+
+    /// A query with the NSID option as defined in RFC 5001.
+    ///
+    /// A server supporting the option responds with its identifier.
+    Nsid,
+
+    /// A query with mixed case in the question name.
+    ///
+    /// The server needs to echo the question name exactly for resolvers
+    /// that use the case of the name as additional entropy, a technique
+    /// known as ‘DNS 0x20.’
+    Case,
+}
+
+impl Probe {
+    /// All probes in the order they are reported.
+    pub const ALL: &'static [Probe] = &[
+        Probe::Plain,
+        Probe::Edns,
+        Probe::EdnsVersion,
+        Probe::Dnssec,
+        Probe::Tcp,
+        Probe::Cookie,
+        Probe::Nsid,
+        Probe::Case,
+    ];
+
+    /// Returns the short name of the probe.
+    pub fn name(self) -> &'static str {
+        match self {
+            Probe::Plain => "plain",
+            Probe::Edns => "edns",
+            Probe::EdnsVersion => "edns1",
+            Probe::Dnssec => "do",
+            Probe::Tcp => "tcp",
+            Probe::Cookie => "cookie",
+            Probe::Nsid => "nsid",
+            Probe::Case => "0x20",
+        }
+    }
+
+    /// Returns whether the probe uses EDNS.
+    fn uses_edns(self) -> bool {
+        !matches!(self, Probe::Plain | Probe::Tcp | Probe::Case)
+    }
+
+    /// Creates the query message for the probe.
+    fn message<N: ToDname>(self, zone: &N, cookie: [u8; 8]) -> QueryMessage {
+        let mut message = MessageBuilder::new_stream_vec();
+        message.header_mut().set_random_id();
+        let mut message = message.question();
+        if self == Probe::Case {
+            message.push((mix_case(zone), Rtype::Soa)).unwrap();
+        } else {
+            message.push((zone, Rtype::Soa)).unwrap();
+        }
+        let mut message = message.additional();
+        if self.uses_edns() {
+            message
+                .opt(|opt| {
+                    opt.set_udp_payload_size(DEFAULT_PAYLOAD_SIZE);
+                    match self {
+                        Probe::EdnsVersion => opt.set_version(1),
+                        Probe::Dnssec => opt.set_dnssec_ok(true),
+                        Probe::Cookie => opt.push_raw_option(
+                            OptionCode::Cookie,
+                            |target| target.append_slice(&cookie),
+                        )?,
+                        Probe::Nsid => {
+                            opt.push_raw_option(OptionCode::Nsid, |_| Ok(()))?
+                        }
+                        _ => {}
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        }
+        message
+    }
+
+    /// Evaluates the answer to the probe.
+    ///
+    /// The `cookie` is the client cookie sent with [`Probe::Cookie`].
+    ///
+    /// [`Probe::Cookie`]: #variant.Cookie
+    pub fn evaluate<Octets>(
+        self,
+        query: &Message<Octets>,
+        answer: &Message<Bytes>,
+        cookie: [u8; 8],
+    ) -> Outcome
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        if answer.answers_question(query).is_err() {
+            return Outcome::Broken;
+        }
+        let rcode = answer.header().rcode();
+        if !self.uses_edns() {
+            if answer.opt().is_some()
+                || rcode != Rcode::NoError
+                || !answer.header().aa()
+            {
+                return Outcome::Broken;
+            }
+            if self == Probe::Case
+                && answer.answers_question_exact(query).is_err()
+            {
+                return Outcome::Broken;
+            }
+            return Outcome::Ok;
+        }
+
+        let opt = match answer.opt() {
+            Some(opt) => opt,
+            None => {
+                // RFC 6891, section 7: a server that doesn’t implement EDNS
+                // responds with FORMERR and without an OPT record.
+                return if rcode == Rcode::FormErr || rcode == Rcode::NotImp {
+                    Outcome::Unsupported
+                } else {
+                    Outcome::Broken
+                };
+            }
+        };
+        if opt.version() != 0 {
+            return Outcome::Broken;
+        }
+        let opt_rcode = answer.opt_rcode();
+        match self {
+            Probe::EdnsVersion => {
+                return if opt_rcode == OptRcode::BadVers {
+                    Outcome::Ok
+                } else {
+                    Outcome::Broken
+                };
+            }
+            _ => {
+                if opt_rcode != OptRcode::NoError || !answer.header().aa() {
+                    return Outcome::Broken;
+                }
+            }
+        }
+        match self {
+            Probe::Dnssec => {
+                if opt.dnssec_ok() {
+                    Outcome::Ok
+                } else {
+                    Outcome::Unsupported
+                }
+            }
+            Probe::Cookie => match find_option(answer, OptionCode::Cookie) {
+                Some(data) => {
+                    if (16..=40).contains(&data.len())
+                        && data[..8] == cookie[..]
+                    {
+                        Outcome::Ok
+                    } else {
+                        Outcome::Broken
+                    }
+                }
+                None => Outcome::Unsupported,
+            },
+            Probe::Nsid => {
+                if find_option(answer, OptionCode::Nsid).is_some() {
+                    Outcome::Ok
+                } else {
+                    Outcome::Unsupported
+                }
+            }
+            _ => Outcome::Ok,
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+//------------ Outcome -------------------------------------------------------
+
+/// The outcome of a probe.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Outcome {
+    /// The server handled the probe correctly.
+    Ok,
+
+    /// The server correctly indicated that it doesn’t support the feature.
+    Unsupported,
+
+    /// The server responded incorrectly.
+    Broken,
+
+    /// The server didn’t respond.
+    NoAnswer,
+}
+
+impl Outcome {
+    /// Returns whether the outcome is acceptable.
+    ///
+    /// This is the case if the server either handled the probe or
+    /// correctly indicated that it doesn’t support the feature.
+    pub fn is_acceptable(self) -> bool {
+        matches!(self, Outcome::Ok | Outcome::Unsupported)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Outcome::Ok => "ok",
+            Outcome::Unsupported => "unsupported",
+            Outcome::Broken => "broken",
+            Outcome::NoAnswer => "no answer",
+        })
+    }
+}
+
+//------------ Capabilities --------------------------------------------------
+
+/// The capabilities of a server as discovered by probing.
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// The outcome of each probe in the order of `Probe::ALL`.
+    outcomes: [Outcome; 8],
+
+    /// The UDP payload size advertised by the server.
+    udp_payload_size: Option<u16>,
+
+    /// The name server identifier returned by the server.
+    nsid: Option<Bytes>,
+}
+
+impl Capabilities {
+    /// Creates a new value without any answers.
+    fn new() -> Self {
+        Capabilities {
+            outcomes: [Outcome::NoAnswer; 8],
+            udp_payload_size: None,
+            nsid: None,
+        }
+    }
+
+    /// Returns the index of a probe in `Probe::ALL`.
+    fn index(probe: Probe) -> usize {
+        Probe::ALL.iter().position(|&item| item == probe).unwrap()
+    }
+
+    /// Sets the outcome of a probe.
+    fn set_outcome(&mut self, probe: Probe, outcome: Outcome) {
+        self.outcomes[Self::index(probe)] = outcome
+    }
+
+    /// Records additional data from the answer to a probe.
+    fn record_data(&mut self, probe: Probe, answer: &Message<Bytes>) {
+        match probe {
+            Probe::Edns => {
+                self.udp_payload_size =
+                    answer.opt().map(|opt| opt.udp_payload_size())
+            }
+            Probe::Nsid => self.nsid = find_option(answer, OptionCode::Nsid),
+            _ => {}
+        }
+    }
+
+    /// Returns the outcome of a probe.
+    pub fn outcome(&self, probe: Probe) -> Outcome {
+        self.outcomes[Self::index(probe)]
+    }
+
+    /// Returns whether the server supports EDNS.
+    pub fn edns(&self) -> bool {
+        self.outcome(Probe::Edns) == Outcome::Ok
+    }
+
+    /// Returns whether the server answers via TCP.
+    pub fn tcp(&self) -> bool {
+        self.outcome(Probe::Tcp) == Outcome::Ok
+    }
+
+    /// Returns the UDP payload size advertised by the server.
+    ///
+    /// This is the largest UDP response the server is willing to send. It
+    /// is taken from the answer to the [`Probe::Edns`] probe and is `None`
+    /// if that answer didn’t include an OPT record.
+    ///
+    /// [`Probe::Edns`]: enum.Probe.html#variant.Edns
+    pub fn udp_payload_size(&self) -> Option<u16> {
+        self.udp_payload_size
+    }
+
+    /// Returns the name server identifier returned by the server.
+    pub fn nsid(&self) -> Option<&Bytes> {
+        self.nsid.as_ref()
+    }
+
+    /// Returns whether all probes had an acceptable outcome.
+    pub fn is_compliant(&self) -> bool {
+        self.outcomes.iter().all(|outcome| outcome.is_acceptable())
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (probe, outcome)) in
+            Probe::ALL.iter().zip(self.outcomes.iter()).enumerate()
+        {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}={}", probe, outcome)?;
+        }
+        if let Some(size) = self.udp_payload_size {
+            write!(f, " udp-size={}", size)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the data of the first option of the given type in the answer.
+fn find_option(answer: &Message<Bytes>, code: OptionCode) -> Option<Bytes> {
+    let opt = answer.opt()?;
+    let found = opt
+        .as_opt()
+        .iter::<UnknownOptData<Bytes>>()
+        .filter_map(Result::ok)
+        .find(|option| option.code() == code)
+        .map(|option| option.data().clone());
+    found
+}
+
+/// Returns a copy of the name with every other letter in upper case.
+fn mix_case<N: ToDname>(name: &N) -> Dname<Vec<u8>> {
+    let mut name = name.to_vec().into_octets();
+    let mut upper = true;
+    for ch in name.iter_mut() {
+        if ch.is_ascii_alphabetic() {
+            if upper {
+                ch.make_ascii_uppercase()
+            } else {
+                ch.make_ascii_lowercase()
+            }
+            upper = !upper
+        }
+    }
+    // Length octets are at most 63 and thus never letters, so the name
+    // stays valid.
+    Dname::from_octets(name).unwrap()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::message_builder::AdditionalBuilder;
+    use crate::rdata::Soa;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn zone() -> Dname<Vec<u8>> {
+        Dname::from_str("example.com").unwrap()
+    }
+
+    /// Creates a correct answer to the probe.
+    ///
+    /// The closure can modify the answer before the OPT record is added.
+    fn answer<F>(probe: Probe, query: &QueryMessage, op: F) -> Message<Bytes>
+    where
+        F: FnOnce(&mut AdditionalBuilder<Vec<u8>>),
+    {
+        let query = query.as_message();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        answer.header_mut().set_aa(true);
+        let qname = query.first_question().unwrap().qname().to_vec();
+        answer
+            .push((
+                qname,
+                Class::In,
+                3600,
+                Soa::new(
+                    Dname::<Vec<u8>>::from_str("ns.example.com").unwrap(),
+                    Dname::<Vec<u8>>::from_str("admin.example.com").unwrap(),
+                    1.into(),
+                    3600,
+                    600,
+                    86400,
+                    300,
+                ),
+            ))
+            .unwrap();
+        let mut answer = answer.additional();
+        op(&mut answer);
+        if let Some(opt) = query.opt() {
+            answer
+                .opt(|builder| {
+                    builder.set_udp_payload_size(1400);
+                    if probe == Probe::EdnsVersion {
+                        builder.set_rcode(OptRcode::BadVers);
+                    }
+                    if opt.dnssec_ok() {
+                        builder.set_dnssec_ok(true)
+                    }
+                    for option in
+                        opt.as_opt().iter::<UnknownOptData<_>>().flatten()
+                    {
+                        if option.code() == OptionCode::Cookie {
+                            builder.push_raw_option(
+                                OptionCode::Cookie,
+                                |target| {
+                                    target.append_slice(option.as_slice())?;
+                                    target.append_slice(&[0x11; 8])
+                                },
+                            )?
+                        } else if option.code() == OptionCode::Nsid {
+                            builder.push_raw_option(
+                                OptionCode::Nsid,
+                                |target| target.append_slice(b"ns1"),
+                            )?
+                        }
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        }
+        Message::from_octets(answer.finish().into()).unwrap()
+    }
+
+    /// Creates an answer a server without EDNS support would give.
+    fn formerr(query: &QueryMessage) -> Message<Bytes> {
+        let answer = MessageBuilder::new_vec()
+            .start_answer(&query.as_message(), Rcode::FormErr)
+            .unwrap();
+        Message::from_octets(answer.finish().into()).unwrap()
+    }
+
+    /// Returns a copy of the answer with the ID of the query.
+    fn with_id<Octets: AsRef<[u8]>>(
+        answer: &Message<Bytes>,
+        query: &Message<Octets>,
+    ) -> Message<Bytes> {
+        let mut octets = answer.as_slice().to_vec();
+        octets[..2].copy_from_slice(&query.header().id().to_be_bytes());
+        Message::from_octets(octets.into()).unwrap()
+    }
+
+    #[test]
+    fn compliant() {
+        let cookie = [0x42; 8];
+        let mut caps = Capabilities::new();
+        for &probe in Probe::ALL {
+            let query = probe.message(&zone(), cookie);
+            let answer = answer(probe, &query, |_| ());
+            caps.record_data(probe, &answer);
+            caps.set_outcome(
+                probe,
+                probe.evaluate(&query.as_message(), &answer, cookie),
+            );
+        }
+        assert!(caps.is_compliant());
+        assert!(caps.edns());
+        assert_eq!(caps.udp_payload_size(), Some(1400));
+        assert_eq!(caps.nsid().map(AsRef::as_ref), Some(b"ns1".as_ref()));
+        assert_eq!(
+            caps.to_string(),
+            "plain=ok edns=ok edns1=ok do=ok tcp=ok cookie=ok nsid=ok \
+             0x20=ok udp-size=1400"
+        );
+    }
+
+    #[test]
+    fn no_edns() {
+        let cookie = [0x42; 8];
+        for &probe in Probe::ALL {
+            if !probe.uses_edns() {
+                continue;
+            }
+            let query = probe.message(&zone(), cookie);
+            assert_eq!(
+                probe.evaluate(&query.as_message(), &formerr(&query), cookie),
+                Outcome::Unsupported,
+                "{}",
+                probe
+            );
+        }
+    }
+
+    #[test]
+    fn broken() {
+        let cookie = [0x42; 8];
+
+        // An answer to an EDNS(1) query with NOERROR.
+        let query = Probe::EdnsVersion.message(&zone(), cookie);
+        let answer = answer(Probe::Edns, &query, |_| ());
+        assert_eq!(
+            Probe::EdnsVersion.evaluate(&query.as_message(), &answer, cookie),
+            Outcome::Broken
+        );
+
+        // An answer that doesn’t preserve the case of the question.
+        let query = Probe::Case.message(&zone(), cookie);
+        let plain = Probe::Plain.message(&zone(), cookie);
+        let answer = with_id(
+            &self::answer(Probe::Plain, &plain, |_| ()),
+            &query.as_message(),
+        );
+        assert_eq!(
+            Probe::Case.evaluate(&query.as_message(), &answer, cookie),
+            Outcome::Broken
+        );
+
+        // An answer to a plain query with an OPT record.
+        let edns = Probe::Edns.message(&zone(), cookie);
+        let answer = with_id(
+            &self::answer(Probe::Edns, &edns, |_| ()),
+            &plain.as_message(),
+        );
+        assert_eq!(
+            Probe::Plain.evaluate(&plain.as_message(), &answer, cookie),
+            Outcome::Broken
+        );
+
+        // A cookie that doesn’t echo the client cookie.
+        let query = Probe::Cookie.message(&zone(), cookie);
+        let answer = self::answer(Probe::Cookie, &query, |_| ());
+        assert_eq!(
+            Probe::Cookie.evaluate(&query.as_message(), &answer, [0x43; 8]),
+            Outcome::Broken
+        );
+    }
+
+    #[test]
+    fn mixed_case() {
+        assert_eq!(mix_case(&zone()).to_string(), "ExAmPlE.cOm");
+    }
+}
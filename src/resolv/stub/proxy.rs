@@ -0,0 +1,326 @@
+//! Connecting to upstream servers through a proxy.
+//!
+//! This module implements the client side of the handshakes for the proxy
+//! protocols listed in [`ProxyProtocol`]. Once the handshake has succeeded,
+//! the stream is connected to the server and can be used as if it had been
+//! connected directly.
+//!
+//! [`ProxyProtocol`]: ../conf/enum.ProxyProtocol.html
+
+use super::conf::{ProxyAuth, ProxyConf, ProxyProtocol};
+use crate::utils::base64;
+use std::net::SocketAddr;
+use std::vec::Vec;
+use std::{format, io};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum size of the response header of an HTTP proxy.
+const MAX_HTTP_HEADER: usize = 8192;
+
+//------------ handshake -----------------------------------------------------
+
+/// Asks the proxy at the other end of `sock` to connect to `target`.
+pub(super) async fn handshake<S>(
+    sock: &mut S,
+    proxy: &ProxyConf,
+    target: SocketAddr,
+) -> Result<(), io::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match proxy.protocol {
+        ProxyProtocol::Socks5 => {
+            socks5(sock, proxy.auth.as_ref(), target).await
+        }
+        ProxyProtocol::HttpConnect => {
+            http_connect(sock, proxy.auth.as_ref(), target).await
+        }
+    }
+}
+
+//------------ SOCKS5 --------------------------------------------------------
+
+/// Performs a SOCKS5 handshake as described in RFC 1928.
+async fn socks5<S>(
+    sock: &mut S,
+    auth: Option<&ProxyAuth>,
+    target: SocketAddr,
+) -> Result<(), io::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Method selection. We offer user name and password authentication
+    // only if we have credentials.
+    match auth {
+        Some(_) => sock.write_all(&[5, 1, 2]).await?,
+        None => sock.write_all(&[5, 1, 0]).await?,
+    }
+    let mut reply = [0u8; 2];
+    sock.read_exact(&mut reply).await?;
+    if reply[0] != 5 {
+        return Err(invalid_response("not a SOCKS5 proxy"));
+    }
+    match (reply[1], auth) {
+        (0, _) => {}
+        (2, Some(auth)) => socks5_auth(sock, auth).await?,
+        _ => return Err(refused("no acceptable authentication method")),
+    }
+
+    // The CONNECT request.
+    let mut request = vec![5, 1, 0];
+    match target {
+        SocketAddr::V4(addr) => {
+            request.push(1);
+            request.extend_from_slice(&addr.ip().octets());
+        }
+        SocketAddr::V6(addr) => {
+            request.push(4);
+            request.extend_from_slice(&addr.ip().octets());
+        }
+    }
+    request.extend_from_slice(&target.port().to_be_bytes());
+    sock.write_all(&request).await?;
+
+    // The reply. We need to read the bound address even though we don’t
+    // need it so that the stream is positioned at the server’s data.
+    let mut reply = [0u8; 4];
+    sock.read_exact(&mut reply).await?;
+    if reply[0] != 5 {
+        return Err(invalid_response("not a SOCKS5 proxy"));
+    }
+    if reply[1] != 0 {
+        return Err(refused(socks5_reply_text(reply[1])));
+    }
+    let addr_len = match reply[3] {
+        1 => 4,
+        3 => usize::from(sock.read_u8().await?),
+        4 => 16,
+        _ => return Err(invalid_response("invalid address type in reply")),
+    };
+    let mut bound = vec![0; addr_len + 2];
+    sock.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+/// Performs user name and password authentication as per RFC 1929.
+async fn socks5_auth<S>(
+    sock: &mut S,
+    auth: &ProxyAuth,
+) -> Result<(), io::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let username = auth.username.as_bytes();
+    let password = auth.password.as_bytes();
+    if username.len() > 255 || password.len() > 255 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "proxy credentials too long",
+        ));
+    }
+    let mut request = Vec::with_capacity(3 + username.len() + password.len());
+    request.push(1);
+    request.push(username.len() as u8);
+    request.extend_from_slice(username);
+    request.push(password.len() as u8);
+    request.extend_from_slice(password);
+    sock.write_all(&request).await?;
+    let mut reply = [0u8; 2];
+    sock.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(refused("authentication failed"));
+    }
+    Ok(())
+}
+
+/// Returns a description of a SOCKS5 reply code.
+fn socks5_reply_text(code: u8) -> &'static str {
+    match code {
+        1 => "general SOCKS server failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown SOCKS5 error",
+    }
+}
+
+//------------ HTTP CONNECT --------------------------------------------------
+
+/// Performs an HTTP CONNECT handshake.
+async fn http_connect<S>(
+    sock: &mut S,
+    auth: Option<&ProxyAuth>,
+    target: SocketAddr,
+) -> Result<(), io::Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request =
+        format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+    if let Some(auth) = auth {
+        request.push_str("Proxy-Authorization: Basic ");
+        request.push_str(&base64::encode_string(
+            format!("{}:{}", auth.username, auth.password).as_bytes(),
+        ));
+        request.push_str("\r\n");
+    }
+    request.push_str("\r\n");
+    sock.write_all(request.as_bytes()).await?;
+
+    // Read the response header octet by octet so we don’t consume any
+    // data from the server.
+    let mut header = Vec::new();
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_HTTP_HEADER {
+            return Err(invalid_response("response header too long"));
+        }
+        header.push(sock.read_u8().await?);
+    }
+    let status = header
+        .split(|&ch| ch == b' ')
+        .nth(1)
+        .ok_or_else(|| invalid_response("invalid response"))?;
+    if !header.starts_with(b"HTTP/1.") {
+        return Err(invalid_response("invalid response"));
+    }
+    if status != b"200" {
+        return Err(refused("proxy refused to connect"));
+    }
+    Ok(())
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// Creates an error for a proxy that sent something unexpected.
+fn invalid_response(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Creates an error for a proxy that refused to connect us.
+fn refused(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, msg)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::run;
+    use std::string::String;
+    use tokio::io::duplex;
+
+    fn target() -> SocketAddr {
+        "192.0.2.1:53".parse().unwrap()
+    }
+
+    fn auth() -> ProxyAuth {
+        ProxyAuth {
+            username: "user".into(),
+            password: "pass".into(),
+        }
+    }
+
+    #[test]
+    fn socks5() {
+        run(async {
+            let (mut client, mut proxy) = duplex(1024);
+            let server = async move {
+                let mut buf = [0u8; 3];
+                proxy.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, [5, 1, 2]);
+                proxy.write_all(&[5, 2]).await.unwrap();
+                let mut buf = [0u8; 11];
+                proxy.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"\x01\x04user\x04pass");
+                proxy.write_all(&[1, 0]).await.unwrap();
+                let mut buf = [0u8; 10];
+                proxy.read_exact(&mut buf).await.unwrap();
+                assert_eq!(buf, [5, 1, 0, 1, 192, 0, 2, 1, 0, 53]);
+                proxy
+                    .write_all(&[5, 0, 0, 1, 10, 0, 0, 1, 0x12, 0x34, 42])
+                    .await
+                    .unwrap();
+                proxy
+            };
+            let mut conf = ProxyConf::new(
+                ProxyProtocol::Socks5,
+                "127.0.0.1:9050".parse().unwrap(),
+            );
+            conf.auth = Some(auth());
+            let (res, _proxy) = futures::join!(
+                handshake(&mut client, &conf, target()),
+                server
+            );
+            res.unwrap();
+            // The server’s data is left in the stream.
+            assert_eq!(client.read_u8().await.unwrap(), 42);
+        });
+    }
+
+    #[test]
+    fn socks5_refused() {
+        run(async {
+            let (mut client, mut proxy) = duplex(1024);
+            let server = async move {
+                let mut buf = [0u8; 3];
+                proxy.read_exact(&mut buf).await.unwrap();
+                proxy.write_all(&[5, 0]).await.unwrap();
+                let mut buf = [0u8; 10];
+                proxy.read_exact(&mut buf).await.unwrap();
+                proxy.write_all(&[5, 5, 0, 1]).await.unwrap();
+                proxy
+            };
+            let conf = ProxyConf::new(
+                ProxyProtocol::Socks5,
+                "127.0.0.1:9050".parse().unwrap(),
+            );
+            let (res, _proxy) = futures::join!(
+                handshake(&mut client, &conf, target()),
+                server
+            );
+            assert_eq!(format!("{}", res.unwrap_err()), "connection refused");
+        });
+    }
+
+    #[test]
+    fn http_connect() {
+        run(async {
+            let (mut client, mut proxy) = duplex(1024);
+            let server = async move {
+                let mut request = Vec::new();
+                while !request.ends_with(b"\r\n\r\n") {
+                    request.push(proxy.read_u8().await.unwrap());
+                }
+                proxy
+                    .write_all(b"HTTP/1.1 200 OK\r\nX-Foo: bar\r\n\r\n*")
+                    .await
+                    .unwrap();
+                (proxy, String::from_utf8(request).unwrap())
+            };
+            let mut conf = ProxyConf::new(
+                ProxyProtocol::HttpConnect,
+                "127.0.0.1:3128".parse().unwrap(),
+            );
+            conf.auth = Some(auth());
+            let (res, (_proxy, request)) = futures::join!(
+                handshake(&mut client, &conf, target()),
+                server
+            );
+            res.unwrap();
+            assert_eq!(
+                request,
+                "CONNECT 192.0.2.1:53 HTTP/1.1\r\n\
+                 Host: 192.0.2.1:53\r\n\
+                 Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+            );
+            assert_eq!(client.read_u8().await.unwrap(), b'*');
+        });
+    }
+}
@@ -0,0 +1,151 @@
+//! Limiting the rate of outgoing queries.
+//!
+//! The limits configured via [`ResolvOptions::rate_limit`] and
+//! [`ResolvOptions::server_rate_limit`] are enforced by token buckets. A
+//! query takes a token from each bucket that applies to its server. If
+//! there are no tokens left, it takes one anyway and waits until the
+//! bucket would have refilled enough. This way, queries go out in the
+//! order they arrived.
+//!
+//! [`ResolvOptions::rate_limit`]: ../conf/struct.ResolvOptions.html#structfield.rate_limit
+//! [`ResolvOptions::server_rate_limit`]: ../conf/struct.ResolvOptions.html#structfield.server_rate_limit
+
+use super::conf::{RateLimit, ResolvConf};
+use super::ServerList;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use tokio::time::sleep;
+
+//------------ RateLimiter ---------------------------------------------------
+
+/// A token bucket limiting the rate of queries.
+#[derive(Debug)]
+pub(super) struct RateLimiter {
+    /// The number of tokens added per second.
+    rate: f64,
+
+    /// The maximum number of tokens.
+    burst: f64,
+
+    /// The current state of the bucket.
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// The number of tokens at `updated`.
+    ///
+    /// This becomes negative if queries are waiting for tokens.
+    tokens: f64,
+
+    /// When `tokens` was last updated.
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a new limiter with a full bucket.
+    pub fn new(limit: RateLimit) -> Self {
+        let burst = f64::from(limit.burst.max(1));
+        RateLimiter {
+            rate: f64::from(limit.qps.max(1)),
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token and returns how long to wait before sending.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        if now > bucket.updated {
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens =
+                (bucket.tokens + elapsed * self.rate).min(self.burst);
+            bucket.updated = now;
+        }
+        bucket.tokens -= 1.;
+        if bucket.tokens >= 0. {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        }
+    }
+
+    /// Waits until a query may be sent.
+    pub async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if wait > Duration::from_secs(0) {
+            sleep(wait).await
+        }
+    }
+}
+
+//------------ apply ---------------------------------------------------------
+
+/// Attaches the limiters configured in `conf` to the servers in `lists`.
+///
+/// A single limiter is used for all servers with the same address, even
+/// if they appear in several lists.
+pub(super) fn apply(conf: &ResolvConf, lists: &mut [&mut ServerList]) {
+    let global = conf
+        .options
+        .rate_limit
+        .map(|limit| Arc::new(RateLimiter::new(limit)));
+    let mut per_server: Vec<(SocketAddr, Arc<RateLimiter>)> = Vec::new();
+    for list in lists.iter_mut() {
+        for server in list.servers.iter_mut() {
+            if let Some(limit) = conf.options.server_rate_limit {
+                let addr = server.conf.addr;
+                let limiter =
+                    match per_server.iter().find(|(item, _)| *item == addr) {
+                        Some((_, limiter)) => limiter.clone(),
+                        None => {
+                            let limiter = Arc::new(RateLimiter::new(limit));
+                            per_server.push((addr, limiter.clone()));
+                            limiter
+                        }
+                    };
+                server.limiters.push(limiter);
+            }
+            if let Some(ref global) = global {
+                server.limiters.push(global.clone())
+            }
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reserve() {
+        let limiter = RateLimiter::new(RateLimit::with_burst(10, 2));
+        let start = Instant::now();
+
+        // The burst goes out right away, then we wait 100ms per query.
+        assert_eq!(limiter.reserve(start), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(start), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(start), Duration::from_millis(100));
+        assert_eq!(limiter.reserve(start), Duration::from_millis(200));
+
+        // After a second, the waiting queries are paid for and the bucket
+        // is full again.
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.reserve(later), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(later), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(later), Duration::from_millis(100));
+
+        // The bucket never holds more than the burst.
+        let much_later = start + Duration::from_secs(60);
+        assert_eq!(limiter.reserve(much_later), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(much_later), Duration::from_secs(0));
+        assert_eq!(limiter.reserve(much_later), Duration::from_millis(100));
+    }
+}
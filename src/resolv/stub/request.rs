@@ -0,0 +1,323 @@
+//! Crafting request messages.
+//!
+//! The query methods of the stub resolver take a single question and
+//! create a standard query with the RD flag set from it. A [`Request`]
+//! allows building other request messages while still using the
+//! resolver’s servers, transports, and retries. It can set the opcode and
+//! the RD, AD, and CD flags and contain more than one question. Note,
+//! however, that most servers refuse requests with more than one
+//! question.
+//!
+//! Questions of any class can be asked. For instance, the version of the
+//! server software is often available as a TXT record for `version.bind.`
+//! in the CH class:
+//!
+//! ```
+//! use domain::base::iana::{Class, Rtype};
+//! use domain::base::name::Dname;
+//! use domain::resolv::stub::request::Request;
+//! use std::str::FromStr;
+//!
+//! let request = Request::new((
+//!     Dname::<Vec<u8>>::from_str("version.bind.").unwrap(),
+//!     Rtype::Txt,
+//!     Class::Ch,
+//! ));
+//! assert_eq!(request.questions()[0].qclass(), Class::Ch);
+//! ```
+//!
+//! Only requests with a single question, the standard query opcode, and
+//! the default flags – RD set, AD and CD cleared – are answered from or
+//! stored in the resolver’s answer cache.
+//!
+//! Diagnostic tools that need full control over the request can build the
+//! complete message themselves and send it via
+//! [`StubResolver::query_raw`]. The header including the ID and flags as
+//! well as all questions and records, including any OPT record with its
+//! EDNS options, are sent unchanged and the response is returned as is.
+//! The only difference is that compressed domain names are sent
+//! uncompressed. Such requests bypass the query filter
+//! and the answer cache and the resolver won’t fall back to sending them
+//! without EDNS. They still use the resolver’s servers, transports, and
+//! retries, including the switch to a stream transport for truncated
+//! responses.
+//!
+//! [`Request`]: struct.Request.html
+//! [`StubResolver::query_raw`]: ../struct.StubResolver.html#method.query_raw
+
+use super::QueryMessage;
+use crate::base::iana::Opcode;
+use crate::base::message::{CopyRecordsError, Message};
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::ShortBuf;
+use crate::base::question::Question;
+use crate::rdata::AllRecordData;
+use std::io;
+use std::vec::Vec;
+
+//------------ Request -------------------------------------------------------
+
+/// A request message to be sent by the stub resolver.
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// The opcode of the request.
+    opcode: Opcode,
+
+    /// The value of the RD flag.
+    rd: bool,
+
+    /// The value of the AD flag.
+    ad: bool,
+
+    /// The value of the CD flag.
+    cd: bool,
+
+    /// The questions of the request.
+    questions: Vec<Question<Dname<Vec<u8>>>>,
+}
+
+impl Request {
+    /// The maximum length of a request message.
+    const MAX_LEN: usize = 512;
+
+    /// Creates a standard query for the given question.
+    ///
+    /// The request has the RD flag set and all other flags cleared.
+    pub fn new<N: ToDname, Q: Into<Question<N>>>(question: Q) -> Self {
+        Request {
+            opcode: Opcode::Query,
+            rd: true,
+            ad: false,
+            cd: false,
+            questions: Vec::new(),
+        }
+        .with_question(question)
+    }
+
+    /// Adds another question to the request.
+    pub fn with_question<N: ToDname, Q: Into<Question<N>>>(
+        mut self,
+        question: Q,
+    ) -> Self {
+        let question = question.into();
+        self.questions.push(Question::new(
+            question.qname().to_vec(),
+            question.qtype(),
+            question.qclass(),
+        ));
+        self
+    }
+
+    /// Sets the opcode of the request.
+    pub fn with_opcode(mut self, opcode: Opcode) -> Self {
+        self.opcode = opcode;
+        self
+    }
+
+    /// Sets the RD flag asking the server to recurse.
+    pub fn with_rd(mut self, rd: bool) -> Self {
+        self.rd = rd;
+        self
+    }
+
+    /// Sets the AD flag asking the server to indicate authenticated data.
+    pub fn with_ad(mut self, ad: bool) -> Self {
+        self.ad = ad;
+        self
+    }
+
+    /// Sets the CD flag asking the server not to validate.
+    pub fn with_cd(mut self, cd: bool) -> Self {
+        self.cd = cd;
+        self
+    }
+
+    /// Returns the opcode of the request.
+    pub fn opcode(&self) -> Opcode {
+        self.opcode
+    }
+
+    /// Returns the questions of the request.
+    pub fn questions(&self) -> &[Question<Dname<Vec<u8>>>] {
+        &self.questions
+    }
+
+    /// Creates the message for the request.
+    ///
+    /// Requests are limited to the size of a plain UDP message.
+    pub(super) fn to_message(&self) -> Result<QueryMessage, ShortBuf> {
+        let mut message = MessageBuilder::new_stream_vec();
+        let header = message.header_mut();
+        header.set_opcode(self.opcode);
+        header.set_rd(self.rd);
+        header.set_ad(self.ad);
+        header.set_cd(self.cd);
+        let mut message = message.question();
+        message.set_push_limit(Self::MAX_LEN);
+        for question in &self.questions {
+            message.push(question)?;
+        }
+        message.clear_push_limit();
+        Ok(message.additional())
+    }
+}
+
+//------------ copy_message --------------------------------------------------
+
+/// Copies a complete request message for sending it unchanged.
+///
+/// The header including the ID is taken over as is. The record data of
+/// the record types defined in RFC 1035 is parsed so that compressed
+/// names are written out in full. The record data of all other types is
+/// copied verbatim, so the OPT record keeps its payload size, extended
+/// flags, and options.
+pub(super) fn copy_message(
+    message: Message<&[u8]>,
+) -> Result<QueryMessage, io::Error> {
+    fn malformed<T>(_: T) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "malformed request")
+    }
+    fn too_long<T>(_: T) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, "request too long")
+    }
+
+    // Check all records first so that copying below doesn’t silently
+    // drop those that fail to parse.
+    for item in message.iter() {
+        let (record, _) = item.map_err(malformed)?;
+        record
+            .into_record::<AllRecordData<_, ParsedDname<_>>>()
+            .map_err(malformed)?;
+    }
+
+    let mut target = MessageBuilder::new_stream_vec();
+    *target.header_mut() = message.header();
+    let mut target = target.question();
+    for question in message.question() {
+        target
+            .push(question.map_err(malformed)?)
+            .map_err(too_long)?;
+    }
+    message
+        .copy_records(target, |record| {
+            record
+                .into_record::<AllRecordData<_, ParsedDname<_>>>()
+                .ok()
+                .flatten()
+        })
+        .map_err(|err| match err {
+            CopyRecordsError::Parse(_) => malformed(()),
+            CopyRecordsError::ShortBuf => too_long(()),
+        })
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rtype};
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn to_message() {
+        let message =
+            Request::new((name("version.bind."), Rtype::Txt, Class::Ch))
+                .with_question((
+                    name("hostname.bind."),
+                    Rtype::Txt,
+                    Class::Ch,
+                ))
+                .with_opcode(Opcode::Query)
+                .with_rd(false)
+                .with_cd(true)
+                .to_message()
+                .unwrap();
+        let message = message.as_message();
+        let header = message.header();
+        assert_eq!(header.opcode(), Opcode::Query);
+        assert!(!header.rd());
+        assert!(!header.ad());
+        assert!(header.cd());
+        let questions: Vec<_> =
+            message.question().map(|item| item.unwrap()).collect();
+        assert_eq!(questions.len(), 2);
+        assert_eq!(questions[0].qname(), &name("version.bind."));
+        assert_eq!(questions[0].qclass(), Class::Ch);
+        assert_eq!(questions[1].qname(), &name("hostname.bind."));
+
+        let mut long = Request::new((name("a.example."), Rtype::A));
+        for _ in 0..50 {
+            long = long.with_question((name("a.example."), Rtype::A));
+        }
+        assert!(long.to_message().is_err());
+    }
+
+    #[test]
+    fn copy_message() {
+        use crate::base::iana::OptionCode;
+        use crate::base::message_builder::StaticCompressor;
+        use crate::base::octets::OctetsBuilder;
+        use crate::rdata::{Ns, Txt};
+
+        // A message with compressed names in the record data.
+        let mut msg =
+            MessageBuilder::from_target(StaticCompressor::new(Vec::new()))
+                .unwrap();
+        msg.header_mut().set_id(0x4711);
+        let mut msg = msg.question();
+        msg.push((name("sub.example.org."), Rtype::Ns)).unwrap();
+        let mut msg = msg.authority();
+        msg.push((
+            name("sub.example.org."),
+            3600,
+            Ns::new(name("ns.sub.example.org.")),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            name("ns.sub.example.org."),
+            3600,
+            Txt::<Vec<u8>>::from_slice(&[b'x'; 200]).unwrap(),
+        ))
+        .unwrap();
+        msg.push((
+            name("ns.sub.example.org."),
+            3600,
+            Txt::<Vec<u8>>::from_slice(&[b'y'; 200]).unwrap(),
+        ))
+        .unwrap();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(1232);
+            opt.push_raw_option(OptionCode::Int(65001), |target| {
+                target.append_slice(b"diag")
+            })
+        })
+        .unwrap();
+        let msg = msg.into_message();
+        let msg = Message::from_octets(msg.as_slice()).unwrap();
+
+        let copy = super::copy_message(msg).unwrap();
+        let copy = copy.as_message();
+        assert!(copy.as_slice().len() > 512);
+        assert_eq!(copy.header(), msg.header());
+        assert_eq!(copy.header_counts(), msg.header_counts());
+        let ns = copy
+            .authority()
+            .unwrap()
+            .limit_to::<Ns<_>>()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ns.owner(), &name("sub.example.org."));
+        assert_eq!(ns.data().nsdname(), &name("ns.sub.example.org."));
+        let opt = copy.opt().unwrap();
+        assert_eq!(opt.udp_payload_size(), 1232);
+        assert_eq!(opt.as_opt(), msg.opt().unwrap().as_opt());
+    }
+}
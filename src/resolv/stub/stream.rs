@@ -0,0 +1,472 @@
+//! Answers streamed record by record.
+//!
+//! Some answers don’t fit into a single message. Most notably, a zone
+//! transfer via AXFR spans as many messages as the zone needs. Instead of
+//! collecting all of them before handing out the answer, the stub
+//! resolver’s [`query_stream`] returns an [`AnswerStream`] that produces
+//! the records of the answer section as each message arrives over TCP.
+//!
+//! For AXFR and IXFR queries, the stream ends with the SOA record that
+//! closes the transfer. For all other queries, it ends after the first
+//! message.
+//!
+//! [`query_stream`]: ../struct.StubResolver.html#method.query_stream
+//! [`AnswerStream`]: struct.AnswerStream.html
+
+use super::entropy::Entropy;
+use super::{QueryMessage, ServerInfo, ServerList};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::Dname;
+use crate::base::octets::OctetsFrom;
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::AllRecordData;
+use bytes::Bytes;
+use futures::stream::{self, Stream};
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::vec::Vec;
+use std::{fmt, format, io};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+//------------ StreamRecord --------------------------------------------------
+
+/// A record produced by an answer stream.
+pub type StreamRecord =
+    Record<Dname<Bytes>, AllRecordData<Bytes, Dname<Bytes>>>;
+
+//------------ AnswerStream --------------------------------------------------
+
+/// The records of an answer as they arrive.
+///
+/// The type implements [`Stream`] producing the records of the answer
+/// sections of all messages that make up the answer. If an error happens,
+/// it is produced as the last item of the stream.
+///
+/// [`Stream`]: https://docs.rs/futures/0.3/futures/stream/trait.Stream.html
+pub struct AnswerStream {
+    inner:
+        Pin<Box<dyn Stream<Item = Result<StreamRecord, io::Error>> + Send>>,
+}
+
+impl AnswerStream {
+    /// Sends `query` to the first of `servers` that accepts a connection.
+    pub(super) async fn start(
+        servers: &ServerList,
+        mut query: QueryMessage,
+        entropy: &Entropy,
+    ) -> Result<Self, io::Error> {
+        if servers.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no stream servers configured",
+            ));
+        }
+        let mut counter = servers.counter(false);
+        loop {
+            match Self::connect(counter.info(servers), &mut query, entropy)
+                .await
+            {
+                Ok(state) => {
+                    return Ok(AnswerStream {
+                        inner: Box::pin(stream::unfold(
+                            state,
+                            |mut state| async move {
+                                state.next().await.map(|item| (item, state))
+                            },
+                        )),
+                    })
+                }
+                Err(err) => {
+                    if !counter.next() {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Connects to `server` and sends the query.
+    async fn connect(
+        server: &ServerInfo,
+        query: &mut QueryMessage,
+        entropy: &Entropy,
+    ) -> Result<State, io::Error> {
+        server.wait_for_rate_limit().await;
+        server.prepare_message(query, entropy);
+        let wait = server.conf.request_timeout;
+        let mut sock = match timeout(
+            wait,
+            ServerInfo::tcp_connect(&server.conf),
+        )
+        .await
+        {
+            Ok(res) => res?,
+            Err(_) => return Err(timed_out()),
+        };
+        sock.write_all(query.as_target().as_stream_slice()).await?;
+        let query = Message::from_octets(Bytes::copy_from_slice(
+            query.as_target().as_dgram_slice(),
+        ))
+        .map_err(|_| invalid_data("invalid query"))?;
+        let qtype = query.sole_question().map(|q| q.qtype()).ok();
+        Ok(State {
+            sock,
+            query,
+            timeout: wait,
+            xfr: matches!(qtype, Some(Rtype::Axfr) | Some(Rtype::Ixfr)),
+            started: false,
+            records: VecDeque::new(),
+            first_serial: None,
+            count: 0,
+            pending: 0,
+            done: false,
+        })
+    }
+}
+
+//--- Stream
+
+impl Stream for AnswerStream {
+    type Item = Result<StreamRecord, io::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+//--- Debug
+
+impl fmt::Debug for AnswerStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AnswerStream")
+    }
+}
+
+//------------ State ---------------------------------------------------------
+
+/// The state of an answer stream.
+struct State {
+    /// The connection to the server.
+    sock: TcpStream,
+
+    /// The query we sent.
+    query: Message<Bytes>,
+
+    /// How long to wait for each message.
+    timeout: Duration,
+
+    /// Whether the query is a zone transfer.
+    xfr: bool,
+
+    /// Whether we have received the first message.
+    started: bool,
+
+    /// The records received but not yet produced.
+    records: VecDeque<StreamRecord>,
+
+    /// The serial of the SOA record starting a zone transfer.
+    first_serial: Option<Serial>,
+
+    /// The number of records of a zone transfer received so far.
+    count: usize,
+
+    /// The number of SOA records still expected to end a zone transfer.
+    pending: usize,
+
+    /// Whether all messages have been received.
+    done: bool,
+}
+
+impl State {
+    /// Produces the next item of the stream.
+    async fn next(&mut self) -> Option<Result<StreamRecord, io::Error>> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Some(Ok(record));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.receive().await {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+
+    /// Receives the next message and queues its records.
+    async fn receive(&mut self) -> Result<(), io::Error> {
+        let message = match timeout(self.timeout, self.read_message()).await {
+            Ok(res) => res?,
+            Err(_) => return Err(timed_out()),
+        };
+        let rcode = message.header().rcode();
+        if rcode != Rcode::NoError && rcode != Rcode::NXDomain {
+            let kind = match rcode {
+                Rcode::Refused | Rcode::NotAuth => {
+                    io::ErrorKind::PermissionDenied
+                }
+                _ => io::ErrorKind::InvalidData,
+            };
+            return Err(io::Error::new(
+                kind,
+                format!("server responded with {}", rcode),
+            ));
+        }
+        let section = message
+            .answer()
+            .map_err(|_| invalid_data("invalid answer"))?;
+        for record in section.limit_to::<AllRecordData<_, _>>() {
+            let record =
+                record.map_err(|_| invalid_data("invalid record"))?;
+            let record = StreamRecord::octets_from(record)
+                .map_err(|_| invalid_data("invalid record"))?;
+            if self.xfr {
+                self.check_xfr(&record)?;
+            }
+            self.records.push_back(record);
+            if self.done {
+                return Ok(());
+            }
+        }
+        // A single SOA record is a complete IXFR answer for a client that
+        // is up to date. Everything else is complete after one message.
+        if !self.xfr
+            || (self.count == 1
+                && self.query.sole_question().map(|q| q.qtype()).ok()
+                    == Some(Rtype::Ixfr))
+        {
+            self.done = true;
+        }
+        Ok(())
+    }
+
+    /// Tracks the records of a zone transfer to find its end.
+    ///
+    /// A transfer starts and ends with the SOA record of the zone. If the
+    /// second record is a SOA record with a different serial, this is an
+    /// incremental transfer in which the new SOA record appears once more
+    /// before the end.
+    fn check_xfr(&mut self, record: &StreamRecord) -> Result<(), io::Error> {
+        self.count += 1;
+        let serial = match record.data() {
+            AllRecordData::Soa(soa) => Some(soa.serial()),
+            _ => None,
+        };
+        match (self.first_serial, serial) {
+            (None, Some(serial)) => {
+                self.first_serial = Some(serial);
+                self.pending = 1;
+            }
+            (None, None) => {
+                return Err(invalid_data("zone transfer without SOA"))
+            }
+            (Some(first), Some(serial)) if first == serial => {
+                self.pending -= 1;
+                if self.pending == 0 {
+                    self.done = true
+                }
+            }
+            (Some(_), Some(_)) if self.count == 2 => self.pending = 2,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Reads the next message belonging to our query.
+    ///
+    /// The first message has to be an answer to the query. Later messages
+    /// of a zone transfer may omit the question and are only checked for
+    /// their ID.
+    async fn read_message(&mut self) -> Result<Message<Bytes>, io::Error> {
+        loop {
+            let len = usize::from(self.sock.read_u16().await?);
+            let mut buf = Vec::with_capacity(len);
+            AsyncReadExt::take(&mut self.sock, len as u64)
+                .read_to_end(&mut buf)
+                .await?;
+            if buf.len() != len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed",
+                ));
+            }
+            let message = Message::from_octets(Bytes::from(buf))
+                .map_err(|_| invalid_data("short message"))?;
+            let matches = if self.started {
+                message.header().qr()
+                    && message.header().id() == self.query.header().id()
+            } else {
+                message.is_answer(&self.query)
+            };
+            if matches {
+                self.started = true;
+                return Ok(message);
+            }
+        }
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "request timed out")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::super::conf::{ResolvConf, ServerConf, Transport};
+    use super::super::StubResolver;
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::rdata::{Soa, A};
+    use futures::stream::StreamExt;
+    use std::str::FromStr;
+    use tokio::net::TcpListener;
+
+    type Data = AllRecordData<Vec<u8>, Dname<Vec<u8>>>;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn soa(serial: u32) -> Data {
+        Soa::new(
+            name("ns.example."),
+            name("hostmaster.example."),
+            Serial(serial),
+            3600,
+            600,
+            86400,
+            300,
+        )
+        .into()
+    }
+
+    fn a(last: u8) -> Data {
+        A::from_octets(192, 0, 2, last).into()
+    }
+
+    /// Answers a single query with the given messages.
+    ///
+    /// The connection is kept open until the client closes it so that the
+    /// stream has to find the end of the answer by itself.
+    async fn serve(
+        listener: TcpListener,
+        rcode: Rcode,
+        messages: Vec<Vec<Data>>,
+    ) {
+        let (mut sock, _) = listener.accept().await.unwrap();
+        let len = usize::from(sock.read_u16().await.unwrap());
+        let mut query = vec![0; len];
+        sock.read_exact(&mut query).await.unwrap();
+        let query = Message::from_octets(query).unwrap();
+        for records in messages {
+            let mut answer = MessageBuilder::new_stream_vec()
+                .start_answer(&query, rcode)
+                .unwrap();
+            for data in records {
+                answer
+                    .push((name("example."), Class::In, 3600, data))
+                    .unwrap();
+            }
+            sock.write_all(answer.finish().as_stream_slice())
+                .await
+                .unwrap();
+        }
+        let _ = sock.read_to_end(&mut Vec::new()).await;
+    }
+
+    fn query(
+        rtype: Rtype,
+        rcode: Rcode,
+        messages: Vec<Vec<Data>>,
+    ) -> Vec<Result<StreamRecord, io::Error>> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let listener =
+                    TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let mut conf = ResolvConf::new();
+                conf.servers.push(ServerConf::new(
+                    listener.local_addr().unwrap(),
+                    Transport::Tcp,
+                ));
+                let server = tokio::spawn(serve(listener, rcode, messages));
+                let resolver = StubResolver::from_conf(conf);
+                let res = resolver
+                    .query_stream((name("example."), rtype))
+                    .await
+                    .unwrap()
+                    .collect::<Vec<_>>()
+                    .await;
+                server.await.unwrap();
+                res
+            })
+    }
+
+    fn rtypes(res: Vec<Result<StreamRecord, io::Error>>) -> Vec<Rtype> {
+        res.into_iter().map(|item| item.unwrap().rtype()).collect()
+    }
+
+    #[test]
+    fn axfr() {
+        let res = query(
+            Rtype::Axfr,
+            Rcode::NoError,
+            vec![vec![soa(1), a(1)], vec![a(2)], vec![a(3), soa(1)]],
+        );
+        assert_eq!(
+            rtypes(res),
+            vec![Rtype::Soa, Rtype::A, Rtype::A, Rtype::A, Rtype::Soa]
+        );
+    }
+
+    #[test]
+    fn ixfr() {
+        // Deltas from serial 1 to 2 and from 2 to 3.
+        let res = query(
+            Rtype::Ixfr,
+            Rcode::NoError,
+            vec![
+                vec![soa(3), soa(1), a(1), soa(2), a(2)],
+                vec![soa(2), a(2), soa(3)],
+                vec![a(3), soa(3)],
+            ],
+        );
+        assert_eq!(res.len(), 10);
+        assert!(res.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn single_message() {
+        let res = query(Rtype::A, Rcode::NoError, vec![vec![a(1), a(2)]]);
+        assert_eq!(rtypes(res), vec![Rtype::A, Rtype::A]);
+    }
+
+    #[test]
+    fn refused() {
+        let res = query(Rtype::Axfr, Rcode::Refused, vec![vec![]]);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_err());
+    }
+}
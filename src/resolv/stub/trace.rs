@@ -0,0 +1,715 @@
+//! Tracing the delegations leading to a name.
+//!
+//! The function [`trace`] resolves a question iteratively, starting at the
+//! root servers and following referrals until a server answers the
+//! question, similar to what `dig +trace` does. Each server response is
+//! recorded as a [`TraceStep`] containing the zone the server was asked
+//! for, the server that responded, the round-trip time, the referral if
+//! there was one, and whether the response contained DNSSEC data. The
+//! complete chain is returned as a [`Trace`].
+//!
+//! The DNSSEC status of a step only reflects which records the server
+//! included in its response. The signatures are not validated.
+//!
+//! [`trace`]: fn.trace.html
+//! [`TraceStep`]: struct.TraceStep.html
+//! [`Trace`]: struct.Trace.html
+
+use super::conf::{ServerConf, Transport};
+use super::edns::DEFAULT_PAYLOAD_SIZE;
+use super::entropy::Entropy;
+use super::{QueryMessage, ServerInfo};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::OctetsRef;
+use crate::rdata::{Aaaa, Ns, A};
+use crate::resolv::lookup::host::lookup_host;
+use crate::resolv::resolver::Resolver;
+use bytes::Bytes;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum number of referrals followed before giving up.
+pub const MAX_REFERRALS: usize = 32;
+
+//------------ trace ---------------------------------------------------------
+
+/// Traces the delegations for a question starting at the root.
+///
+/// The root servers are given via `roots`. The queries are sent via UDP
+/// with the DO bit set and repeated via TCP if the response is truncated.
+/// The servers of each zone are tried in the order they are listed in the
+/// referral until one of them responds.
+///
+/// If a referral doesn’t contain glue for any of the name servers of a
+/// zone, their addresses are looked up via `resolver`.
+pub async fn trace<R: Resolver, N: ToDname>(
+    resolver: &R,
+    roots: &[SocketAddr],
+    qname: &N,
+    qtype: Rtype,
+) -> Result<Trace, TraceError>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    let qname = qname.to_vec();
+    let mut zone = Dname::root_vec();
+    let mut servers = roots.to_vec();
+    let mut steps = Vec::new();
+    let mut message = query_message(&qname, qtype);
+
+    loop {
+        if steps.len() > MAX_REFERRALS {
+            return Err(TraceError::TooManyReferrals);
+        }
+        let (server, rtt, response) =
+            match query_servers(&servers, &mut message).await {
+                Some(res) => res,
+                None => return Err(TraceError::NoResponse(zone)),
+            };
+        let step =
+            TraceStep::new(zone, servers, server, rtt, &qname, &response)?;
+        let referral = match step.referral {
+            Some(ref referral) => referral.clone(),
+            None => {
+                steps.push(step);
+                return Ok(Trace {
+                    qname,
+                    qtype,
+                    steps,
+                    response,
+                });
+            }
+        };
+        steps.push(step);
+        zone = referral.zone.clone();
+        servers = referral
+            .glue
+            .iter()
+            .map(|(_, addr)| (*addr, 53).into())
+            .collect();
+        if servers.is_empty() {
+            servers = lookup_servers(resolver, &referral.ns).await;
+        }
+        if servers.is_empty() {
+            return Err(TraceError::NoAddresses(zone));
+        }
+    }
+}
+
+/// Creates the query message for the question.
+fn query_message(qname: &Dname<Vec<u8>>, qtype: Rtype) -> QueryMessage {
+    let message = MessageBuilder::new_stream_vec();
+    let mut message = message.question();
+    message.push((qname, qtype)).unwrap();
+    let mut message = message.additional();
+    message
+        .opt(|opt| {
+            opt.set_udp_payload_size(DEFAULT_PAYLOAD_SIZE);
+            opt.set_dnssec_ok(true);
+            Ok(())
+        })
+        .unwrap();
+    message
+}
+
+/// Sends the query to the servers in turn until one of them responds.
+async fn query_servers(
+    servers: &[SocketAddr],
+    message: &mut QueryMessage,
+) -> Option<(SocketAddr, Duration, Message<Bytes>)> {
+    let entropy = Entropy::new(false);
+    for &addr in servers {
+        message.header_mut().set_id(entropy.message_id());
+        let start = Instant::now();
+        let udp = ServerInfo::from(ServerConf::new(addr, Transport::Udp));
+        let answer = match udp.query(message, &entropy).await {
+            Ok(answer) if answer.is_truncated() => {
+                let tcp =
+                    ServerInfo::from(ServerConf::new(addr, Transport::Tcp));
+                match tcp.query(message, &entropy).await {
+                    Ok(answer) => answer,
+                    Err(_) => continue,
+                }
+            }
+            Ok(answer) => answer,
+            Err(_) => continue,
+        };
+        return Some((addr, start.elapsed(), answer.into_message()));
+    }
+    None
+}
+
+/// Looks up the addresses of name servers.
+///
+/// Stops at the first name server that has addresses.
+async fn lookup_servers<R: Resolver>(
+    resolver: &R,
+    names: &[Dname<Vec<u8>>],
+) -> Vec<SocketAddr>
+where
+    for<'a> &'a R::Octets: OctetsRef,
+{
+    for name in names {
+        if let Ok(found) = lookup_host(resolver, name).await {
+            let addrs: Vec<_> =
+                found.iter().map(|addr| SocketAddr::new(addr, 53)).collect();
+            if !addrs.is_empty() {
+                return addrs;
+            }
+        }
+    }
+    Vec::new()
+}
+
+//------------ Trace ---------------------------------------------------------
+
+/// The delegation chain for a question.
+#[derive(Clone)]
+pub struct Trace {
+    /// The question name.
+    qname: Dname<Vec<u8>>,
+
+    /// The question type.
+    qtype: Rtype,
+
+    /// The steps from the root to the final response.
+    steps: Vec<TraceStep>,
+
+    /// The final response.
+    response: Message<Bytes>,
+}
+
+impl Trace {
+    /// Returns the question name.
+    pub fn qname(&self) -> &Dname<Vec<u8>> {
+        &self.qname
+    }
+
+    /// Returns the question type.
+    pub fn qtype(&self) -> Rtype {
+        self.qtype
+    }
+
+    /// Returns the steps from the root to the final response.
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    /// Returns the final response.
+    ///
+    /// This is the response of the first server that didn’t respond with a
+    /// referral.
+    pub fn response(&self) -> &Message<Bytes> {
+        &self.response
+    }
+
+    /// Returns the total time spent waiting for responses.
+    pub fn total_rtt(&self) -> Duration {
+        self.steps.iter().map(|step| step.rtt).sum()
+    }
+}
+
+//--- Display
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ TraceStep -----------------------------------------------------
+
+/// A single step of a trace.
+#[derive(Clone, Debug)]
+pub struct TraceStep {
+    /// The zone the servers were asked for.
+    zone: Dname<Vec<u8>>,
+
+    /// The addresses of the servers of the zone.
+    servers: Vec<SocketAddr>,
+
+    /// The server that responded.
+    server: SocketAddr,
+
+    /// The round-trip time of the query to the server.
+    rtt: Duration,
+
+    /// The response code.
+    rcode: Rcode,
+
+    /// The referral if the response was one.
+    referral: Option<Referral>,
+
+    /// The DNSSEC data in the response.
+    dnssec: DnssecStatus,
+}
+
+impl TraceStep {
+    /// Creates a step from a server’s response.
+    fn new(
+        zone: Dname<Vec<u8>>,
+        servers: Vec<SocketAddr>,
+        server: SocketAddr,
+        rtt: Duration,
+        qname: &Dname<Vec<u8>>,
+        response: &Message<Bytes>,
+    ) -> Result<Self, TraceError> {
+        let referral = Referral::from_response(response)?;
+        if let Some(ref referral) = referral {
+            // A referral has to bring us closer to the question name.
+            if referral.zone.name_eq(&zone)
+                || !referral.zone.ends_with(&zone)
+                || !qname.ends_with(&referral.zone)
+            {
+                return Err(TraceError::BadReferral {
+                    zone,
+                    target: referral.zone.clone(),
+                });
+            }
+        }
+        let dnssec =
+            DnssecStatus::from_response(response, referral.is_some())?;
+        Ok(TraceStep {
+            zone,
+            servers,
+            server,
+            rtt,
+            rcode: response.header().rcode(),
+            referral,
+            dnssec,
+        })
+    }
+
+    /// Returns the zone the servers were asked for.
+    pub fn zone(&self) -> &Dname<Vec<u8>> {
+        &self.zone
+    }
+
+    /// Returns the addresses of the servers of the zone.
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// Returns the address of the server that responded.
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    /// Returns the round-trip time of the query to the server.
+    pub fn rtt(&self) -> Duration {
+        self.rtt
+    }
+
+    /// Returns the response code.
+    pub fn rcode(&self) -> Rcode {
+        self.rcode
+    }
+
+    /// Returns the referral if the response was one.
+    pub fn referral(&self) -> Option<&Referral> {
+        self.referral.as_ref()
+    }
+
+    /// Returns the DNSSEC data in the response.
+    pub fn dnssec(&self) -> DnssecStatus {
+        self.dnssec
+    }
+}
+
+//--- Display
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}. from {} in {} ms: ",
+            self.zone,
+            self.server,
+            self.rtt.as_millis()
+        )?;
+        match self.referral {
+            Some(ref referral) => {
+                write!(f, "referral to {}.", referral.zone)?
+            }
+            None => write!(f, "{}", self.rcode)?,
+        }
+        write!(f, " ({})", self.dnssec)
+    }
+}
+
+//------------ Referral ------------------------------------------------------
+
+/// A referral to the servers of a child zone.
+#[derive(Clone, Debug)]
+pub struct Referral {
+    /// The apex of the child zone.
+    zone: Dname<Vec<u8>>,
+
+    /// The names of the child zone’s name servers.
+    ns: Vec<Dname<Vec<u8>>>,
+
+    /// The glue addresses of the name servers.
+    glue: Vec<(Dname<Vec<u8>>, IpAddr)>,
+}
+
+impl Referral {
+    /// Takes the referral from a response.
+    ///
+    /// A response is a referral if it is not authoritative, has no
+    /// answers, and has NS records in its authority section.
+    fn from_response(
+        response: &Message<Bytes>,
+    ) -> Result<Option<Self>, TraceError> {
+        let header = response.header();
+        if header.aa()
+            || header.rcode() != Rcode::NoError
+            || response.header_counts().ancount() != 0
+        {
+            return Ok(None);
+        }
+        let (_, _, authority, additional) = response.sections()?;
+        let mut zone: Option<Dname<Vec<u8>>> = None;
+        let mut ns = Vec::new();
+        for record in authority.limit_to::<Ns<ParsedDname<&Bytes>>>() {
+            let record = record?;
+            match zone {
+                Some(ref zone) if !record.owner().name_eq(zone) => continue,
+                Some(_) => {}
+                None => zone = Some(record.owner().to_vec()),
+            }
+            ns.push(record.data().nsdname().to_vec());
+        }
+        let zone = match zone {
+            Some(zone) => zone,
+            None => return Ok(None),
+        };
+        let mut glue = Vec::new();
+        for record in additional.limit_to::<A>() {
+            let record = record?;
+            if ns.iter().any(|name| record.owner().name_eq(name)) {
+                glue.push((
+                    record.owner().to_vec(),
+                    record.data().addr().into(),
+                ))
+            }
+        }
+        for record in additional.limit_to::<Aaaa>() {
+            let record = record?;
+            if ns.iter().any(|name| record.owner().name_eq(name)) {
+                glue.push((
+                    record.owner().to_vec(),
+                    record.data().addr().into(),
+                ))
+            }
+        }
+        Ok(Some(Referral { zone, ns, glue }))
+    }
+
+    /// Returns the apex of the child zone.
+    pub fn zone(&self) -> &Dname<Vec<u8>> {
+        &self.zone
+    }
+
+    /// Returns the names of the child zone’s name servers.
+    pub fn ns(&self) -> &[Dname<Vec<u8>>] {
+        &self.ns
+    }
+
+    /// Returns the glue addresses of the name servers.
+    pub fn glue(&self) -> &[(Dname<Vec<u8>>, IpAddr)] {
+        &self.glue
+    }
+}
+
+//------------ DnssecStatus --------------------------------------------------
+
+/// The DNSSEC data included in a response.
+///
+/// Note that this only tells which records are present. They are not
+/// validated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DnssecStatus {
+    /// The response is signed.
+    ///
+    /// For a referral, this means that it contains DS records for the
+    /// child zone. Otherwise, the response contains RRSIG records.
+    Signed,
+
+    /// The referral is to an unsigned zone.
+    ///
+    /// The referral contains no DS records but NSEC or NSEC3 records
+    /// proving their absence.
+    Insecure,
+
+    /// The response contains no DNSSEC data at all.
+    Unsigned,
+}
+
+impl DnssecStatus {
+    /// Determines the status of a response.
+    fn from_response(
+        response: &Message<Bytes>,
+        referral: bool,
+    ) -> Result<Self, TraceError> {
+        let (_, answer, authority, _) = response.sections()?;
+        let mut rrsig = false;
+        let mut ds = false;
+        let mut nsec = false;
+        for record in answer.chain(authority) {
+            match record?.rtype() {
+                Rtype::Rrsig => rrsig = true,
+                Rtype::Ds => ds = true,
+                Rtype::Nsec | Rtype::Nsec3 => nsec = true,
+                _ => {}
+            }
+        }
+        Ok(if referral {
+            if ds {
+                DnssecStatus::Signed
+            } else if nsec {
+                DnssecStatus::Insecure
+            } else {
+                DnssecStatus::Unsigned
+            }
+        } else if rrsig {
+            DnssecStatus::Signed
+        } else {
+            DnssecStatus::Unsigned
+        })
+    }
+}
+
+//--- Display
+
+impl fmt::Display for DnssecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DnssecStatus::Signed => "signed",
+            DnssecStatus::Insecure => "insecure",
+            DnssecStatus::Unsigned => "unsigned",
+        })
+    }
+}
+
+//------------ TraceError ----------------------------------------------------
+
+/// An error happened while tracing.
+#[derive(Clone, Debug)]
+pub enum TraceError {
+    /// None of the servers of the zone responded.
+    NoResponse(Dname<Vec<u8>>),
+
+    /// None of the name servers of the zone has an address.
+    NoAddresses(Dname<Vec<u8>>),
+
+    /// A server referred to a zone that isn’t closer to the question.
+    BadReferral {
+        /// The zone the server was asked for.
+        zone: Dname<Vec<u8>>,
+
+        /// The zone the server referred to.
+        target: Dname<Vec<u8>>,
+    },
+
+    /// A response couldn’t be parsed.
+    Parse,
+
+    /// There were more than `MAX_REFERRALS` referrals.
+    TooManyReferrals,
+}
+
+impl From<crate::base::octets::ParseError> for TraceError {
+    fn from(_: crate::base::octets::ParseError) -> Self {
+        TraceError::Parse
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TraceError::NoResponse(ref zone) => {
+                write!(f, "no server for {} responded", zone)
+            }
+            TraceError::NoAddresses(ref zone) => {
+                write!(f, "no addresses for the servers of {}", zone)
+            }
+            TraceError::BadReferral {
+                ref zone,
+                ref target,
+            } => write!(f, "server for {} referred to {}", zone, target),
+            TraceError::Parse => f.write_str("malformed response"),
+            TraceError::TooManyReferrals => f.write_str("too many referrals"),
+        }
+    }
+}
+
+impl error::Error for TraceError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::message_builder::AuthorityBuilder;
+    use crate::rdata::Ds;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        if s == "." {
+            Dname::root_vec()
+        } else {
+            Dname::from_str(s).unwrap()
+        }
+    }
+
+    fn response<F>(aa: bool, op: F) -> Message<Bytes>
+    where
+        F: FnOnce(&mut AuthorityBuilder<Vec<u8>>),
+    {
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((name("www.example.com"), Rtype::A)).unwrap();
+        let query = query.into_message();
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        answer.header_mut().set_aa(aa);
+        let mut authority = answer.authority();
+        op(&mut authority);
+        Message::from_octets(authority.finish().into()).unwrap()
+    }
+
+    fn referral(zone: &str, ds: bool) -> Message<Bytes> {
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((name("www.example.com"), Rtype::A)).unwrap();
+        let query = query.into_message();
+        let answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        let mut authority = answer.authority();
+        for ns in &["ns1", "ns2"] {
+            let ns = name(&format!("{}.{}", ns, zone));
+            authority
+                .push((name(zone), Class::In, 3600, Ns::new(ns)))
+                .unwrap();
+        }
+        if ds {
+            authority
+                .push((
+                    name(zone),
+                    Class::In,
+                    3600,
+                    Ds::new(
+                        1,
+                        crate::base::iana::SecAlg::EcdsaP256Sha256,
+                        crate::base::iana::DigestAlg::Sha256,
+                        vec![0; 32],
+                    ),
+                ))
+                .unwrap();
+        }
+        let mut additional = authority.additional();
+        additional
+            .push((
+                name(&format!("ns1.{}", zone)),
+                Class::In,
+                3600,
+                A::from_octets(192, 0, 2, 1),
+            ))
+            .unwrap();
+        additional
+            .push((
+                name("ns1.unrelated"),
+                Class::In,
+                3600,
+                A::from_octets(192, 0, 2, 2),
+            ))
+            .unwrap();
+        Message::from_octets(additional.finish().into()).unwrap()
+    }
+
+    fn step(
+        zone: &str,
+        response: &Message<Bytes>,
+    ) -> Result<TraceStep, TraceError> {
+        let server = "192.0.2.53:53".parse().unwrap();
+        TraceStep::new(
+            name(zone),
+            vec![server],
+            server,
+            Duration::from_millis(12),
+            &name("www.example.com"),
+            response,
+        )
+    }
+
+    #[test]
+    fn referral_steps() {
+        let step = step(".", &referral("com", true)).unwrap();
+        let found = step.referral().unwrap();
+        assert_eq!(found.zone(), &name("com"));
+        assert_eq!(found.ns(), [name("ns1.com"), name("ns2.com")]);
+        assert_eq!(
+            found.glue(),
+            [(name("ns1.com"), IpAddr::from([192, 0, 2, 1]))]
+        );
+        assert_eq!(step.dnssec(), DnssecStatus::Signed);
+        assert_eq!(
+            step.to_string(),
+            ". from 192.0.2.53:53 in 12 ms: referral to com. (signed)"
+        );
+
+        let step =
+            self::step("com", &referral("example.com", false)).unwrap();
+        assert_eq!(step.dnssec(), DnssecStatus::Unsigned);
+
+        // Referrals must lead closer to the question name.
+        assert!(matches!(
+            self::step("com", &referral("com", false)),
+            Err(TraceError::BadReferral { .. })
+        ));
+        assert!(matches!(
+            self::step("example.com", &referral("com", false)),
+            Err(TraceError::BadReferral { .. })
+        ));
+        assert!(matches!(
+            self::step(".", &referral("example.org", false)),
+            Err(TraceError::BadReferral { .. })
+        ));
+    }
+
+    #[test]
+    fn final_step() {
+        let response = response(true, |authority| {
+            authority
+                .push((
+                    name("example.com"),
+                    Class::In,
+                    3600,
+                    Ns::new(name("ns1.example.com")),
+                ))
+                .unwrap()
+        });
+        let step = step("example.com", &response).unwrap();
+        assert!(step.referral().is_none());
+        assert_eq!(step.rcode(), Rcode::NoError);
+        assert_eq!(step.dnssec(), DnssecStatus::Unsigned);
+        assert_eq!(
+            step.to_string(),
+            "example.com. from 192.0.2.53:53 in 12 ms: NOERROR (unsigned)"
+        );
+    }
+}
@@ -0,0 +1,700 @@
+//! Access control lists.
+//!
+//! An access control list, or ACL for short, decides whether a client is
+//! allowed to perform a certain operation such as a zone transfer, a
+//! dynamic update, or sending a NOTIFY. A server typically keeps one
+//! [`Acl`] per operation and evaluates it for every request.
+//!
+//! An ACL is an ordered list of entries. Each entry matches clients by
+//! their address, the name of the TSIG key used to sign the request, and
+//! the transport protocol, and either allows or denies them. As in BIND’s
+//! address match lists, the first matching entry decides. If no entry
+//! matches, the request is denied.
+//!
+//! ACLs can be constructed entry by entry or parsed from a string in a
+//! syntax resembling that of BIND’s `allow-transfer` and similar options.
+//! The string consists of elements separated by semicolons. Each element
+//! is either `any`, `none`, an address optionally followed by a slash and
+//! a prefix length, or the word `key` followed by the name of a TSIG key.
+//! An element can be prefixed with an exclamation mark to deny rather than
+//! allow matching clients:
+//!
+//! ```
+//! use std::net::IpAddr;
+//! use domain::base::Dname;
+//! use domain::server::acl::{Acl, AclRequest, Transport};
+//!
+//! let acl: Acl = "!192.0.2.13; 192.0.2.0/24; key xfr.example.com"
+//!     .parse().unwrap();
+//!
+//! let addr: IpAddr = "192.0.2.13".parse().unwrap();
+//! assert!(!acl.allows(&AclRequest::<Dname<Vec<u8>>>::new(
+//!     addr, Transport::Tcp
+//! )));
+//! ```
+//!
+//! [`Acl`]: struct.Acl.html
+
+use crate::base::name::{Dname, ToDname};
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+use std::vec::Vec;
+use std::{error, fmt, slice};
+
+//------------ Acl -----------------------------------------------------------
+
+/// An access control list.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Clone, Debug, Default)]
+pub struct Acl {
+    /// The entries in order of evaluation.
+    entries: Vec<AclEntry>,
+}
+
+impl Acl {
+    /// Creates a new, empty ACL that denies all requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new ACL that allows all requests.
+    pub fn allow_any() -> Self {
+        Acl {
+            entries: vec![AclEntry::allow()],
+        }
+    }
+
+    /// Appends an entry to the end of the list.
+    pub fn push(&mut self, entry: AclEntry) {
+        self.entries.push(entry)
+    }
+
+    /// Returns whether the list has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries of the list.
+    pub fn iter(&self) -> slice::Iter<'_, AclEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the action for a request.
+    ///
+    /// This is the action of the first entry matching the request or
+    /// `Action::Deny` if no entry matches.
+    pub fn check<N: ToDname>(&self, request: &AclRequest<N>) -> Action {
+        self.entries
+            .iter()
+            .find(|entry| entry.matches(request))
+            .map(AclEntry::action)
+            .unwrap_or(Action::Deny)
+    }
+
+    /// Returns whether a request is allowed.
+    pub fn allows<N: ToDname>(&self, request: &AclRequest<N>) -> bool {
+        self.check(request) == Action::Allow
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for Acl {
+    type Err = ParseAclError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = Acl::new();
+        for element in s.split(';') {
+            let element = element.trim();
+            if element.is_empty() {
+                continue;
+            }
+            res.push(AclEntry::from_str(element)?);
+        }
+        Ok(res)
+    }
+}
+
+impl fmt::Display for Acl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut entries = self.entries.iter();
+        if let Some(entry) = entries.next() {
+            entry.fmt(f)?;
+        }
+        for entry in entries {
+            write!(f, "; {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+//--- IntoIterator
+
+impl<'a> IntoIterator for &'a Acl {
+    type Item = &'a AclEntry;
+    type IntoIter = slice::Iter<'a, AclEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+//------------ AclEntry ------------------------------------------------------
+
+/// An entry of an access control list.
+///
+/// An entry matches a request if all of its conditions are met. An entry
+/// without any conditions matches all requests. Conditions are added via
+/// the `with_*` methods.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AclEntry {
+    /// The action taken for matching requests.
+    action: Action,
+
+    /// The prefix the client address must be in.
+    prefix: Option<AddrPrefix>,
+
+    /// The name of the TSIG key the request must be signed with.
+    key: Option<Dname<Vec<u8>>>,
+
+    /// The transport the request must have been received over.
+    transport: Option<Transport>,
+}
+
+impl AclEntry {
+    /// Creates a new entry taking the given action for all requests.
+    pub fn new(action: Action) -> Self {
+        AclEntry {
+            action,
+            prefix: None,
+            key: None,
+            transport: None,
+        }
+    }
+
+    /// Creates a new entry allowing all requests.
+    pub fn allow() -> Self {
+        Self::new(Action::Allow)
+    }
+
+    /// Creates a new entry denying all requests.
+    pub fn deny() -> Self {
+        Self::new(Action::Deny)
+    }
+
+    /// Limits the entry to clients within the given prefix.
+    pub fn with_prefix(mut self, prefix: AddrPrefix) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Limits the entry to requests signed with the given TSIG key.
+    pub fn with_key<N: ToDname>(mut self, key: &N) -> Self {
+        self.key = Some(key.to_vec());
+        self
+    }
+
+    /// Limits the entry to requests received over the given transport.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Returns the action of the entry.
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Returns the address prefix of the entry if there is one.
+    pub fn prefix(&self) -> Option<AddrPrefix> {
+        self.prefix
+    }
+
+    /// Returns the TSIG key name of the entry if there is one.
+    pub fn key(&self) -> Option<&Dname<Vec<u8>>> {
+        self.key.as_ref()
+    }
+
+    /// Returns the transport of the entry if there is one.
+    pub fn transport(&self) -> Option<Transport> {
+        self.transport
+    }
+
+    /// Returns whether the entry matches a request.
+    pub fn matches<N: ToDname>(&self, request: &AclRequest<N>) -> bool {
+        if let Some(prefix) = self.prefix {
+            if !prefix.contains(request.addr) {
+                return false;
+            }
+        }
+        if let Some(ref key) = self.key {
+            match request.key {
+                Some(ref request_key) if key.name_eq(request_key) => {}
+                _ => return false,
+            }
+        }
+        if let Some(transport) = self.transport {
+            if transport != request.transport {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for AclEntry {
+    type Err = ParseAclError;
+
+    /// Parses a single element of an ACL.
+    ///
+    /// See the [module documentation] for the syntax.
+    ///
+    /// [module documentation]: index.html
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (negated, s) = if let Some(s) = s.strip_prefix('!') {
+            (true, s.trim_start())
+        } else {
+            (false, s)
+        };
+        let entry = match s {
+            "any" => Self::allow(),
+            "none" => Self::deny(),
+            _ => {
+                let mut words = s.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some("key"), Some(name), None) => Self::allow()
+                        .with_key(
+                            &Dname::<Vec<u8>>::from_str(name)
+                                .map_err(|_| ParseAclError::BadKeyName)?,
+                        ),
+                    (Some(prefix), None, None) => Self::allow()
+                        .with_prefix(AddrPrefix::from_str(prefix)?),
+                    _ => return Err(ParseAclError::BadElement),
+                }
+            }
+        };
+        if negated {
+            Ok(AclEntry {
+                action: entry.action.negate(),
+                ..entry
+            })
+        } else {
+            Ok(entry)
+        }
+    }
+}
+
+impl fmt::Display for AclEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.prefix.is_none()
+            && self.key.is_none()
+            && self.transport.is_none()
+        {
+            return f.write_str(match self.action {
+                Action::Allow => "any",
+                Action::Deny => "none",
+            });
+        }
+        if self.action == Action::Deny {
+            f.write_str("!")?;
+        }
+        let mut sep = "";
+        if let Some(addr) = self.prefix {
+            write!(f, "{}", addr)?;
+            sep = " ";
+        }
+        if let Some(ref key) = self.key {
+            write!(f, "{}key {}", sep, key)?;
+            sep = " ";
+        }
+        if let Some(transport) = self.transport {
+            write!(f, "{}transport {}", sep, transport)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Action --------------------------------------------------------
+
+/// The action an ACL entry takes for matching requests.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    /// The request is allowed.
+    Allow,
+
+    /// The request is denied.
+    Deny,
+}
+
+impl Action {
+    /// Returns the opposite action.
+    fn negate(self) -> Self {
+        match self {
+            Action::Allow => Action::Deny,
+            Action::Deny => Action::Allow,
+        }
+    }
+}
+
+//------------ AclRequest ----------------------------------------------------
+
+/// The information about a request an ACL is evaluated against.
+#[derive(Clone, Debug)]
+pub struct AclRequest<N> {
+    /// The address of the client.
+    addr: IpAddr,
+
+    /// The transport the request was received over.
+    transport: Transport,
+
+    /// The name of the TSIG key the request was signed with, if any.
+    key: Option<N>,
+}
+
+impl<N> AclRequest<N> {
+    /// Creates a new unsigned request.
+    pub fn new(addr: IpAddr, transport: Transport) -> Self {
+        AclRequest {
+            addr,
+            transport,
+            key: None,
+        }
+    }
+
+    /// Adds the name of the TSIG key the request was signed with.
+    ///
+    /// The key name should only be given once the TSIG signature has been
+    /// successfully verified.
+    pub fn with_key(mut self, key: N) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Returns the address of the client.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the transport the request was received over.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns the name of the TSIG key if the request was signed.
+    pub fn key(&self) -> Option<&N> {
+        self.key.as_ref()
+    }
+}
+
+//------------ Transport -----------------------------------------------------
+
+/// The transport protocol a request was received over.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Transport {
+    /// Unencrypted UDP.
+    Udp,
+
+    /// Unencrypted TCP.
+    Tcp,
+
+    /// DNS over TLS.
+    Tls,
+
+    /// DNS over HTTPS.
+    Https,
+
+    /// DNS over QUIC.
+    Quic,
+}
+
+impl Transport {
+    /// Returns whether the transport is encrypted.
+    pub fn is_encrypted(self) -> bool {
+        match self {
+            Transport::Udp | Transport::Tcp => false,
+            Transport::Tls | Transport::Https | Transport::Quic => true,
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Transport::Udp => "udp",
+            Transport::Tcp => "tcp",
+            Transport::Tls => "tls",
+            Transport::Https => "https",
+            Transport::Quic => "quic",
+        })
+    }
+}
+
+//------------ AddrPrefix ----------------------------------------------------
+
+/// An IP address prefix.
+///
+/// The prefix consists of a network address and the number of leading
+/// bits of it that addresses need to share in order to be within the
+/// prefix.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AddrPrefix {
+    /// The network address.
+    addr: IpAddr,
+
+    /// The length of the prefix in bits.
+    len: u8,
+}
+
+impl AddrPrefix {
+    /// Creates a new prefix from an address and a prefix length.
+    ///
+    /// Returns `None` if the prefix length is too long for the address.
+    pub fn new(addr: IpAddr, len: u8) -> Option<Self> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if len > max {
+            None
+        } else {
+            Some(AddrPrefix { addr, len })
+        }
+    }
+
+    /// Creates a prefix containing only the given address.
+    pub fn host(addr: IpAddr) -> Self {
+        AddrPrefix {
+            addr,
+            len: if addr.is_ipv4() { 32 } else { 128 },
+        }
+    }
+
+    /// Returns the network address of the prefix.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the length of the prefix in bits.
+    pub fn len(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns whether the prefix has a length of zero.
+    ///
+    /// A prefix of length zero contains all addresses of its family.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether `addr` is within the prefix.
+    ///
+    /// IPv4-mapped IPv6 addresses are treated as IPv4 addresses.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => {
+                let octets = v6.octets();
+                if octets[..10] == [0; 10] && octets[10..12] == [0xff; 2] {
+                    IpAddr::V4(Ipv4Addr::new(
+                        octets[12], octets[13], octets[14], octets[15],
+                    ))
+                } else {
+                    addr
+                }
+            }
+            _ => addr,
+        };
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.len))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.len))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+//--- From
+
+impl From<IpAddr> for AddrPrefix {
+    fn from(addr: IpAddr) -> Self {
+        Self::host(addr)
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for AddrPrefix {
+    type Err = ParseAclError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let addr = IpAddr::from_str(parts.next().unwrap())
+            .map_err(|_| ParseAclError::BadPrefix)?;
+        match parts.next() {
+            Some(len) => Self::new(
+                addr,
+                u8::from_str(len).map_err(|_| ParseAclError::BadPrefix)?,
+            )
+            .ok_or(ParseAclError::BadPrefix),
+            None => Ok(Self::host(addr)),
+        }
+    }
+}
+
+impl fmt::Display for AddrPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+//------------ ParseAclError -------------------------------------------------
+
+/// An error happened while parsing an ACL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseAclError {
+    /// An address prefix was invalid.
+    BadPrefix,
+
+    /// The name of a TSIG key was invalid.
+    BadKeyName,
+
+    /// An element was not recognized.
+    BadElement,
+}
+
+impl fmt::Display for ParseAclError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseAclError::BadPrefix => "invalid address prefix",
+            ParseAclError::BadKeyName => "invalid key name",
+            ParseAclError::BadElement => "invalid ACL element",
+        })
+    }
+}
+
+impl error::Error for ParseAclError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    type Request = AclRequest<Dname<Vec<u8>>>;
+
+    fn request(
+        addr: &str,
+        transport: Transport,
+        key: Option<&str>,
+    ) -> Request {
+        let res = AclRequest::new(IpAddr::from_str(addr).unwrap(), transport);
+        match key {
+            Some(key) => res.with_key(Dname::from_str(key).unwrap()),
+            None => res,
+        }
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let acl = Acl::from_str(
+            "!192.0.2.13; 192.0.2.0/24; 2001:db8::/32; key XFR.example.com",
+        )
+        .unwrap();
+        assert!(!acl.allows(&request("192.0.2.13", Transport::Udp, None)));
+        assert!(acl.allows(&request("192.0.2.14", Transport::Udp, None)));
+        assert!(acl.allows(&request(
+            "::ffff:192.0.2.14",
+            Transport::Tcp,
+            None
+        )));
+        assert!(acl.allows(&request("2001:db8::1", Transport::Tcp, None)));
+        assert!(!acl.allows(&request("198.51.100.1", Transport::Tcp, None)));
+        assert!(acl.allows(&request(
+            "198.51.100.1",
+            Transport::Tcp,
+            Some("xfr.example.com")
+        )));
+        assert!(!acl.allows(&request(
+            "192.0.2.13",
+            Transport::Tcp,
+            Some("xfr.example.com")
+        )));
+        assert!(!Acl::new().allows(&request(
+            "192.0.2.1",
+            Transport::Udp,
+            None
+        )));
+        assert!(Acl::allow_any().allows(&request(
+            "192.0.2.1",
+            Transport::Udp,
+            None
+        )));
+    }
+
+    #[test]
+    fn combined_conditions() {
+        let mut acl = Acl::new();
+        acl.push(
+            AclEntry::allow()
+                .with_prefix(AddrPrefix::from_str("192.0.2.0/24").unwrap())
+                .with_key(&Dname::<Vec<u8>>::from_str("xfr.example").unwrap())
+                .with_transport(Transport::Tcp),
+        );
+        assert!(acl.allows(&request(
+            "192.0.2.1",
+            Transport::Tcp,
+            Some("xfr.example")
+        )));
+        assert!(!acl.allows(&request(
+            "192.0.2.1",
+            Transport::Udp,
+            Some("xfr.example")
+        )));
+        assert!(!acl.allows(&request("192.0.2.1", Transport::Tcp, None)));
+        assert!(!acl.allows(&request(
+            "10.0.0.1",
+            Transport::Tcp,
+            Some("xfr.example")
+        )));
+        assert_eq!(
+            acl.to_string(),
+            "192.0.2.0/24 key xfr.example transport tcp"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Acl::from_str("192.0.2.0/33").unwrap_err(),
+            ParseAclError::BadPrefix
+        );
+        assert_eq!(
+            Acl::from_str("key a..b").unwrap_err(),
+            ParseAclError::BadKeyName
+        );
+        assert_eq!(
+            Acl::from_str("key").unwrap_err(),
+            ParseAclError::BadPrefix
+        );
+        assert_eq!(
+            Acl::from_str("foo bar").unwrap_err(),
+            ParseAclError::BadElement
+        );
+        assert_eq!(
+            Acl::from_str("!any; none; 192.0.2.1").unwrap().to_string(),
+            "none; none; 192.0.2.1/32"
+        );
+    }
+}
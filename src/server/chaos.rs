@@ -0,0 +1,285 @@
+//! Answering queries for server information in the CHAOS class.
+//!
+//! Many servers answer TXT queries in the CHAOS class for a few well-known
+//! names with information about themselves:
+//!
+//! * `version.bind` and `version.server` for the server software version,
+//! * `id.server` for the identity of a server instance as defined in
+//!   RFC 4892, and
+//! * `hostname.bind` for the host name of the server.
+//!
+//! The [`Chaos`] type in this module implements these queries. For each of
+//! the three pieces of information, it can be configured what the answer
+//! should be or whether the query is to be refused, which is the default
+//! since operators often prefer not to reveal the information.
+//!
+//! [`Chaos`] implements [`Service`] for queries in the CHAOS class and
+//! declines to answer all other queries. It is intended to be used before
+//! a server’s main service:
+//!
+//! ```
+//! use domain::base::message_builder::MessageBuilder;
+//! use domain::server::chaos::{Chaos, Reply};
+//! use domain::server::service::{PooledBuf, Request, Service};
+//!
+//! let mut chaos = Chaos::new();
+//! chaos.set_version(Reply::text("domain"));
+//!
+//! fn main_service(
+//!     request: &Request, builder: MessageBuilder<PooledBuf>
+//! ) -> Option<PooledBuf> {
+//!     # let _ = (request, builder);
+//!     // Answer everything else.
+//!     None
+//! }
+//!
+//! let service = |request: &Request, builder| {
+//!     if chaos.is_chaos(request.message()) {
+//!         chaos.call(request, builder)
+//!     } else {
+//!         main_service(request, builder)
+//!     }
+//! };
+//! # let _ = service;
+//! ```
+//!
+//! [`Chaos`]: struct.Chaos.html
+//! [`Service`]: ../service/trait.Service.html
+
+use super::service::{PooledBuf, Request, Service};
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ToDname};
+use crate::rdata::Txt;
+use std::string::String;
+use std::vec::Vec;
+
+//------------ Chaos ---------------------------------------------------------
+
+/// A service answering queries for server information.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Clone, Debug, Default)]
+pub struct Chaos {
+    /// The reply for `version.bind` and `version.server`.
+    version: Reply,
+
+    /// The reply for `id.server`.
+    id: Reply,
+
+    /// The reply for `hostname.bind`.
+    hostname: Reply,
+}
+
+impl Chaos {
+    /// Creates a new value refusing all queries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the reply for `version.bind` and `version.server`.
+    pub fn version(&self) -> &Reply {
+        &self.version
+    }
+
+    /// Sets the reply for `version.bind` and `version.server`.
+    pub fn set_version(&mut self, reply: Reply) {
+        self.version = reply
+    }
+
+    /// Returns the reply for `id.server`.
+    pub fn id(&self) -> &Reply {
+        &self.id
+    }
+
+    /// Sets the reply for `id.server`.
+    pub fn set_id(&mut self, reply: Reply) {
+        self.id = reply
+    }
+
+    /// Returns the reply for `hostname.bind`.
+    pub fn hostname(&self) -> &Reply {
+        &self.hostname
+    }
+
+    /// Sets the reply for `hostname.bind`.
+    pub fn set_hostname(&mut self, reply: Reply) {
+        self.hostname = reply
+    }
+
+    /// Returns whether a request is a query in the CHAOS class.
+    ///
+    /// Only requests for which this method returns `true` are answered by
+    /// the service.
+    pub fn is_chaos<Octets: AsRef<[u8]>>(
+        &self,
+        request: &Message<Octets>,
+    ) -> bool {
+        request
+            .for_slice()
+            .first_question()
+            .map(|question| question.qclass() == Class::Ch)
+            .unwrap_or(false)
+    }
+
+    /// Returns the reply for a query name.
+    ///
+    /// Returns `None` if the name is not one of the well-known names.
+    pub fn reply<N: ToDname>(&self, qname: &N) -> Option<&Reply> {
+        let name = |wire| Dname::from_slice(wire).unwrap();
+        if qname.name_eq(name(b"\x07version\x04bind\x00"))
+            || qname.name_eq(name(b"\x07version\x06server\x00"))
+        {
+            Some(&self.version)
+        } else if qname.name_eq(name(b"\x02id\x06server\x00")) {
+            Some(&self.id)
+        } else if qname.name_eq(name(b"\x08hostname\x04bind\x00")) {
+            Some(&self.hostname)
+        } else {
+            None
+        }
+    }
+}
+
+impl Service for Chaos {
+    /// Answers a query in the CHAOS class.
+    ///
+    /// Queries for TXT or ANY records of a well-known name are answered
+    /// according to the configured reply. Queries for other types of a
+    /// well-known name are answered with an empty NOERROR response and
+    /// queries for all other names are refused. Requests that are not a
+    /// query in the CHAOS class are not answered at all.
+    fn call(
+        &self,
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf> {
+        let msg = request.message();
+        if !self.is_chaos(msg) || msg.header().qr() {
+            return None;
+        }
+        let question = msg.first_question()?;
+        let text = match self.reply(&question.qname()) {
+            Some(Reply::Text(text)) => text,
+            Some(Reply::Refuse) | None => {
+                return builder
+                    .start_answer_verbatim(msg, Rcode::Refused)
+                    .ok()
+                    .map(|answer| answer.finish())
+            }
+        };
+        let mut answer =
+            builder.start_answer_verbatim(msg, Rcode::NoError).ok()?;
+        if question.qtype() == Rtype::Txt || question.qtype() == Rtype::Any {
+            let txt = Txt::<Vec<u8>>::from_slice(text.as_bytes()).ok()?;
+            answer.push((question.qname(), Class::Ch, 0, txt)).ok()?;
+        }
+        Some(answer.finish())
+    }
+}
+
+//------------ Reply ---------------------------------------------------------
+
+/// The reply for one of the well-known names.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Reply {
+    /// Answer with a TXT record containing the text.
+    Text(String),
+
+    /// Refuse the query.
+    Refuse,
+}
+
+impl Reply {
+    /// Creates a reply answering with the given text.
+    pub fn text(text: impl Into<String>) -> Self {
+        Reply::Text(text.into())
+    }
+}
+
+#[allow(clippy::derivable_impls)] // #[default] needs Rust 1.62.
+impl Default for Reply {
+    fn default() -> Self {
+        Reply::Refuse
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use crate::base::octets::Octets512;
+    use crate::server::acl::Transport;
+    use crate::server::service::BufferPools;
+    use core::str::FromStr;
+
+    fn query(name: &str, qtype: Rtype, qclass: Class) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((Dname::<Vec<u8>>::from_str(name).unwrap(), qtype, qclass))
+            .unwrap();
+        msg.finish()
+    }
+
+    fn ask(
+        chaos: &Chaos,
+        name: &str,
+        qtype: Rtype,
+        qclass: Class,
+    ) -> Option<(Rcode, Vec<Vec<u8>>)> {
+        let pools = BufferPools::default();
+        let octets = query(name, qtype, qclass);
+        let client = "192.0.2.1:53".parse().unwrap();
+        let request = Request::new(&octets, client, Transport::Udp).unwrap();
+        let response = chaos.respond(&request, &pools)?;
+        let response = Message::from_octets(response.as_ref()).unwrap();
+        let texts = response
+            .answer()
+            .unwrap()
+            .limit_to::<Txt<_>>()
+            .map(|record| {
+                let record = record.unwrap();
+                assert_eq!(record.class(), Class::Ch);
+                record.data().text::<Octets512>().unwrap().as_ref().to_vec()
+            })
+            .collect();
+        Some((response.header().rcode(), texts))
+    }
+
+    #[test]
+    fn answers() {
+        let mut chaos = Chaos::new();
+        chaos.set_version(Reply::text("domain 0.6"));
+        chaos.set_id(Reply::text("ns1.example"));
+
+        assert_eq!(
+            ask(&chaos, "VERSION.bind", Rtype::Txt, Class::Ch),
+            Some((Rcode::NoError, vec![b"domain 0.6".to_vec()]))
+        );
+        assert_eq!(
+            ask(&chaos, "version.server", Rtype::Any, Class::Ch),
+            Some((Rcode::NoError, vec![b"domain 0.6".to_vec()]))
+        );
+        assert_eq!(
+            ask(&chaos, "id.server", Rtype::Txt, Class::Ch),
+            Some((Rcode::NoError, vec![b"ns1.example".to_vec()]))
+        );
+        assert_eq!(
+            ask(&chaos, "id.server", Rtype::A, Class::Ch),
+            Some((Rcode::NoError, vec![]))
+        );
+        assert_eq!(
+            ask(&chaos, "hostname.bind", Rtype::Txt, Class::Ch),
+            Some((Rcode::Refused, vec![]))
+        );
+        assert_eq!(
+            ask(&chaos, "authors.bind", Rtype::Txt, Class::Ch),
+            Some((Rcode::Refused, vec![]))
+        );
+        assert_eq!(ask(&chaos, "version.bind", Rtype::Txt, Class::In), None);
+    }
+}
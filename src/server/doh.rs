@@ -0,0 +1,681 @@
+//! Serving DNS over HTTPS.
+//!
+//! This module provides [`DohEndpoint`], the server side of DNS over
+//! HTTPS as defined in RFC 8484. The endpoint answers requests for a
+//! single path – by default the well-known path [`DEFAULT_PATH`] – with
+//! the help of a [`Service`]. Both the GET method with the request encoded
+//! in the `dns` query parameter and the POST method with the request as
+//! the body are supported.
+//!
+//! Successful responses carry a `Cache-Control` header with a `max-age`
+//! derived from the records of the response as required by section 5.1 of
+//! RFC 8484: it is the smallest TTL of the records in the answer section
+//! or, for negative responses, the TTL and minimum of the SOA record in
+//! the authority section. The function [`cache_max_age`] performs this
+//! calculation.
+//!
+//! The exchange itself is independent of any particular HTTP
+//! implementation: [`DohEndpoint::handle`] takes the relevant parts of an
+//! HTTP request as an [`HttpRequest`] and returns an [`HttpResponse`].
+//! This allows using the endpoint with any HTTP server. With the `hyper`
+//! feature, [`DohEndpoint::serve`] serves a single connection via the
+//! [hyper] crate, speaking HTTP/2 as recommended by RFC 8484 as well as
+//! HTTP/1.1. If this connection is wrapped in TLS by the application, this
+//! provides a complete DNS over HTTPS server.
+//!
+//! [`DohEndpoint`]: struct.DohEndpoint.html
+//! [`DohEndpoint::handle`]: struct.DohEndpoint.html#method.handle
+//! [`DohEndpoint::serve`]: struct.DohEndpoint.html#method.serve
+//! [`DEFAULT_PATH`]: constant.DEFAULT_PATH.html
+//! [`Service`]: ../service/trait.Service.html
+//! [`cache_max_age`]: fn.cache_max_age.html
+//! [`HttpRequest`]: struct.HttpRequest.html
+//! [`HttpResponse`]: struct.HttpResponse.html
+//! [hyper]: https://docs.rs/hyper/
+
+use super::acl::Transport;
+use super::service::{BufferPools, PooledBuf, Request, Service};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::ParsedDname;
+use crate::base::opt::rfc7830::PaddingPolicy;
+use crate::rdata::Soa;
+use crate::utils::base64;
+use bytes::Bytes;
+use core::cmp;
+use std::net::SocketAddr;
+use std::string::String;
+use std::vec::Vec;
+#[cfg(feature = "hyper")]
+use {
+    hyper::body::HttpBody,
+    hyper::server::conn::Http,
+    std::convert::Infallible,
+    std::sync::Arc,
+    tokio::io::{AsyncRead, AsyncWrite},
+};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The well-known path of a DNS over HTTPS endpoint.
+pub const DEFAULT_PATH: &str = "/dns-query";
+
+/// The content type of DNS messages in DNS over HTTPS.
+pub const DOH_CONTENT_TYPE: &str = "application/dns-message";
+
+/// The maximum size of the body of a POST request.
+///
+/// This is the maximum size of a DNS message.
+const MAX_BODY_LEN: usize = 0xFFFF;
+
+//------------ DohEndpoint ---------------------------------------------------
+
+/// A DNS over HTTPS endpoint.
+#[derive(Clone, Debug)]
+pub struct DohEndpoint<S> {
+    /// The service producing the responses.
+    service: S,
+
+    /// The pools for the response buffers.
+    pools: BufferPools,
+
+    /// The path the endpoint answers requests for.
+    path: String,
+
+    /// The padding policy for responses.
+    padding: PaddingPolicy,
+}
+
+impl<S> DohEndpoint<S> {
+    /// Creates a new endpoint using `service` at the default path.
+    pub fn new(service: S) -> Self {
+        Self::with_pools(service, BufferPools::default())
+    }
+
+    /// Creates a new endpoint using the given buffer pools.
+    pub fn with_pools(service: S, pools: BufferPools) -> Self {
+        DohEndpoint {
+            service,
+            pools,
+            path: DEFAULT_PATH.into(),
+            padding: PaddingPolicy::RESPONSE,
+        }
+    }
+
+    /// Returns a reference to the service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// Returns the path the endpoint answers requests for.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Sets the path the endpoint answers requests for.
+    ///
+    /// The path must start with a slash. It is compared to the path
+    /// component of the request target exactly.
+    pub fn set_path(&mut self, path: impl Into<String>) {
+        self.path = path.into();
+    }
+
+    /// Returns the padding policy for responses.
+    pub fn padding(&self) -> PaddingPolicy {
+        self.padding
+    }
+
+    /// Sets the padding policy for responses.
+    ///
+    /// Responses are only padded if the request was padded, too. The
+    /// default is the block-length padding recommended by RFC 8467.
+    pub fn set_padding(&mut self, padding: PaddingPolicy) {
+        self.padding = padding
+    }
+}
+
+impl<S: Service> DohEndpoint<S> {
+    /// Answers an HTTP request.
+    ///
+    /// Requests for a path other than the endpoint’s path are answered
+    /// with status 404, methods other than GET and POST with 405, POST
+    /// requests with a content type other than `application/dns-message`
+    /// with 415, and requests that don’t contain a DNS message with 400.
+    /// If the service decides not to answer, status 500 is returned.
+    pub fn handle(&self, request: &HttpRequest) -> HttpResponse {
+        let (path, query) = match request.target.find('?') {
+            Some(pos) => (&request.target[..pos], &request.target[pos + 1..]),
+            None => (request.target, ""),
+        };
+        if path != self.path {
+            return HttpResponse::status(404);
+        }
+        let decoded: Bytes;
+        let octets = match request.method {
+            "GET" | "HEAD" => match doh_get_request(query) {
+                Some(octets) => {
+                    decoded = octets;
+                    decoded.as_ref()
+                }
+                None => return HttpResponse::status(400),
+            },
+            "POST" => {
+                match request.content_type {
+                    Some(content_type) if is_dns_message(content_type) => {}
+                    _ => return HttpResponse::status(415),
+                }
+                if request.body.len() > MAX_BODY_LEN {
+                    return HttpResponse::status(413);
+                }
+                request.body
+            }
+            _ => return HttpResponse::status(405),
+        };
+        let dns_request =
+            match Request::new(octets, request.client, Transport::Https) {
+                Ok(dns_request) => dns_request,
+                Err(_) => return HttpResponse::status(400),
+            };
+        let body = match self.service.respond(&dns_request, &self.pools) {
+            Some(mut body) => {
+                dns_request.pad_response(&mut body, self.padding);
+                body
+            }
+            None => return HttpResponse::status(500),
+        };
+        let max_age = Message::from_octets(body.as_ref())
+            .ok()
+            .and_then(|msg| cache_max_age(&msg));
+        HttpResponse {
+            status: 200,
+            max_age,
+            body: Some(body),
+        }
+    }
+
+    /// Serves DNS over HTTPS on a single connection.
+    ///
+    /// The connection is served by [hyper] which speaks both HTTP/2 and
+    /// HTTP/1.1, picking the version from the first octets sent by the
+    /// client. Requests are answered via [`handle`] until the client
+    /// closes the connection. Request bodies longer than the maximum size
+    /// of a DNS message are rejected with status 413.
+    ///
+    /// The `client` is the address of the remote end of the connection.
+    /// Returns an error if the connection fails. The method requires the
+    /// `hyper` feature and a Tokio runtime for the HTTP/2 streams.
+    ///
+    /// [hyper]: https://docs.rs/hyper/
+    /// [`handle`]: #method.handle
+    #[cfg(feature = "hyper")]
+    pub async fn serve<Sock>(
+        self: Arc<Self>,
+        sock: Sock,
+        client: SocketAddr,
+    ) -> Result<(), hyper::Error>
+    where
+        S: Send + Sync + 'static,
+        Sock: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let service = hyper::service::service_fn(move |request| {
+            let endpoint = self.clone();
+            async move {
+                Ok::<_, Infallible>(
+                    endpoint.handle_hyper(request, client).await,
+                )
+            }
+        });
+        Http::new().serve_connection(sock, service).await
+    }
+
+    /// Answers a request received via hyper.
+    #[cfg(feature = "hyper")]
+    async fn handle_hyper(
+        &self,
+        request: hyper::Request<hyper::Body>,
+        client: SocketAddr,
+    ) -> hyper::Response<hyper::Body> {
+        let (head, mut body) = request.into_parts();
+        let mut octets = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => return hyper_response(HttpResponse::status(400)),
+            };
+            if octets.len() + chunk.len() > MAX_BODY_LEN {
+                return hyper_response(HttpResponse::status(413));
+            }
+            octets.extend_from_slice(&chunk);
+        }
+        let target = match head.uri.path_and_query() {
+            Some(target) => target.as_str(),
+            None => head.uri.path(),
+        };
+        let content_type = head
+            .headers
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        hyper_response(self.handle(&HttpRequest {
+            method: head.method.as_str(),
+            target,
+            content_type,
+            body: &octets,
+            client,
+        }))
+    }
+}
+
+//------------ HttpRequest ---------------------------------------------------
+
+/// The parts of an HTTP request relevant to DNS over HTTPS.
+#[derive(Clone, Copy, Debug)]
+pub struct HttpRequest<'a> {
+    /// The request method, such as `"GET"` or `"POST"`.
+    pub method: &'a str,
+
+    /// The request target, i.e., the path and the optional query.
+    pub target: &'a str,
+
+    /// The value of the `Content-Type` header if present.
+    pub content_type: Option<&'a str>,
+
+    /// The body of the request.
+    pub body: &'a [u8],
+
+    /// The address of the client.
+    pub client: SocketAddr,
+}
+
+//------------ HttpResponse --------------------------------------------------
+
+/// The response to a DNS over HTTPS request.
+#[derive(Debug)]
+pub struct HttpResponse {
+    /// The HTTP status code.
+    status: u16,
+
+    /// The freshness lifetime of the response in seconds if it is cachable.
+    max_age: Option<u32>,
+
+    /// The DNS response for successful requests.
+    body: Option<PooledBuf>,
+}
+
+impl HttpResponse {
+    /// Creates a response with the given status and no body.
+    fn status(status: u16) -> Self {
+        HttpResponse {
+            status,
+            max_age: None,
+            body: None,
+        }
+    }
+
+    /// Returns the HTTP status code of the response.
+    pub fn status_code(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the reason phrase for the status code.
+    pub fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            415 => "Unsupported Media Type",
+            500 => "Internal Server Error",
+            _ => "",
+        }
+    }
+
+    /// Returns the freshness lifetime of the response in seconds.
+    ///
+    /// Returns `None` if the response should not be cached.
+    pub fn max_age(&self) -> Option<u32> {
+        self.max_age
+    }
+
+    /// Returns the headers of the response.
+    ///
+    /// The list contains the `Content-Type` and `Cache-Control` headers
+    /// for successful responses and the `Allow` header for responses with
+    /// status 405. It does not contain the `Content-Length` header.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut res = Vec::new();
+        if self.body.is_some() {
+            res.push(("Content-Type", DOH_CONTENT_TYPE.into()));
+            match self.max_age {
+                Some(max_age) => res
+                    .push(("Cache-Control", format!("max-age={}", max_age))),
+                None => res.push(("Cache-Control", "no-store".into())),
+            }
+        }
+        if self.status == 405 {
+            res.push(("Allow", "GET, HEAD, POST".into()));
+        }
+        res
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &[u8] {
+        self.body.as_ref().map(AsRef::as_ref).unwrap_or(b"")
+    }
+
+    /// Converts the response into its body.
+    pub fn into_body(self) -> Option<PooledBuf> {
+        self.body
+    }
+}
+
+//------------ cache_max_age -------------------------------------------------
+
+/// Returns the freshness lifetime of a DNS over HTTPS response in seconds.
+///
+/// For responses with records in the answer section, this is the smallest
+/// TTL of these records. For negative responses, i.e., NXDOMAIN responses
+/// or NOERROR responses with an empty answer section, it is the smaller
+/// of the TTL and the minimum field of the SOA record in the authority
+/// section as defined in RFC 2308.
+///
+/// Returns `None` if the response should not be cached, which is the case
+/// for truncated responses, responses with other response codes, negative
+/// responses without an SOA record, and messages that cannot be parsed.
+pub fn cache_max_age<Octets: AsRef<[u8]>>(
+    response: &Message<Octets>,
+) -> Option<u32> {
+    let response = response.for_slice();
+    let header = response.header();
+    if header.tc() {
+        return None;
+    }
+    match header.rcode() {
+        Rcode::NoError | Rcode::NXDomain => {}
+        _ => return None,
+    }
+    let mut ttl = None;
+    if header.rcode() == Rcode::NoError {
+        for record in response.answer().ok()? {
+            let record = record.ok()?;
+            if record.rtype() != Rtype::Opt {
+                ttl =
+                    Some(ttl.map_or(record.ttl(), |ttl| {
+                        cmp::min(ttl, record.ttl())
+                    }));
+            }
+        }
+    }
+    if ttl.is_some() {
+        return ttl;
+    }
+    let soa = response
+        .authority()
+        .ok()?
+        .limit_to::<Soa<ParsedDname<&&[u8]>>>()
+        .next()?
+        .ok()?;
+    Some(cmp::min(soa.ttl(), soa.data().minimum()))
+}
+
+//------------ doh_get_request -----------------------------------------------
+
+/// Extracts the request from the query of a DNS over HTTPS GET request.
+///
+/// The `query` is the query component of the request URI, i.e., the part
+/// after the question mark. The request is taken from the `dns` parameter
+/// and decoded from *base64url* as described in RFC 8484.
+///
+/// Returns `None` if there is no `dns` parameter or it cannot be decoded.
+pub fn doh_get_request(query: &str) -> Option<Bytes> {
+    let value = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("dns="))?;
+    let mut encoded: String = value
+        .chars()
+        .map(|ch| match ch {
+            '-' => '+',
+            '_' => '/',
+            ch => ch,
+        })
+        .collect();
+    let padding = (4 - encoded.len() % 4) % 4;
+    encoded.push_str(&"==="[..padding]);
+    base64::decode(&encoded).ok()
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// Returns whether a content type is that of a DNS message.
+///
+/// Parameters of the content type are ignored.
+fn is_dns_message(content_type: &str) -> bool {
+    let value = match content_type.find(';') {
+        Some(pos) => &content_type[..pos],
+        None => content_type,
+    };
+    value.trim().eq_ignore_ascii_case(DOH_CONTENT_TYPE)
+}
+
+/// Converts a response into a hyper response.
+#[cfg(feature = "hyper")]
+fn hyper_response(response: HttpResponse) -> hyper::Response<hyper::Body> {
+    let mut res = hyper::Response::builder().status(response.status_code());
+    for (name, value) in response.headers() {
+        res = res.header(name, value);
+    }
+    let body = match response.into_body() {
+        Some(body) => hyper::Body::from(body.as_ref().to_vec()),
+        None => hyper::Body::empty(),
+    };
+    // All header names and values are valid, so this can’t fail.
+    res.body(body).unwrap()
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    #[cfg(feature = "hyper")]
+    use crate::utils::testing::run;
+    use core::str::FromStr;
+    use std::string::ToString;
+
+    fn client() -> SocketAddr {
+        "192.0.2.1:443".parse().unwrap()
+    }
+
+    fn query(name: &str) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((Dname::<Vec<u8>>::from_str(name).unwrap(), Rtype::A))
+            .unwrap();
+        msg.finish()
+    }
+
+    /// Answers queries for example.com with two A records and everything
+    /// else with NXDOMAIN.
+    fn service(
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf> {
+        let msg = request.message();
+        let question = msg.first_question()?;
+        let apex = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        if question.qname().to_string() == "example.com" {
+            let mut answer =
+                builder.start_answer_verbatim(msg, Rcode::NoError).ok()?;
+            answer
+                .push((&apex, 300, A::from_octets(192, 0, 2, 1)))
+                .ok()?;
+            answer
+                .push((&apex, 60, A::from_octets(192, 0, 2, 2)))
+                .ok()?;
+            Some(answer.finish())
+        } else {
+            let mut answer = builder
+                .start_answer_verbatim(msg, Rcode::NXDomain)
+                .ok()?
+                .authority();
+            let soa = Soa::new(
+                apex.clone(),
+                apex.clone(),
+                1.into(),
+                3600,
+                600,
+                86400,
+                120,
+            );
+            answer.push((&apex, 3600, soa)).ok()?;
+            Some(answer.finish())
+        }
+    }
+
+    fn post<'a>(target: &'a str, body: &'a [u8]) -> HttpRequest<'a> {
+        HttpRequest {
+            method: "POST",
+            target,
+            content_type: Some("application/dns-message"),
+            body,
+            client: client(),
+        }
+    }
+
+    #[test]
+    fn handle_post() {
+        let endpoint = DohEndpoint::new(service);
+        let request = query("example.com");
+        let response = endpoint.handle(&post("/dns-query", &request));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.max_age(), Some(60));
+        assert!(response
+            .headers()
+            .contains(&("Cache-Control", "max-age=60".into())));
+        let msg = Message::from_octets(response.body()).unwrap();
+        assert_eq!(msg.header_counts().ancount(), 2);
+
+        let request = query("nope.example.com");
+        let response = endpoint.handle(&post("/dns-query", &request));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.max_age(), Some(120));
+
+        let mut wrong = post("/dns-query", &request);
+        wrong.content_type = Some("text/plain");
+        assert_eq!(endpoint.handle(&wrong).status_code(), 415);
+        wrong.content_type = Some("Application/DNS-Message; charset=x");
+        assert_eq!(endpoint.handle(&wrong).status_code(), 200);
+        assert_eq!(
+            endpoint.handle(&post("/dns", &request)).status_code(),
+            404
+        );
+        assert_eq!(
+            endpoint.handle(&post("/dns-query", b"")).status_code(),
+            400
+        );
+        let mut put = post("/dns-query", &request);
+        put.method = "PUT";
+        let response = endpoint.handle(&put);
+        assert_eq!(response.status_code(), 405);
+        assert!(response.headers().iter().any(|(name, _)| *name == "Allow"));
+    }
+
+    #[test]
+    fn handle_get() {
+        let mut endpoint = DohEndpoint::new(service);
+        endpoint.set_path("/resolve");
+        let request = HttpRequest {
+            method: "GET",
+            target: "/resolve?dns=AAABAAABAAAAAAAAB2V4YW1wbGUDY29tAAABAAE",
+            content_type: None,
+            body: b"",
+            client: client(),
+        };
+        let response = endpoint.handle(&request);
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.max_age(), Some(60));
+
+        let request = HttpRequest {
+            target: "/resolve?ct=foo",
+            ..request
+        };
+        assert_eq!(endpoint.handle(&request).status_code(), 400);
+        let request = HttpRequest {
+            target: "/dns-query?dns=AAABAAABAAAAAAAAB2V4YW1wbGUDY29tAAABAAE",
+            ..request
+        };
+        assert_eq!(endpoint.handle(&request).status_code(), 404);
+    }
+
+    #[cfg(feature = "hyper")]
+    #[test]
+    fn serve() {
+        use hyper::client::conn::Builder;
+
+        run(async {
+            let endpoint = Arc::new(DohEndpoint::new(service));
+            let (client_sock, server_sock) = tokio::io::duplex(4096);
+            tokio::spawn(endpoint.serve(server_sock, client()));
+            let (mut send, conn) = Builder::new()
+                .http2_only(true)
+                .handshake::<_, hyper::Body>(client_sock)
+                .await
+                .unwrap();
+            tokio::spawn(conn);
+
+            let request = hyper::Request::post("/dns-query")
+                .header("Content-Type", "application/dns-message")
+                .body(query("example.com").into())
+                .unwrap();
+            let response = send.send_request(request).await.unwrap();
+            assert_eq!(response.version(), hyper::Version::HTTP_2);
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()["cache-control"], "max-age=60");
+            let body =
+                hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let msg = Message::from_octets(body.as_ref()).unwrap();
+            assert_eq!(msg.header_counts().ancount(), 2);
+
+            let request = hyper::Request::post("/dns-query")
+                .header("Content-Type", "application/dns-message")
+                .body(vec![0; MAX_BODY_LEN + 1].into())
+                .unwrap();
+            let response = send.send_request(request).await.unwrap();
+            assert_eq!(response.status(), 413);
+
+            let request = hyper::Request::get("/other")
+                .body(hyper::Body::empty())
+                .unwrap();
+            let response = send.send_request(request).await.unwrap();
+            assert_eq!(response.status(), 404);
+        })
+    }
+
+    #[test]
+    fn max_age() {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_rcode(Rcode::ServFail);
+        let msg = msg.into_message();
+        assert_eq!(cache_max_age(&msg), None);
+
+        let msg = MessageBuilder::new_vec().into_message();
+        assert_eq!(cache_max_age(&msg), None);
+    }
+
+    #[test]
+    fn doh_get() {
+        // The example from RFC 8484, section 4.1.1.
+        let request = doh_get_request(
+            "foo=bar&dns=AAABAAABAAAAAAAAA3d3dwdleGFtcGxlA2NvbQAAAQAB",
+        )
+        .unwrap();
+        let request = Message::from_octets(request).unwrap();
+        let question = request.first_question().unwrap();
+        assert_eq!(question.qname().to_string(), "www.example.com");
+        assert_eq!(question.qtype(), Rtype::A);
+        assert!(doh_get_request("dns=!!").is_none());
+        assert!(doh_get_request("ct=foo").is_none());
+    }
+}
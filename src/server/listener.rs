@@ -0,0 +1,877 @@
+//! Listeners for DNS over TLS and DNS over QUIC.
+//!
+//! This module provides the connection handling for the two encrypted
+//! stream transports. Both answer requests via a [`Service`] and apply the
+//! same [`ConnectionLimits`]: the number of queries answered on a single
+//! connection can be limited and connections are closed after having been
+//! idle for some time.
+//!
+//! The crate itself doesn’t contain a TLS or QUIC implementation. Instead,
+//! the listeners use one provided by the application through the traits
+//! [`TlsAcceptor`] and [`QuicConnection`].
+//!
+//! [`TlsListener`] serves DNS over TLS as defined in RFC 7858. It accepts
+//! TCP connections, performs the TLS handshake through the acceptor, and
+//! then answers length-prefixed requests in order. The acceptor is kept
+//! in a [`Reloadable`], so the certificate can be replaced while serving
+//! by reloading a new acceptor. Existing connections continue with the
+//! certificate they were established with. If the client negotiated an
+//! application protocol via ALPN, it must be [`DOT_ALPN`].
+//!
+//! [`DoqListener`] serves DNS over QUIC as defined in RFC 9250 on a QUIC
+//! connection already established by the application’s QUIC endpoint,
+//! which is also responsible for certificate handling. The connection
+//! must have negotiated [`DOQ_ALPN`]. Each request arrives on its own
+//! bidirectional stream and streams are served concurrently up to a
+//! configurable limit.
+//!
+//! [`Service`]: ../service/trait.Service.html
+//! [`Reloadable`]: ../reload/struct.Reloadable.html
+//! [`ConnectionLimits`]: struct.ConnectionLimits.html
+//! [`TlsAcceptor`]: trait.TlsAcceptor.html
+//! [`QuicConnection`]: trait.QuicConnection.html
+//! [`TlsListener`]: struct.TlsListener.html
+//! [`DoqListener`]: struct.DoqListener.html
+//! [`DOT_ALPN`]: constant.DOT_ALPN.html
+//! [`DOQ_ALPN`]: constant.DOQ_ALPN.html
+
+use super::acl::Transport;
+use super::reload::Reloadable;
+use super::service::{BufferPools, Request, Service};
+use super::tcp::{read_message, write_message};
+use crate::base::opt::rfc7830::PaddingPolicy;
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::boxed::Box;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use std::vec::Vec;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep_until, timeout, Instant};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The ALPN protocol identifier for DNS over TLS.
+pub const DOT_ALPN: &[u8] = b"dot";
+
+/// The ALPN protocol identifier for DNS over QUIC.
+pub const DOQ_ALPN: &[u8] = b"doq";
+
+/// The DoQ error code for closing a connection without an error.
+pub const DOQ_NO_ERROR: u64 = 0x0;
+
+/// The DoQ error code for an internal error.
+pub const DOQ_INTERNAL_ERROR: u64 = 0x1;
+
+/// The DoQ error code for a violation of the protocol by the peer.
+pub const DOQ_PROTOCOL_ERROR: u64 = 0x2;
+
+//------------ ConnectionLimits ----------------------------------------------
+
+/// Limits applied to each connection.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionLimits {
+    /// The maximum number of queries answered on a connection.
+    max_queries: Option<u64>,
+
+    /// The maximum number of concurrent DoQ streams.
+    max_streams: usize,
+
+    /// The time after which an idle connection is closed.
+    idle_timeout: Duration,
+}
+
+impl ConnectionLimits {
+    /// The default idle timeout.
+    pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// The default maximum number of concurrent DoQ streams.
+    pub const DEFAULT_MAX_STREAMS: usize = 100;
+
+    /// Creates the default limits.
+    ///
+    /// By default, the number of queries per connection is not limited.
+    pub fn new() -> Self {
+        ConnectionLimits {
+            max_queries: None,
+            max_streams: Self::DEFAULT_MAX_STREAMS,
+            idle_timeout: Self::DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Returns the maximum number of queries answered on a connection.
+    pub fn max_queries(&self) -> Option<u64> {
+        self.max_queries
+    }
+
+    /// Sets the maximum number of queries answered on a connection.
+    ///
+    /// Once this many queries have been received, no further queries are
+    /// accepted and the connection is closed after the pending responses
+    /// have been sent.
+    pub fn set_max_queries(&mut self, max_queries: Option<u64>) {
+        self.max_queries = max_queries
+    }
+
+    /// Returns the maximum number of concurrent DoQ streams.
+    pub fn max_streams(&self) -> usize {
+        self.max_streams
+    }
+
+    /// Sets the maximum number of concurrent DoQ streams.
+    ///
+    /// While this many streams are being served, no new streams are
+    /// accepted. The value is raised to at least one.
+    pub fn set_max_streams(&mut self, max_streams: usize) {
+        self.max_streams = max_streams.max(1)
+    }
+
+    /// Returns the idle timeout.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Sets the idle timeout.
+    ///
+    /// A connection is closed if no query has been received for this long
+    /// while no query is being processed. The timeout also limits the
+    /// duration of the TLS handshake.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = idle_timeout
+    }
+
+    /// Returns whether the limit of queries is reached after `count`.
+    fn queries_exhausted(&self, count: u64) -> bool {
+        match self.max_queries {
+            Some(max) => count >= max,
+            None => false,
+        }
+    }
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ TlsAcceptor ---------------------------------------------------
+
+/// A type that performs the server side of a TLS handshake.
+///
+/// The type parameter `Io` is the type of the underlying connection,
+/// normally `TcpStream`.
+pub trait TlsAcceptor<Io> {
+    /// The type of the established TLS stream.
+    type Stream: AsyncRead + AsyncWrite + Unpin;
+
+    /// Performs the handshake on `io`.
+    ///
+    /// The acceptor should offer [`DOT_ALPN`] as application protocol.
+    ///
+    /// [`DOT_ALPN`]: constant.DOT_ALPN.html
+    fn accept(&self, io: Io) -> AcceptFuture<Self::Stream>;
+}
+
+/// The future returned by [`TlsAcceptor::accept`].
+///
+/// [`TlsAcceptor::accept`]: trait.TlsAcceptor.html#tymethod.accept
+pub type AcceptFuture<S> =
+    Pin<Box<dyn Future<Output = Result<Accepted<S>, io::Error>>>>;
+
+/// An established TLS stream.
+#[derive(Debug)]
+pub struct Accepted<S> {
+    /// The TLS stream.
+    pub stream: S,
+
+    /// The application protocol negotiated via ALPN if any.
+    pub alpn: Option<Vec<u8>>,
+}
+
+//------------ TlsListener ---------------------------------------------------
+
+/// A listener for DNS over TLS.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+pub struct TlsListener<A, S> {
+    /// The current TLS acceptor.
+    acceptor: Reloadable<A>,
+
+    /// The service producing the responses.
+    service: S,
+
+    /// The pools for the response buffers.
+    pools: BufferPools,
+
+    /// The limits for each connection.
+    limits: ConnectionLimits,
+
+    /// The padding policy for responses.
+    padding: PaddingPolicy,
+}
+
+impl<A, S> TlsListener<A, S> {
+    /// Creates a new listener.
+    pub fn new(acceptor: Reloadable<A>, service: S) -> Self {
+        TlsListener {
+            acceptor,
+            service,
+            pools: BufferPools::default(),
+            limits: ConnectionLimits::default(),
+            padding: PaddingPolicy::RESPONSE,
+        }
+    }
+
+    /// Returns a reference to the acceptor.
+    ///
+    /// A new acceptor, for instance one with an updated certificate, can
+    /// be installed via [`Reloadable::reload`]. It is used for all
+    /// connections accepted afterwards.
+    ///
+    /// [`Reloadable::reload`]: ../reload/struct.Reloadable.html#method.reload
+    pub fn acceptor(&self) -> &Reloadable<A> {
+        &self.acceptor
+    }
+
+    /// Returns a reference to the service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// Returns the connection limits.
+    pub fn limits(&self) -> ConnectionLimits {
+        self.limits
+    }
+
+    /// Sets the connection limits.
+    pub fn set_limits(&mut self, limits: ConnectionLimits) {
+        self.limits = limits
+    }
+
+    /// Returns the padding policy for responses.
+    pub fn padding(&self) -> PaddingPolicy {
+        self.padding
+    }
+
+    /// Sets the padding policy for responses.
+    ///
+    /// Responses are only padded if the request was padded, too. The
+    /// default is the block-length padding recommended by RFC 8467.
+    pub fn set_padding(&mut self, padding: PaddingPolicy) {
+        self.padding = padding
+    }
+
+    /// Sets the pools for the response buffers.
+    pub fn set_pools(&mut self, pools: BufferPools) {
+        self.pools = pools
+    }
+}
+
+impl<A, S: Service> TlsListener<A, S> {
+    /// Accepts and serves connections on a TCP listener.
+    ///
+    /// Connections are served concurrently. Errors on individual
+    /// connections only end these connections. The function only returns
+    /// if accepting a new connection fails.
+    pub async fn serve(&self, listener: &TcpListener) -> Result<(), io::Error>
+    where
+        A: TlsAcceptor<TcpStream>,
+    {
+        let mut connections = FuturesUnordered::new();
+        loop {
+            tokio::select! {
+                res = listener.accept() => {
+                    let (sock, client) = res?;
+                    connections.push(self.serve_connection(sock, client));
+                }
+                Some(_) = connections.next(), if !connections.is_empty() => {}
+            }
+        }
+    }
+
+    /// Serves a single connection.
+    ///
+    /// Performs the TLS handshake on `io` and then answers requests until
+    /// the client closes the connection, the connection has been idle for
+    /// too long, or the maximum number of queries has been answered.
+    ///
+    /// Returns an error if the handshake fails or times out, if the client
+    /// negotiated an application protocol other than DoT, or if reading
+    /// or writing fails.
+    pub async fn serve_connection<Io>(
+        &self,
+        io: Io,
+        client: SocketAddr,
+    ) -> Result<(), io::Error>
+    where
+        A: TlsAcceptor<Io>,
+    {
+        let handshake = self.acceptor.acquire().accept(io);
+        let accepted =
+            match timeout(self.limits.idle_timeout, handshake).await {
+                Ok(accepted) => accepted?,
+                Err(_) => return Err(io::ErrorKind::TimedOut.into()),
+            };
+        match accepted.alpn {
+            Some(ref alpn) if alpn.as_slice() != DOT_ALPN => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected application protocol",
+                ))
+            }
+            _ => {}
+        }
+        let mut stream = accepted.stream;
+        let mut count = 0;
+        while !self.limits.queries_exhausted(count) {
+            let request = match timeout(
+                self.limits.idle_timeout,
+                read_message(&mut stream),
+            )
+            .await
+            {
+                Ok(Ok(Some(request))) => request,
+                Ok(Ok(None)) | Err(_) => break,
+                Ok(Err(err)) => return Err(err),
+            };
+            count += 1;
+            let request = match Request::new(
+                request.as_ref(),
+                client,
+                Transport::Tls,
+            ) {
+                Ok(request) => request,
+                Err(_) => break,
+            };
+            if let Some(mut response) =
+                self.service.respond(&request, &self.pools)
+            {
+                request.pad_response(&mut response, self.padding);
+                write_message(&mut stream, response.as_ref()).await?;
+            }
+        }
+        stream.shutdown().await
+    }
+}
+
+//------------ QuicConnection ------------------------------------------------
+
+/// An established QUIC connection.
+pub trait QuicConnection {
+    /// The type of a bidirectional stream.
+    type Stream: AsyncRead + AsyncWrite + Unpin;
+
+    /// Returns the application protocol negotiated for the connection.
+    fn alpn(&self) -> Option<&[u8]>;
+
+    /// Accepts the next bidirectional stream opened by the client.
+    ///
+    /// Resolves to `None` if the connection has been closed. The returned
+    /// future must be cancel safe: if it is dropped before completion, no
+    /// stream may be lost.
+    fn accept_stream(&mut self) -> StreamFuture<'_, Self::Stream>;
+
+    /// Closes the connection with the given application error code.
+    fn close(&mut self, code: u64);
+}
+
+/// The future returned by [`QuicConnection::accept_stream`].
+///
+/// [`QuicConnection::accept_stream`]: trait.QuicConnection.html#tymethod.accept_stream
+pub type StreamFuture<'a, S> =
+    Pin<Box<dyn Future<Output = Result<Option<S>, io::Error>> + 'a>>;
+
+//------------ DoqListener ---------------------------------------------------
+
+/// A listener for DNS over QUIC.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+pub struct DoqListener<S> {
+    /// The service producing the responses.
+    service: S,
+
+    /// The pools for the response buffers.
+    pools: BufferPools,
+
+    /// The limits for each connection.
+    limits: ConnectionLimits,
+
+    /// The padding policy for responses.
+    padding: PaddingPolicy,
+}
+
+impl<S> DoqListener<S> {
+    /// Creates a new listener.
+    pub fn new(service: S) -> Self {
+        DoqListener {
+            service,
+            pools: BufferPools::default(),
+            limits: ConnectionLimits::default(),
+            padding: PaddingPolicy::RESPONSE,
+        }
+    }
+
+    /// Returns a reference to the service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// Returns the connection limits.
+    pub fn limits(&self) -> ConnectionLimits {
+        self.limits
+    }
+
+    /// Sets the connection limits.
+    pub fn set_limits(&mut self, limits: ConnectionLimits) {
+        self.limits = limits
+    }
+
+    /// Returns the padding policy for responses.
+    pub fn padding(&self) -> PaddingPolicy {
+        self.padding
+    }
+
+    /// Sets the padding policy for responses.
+    ///
+    /// Responses are only padded if the request was padded, too. The
+    /// default is the block-length padding recommended by RFC 8467.
+    pub fn set_padding(&mut self, padding: PaddingPolicy) {
+        self.padding = padding
+    }
+
+    /// Sets the pools for the response buffers.
+    pub fn set_pools(&mut self, pools: BufferPools) {
+        self.pools = pools
+    }
+}
+
+impl<S: Service> DoqListener<S> {
+    /// Serves a single QUIC connection.
+    ///
+    /// If the connection hasn’t negotiated [`DOQ_ALPN`], it is closed
+    /// with a protocol error right away. Otherwise, requests are answered
+    /// until the client closes the connection, the connection has been
+    /// idle for too long, or the maximum number of queries has been
+    /// received, in which case the connection is closed with
+    /// [`DOQ_NO_ERROR`]. If the client violates the protocol on any
+    /// stream, the connection is closed with [`DOQ_PROTOCOL_ERROR`].
+    ///
+    /// Returns an error if accepting a stream fails.
+    ///
+    /// [`DOQ_ALPN`]: constant.DOQ_ALPN.html
+    /// [`DOQ_NO_ERROR`]: constant.DOQ_NO_ERROR.html
+    /// [`DOQ_PROTOCOL_ERROR`]: constant.DOQ_PROTOCOL_ERROR.html
+    pub async fn serve_connection<C: QuicConnection>(
+        &self,
+        mut conn: C,
+        client: SocketAddr,
+    ) -> Result<(), io::Error> {
+        if conn.alpn() != Some(DOQ_ALPN) {
+            conn.close(DOQ_PROTOCOL_ERROR);
+            return Ok(());
+        }
+
+        /// What happened while waiting on the connection.
+        enum Event<S> {
+            Stream(Result<Option<S>, io::Error>),
+            Done(Result<(), u64>),
+            Idle,
+        }
+
+        let mut streams = FuturesUnordered::new();
+        let mut count = 0;
+        let mut accepting = true;
+        let mut deadline = Instant::now() + self.limits.idle_timeout;
+        while accepting || !streams.is_empty() {
+            let can_accept =
+                accepting && streams.len() < self.limits.max_streams;
+            let event = tokio::select! {
+                res = conn.accept_stream(), if can_accept => {
+                    Event::Stream(res)
+                }
+                Some(res) = streams.next(), if !streams.is_empty() => {
+                    Event::Done(res)
+                }
+                _ = sleep_until(deadline), if streams.is_empty() => {
+                    Event::Idle
+                }
+            };
+            match event {
+                Event::Stream(Ok(Some(stream))) => {
+                    count += 1;
+                    if self.limits.queries_exhausted(count) {
+                        accepting = false;
+                    }
+                    streams.push(self.serve_stream(stream, client));
+                }
+                Event::Stream(Ok(None)) => return Ok(()),
+                Event::Stream(Err(err)) => return Err(err),
+                Event::Done(Ok(())) => {
+                    deadline = Instant::now() + self.limits.idle_timeout;
+                }
+                Event::Done(Err(code)) => {
+                    conn.close(code);
+                    return Ok(());
+                }
+                Event::Idle => accepting = false,
+            }
+        }
+        conn.close(DOQ_NO_ERROR);
+        Ok(())
+    }
+
+    /// Serves a single DoQ stream.
+    ///
+    /// Returns the error code for closing the connection if the client
+    /// violated the protocol. Errors on the stream itself only end the
+    /// stream.
+    async fn serve_stream<T>(
+        &self,
+        mut stream: T,
+        client: SocketAddr,
+    ) -> Result<(), u64>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let request = match read_message(&mut stream).await {
+            Ok(Some(request)) => request,
+            Ok(None) => return Err(DOQ_PROTOCOL_ERROR),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(DOQ_PROTOCOL_ERROR)
+            }
+            Err(_) => return Ok(()),
+        };
+        let request =
+            match Request::new(request.as_ref(), client, Transport::Quic) {
+                Ok(request) => request,
+                Err(_) => return Err(DOQ_PROTOCOL_ERROR),
+            };
+        // RFC 9250, section 4.2.1: the message ID must be zero.
+        if request.message().header().id() != 0 {
+            return Err(DOQ_PROTOCOL_ERROR);
+        }
+        if let Some(mut response) =
+            self.service.respond(&request, &self.pools)
+        {
+            request.pad_response(&mut response, self.padding);
+            if write_message(&mut stream, response.as_ref()).await.is_err() {
+                return Ok(());
+            }
+        }
+        let _ = stream.shutdown().await;
+        Ok(())
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::server::service::PooledBuf;
+    use crate::utils::testing::run;
+    use core::str::FromStr;
+    use futures::channel::mpsc;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{duplex, AsyncReadExt, DuplexStream};
+
+    fn client() -> SocketAddr {
+        "192.0.2.1:853".parse().unwrap()
+    }
+
+    fn query(id: u16) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish()
+    }
+
+    fn service(
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf> {
+        builder
+            .start_answer_verbatim(request.message(), Rcode::NXDomain)
+            .ok()
+            .map(|answer| answer.finish())
+    }
+
+    async fn exchange(stream: &mut DuplexStream, id: u16) -> Option<u16> {
+        write_message(stream, &query(id)).await.unwrap();
+        let response = read_message(stream).await.unwrap()?;
+        let response = Message::from_octets(response).unwrap();
+        assert_eq!(response.header().rcode(), Rcode::NXDomain);
+        Some(response.header().id())
+    }
+
+    /// An acceptor that doesn’t encrypt and reports a fixed protocol.
+    struct Plain(Option<&'static [u8]>);
+
+    impl TlsAcceptor<DuplexStream> for Plain {
+        type Stream = DuplexStream;
+
+        fn accept(&self, io: DuplexStream) -> AcceptFuture<DuplexStream> {
+            let alpn = self.0.map(Vec::from);
+            Box::pin(async move { Ok(Accepted { stream: io, alpn }) })
+        }
+    }
+
+    #[test]
+    fn dot_limits() {
+        run(async {
+            let mut listener = TlsListener::new(
+                Reloadable::new(Plain(Some(b"dot"))),
+                service,
+            );
+            let mut limits = ConnectionLimits::new();
+            limits.set_max_queries(Some(2));
+            listener.set_limits(limits);
+
+            let (mut sock, server) = duplex(4096);
+            let client_side = async move {
+                assert_eq!(exchange(&mut sock, 1).await, Some(1));
+                assert_eq!(exchange(&mut sock, 2).await, Some(2));
+                // The connection is closed after the second answer.
+                assert!(read_message(&mut sock).await.unwrap().is_none());
+            };
+            let (res, _) = tokio::join!(
+                listener.serve_connection(server, client()),
+                client_side
+            );
+            res.unwrap();
+
+            // An idle connection is closed.
+            limits.set_max_queries(None);
+            limits.set_idle_timeout(Duration::from_millis(50));
+            listener.set_limits(limits);
+            let (mut sock, server) = duplex(4096);
+            listener.serve_connection(server, client()).await.unwrap();
+            let mut buf = Vec::new();
+            assert_eq!(sock.read_to_end(&mut buf).await.unwrap(), 0);
+        })
+    }
+
+    #[test]
+    fn dot_alpn() {
+        run(async {
+            let listener = TlsListener::new(
+                Reloadable::new(Plain(Some(b"h2"))),
+                service,
+            );
+            let (_sock, server) = duplex(4096);
+            assert!(listener
+                .serve_connection(server, client())
+                .await
+                .is_err());
+
+            // Reloading the acceptor applies to new connections. Not
+            // negotiating a protocol at all is fine, too.
+            listener.acceptor().reload(Plain(None));
+            let (mut sock, server) = duplex(4096);
+            let client_side = async move {
+                assert_eq!(exchange(&mut sock, 7).await, Some(7));
+                sock.shutdown().await.unwrap();
+            };
+            let (res, _) = tokio::join!(
+                listener.serve_connection(server, client()),
+                client_side
+            );
+            res.unwrap();
+        })
+    }
+
+    #[test]
+    fn dot_padding() {
+        use crate::base::opt::rfc7830::{Padding, PaddingMode};
+
+        fn padding_service(
+            request: &Request,
+            builder: MessageBuilder<PooledBuf>,
+        ) -> Option<PooledBuf> {
+            let mut answer = builder
+                .start_answer_verbatim(request.message(), Rcode::NXDomain)
+                .ok()?
+                .additional();
+            answer.opt(|_| Ok(())).ok()?;
+            Some(answer.finish())
+        }
+
+        fn padded_query(padded: bool) -> Vec<u8> {
+            let mut msg = MessageBuilder::new_vec().question();
+            msg.push((
+                Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+                Rtype::A,
+            ))
+            .unwrap();
+            let mut msg = msg.additional();
+            msg.opt(|opt| {
+                if padded {
+                    Padding::push(opt, 100, PaddingMode::Zero)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+            msg.finish()
+        }
+
+        run(async {
+            let mut listener = TlsListener::new(
+                Reloadable::new(Plain(Some(b"dot"))),
+                padding_service,
+            );
+            assert_eq!(listener.padding(), PaddingPolicy::RESPONSE);
+            let (mut sock, server) = duplex(4096);
+            let client_side = async move {
+                write_message(&mut sock, &padded_query(true)).await.unwrap();
+                let response = read_message(&mut sock).await.unwrap();
+                assert_eq!(response.unwrap().len(), 468);
+                write_message(&mut sock, &padded_query(false))
+                    .await
+                    .unwrap();
+                let response = read_message(&mut sock).await.unwrap();
+                assert!(response.unwrap().len() < 128);
+                sock.shutdown().await.unwrap();
+            };
+            let (res, _) = tokio::join!(
+                listener.serve_connection(server, client()),
+                client_side
+            );
+            res.unwrap();
+
+            // A different policy leads to different buckets.
+            listener.set_padding(PaddingPolicy::BlockLength(128));
+            let (mut sock, server) = duplex(4096);
+            let client_side = async move {
+                write_message(&mut sock, &padded_query(true)).await.unwrap();
+                let response = read_message(&mut sock).await.unwrap();
+                assert_eq!(response.unwrap().len(), 128);
+                sock.shutdown().await.unwrap();
+            };
+            let (res, _) = tokio::join!(
+                listener.serve_connection(server, client()),
+                client_side
+            );
+            res.unwrap();
+        })
+    }
+
+    /// A QUIC connection receiving streams from a channel.
+    struct FakeQuic {
+        alpn: &'static [u8],
+        streams: mpsc::UnboundedReceiver<DuplexStream>,
+        closed: Arc<Mutex<Option<u64>>>,
+    }
+
+    impl QuicConnection for FakeQuic {
+        type Stream = DuplexStream;
+
+        fn alpn(&self) -> Option<&[u8]> {
+            Some(self.alpn)
+        }
+
+        fn accept_stream(&mut self) -> StreamFuture<'_, DuplexStream> {
+            Box::pin(async move { Ok(self.streams.next().await) })
+        }
+
+        fn close(&mut self, code: u64) {
+            *self.closed.lock().unwrap() = Some(code);
+        }
+    }
+
+    fn fake_quic(
+        alpn: &'static [u8],
+    ) -> (
+        FakeQuic,
+        mpsc::UnboundedSender<DuplexStream>,
+        Arc<Mutex<Option<u64>>>,
+    ) {
+        let (tx, rx) = mpsc::unbounded();
+        let closed = Arc::new(Mutex::new(None));
+        let conn = FakeQuic {
+            alpn,
+            streams: rx,
+            closed: closed.clone(),
+        };
+        (conn, tx, closed)
+    }
+
+    fn open_stream(tx: &mpsc::UnboundedSender<DuplexStream>) -> DuplexStream {
+        let (client, server) = duplex(4096);
+        tx.unbounded_send(server).unwrap();
+        client
+    }
+
+    #[test]
+    fn doq() {
+        run(async {
+            let mut listener = DoqListener::new(service);
+            let mut limits = ConnectionLimits::new();
+            limits.set_max_queries(Some(2));
+            listener.set_limits(limits);
+
+            let (conn, tx, closed) = fake_quic(b"doq");
+            let mut first = open_stream(&tx);
+            let mut second = open_stream(&tx);
+            let client_side = async move {
+                assert_eq!(exchange(&mut first, 0).await, Some(0));
+                assert_eq!(exchange(&mut second, 0).await, Some(0));
+            };
+            let (res, _) = tokio::join!(
+                listener.serve_connection(conn, client()),
+                client_side
+            );
+            res.unwrap();
+            assert_eq!(*closed.lock().unwrap(), Some(DOQ_NO_ERROR));
+
+            // A non-zero message ID is a protocol error.
+            let (conn, tx, closed) = fake_quic(b"doq");
+            let mut stream = open_stream(&tx);
+            let client_side = async move {
+                assert_eq!(exchange(&mut stream, 12).await, None);
+            };
+            let (res, _) = tokio::join!(
+                listener.serve_connection(conn, client()),
+                client_side
+            );
+            res.unwrap();
+            assert_eq!(*closed.lock().unwrap(), Some(DOQ_PROTOCOL_ERROR));
+
+            // So is the wrong protocol.
+            let (conn, _tx, closed) = fake_quic(b"dot");
+            listener.serve_connection(conn, client()).await.unwrap();
+            assert_eq!(*closed.lock().unwrap(), Some(DOQ_PROTOCOL_ERROR));
+
+            // Idle connections are closed.
+            limits.set_idle_timeout(Duration::from_millis(50));
+            listener.set_limits(limits);
+            let (conn, _tx, closed) = fake_quic(b"doq");
+            listener.serve_connection(conn, client()).await.unwrap();
+            assert_eq!(*closed.lock().unwrap(), Some(DOQ_NO_ERROR));
+        })
+    }
+
+    #[test]
+    fn max_streams() {
+        let mut limits = ConnectionLimits::new();
+        limits.set_max_streams(0);
+        assert_eq!(limits.max_streams(), 1);
+        assert_eq!(limits.max_queries(), None);
+    }
+}
@@ -0,0 +1,56 @@
+//! Building blocks for DNS servers.
+//!
+//! This module collects types that are useful when implementing the
+//! server side of the DNS, such as authoritative name servers or
+//! forwarders. It doesn’t provide a server itself but rather the
+//! components that the request handlers of a server need.
+//!
+//! Currently, the following components are available:
+//!
+//! * [acl]: access control lists for deciding which clients are allowed
+//!   to perform an operation.
+//! * [chaos]: answering queries for server information in the CHAOS
+//!   class such as `version.bind`.
+//! * [doh]: serving DNS over HTTPS. Serving HTTP connections requires
+//!   the `hyper` feature, too.
+//! * [listener]: listeners for DNS over TLS and DNS over QUIC.
+//! * [proxy]: relaying requests to upstream servers, possibly over a
+//!   different transport. This requires the `resolv` feature, too.
+//! * [reload]: replacing configuration and zone data while serving.
+//! * [rrl]: response rate limiting to keep a server from being abused in
+//!   reflection attacks.
+//! * [service]: a trait for answering requests by composing responses
+//!   directly into pooled buffers.
+//! * [tcp]: serving DNS over TCP with pipelining, idle timeouts, and
+//!   graceful shutdown.
+//! * [udp]: batched UDP I/O and sharding of requests between sockets.
+//! * [zones]: a set of zones with selection of the zone responsible for a
+//!   query and reloading of individual zones.
+//!
+//! The module requires the `server` feature.
+//!
+//! [acl]: acl/index.html
+//! [chaos]: chaos/index.html
+//! [doh]: doh/index.html
+//! [listener]: listener/index.html
+//! [proxy]: proxy/index.html
+//! [reload]: reload/index.html
+//! [rrl]: rrl/index.html
+//! [service]: service/index.html
+//! [tcp]: tcp/index.html
+//! [udp]: udp/index.html
+//! [zones]: zones/index.html
+#![cfg(feature = "server")]
+
+pub mod acl;
+pub mod chaos;
+pub mod doh;
+pub mod listener;
+#[cfg(feature = "resolv")]
+pub mod proxy;
+pub mod reload;
+pub mod rrl;
+pub mod service;
+pub mod tcp;
+pub mod udp;
+pub mod zones;
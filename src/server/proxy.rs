@@ -0,0 +1,530 @@
+//! Forwarding requests to upstream servers.
+//!
+//! This module provides the building blocks for a DNS proxy: a server that
+//! accepts requests from clients and relays them to upstream servers. The
+//! transport used towards the clients is independent of the one used
+//! towards the upstream servers, so a proxy can, for instance, terminate
+//! DNS over HTTPS and forward the requests via plain UDP and TCP.
+//!
+//! The upstream side is represented by the [`Upstream`] trait. It is
+//! implemented for both the [`StubResolver`] and the [`Forwarder`] whose
+//! configuration determines the upstream servers and transports.
+//!
+//! The [`Proxy`] wraps an upstream and turns request messages into
+//! response messages via [`Proxy::handle`]. It also provides a few
+//! functions for serving the client side:
+//!
+//! * [`Proxy::serve_udp`] answers requests received on a UDP socket,
+//! * [`Proxy::serve_stream`] answers requests received on a single stream
+//!   connection. This can be a TCP connection or, if the application
+//!   wraps the connection into a TLS stream, a DNS over TLS connection.
+//!
+//! For DNS over HTTPS, the [doh] module provides an endpoint that can be
+//! used with any [`Service`]. Alternatively, an application can pass the
+//! body of a POST request directly to [`Proxy::handle`] and use
+//! [`doh_get_request`] to extract the request from the query of a GET
+//! request. Responses are to be returned with the
+//! content type [`DOH_CONTENT_TYPE`].
+//!
+//! [doh]: ../doh/index.html
+//! [`Service`]: ../service/trait.Service.html
+//! [`Upstream`]: trait.Upstream.html
+//! [`StubResolver`]: ../../resolv/stub/struct.StubResolver.html
+//! [`Forwarder`]: ../../resolv/forward/struct.Forwarder.html
+//! [`Proxy`]: struct.Proxy.html
+//! [`Proxy::handle`]: struct.Proxy.html#method.handle
+//! [`Proxy::serve_udp`]: struct.Proxy.html#method.serve_udp
+//! [`Proxy::serve_stream`]: struct.Proxy.html#method.serve_stream
+//! [`doh_get_request`]: ../doh/fn.doh_get_request.html
+//! [`DOH_CONTENT_TYPE`]: ../doh/constant.DOH_CONTENT_TYPE.html
+
+use super::acl::Transport;
+use super::tcp::{read_message, write_message};
+use super::udp::{self, RecvBatch};
+use crate::base::header::Header;
+use crate::base::iana::{Opcode, Rcode};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+#[cfg(feature = "metrics")]
+use crate::metrics::ServerMetrics;
+use crate::resolv::forward::Forwarder;
+use crate::resolv::stub::{Answer, StubResolver};
+use bytes::Bytes;
+use futures::future::FutureExt;
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use std::boxed::Box;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+use std::vec::Vec;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::UdpSocket;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum size of a response over UDP if the request has no OPT record.
+const MIN_UDP_SIZE: u16 = 512;
+
+/// The maximum number of datagrams received or sent at once.
+const UDP_BATCH_LEN: usize = 32;
+
+/// The size of the receive buffer for each UDP request.
+///
+/// Longer requests are dropped.
+const UDP_BUF_LEN: usize = 4096;
+
+//------------ Upstream ------------------------------------------------------
+
+/// A type that can forward a request to upstream servers.
+pub trait Upstream {
+    /// Forwards a request and returns the upstream’s response.
+    ///
+    /// The message ID of the response doesn’t need to match that of the
+    /// request. The proxy will take care of that.
+    fn forward<'a>(
+        &'a self,
+        request: &'a Message<Bytes>,
+    ) -> UpstreamFuture<'a>;
+}
+
+/// The future returned by [`Upstream::forward`].
+///
+/// [`Upstream::forward`]: trait.Upstream.html#tymethod.forward
+pub type UpstreamFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Message<Bytes>, io::Error>> + 'a>>;
+
+impl Upstream for StubResolver {
+    /// Answers the first question of the request.
+    fn forward<'a>(
+        &'a self,
+        request: &'a Message<Bytes>,
+    ) -> UpstreamFuture<'a> {
+        Box::pin(async move {
+            let question = request.first_question().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "request without question",
+                )
+            })?;
+            self.query(question).await.map(Answer::into_message)
+        })
+    }
+}
+
+impl Upstream for Forwarder {
+    fn forward<'a>(
+        &'a self,
+        request: &'a Message<Bytes>,
+    ) -> UpstreamFuture<'a> {
+        Box::pin(async move {
+            Forwarder::forward(self, request)
+                .await
+                .map(Answer::into_message)
+        })
+    }
+}
+
+//------------ Proxy ---------------------------------------------------------
+
+/// A proxy relaying requests to an upstream.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Clone, Debug)]
+pub struct Proxy<U> {
+    /// The upstream to forward requests to.
+    upstream: U,
+
+    /// The metrics to update if any.
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<ServerMetrics>>,
+}
+
+impl<U> Proxy<U> {
+    /// Creates a new proxy for the given upstream.
+    pub fn new(upstream: U) -> Self {
+        Proxy {
+            upstream,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Returns a reference to the upstream.
+    pub fn upstream(&self) -> &U {
+        &self.upstream
+    }
+
+    /// Attaches metrics to be updated for every response.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics(&mut self, metrics: Arc<ServerMetrics>) {
+        self.metrics = Some(metrics)
+    }
+
+    /// Returns the metrics updated by the proxy if there are any.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Option<&Arc<ServerMetrics>> {
+        self.metrics.as_ref()
+    }
+}
+
+impl<U: Upstream> Proxy<U> {
+    /// Produces the response for a request received over `transport`.
+    ///
+    /// Returns `None` if the request should be dropped because it is too
+    /// short to contain a message header or is a response itself.
+    /// Requests with an opcode other than QUERY receive a NOTIMP response
+    /// and requests without exactly one question a FORMERR response. If
+    /// the upstream fails, the response is SERVFAIL.
+    ///
+    /// The response has the message ID of the request. If `transport` is
+    /// UDP and the response is larger than the client accepts, a truncated
+    /// response without any records is returned instead.
+    pub async fn handle(
+        &self,
+        request: Bytes,
+        transport: Transport,
+    ) -> Option<Bytes> {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let response = self.process(request, transport).await?;
+        #[cfg(feature = "metrics")]
+        if let Some(ref metrics) = self.metrics {
+            let message = Message::from_octets(response.clone()).ok()?;
+            if let Some(question) = message.first_question() {
+                metrics.record_query(
+                    &transport,
+                    question.qtype(),
+                    message.header().rcode(),
+                    start.elapsed(),
+                );
+            }
+        }
+        Some(response)
+    }
+
+    /// Produces the response for a request.
+    async fn process(
+        &self,
+        request: Bytes,
+        transport: Transport,
+    ) -> Option<Bytes> {
+        let request = Message::from_octets(request).ok()?;
+        if request.header().qr() {
+            return None;
+        }
+        if request.header().opcode() != Opcode::Query {
+            return Some(error_response(&request, Rcode::NotImp));
+        }
+        if request.header_counts().qdcount() != 1
+            || request.first_question().is_none()
+        {
+            return Some(error_response(&request, Rcode::FormErr));
+        }
+        let response = match self.upstream.forward(&request).await {
+            Ok(response) => response,
+            Err(_) => return Some(error_response(&request, Rcode::ServFail)),
+        };
+        if transport == Transport::Udp
+            && response.as_slice().len() > max_udp_size(&request)
+        {
+            let mut response = MessageBuilder::new_bytes()
+                .start_answer(&request, response.header().rcode())
+                .ok()?;
+            response.header_mut().set_tc(true);
+            return Some(response.finish().freeze());
+        }
+        let mut response = response.into_octets().to_vec();
+        let header = Header::for_message_slice_mut(&mut response);
+        header.set_id(request.header().id());
+        header.set_rd(request.header().rd());
+        Some(response.into())
+    }
+
+    /// Answers requests received on a UDP socket.
+    ///
+    /// Requests are received and responses sent in batches using the
+    /// functions of the [udp] module. They are processed concurrently. The
+    /// function only returns if receiving from or sending to the socket
+    /// fails.
+    ///
+    /// To make use of several CPUs, create sockets via
+    /// [`udp::bind_sharded`] and serve each of them on its own thread.
+    ///
+    /// [udp]: ../udp/index.html
+    /// [`udp::bind_sharded`]: ../udp/fn.bind_sharded.html
+    pub async fn serve_udp(&self, sock: &UdpSocket) -> Result<(), io::Error> {
+        let mut batch = RecvBatch::new(UDP_BATCH_LEN, UDP_BUF_LEN);
+        let mut pending = FuturesUnordered::new();
+        let mut responses = Vec::with_capacity(UDP_BATCH_LEN);
+        loop {
+            tokio::select! {
+                res = udp::recv_batch(sock, &mut batch) => {
+                    res?;
+                    for (request, addr) in batch.iter() {
+                        let request = Bytes::copy_from_slice(request);
+                        pending.push(async move {
+                            (self.handle(request, Transport::Udp).await, addr)
+                        });
+                    }
+                }
+                Some(item) = pending.next(), if !pending.is_empty() => {
+                    // Collect all other responses that are ready, too.
+                    let mut item = Some(item);
+                    while let Some((response, addr)) = item {
+                        if let Some(response) = response {
+                            responses.push((response, addr));
+                        }
+                        if responses.len() == UDP_BATCH_LEN {
+                            break;
+                        }
+                        item = pending.next().now_or_never().flatten();
+                    }
+                    udp::send_batch(sock, &responses).await?;
+                    responses.clear();
+                }
+            }
+        }
+    }
+
+    /// Answers requests received on a stream connection.
+    ///
+    /// Messages on the stream are preceded by a two octet length as
+    /// described in RFC 1035. Requests are processed concurrently and
+    /// their responses written in the order they become available. The
+    /// `transport` is passed on to [`handle`] and should be
+    /// `Transport::Tcp` or, for a TLS stream, `Transport::Tls`.
+    ///
+    /// The function returns once the client has closed its side of the
+    /// connection and all responses have been written.
+    ///
+    /// [`handle`]: #method.handle
+    pub async fn serve_stream<S>(
+        &self,
+        sock: S,
+        transport: Transport,
+    ) -> Result<(), io::Error>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (read, mut write) = tokio::io::split(sock);
+        let requests = stream::unfold(read, |mut read| async move {
+            match read_message(&mut read).await {
+                Ok(Some(message)) => Some((Ok(message), read)),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), read)),
+            }
+        });
+        futures::pin_mut!(requests);
+        let mut pending = FuturesUnordered::new();
+        let mut reading = true;
+        loop {
+            tokio::select! {
+                request = requests.next(), if reading => {
+                    match request {
+                        Some(request) => {
+                            let request = request?;
+                            pending.push(self.handle(request, transport));
+                        }
+                        None => reading = false,
+                    }
+                }
+                Some(response) = pending.next(), if !pending.is_empty() => {
+                    if let Some(response) = response {
+                        write_message(&mut write, &response).await?;
+                    }
+                }
+                else => break,
+            }
+        }
+        write.shutdown().await
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// Creates a response with the given rcode and no records.
+fn error_response(request: &Message<Bytes>, rcode: Rcode) -> Bytes {
+    MessageBuilder::new_bytes()
+        .start_answer(request, rcode)
+        .map(|builder| builder.finish().freeze())
+        .unwrap_or_else(|_| {
+            let mut builder = MessageBuilder::new_bytes();
+            let header = builder.header_mut();
+            header.set_id(request.header().id());
+            header.set_qr(true);
+            header.set_opcode(request.header().opcode());
+            header.set_rcode(rcode);
+            builder.finish().freeze()
+        })
+}
+
+/// Returns the maximum size of a UDP response to `request`.
+fn max_udp_size(request: &Message<Bytes>) -> usize {
+    let size = match request.opt() {
+        Some(opt) => opt.udp_payload_size().max(MIN_UDP_SIZE),
+        None => MIN_UDP_SIZE,
+    };
+    usize::from(size)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::name::{Dname, ToDname};
+    use crate::rdata::A;
+    use crate::utils::testing::run;
+    use core::str::FromStr;
+    use std::string::ToString;
+    use tokio::io::duplex;
+
+    /// An upstream answering with a number of A records.
+    struct Mock {
+        records: Option<u8>,
+    }
+
+    impl Upstream for Mock {
+        fn forward<'a>(
+            &'a self,
+            request: &'a Message<Bytes>,
+        ) -> UpstreamFuture<'a> {
+            Box::pin(async move {
+                let count = self.records.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::Other, "upstream failed")
+                })?;
+                let mut answer = MessageBuilder::new_bytes()
+                    .start_answer(request, Rcode::NoError)
+                    .unwrap();
+                answer.header_mut().set_id(request.header().id() ^ 0xFFFF);
+                answer.header_mut().set_rd(false);
+                let qname =
+                    request.first_question().unwrap().qname().to_vec();
+                for i in 0..count {
+                    answer
+                        .push((&qname, 3600, A::from_octets(192, 0, 2, i)))
+                        .unwrap();
+                }
+                Ok(answer.into_message())
+            })
+        }
+    }
+
+    fn request(id: u16) -> Bytes {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_id(id);
+        msg.header_mut().set_rd(true);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        msg.finish().freeze()
+    }
+
+    fn handle(
+        records: Option<u8>,
+        request: Bytes,
+        transport: Transport,
+    ) -> Option<Message<Bytes>> {
+        run(Proxy::new(Mock { records }).handle(request, transport))
+            .map(|response| Message::from_octets(response).unwrap())
+    }
+
+    #[test]
+    fn handle_request() {
+        let response = handle(Some(2), request(12), Transport::Udp).unwrap();
+        assert!(
+            response.is_answer(&Message::from_octets(request(12)).unwrap())
+        );
+        assert!(response.header().rd());
+        assert_eq!(response.header_counts().ancount(), 2);
+
+        let response = handle(None, request(13), Transport::Tcp).unwrap();
+        assert_eq!(response.header().id(), 13);
+        assert_eq!(response.header().rcode(), Rcode::ServFail);
+
+        // Responses and short messages are dropped.
+        assert!(
+            handle(Some(1), response.into_octets(), Transport::Udp).is_none()
+        );
+        assert!(handle(
+            Some(1),
+            Bytes::from_static(b"\0\x01"),
+            Transport::Udp
+        )
+        .is_none());
+
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_id(14);
+        let response =
+            handle(Some(1), msg.finish().freeze(), Transport::Udp).unwrap();
+        assert_eq!(response.header().id(), 14);
+        assert_eq!(response.header().rcode(), Rcode::FormErr);
+
+        let mut msg = request(15).to_vec();
+        Header::for_message_slice_mut(&mut msg).set_opcode(Opcode::Status);
+        let response = handle(Some(1), msg.into(), Transport::Udp).unwrap();
+        assert_eq!(response.header().rcode(), Rcode::NotImp);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        let metrics = Arc::new(ServerMetrics::new());
+        let mut proxy = Proxy::new(Mock { records: Some(1) });
+        proxy.set_metrics(metrics.clone());
+        run(proxy.handle(request(1), Transport::Tls)).unwrap();
+        assert_eq!(metrics.queries().get(&["tls", "A", "NOERROR"]), 1);
+        assert_eq!(metrics.latency().count(), 1);
+    }
+
+    #[test]
+    fn truncate_udp() {
+        let response = handle(Some(40), request(1), Transport::Udp).unwrap();
+        assert!(response.header().tc());
+        assert_eq!(response.header().id(), 1);
+        assert_eq!(response.header_counts().qdcount(), 1);
+        assert_eq!(response.header_counts().ancount(), 0);
+
+        let response = handle(Some(40), request(1), Transport::Tls).unwrap();
+        assert!(!response.header().tc());
+        assert_eq!(response.header_counts().ancount(), 40);
+    }
+
+    #[test]
+    fn serve_stream() {
+        run(async {
+            let (mut client, server) = duplex(1024);
+            let proxy = Proxy::new(Mock { records: Some(1) });
+            let server = proxy.serve_stream(server, Transport::Tcp);
+            let client = async move {
+                for id in &[1u16, 2] {
+                    write_message(&mut client, &request(*id)).await.unwrap();
+                }
+                client.shutdown().await.unwrap();
+                let mut ids = Vec::new();
+                while let Some(response) =
+                    read_message(&mut client).await.unwrap()
+                {
+                    ids.push(
+                        Message::from_octets(response).unwrap().header().id(),
+                    );
+                }
+                ids.sort_unstable();
+                ids
+            };
+            let (res, ids) = tokio::join!(server, client);
+            res.unwrap();
+            assert_eq!(ids, [1, 2]);
+        })
+    }
+}
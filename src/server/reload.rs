@@ -0,0 +1,279 @@
+//! Reloading server data while serving.
+//!
+//! A server needs to be able to replace its configuration and zone data
+//! without restarting and thus without dropping connections. The
+//! [`Reloadable`] type in this module helps with this. It holds the
+//! current version of some data – typically a value combining the
+//! configuration with a [`ZoneSet`] – and allows replacing it at any time.
+//!
+//! Request handlers call [`Reloadable::acquire`] when they start working
+//! on a request and keep the returned [`Guard`] until the response has
+//! been sent. The guard dereferences to the version of the data that was
+//! current when it was acquired, so a request is answered consistently
+//! from a single version even if a reload happens in the meantime.
+//!
+//! After [`Reloadable::reload`] has installed a new version, new requests
+//! are answered from it right away while the requests in flight finish
+//! with the old version. [`Reloadable::wait_drained`] waits for these to
+//! complete, after which the old data is dropped. Finally, other parts of
+//! the server can learn about reloads by subscribing to notifications via
+//! [`Reloadable::subscribe`].
+//!
+//! Individual zones of a zone set can be swapped without going through
+//! this type via [`ZoneEntry::reload`].
+//!
+//! [`Reloadable`]: struct.Reloadable.html
+//! [`Reloadable::acquire`]: struct.Reloadable.html#method.acquire
+//! [`Reloadable::reload`]: struct.Reloadable.html#method.reload
+//! [`Reloadable::wait_drained`]: struct.Reloadable.html#method.wait_drained
+//! [`Reloadable::subscribe`]: struct.Reloadable.html#method.subscribe
+//! [`Guard`]: struct.Guard.html
+//! [`ZoneSet`]: ../zones/struct.ZoneSet.html
+//! [`ZoneEntry::reload`]: ../zones/struct.ZoneEntry.html#method.reload
+
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+//------------ Reloadable ----------------------------------------------------
+
+/// Data that can be replaced while it is in use.
+///
+/// See the [module documentation] for details. Values of this type are
+/// cheap to clone and all clones refer to the same data.
+///
+/// [module documentation]: index.html
+#[derive(Debug)]
+pub struct Reloadable<T> {
+    inner: Arc<Inner<T>>,
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    /// The mutable state.
+    state: Mutex<State<T>>,
+
+    /// Notified whenever a guard is dropped.
+    released: Condvar,
+}
+
+#[derive(Debug)]
+struct State<T> {
+    /// The current version.
+    current: Arc<Version<T>>,
+
+    /// The number of guards in flight per version.
+    ///
+    /// Versions without guards are removed.
+    in_flight: BTreeMap<u64, usize>,
+
+    /// The subscribers to reload notifications.
+    subscribers: Vec<mpsc::Sender<u64>>,
+}
+
+impl<T> Reloadable<T> {
+    /// Creates a new value with `data` as version zero.
+    pub fn new(data: T) -> Self {
+        Reloadable {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    current: Arc::new(Version { number: 0, data }),
+                    in_flight: BTreeMap::new(),
+                    subscribers: Vec::new(),
+                }),
+                released: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of the current version.
+    pub fn version(&self) -> u64 {
+        self.inner.state.lock().unwrap().current.number
+    }
+
+    /// Acquires the current version for processing a request.
+    ///
+    /// The version is considered in flight until the returned guard is
+    /// dropped.
+    pub fn acquire(&self) -> Guard<T> {
+        let mut state = self.inner.state.lock().unwrap();
+        let version = state.current.clone();
+        *state.in_flight.entry(version.number).or_insert(0) += 1;
+        Guard {
+            version,
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Installs a new version of the data.
+    ///
+    /// Returns the number of the new version. Subscribers are notified of
+    /// the new version. Guards acquired earlier keep the previous version.
+    pub fn reload(&self, data: T) -> u64 {
+        let mut state = self.inner.state.lock().unwrap();
+        let number = state.current.number + 1;
+        state.current = Arc::new(Version { number, data });
+        state
+            .subscribers
+            .retain(|subscriber| subscriber.send(number).is_ok());
+        number
+    }
+
+    /// Returns the number of guards in flight for versions before `version`.
+    pub fn in_flight_before(&self, version: u64) -> usize {
+        self.inner
+            .state
+            .lock()
+            .unwrap()
+            .in_flight
+            .range(..version)
+            .map(|(_, count)| *count)
+            .sum()
+    }
+
+    /// Waits until all guards for versions before `version` are dropped.
+    ///
+    /// Returns whether this happened before `timeout` has passed. Passing
+    /// the number returned by [`reload`] waits for all requests still
+    /// working with older data to finish.
+    ///
+    /// [`reload`]: #method.reload
+    pub fn wait_drained(&self, version: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if state.in_flight.range(..version).next().is_none() {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self
+                .inner
+                .released
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Subscribes to reload notifications.
+    ///
+    /// The returned receiver receives the number of each new version
+    /// installed via [`reload`]. Dropping the receiver ends the
+    /// subscription.
+    ///
+    /// [`reload`]: #method.reload
+    pub fn subscribe(&self) -> mpsc::Receiver<u64> {
+        let (tx, rx) = mpsc::channel();
+        self.inner.state.lock().unwrap().subscribers.push(tx);
+        rx
+    }
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Reloadable {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+//------------ Version -------------------------------------------------------
+
+/// A version of reloadable data.
+#[derive(Debug)]
+struct Version<T> {
+    number: u64,
+    data: T,
+}
+
+//------------ Guard ---------------------------------------------------------
+
+/// A version of reloadable data in use.
+///
+/// The guard dereferences to the data. See [`Reloadable::acquire`] for
+/// details.
+///
+/// [`Reloadable::acquire`]: struct.Reloadable.html#method.acquire
+#[derive(Debug)]
+pub struct Guard<T> {
+    version: Arc<Version<T>>,
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Guard<T> {
+    /// Returns the number of the version held by the guard.
+    pub fn version(&self) -> u64 {
+        self.version.number
+    }
+}
+
+impl<T> Deref for Guard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.version.data
+    }
+}
+
+impl<T> Drop for Guard<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.lock().unwrap();
+        let number = self.version.number;
+        if let Some(count) = state.in_flight.get_mut(&number) {
+            *count -= 1;
+            if *count == 0 {
+                state.in_flight.remove(&number);
+            }
+        }
+        drop(state);
+        self.inner.released.notify_all();
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::String;
+    use std::thread;
+
+    #[test]
+    fn reload_and_drain() {
+        let data = Reloadable::new(String::from("one"));
+        let updates = data.subscribe();
+        let old = data.acquire();
+        assert_eq!(old.version(), 0);
+
+        let version = data.reload(String::from("two"));
+        assert_eq!(version, 1);
+        assert_eq!(updates.try_recv(), Ok(1));
+        assert_eq!(old.as_str(), "one");
+        let new = data.acquire();
+        assert_eq!(new.as_str(), "two");
+
+        assert_eq!(data.in_flight_before(version), 1);
+        assert!(!data.wait_drained(version, Duration::from_millis(10)));
+
+        let handle = {
+            let data = data.clone();
+            thread::spawn(move || {
+                data.wait_drained(version, Duration::from_secs(10))
+            })
+        };
+        thread::sleep(Duration::from_millis(20));
+        drop(old);
+        assert!(handle.join().unwrap());
+        assert_eq!(data.in_flight_before(version), 0);
+        assert_eq!(data.in_flight_before(version + 1), 1);
+
+        drop(updates);
+        assert_eq!(data.reload(String::from("three")), 2);
+        assert!(data.inner.state.lock().unwrap().subscribers.is_empty());
+    }
+}
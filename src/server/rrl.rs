@@ -0,0 +1,664 @@
+//! Response rate limiting.
+//!
+//! Authoritative servers can be abused for reflection attacks: an attacker
+//! sends queries with the spoofed address of a victim and the server sends
+//! its – typically much larger – responses to the victim. Response rate
+//! limiting, or RRL for short, mitigates this by limiting the rate of
+//! identical responses sent to the same network.
+//!
+//! The [`Rrl`] type in this module implements the scheme used by BIND and
+//! other servers. Responses are sorted into token buckets keyed by the
+//! network prefix of the client and a tuple describing the response: its
+//! [kind][`ResponseKind`], the query name or, for negative responses and
+//! referrals, the name of the zone or delegation, and the query type. Each
+//! bucket is credited with a configured number of responses per second.
+//! Once a bucket has run dry, responses are either dropped or, every so
+//! often, ‘slipped:’ replaced by an empty response with the TC flag set.
+//! Legitimate clients receiving a slipped response will retry over TCP
+//! which cannot be spoofed and is therefore never limited.
+//!
+//! The limiter is placed at the end of the server pipeline: once a
+//! response has been assembled, it is passed to [`Rrl::check`] together
+//! with the client address and transport and the returned [`RrlAction`]
+//! decides what happens to it. For a slip, [`slip_response`] creates the
+//! truncated response to send instead.
+//!
+//! [`Rrl`]: struct.Rrl.html
+//! [`Rrl::check`]: struct.Rrl.html#method.check
+//! [`RrlAction`]: enum.RrlAction.html
+//! [`ResponseKind`]: enum.ResponseKind.html
+//! [`slip_response`]: fn.slip_response.html
+
+use super::acl::{Acl, AclRequest, Transport};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{OctetsRef, ShortBuf};
+use core::cmp;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+//------------ RrlConfig -----------------------------------------------------
+
+/// The configuration of response rate limiting.
+///
+/// A rate of zero disables limiting for the respective kind of responses.
+#[derive(Clone, Debug)]
+pub struct RrlConfig {
+    /// The number of positive answers and NODATA responses per second.
+    pub responses_per_second: u32,
+
+    /// The number of referrals per second.
+    pub referrals_per_second: u32,
+
+    /// The number of NXDOMAIN responses per second.
+    pub nxdomains_per_second: u32,
+
+    /// The number of error responses per second.
+    pub errors_per_second: u32,
+
+    /// The number of seconds a bucket can be in debt.
+    ///
+    /// A client that keeps exceeding the rate needs to stay quiet for up
+    /// to this many seconds before it receives responses again.
+    pub window: u32,
+
+    /// Every how many limited responses one is slipped.
+    ///
+    /// If this is zero, limited responses are always dropped. If it is
+    /// one, they are always slipped.
+    pub slip: u32,
+
+    /// The length of the prefix of IPv4 client addresses forming a bucket.
+    pub ipv4_prefix_len: u8,
+
+    /// The length of the prefix of IPv6 client addresses forming a bucket.
+    pub ipv6_prefix_len: u8,
+
+    /// The maximum number of buckets to keep.
+    ///
+    /// If a new bucket is needed while this many buckets exist, idle
+    /// buckets are purged. If that isn’t enough, the least recently used
+    /// buckets are evicted until a tenth of the buckets is free again, so
+    /// that this doesn’t happen for every response during a flood of
+    /// queries from many different networks. At least one bucket is kept.
+    pub max_buckets: usize,
+}
+
+impl Default for RrlConfig {
+    fn default() -> Self {
+        RrlConfig {
+            responses_per_second: 5,
+            referrals_per_second: 5,
+            nxdomains_per_second: 5,
+            errors_per_second: 5,
+            window: 15,
+            slip: 2,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 56,
+            max_buckets: 100_000,
+        }
+    }
+}
+
+//------------ Rrl -----------------------------------------------------------
+
+/// A response rate limiter.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Clone, Debug)]
+pub struct Rrl {
+    /// The configuration.
+    config: RrlConfig,
+
+    /// Clients that are never limited.
+    exempt: Acl,
+
+    /// The token buckets.
+    buckets: HashMap<BucketKey, Bucket>,
+}
+
+impl Rrl {
+    /// Creates a new rate limiter with the given configuration.
+    pub fn new(config: RrlConfig) -> Self {
+        Rrl {
+            config,
+            exempt: Acl::new(),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Sets the clients that are exempt from rate limiting.
+    ///
+    /// Responses to clients allowed by `exempt` are always sent.
+    pub fn with_exempt(mut self, exempt: Acl) -> Self {
+        self.exempt = exempt;
+        self
+    }
+
+    /// Returns the configuration of the rate limiter.
+    pub fn config(&self) -> &RrlConfig {
+        &self.config
+    }
+
+    /// Returns the number of buckets currently kept.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Returns whether there are currently no buckets.
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Decides what to do with a response.
+    pub fn check<Octets>(
+        &mut self,
+        addr: IpAddr,
+        transport: Transport,
+        response: &Message<Octets>,
+    ) -> RrlAction
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        self.check_at(addr, transport, response, Instant::now())
+    }
+
+    /// Decides what to do with a response sent at the given time.
+    pub fn check_at<Octets>(
+        &mut self,
+        addr: IpAddr,
+        transport: Transport,
+        response: &Message<Octets>,
+        now: Instant,
+    ) -> RrlAction
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        // Only datagram transports can be spoofed.
+        if transport != Transport::Udp {
+            return RrlAction::Send;
+        }
+        let request = AclRequest::<Dname<Vec<u8>>>::new(addr, transport);
+        if self.exempt.allows(&request) {
+            return RrlAction::Send;
+        }
+        let kind = ResponseKind::from_response(response);
+        let rate = self.rate(kind);
+        if rate == 0 {
+            return RrlAction::Send;
+        }
+
+        let key = BucketKey::new(&self.config, addr, kind, response);
+        if self.buckets.len() >= self.config.max_buckets.max(1)
+            && !self.buckets.contains_key(&key)
+        {
+            self.make_room(now);
+        }
+        let window = self.config.window;
+        let bucket = self
+            .buckets
+            .entry(key)
+            .or_insert_with(|| Bucket::new(rate, now));
+        if bucket.take(rate, window, now) {
+            return RrlAction::Send;
+        }
+        match self.config.slip {
+            0 => RrlAction::Drop,
+            slip => {
+                bucket.limited += 1;
+                if bucket.limited >= slip {
+                    bucket.limited = 0;
+                    RrlAction::Slip
+                } else {
+                    RrlAction::Drop
+                }
+            }
+        }
+    }
+
+    /// Removes all buckets that have been idle for at least the window.
+    pub fn purge(&mut self) {
+        self.purge_at(Instant::now())
+    }
+
+    /// Removes all buckets that are idle at the given time.
+    pub fn purge_at(&mut self, now: Instant) {
+        let idle = Duration::from_secs(u64::from(self.config.window.max(1)));
+        self.buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.last) < idle
+        })
+    }
+
+    /// Frees up space for new buckets.
+    ///
+    /// Purges idle buckets and then evicts the least recently used ones
+    /// until at most nine tenths of the maximum number of buckets are left.
+    fn make_room(&mut self, now: Instant) {
+        self.purge_at(now);
+        let max = self.config.max_buckets.max(1);
+        let keep = max - cmp::max(max / 10, 1);
+        if self.buckets.len() <= keep {
+            return;
+        }
+        let mut last: Vec<_> =
+            self.buckets.values().map(|bucket| bucket.last).collect();
+        last.sort_unstable();
+        let cutoff = last[last.len() - keep - 1];
+        self.buckets.retain(|_, bucket| bucket.last > cutoff);
+    }
+
+    /// Returns the rate for the given kind of response.
+    fn rate(&self, kind: ResponseKind) -> u32 {
+        match kind {
+            ResponseKind::Answer | ResponseKind::NoData => {
+                self.config.responses_per_second
+            }
+            ResponseKind::Referral => self.config.referrals_per_second,
+            ResponseKind::NxDomain => self.config.nxdomains_per_second,
+            ResponseKind::Error => self.config.errors_per_second,
+        }
+    }
+}
+
+impl Default for Rrl {
+    fn default() -> Self {
+        Self::new(RrlConfig::default())
+    }
+}
+
+//------------ RrlAction -----------------------------------------------------
+
+/// What to do with a response.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RrlAction {
+    /// Send the response.
+    Send,
+
+    /// Don’t send anything.
+    Drop,
+
+    /// Send a truncated response instead.
+    ///
+    /// The response can be created via [`slip_response`].
+    ///
+    /// [`slip_response`]: fn.slip_response.html
+    Slip,
+}
+
+//------------ ResponseKind --------------------------------------------------
+
+/// The kind of a response for the purpose of rate limiting.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResponseKind {
+    /// A positive answer.
+    Answer,
+
+    /// A response stating that there is no data for the query type.
+    NoData,
+
+    /// A referral to a delegated zone.
+    Referral,
+
+    /// A response stating that the query name doesn’t exist.
+    NxDomain,
+
+    /// A response with an error code.
+    Error,
+}
+
+impl ResponseKind {
+    /// Determines the kind of a response.
+    pub fn from_response<Octets>(response: &Message<Octets>) -> Self
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let header = response.header();
+        match header.rcode() {
+            Rcode::NoError => {}
+            Rcode::NXDomain => return ResponseKind::NxDomain,
+            _ => return ResponseKind::Error,
+        }
+        let counts = response.header_counts();
+        if counts.ancount() > 0 {
+            ResponseKind::Answer
+        } else if !header.aa() && authority_type(response) == Some(Rtype::Ns)
+        {
+            ResponseKind::Referral
+        } else {
+            ResponseKind::NoData
+        }
+    }
+}
+
+//------------ slip_response -------------------------------------------------
+
+/// Creates the truncated response sent instead of a limited response.
+///
+/// The truncated response has the header and question of the original
+/// response but no records and has its TC flag set.
+pub fn slip_response<Octets>(
+    response: &Message<Octets>,
+) -> Result<Message<Vec<u8>>, ShortBuf>
+where
+    Octets: AsRef<[u8]>,
+    for<'a> &'a Octets: OctetsRef,
+{
+    let mut builder = MessageBuilder::new_vec();
+    *builder.header_mut() = response.header();
+    builder.header_mut().set_tc(true);
+    let mut builder = builder.question();
+    for question in response.question().flatten() {
+        builder.push(question)?;
+    }
+    Ok(builder.into_message())
+}
+
+//------------ BucketKey -----------------------------------------------------
+
+/// The key identifying a token bucket.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct BucketKey {
+    /// The network address of the client.
+    network: IpAddr,
+
+    /// The kind of response.
+    kind: ResponseKind,
+
+    /// The name the response is about.
+    ///
+    /// This is the query name for answers and NODATA responses and the
+    /// owner of the first authority record for referrals and NXDOMAIN
+    /// responses. For errors, it is the root name.
+    name: Dname<Vec<u8>>,
+
+    /// The query type or `Rtype::Any` for responses not specific to it.
+    rtype: Rtype,
+}
+
+impl BucketKey {
+    /// Creates the bucket key for a response to a client.
+    fn new<Octets>(
+        config: &RrlConfig,
+        addr: IpAddr,
+        kind: ResponseKind,
+        response: &Message<Octets>,
+    ) -> Self
+    where
+        Octets: AsRef<[u8]>,
+        for<'a> &'a Octets: OctetsRef,
+    {
+        let question = response.first_question();
+        let (name, rtype) = match kind {
+            ResponseKind::Answer | ResponseKind::NoData => match question {
+                Some(question) => {
+                    (question.qname().to_vec(), question.qtype())
+                }
+                None => (Dname::root_vec(), Rtype::Any),
+            },
+            ResponseKind::Referral | ResponseKind::NxDomain => {
+                (authority_owner(response), Rtype::Any)
+            }
+            ResponseKind::Error => (Dname::root_vec(), Rtype::Any),
+        };
+        BucketKey {
+            network: network(config, addr),
+            kind,
+            name,
+            rtype,
+        }
+    }
+}
+
+//------------ Bucket --------------------------------------------------------
+
+/// A token bucket.
+#[derive(Clone, Debug)]
+struct Bucket {
+    /// The current balance in thousandths of a response.
+    balance: i64,
+
+    /// The time the balance was last updated.
+    last: Instant,
+
+    /// The number of limited responses since the last slip.
+    limited: u32,
+}
+
+impl Bucket {
+    /// Creates a new, full bucket.
+    fn new(rate: u32, now: Instant) -> Self {
+        Bucket {
+            balance: i64::from(rate) * 1000,
+            last: now,
+            limited: 0,
+        }
+    }
+
+    /// Takes a response from the bucket.
+    ///
+    /// Returns whether the response is within the rate.
+    fn take(&mut self, rate: u32, window: u32, now: Instant) -> bool {
+        let rate = i64::from(rate);
+        let elapsed = now.saturating_duration_since(self.last);
+        let elapsed = elapsed.as_millis().min(u128::from(u32::MAX)) as i64;
+        self.balance = (self.balance + elapsed * rate).min(rate * 1000);
+        self.last = now;
+        let allowed = self.balance > 0;
+        self.balance =
+            (self.balance - 1000).max(-i64::from(window) * rate * 1000);
+        allowed
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the network address of a client.
+fn network(config: &RrlConfig, addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(config.ipv4_prefix_len.min(32)))
+                .unwrap_or(0);
+            IpAddr::V4((u32::from(addr) & mask).into())
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(config.ipv6_prefix_len.min(128)))
+                .unwrap_or(0);
+            IpAddr::V6((u128::from(addr) & mask).into())
+        }
+    }
+}
+
+/// Returns the type of the first record in the authority section.
+fn authority_type<Octets>(response: &Message<Octets>) -> Option<Rtype>
+where
+    Octets: AsRef<[u8]>,
+    for<'a> &'a Octets: OctetsRef,
+{
+    let mut authority = response.authority().ok()?;
+    Some(authority.next()?.ok()?.rtype())
+}
+
+/// Returns the owner of the first record in the authority section.
+///
+/// Returns the root name if there is no such record.
+fn authority_owner<Octets>(response: &Message<Octets>) -> Dname<Vec<u8>>
+where
+    Octets: AsRef<[u8]>,
+    for<'a> &'a Octets: OctetsRef,
+{
+    response
+        .authority()
+        .ok()
+        .and_then(|mut authority| authority.next())
+        .and_then(Result::ok)
+        .map(|record| record.owner().to_vec())
+        .unwrap_or_else(Dname::root_vec)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::rdata::{Ns, A};
+    use core::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn answer(qname: &str) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_aa(true);
+        let mut msg = msg.question();
+        msg.push((name(qname), Rtype::A)).unwrap();
+        let mut msg = msg.answer();
+        msg.push((name(qname), 3600, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        msg.into_message()
+    }
+
+    fn referral(qname: &str, zone: &str) -> Message<Vec<u8>> {
+        let mut msg = MessageBuilder::new_vec().question();
+        msg.push((name(qname), Rtype::A)).unwrap();
+        let mut msg = msg.authority();
+        msg.push((name(zone), Class::In, 3600, Ns::new(name("ns.example"))))
+            .unwrap();
+        msg.into_message()
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn response_kind() {
+        assert_eq!(
+            ResponseKind::from_response(&answer("www.example")),
+            ResponseKind::Answer
+        );
+        assert_eq!(
+            ResponseKind::from_response(&referral("www.sub.example", "sub")),
+            ResponseKind::Referral
+        );
+        let mut nx = answer("www.example");
+        nx.header_mut().set_rcode(Rcode::NXDomain);
+        assert_eq!(ResponseKind::from_response(&nx), ResponseKind::NxDomain);
+    }
+
+    #[test]
+    fn limit_and_slip() {
+        let mut rrl = Rrl::new(RrlConfig {
+            responses_per_second: 2,
+            slip: 2,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let resp = answer("www.example");
+        let client = addr("192.0.2.1");
+        let neighbour = addr("192.0.2.200");
+        let mut check = |addr, resp: &Message<Vec<u8>>, now| {
+            rrl.check_at(addr, Transport::Udp, resp, now)
+        };
+
+        // The neighbour shares the client’s bucket.
+        assert_eq!(check(client, &resp, now), RrlAction::Send);
+        assert_eq!(check(neighbour, &resp, now), RrlAction::Send);
+        assert_eq!(check(client, &resp, now), RrlAction::Drop);
+        assert_eq!(check(client, &resp, now), RrlAction::Slip);
+        assert_eq!(check(client, &resp, now), RrlAction::Drop);
+
+        // Different name, different bucket.
+        assert_eq!(
+            check(client, &answer("mail.example"), now),
+            RrlAction::Send
+        );
+        // Different network, different bucket.
+        assert_eq!(check(addr("198.51.100.1"), &resp, now), RrlAction::Send);
+
+        // The bucket is in debt and needs time to recover.
+        let later = now + Duration::from_secs(1);
+        assert_eq!(check(client, &resp, later), RrlAction::Slip);
+        let later = now + Duration::from_secs(5);
+        assert_eq!(check(client, &resp, later), RrlAction::Send);
+
+        // TCP is never limited.
+        for _ in 0..10 {
+            assert_eq!(
+                rrl.check_at(client, Transport::Tcp, &resp, now),
+                RrlAction::Send
+            );
+        }
+
+        rrl.purge_at(now + Duration::from_secs(60));
+        assert!(rrl.is_empty());
+    }
+
+    #[test]
+    fn max_buckets() {
+        let mut rrl = Rrl::new(RrlConfig {
+            max_buckets: 100,
+            ..Default::default()
+        });
+        let resp = answer("www.example");
+        let start = Instant::now();
+        // Every client is in its own network and all buckets stay active.
+        for idx in 0..250u32 {
+            let client = IpAddr::V4((0x0a00_0000 + (idx << 8)).into());
+            let now = start + Duration::from_millis(idx.into());
+            assert_eq!(
+                rrl.check_at(client, Transport::Udp, &resp, now),
+                RrlAction::Send
+            );
+            assert!(rrl.len() <= 100);
+        }
+        assert!(rrl.len() >= 90);
+
+        // The most recent client keeps its bucket and stays limited.
+        let client = addr("10.0.249.1");
+        let now = start + Duration::from_millis(249);
+        for _ in 0..4 {
+            rrl.check_at(client, Transport::Udp, &resp, now);
+        }
+        assert_ne!(
+            rrl.check_at(client, Transport::Udp, &resp, now),
+            RrlAction::Send
+        );
+    }
+
+    #[test]
+    fn exempt_and_slip_response() {
+        let mut rrl = Rrl::new(RrlConfig {
+            responses_per_second: 1,
+            ..Default::default()
+        })
+        .with_exempt("192.0.2.0/24".parse().unwrap());
+        let resp = answer("www.example");
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(
+                rrl.check_at(addr("192.0.2.1"), Transport::Udp, &resp, now),
+                RrlAction::Send
+            );
+        }
+
+        let slip = slip_response(&resp).unwrap();
+        assert!(slip.header().tc());
+        assert!(slip.header().aa());
+        assert_eq!(slip.header_counts().qdcount(), 1);
+        assert_eq!(slip.header_counts().ancount(), 0);
+    }
+}
@@ -0,0 +1,533 @@
+//! Answering requests without intermediate allocations.
+//!
+//! This module defines the [`Service`] trait for the part of a server that
+//! turns a request into a response. A service receives the request as a
+//! [`Request`] that borrows the received datagram or stream message and a
+//! [`MessageBuilder`] atop a [`PooledBuf`] into which it composes the
+//! response directly. Using [`MessageBuilder::start_answer_verbatim`],
+//! the question section of the request is copied into the response as is.
+//! Once the response has been sent, the buffer returns to its pool to be
+//! reused for the next response.
+//!
+//! Buffers are taken from [`BufferPools`], which keeps separate pools for
+//! datagram and stream transports since these have very different maximum
+//! message sizes. The buffers of a pool are allocated once with the pool’s
+//! buffer size and never grow: composing a response beyond this size – or
+//! beyond what the client accepts over UDP – fails with a [`ShortBuf`]
+//! error, which a service can use to produce a truncated response instead.
+//!
+//! [`Service`]: trait.Service.html
+//! [`Request`]: struct.Request.html
+//! [`PooledBuf`]: struct.PooledBuf.html
+//! [`BufferPools`]: struct.BufferPools.html
+//! [`MessageBuilder`]: ../../base/message_builder/struct.MessageBuilder.html
+//! [`MessageBuilder::start_answer_verbatim`]: ../../base/message_builder/struct.MessageBuilder.html#method.start_answer_verbatim
+//! [`ShortBuf`]: ../../base/octets/struct.ShortBuf.html
+
+use super::acl::Transport;
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::octets::{OctetsBuilder, ShortBuf};
+use crate::base::opt::rfc7830::{pad_message, Padding, PaddingPolicy};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+use std::{fmt, mem, ops};
+
+//------------ Service -------------------------------------------------------
+
+/// A type that answers requests.
+///
+/// The trait is implemented for closures with the signature of
+/// [`call`].
+///
+/// [`call`]: #tymethod.call
+pub trait Service {
+    /// Composes the response to a request.
+    ///
+    /// The response is composed via `builder` and the final buffer
+    /// returned. If no response should be sent at all, returns `None`.
+    fn call(
+        &self,
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf>;
+
+    /// Produces the response to a request using a buffer from `pools`.
+    ///
+    /// The message builder passed to [`call`] has its push limit set to
+    /// the maximum response size for the request.
+    ///
+    /// [`call`]: #tymethod.call
+    fn respond(
+        &self,
+        request: &Request,
+        pools: &BufferPools,
+    ) -> Option<PooledBuf> {
+        self.call(request, pools.builder(request).ok()?)
+    }
+}
+
+impl<F> Service for F
+where
+    F: Fn(&Request, MessageBuilder<PooledBuf>) -> Option<PooledBuf>,
+{
+    fn call(
+        &self,
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf> {
+        (*self)(request, builder)
+    }
+}
+
+//------------ Request -------------------------------------------------------
+
+/// A request received by a server.
+///
+/// The request borrows the octets it was received in.
+#[derive(Clone, Copy)]
+pub struct Request<'a> {
+    /// The request message.
+    message: Message<&'a [u8]>,
+
+    /// The address the request was received from.
+    client: SocketAddr,
+
+    /// The transport the request was received over.
+    transport: Transport,
+}
+
+impl<'a> Request<'a> {
+    /// Creates a request from received octets.
+    ///
+    /// Returns an error if the octets are too short to be a message.
+    pub fn new(
+        octets: &'a [u8],
+        client: SocketAddr,
+        transport: Transport,
+    ) -> Result<Self, ShortBuf> {
+        Ok(Request {
+            message: Message::from_octets(octets)?,
+            client,
+            transport,
+        })
+    }
+
+    /// Returns the request message.
+    pub fn message(&self) -> &Message<&'a [u8]> {
+        &self.message
+    }
+
+    /// Returns the address of the client.
+    pub fn client(&self) -> SocketAddr {
+        self.client
+    }
+
+    /// Returns the transport the request was received over.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns the maximum size of a response to this request.
+    ///
+    /// For UDP, this is the payload size advertised in the request’s OPT
+    /// record or 512 octets if there is none. For all other transports, it
+    /// is the maximum size of a message.
+    pub fn max_response_len(&self) -> usize {
+        if self.transport != Transport::Udp {
+            return usize::from(u16::MAX);
+        }
+        let size = match self.message.opt() {
+            Some(opt) => opt.udp_payload_size().max(512),
+            None => 512,
+        };
+        usize::from(size)
+    }
+
+    /// Returns whether the request contains an EDNS padding option.
+    pub fn is_padded(&self) -> bool {
+        match self.message.opt() {
+            Some(opt) => opt.iter::<Padding>().next().is_some(),
+            None => false,
+        }
+    }
+
+    /// Pads a response to this request according to `policy`.
+    ///
+    /// Following RFC 8467, a response is only padded if the request was
+    /// received over an encrypted transport and was padded itself. The
+    /// response needs to end in an OPT record for the padding to be added
+    /// to. Returns whether the response was padded.
+    pub fn pad_response<Target>(
+        &self,
+        response: &mut Target,
+        policy: PaddingPolicy,
+    ) -> bool
+    where
+        Target: OctetsBuilder + AsRef<[u8]> + AsMut<[u8]>,
+    {
+        self.transport.is_encrypted()
+            && self.is_padded()
+            && pad_message(response, policy, self.max_response_len())
+    }
+}
+
+impl<'a> fmt::Debug for Request<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("message", &self.message.as_slice())
+            .field("client", &self.client)
+            .field("transport", &self.transport)
+            .finish()
+    }
+}
+
+//------------ BufferPools ---------------------------------------------------
+
+/// Pools of response buffers for datagram and stream transports.
+///
+/// UDP uses the datagram pool, all other transports use the stream pool.
+#[derive(Clone, Debug)]
+pub struct BufferPools {
+    /// The pool for UDP responses.
+    datagram: BufferPool,
+
+    /// The pool for responses over stream transports.
+    stream: BufferPool,
+}
+
+impl BufferPools {
+    /// The default buffer size for datagram transports.
+    pub const DEFAULT_DATAGRAM_SIZE: usize = 4096;
+
+    /// The default maximum number of idle buffers per pool.
+    pub const DEFAULT_MAX_IDLE: usize = 256;
+
+    /// Creates new pools from the two individual pools.
+    pub fn new(datagram: BufferPool, stream: BufferPool) -> Self {
+        BufferPools { datagram, stream }
+    }
+
+    /// Returns the pool for the given transport.
+    pub fn pool(&self, transport: Transport) -> &BufferPool {
+        match transport {
+            Transport::Udp => &self.datagram,
+            _ => &self.stream,
+        }
+    }
+
+    /// Returns a message builder for the response to `request`.
+    ///
+    /// The builder uses a buffer from the pool for the request’s transport
+    /// and has its push limit set to the request’s maximum response size.
+    pub fn builder(
+        &self,
+        request: &Request,
+    ) -> Result<MessageBuilder<PooledBuf>, ShortBuf> {
+        let mut builder = MessageBuilder::from_target(
+            self.pool(request.transport()).get(),
+        )?;
+        builder.set_push_limit(request.max_response_len());
+        Ok(builder)
+    }
+}
+
+impl Default for BufferPools {
+    fn default() -> Self {
+        BufferPools::new(
+            BufferPool::new(
+                Self::DEFAULT_DATAGRAM_SIZE,
+                Self::DEFAULT_MAX_IDLE,
+            ),
+            BufferPool::new(usize::from(u16::MAX), Self::DEFAULT_MAX_IDLE),
+        )
+    }
+}
+
+//------------ BufferPool ----------------------------------------------------
+
+/// A pool of fixed-size buffers.
+///
+/// Values of this type are cheap to clone and all clones share the same
+/// buffers.
+#[derive(Clone, Debug)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    /// The size of the buffers.
+    size: usize,
+
+    /// The maximum number of idle buffers to keep.
+    max_idle: usize,
+
+    /// The idle buffers.
+    idle: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    /// Creates a new pool for buffers of `size` octets.
+    ///
+    /// At most `max_idle` buffers are kept for reuse. Buffers returned
+    /// while the pool is full are freed.
+    pub fn new(size: usize, max_idle: usize) -> Self {
+        BufferPool {
+            inner: Arc::new(PoolInner {
+                size,
+                max_idle,
+                idle: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Returns the size of the buffers.
+    pub fn buf_size(&self) -> usize {
+        self.inner.size
+    }
+
+    /// Returns the number of idle buffers.
+    pub fn idle(&self) -> usize {
+        self.inner.idle.lock().unwrap().len()
+    }
+
+    /// Takes an empty buffer from the pool.
+    ///
+    /// If there is no idle buffer, a new one is allocated.
+    pub fn get(&self) -> PooledBuf {
+        let buf = self
+            .inner
+            .idle
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(self.inner.size));
+        PooledBuf {
+            buf,
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+//------------ PooledBuf -----------------------------------------------------
+
+/// A buffer taken from a pool.
+///
+/// The buffer is returned to its pool when dropped. It can be used as the
+/// target of a message builder. Since its size is fixed, appending data
+/// beyond it fails.
+pub struct PooledBuf {
+    /// The buffer.
+    buf: Vec<u8>,
+
+    /// The pool to return the buffer to.
+    pool: Arc<PoolInner>,
+}
+
+impl PooledBuf {
+    /// Returns the maximum size of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.pool.size
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        let mut idle = self.pool.idle.lock().unwrap();
+        if idle.len() < self.pool.max_idle {
+            let mut buf = mem::take(&mut self.buf);
+            buf.clear();
+            idle.push(buf);
+        }
+    }
+}
+
+impl ops::Deref for PooledBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl AsRef<[u8]> for PooledBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl AsMut<[u8]> for PooledBuf {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl OctetsBuilder for PooledBuf {
+    type Octets = Self;
+
+    fn append_slice(&mut self, slice: &[u8]) -> Result<(), ShortBuf> {
+        if self.buf.len() + slice.len() > self.pool.size {
+            return Err(ShortBuf);
+        }
+        self.buf.extend_from_slice(slice);
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.buf.truncate(len)
+    }
+
+    fn freeze(self) -> Self {
+        self
+    }
+}
+
+impl fmt::Debug for PooledBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PooledBuf").field(&self.buf).finish()
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use core::str::FromStr;
+
+    fn request(name: &str) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(7);
+        let mut msg = msg.question();
+        msg.push((Dname::<Vec<u8>>::from_str(name).unwrap(), Rtype::A))
+            .unwrap();
+        msg.finish()
+    }
+
+    /// Answers with 40 A records for the query name.
+    fn service(
+        request: &Request,
+        builder: MessageBuilder<PooledBuf>,
+    ) -> Option<PooledBuf> {
+        let msg = request.message();
+        let mut answer =
+            builder.start_answer_verbatim(msg, Rcode::NoError).ok()?;
+        let question = msg.first_question()?;
+        for i in 0..40 {
+            let record = (question.qname(), 60, A::from_octets(192, 0, 2, i));
+            if answer.push(record).is_err() {
+                answer.rewind();
+                answer.header_mut().set_tc(true);
+                break;
+            }
+        }
+        Some(answer.finish())
+    }
+
+    #[test]
+    fn respond() {
+        let pools = BufferPools::default();
+        let client = "192.0.2.1:53".parse().unwrap();
+        let octets = request("Example.COM");
+
+        let request = Request::new(&octets, client, Transport::Tcp).unwrap();
+        let response = service.respond(&request, &pools).unwrap();
+        let message = Message::from_octets(response.as_ref()).unwrap();
+        assert!(message.is_answer(request.message()));
+        assert_eq!(message.header_counts().ancount(), 40);
+        assert!(!message.header().tc());
+        // The question is copied including the case of the name.
+        assert_eq!(&response[12..octets.len()], &octets[12..]);
+        assert_eq!(pools.pool(Transport::Tcp).idle(), 0);
+        drop(response);
+        assert_eq!(pools.pool(Transport::Tcp).idle(), 1);
+
+        let request = Request::new(&octets, client, Transport::Udp).unwrap();
+        let response = service.respond(&request, &pools).unwrap();
+        assert!(response.len() <= 512);
+        let message = Message::from_octets(response.as_ref()).unwrap();
+        assert!(message.header().tc());
+        assert_eq!(message.header_counts().ancount(), 0);
+        assert_eq!(message.header_counts().qdcount(), 1);
+        assert_eq!(pools.pool(Transport::Udp).idle(), 0);
+    }
+
+    #[test]
+    fn pad_response() {
+        use crate::base::opt::rfc7830::PaddingMode;
+
+        fn query(padded: bool) -> Vec<u8> {
+            let mut msg = MessageBuilder::new_vec().question();
+            msg.push((
+                Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+                Rtype::A,
+            ))
+            .unwrap();
+            let mut msg = msg.additional();
+            msg.opt(|opt| {
+                if padded {
+                    Padding::push(opt, 20, PaddingMode::Zero)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+            msg.finish()
+        }
+
+        fn response(request: &Request) -> Vec<u8> {
+            let mut msg = MessageBuilder::new_vec()
+                .start_answer_verbatim(request.message(), Rcode::NoError)
+                .unwrap()
+                .additional();
+            msg.opt(|_| Ok(())).unwrap();
+            msg.finish()
+        }
+
+        let client = "192.0.2.1:53".parse().unwrap();
+        let padded = query(true);
+        let unpadded = query(false);
+
+        let request = Request::new(&padded, client, Transport::Tls).unwrap();
+        assert!(request.is_padded());
+        let mut msg = response(&request);
+        assert!(request.pad_response(&mut msg, PaddingPolicy::RESPONSE));
+        assert_eq!(msg.len(), 468);
+        assert!(Message::from_octets(msg).unwrap().opt().is_some());
+
+        // Responses to unpadded requests aren’t padded.
+        let request =
+            Request::new(&unpadded, client, Transport::Quic).unwrap();
+        assert!(!request.is_padded());
+        let mut msg = response(&request);
+        let len = msg.len();
+        assert!(!request.pad_response(&mut msg, PaddingPolicy::RESPONSE));
+        assert_eq!(msg.len(), len);
+
+        // Neither are responses over unencrypted transports.
+        let request = Request::new(&padded, client, Transport::Tcp).unwrap();
+        let mut msg = response(&request);
+        assert!(!request.pad_response(&mut msg, PaddingPolicy::RESPONSE));
+        assert_eq!(msg.len(), len);
+    }
+
+    #[test]
+    fn pool_limits() {
+        let pool = BufferPool::new(16, 1);
+        let mut first = pool.get();
+        assert!(first.append_slice(&[0; 12]).is_ok());
+        assert_eq!(first.append_slice(&[0; 5]), Err(ShortBuf));
+        assert_eq!(first.len(), 12);
+        let second = pool.get();
+        drop(first);
+        drop(second);
+        assert_eq!(pool.idle(), 1);
+        let reused = pool.get();
+        assert!(reused.is_empty());
+        assert_eq!(reused.capacity(), 16);
+    }
+}
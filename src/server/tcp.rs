@@ -0,0 +1,648 @@
+//! Serving DNS over TCP.
+//!
+//! This module provides [`TcpServer`] which manages TCP connections
+//! following the recommendations of RFC 7766 and RFC 9210:
+//!
+//! * Requests received on a connection are processed concurrently and
+//!   their responses sent as soon as they are available, i.e., possibly
+//!   out of order. The number of requests processed concurrently on a
+//!   single connection is limited. Once the limit is reached, no further
+//!   requests are read until a response has been sent.
+//! * A connection is closed after it has been idle – no requests in flight
+//!   and none received – for a configurable time. If a client includes
+//!   the edns-tcp-keepalive option defined in RFC 7828 in its request,
+//!   this idle timeout is announced to it via the same option in the
+//!   response.
+//! * When the server is shut down, it stops accepting new connections and
+//!   reading new requests while the responses to requests already
+//!   received are still sent. These responses carry a keepalive timeout
+//!   of zero, asking clients to close the connection.
+//!
+//! Requests are answered by a [`StreamService`]. The trait is implemented
+//! by [`Proxy`] if the `resolv` feature is enabled and, for synchronous
+//! services answering into pooled buffers, by [`Pooled`].
+//!
+//! [`TcpServer`]: struct.TcpServer.html
+//! [`StreamService`]: trait.StreamService.html
+//! [`Pooled`]: struct.Pooled.html
+//! [`Proxy`]: ../proxy/struct.Proxy.html
+
+use super::acl::Transport;
+#[cfg(feature = "resolv")]
+use super::proxy::{Proxy, Upstream};
+use super::service::{BufferPools, PooledBuf, Request, Service};
+use crate::base::iana::{OptionCode, Rtype};
+use crate::base::message::Message;
+use crate::base::octets::ParseError;
+use crate::base::opt::TcpKeepalive;
+use bytes::Bytes;
+use core::convert::TryFrom;
+use futures::future::{self, FutureExt};
+use futures::stream::{self, FuturesUnordered, StreamExt};
+use std::boxed::Box;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Duration;
+use std::vec;
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter,
+};
+use tokio::net::TcpListener;
+use tokio::time::{sleep_until, Instant};
+
+//------------ StreamService -------------------------------------------------
+
+/// A type that answers requests received over a stream transport.
+pub trait StreamService {
+    /// The type of a response message.
+    type Response: AsRef<[u8]>;
+
+    /// Produces the response to a request received from `client`.
+    ///
+    /// If no response should be sent, the returned future resolves to
+    /// `None`.
+    fn call(
+        &self,
+        request: Bytes,
+        client: SocketAddr,
+    ) -> ResponseFuture<'_, Self::Response>;
+}
+
+/// The future returned by [`StreamService::call`].
+///
+/// [`StreamService::call`]: trait.StreamService.html#tymethod.call
+pub type ResponseFuture<'a, R> =
+    Pin<Box<dyn Future<Output = Option<R>> + 'a>>;
+
+#[cfg(feature = "resolv")]
+impl<U: Upstream> StreamService for Proxy<U> {
+    type Response = Bytes;
+
+    fn call(
+        &self,
+        request: Bytes,
+        _client: SocketAddr,
+    ) -> ResponseFuture<'_, Bytes> {
+        Box::pin(self.handle(request, Transport::Tcp))
+    }
+}
+
+//------------ Pooled --------------------------------------------------------
+
+/// A stream service for a synchronous service using pooled buffers.
+#[derive(Clone, Debug)]
+pub struct Pooled<S> {
+    /// The service.
+    service: S,
+
+    /// The pools for the response buffers.
+    pools: BufferPools,
+}
+
+impl<S> Pooled<S> {
+    /// Creates a new stream service from a service and buffer pools.
+    pub fn new(service: S, pools: BufferPools) -> Self {
+        Pooled { service, pools }
+    }
+
+    /// Returns a reference to the service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+}
+
+impl<S: Service> StreamService for Pooled<S> {
+    type Response = PooledBuf;
+
+    fn call(
+        &self,
+        request: Bytes,
+        client: SocketAddr,
+    ) -> ResponseFuture<'_, PooledBuf> {
+        let response = Request::new(request.as_ref(), client, Transport::Tcp)
+            .ok()
+            .and_then(|request| self.service.respond(&request, &self.pools));
+        Box::pin(future::ready(response))
+    }
+}
+
+//------------ TcpConfig -----------------------------------------------------
+
+/// Configuration for the handling of TCP connections.
+#[derive(Clone, Copy, Debug)]
+pub struct TcpConfig {
+    /// The maximum number of requests processed concurrently.
+    max_in_flight: usize,
+
+    /// The time after which an idle connection is closed.
+    idle_timeout: Duration,
+
+    /// Whether to answer the edns-tcp-keepalive option.
+    keepalive: bool,
+}
+
+impl TcpConfig {
+    /// The default maximum number of concurrent requests per connection.
+    pub const DEFAULT_MAX_IN_FLIGHT: usize = 16;
+
+    /// The default idle timeout.
+    pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Creates the default configuration.
+    pub fn new() -> Self {
+        TcpConfig {
+            max_in_flight: Self::DEFAULT_MAX_IN_FLIGHT,
+            idle_timeout: Self::DEFAULT_IDLE_TIMEOUT,
+            keepalive: true,
+        }
+    }
+
+    /// Returns the maximum number of concurrent requests per connection.
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// Sets the maximum number of concurrent requests per connection.
+    ///
+    /// The value is raised to at least one.
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.max_in_flight = max_in_flight.max(1)
+    }
+
+    /// Returns the idle timeout.
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Sets the idle timeout.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = idle_timeout
+    }
+
+    /// Returns whether the edns-tcp-keepalive option is answered.
+    pub fn keepalive(&self) -> bool {
+        self.keepalive
+    }
+
+    /// Sets whether the edns-tcp-keepalive option is answered.
+    pub fn set_keepalive(&mut self, keepalive: bool) {
+        self.keepalive = keepalive
+    }
+
+    /// Returns the idle timeout in units of 100 milliseconds.
+    fn keepalive_timeout(&self) -> u16 {
+        u16::try_from(self.idle_timeout.as_millis() / 100).unwrap_or(u16::MAX)
+    }
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ TcpServer -----------------------------------------------------
+
+/// A server for DNS over TCP.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Clone, Debug)]
+pub struct TcpServer<S> {
+    /// The service answering requests.
+    service: S,
+
+    /// The configuration.
+    config: TcpConfig,
+}
+
+impl<S> TcpServer<S> {
+    /// Creates a new server using the default configuration.
+    pub fn new(service: S) -> Self {
+        TcpServer {
+            service,
+            config: TcpConfig::default(),
+        }
+    }
+
+    /// Returns a reference to the service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> TcpConfig {
+        self.config
+    }
+
+    /// Sets the configuration.
+    pub fn set_config(&mut self, config: TcpConfig) {
+        self.config = config
+    }
+}
+
+impl<S: StreamService> TcpServer<S> {
+    /// Accepts and serves connections until `shutdown` resolves.
+    ///
+    /// Once `shutdown` has resolved, no new connections are accepted and
+    /// the existing connections are shut down gracefully. The function
+    /// returns when all connections have been closed.
+    ///
+    /// Returns an error if accepting a connection fails.
+    pub async fn serve<F>(
+        &self,
+        listener: &TcpListener,
+        shutdown: F,
+    ) -> Result<(), io::Error>
+    where
+        F: Future<Output = ()>,
+    {
+        let shutdown = shutdown.boxed_local().shared();
+        let mut connections = FuturesUnordered::new();
+        let mut stopping = false;
+        loop {
+            tokio::select! {
+                res = listener.accept(), if !stopping => {
+                    let (sock, client) = res?;
+                    connections.push(
+                        self.serve_connection(sock, client, shutdown.clone())
+                    );
+                }
+                Some(_) = connections.next(), if !connections.is_empty() => {}
+                _ = shutdown.clone(), if !stopping => stopping = true,
+                else => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Serves a single connection until it is closed or `shutdown` resolves.
+    ///
+    /// Returns an error if reading from or writing to the connection
+    /// fails.
+    pub async fn serve_connection<Sock, F>(
+        &self,
+        sock: Sock,
+        client: SocketAddr,
+        shutdown: F,
+    ) -> Result<(), io::Error>
+    where
+        Sock: AsyncRead + AsyncWrite,
+        F: Future<Output = ()>,
+    {
+        let (read, write) = tokio::io::split(sock);
+        let mut write = BufWriter::new(write);
+        let requests = stream::unfold(read, |mut read| async move {
+            match read_message(&mut read).await {
+                Ok(Some(message)) => Some((Ok(message), read)),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), read)),
+            }
+        });
+        futures::pin_mut!(requests);
+        let shutdown = shutdown.fuse();
+        futures::pin_mut!(shutdown);
+        let mut pending = FuturesUnordered::new();
+        let mut reading = true;
+        let mut stopping = false;
+        let mut deadline = Instant::now() + self.config.idle_timeout;
+        while reading || !pending.is_empty() {
+            let can_read =
+                reading && pending.len() < self.config.max_in_flight;
+            tokio::select! {
+                request = requests.next(), if can_read => {
+                    match request {
+                        Some(request) => {
+                            let request = request?;
+                            let keepalive = self.config.keepalive
+                                && has_keepalive(&request);
+                            pending.push(
+                                self.service
+                                    .call(request, client)
+                                    .map(move |res| (res, keepalive)),
+                            );
+                        }
+                        None => reading = false,
+                    }
+                }
+                Some((response, keepalive)) = pending.next(),
+                    if !pending.is_empty() =>
+                {
+                    if let Some(response) = response {
+                        let timeout = if !keepalive {
+                            None
+                        } else if stopping {
+                            Some(0)
+                        } else {
+                            Some(self.config.keepalive_timeout())
+                        };
+                        write_response(&mut write, response.as_ref(), timeout)
+                            .await?;
+                    }
+                    deadline = Instant::now() + self.config.idle_timeout;
+                }
+                _ = sleep_until(deadline), if pending.is_empty() => {
+                    reading = false;
+                }
+                _ = &mut shutdown, if !stopping => {
+                    stopping = true;
+                    reading = false;
+                }
+            }
+        }
+        write.shutdown().await
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// Returns whether a request contains the edns-tcp-keepalive option.
+fn has_keepalive(request: &Bytes) -> bool {
+    let msg = match Message::from_octets(request.as_ref()) {
+        Ok(msg) => msg,
+        Err(_) => return false,
+    };
+    let opt = match msg.opt() {
+        Some(opt) => opt,
+        None => return false,
+    };
+    let res = opt.as_opt().iter::<TcpKeepalive>().next().is_some();
+    res
+}
+
+/// Returns the position of the RDLEN field of the OPT record.
+///
+/// Returns `None` unless the OPT record is the last record of the message,
+/// since only then can options be appended to it.
+fn opt_rdlen_pos(message: &[u8]) -> Result<Option<usize>, ParseError> {
+    let mut res = None;
+    Message::from_octets(message)
+        .map_err(|_| ParseError::ShortInput)?
+        .for_each_ttl_field(|rtype, pos| {
+            res = if rtype == Rtype::Opt {
+                Some(pos + 4)
+            } else {
+                None
+            };
+        })?;
+    Ok(res)
+}
+
+/// Writes a length-prefixed response to a stream.
+///
+/// If `keepalive` is given and the response has an OPT record as its last
+/// record, an edns-tcp-keepalive option with this value is appended to the
+/// record.
+async fn write_response<W>(
+    write: &mut W,
+    response: &[u8],
+    keepalive: Option<u16>,
+) -> Result<(), io::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let opt_pos = match keepalive {
+        Some(_) if response.len() + 6 <= usize::from(u16::MAX) => {
+            opt_rdlen_pos(response).ok().flatten()
+        }
+        _ => None,
+    };
+    match (opt_pos, keepalive) {
+        (Some(pos), Some(timeout)) => {
+            let rdlen =
+                u16::from_be_bytes([response[pos], response[pos + 1]]);
+            let len = (response.len() + 6) as u16;
+            write.write_all(&len.to_be_bytes()).await?;
+            write.write_all(&response[..pos]).await?;
+            write.write_all(&(rdlen + 6).to_be_bytes()).await?;
+            write.write_all(&response[pos + 2..]).await?;
+            write
+                .write_all(&OptionCode::TcpKeepalive.to_int().to_be_bytes())
+                .await?;
+            write.write_all(&2u16.to_be_bytes()).await?;
+            write.write_all(&timeout.to_be_bytes()).await?;
+        }
+        _ => {
+            // Responses are created from messages and thus always fit.
+            let len = response.len() as u16;
+            write.write_all(&len.to_be_bytes()).await?;
+            write.write_all(response).await?;
+        }
+    }
+    write.flush().await
+}
+
+/// Reads a length-prefixed message from a stream.
+///
+/// Returns `Ok(None)` if the stream ended before a new message started.
+pub(super) async fn read_message<R>(
+    read: &mut R,
+) -> Result<Option<Bytes>, io::Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len = [0u8; 2];
+    match read.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+            return Ok(None)
+        }
+        Err(err) => return Err(err),
+    }
+    let mut buf = vec![0u8; usize::from(u16::from_be_bytes(len))];
+    read.read_exact(&mut buf).await?;
+    Ok(Some(buf.into()))
+}
+
+/// Writes a length-prefixed message to a stream.
+pub(super) async fn write_message<W>(
+    write: &mut W,
+    message: &[u8],
+) -> Result<(), io::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    // Responses are created from messages and thus always fit.
+    let len = message.len() as u16;
+    write.write_all(&len.to_be_bytes()).await?;
+    write.write_all(message).await?;
+    write.flush().await
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rcode;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::utils::testing::run;
+    use core::str::FromStr;
+    use futures::channel::oneshot;
+    use std::vec::Vec;
+    use tokio::io::{duplex, AsyncReadExt, DuplexStream};
+    use tokio::time::sleep;
+
+    fn client() -> SocketAddr {
+        "192.0.2.1:53".parse().unwrap()
+    }
+
+    fn query(id: u16, keepalive: bool) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_id(id);
+        let mut msg = msg.question();
+        msg.push((
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Rtype::A,
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        if keepalive {
+            msg.opt(|opt| TcpKeepalive::push(opt, 0)).unwrap();
+        }
+        msg.finish()
+    }
+
+    /// Answers with an OPT record, delaying the answer for ID 1.
+    struct Delayed;
+
+    impl StreamService for Delayed {
+        type Response = Vec<u8>;
+
+        fn call(
+            &self,
+            request: Bytes,
+            _client: SocketAddr,
+        ) -> ResponseFuture<'_, Vec<u8>> {
+            Box::pin(async move {
+                let request = Message::from_octets(request).unwrap();
+                if request.header().id() == 1 {
+                    sleep(Duration::from_millis(100)).await;
+                }
+                let mut answer = MessageBuilder::new_vec()
+                    .start_answer(&request, Rcode::NoError)
+                    .unwrap()
+                    .additional();
+                answer.opt(|_| Ok(())).unwrap();
+                Some(answer.finish())
+            })
+        }
+    }
+
+    async fn send(sock: &mut DuplexStream, id: u16, keepalive: bool) {
+        let query = query(id, keepalive);
+        sock.write_all(&(query.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        sock.write_all(&query).await.unwrap();
+    }
+
+    /// Reads a response and returns its ID and keepalive timeout.
+    async fn recv(sock: &mut DuplexStream) -> Option<(u16, Option<u16>)> {
+        let response = read_message(sock).await.unwrap()?;
+        let response = Message::from_octets(response).unwrap();
+        let keepalive = response
+            .opt()
+            .unwrap()
+            .as_opt()
+            .iter::<TcpKeepalive>()
+            .next()
+            .map(|opt| opt.unwrap().timeout());
+        Some((response.header().id(), keepalive))
+    }
+
+    #[test]
+    fn out_of_order() {
+        run(async {
+            let mut server = TcpServer::new(Delayed);
+            let (mut sock, server_sock) = duplex(4096);
+            let client_side = async {
+                send(&mut sock, 1, true).await;
+                send(&mut sock, 2, false).await;
+                assert_eq!(recv(&mut sock).await, Some((2, None)));
+                assert_eq!(recv(&mut sock).await, Some((1, Some(100))));
+                sock.shutdown().await.unwrap();
+                assert_eq!(recv(&mut sock).await, None);
+            };
+            let (res, _) = tokio::join!(
+                server.serve_connection(
+                    server_sock,
+                    client(),
+                    future::pending()
+                ),
+                client_side
+            );
+            res.unwrap();
+
+            // With only one request in flight, responses are in order.
+            let mut config = TcpConfig::new();
+            config.set_max_in_flight(1);
+            server.set_config(config);
+            let (mut sock, server_sock) = duplex(4096);
+            let client_side = async {
+                send(&mut sock, 1, false).await;
+                send(&mut sock, 2, false).await;
+                assert_eq!(recv(&mut sock).await, Some((1, None)));
+                assert_eq!(recv(&mut sock).await, Some((2, None)));
+                sock.shutdown().await.unwrap();
+            };
+            let (res, _) = tokio::join!(
+                server.serve_connection(
+                    server_sock,
+                    client(),
+                    future::pending()
+                ),
+                client_side
+            );
+            res.unwrap();
+        })
+    }
+
+    #[test]
+    fn idle_timeout() {
+        run(async {
+            let mut server = TcpServer::new(Delayed);
+            let mut config = TcpConfig::new();
+            config.set_idle_timeout(Duration::from_millis(50));
+            server.set_config(config);
+            let (mut sock, server_sock) = duplex(4096);
+            server
+                .serve_connection(server_sock, client(), future::pending())
+                .await
+                .unwrap();
+            let mut buf = Vec::new();
+            assert_eq!(sock.read_to_end(&mut buf).await.unwrap(), 0);
+        })
+    }
+
+    #[test]
+    fn graceful_shutdown() {
+        run(async {
+            let server = TcpServer::new(Delayed);
+            let (tx, rx) = oneshot::channel::<()>();
+            let (mut sock, server_sock) = duplex(4096);
+            let client_side = async {
+                send(&mut sock, 1, true).await;
+                sleep(Duration::from_millis(20)).await;
+                tx.send(()).unwrap();
+                // The pending response is still sent, asking the client to
+                // close the connection.
+                assert_eq!(recv(&mut sock).await, Some((1, Some(0))));
+                assert_eq!(recv(&mut sock).await, None);
+            };
+            let (res, _) = tokio::join!(
+                server.serve_connection(
+                    server_sock,
+                    client(),
+                    rx.map(|_| ())
+                ),
+                client_side
+            );
+            res.unwrap();
+        })
+    }
+}
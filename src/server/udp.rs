@@ -0,0 +1,540 @@
+//! Batched UDP I/O for servers.
+//!
+//! A server receiving and sending one datagram per system call spends
+//! most of its time in the kernel transition rather than on the requests.
+//! This module provides functions that move a whole batch of datagrams
+//! at once: [`recv_batch`] fills a [`RecvBatch`] with as many datagrams as
+//! are available, and [`send_batch`] sends a list of datagrams. On Linux,
+//! these use the `recvmmsg` and `sendmmsg` system calls. On other systems,
+//! they fall back to receiving and sending datagrams one by one without
+//! waiting in between.
+//!
+//! To spread the load over several CPUs, [`bind_sharded`] creates several
+//! sockets bound to the same address with the `SO_REUSEPORT` option. The
+//! kernel then distributes incoming datagrams between these sockets based
+//! on the client address and port, so each socket can be served from its
+//! own thread.
+//!
+//! Generic segmentation and receive offload (GSO and GRO) are not used:
+//! they only combine datagrams of the same flow whereas the datagrams of a
+//! DNS server typically all belong to different clients.
+//!
+//! Batching on Linux and sharding require the `libc` crate, which is
+//! enabled by the `server` feature.
+//!
+//! [`recv_batch`]: fn.recv_batch.html
+//! [`send_batch`]: fn.send_batch.html
+//! [`RecvBatch`]: struct.RecvBatch.html
+//! [`bind_sharded`]: fn.bind_sharded.html
+
+use bytes::Bytes;
+use std::io;
+use std::net::SocketAddr;
+use std::vec::Vec;
+use tokio::net::UdpSocket;
+
+//------------ RecvBatch -----------------------------------------------------
+
+/// A set of buffers for receiving a batch of datagrams.
+#[derive(Clone, Debug)]
+pub struct RecvBatch {
+    /// The buffers, one per datagram.
+    bufs: Vec<Vec<u8>>,
+
+    /// The received datagrams as the length and source address.
+    received: Vec<(usize, SocketAddr)>,
+}
+
+impl RecvBatch {
+    /// Creates buffers for up to `count` datagrams of `size` octets each.
+    ///
+    /// Datagrams longer than `size` are dropped when received.
+    pub fn new(count: usize, size: usize) -> Self {
+        RecvBatch {
+            bufs: (0..count.max(1)).map(|_| vec![0; size]).collect(),
+            received: Vec::with_capacity(count.max(1)),
+        }
+    }
+
+    /// Returns the maximum number of datagrams in a batch.
+    pub fn capacity(&self) -> usize {
+        self.bufs.len()
+    }
+
+    /// Returns the number of datagrams received.
+    pub fn len(&self) -> usize {
+        self.received.len()
+    }
+
+    /// Returns whether no datagrams have been received.
+    pub fn is_empty(&self) -> bool {
+        self.received.is_empty()
+    }
+
+    /// Returns an iterator over the datagrams and their source addresses.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], SocketAddr)> + '_ {
+        self.received
+            .iter()
+            .zip(&self.bufs)
+            .map(|(&(len, addr), buf)| (&buf[..len], addr))
+    }
+}
+
+//------------ recv_batch and send_batch -------------------------------------
+
+/// Receives a batch of datagrams.
+///
+/// Waits until at least one datagram is available and then receives as
+/// many as are available and fit into `batch`, replacing its previous
+/// content. Returns the number of datagrams received which is never zero:
+/// datagrams that are dropped, such as those that don’t fit into the
+/// buffers, do not count and the function keeps waiting if all of them
+/// were dropped.
+///
+/// The function is cancel safe: if the returned future is dropped before
+/// it completes, no datagrams have been received.
+pub async fn recv_batch(
+    sock: &UdpSocket,
+    batch: &mut RecvBatch,
+) -> Result<usize, io::Error> {
+    batch.received.clear();
+    loop {
+        sock.readable().await?;
+        match sys::recv(sock, batch) {
+            Ok(0) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                // Tokio only clears the readiness of the socket if one of
+                // its own methods runs into `WouldBlock`.
+                match sock.try_recv_from(&mut batch.bufs[0]) {
+                    Ok((len, addr)) => {
+                        batch.received.push((len, addr));
+                        return Ok(1);
+                    }
+                    Err(ref err)
+                        if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Sends a batch of datagrams.
+///
+/// Returns once all datagrams have been handed to the kernel.
+pub async fn send_batch(
+    sock: &UdpSocket,
+    datagrams: &[(Bytes, SocketAddr)],
+) -> Result<(), io::Error> {
+    let mut sent = 0;
+    while sent < datagrams.len() {
+        sock.writable().await?;
+        match sys::send(sock, &datagrams[sent..]) {
+            Ok(count) => sent += count,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                // See recv_batch for why this goes through Tokio.
+                let (data, addr) = &datagrams[sent];
+                match sock.try_send_to(data, *addr) {
+                    Ok(_) => sent += 1,
+                    Err(ref err)
+                        if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+//------------ bind_sharded --------------------------------------------------
+
+/// Binds `count` sockets to `addr` for sharding requests between them.
+///
+/// All sockets have the `SO_REUSEPORT` option set. If `addr` has port
+/// zero, the port chosen for the first socket is used for all others.
+///
+/// Sharding is only available on Unix systems. Elsewhere, a single socket
+/// is returned if `count` is one and an error otherwise.
+///
+/// The function must be called from within a Tokio runtime.
+pub fn bind_sharded(
+    addr: SocketAddr,
+    count: usize,
+) -> Result<Vec<UdpSocket>, io::Error> {
+    let mut res = Vec::with_capacity(count);
+    let mut addr = addr;
+    for _ in 0..count.max(1) {
+        let sock = sys::bind_reuseport(addr)?;
+        sock.set_nonblocking(true)?;
+        addr = sock.local_addr()?;
+        res.push(UdpSocket::from_std(sock)?);
+    }
+    Ok(res)
+}
+
+//------------ sys -----------------------------------------------------------
+
+/// The implementation using `recvmmsg` and `sendmmsg`.
+#[cfg(all(target_os = "linux", feature = "libc"))]
+mod sys {
+    use super::RecvBatch;
+    use bytes::Bytes;
+    use std::net::{
+        Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6,
+    };
+    use std::os::unix::io::AsRawFd;
+    use std::vec::Vec;
+    use std::{io, mem, ptr};
+
+    pub use super::unix::bind_reuseport;
+
+    pub fn recv(
+        sock: &impl AsRawFd,
+        batch: &mut RecvBatch,
+    ) -> Result<usize, io::Error> {
+        let count = batch.bufs.len();
+        let mut addrs: Vec<libc::sockaddr_storage> =
+            vec![unsafe { mem::zeroed() }; count];
+        let mut iovecs: Vec<libc::iovec> = batch
+            .bufs
+            .iter_mut()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_mut_ptr() as *mut _,
+                iov_len: buf.len(),
+            })
+            .collect();
+        let mut msgs: Vec<libc::mmsghdr> = iovecs
+            .iter_mut()
+            .zip(addrs.iter_mut())
+            .map(|(iovec, addr)| {
+                let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+                msg.msg_hdr.msg_name = addr as *mut _ as *mut _;
+                msg.msg_hdr.msg_namelen =
+                    mem::size_of::<libc::sockaddr_storage>() as _;
+                msg.msg_hdr.msg_iov = iovec;
+                msg.msg_hdr.msg_iovlen = 1;
+                msg
+            })
+            .collect();
+        let res = unsafe {
+            libc::recvmmsg(
+                sock.as_raw_fd(),
+                msgs.as_mut_ptr(),
+                count as _,
+                libc::MSG_DONTWAIT as _,
+                ptr::null_mut(),
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Datagrams that were truncated or have an unexpected source are
+        // dropped. The buffers of the others are moved to the front so
+        // that they line up with their entries in `received`.
+        for idx in 0..res as usize {
+            let msg = &msgs[idx];
+            if msg.msg_hdr.msg_flags & libc::MSG_TRUNC != 0 {
+                continue;
+            }
+            if let Some(addr) = from_sockaddr(&addrs[idx]) {
+                batch.bufs.swap(batch.received.len(), idx);
+                batch.received.push((msg.msg_len as usize, addr));
+            }
+        }
+        Ok(batch.received.len())
+    }
+
+    pub fn send(
+        sock: &impl AsRawFd,
+        datagrams: &[(Bytes, SocketAddr)],
+    ) -> Result<usize, io::Error> {
+        let count = datagrams.len().min(libc::UIO_MAXIOV as usize);
+        let datagrams = &datagrams[..count];
+        let mut addrs: Vec<(libc::sockaddr_storage, libc::socklen_t)> =
+            datagrams
+                .iter()
+                .map(|(_, addr)| to_sockaddr(addr))
+                .collect();
+        let mut iovecs: Vec<libc::iovec> = datagrams
+            .iter()
+            .map(|(data, _)| libc::iovec {
+                iov_base: data.as_ptr() as *mut _,
+                iov_len: data.len(),
+            })
+            .collect();
+        let mut msgs: Vec<libc::mmsghdr> = iovecs
+            .iter_mut()
+            .zip(addrs.iter_mut())
+            .map(|(iovec, (addr, len))| {
+                let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+                msg.msg_hdr.msg_name = addr as *mut _ as *mut _;
+                msg.msg_hdr.msg_namelen = *len;
+                msg.msg_hdr.msg_iov = iovec;
+                msg.msg_hdr.msg_iovlen = 1;
+                msg
+            })
+            .collect();
+        let res = unsafe {
+            libc::sendmmsg(
+                sock.as_raw_fd(),
+                msgs.as_mut_ptr(),
+                count as _,
+                libc::MSG_DONTWAIT as _,
+            )
+        };
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    fn from_sockaddr(addr: &libc::sockaddr_storage) -> Option<SocketAddr> {
+        match i32::from(addr.ss_family) {
+            libc::AF_INET => {
+                let addr = unsafe {
+                    &*(addr as *const _ as *const libc::sockaddr_in)
+                };
+                Some(SocketAddr::V4(SocketAddrV4::new(
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                    u16::from_be(addr.sin_port),
+                )))
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe {
+                    &*(addr as *const _ as *const libc::sockaddr_in6)
+                };
+                Some(SocketAddr::V6(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_sockaddr(
+        addr: &SocketAddr,
+    ) -> (libc::sockaddr_storage, libc::socklen_t) {
+        super::unix::to_sockaddr(addr)
+    }
+}
+
+/// The fallback implementation sending one datagram at a time.
+#[cfg(not(all(target_os = "linux", feature = "libc")))]
+mod sys {
+    use super::RecvBatch;
+    use bytes::Bytes;
+    use std::io;
+    use std::net::SocketAddr;
+    use tokio::net::UdpSocket;
+
+    #[cfg(all(unix, feature = "libc"))]
+    pub use super::unix::bind_reuseport;
+
+    pub fn recv(
+        sock: &UdpSocket,
+        batch: &mut RecvBatch,
+    ) -> Result<usize, io::Error> {
+        while batch.received.len() < batch.bufs.len() {
+            let idx = batch.received.len();
+            match sock.try_recv_from(&mut batch.bufs[idx]) {
+                Ok((len, addr)) => batch.received.push((len, addr)),
+                Err(err) => {
+                    if batch.received.is_empty() {
+                        return Err(err);
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(batch.received.len())
+    }
+
+    pub fn send(
+        sock: &UdpSocket,
+        datagrams: &[(Bytes, SocketAddr)],
+    ) -> Result<usize, io::Error> {
+        let mut sent = 0;
+        for (data, addr) in datagrams {
+            match sock.try_send_to(data, *addr) {
+                Ok(_) => sent += 1,
+                Err(err) => {
+                    if sent == 0 {
+                        return Err(err);
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    #[cfg(not(all(unix, feature = "libc")))]
+    pub fn bind_reuseport(
+        addr: SocketAddr,
+    ) -> Result<std::net::UdpSocket, io::Error> {
+        std::net::UdpSocket::bind(addr)
+    }
+}
+
+//------------ unix ----------------------------------------------------------
+
+/// Creating sockets with `SO_REUSEPORT`.
+#[cfg(all(unix, feature = "libc"))]
+mod unix {
+    use std::net::SocketAddr;
+    use std::os::unix::io::FromRawFd;
+    use std::{io, mem};
+
+    pub fn bind_reuseport(
+        addr: SocketAddr,
+    ) -> Result<std::net::UdpSocket, io::Error> {
+        let family = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        let fd = unsafe { libc::socket(family, libc::SOCK_DGRAM, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Hand the descriptor to a socket right away so it is closed on
+        // error.
+        let sock = unsafe { std::net::UdpSocket::from_raw_fd(fd) };
+        let one: libc::c_int = 1;
+        let res = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_REUSEPORT,
+                &one as *const _ as *const _,
+                mem::size_of::<libc::c_int>() as _,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let (addr, len) = to_sockaddr(&addr);
+        let res =
+            unsafe { libc::bind(fd, &addr as *const _ as *const _, len) };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(sock)
+    }
+
+    pub fn to_sockaddr(
+        addr: &SocketAddr,
+    ) -> (libc::sockaddr_storage, libc::socklen_t) {
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let len = match *addr {
+            SocketAddr::V4(ref addr) => {
+                let sin = unsafe {
+                    &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in)
+                };
+                sin.sin_family = libc::AF_INET as _;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(ref addr) => {
+                let sin6 = unsafe {
+                    &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6)
+                };
+                sin6.sin6_family = libc::AF_INET6 as _;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_scope_id = addr.scope_id();
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+        (storage, len as libc::socklen_t)
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::testing::run;
+
+    #[test]
+    fn batch_round_trip() {
+        run(async {
+            let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let server_addr = server.local_addr().unwrap();
+            let client_addr = client.local_addr().unwrap();
+
+            let datagrams = (0..5u8)
+                .map(|i| (Bytes::from(vec![i; 10 + i as usize]), server_addr))
+                .collect::<Vec<_>>();
+            send_batch(&client, &datagrams).await.unwrap();
+
+            let mut batch = RecvBatch::new(8, 512);
+            let mut received = Vec::new();
+            while received.len() < 5 {
+                recv_batch(&server, &mut batch).await.unwrap();
+                assert!(!batch.is_empty());
+                for (data, addr) in batch.iter() {
+                    assert_eq!(addr, client_addr);
+                    received.push(data.to_vec());
+                }
+            }
+            received.sort();
+            assert_eq!(
+                received,
+                datagrams
+                    .iter()
+                    .map(|(data, _)| data.to_vec())
+                    .collect::<Vec<_>>()
+            );
+        })
+    }
+
+    #[test]
+    fn drop_oversized() {
+        run(async {
+            let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let server_addr = server.local_addr().unwrap();
+            let datagrams = [
+                (Bytes::from(vec![1; 100]), server_addr),
+                (Bytes::from(vec![2; 10]), server_addr),
+            ];
+            send_batch(&client, &datagrams).await.unwrap();
+            // With room for a single datagram, the oversized one is
+            // received and dropped first. This must not end the receive.
+            let mut batch = RecvBatch::new(1, 50);
+            assert_eq!(recv_batch(&server, &mut batch).await.unwrap(), 1);
+            let received = batch
+                .iter()
+                .map(|(data, _)| data.to_vec())
+                .collect::<Vec<_>>();
+            if cfg!(all(target_os = "linux", feature = "libc")) {
+                assert_eq!(received, [vec![2; 10]]);
+            }
+        })
+    }
+
+    #[cfg(all(unix, feature = "libc"))]
+    #[test]
+    fn sharded() {
+        run(async {
+            let socks =
+                bind_sharded("127.0.0.1:0".parse().unwrap(), 3).unwrap();
+            assert_eq!(socks.len(), 3);
+            let addr = socks[0].local_addr().unwrap();
+            assert_ne!(addr.port(), 0);
+            assert!(socks
+                .iter()
+                .all(|sock| sock.local_addr().unwrap() == addr));
+        })
+    }
+}
@@ -0,0 +1,446 @@
+//! Managing the zones served by a server.
+//!
+//! An authoritative server typically serves many zones. The [`ZoneSet`]
+//! type in this module keeps track of them. Each zone is identified by its
+//! apex name and class and kept in a [`ZoneEntry`] together with its
+//! [metadata][`ZoneMeta`]: whether the server is primary or secondary for
+//! the zone, which clients may transfer or update it, and which TSIG keys
+//! are used with it.
+//!
+//! The zone set is generic over the type of the zone content. This can be
+//! anything from the records of a zone file to a complete database. To
+//! answer a query, [`ZoneSet::find`] selects the zone with the longest
+//! apex that the query name is at or below.
+//!
+//! All methods take `&self`, so a zone set can be shared between request
+//! handlers via an `Arc`. Zones can be added and removed at any time and
+//! the content of an individual zone can be replaced via
+//! [`ZoneEntry::reload`]. Request handlers hold on to an `Arc` of the
+//! content they are working with, so a reload never affects a request
+//! that is already being answered.
+//!
+//! [`ZoneSet`]: struct.ZoneSet.html
+//! [`ZoneSet::find`]: struct.ZoneSet.html#method.find
+//! [`ZoneEntry`]: struct.ZoneEntry.html
+//! [`ZoneEntry::reload`]: struct.ZoneEntry.html#method.reload
+//! [`ZoneMeta`]: struct.ZoneMeta.html
+
+use super::acl::Acl;
+use crate::base::iana::Class;
+use crate::base::name::{Dname, ToDname};
+use std::collections::HashMap;
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::vec::Vec;
+
+//------------ ZoneSet -------------------------------------------------------
+
+/// A set of zones indexed by their apex.
+///
+/// See the [module documentation] for details.
+///
+/// [module documentation]: index.html
+#[derive(Debug)]
+pub struct ZoneSet<Z> {
+    /// The zones by class and lowercase apex name in wire format.
+    zones: RwLock<HashMap<Class, ClassZones<Z>>>,
+}
+
+/// The zones of a single class by lowercase apex name in wire format.
+type ClassZones<Z> = HashMap<Vec<u8>, Arc<ZoneEntry<Z>>>;
+
+impl<Z> ZoneSet<Z> {
+    /// Creates a new, empty zone set.
+    pub fn new() -> Self {
+        ZoneSet {
+            zones: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the number of zones in the set.
+    pub fn len(&self) -> usize {
+        self.zones.read().unwrap().values().map(HashMap::len).sum()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a zone to the set.
+    ///
+    /// If there already is a zone with the same apex and class, it is
+    /// replaced and returned.
+    pub fn insert<N: ToDname>(
+        &self,
+        apex: &N,
+        class: Class,
+        meta: ZoneMeta,
+        content: Z,
+    ) -> Option<Arc<ZoneEntry<Z>>> {
+        let entry = Arc::new(ZoneEntry {
+            apex: apex.to_vec(),
+            class,
+            meta,
+            content: RwLock::new((Arc::new(content), 0)),
+        });
+        self.zones
+            .write()
+            .unwrap()
+            .entry(class)
+            .or_default()
+            .insert(key(apex), entry)
+    }
+
+    /// Removes the zone with the given apex and class.
+    ///
+    /// Returns the zone if it was present.
+    pub fn remove<N: ToDname>(
+        &self,
+        apex: &N,
+        class: Class,
+    ) -> Option<Arc<ZoneEntry<Z>>> {
+        let mut zones = self.zones.write().unwrap();
+        let class_zones = zones.get_mut(&class)?;
+        let res = class_zones.remove(&key(apex));
+        if class_zones.is_empty() {
+            zones.remove(&class);
+        }
+        res
+    }
+
+    /// Returns the zone with exactly the given apex and class.
+    pub fn get<N: ToDname>(
+        &self,
+        apex: &N,
+        class: Class,
+    ) -> Option<Arc<ZoneEntry<Z>>> {
+        self.zones
+            .read()
+            .unwrap()
+            .get(&class)?
+            .get(&key(apex))
+            .cloned()
+    }
+
+    /// Returns the zone responsible for a query name.
+    ///
+    /// This is the zone of the given class with the longest apex that
+    /// `qname` is equal to or a subdomain of.
+    pub fn find<N: ToDname>(
+        &self,
+        qname: &N,
+        class: Class,
+    ) -> Option<Arc<ZoneEntry<Z>>> {
+        let zones = self.zones.read().unwrap();
+        let class_zones = zones.get(&class)?;
+        let key = key(qname);
+        let mut pos = 0;
+        loop {
+            if let Some(zone) = class_zones.get(&key[pos..]) {
+                return Some(zone.clone());
+            }
+            match key[pos] {
+                0 => return None,
+                len => pos += usize::from(len) + 1,
+            }
+        }
+    }
+
+    /// Replaces the content of the zone with the given apex and class.
+    ///
+    /// Returns the previous content or, if there is no such zone, gives
+    /// back `content`.
+    pub fn reload<N: ToDname>(
+        &self,
+        apex: &N,
+        class: Class,
+        content: Z,
+    ) -> Result<Arc<Z>, Z> {
+        match self.get(apex, class) {
+            Some(zone) => Ok(zone.reload(content)),
+            None => Err(content),
+        }
+    }
+
+    /// Returns all zones of the set.
+    ///
+    /// The zones are ordered by class and apex name.
+    pub fn zones(&self) -> Vec<Arc<ZoneEntry<Z>>> {
+        let mut res = self
+            .zones
+            .read()
+            .unwrap()
+            .values()
+            .flat_map(HashMap::values)
+            .cloned()
+            .collect::<Vec<_>>();
+        res.sort_by(|left, right| {
+            left.class
+                .to_int()
+                .cmp(&right.class.to_int())
+                .then_with(|| left.apex.name_cmp(&right.apex))
+        });
+        res
+    }
+}
+
+impl<Z> Default for ZoneSet<Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the key for a name: its lowercase wire format.
+fn key<N: ToDname>(name: &N) -> Vec<u8> {
+    let mut res = Vec::new();
+    for label in name.iter_labels() {
+        res.push(label.len() as u8);
+        res.extend(label.as_slice().iter().map(u8::to_ascii_lowercase));
+    }
+    res
+}
+
+//------------ ZoneEntry -----------------------------------------------------
+
+/// A zone in a zone set.
+#[derive(Debug)]
+pub struct ZoneEntry<Z> {
+    /// The apex of the zone.
+    apex: Dname<Vec<u8>>,
+
+    /// The class of the zone.
+    class: Class,
+
+    /// The metadata of the zone.
+    meta: ZoneMeta,
+
+    /// The current content of the zone and its generation.
+    content: RwLock<(Arc<Z>, u64)>,
+}
+
+impl<Z> ZoneEntry<Z> {
+    /// Returns the apex name of the zone.
+    pub fn apex(&self) -> &Dname<Vec<u8>> {
+        &self.apex
+    }
+
+    /// Returns the class of the zone.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Returns the metadata of the zone.
+    pub fn meta(&self) -> &ZoneMeta {
+        &self.meta
+    }
+
+    /// Returns the current content of the zone.
+    ///
+    /// The returned value stays valid even if the zone is reloaded in the
+    /// meantime.
+    pub fn content(&self) -> Arc<Z> {
+        self.content.read().unwrap().0.clone()
+    }
+
+    /// Returns the current content together with its generation.
+    ///
+    /// The generation starts at zero and increases with each reload. It
+    /// can be used to detect whether content derived from the zone, such
+    /// as cached responses, is still current.
+    pub fn content_and_generation(&self) -> (Arc<Z>, u64) {
+        self.content.read().unwrap().clone()
+    }
+
+    /// Returns the current generation of the content.
+    pub fn generation(&self) -> u64 {
+        self.content.read().unwrap().1
+    }
+
+    /// Replaces the content of the zone.
+    ///
+    /// Returns the previous content.
+    pub fn reload(&self, content: Z) -> Arc<Z> {
+        let mut current = self.content.write().unwrap();
+        let generation = current.1 + 1;
+        mem::replace(&mut *current, (Arc::new(content), generation)).0
+    }
+}
+
+//------------ ZoneMeta ------------------------------------------------------
+
+/// Metadata about a zone.
+#[derive(Clone, Debug)]
+pub struct ZoneMeta {
+    /// The role of the server for the zone.
+    pub role: ZoneRole,
+
+    /// The clients allowed to transfer the zone.
+    ///
+    /// By default, nobody is allowed.
+    pub transfer_acl: Acl,
+
+    /// The clients allowed to send dynamic updates for the zone.
+    ///
+    /// By default, nobody is allowed.
+    pub update_acl: Acl,
+
+    /// The servers to send NOTIFY messages to when the zone changes.
+    pub notify: Vec<SocketAddr>,
+
+    /// The names of the TSIG keys used with the zone.
+    ///
+    /// For a secondary zone, the first key is used for requesting
+    /// transfers from the primaries.
+    pub keys: Vec<Dname<Vec<u8>>>,
+}
+
+impl ZoneMeta {
+    /// Creates metadata for a zone with the given role.
+    ///
+    /// Nobody is allowed to transfer or update the zone, no NOTIFY
+    /// messages are sent, and no TSIG keys are used.
+    pub fn new(role: ZoneRole) -> Self {
+        ZoneMeta {
+            role,
+            transfer_acl: Acl::new(),
+            update_acl: Acl::new(),
+            notify: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    /// Creates metadata for a primary zone.
+    pub fn primary() -> Self {
+        Self::new(ZoneRole::Primary)
+    }
+
+    /// Creates metadata for a secondary zone.
+    pub fn secondary(primaries: Vec<SocketAddr>) -> Self {
+        Self::new(ZoneRole::Secondary { primaries })
+    }
+}
+
+impl Default for ZoneMeta {
+    fn default() -> Self {
+        Self::primary()
+    }
+}
+
+//------------ ZoneRole ------------------------------------------------------
+
+/// The role of a server for a zone.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ZoneRole {
+    /// The server is primary for the zone.
+    ///
+    /// The zone’s content is maintained locally.
+    Primary,
+
+    /// The server is secondary for the zone.
+    ///
+    /// The zone’s content is transferred from the given primaries.
+    Secondary {
+        /// The addresses of the primaries.
+        primaries: Vec<SocketAddr>,
+    },
+}
+
+impl ZoneRole {
+    /// Returns whether the role is primary.
+    pub fn is_primary(&self) -> bool {
+        matches!(*self, ZoneRole::Primary)
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use std::string::{String, ToString};
+    use std::vec;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        if s == "." {
+            Dname::root_vec()
+        } else {
+            Dname::from_str(s).unwrap()
+        }
+    }
+
+    fn set() -> ZoneSet<String> {
+        let set = ZoneSet::new();
+        for apex in &["example.com", "sub.example.com", "example.net", "."] {
+            set.insert(
+                &name(apex),
+                Class::In,
+                ZoneMeta::primary(),
+                apex.to_string(),
+            );
+        }
+        set
+    }
+
+    fn find(set: &ZoneSet<String>, qname: &str) -> Option<String> {
+        set.find(&name(qname), Class::In)
+            .map(|zone| zone.content().as_ref().clone())
+    }
+
+    #[test]
+    fn find_longest_match() {
+        let set = set();
+        assert_eq!(set.len(), 4);
+        assert_eq!(find(&set, "example.com").unwrap(), "example.com");
+        assert_eq!(find(&set, "www.Example.COM").unwrap(), "example.com");
+        assert_eq!(
+            find(&set, "a.b.sub.example.com").unwrap(),
+            "sub.example.com"
+        );
+        assert_eq!(find(&set, "xsub.example.com").unwrap(), "example.com");
+        assert_eq!(find(&set, "example.org").unwrap(), ".");
+        assert!(set.find(&name("example.com"), Class::Ch).is_none());
+
+        set.remove(&name("."), Class::In).unwrap();
+        assert!(find(&set, "example.org").is_none());
+        assert_eq!(
+            set.zones()
+                .iter()
+                .map(|zone| zone.apex().to_string())
+                .collect::<Vec<_>>(),
+            ["example.com", "sub.example.com", "example.net"]
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let set = set();
+        let zone = set.get(&name("EXAMPLE.com"), Class::In).unwrap();
+        assert_eq!(zone.generation(), 0);
+        let old = zone.content();
+
+        assert_eq!(
+            set.reload(&name("example.com"), Class::In, "new".into())
+                .unwrap()
+                .as_str(),
+            "example.com"
+        );
+        assert_eq!(old.as_str(), "example.com");
+        assert_eq!(zone.content().as_str(), "new");
+        assert_eq!(zone.content_and_generation().1, 1);
+        assert_eq!(
+            set.reload(&name("example.org"), Class::In, "x".into()),
+            Err(String::from("x"))
+        );
+
+        let meta = ZoneMeta::secondary(vec!["192.0.2.1:53".parse().unwrap()]);
+        let old = set
+            .insert(&name("example.com"), Class::In, meta, "xfr".into())
+            .unwrap();
+        assert!(old.meta().role.is_primary());
+        let zone = set.get(&name("example.com"), Class::In).unwrap();
+        assert!(!zone.meta().role.is_primary());
+    }
+}
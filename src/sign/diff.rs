@@ -0,0 +1,301 @@
+//! Comparing zones.
+//!
+//! This module provides [`diff`], a function that compares two sets of
+//! sorted records and produces the RRsets that were added, removed, or
+//! changed between them. The changes are produced in canonical order which
+//! makes them suitable for reviewing changes to a zone, for creating the
+//! difference sequences of an incremental zone transfer, or for determining
+//! which RRsets need to be re-signed.
+//!
+//! [`diff`]: fn.diff.html
+
+use super::records::{Rrset, RrsetIter, SortedRecords};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Class, Rtype};
+use crate::base::name::ToDname;
+use crate::base::rdata::RecordData;
+use crate::base::record::Record;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::vec::Vec;
+
+//------------ diff ----------------------------------------------------------
+
+/// Returns an iterator over the differences between two sets of records.
+///
+/// The iterator compares the RRsets of `old` and `new` and produces an
+/// [`RrsetChange`] for every RRset that only appears in one of them or
+/// whose records differ. An RRset is considered changed if any of its
+/// records were added or removed or if its TTL changed.
+///
+/// [`RrsetChange`]: enum.RrsetChange.html
+pub fn diff<'a, N, D>(
+    old: &'a SortedRecords<N, D>,
+    new: &'a SortedRecords<N, D>,
+) -> DiffIter<'a, N, D>
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd,
+{
+    DiffIter {
+        old: old.rrsets().peekable(),
+        new: new.rrsets().peekable(),
+    }
+}
+
+//------------ RrsetChange ---------------------------------------------------
+
+/// A change to an RRset between two sets of records.
+pub enum RrsetChange<'a, N, D> {
+    /// The RRset only exists in the new records.
+    Added(Rrset<'a, N, D>),
+
+    /// The RRset only exists in the old records.
+    Removed(Rrset<'a, N, D>),
+
+    /// The RRset exists in both but its records differ.
+    Changed {
+        /// The RRset in the old records.
+        old: Rrset<'a, N, D>,
+
+        /// The RRset in the new records.
+        new: Rrset<'a, N, D>,
+    },
+}
+
+impl<'a, N, D> RrsetChange<'a, N, D> {
+    /// Returns an RRset of the change.
+    ///
+    /// For a changed RRset, this is the new one.
+    fn rrset(&self) -> &Rrset<'a, N, D> {
+        match *self {
+            RrsetChange::Added(ref rrset) => rrset,
+            RrsetChange::Removed(ref rrset) => rrset,
+            RrsetChange::Changed { ref new, .. } => new,
+        }
+    }
+
+    /// Returns the owner name of the changed RRset.
+    pub fn owner(&self) -> &N {
+        self.rrset().owner()
+    }
+
+    /// Returns the class of the changed RRset.
+    pub fn class(&self) -> Class {
+        self.rrset().class()
+    }
+
+    /// Returns the record type of the changed RRset.
+    pub fn rtype(&self) -> Rtype
+    where
+        D: RecordData,
+    {
+        self.rrset().rtype()
+    }
+
+    /// Returns the records that need to be removed from the old RRset.
+    ///
+    /// For a removed RRset, these are all its records. For a changed
+    /// RRset, these are the records that are not part of the new RRset.
+    /// Since a record with a changed TTL has to be removed and added
+    /// again, all records are returned if the TTL has changed.
+    pub fn removed_records(&self) -> Vec<&'a Record<N, D>>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        match *self {
+            RrsetChange::Added(_) => Vec::new(),
+            RrsetChange::Removed(ref rrset) => rrset.iter().collect(),
+            RrsetChange::Changed { ref old, ref new } => {
+                missing_records(old, new)
+            }
+        }
+    }
+
+    /// Returns the records that need to be added to the old RRset.
+    ///
+    /// This is the counterpart to [`removed_records`].
+    ///
+    /// [`removed_records`]: #method.removed_records
+    pub fn added_records(&self) -> Vec<&'a Record<N, D>>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        match *self {
+            RrsetChange::Added(ref rrset) => rrset.iter().collect(),
+            RrsetChange::Removed(_) => Vec::new(),
+            RrsetChange::Changed { ref old, ref new } => {
+                missing_records(new, old)
+            }
+        }
+    }
+}
+
+//------------ DiffIter ------------------------------------------------------
+
+/// An iterator over the differences between two sets of records.
+///
+/// The iterator is created via the [`diff`] function.
+///
+/// [`diff`]: fn.diff.html
+pub struct DiffIter<'a, N, D>
+where
+    N: ToDname,
+    D: RecordData,
+{
+    old: Peekable<RrsetIter<'a, N, D>>,
+    new: Peekable<RrsetIter<'a, N, D>>,
+}
+
+impl<'a, N, D> Iterator for DiffIter<'a, N, D>
+where
+    N: ToDname + 'a,
+    D: RecordData + CanonicalOrd + 'a,
+{
+    type Item = RrsetChange<'a, N, D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.old.peek(), self.new.peek()) {
+                (Some(old), Some(new)) => rrset_cmp(old, new),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => return None,
+            };
+            match order {
+                Ordering::Less => {
+                    return self.old.next().map(RrsetChange::Removed)
+                }
+                Ordering::Greater => {
+                    return self.new.next().map(RrsetChange::Added)
+                }
+                Ordering::Equal => {
+                    let old = self.old.next().unwrap();
+                    let new = self.new.next().unwrap();
+                    if !rrset_eq(&old, &new) {
+                        return Some(RrsetChange::Changed { old, new });
+                    }
+                }
+            }
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Compares the class, owner, and type of two RRsets in canonical order.
+fn rrset_cmp<N, D>(left: &Rrset<N, D>, right: &Rrset<N, D>) -> Ordering
+where
+    N: ToDname,
+    D: RecordData,
+{
+    left.class()
+        .cmp(&right.class())
+        .then_with(|| left.owner().name_cmp(right.owner()))
+        .then_with(|| left.rtype().cmp(&right.rtype()))
+}
+
+/// Returns whether two RRsets with the same owner and type are equal.
+fn rrset_eq<N, D>(left: &Rrset<N, D>, right: &Rrset<N, D>) -> bool
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd,
+{
+    left.iter().len() == right.iter().len()
+        && left.iter().zip(right.iter()).all(|(left, right)| {
+            left.ttl() == right.ttl()
+                && left.canonical_cmp(right) == Ordering::Equal
+        })
+}
+
+/// Returns the records of `rrset` that are not present in `other`.
+///
+/// If the TTLs of the two RRsets differ, all records are returned.
+fn missing_records<'a, N, D>(
+    rrset: &Rrset<'a, N, D>,
+    other: &Rrset<'a, N, D>,
+) -> Vec<&'a Record<N, D>>
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd,
+{
+    if rrset.ttl() != other.ttl() {
+        return rrset.iter().collect();
+    }
+    let mut other = other.iter().peekable();
+    let mut res = Vec::new();
+    for record in rrset.iter() {
+        while other
+            .peek()
+            .map(|item| item.canonical_cmp(record) == Ordering::Less)
+            .unwrap_or(false)
+        {
+            other.next();
+        }
+        match other.peek() {
+            Some(item) if item.canonical_cmp(record) == Ordering::Equal => {}
+            _ => res.push(record),
+        }
+    }
+    res
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use crate::utils::testing::a_record;
+    use std::string::String;
+
+    fn describe(change: &RrsetChange<Dname<Vec<u8>>, A>) -> String {
+        let kind = match *change {
+            RrsetChange::Added(_) => "added",
+            RrsetChange::Removed(_) => "removed",
+            RrsetChange::Changed { .. } => "changed",
+        };
+        format!(
+            "{} {} -{} +{}",
+            kind,
+            change.owner(),
+            change.removed_records().len(),
+            change.added_records().len()
+        )
+    }
+
+    #[test]
+    fn diff() {
+        let old: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("b.example.", 3600, "192.0.2.1"),
+            a_record("b.example.", 3600, "192.0.2.2"),
+            a_record("c.example.", 3600, "192.0.2.1"),
+            a_record("d.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let new: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("b.example.", 3600, "192.0.2.2"),
+            a_record("b.example.", 3600, "192.0.2.3"),
+            a_record("c.example.", 300, "192.0.2.1"),
+            a_record("e.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        assert_eq!(
+            super::diff(&old, &new)
+                .map(|change| describe(&change))
+                .collect::<Vec<_>>(),
+            [
+                "changed b.example -1 +1",
+                "changed c.example -1 +1",
+                "removed d.example -1 +0",
+                "added e.example -0 +1",
+            ]
+        );
+        assert_eq!(super::diff(&old, &old).count(), 0);
+    }
+}
@@ -0,0 +1,342 @@
+//! Sorting zones that don’t fit into memory.
+//!
+//! Building [`SortedRecords`] requires all records of a zone plus a sort
+//! key for each of them to be held in memory at once. For very large
+//! zones, this may be too much. The [`ExternalSort`] type provided by this
+//! module instead collects records in runs of limited length. Each full run
+//! is sorted and spilled to a temporary file in master file format. Once
+//! all records have been added, the runs are merged, producing the records
+//! in canonical order one by one.
+//!
+//! [`SortedRecords`]: ../records/struct.SortedRecords.html
+//! [`ExternalSort`]: struct.ExternalSort.html
+
+use super::inline::Zone;
+use super::records::{canonical_sort_key, SortedRecords};
+use crate::base::name::Dname;
+use crate::master::entry::MasterRecord;
+use crate::master::reader::{Reader, ReaderItem};
+use crate::master::scan::ScanError;
+use crate::master::source::Utf8File;
+use bytes::Bytes;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::string::ToString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::vec::Vec;
+use std::{format, mem, process, vec};
+
+//------------ ExternalSort --------------------------------------------------
+
+/// Sorts records using temporary files.
+///
+/// Records are added via [`push`] or [`extend`]. Whenever `run_len`
+/// records have been collected, they are sorted and written to a new file
+/// in the directory given upon creation. Calling [`finish`] merges these
+/// files and the remaining records into an iterator over all records in
+/// canonical order. Alternatively, [`into_sorted_records`] collects them
+/// into a [`SortedRecords`], which is useful if only the sorting itself
+/// would have needed too much memory.
+///
+/// Duplicate records are dropped, just as when collecting records into
+/// [`SortedRecords`].
+///
+/// The temporary files are deleted when the value or the iterator returned
+/// by [`finish`] is dropped.
+///
+/// [`push`]: #method.push
+/// [`extend`]: #method.extend
+/// [`finish`]: #method.finish
+/// [`into_sorted_records`]: #method.into_sorted_records
+/// [`SortedRecords`]: ../records/struct.SortedRecords.html
+pub struct ExternalSort {
+    /// The directory for the temporary files.
+    dir: PathBuf,
+
+    /// The maximum number of records kept in memory.
+    run_len: usize,
+
+    /// The records of the current run.
+    batch: Vec<MasterRecord>,
+
+    /// The paths of the runs spilled so far.
+    runs: RunFiles,
+}
+
+impl ExternalSort {
+    /// Creates a new sorter.
+    ///
+    /// The temporary files will be created in `dir`. At most `run_len`
+    /// records will be kept in memory at any time. A value of zero is
+    /// treated as one.
+    pub fn new(dir: impl Into<PathBuf>, run_len: usize) -> Self {
+        ExternalSort {
+            dir: dir.into(),
+            run_len: run_len.max(1),
+            batch: Vec::new(),
+            runs: RunFiles::default(),
+        }
+    }
+
+    /// Adds a record.
+    ///
+    /// If this completes a run, the run is written to a temporary file.
+    pub fn push(&mut self, record: MasterRecord) -> Result<(), io::Error> {
+        self.batch.push(record);
+        if self.batch.len() >= self.run_len {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Adds all records produced by an iterator.
+    pub fn extend<I>(&mut self, iter: I) -> Result<(), io::Error>
+    where
+        I: IntoIterator<Item = MasterRecord>,
+    {
+        for record in iter {
+            self.push(record)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of runs written to temporary files so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.0.len()
+    }
+
+    /// Finishes adding records and returns them in canonical order.
+    pub fn finish(mut self) -> Result<Merge, io::Error> {
+        let mut sources = Vec::with_capacity(self.runs.0.len() + 1);
+        for path in &self.runs.0 {
+            sources.push(Run::File(Reader::open(path)?));
+        }
+        sources.push(Run::Memory(sorted_run(mem::take(&mut self.batch))));
+        Merge::new(sources, mem::take(&mut self.runs))
+    }
+
+    /// Finishes adding records and collects them into sorted records.
+    pub fn into_sorted_records(
+        self,
+    ) -> Result<Zone<Dname<Bytes>, Bytes>, io::Error> {
+        self.finish()?
+            .collect::<Result<Vec<_>, _>>()
+            .map(SortedRecords::from_sorted_unchecked)
+    }
+
+    /// Sorts the current batch and writes it to a new temporary file.
+    fn spill(&mut self) -> Result<(), io::Error> {
+        static INSTANCE: AtomicUsize = AtomicUsize::new(0);
+
+        let path = self.dir.join(format!(
+            "domain-sort-{}-{}.zone",
+            process::id(),
+            INSTANCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = BufWriter::new(File::create(&path)?);
+        self.runs.0.push(path);
+        for record in sorted_run(mem::take(&mut self.batch)) {
+            writeln!(file, "{}", record)?;
+        }
+        file.flush()
+    }
+}
+
+/// Sorts a run of records and drops duplicates.
+fn sorted_run(records: Vec<MasterRecord>) -> vec::IntoIter<MasterRecord> {
+    let mut records = records
+        .into_iter()
+        .map(|record| (canonical_sort_key(&record), record))
+        .collect::<Vec<_>>();
+    records.sort_by(|left, right| left.0.cmp(&right.0));
+    records.dedup_by(|left, right| left.0 == right.0);
+    records
+        .into_iter()
+        .map(|(_, record)| record)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+//------------ Merge ---------------------------------------------------------
+
+/// An iterator over the merged runs of an external sort.
+///
+/// The iterator produces the records in canonical order. It returns an
+/// error if reading a temporary file fails. Iteration should be stopped
+/// after an error.
+pub struct Merge {
+    /// The sorted runs.
+    sources: Vec<Run>,
+
+    /// The next record of each run not yet returned.
+    heads: Vec<Option<MasterRecord>>,
+
+    /// The sort keys of the heads and their run index.
+    queue: BinaryHeap<Reverse<(Vec<u8>, usize)>>,
+
+    /// The sort key of the last returned record for dropping duplicates.
+    last: Option<Vec<u8>>,
+
+    /// The temporary files, deleted when the iterator is dropped.
+    _files: RunFiles,
+}
+
+impl Merge {
+    fn new(sources: Vec<Run>, files: RunFiles) -> Result<Self, io::Error> {
+        let mut res = Merge {
+            heads: (0..sources.len()).map(|_| None).collect(),
+            sources,
+            queue: BinaryHeap::new(),
+            last: None,
+            _files: files,
+        };
+        for idx in 0..res.sources.len() {
+            res.advance(idx)?;
+        }
+        Ok(res)
+    }
+
+    /// Reads the next record of run `idx` into its head.
+    fn advance(&mut self, idx: usize) -> Result<(), io::Error> {
+        if let Some(record) = self.sources[idx].next_record()? {
+            self.queue.push(Reverse((canonical_sort_key(&record), idx)));
+            self.heads[idx] = Some(record);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<MasterRecord, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Reverse((key, idx))) = self.queue.pop() {
+            let record = self.heads[idx].take().unwrap();
+            if let Err(err) = self.advance(idx) {
+                return Some(Err(err));
+            }
+            if self.last.as_ref() != Some(&key) {
+                self.last = Some(key);
+                return Some(Ok(record));
+            }
+        }
+        None
+    }
+}
+
+//------------ Run -----------------------------------------------------------
+
+/// A sorted run of records.
+enum Run {
+    /// A run spilled to a temporary file.
+    File(Reader<Utf8File>),
+
+    /// The last run that was kept in memory.
+    Memory(vec::IntoIter<MasterRecord>),
+}
+
+impl Run {
+    fn next_record(&mut self) -> Result<Option<MasterRecord>, io::Error> {
+        match *self {
+            Run::File(ref mut reader) => loop {
+                match reader.next_record() {
+                    Ok(Some(ReaderItem::Record(record))) => {
+                        return Ok(Some(record))
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => return Ok(None),
+                    Err(ScanError::Source(err, _)) => return Err(err),
+                    Err(err) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            err.to_string(),
+                        ))
+                    }
+                }
+            },
+            Run::Memory(ref mut iter) => Ok(iter.next()),
+        }
+    }
+}
+
+//------------ RunFiles ------------------------------------------------------
+
+/// The paths of temporary files that are deleted when dropped.
+#[derive(Default)]
+struct RunFiles(Vec<PathBuf>);
+
+impl Drop for RunFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Class;
+    use crate::base::record::Record;
+    use crate::rdata::{MasterRecordData, Txt, A};
+    use std::str::FromStr;
+    use std::string::String;
+
+    fn record(
+        owner: &str,
+        data: MasterRecordData<Bytes, Dname<Bytes>>,
+    ) -> MasterRecord {
+        Record::new(Dname::from_str(owner).unwrap(), Class::In, 3600, data)
+    }
+
+    fn records() -> Vec<MasterRecord> {
+        let mut res = Vec::new();
+        for i in (0..50u8).rev() {
+            let owner = format!("host{}.example.com", i % 17);
+            res.push(record(
+                &owner,
+                MasterRecordData::A(A::from_octets(192, 0, 2, i)),
+            ));
+            res.push(record(
+                &owner,
+                MasterRecordData::Txt(Txt::from_slice(b"hello").unwrap()),
+            ));
+        }
+        res
+    }
+
+    #[test]
+    fn external_sort() {
+        let dir = std::env::temp_dir();
+        let mut sort = ExternalSort::new(&dir, 7);
+        sort.extend(records()).unwrap();
+        assert_eq!(sort.spilled_runs(), 14);
+        let runs = sort.runs.0.clone();
+        assert!(runs.iter().all(|path| path.exists()));
+
+        let sorted = sort.into_sorted_records().unwrap();
+        assert!(runs.iter().all(|path| !path.exists()));
+
+        let expected: SortedRecords<_, _> = records().into_iter().collect();
+        let fmt = |records: &Zone<Dname<Bytes>, Bytes>| {
+            let mut buf = Vec::new();
+            records.write(&mut buf).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        assert_eq!(fmt(&sorted), fmt(&expected));
+        assert_eq!(sorted.families().count(), 17);
+    }
+
+    #[test]
+    fn in_memory() {
+        let mut sort = ExternalSort::new(std::env::temp_dir(), 1000);
+        sort.extend(records()).unwrap();
+        assert_eq!(sort.spilled_runs(), 0);
+        assert_eq!(sort.finish().unwrap().count(), 67);
+    }
+}
@@ -0,0 +1,570 @@
+//! Maintaining a signed copy of an unsigned zone.
+//!
+//! With inline signing, a server keeps the zone as provided by its
+//! operator unsigned and maintains a signed copy of it that it serves. This
+//! module provides the core of such a setup: the [`InlineSigner`] owns the
+//! unsigned zone, accepts updates to it either in the form of individual
+//! records, e.g., from a dynamic update, or as a whole new zone, e.g.,
+//! after a reload, and keeps the signed zone in sync.
+//!
+//! Whenever the unsigned zone changes, the signed zone is recreated with
+//! [`sign_zone`] which also rebuilds the NSEC or NSEC3 chain. Signatures of
+//! RRsets that didn’t change are kept as long as they remain valid long
+//! enough, so that the signed zone only changes where necessary. The serial
+//! of the signed zone is managed independently of the unsigned zone: it
+//! always increases whenever the signed zone changes.
+//!
+//! Since signatures expire, the signed zone has to be refreshed regularly.
+//! [`InlineSigner::refresh_time`] returns when this is necessary next and
+//! [`InlineSigner::refresh`] does it.
+//!
+//! [`InlineSigner`]: struct.InlineSigner.html
+//! [`InlineSigner::refresh`]: struct.InlineSigner.html#method.refresh
+//! [`InlineSigner::refresh_time`]: struct.InlineSigner.html#method.refresh_time
+//! [`sign_zone`]: ../zone/fn.sign_zone.html
+#![cfg(feature = "ring")]
+
+use super::key::SigningKey;
+use super::records::{FamilyName, SortedRecords};
+use super::zone::{sign_zone, SerialPolicy, SignError, SigningPolicy};
+use crate::base::clock::{Clock, MockClock};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Class, Rtype, SecAlg};
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{EmptyBuilder, FromBuilder};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::base::time32::Time32;
+use crate::rdata::{MasterRecordData, Soa};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::mem;
+use std::sync::Arc;
+use std::vec::Vec;
+
+//------------ Type Aliases --------------------------------------------------
+
+/// The records of a zone as used by the inline signer.
+pub type Zone<N, Octets> = SortedRecords<N, MasterRecordData<Octets, N>>;
+
+//------------ InlineSigner --------------------------------------------------
+
+/// An unsigned zone together with its signed counterpart.
+pub struct InlineSigner<N, Octets, Key> {
+    /// The unsigned zone.
+    unsigned: Zone<N, Octets>,
+
+    /// The signed zone.
+    signed: Zone<N, Octets>,
+
+    /// The keys to sign the zone with.
+    keys: Vec<Key>,
+
+    /// The signing policy.
+    policy: SigningPolicy<Octets>,
+
+    /// The number of seconds before expiration a signature is replaced.
+    refresh: u32,
+}
+
+impl<N, Octets, Key> InlineSigner<N, Octets, Key>
+where
+    N: ToDname + CanonicalOrd + Clone + From<Dname<Octets>>,
+    Octets: AsRef<[u8]> + Clone + FromBuilder,
+    Octets::Builder: EmptyBuilder,
+    Key: SigningKey,
+{
+    /// Creates a new inline signer and signs the zone for the first time.
+    ///
+    /// Signatures are replaced once less than a quarter of the validity
+    /// period given by `policy` is left.
+    pub fn new(
+        unsigned: Zone<N, Octets>,
+        keys: Vec<Key>,
+        policy: SigningPolicy<Octets>,
+    ) -> Result<Self, SignError<Key::Error>> {
+        let refresh = policy.validity / 4;
+        let mut res = InlineSigner {
+            unsigned,
+            signed: SortedRecords::new(),
+            keys,
+            policy,
+            refresh,
+        };
+        res.resign()?;
+        Ok(res)
+    }
+
+    /// Returns the unsigned zone.
+    pub fn unsigned(&self) -> &Zone<N, Octets> {
+        &self.unsigned
+    }
+
+    /// Returns the signed zone.
+    pub fn signed(&self) -> &Zone<N, Octets> {
+        &self.signed
+    }
+
+    /// Returns the signing keys.
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Returns the signing policy.
+    pub fn policy(&self) -> &SigningPolicy<Octets> {
+        &self.policy
+    }
+
+    /// Returns the number of seconds before expiration signatures are
+    /// replaced.
+    pub fn refresh_interval(&self) -> u32 {
+        self.refresh
+    }
+
+    /// Sets the number of seconds before expiration signatures are
+    /// replaced.
+    pub fn set_refresh_interval(&mut self, refresh: u32) {
+        self.refresh = refresh
+    }
+
+    /// Returns the serial of the signed zone.
+    pub fn serial(&self) -> Option<Serial> {
+        soa_serial(&self.signed)
+    }
+
+    /// Returns the time when the signed zone needs to be refreshed next.
+    ///
+    /// This is the earliest expiration time of any signature minus the
+    /// refresh interval.
+    pub fn refresh_time(&self) -> Option<Time32> {
+        self.signed
+            .families()
+            .flat_map(|family| family.records())
+            .filter_map(|record| match *record.data() {
+                MasterRecordData::Rrsig(ref rrsig) => {
+                    Some(rrsig.expiration())
+                }
+                _ => None,
+            })
+            .min_by(|left, right| {
+                left.partial_cmp(right).unwrap_or(Ordering::Equal)
+            })
+            .map(|expiration| {
+                Time32::from_int(
+                    expiration.into_int().wrapping_sub(self.refresh),
+                )
+            })
+    }
+
+    /// Applies an update to the unsigned zone.
+    ///
+    /// The records in `removed` are removed from the zone, the records in
+    /// `added` are added to it. Records are considered equal if their
+    /// owner, class, and data are equal. DNSSEC records are ignored as
+    /// they are created during signing.
+    ///
+    /// Returns the previous version of the signed zone which can be used
+    /// to determine the differences to the new version, e.g., via
+    /// [`diff`].
+    ///
+    /// [`diff`]: ../diff/fn.diff.html
+    pub fn update<R, A>(
+        &mut self,
+        removed: R,
+        added: A,
+    ) -> Result<Zone<N, Octets>, SignError<Key::Error>>
+    where
+        R: IntoIterator<Item = Record<N, MasterRecordData<Octets, N>>>,
+        A: IntoIterator<Item = Record<N, MasterRecordData<Octets, N>>>,
+    {
+        let removed: Vec<_> = removed.into_iter().collect();
+        if !removed.is_empty() {
+            self.unsigned.retain(|record| {
+                !removed.iter().any(|item| {
+                    item.owner().name_eq(record.owner())
+                        && item.class() == record.class()
+                        && item.data() == record.data()
+                })
+            });
+        }
+        for record in added {
+            if is_dnssec(record.rtype()) {
+                continue;
+            }
+            // A SOA record replaces the existing one.
+            if record.rtype() == Rtype::Soa {
+                self.unsigned.retain(|item| {
+                    item.rtype() != Rtype::Soa
+                        || !item.owner().name_eq(record.owner())
+                });
+            }
+            let _ = self.unsigned.insert(record);
+        }
+        self.resign()
+    }
+
+    /// Replaces the unsigned zone with a new version.
+    ///
+    /// Returns the previous version of the signed zone.
+    pub fn reload(
+        &mut self,
+        unsigned: Zone<N, Octets>,
+    ) -> Result<Zone<N, Octets>, SignError<Key::Error>> {
+        self.unsigned = unsigned;
+        self.resign()
+    }
+
+    /// Replaces the signing keys.
+    ///
+    /// Returns the previous version of the signed zone.
+    pub fn set_keys(
+        &mut self,
+        keys: Vec<Key>,
+    ) -> Result<Zone<N, Octets>, SignError<Key::Error>> {
+        self.keys = keys;
+        self.resign()
+    }
+
+    /// Replaces the signing policy.
+    ///
+    /// Returns the previous version of the signed zone.
+    pub fn set_policy(
+        &mut self,
+        policy: SigningPolicy<Octets>,
+    ) -> Result<Zone<N, Octets>, SignError<Key::Error>> {
+        self.policy = policy;
+        self.resign()
+    }
+
+    /// Replaces signatures that are about to expire.
+    ///
+    /// Returns the previous version of the signed zone.
+    pub fn refresh(
+        &mut self,
+    ) -> Result<Zone<N, Octets>, SignError<Key::Error>> {
+        self.resign()
+    }
+
+    /// Recreates the signed zone.
+    ///
+    /// Returns the previous signed zone.
+    fn resign(&mut self) -> Result<Zone<N, Octets>, SignError<Key::Error>> {
+        // Sign with a clock that stands still so that the time is the
+        // same throughout.
+        let clock = MockClock::new(self.policy.clock.now());
+        let now = clock.time32();
+
+        // Determine the serial. It needs to be larger than that of the
+        // current signed zone.
+        let unsigned_serial =
+            soa_serial(&self.unsigned).ok_or(SignError::NoSoa)?;
+        let mut serial = self.policy.serial.next_at(unsigned_serial, now);
+        if let Some(current) = soa_serial(&self.signed) {
+            if serial.partial_cmp(&current) != Some(Ordering::Greater) {
+                serial = current.add(1)
+            }
+        }
+
+        let mut unsigned = self.unsigned.clone();
+        set_soa_serial(&mut unsigned, serial);
+        let mut policy = self.policy.clone();
+        policy.serial = SerialPolicy::Keep;
+        policy.clock = Arc::new(clock);
+        let signed = sign_zone(unsigned, &self.keys, &policy)?;
+        let signed = if policy.zonemd.is_none() {
+            reuse_signatures(
+                &self.signed,
+                signed,
+                Time32::from_int(now.into_int().wrapping_add(self.refresh)),
+            )
+        } else {
+            // The ZONEMD digest covers the signatures, so they can’t be
+            // exchanged afterwards.
+            signed
+        };
+        Ok(mem::replace(&mut self.signed, signed))
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether records of a type are created during signing.
+fn is_dnssec(rtype: Rtype) -> bool {
+    matches!(
+        rtype,
+        Rtype::Rrsig
+            | Rtype::Nsec
+            | Rtype::Nsec3
+            | Rtype::Nsec3param
+            | Rtype::Zonemd
+    )
+}
+
+/// Returns the serial of the SOA record of a zone.
+fn soa_serial<N, Octets>(zone: &Zone<N, Octets>) -> Option<Serial>
+where
+    N: ToDname,
+    Octets: AsRef<[u8]>,
+{
+    match *zone.find_soa()?.first().data() {
+        MasterRecordData::Soa(ref soa) => Some(soa.serial()),
+        _ => None,
+    }
+}
+
+/// Sets the serial of the SOA record of a zone.
+fn set_soa_serial<N, Octets>(zone: &mut Zone<N, Octets>, serial: Serial)
+where
+    N: ToDname + CanonicalOrd + Clone,
+    Octets: AsRef<[u8]> + Clone,
+{
+    let record = match zone.find_soa() {
+        Some(soa) => soa.first().clone(),
+        None => return,
+    };
+    let soa = match *record.data() {
+        MasterRecordData::Soa(ref soa) => soa,
+        _ => return,
+    };
+    let new = Record::new(
+        record.owner().clone(),
+        record.class(),
+        record.ttl(),
+        Soa::new(
+            soa.mname().clone(),
+            soa.rname().clone(),
+            serial,
+            soa.refresh(),
+            soa.retry(),
+            soa.expire(),
+            soa.minimum(),
+        )
+        .into(),
+    );
+    zone.retain(|item| {
+        item.rtype() != Rtype::Soa || !item.owner().name_eq(record.owner())
+    });
+    let _ = zone.insert(new);
+}
+
+/// Puts the still valid signatures of unchanged RRsets back into the zone.
+///
+/// An RRset’s signatures from the `old` zone are used in the `new` zone if
+/// the RRset is the same in both zones, all its old signatures expire after
+/// `until`, and they were made by the same keys as the new signatures.
+fn reuse_signatures<N, Octets>(
+    old: &Zone<N, Octets>,
+    mut new: Zone<N, Octets>,
+    until: Time32,
+) -> Zone<N, Octets>
+where
+    N: ToDname + CanonicalOrd + Clone,
+    Octets: AsRef<[u8]> + Clone,
+{
+    type SigKey = (Dname<Vec<u8>>, Class, Rtype);
+
+    /// Returns the signatures covering an RRset and their keys.
+    #[allow(clippy::type_complexity)]
+    fn signatures<'a, N: ToDname, Octets: AsRef<[u8]>>(
+        zone: &'a Zone<N, Octets>,
+        family: &FamilyName<&N>,
+        rtype: Rtype,
+    ) -> (
+        Vec<&'a Record<N, MasterRecordData<Octets, N>>>,
+        Vec<(SecAlg, u16)>,
+    ) {
+        let mut records = Vec::new();
+        let mut keys = Vec::new();
+        let found = zone
+            .subtree(family)
+            .next()
+            .filter(|item| item.family_name() == *family);
+        if let Some(found) = found {
+            for record in found.records() {
+                if let MasterRecordData::Rrsig(ref rrsig) = *record.data() {
+                    if rrsig.type_covered() == rtype {
+                        records.push(record);
+                        keys.push((rrsig.algorithm(), rrsig.key_tag()));
+                    }
+                }
+            }
+        }
+        keys.sort_by_key(|(alg, tag)| (alg.to_int(), *tag));
+        (records, keys)
+    }
+
+    let mut reused: HashSet<SigKey> = HashSet::new();
+    let mut records = Vec::new();
+    for rrset in new.rrsets() {
+        if rrset.rtype() == Rtype::Rrsig {
+            continue;
+        }
+        let family = rrset.family_name();
+        let old_rrset = match old
+            .subtree(&family)
+            .next()
+            .filter(|item| item.family_name() == family)
+            .and_then(|item| {
+                item.rrsets().find(|item| item.rtype() == rrset.rtype())
+            }) {
+            Some(old_rrset) => old_rrset,
+            None => continue,
+        };
+        let unchanged = old_rrset.ttl() == rrset.ttl()
+            && old_rrset.iter().len() == rrset.iter().len()
+            && old_rrset
+                .iter()
+                .zip(rrset.iter())
+                .all(|(left, right)| left.data() == right.data());
+        if !unchanged {
+            continue;
+        }
+        let (old_sigs, old_keys) = signatures(old, &family, rrset.rtype());
+        let (_, new_keys) = signatures(&new, &family, rrset.rtype());
+        if old_sigs.is_empty() || old_keys != new_keys {
+            continue;
+        }
+        let valid = old_sigs.iter().all(|record| match *record.data() {
+            MasterRecordData::Rrsig(ref rrsig) => rrsig.expiration() > until,
+            _ => false,
+        });
+        if !valid {
+            continue;
+        }
+        reused.insert((rrset.owner().to_vec(), rrset.class(), rrset.rtype()));
+        records.extend(old_sigs.into_iter().cloned());
+    }
+
+    if !reused.is_empty() {
+        new.retain(|record| match *record.data() {
+            MasterRecordData::Rrsig(ref rrsig) => !reused.contains(&(
+                record.owner().to_vec(),
+                record.class(),
+                rrsig.type_covered(),
+            )),
+            _ => true,
+        });
+        for record in records {
+            let _ = new.insert(record);
+        }
+    }
+    new
+}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "bytes"))]
+mod test {
+    use super::*;
+    use crate::rdata::A;
+    use crate::sign::ring::Key;
+    use crate::utils::testing::{self, name, record, ZoneData};
+    use bytes::Bytes;
+    use core::str::FromStr;
+    use ring::rand::SystemRandom;
+    use std::time::Duration;
+
+    type Name = Dname<Bytes>;
+    type Data = ZoneData<Bytes>;
+
+    fn soa(serial: u32) -> Record<Name, Data> {
+        record("example.", 3600, testing::soa(serial).into())
+    }
+
+    fn a(owner: &str, addr: &str) -> Record<Name, Data> {
+        record(owner, 3600, A::from_str(addr).unwrap().into())
+    }
+
+    /// Returns the signatures for the given RRset.
+    fn rrsigs(
+        zone: &Zone<Name, Bytes>,
+        owner: &str,
+        rtype: Rtype,
+    ) -> Vec<Bytes> {
+        let owner = name::<Bytes>(owner);
+        zone.families()
+            .flat_map(|family| family.records())
+            .filter(|record| record.owner() == &owner)
+            .filter_map(|record| match *record.data() {
+                MasterRecordData::Rrsig(ref rrsig)
+                    if rrsig.type_covered() == rtype =>
+                {
+                    Some(rrsig.signature().clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn count(zone: &Zone<Name, Bytes>, rtype: Rtype) -> usize {
+        zone.families()
+            .flat_map(|family| family.records())
+            .filter(|record| record.rtype() == rtype)
+            .count()
+    }
+
+    #[test]
+    fn inline_signing() {
+        let rng = SystemRandom::new();
+        let keys = vec![Key::throwaway_13(257, &rng).unwrap()];
+        let start = 1_600_000_000;
+        let clock = MockClock::from_unix_secs(start.into());
+        let policy = SigningPolicy {
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        };
+        let unsigned: Zone<_, _> =
+            vec![soa(10), a("www.example.", "192.0.2.1")].into();
+        let mut signer = InlineSigner::new(unsigned, keys, policy).unwrap();
+        assert_eq!(signer.serial(), Some(Serial(10)));
+        assert_eq!(count(signer.signed(), Rtype::Nsec), 2);
+        assert_eq!(
+            signer.refresh_time(),
+            Some(Time32::from_int(
+                start + 30 * 24 * 3600 - 30 * 24 * 3600 / 4
+            ))
+        );
+        let www = rrsigs(signer.signed(), "www.example.", Rtype::A);
+        assert_eq!(www.len(), 1);
+
+        // Adding a record changes the serial and the NSEC chain but keeps
+        // the signatures of unchanged RRsets.
+        let old = signer
+            .update(None, Some(a("mail.example.", "192.0.2.2")))
+            .unwrap();
+        assert_eq!(signer.serial(), Some(Serial(11)));
+        assert_eq!(count(signer.signed(), Rtype::Nsec), 3);
+        assert_eq!(rrsigs(signer.signed(), "www.example.", Rtype::A), www);
+        assert_ne!(
+            rrsigs(signer.signed(), "example.", Rtype::Soa),
+            rrsigs(&old, "example.", Rtype::Soa)
+        );
+        assert_eq!(
+            crate::sign::diff::diff(&old, signer.signed()).count(),
+            // SOA, apex NSEC, new A and NSEC at mail, and RRSIGs at the
+            // apex and mail.
+            6
+        );
+
+        // Removing it again.
+        signer
+            .update(Some(a("mail.example.", "192.0.2.2")), None)
+            .unwrap();
+        assert_eq!(signer.serial(), Some(Serial(12)));
+        assert_eq!(count(signer.signed(), Rtype::Nsec), 2);
+        assert_eq!(count(signer.unsigned(), Rtype::A), 1);
+
+        // A reload with a lower serial still increases the signed serial.
+        signer
+            .reload(vec![soa(5), a("www.example.", "192.0.2.1")].into())
+            .unwrap();
+        assert_eq!(signer.serial(), Some(Serial(13)));
+        assert_eq!(rrsigs(signer.signed(), "www.example.", Rtype::A), www);
+
+        // Close to expiration, all signatures are replaced.
+        clock.advance(Duration::from_secs(29 * 24 * 3600));
+        signer.refresh().unwrap();
+        assert_eq!(signer.serial(), Some(Serial(14)));
+        assert_ne!(rrsigs(signer.signed(), "www.example.", Rtype::A), www);
+    }
+}
@@ -1,6 +1,12 @@
-use crate::base::iana::SecAlg;
+use crate::base::iana::{Requirement, SecAlg};
 use crate::base::name::ToDname;
+use crate::base::time32::Time32;
 use crate::rdata::{Dnskey, Ds};
+use std::str::FromStr;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ SigningKey ----------------------------------------------------
 
 pub trait SigningKey {
     type Octets: AsRef<[u8]>;
@@ -22,6 +28,31 @@ pub trait SigningKey {
     }
 
     fn sign(&self, data: &[u8]) -> Result<Self::Signature, Self::Error>;
+
+    /// Signs data provided in pieces.
+    ///
+    /// The closure `data` is called with a function that receives the data
+    /// to be signed piece by piece. The default implementation clears
+    /// `buf`, collects the pieces in it, and then signs them via [`sign`].
+    /// Passing the same buffer to successive calls avoids allocations.
+    ///
+    /// Keys that calculate a digest over the data before signing it can
+    /// override this method to feed the pieces into the digest directly
+    /// without collecting them first. Such keys may leave `buf` alone.
+    ///
+    /// [`sign`]: #tymethod.sign
+    fn sign_incremental<F>(
+        &self,
+        buf: &mut Vec<u8>,
+        data: F,
+    ) -> Result<Self::Signature, Self::Error>
+    where
+        F: FnOnce(&mut dyn FnMut(&[u8])),
+    {
+        buf.clear();
+        data(&mut |piece| buf.extend_from_slice(piece));
+        self.sign(buf)
+    }
 }
 
 impl<'a, K: SigningKey> SigningKey for &'a K {
@@ -50,4 +81,891 @@ impl<'a, K: SigningKey> SigningKey for &'a K {
     fn sign(&self, data: &[u8]) -> Result<Self::Signature, Self::Error> {
         (*self).sign(data)
     }
+
+    fn sign_incremental<F>(
+        &self,
+        buf: &mut Vec<u8>,
+        data: F,
+    ) -> Result<Self::Signature, Self::Error>
+    where
+        F: FnOnce(&mut dyn FnMut(&[u8])),
+    {
+        (*self).sign_incremental(buf, data)
+    }
+}
+
+//------------ Key Tags ------------------------------------------------------
+
+/// Finds keys with colliding key tags in a set of keys.
+///
+/// Since signatures identify the key used for signing only by algorithm
+/// and key tag, keys with identical algorithm and key tag cannot be told
+/// apart by validators which then have to try all of them. Signers may
+/// want to avoid this by generating a new key instead.
+///
+/// Returns a list with a list of colliding keys for each combination of
+/// algorithm and key tag that is used by more than one key.
+pub fn key_tag_collisions<'a, Octets, I>(
+    keys: I,
+) -> Vec<Vec<&'a Dnskey<Octets>>>
+where
+    Octets: AsRef<[u8]> + 'a,
+    I: IntoIterator<Item = &'a Dnskey<Octets>>,
+{
+    let mut keys: Vec<_> = keys
+        .into_iter()
+        .map(|key| ((key.algorithm().to_int(), key.key_tag()), key))
+        .collect();
+    keys.sort_by_key(|item| item.0);
+    let mut res = Vec::new();
+    let mut keys = keys.as_slice();
+    while let Some((first, _)) = keys.first() {
+        let len = keys.iter().take_while(|item| item.0 == *first).count();
+        let (head, tail) = keys.split_at(len);
+        if len > 1 {
+            res.push(head.iter().map(|item| item.1).collect());
+        }
+        keys = tail;
+    }
+    res
+}
+
+//------------ KeyMetadata ---------------------------------------------------
+
+/// Timing metadata and rollover state of a key.
+///
+/// The timestamps describe when a key was created and when it is to be
+/// published, used for signing, retired, and removed from the zone. They
+/// are given in seconds since the Unix epoch. Together with the key’s
+/// current [`KeyState`], this allows a key manager to persist its state
+/// and resume it later.
+///
+/// The metadata can be stored in a simple text format via the `Display`
+/// implementation and read back via `FromStr`. Each line contains a field
+/// name followed by a colon and the value. Timestamps are given in the
+/// form `YYYYMMDDHHmmSS` in UTC. Empty lines and lines starting with a
+/// semicolon are ignored as are unknown fields. Missing timestamps are
+/// left out:
+///
+/// ```text
+/// ; DNSSEC key state
+/// Algorithm: 13
+/// KeyTag: 60485
+/// Flags: 257
+/// State: active
+/// Created: 20200913122640
+/// Publish: 20200913122640
+/// Activate: 20200914122640
+/// ```
+///
+/// [`KeyState`]: enum.KeyState.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyMetadata {
+    /// The algorithm of the key.
+    pub algorithm: SecAlg,
+
+    /// The key tag of the key.
+    pub key_tag: u16,
+
+    /// The flags of the key’s DNSKEY record.
+    pub flags: u16,
+
+    /// The current rollover state of the key.
+    pub state: KeyState,
+
+    /// When the key was created.
+    pub created: Option<Time32>,
+
+    /// When the key’s DNSKEY record is to be added to the zone.
+    pub publish: Option<Time32>,
+
+    /// When the key starts signing.
+    pub activate: Option<Time32>,
+
+    /// When the key stops signing.
+    pub inactive: Option<Time32>,
+
+    /// When the key’s DNSKEY record is to be removed from the zone.
+    pub delete: Option<Time32>,
+}
+
+impl KeyMetadata {
+    /// Creates metadata for a newly generated key.
+    ///
+    /// All timestamps except for the creation time are left unset.
+    pub fn new<Octets: AsRef<[u8]>>(
+        dnskey: &Dnskey<Octets>,
+        created: Time32,
+    ) -> Self {
+        KeyMetadata {
+            algorithm: dnskey.algorithm(),
+            key_tag: dnskey.key_tag(),
+            flags: dnskey.flags(),
+            state: KeyState::Generated,
+            created: Some(created),
+            publish: None,
+            activate: None,
+            inactive: None,
+            delete: None,
+        }
+    }
+
+    /// Returns whether the metadata belongs to the given key.
+    pub fn matches<Octets: AsRef<[u8]>>(
+        &self,
+        dnskey: &Dnskey<Octets>,
+    ) -> bool {
+        self.algorithm == dnskey.algorithm()
+            && self.key_tag == dnskey.key_tag()
+            && self.flags == dnskey.flags()
+    }
+
+    /// Returns whether the key’s DNSKEY record is in the zone at `now`.
+    pub fn is_published_at(&self, now: Time32) -> bool {
+        reached(self.publish, now) && !reached(self.delete, now)
+    }
+
+    /// Returns whether the key is used for signing at `now`.
+    pub fn is_active_at(&self, now: Time32) -> bool {
+        reached(self.activate, now) && !reached(self.inactive, now)
+    }
+}
+
+/// Returns whether a timestamp is set and not after `now`.
+fn reached(time: Option<Time32>, now: Time32) -> bool {
+    match time {
+        Some(time) => time <= now,
+        None => false,
+    }
+}
+
+//--- Display and FromStr
+
+impl fmt::Display for KeyMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "; DNSSEC key state")?;
+        writeln!(f, "Algorithm: {}", self.algorithm.to_int())?;
+        writeln!(f, "KeyTag: {}", self.key_tag)?;
+        writeln!(f, "Flags: {}", self.flags)?;
+        writeln!(f, "State: {}", self.state)?;
+        for (name, value) in &[
+            ("Created", self.created),
+            ("Publish", self.publish),
+            ("Activate", self.activate),
+            ("Inactive", self.inactive),
+            ("Delete", self.delete),
+        ] {
+            if let Some(value) = *value {
+                writeln!(f, "{}: {}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KeyMetadata {
+    type Err = KeyMetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut algorithm = None;
+        let mut key_tag = None;
+        let mut flags = None;
+        let mut state = None;
+        let mut res = KeyMetadata {
+            algorithm: SecAlg::Int(0),
+            key_tag: 0,
+            flags: 0,
+            state: KeyState::Generated,
+            created: None,
+            publish: None,
+            activate: None,
+            inactive: None,
+            delete: None,
+        };
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let mut parts = line.splitn(2, ':');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => return Err(KeyMetadataError::BadLine),
+            };
+            let time = || {
+                if value.len() != 14 {
+                    return Err(KeyMetadataError::BadValue);
+                }
+                value
+                    .parse::<Time32>()
+                    .map(Some)
+                    .map_err(|_| KeyMetadataError::BadValue)
+            };
+            match name {
+                "Algorithm" => {
+                    algorithm = Some(SecAlg::from_int(
+                        value
+                            .parse()
+                            .map_err(|_| KeyMetadataError::BadValue)?,
+                    ))
+                }
+                "KeyTag" => {
+                    key_tag = Some(
+                        value
+                            .parse()
+                            .map_err(|_| KeyMetadataError::BadValue)?,
+                    )
+                }
+                "Flags" => {
+                    flags = Some(
+                        value
+                            .parse()
+                            .map_err(|_| KeyMetadataError::BadValue)?,
+                    )
+                }
+                "State" => state = Some(value.parse()?),
+                "Created" => res.created = time()?,
+                "Publish" => res.publish = time()?,
+                "Activate" => res.activate = time()?,
+                "Inactive" => res.inactive = time()?,
+                "Delete" => res.delete = time()?,
+                _ => {}
+            }
+        }
+        res.algorithm = algorithm.ok_or(KeyMetadataError::MissingField)?;
+        res.key_tag = key_tag.ok_or(KeyMetadataError::MissingField)?;
+        res.flags = flags.ok_or(KeyMetadataError::MissingField)?;
+        res.state = state.ok_or(KeyMetadataError::MissingField)?;
+        Ok(res)
+    }
+}
+
+//------------ KeyState ------------------------------------------------------
+
+/// The state of a key during its lifetime.
+///
+/// The states are those defined in RFC 7583, section 3.1.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyState {
+    /// The key has been created but not been used yet.
+    Generated,
+
+    /// The DNSKEY record has been added to the zone but may not yet have
+    /// reached all validators.
+    Published,
+
+    /// The DNSKEY record is known to all validators.
+    Ready,
+
+    /// The key is used for signing.
+    Active,
+
+    /// The key no longer signs but its signatures may still be cached.
+    Retired,
+
+    /// All signatures of the key have expired from caches.
+    Dead,
+
+    /// The DNSKEY record has been removed from the zone.
+    Removed,
+
+    /// The key has been revoked as described in RFC 5011.
+    Revoked,
+}
+
+impl KeyState {
+    /// Returns the name of the state as used in the key state file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyState::Generated => "generated",
+            KeyState::Published => "published",
+            KeyState::Ready => "ready",
+            KeyState::Active => "active",
+            KeyState::Retired => "retired",
+            KeyState::Dead => "dead",
+            KeyState::Removed => "removed",
+            KeyState::Revoked => "revoked",
+        }
+    }
+}
+
+//--- Display and FromStr
+
+impl fmt::Display for KeyState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KeyState {
+    type Err = KeyMetadataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "generated" => Ok(KeyState::Generated),
+            "published" => Ok(KeyState::Published),
+            "ready" => Ok(KeyState::Ready),
+            "active" => Ok(KeyState::Active),
+            "retired" => Ok(KeyState::Retired),
+            "dead" => Ok(KeyState::Dead),
+            "removed" => Ok(KeyState::Removed),
+            "revoked" => Ok(KeyState::Revoked),
+            _ => Err(KeyMetadataError::BadValue),
+        }
+    }
+}
+
+//------------ KeyPolicy -----------------------------------------------------
+
+/// A policy for rejecting weak signing keys.
+///
+/// The policy checks the algorithm of each key against the requirement
+/// levels of RFC 8624 and the size of RSA keys. There are three presets
+/// with different levels of strictness: [`strict`], which is also the
+/// default, [`relaxed`], and [`permissive`].
+///
+/// [`strict`]: #method.strict
+/// [`relaxed`]: #method.relaxed
+/// [`permissive`]: #method.permissive
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeyPolicy {
+    /// The minimum size of RSA keys in bits.
+    pub min_rsa_bits: usize,
+
+    /// The minimum requirement level for the algorithm for signing.
+    ///
+    /// If this is `None`, all algorithms are accepted.
+    pub min_requirement: Option<Requirement>,
+
+    /// Whether algorithms not listed in RFC 8624 are accepted.
+    ///
+    /// This includes unassigned values and the private algorithms.
+    pub allow_unlisted: bool,
+}
+
+impl KeyPolicy {
+    /// Returns a strict policy.
+    ///
+    /// This policy rejects RSA keys with less than 2048 bits and all
+    /// algorithms that are NOT RECOMMENDED or worse for signing, i.e., all
+    /// SHA-1 based algorithms, DSA, and GOST, as well as unlisted
+    /// algorithms.
+    pub fn strict() -> Self {
+        KeyPolicy {
+            min_rsa_bits: 2048,
+            min_requirement: Some(Requirement::May),
+            allow_unlisted: false,
+        }
+    }
+
+    /// Returns a relaxed policy.
+    ///
+    /// This policy rejects RSA keys with less than 1024 bits and all
+    /// algorithms that MUST NOT be used for signing, i.e., RSA/MD5, DSA,
+    /// and GOST. It allows SHA-1 based algorithms and unlisted algorithms.
+    pub fn relaxed() -> Self {
+        KeyPolicy {
+            min_rsa_bits: 1024,
+            min_requirement: Some(Requirement::NotRecommended),
+            allow_unlisted: true,
+        }
+    }
+
+    /// Returns a policy that accepts all keys.
+    pub fn permissive() -> Self {
+        KeyPolicy {
+            min_rsa_bits: 0,
+            min_requirement: None,
+            allow_unlisted: true,
+        }
+    }
+
+    /// Checks a single key against the policy.
+    pub fn check_key<Octets: AsRef<[u8]>>(
+        &self,
+        dnskey: &Dnskey<Octets>,
+    ) -> Result<(), WeakKey> {
+        let weak = |weakness| WeakKey {
+            algorithm: dnskey.algorithm(),
+            key_tag: dnskey.key_tag(),
+            weakness,
+        };
+        if let Some(min) = self.min_requirement {
+            match dnskey.algorithm().signing_requirement() {
+                Some(requirement) if requirement < min => {
+                    return Err(weak(Weakness::Algorithm(requirement)))
+                }
+                None if !self.allow_unlisted => {
+                    return Err(weak(Weakness::UnlistedAlgorithm))
+                }
+                _ => {}
+            }
+        }
+        if matches!(
+            dnskey.algorithm(),
+            SecAlg::RsaMd5
+                | SecAlg::RsaSha1
+                | SecAlg::RsaSha1Nsec3Sha1
+                | SecAlg::RsaSha256
+                | SecAlg::RsaSha512
+        ) {
+            match rsa_key_bits(dnskey.public_key().as_ref()) {
+                Some(bits) if bits >= self.min_rsa_bits => {}
+                Some(bits) => return Err(weak(Weakness::ShortRsaKey(bits))),
+                None => return Err(weak(Weakness::ShortRsaKey(0))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a set of keys against the policy.
+    ///
+    /// Returns all keys that violate the policy.
+    pub fn check_keys<'a, Octets, I>(&self, keys: I) -> Vec<WeakKey>
+    where
+        Octets: AsRef<[u8]> + 'a,
+        I: IntoIterator<Item = &'a Dnskey<Octets>>,
+    {
+        keys.into_iter()
+            .filter_map(|key| self.check_key(key).err())
+            .collect()
+    }
+}
+
+//--- Default
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+//------------ WeakKey -------------------------------------------------------
+
+/// A key that violates a key policy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WeakKey {
+    /// The algorithm of the key.
+    pub algorithm: SecAlg,
+
+    /// The key tag of the key.
+    pub key_tag: u16,
+
+    /// Why the key was rejected.
+    pub weakness: Weakness,
+}
+
+//--- Display and Error
+
+impl fmt::Display for WeakKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "key {}/{}: ", self.algorithm, self.key_tag)?;
+        match self.weakness {
+            Weakness::Algorithm(requirement) => write!(
+                f,
+                "algorithm has signing requirement {:?}",
+                requirement
+            ),
+            Weakness::UnlistedAlgorithm => f.write_str("unlisted algorithm"),
+            Weakness::ShortRsaKey(bits) => {
+                write!(f, "RSA key too short ({} bits)", bits)
+            }
+        }
+    }
+}
+
+impl error::Error for WeakKey {}
+
+//------------ Weakness ------------------------------------------------------
+
+/// The reason a key was rejected by a key policy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Weakness {
+    /// The algorithm’s signing requirement level is too low.
+    Algorithm(Requirement),
+
+    /// The algorithm is not listed in RFC 8624.
+    UnlistedAlgorithm,
+
+    /// The RSA key has too few bits.
+    ///
+    /// A malformed RSA key is reported as having zero bits.
+    ShortRsaKey(usize),
+}
+
+//------------ Verification --------------------------------------------------
+
+/// Verifies a signature over `data` using the public key of `dnskey`.
+///
+/// The signature needs to be encoded as it would appear in an RRSIG
+/// record. For ECDSA, this is the raw concatenation of the two integers
+/// as described in RFC 6605. For convenience, DER encoded ECDSA signatures
+/// as produced by many crypto libraries are accepted, too.
+///
+/// RSA keys of less than 1024 bits, DSA, and GOST are not supported.
+#[cfg(feature = "ring")]
+pub fn verify<Octets: AsRef<[u8]>>(
+    dnskey: &Dnskey<Octets>,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<(), VerifyError> {
+    use ring::signature;
+
+    let public_key = dnskey.public_key().as_ref();
+    match dnskey.algorithm() {
+        SecAlg::RsaSha1
+        | SecAlg::RsaSha1Nsec3Sha1
+        | SecAlg::RsaSha256
+        | SecAlg::RsaSha512 => {
+            let algorithm = match dnskey.algorithm() {
+                SecAlg::RsaSha256 => {
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY
+                }
+                SecAlg::RsaSha512 => {
+                    &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY
+                }
+                _ => &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY,
+            };
+            let (e, n) = rsa_exponent_modulus(public_key)
+                .ok_or(VerifyError::InvalidKey)?;
+            signature::RsaPublicKeyComponents { n, e }
+                .verify(algorithm, data, signature)
+                .map_err(|_| VerifyError::BadSignature)
+        }
+        SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => {
+            let (fixed, asn1) = match dnskey.algorithm() {
+                SecAlg::EcdsaP256Sha256 => (
+                    &signature::ECDSA_P256_SHA256_FIXED,
+                    &signature::ECDSA_P256_SHA256_ASN1,
+                ),
+                _ => (
+                    &signature::ECDSA_P384_SHA384_FIXED,
+                    &signature::ECDSA_P384_SHA384_ASN1,
+                ),
+            };
+
+            // The DNSKEY contains the bare point. Ring wants the
+            // uncompressed point marker in front of it.
+            let mut key = Vec::with_capacity(public_key.len() + 1);
+            key.push(0x04);
+            key.extend_from_slice(public_key);
+
+            let algorithm = if signature.len() == public_key.len() {
+                fixed
+            } else {
+                asn1
+            };
+            signature::UnparsedPublicKey::new(algorithm, &key)
+                .verify(data, signature)
+                .map_err(|_| VerifyError::BadSignature)
+        }
+        SecAlg::Ed25519 => {
+            signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+                .verify(data, signature)
+                .map_err(|_| VerifyError::BadSignature)
+        }
+        _ => Err(VerifyError::UnsupportedAlgorithm),
+    }
+}
+
+/// Converts a DER encoded ECDSA signature into its DNSSEC encoding.
+///
+/// DNSSEC uses the concatenation of the two integers r and s, each
+/// left-padded to `len` octets, i.e., 32 octets for P-256 and 48 octets
+/// for P-384. Returns `None` if `der` isn’t a valid DER encoded signature
+/// or the integers don’t fit into `len` octets.
+pub fn ecdsa_der_to_fixed(der: &[u8], len: usize) -> Option<Vec<u8>> {
+    fn take_tlv(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+        let (&first, data) = data.split_first()?;
+        if first != tag {
+            return None;
+        }
+        let (&size, data) = data.split_first()?;
+        let (size, data) = match size {
+            0..=0x7f => (usize::from(size), data),
+            0x81 => {
+                let (&size, data) = data.split_first()?;
+                (usize::from(size), data)
+            }
+            _ => return None,
+        };
+        if data.len() < size {
+            return None;
+        }
+        Some(data.split_at(size))
+    }
+
+    let (seq, rest) = take_tlv(der, 0x30)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let mut res = Vec::with_capacity(2 * len);
+    let mut seq = seq;
+    for _ in 0..2 {
+        let (mut int, rest) = take_tlv(seq, 0x02)?;
+        while let Some((&0, tail)) = int.split_first() {
+            int = tail;
+        }
+        if int.len() > len {
+            return None;
+        }
+        res.resize(res.len() + len - int.len(), 0);
+        res.extend_from_slice(int);
+        seq = rest;
+    }
+    if !seq.is_empty() {
+        return None;
+    }
+    Some(res)
+}
+
+/// Returns the RSA exponent and modulus from a DNSKEY public key.
+///
+/// The format is defined in RFC 3110, section 2.
+fn rsa_exponent_modulus(public_key: &[u8]) -> Option<(&[u8], &[u8])> {
+    if public_key.len() <= 3 {
+        return None;
+    }
+    let (pos, exp_len) = match public_key[0] {
+        0 => (
+            3,
+            (usize::from(public_key[1]) << 8) | usize::from(public_key[2]),
+        ),
+        len => (1, usize::from(len)),
+    };
+    if public_key.len() < pos + exp_len {
+        return None;
+    }
+    Some(public_key[pos..].split_at(exp_len))
+}
+
+/// Returns the size in bits of the modulus of an RSA DNSKEY public key.
+///
+/// Returns `None` if the key is malformed.
+pub fn rsa_key_bits(public_key: &[u8]) -> Option<usize> {
+    let (_, modulus) = rsa_exponent_modulus(public_key)?;
+    let modulus = match modulus.iter().position(|&octet| octet != 0) {
+        Some(pos) => &modulus[pos..],
+        None => return Some(0),
+    };
+    Some(modulus.len() * 8 - modulus[0].leading_zeros() as usize)
+}
+
+//============ Error Types ===================================================
+
+//------------ KeyMetadataError ----------------------------------------------
+
+/// The key metadata could not be parsed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyMetadataError {
+    /// A line did not contain a field name and a value.
+    BadLine,
+
+    /// The value of a field was invalid.
+    BadValue,
+
+    /// A required field was missing.
+    MissingField,
+}
+
+//--- Display and Error
+
+impl fmt::Display for KeyMetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyMetadataError::BadLine => f.write_str("malformed line"),
+            KeyMetadataError::BadValue => f.write_str("invalid value"),
+            KeyMetadataError::MissingField => {
+                f.write_str("missing required field")
+            }
+        }
+    }
+}
+
+impl error::Error for KeyMetadataError {}
+
+//------------ VerifyError ---------------------------------------------------
+
+/// A signature could not be verified.
+#[cfg(feature = "ring")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerifyError {
+    /// The key’s algorithm is not supported.
+    UnsupportedAlgorithm,
+
+    /// The public key is malformed.
+    InvalidKey,
+
+    /// The signature does not match the data.
+    BadSignature,
+}
+
+//--- Display and Error
+
+#[cfg(feature = "ring")]
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::UnsupportedAlgorithm => {
+                f.write_str("unsupported algorithm")
+            }
+            VerifyError::InvalidKey => f.write_str("invalid public key"),
+            VerifyError::BadSignature => f.write_str("bad signature"),
+        }
+    }
+}
+
+#[cfg(feature = "ring")]
+impl error::Error for VerifyError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_tag_collisions() {
+        let keys = [
+            Dnskey::new(257, 3, SecAlg::RsaSha256, vec![1, 0]),
+            Dnskey::new(257, 3, SecAlg::RsaSha256, vec![0, 0, 1, 0]),
+            Dnskey::new(257, 3, SecAlg::RsaSha256, vec![0, 2]),
+            Dnskey::new(257, 3, SecAlg::EcdsaP256Sha256, vec![0, 1]),
+        ];
+        assert_eq!(keys[0].key_tag(), keys[1].key_tag());
+        let collisions = super::key_tag_collisions(&keys);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].len(), 2);
+    }
+
+    #[test]
+    fn key_policy() {
+        // An RSA key with exponent 65537 and a modulus of the given bits.
+        let rsa = |alg, bits: usize| {
+            let mut key = vec![3, 1, 0, 1, 0x80];
+            key.resize(4 + bits / 8, 0);
+            Dnskey::new(256, 3, alg, key)
+        };
+        assert_eq!(
+            rsa_key_bits(rsa(SecAlg::RsaSha256, 2048).public_key()),
+            Some(2048)
+        );
+        assert_eq!(rsa_key_bits(&[3, 1, 0, 1, 0, 0x7f]), Some(7));
+        assert_eq!(rsa_key_bits(&[3, 1, 0]), None);
+
+        let keys = [
+            rsa(SecAlg::RsaSha256, 2048),
+            rsa(SecAlg::RsaSha256, 1024),
+            rsa(SecAlg::RsaSha1, 2048),
+            rsa(SecAlg::RsaMd5, 2048),
+            Dnskey::new(256, 3, SecAlg::Dsa, vec![0; 20]),
+            Dnskey::new(256, 3, SecAlg::EcdsaP256Sha256, vec![0; 64]),
+            Dnskey::new(256, 3, SecAlg::PrivateDns, vec![0; 64]),
+        ];
+        let weaknesses = |policy: KeyPolicy| {
+            policy
+                .check_keys(&keys)
+                .into_iter()
+                .map(|weak| (weak.algorithm, weak.weakness))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            weaknesses(KeyPolicy::strict()),
+            [
+                (SecAlg::RsaSha256, Weakness::ShortRsaKey(1024)),
+                (
+                    SecAlg::RsaSha1,
+                    Weakness::Algorithm(Requirement::NotRecommended)
+                ),
+                (SecAlg::RsaMd5, Weakness::Algorithm(Requirement::MustNot)),
+                (SecAlg::Dsa, Weakness::Algorithm(Requirement::MustNot)),
+                (SecAlg::PrivateDns, Weakness::UnlistedAlgorithm),
+            ]
+        );
+        assert_eq!(
+            weaknesses(KeyPolicy::relaxed()),
+            [
+                (SecAlg::RsaMd5, Weakness::Algorithm(Requirement::MustNot)),
+                (SecAlg::Dsa, Weakness::Algorithm(Requirement::MustNot)),
+            ]
+        );
+        assert_eq!(weaknesses(KeyPolicy::permissive()), []);
+    }
+
+    #[test]
+    fn key_metadata() {
+        let dnskey = Dnskey::new(257, 3, SecAlg::EcdsaP256Sha256, vec![1, 2]);
+        let mut meta =
+            KeyMetadata::new(&dnskey, Time32::from_int(1_600_000_000));
+        assert!(meta.matches(&dnskey));
+        assert!(!meta.is_published_at(Time32::from_int(1_600_000_000)));
+        meta.state = KeyState::Active;
+        meta.publish = Some(Time32::from_int(1_600_000_000));
+        meta.activate = Some(Time32::from_int(1_600_086_400));
+        meta.inactive = Some(Time32::from_int(1_700_000_000));
+        assert!(meta.is_published_at(Time32::from_int(1_600_000_000)));
+        assert!(!meta.is_active_at(Time32::from_int(1_600_000_000)));
+        assert!(meta.is_active_at(Time32::from_int(1_600_086_400)));
+        assert!(!meta.is_active_at(Time32::from_int(1_700_000_000)));
+        assert!(meta.is_published_at(Time32::from_int(1_700_000_000)));
+
+        let text = format!("{}", meta);
+        assert_eq!(
+            text,
+            "; DNSSEC key state\n\
+             Algorithm: 13\n\
+             KeyTag: 1296\n\
+             Flags: 257\n\
+             State: active\n\
+             Created: 20200913122640\n\
+             Publish: 20200913122640\n\
+             Activate: 20200914122640\n\
+             Inactive: 20231114221320\n"
+        );
+        assert_eq!(KeyMetadata::from_str(&text), Ok(meta));
+
+        assert_eq!(
+            KeyMetadata::from_str("Algorithm: 13\nKeyTag: 1\nFlags: 256\n"),
+            Err(KeyMetadataError::MissingField)
+        );
+        assert_eq!(
+            KeyMetadata::from_str("Algorithm 13\n"),
+            Err(KeyMetadataError::BadLine)
+        );
+        assert_eq!(
+            KeyMetadata::from_str(
+                "Algorithm: 13\nKeyTag: 1\nFlags: 256\nState: gone\n"
+            ),
+            Err(KeyMetadataError::BadValue)
+        );
+    }
+
+    #[test]
+    fn ecdsa_der_to_fixed() {
+        let der =
+            [0x30, 0x08, 0x02, 0x02, 0x00, 0x81, 0x02, 0x02, 0x01, 0x02];
+        assert_eq!(
+            super::ecdsa_der_to_fixed(&der, 4),
+            Some(vec![0, 0, 0, 0x81, 0, 0, 1, 2])
+        );
+        assert_eq!(super::ecdsa_der_to_fixed(&der, 1), None);
+        assert_eq!(super::ecdsa_der_to_fixed(&der[..9], 4), None);
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn verify_ecdsa() {
+        use crate::sign::ring::Key;
+        use ::ring::rand::SystemRandom;
+
+        let rng = SystemRandom::new();
+        let key = Key::throwaway_13(257, &rng).unwrap();
+        let dnskey = key.dnskey().unwrap();
+        let sig = key.sign(b"foo").unwrap();
+        assert_eq!(verify(&dnskey, b"foo", sig.as_ref()), Ok(()));
+        assert_eq!(
+            verify(&dnskey, b"bar", sig.as_ref()),
+            Err(VerifyError::BadSignature)
+        );
+    }
 }
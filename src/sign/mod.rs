@@ -3,7 +3,14 @@
 //! **This module is experimental and likely to change significantly.**
 #![cfg(feature = "sign")]
 
+pub mod diff;
+#[cfg(feature = "master")]
+pub mod extsort;
+pub mod inline;
 pub mod key;
+pub mod multi;
+pub mod offline;
 //pub mod openssl;
 pub mod records;
 pub mod ring;
+pub mod zone;
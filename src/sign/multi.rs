@@ -0,0 +1,539 @@
+//! Coordinating multiple signers of a zone.
+//!
+//! [RFC 8901] describes how a zone can be served and signed by several
+//! independent providers at once. In the common model, each provider signs
+//! the zone with its own zone signing key. Since a resolver may receive
+//! the DNSKEY RRset from one provider and signatures from another, each
+//! provider’s DNSKEY RRset needs to include the zone signing keys of all
+//! other providers. Likewise, the CDS and CDNSKEY RRsets used to maintain
+//! the DS RRset at the parent need to be identical at all providers and
+//! cover the key signing keys of all of them.
+//!
+//! This module provides [`MultiSigner`] which collects the key material
+//! published by each provider as a [`Signer`], checks that the providers
+//! are consistent with each other via [`MultiSigner::check`], and
+//! calculates the combined RRsets each provider has to publish.
+//!
+//! Keys are considered key signing keys if they have the Secure Entry
+//! Point flag set and zone signing keys otherwise.
+//!
+//! [RFC 8901]: https://tools.ietf.org/html/rfc8901
+//! [`MultiSigner`]: struct.MultiSigner.html
+//! [`MultiSigner::check`]: struct.MultiSigner.html#method.check
+//! [`Signer`]: struct.Signer.html
+
+use super::key::key_tag_collisions;
+use crate::base::iana::SecAlg;
+use crate::rdata::{Cdnskey, Cds, Dnskey};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Signer --------------------------------------------------------
+
+/// The key material published by a single signer of a zone.
+#[derive(Clone)]
+pub struct Signer<Octets> {
+    /// The DNSKEY RRset of the signer.
+    dnskeys: Vec<Dnskey<Octets>>,
+
+    /// The CDS RRset of the signer.
+    cds: Vec<Cds<Octets>>,
+
+    /// The CDNSKEY RRset of the signer.
+    cdnskeys: Vec<Cdnskey<Octets>>,
+}
+
+impl<Octets> Signer<Octets> {
+    /// Creates a new signer without any records.
+    pub fn new() -> Self {
+        Signer {
+            dnskeys: Vec::new(),
+            cds: Vec::new(),
+            cdnskeys: Vec::new(),
+        }
+    }
+
+    /// Adds a record to the signer’s DNSKEY RRset.
+    pub fn push_dnskey(&mut self, dnskey: Dnskey<Octets>) {
+        self.dnskeys.push(dnskey)
+    }
+
+    /// Adds a record to the signer’s CDS RRset.
+    pub fn push_cds(&mut self, cds: Cds<Octets>) {
+        self.cds.push(cds)
+    }
+
+    /// Adds a record to the signer’s CDNSKEY RRset.
+    pub fn push_cdnskey(&mut self, cdnskey: Cdnskey<Octets>) {
+        self.cdnskeys.push(cdnskey)
+    }
+
+    /// Returns the signer’s DNSKEY RRset.
+    pub fn dnskeys(&self) -> &[Dnskey<Octets>] {
+        &self.dnskeys
+    }
+
+    /// Returns the signer’s CDS RRset.
+    pub fn cds(&self) -> &[Cds<Octets>] {
+        &self.cds
+    }
+
+    /// Returns the signer’s CDNSKEY RRset.
+    pub fn cdnskeys(&self) -> &[Cdnskey<Octets>] {
+        &self.cdnskeys
+    }
+
+    /// Returns an iterator over the key signing keys of the signer.
+    pub fn ksks(&self) -> impl Iterator<Item = &Dnskey<Octets>> {
+        self.dnskeys
+            .iter()
+            .filter(|key| key.is_secure_entry_point())
+    }
+
+    /// Returns an iterator over the zone signing keys of the signer.
+    pub fn zsks(&self) -> impl Iterator<Item = &Dnskey<Octets>> {
+        self.dnskeys
+            .iter()
+            .filter(|key| !key.is_secure_entry_point())
+    }
+}
+
+//--- Default and Debug
+
+impl<Octets> Default for Signer<Octets> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Signer<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Signer")
+            .field("dnskeys", &self.dnskeys)
+            .field("cds", &self.cds)
+            .field("cdnskeys", &self.cdnskeys)
+            .finish()
+    }
+}
+
+//------------ MultiSigner ---------------------------------------------------
+
+/// The signers of a zone signed by multiple independent providers.
+///
+/// Signers are identified by their index, i.e., the order in which they
+/// were added.
+#[derive(Clone)]
+pub struct MultiSigner<Octets> {
+    signers: Vec<Signer<Octets>>,
+}
+
+impl<Octets> MultiSigner<Octets> {
+    /// Creates a new value without any signers.
+    pub fn new() -> Self {
+        MultiSigner {
+            signers: Vec::new(),
+        }
+    }
+
+    /// Adds a signer and returns its index.
+    pub fn push(&mut self, signer: Signer<Octets>) -> usize {
+        self.signers.push(signer);
+        self.signers.len() - 1
+    }
+
+    /// Returns the signers.
+    pub fn signers(&self) -> &[Signer<Octets>] {
+        &self.signers
+    }
+
+    /// Returns the number of signers.
+    pub fn len(&self) -> usize {
+        self.signers.len()
+    }
+
+    /// Returns whether there are no signers.
+    pub fn is_empty(&self) -> bool {
+        self.signers.is_empty()
+    }
+}
+
+impl<Octets: AsRef<[u8]> + Clone> MultiSigner<Octets> {
+    /// Returns the union of the DNSKEY RRsets of all signers.
+    pub fn merged_dnskeys(&self) -> Vec<Dnskey<Octets>> {
+        merge(self.signers.iter().flat_map(|signer| signer.dnskeys()))
+    }
+
+    /// Returns the CDS RRset all signers need to publish.
+    ///
+    /// This is the union of the CDS RRsets of all signers.
+    pub fn merged_cds(&self) -> Vec<Cds<Octets>> {
+        merge(self.signers.iter().flat_map(|signer| signer.cds()))
+    }
+
+    /// Returns the CDNSKEY RRset all signers need to publish.
+    ///
+    /// This is the union of the CDNSKEY RRsets of all signers.
+    pub fn merged_cdnskeys(&self) -> Vec<Cdnskey<Octets>> {
+        merge(self.signers.iter().flat_map(|signer| signer.cdnskeys()))
+    }
+
+    /// Returns the DNSKEY RRset the signer `index` needs to publish.
+    ///
+    /// This is the signer’s own DNSKEY RRset plus the zone signing keys
+    /// of all other signers.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if there is no signer with the given index.
+    pub fn publish_dnskeys(&self, index: usize) -> Vec<Dnskey<Octets>> {
+        merge(
+            self.signers[index].dnskeys().iter().chain(
+                self.signers
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != index)
+                    .flat_map(|(_, signer)| signer.zsks()),
+            ),
+        )
+    }
+
+    /// Checks that the signers are consistent with each other.
+    ///
+    /// Returns a list of all problems found. If the list is empty, the
+    /// signers are consistent.
+    pub fn check(&self) -> Vec<Inconsistency> {
+        let mut res = Vec::new();
+        let dnskeys = self.merged_dnskeys();
+        let cds = self.merged_cds();
+        let cdnskeys = self.merged_cdnskeys();
+
+        // All signers need to use the same algorithms.
+        let algorithms = |signer: &Signer<Octets>| {
+            let mut res: Vec<_> = signer
+                .dnskeys()
+                .iter()
+                .map(|key| key.algorithm().to_int())
+                .collect();
+            res.sort_unstable();
+            res.dedup();
+            res
+        };
+        if let Some(first) = self.signers.first() {
+            let first = algorithms(first);
+            for (index, signer) in self.signers.iter().enumerate().skip(1) {
+                if algorithms(signer) != first {
+                    res.push(Inconsistency::AlgorithmMismatch {
+                        signer: index,
+                    })
+                }
+            }
+        }
+
+        for (index, signer) in self.signers.iter().enumerate() {
+            // Each signer needs to publish the ZSKs of all others.
+            for (other, other_signer) in self.signers.iter().enumerate() {
+                if other == index {
+                    continue;
+                }
+                for key in other_signer.zsks() {
+                    if !signer.dnskeys().contains(key) {
+                        res.push(Inconsistency::MissingZsk {
+                            signer: index,
+                            owner: other,
+                            algorithm: key.algorithm(),
+                            key_tag: key.key_tag(),
+                        })
+                    }
+                }
+            }
+
+            // CDS and CDNSKEY RRsets need to be identical everywhere.
+            if !cds.is_empty() && merge(signer.cds()) != cds {
+                res.push(Inconsistency::CdsMismatch { signer: index })
+            }
+            if !cdnskeys.is_empty() && merge(signer.cdnskeys()) != cdnskeys {
+                res.push(Inconsistency::CdnskeyMismatch { signer: index })
+            }
+
+            // CDS and CDNSKEY records need to refer to known KSKs. A
+            // record with algorithm 0 requests deletion of the DS RRset
+            // and doesn’t refer to a key.
+            for item in signer.cds() {
+                if item.algorithm().to_int() == 0 {
+                    continue;
+                }
+                if !dnskeys.iter().any(|key| {
+                    key.is_secure_entry_point()
+                        && key.algorithm() == item.algorithm()
+                        && key.key_tag() == item.key_tag()
+                }) {
+                    res.push(Inconsistency::UnknownCds {
+                        signer: index,
+                        algorithm: item.algorithm(),
+                        key_tag: item.key_tag(),
+                    })
+                }
+            }
+            for item in signer.cdnskeys() {
+                if item.algorithm().to_int() == 0 {
+                    continue;
+                }
+                let item = Dnskey::new(
+                    item.flags(),
+                    item.protocol(),
+                    item.algorithm(),
+                    item.public_key().clone(),
+                );
+                if !item.is_secure_entry_point() || !dnskeys.contains(&item) {
+                    res.push(Inconsistency::UnknownCdnskey {
+                        signer: index,
+                        algorithm: item.algorithm(),
+                        key_tag: item.key_tag(),
+                    })
+                }
+            }
+        }
+
+        // Keys of different signers must be distinguishable.
+        for keys in key_tag_collisions(&dnskeys) {
+            res.push(Inconsistency::KeyTagCollision {
+                algorithm: keys[0].algorithm(),
+                key_tag: keys[0].key_tag(),
+            })
+        }
+
+        res
+    }
+}
+
+//--- Default and Debug
+
+impl<Octets> Default for MultiSigner<Octets> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for MultiSigner<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultiSigner")
+            .field("signers", &self.signers)
+            .finish()
+    }
+}
+
+/// Returns the sorted union of the given records.
+fn merge<'a, T, I>(iter: I) -> Vec<T>
+where
+    T: Clone + Ord + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut res: Vec<_> = iter.into_iter().cloned().collect();
+    res.sort();
+    res.dedup();
+    res
+}
+
+//------------ Inconsistency -------------------------------------------------
+
+/// A problem found when checking multiple signers for consistency.
+///
+/// Signers are identified by their index in the [`MultiSigner`].
+///
+/// [`MultiSigner`]: struct.MultiSigner.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Inconsistency {
+    /// A signer uses a different set of algorithms than the first signer.
+    AlgorithmMismatch { signer: usize },
+
+    /// A signer doesn’t publish a zone signing key of another signer.
+    MissingZsk {
+        signer: usize,
+        owner: usize,
+        algorithm: SecAlg,
+        key_tag: u16,
+    },
+
+    /// A signer’s CDS RRset differs from the combined CDS RRset.
+    CdsMismatch { signer: usize },
+
+    /// A signer’s CDNSKEY RRset differs from the combined CDNSKEY RRset.
+    CdnskeyMismatch { signer: usize },
+
+    /// A CDS record doesn’t refer to a key signing key of any signer.
+    UnknownCds {
+        signer: usize,
+        algorithm: SecAlg,
+        key_tag: u16,
+    },
+
+    /// A CDNSKEY record isn’t a key signing key of any signer.
+    UnknownCdnskey {
+        signer: usize,
+        algorithm: SecAlg,
+        key_tag: u16,
+    },
+
+    /// Different keys share the same algorithm and key tag.
+    KeyTagCollision { algorithm: SecAlg, key_tag: u16 },
+}
+
+//--- Display and Error
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Inconsistency::AlgorithmMismatch { signer } => {
+                write!(f, "signer {} uses different algorithms", signer)
+            }
+            Inconsistency::MissingZsk {
+                signer,
+                owner,
+                algorithm,
+                key_tag,
+            } => write!(
+                f,
+                "signer {} is missing ZSK {}/{} of signer {}",
+                signer, algorithm, key_tag, owner
+            ),
+            Inconsistency::CdsMismatch { signer } => {
+                write!(f, "signer {} has a different CDS RRset", signer)
+            }
+            Inconsistency::CdnskeyMismatch { signer } => {
+                write!(f, "signer {} has a different CDNSKEY RRset", signer)
+            }
+            Inconsistency::UnknownCds {
+                signer,
+                algorithm,
+                key_tag,
+            } => write!(
+                f,
+                "signer {} has CDS for unknown key {}/{}",
+                signer, algorithm, key_tag
+            ),
+            Inconsistency::UnknownCdnskey {
+                signer,
+                algorithm,
+                key_tag,
+            } => write!(
+                f,
+                "signer {} has CDNSKEY for unknown key {}/{}",
+                signer, algorithm, key_tag
+            ),
+            Inconsistency::KeyTagCollision { algorithm, key_tag } => {
+                write!(f, "key tag collision for {}/{}", algorithm, key_tag)
+            }
+        }
+    }
+}
+
+impl error::Error for Inconsistency {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::DigestAlg;
+
+    fn key(flags: u16, key: &[u8]) -> Dnskey<Vec<u8>> {
+        Dnskey::new(flags, 3, SecAlg::EcdsaP256Sha256, key.into())
+    }
+
+    fn cds(key: &Dnskey<Vec<u8>>) -> Cds<Vec<u8>> {
+        Cds::new(key.key_tag(), key.algorithm(), DigestAlg::Sha256, vec![1])
+    }
+
+    fn cdnskey(key: &Dnskey<Vec<u8>>) -> Cdnskey<Vec<u8>> {
+        Cdnskey::new(
+            key.flags(),
+            key.protocol(),
+            key.algorithm(),
+            key.public_key().clone(),
+        )
+    }
+
+    /// Returns two consistent signers.
+    fn signers() -> MultiSigner<Vec<u8>> {
+        let (ksk1, zsk1) = (key(257, b"ksk1"), key(256, b"zsk1"));
+        let (ksk2, zsk2) = (key(257, b"ksk2"), key(256, b"zsk2"));
+        let mut res = MultiSigner::new();
+        for (ksk, zsk) in &[(&ksk1, &zsk1), (&ksk2, &zsk2)] {
+            let mut signer = Signer::new();
+            signer.push_dnskey((*ksk).clone());
+            signer.push_dnskey((*zsk).clone());
+            signer.push_cds(cds(&ksk1));
+            signer.push_cds(cds(&ksk2));
+            signer.push_cdnskey(cdnskey(&ksk1));
+            signer.push_cdnskey(cdnskey(&ksk2));
+            res.push(signer);
+        }
+        res
+    }
+
+    #[test]
+    fn publish() {
+        let signers = signers();
+        assert_eq!(signers.merged_dnskeys().len(), 4);
+        assert_eq!(signers.merged_cds().len(), 2);
+        assert_eq!(signers.merged_cdnskeys().len(), 2);
+
+        let published = signers.publish_dnskeys(0);
+        assert_eq!(published.len(), 3);
+        assert!(published.contains(&key(257, b"ksk1")));
+        assert!(published.contains(&key(256, b"zsk1")));
+        assert!(published.contains(&key(256, b"zsk2")));
+        assert!(!published.contains(&key(257, b"ksk2")));
+
+        // Everything is consistent once each signer publishes the ZSKs of
+        // the other one.
+        let mut fixed = MultiSigner::new();
+        for index in 0..signers.len() {
+            let mut signer = signers.signers()[index].clone();
+            signer.dnskeys = signers.publish_dnskeys(index);
+            fixed.push(signer);
+        }
+        assert_eq!(fixed.check(), []);
+    }
+
+    #[test]
+    fn check() {
+        let mut signers = signers();
+        let zsk2 = key(256, b"zsk2");
+        let ksk3 = Dnskey::new(257, 3, SecAlg::Ed25519, b"ksk3".to_vec());
+        signers.signers[1].cds.pop();
+        signers.signers[1].push_cdnskey(cdnskey(&ksk3));
+        let res = signers.check();
+        assert_eq!(
+            res,
+            [
+                Inconsistency::MissingZsk {
+                    signer: 0,
+                    owner: 1,
+                    algorithm: SecAlg::EcdsaP256Sha256,
+                    key_tag: zsk2.key_tag(),
+                },
+                Inconsistency::CdnskeyMismatch { signer: 0 },
+                Inconsistency::MissingZsk {
+                    signer: 1,
+                    owner: 0,
+                    algorithm: SecAlg::EcdsaP256Sha256,
+                    key_tag: key(256, b"zsk1").key_tag(),
+                },
+                Inconsistency::CdsMismatch { signer: 1 },
+                Inconsistency::UnknownCdnskey {
+                    signer: 1,
+                    algorithm: SecAlg::Ed25519,
+                    key_tag: ksk3.key_tag(),
+                },
+            ]
+        );
+
+        let mut signer = Signer::new();
+        signer.push_dnskey(ksk3);
+        signers.push(signer);
+        assert!(signers
+            .check()
+            .contains(&Inconsistency::AlgorithmMismatch { signer: 2 }));
+    }
+}
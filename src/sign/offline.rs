@@ -0,0 +1,285 @@
+//! Offline signing of RRsets.
+//!
+//! In some setups, the key signing key is kept offline and only used in
+//! dedicated signing ceremonies. The zone signing key signs the zone online
+//! while the DNSKEY RRset is signed offline with the key signing key.
+//!
+//! This module supports such a split workflow. A [`SigningRequest`] is
+//! created for the RRset to be signed. It contains the canonical data to be
+//! signed which can be exported and handed to the offline signer. The
+//! resulting signature can then be turned into an RRSIG record via
+//! [`SigningRequest::into_record`]. Alternatively, if the offline signer
+//! produces complete RRSIG records, these can be checked against the
+//! request via [`SigningRequest::import`].
+//!
+//! [`SigningRequest`]: struct.SigningRequest.html
+//! [`SigningRequest::into_record`]: struct.SigningRequest.html#method.into_record
+//! [`SigningRequest::import`]: struct.SigningRequest.html#method.import
+
+use super::records::{FamilyName, Rrset, SortedRecords};
+use crate::base::iana::{Class, Rtype, SecAlg};
+use crate::base::name::ToDname;
+use crate::base::rdata::RecordData;
+use crate::base::record::Record;
+use crate::base::time32::Time32;
+use crate::rdata::rfc4034::ProtoRrsig;
+use crate::rdata::Rrsig;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ SigningRequest ------------------------------------------------
+
+/// The data necessary to create the signature for an RRset.
+#[derive(Clone)]
+pub struct SigningRequest<N, SignerName> {
+    /// The owner name of the RRset.
+    owner: N,
+
+    /// The class of the RRset.
+    class: Class,
+
+    /// The RRSIG record data without the signature.
+    rrsig: ProtoRrsig<SignerName>,
+
+    /// The canonical form of the data to be signed.
+    data: Vec<u8>,
+}
+
+impl<N, SignerName> SigningRequest<N, SignerName> {
+    /// Creates a signing request for an RRset.
+    pub fn new<D>(
+        rrset: &Rrset<N, D>,
+        algorithm: SecAlg,
+        key_tag: u16,
+        expiration: Time32,
+        inception: Time32,
+        signer_name: SignerName,
+    ) -> Self
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        SignerName: ToDname,
+    {
+        let rrsig = ProtoRrsig::new(
+            rrset.rtype(),
+            algorithm,
+            rrset.owner().rrsig_label_count(),
+            rrset.ttl(),
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+        );
+        let mut data = Vec::new();
+        rrset.compose_signed_data(&rrsig, &mut data).unwrap();
+        SigningRequest {
+            owner: rrset.owner().clone(),
+            class: rrset.class(),
+            rrsig,
+            data,
+        }
+    }
+
+    /// Creates a signing request for the DNSKEY RRset at the apex.
+    ///
+    /// The apex’s owner name is used as the signer name. Returns `None` if
+    /// there is no DNSKEY RRset at the apex.
+    pub fn dnskeys<D>(
+        records: &SortedRecords<N, D>,
+        apex: &FamilyName<SignerName>,
+        algorithm: SecAlg,
+        key_tag: u16,
+        expiration: Time32,
+        inception: Time32,
+    ) -> Option<Self>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        SignerName: ToDname + Clone,
+    {
+        records
+            .rrsets()
+            .find(|rrset| {
+                rrset.rtype() == Rtype::Dnskey && rrset.family_name() == *apex
+            })
+            .map(|rrset| {
+                Self::new(
+                    &rrset,
+                    algorithm,
+                    key_tag,
+                    expiration,
+                    inception,
+                    apex.owner().clone(),
+                )
+            })
+    }
+
+    /// Returns the owner name of the RRset to be signed.
+    pub fn owner(&self) -> &N {
+        &self.owner
+    }
+
+    /// Returns the class of the RRset to be signed.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Returns the RRSIG record data without the signature.
+    pub fn rrsig(&self) -> &ProtoRrsig<SignerName> {
+        &self.rrsig
+    }
+
+    /// Returns the data to be signed.
+    ///
+    /// This is the RRSIG record data without the signature followed by the
+    /// records of the RRset in canonical form as described in RFC 4034,
+    /// section 3.1.8.1.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Creates the RRSIG record from a signature over the data.
+    pub fn into_record<Octets>(
+        self,
+        signature: Octets,
+    ) -> Record<N, Rrsig<Octets, SignerName>> {
+        let ttl = self.rrsig.original_ttl();
+        Record::new(
+            self.owner,
+            self.class,
+            ttl,
+            self.rrsig.into_rrsig(signature),
+        )
+    }
+
+    /// Imports an RRSIG record created by an offline signer.
+    ///
+    /// The record is checked against the request and is only accepted if
+    /// owner name, class, TTL, and all RRSIG fields other than the signature
+    /// match the request. Note that the signature itself is not verified.
+    pub fn import<Octets, NN, SN>(
+        self,
+        record: Record<NN, Rrsig<Octets, SN>>,
+    ) -> Result<Record<N, Rrsig<Octets, SignerName>>, ImportError>
+    where
+        N: ToDname,
+        NN: ToDname,
+        SignerName: ToDname,
+        SN: ToDname,
+    {
+        if !record.owner().name_eq(&self.owner) {
+            return Err(ImportError::Owner);
+        }
+        if record.class() != self.class {
+            return Err(ImportError::Class);
+        }
+        let rrsig = record.data();
+        if rrsig.type_covered() != self.rrsig.type_covered() {
+            return Err(ImportError::TypeCovered);
+        }
+        if rrsig.algorithm() != self.rrsig.algorithm()
+            || rrsig.key_tag() != self.rrsig.key_tag()
+            || !rrsig.signer_name().name_eq(self.rrsig.signer_name())
+        {
+            return Err(ImportError::Key);
+        }
+        if rrsig.labels() != self.rrsig.labels()
+            || rrsig.original_ttl() != self.rrsig.original_ttl()
+            || record.ttl() != self.rrsig.original_ttl()
+        {
+            return Err(ImportError::Rrset);
+        }
+        if rrsig.expiration() != self.rrsig.expiration()
+            || rrsig.inception() != self.rrsig.inception()
+        {
+            return Err(ImportError::Validity);
+        }
+        Ok(self.into_record(record.into_data().into_signature()))
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ ImportError ---------------------------------------------------
+
+/// An imported RRSIG record does not match the signing request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImportError {
+    /// The owner name differs.
+    Owner,
+
+    /// The class differs.
+    Class,
+
+    /// The record type covered differs.
+    TypeCovered,
+
+    /// The algorithm, key tag, or signer name differ.
+    Key,
+
+    /// The label count or TTLs differ.
+    Rrset,
+
+    /// The inception or expiration times differ.
+    Validity,
+}
+
+//--- Display and Error
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImportError::Owner => f.write_str("owner name mismatch"),
+            ImportError::Class => f.write_str("class mismatch"),
+            ImportError::TypeCovered => f.write_str("type covered mismatch"),
+            ImportError::Key => f.write_str("signing key mismatch"),
+            ImportError::Rrset => f.write_str("label count or TTL mismatch"),
+            ImportError::Validity => f.write_str("validity period mismatch"),
+        }
+    }
+}
+
+impl error::Error for ImportError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use crate::rdata::Dnskey;
+    use core::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+
+    fn request() -> SigningRequest<Name, Name> {
+        let apex = Name::from_str("example.").unwrap();
+        let records: SortedRecords<_, _> = vec![Record::new(
+            apex.clone(),
+            Class::In,
+            3600,
+            Dnskey::new(257, 3, SecAlg::EcdsaP256Sha256, vec![1, 2, 3]),
+        )]
+        .into();
+        SigningRequest::dnskeys(
+            &records,
+            &FamilyName::new(apex, Class::In),
+            SecAlg::EcdsaP256Sha256,
+            12345,
+            Time32::from_int(2000),
+            Time32::from_int(1000),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn import() {
+        let signed = request().into_record(vec![4, 5, 6]);
+        let imported = request().import(signed.clone()).unwrap();
+        assert_eq!(imported.data().signature(), &[4, 5, 6]);
+
+        let mut bad = signed;
+        bad.set_ttl(60);
+        assert_eq!(request().import(bad).unwrap_err(), ImportError::Rrset);
+    }
+}
@@ -1,18 +1,37 @@
 //! Actual signing.
 
 use super::key::SigningKey;
+#[cfg(feature = "ring")]
+use crate::base::charstr::CharStr;
 use crate::base::cmp::CanonicalOrd;
+#[cfg(feature = "ring")]
+use crate::base::iana::Nsec3HashAlg;
 use crate::base::iana::{Class, Rtype};
-use crate::base::name::ToDname;
-use crate::base::octets::{Compose, EmptyBuilder, FromBuilder};
+#[cfg(feature = "ring")]
+use crate::base::name::DnameBuilder;
+#[cfg(feature = "bytes")]
+use crate::base::name::NamePool;
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{
+    Compose, EmptyBuilder, FromBuilder, OctetsBuilder, ShortBuf,
+};
 use crate::base::rdata::RecordData;
 use crate::base::record::Record;
-use crate::base::serial::Serial;
+use crate::base::time32::Time32;
 use crate::rdata::rfc4034::{ProtoRrsig, RtypeBitmap};
-use crate::rdata::{Dnskey, Ds, Nsec, Rrsig};
+use crate::rdata::{Dnskey, Ds, Nsec, Rrsig, Soa};
+#[cfg(feature = "ring")]
+use crate::rdata::{Nsec3, Nsec3param};
+#[cfg(feature = "ring")]
+use crate::utils::base32;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+#[cfg(feature = "ring")]
+use std::collections::BTreeMap;
+use std::convert::Infallible;
 use std::iter::FromIterator;
 use std::vec::Vec;
-use std::{fmt, io, slice};
+use std::{cmp, error, fmt, io, mem, slice};
 
 //------------ SortedRecords -------------------------------------------------
 
@@ -46,6 +65,156 @@ impl<N, D> SortedRecords<N, D> {
         }
     }
 
+    /// Inserts all records produced by an iterator.
+    ///
+    /// Rather than inserting the records one by one, the new records are
+    /// sorted first and then merged with the existing records in a single
+    /// pass. Records that are already present are not inserted but
+    /// returned instead, just as with `insert`.
+    pub fn insert_all<I>(&mut self, iter: I) -> Vec<Record<N, D>>
+    where
+        I: IntoIterator<Item = Record<N, D>>,
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        self.insert_all_with(iter, DuplicatePolicy::Collect)
+    }
+
+    /// Inserts all records produced by an iterator treating duplicates.
+    ///
+    /// This is like `insert_all` but duplicates – both of records already
+    /// present and within the new records – are treated according to
+    /// `policy`. Returns the records that were not kept but collected by
+    /// the policy.
+    pub fn insert_all_with<I>(
+        &mut self,
+        iter: I,
+        policy: DuplicatePolicy,
+    ) -> Vec<Record<N, D>>
+    where
+        I: IntoIterator<Item = Record<N, D>>,
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        let mut batch = iter
+            .into_iter()
+            .map(|record| (canonical_sort_key(&record), record))
+            .collect::<Vec<_>>();
+        batch.sort_by(|left, right| left.0.cmp(&right.0));
+
+        let mut res = Vec::new();
+        let mut records = Vec::<Record<N, D>>::with_capacity(batch.len());
+        let mut last_key = None;
+        for (key, record) in batch {
+            if last_key.as_ref() == Some(&key) {
+                policy.resolve(records.last_mut().unwrap(), record, &mut res);
+            } else {
+                records.push(record);
+                last_key = Some(key);
+            }
+        }
+        res.extend(self.merge_with(SortedRecords { records }, policy));
+        res
+    }
+
+    /// Merges the records of another set into this set.
+    ///
+    /// Both sets are walked in a single pass. Records of `other` that are
+    /// already present are dropped and returned.
+    pub fn merge(&mut self, other: Self) -> Vec<Record<N, D>>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        self.merge_with(other, DuplicatePolicy::Collect)
+    }
+
+    /// Merges the records of another set into this set treating duplicates.
+    ///
+    /// This is like `merge` but records of `other` that are already
+    /// present are treated according to `policy`. Returns the records that
+    /// were not kept but collected by the policy.
+    pub fn merge_with(
+        &mut self,
+        other: Self,
+        policy: DuplicatePolicy,
+    ) -> Vec<Record<N, D>>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        let mut res = Vec::new();
+        if self.records.is_empty() {
+            self.records = other.records;
+            return res;
+        }
+        let existing = mem::take(&mut self.records);
+        self.records.reserve(existing.len() + other.records.len());
+        let mut existing = existing.into_iter().peekable();
+        let mut other = other.records.into_iter().peekable();
+        loop {
+            let order = match (existing.peek(), other.peek()) {
+                (Some(left), Some(right)) => left.canonical_cmp(right),
+                (Some(_), None) => cmp::Ordering::Less,
+                (None, Some(_)) => cmp::Ordering::Greater,
+                (None, None) => break,
+            };
+            match order {
+                cmp::Ordering::Less => self.records.extend(existing.next()),
+                cmp::Ordering::Greater => self.records.extend(other.next()),
+                cmp::Ordering::Equal => {
+                    let mut record = existing.next().unwrap();
+                    policy.resolve(
+                        &mut record,
+                        other.next().unwrap(),
+                        &mut res,
+                    );
+                    self.records.push(record);
+                }
+            }
+        }
+        res
+    }
+
+    /// Removes duplicate records and normalizes TTLs within RRsets.
+    ///
+    /// Creating a set from a vec via `From` keeps all records, including
+    /// those that are equal in canonical order, i.e., only differ in their
+    /// TTL if at all. This method first makes the TTLs of each RRset
+    /// identical as `normalize_ttls` does and then removes all but the
+    /// first of these duplicates.
+    ///
+    /// This is safe for a zone that is already signed. RRSIG records get
+    /// the TTL of the RRset they cover but keep their original TTL field,
+    /// so the signatures stay valid. Only RRSIG records that are identical
+    /// apart from their TTL are removed as duplicates.
+    ///
+    /// Returns a report of what was changed. Note that the TTL changes
+    /// include those of duplicates that were removed afterwards.
+    pub fn dedup(&mut self) -> Dedup<N, D>
+    where
+        N: ToDname + Clone,
+        D: RecordData + CanonicalOrd,
+    {
+        let ttl_changes = self.normalize_ttls();
+        let mut removed = Vec::new();
+        for record in mem::take(&mut self.records) {
+            match self.records.last() {
+                Some(last)
+                    if last.canonical_cmp(&record)
+                        == cmp::Ordering::Equal =>
+                {
+                    removed.push(record)
+                }
+                _ => self.records.push(record),
+            }
+        }
+        Dedup {
+            removed,
+            ttl_changes,
+        }
+    }
+
     pub fn families(&self) -> RecordsIter<N, D> {
         RecordsIter::new(&self.records)
     }
@@ -54,6 +223,104 @@ impl<N, D> SortedRecords<N, D> {
         RrsetIter::new(&self.records)
     }
 
+    /// Returns an iterator classifying all records for the given apex.
+    ///
+    /// The iterator returns each record together with its
+    /// [`Classification`] which tells whether the record is authoritative
+    /// data of the zone, a delegation, glue, or occluded data that must
+    /// not be served or signed.
+    ///
+    /// [`Classification`]: enum.Classification.html
+    pub fn classify<'a, A>(
+        &'a self,
+        apex: &'a FamilyName<A>,
+    ) -> ClassifyIter<'a, N, D, A> {
+        ClassifyIter::new(self.families(), apex)
+    }
+
+    /// Returns an iterator classifying all RRsets for the given apex.
+    ///
+    /// This is the same as [`classify`] but for whole RRsets.
+    ///
+    /// [`classify`]: #method.classify
+    pub fn classify_rrsets<'a, A>(
+        &'a self,
+        apex: &'a FamilyName<A>,
+    ) -> ClassifyRrsetIter<'a, N, D, A> {
+        ClassifyRrsetIter::new(self.families(), apex)
+    }
+
+    /// Returns an iterator over the zone cuts of the zone with the given apex.
+    ///
+    /// Each item describes one delegation point below the apex together
+    /// with all records at and below it, including glue. Zone cuts that are
+    /// themselves below a zone cut or a DNAME are skipped.
+    pub fn zone_cuts<'a, A>(
+        &'a self,
+        apex: &'a FamilyName<A>,
+    ) -> ZoneCutIter<'a, N, D, A> {
+        ZoneCutIter::new(self.families(), apex)
+    }
+
+    /// Returns an iterator over all families at and below the given name.
+    ///
+    /// Since the records are in canonical order, the subtree is a
+    /// contiguous range of records which is located via a binary search.
+    pub fn subtree<A>(&self, root: &FamilyName<A>) -> RecordsIter<'_, N, D>
+    where
+        N: ToDname,
+        A: ToDname,
+    {
+        let start = self
+            .records
+            .binary_search_by(|record| {
+                match record
+                    .class()
+                    .cmp(&root.class)
+                    .then_with(|| record.owner().name_cmp(&root.owner))
+                {
+                    cmp::Ordering::Equal => cmp::Ordering::Greater,
+                    order => order,
+                }
+            })
+            .unwrap_or_else(|idx| idx);
+        let len = self.records[start..]
+            .iter()
+            .take_while(|record| {
+                record.class() == root.class
+                    && record.owner().ends_with(&root.owner)
+            })
+            .count();
+        RecordsIter::new(&self.records[start..start + len])
+    }
+
+    /// Removes all records for which `keep` returns `false`.
+    pub fn retain<F>(&mut self, keep: F)
+    where
+        F: FnMut(&Record<N, D>) -> bool,
+    {
+        self.records.retain(keep)
+    }
+
+    /// Makes the owner names of all records share storage via `pool`.
+    ///
+    /// Since all records of a family have the same owner name, this
+    /// reduces the memory used by a zone considerably. The savings can be
+    /// determined via the pool’s [`stats`]. Domain names in the record
+    /// data are not affected.
+    ///
+    /// [`stats`]: ../../base/name/struct.NamePool.html#method.stats
+    #[cfg(feature = "bytes")]
+    pub fn intern_owners(&mut self, pool: &mut NamePool)
+    where
+        N: ToDname + From<Dname<Bytes>>,
+    {
+        for record in &mut self.records {
+            let owner = pool.intern(record.owner());
+            record.set_owner(owner.into());
+        }
+    }
+
     pub fn find_soa(&self) -> Option<Rrset<N, D>>
     where
         N: ToDname,
@@ -67,23 +334,86 @@ impl<N, D> SortedRecords<N, D> {
         None
     }
 
+    /// Determines the apex of the zone from its SOA record.
+    ///
+    /// The zone must contain exactly one SOA record and all records must
+    /// be of the same class as the SOA record. Otherwise an error is
+    /// returned.
+    pub fn find_apex(&self) -> Result<FamilyName<N>, ApexError>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+    {
+        let mut apex = None;
+        for rrset in self.rrsets() {
+            if rrset.rtype() != Rtype::Soa {
+                continue;
+            }
+            if apex.is_some() || rrset.iter().len() > 1 {
+                return Err(ApexError::MultipleSoa);
+            }
+            apex = Some(rrset.family_name().cloned());
+        }
+        let apex = apex.ok_or(ApexError::NoSoa)?;
+        if let Some(record) = self
+            .records
+            .iter()
+            .find(|record| record.class() != apex.class())
+        {
+            return Err(ApexError::ClassMismatch(record.class()));
+        }
+        Ok(apex)
+    }
+
+    /// Signs the zone using the apex determined from its SOA record.
+    ///
+    /// This is the same as [`sign`] with the apex determined via
+    /// [`find_apex`].
+    ///
+    /// [`sign`]: #method.sign
+    /// [`find_apex`]: #method.find_apex
+    #[allow(clippy::type_complexity)]
+    pub fn sign_from_soa<Octets, Key>(
+        &self,
+        expiration: Time32,
+        inception: Time32,
+        key: Key,
+    ) -> Result<Vec<Record<N, Rrsig<Octets, N>>>, FromSoaError<Key::Error>>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        Key: SigningKey,
+        Octets: FromBuilder,
+        Octets::Builder: EmptyBuilder,
+    {
+        let apex = self.find_apex()?;
+        self.sign(&apex, expiration, inception, key)
+            .map_err(FromSoaError::Inner)
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn sign<Octets, Key, ApexName>(
         &self,
         apex: &FamilyName<ApexName>,
-        expiration: Serial,
-        inception: Serial,
+        expiration: Time32,
+        inception: Time32,
         key: Key,
     ) -> Result<Vec<Record<N, Rrsig<Octets, ApexName>>>, Key::Error>
     where
         N: ToDname + Clone,
         D: RecordData,
         Key: SigningKey,
-        Octets: From<Key::Signature>,
+        Octets: FromBuilder,
+        Octets::Builder: EmptyBuilder,
         ApexName: ToDname + Clone,
     {
         let mut res = Vec::new();
+
+        // The buffers are reused for all RRsets. The first one collects the
+        // data to be signed if the key needs it, the second one receives
+        // each record in turn.
         let mut buf = Vec::new();
+        let mut record_buf = Vec::new();
 
         // The owner name of a zone cut if we currently are at or below one.
         let mut cut: Option<FamilyName<N>> = None;
@@ -138,7 +468,6 @@ impl<N, D> SortedRecords<N, D> {
                 }
 
                 // Create the signature.
-                buf.clear();
                 let rrsig = ProtoRrsig::new(
                     rrset.rtype(),
                     key.algorithm()?,
@@ -149,23 +478,52 @@ impl<N, D> SortedRecords<N, D> {
                     key.key_tag()?,
                     apex.owner().clone(),
                 );
-                rrsig.compose_canonical(&mut buf).unwrap();
-                for record in rrset.iter() {
-                    record.compose_canonical(&mut buf).unwrap();
-                }
+                let signature = key.sign_incremental(&mut buf, |feed| {
+                    rrset
+                        .feed_signed_data(&rrsig, &mut record_buf, feed)
+                        .unwrap()
+                })?;
 
                 // Create and push the RRSIG record.
                 res.push(Record::new(
                     name.owner().clone(),
                     name.class(),
                     rrset.ttl(),
-                    rrsig.into_rrsig(key.sign(&buf)?.into()),
+                    rrsig.into_rrsig(octets_from_slice(signature.as_ref())),
                 ));
             }
         }
         Ok(res)
     }
 
+    /// Creates the NSEC records using the apex determined from the SOA.
+    ///
+    /// This is the same as [`nsecs`] with the apex determined via
+    /// [`find_apex`].
+    ///
+    /// [`nsecs`]: #method.nsecs
+    /// [`find_apex`]: #method.find_apex
+    #[allow(clippy::type_complexity)]
+    pub fn nsecs_from_soa<Octets>(
+        &self,
+        ttl: u32,
+    ) -> Result<Vec<Record<N, Nsec<Octets, N>>>, ApexError>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        Octets: FromBuilder,
+        Octets::Builder: EmptyBuilder,
+    {
+        let apex = self.find_apex()?;
+        Ok(self.nsecs(&apex, ttl))
+    }
+
+    /// Creates the NSEC records for the zone.
+    ///
+    /// An NSEC record is created for every authoritative name and every
+    /// delegation within the zone. Empty non-terminals don’t get an NSEC
+    /// record. They are proven to exist by the NSEC record of the name
+    /// preceding them whose next name is below the empty non-terminal.
     pub fn nsecs<Octets, ApexName>(
         &self,
         apex: &FamilyName<ApexName>,
@@ -246,103 +604,932 @@ impl<N, D> SortedRecords<N, D> {
         res
     }
 
-    pub fn write<W>(&self, target: &mut W) -> Result<(), io::Error>
+    /// Returns the empty non-terminals of the zone with the given apex.
+    ///
+    /// An empty non-terminal is a name that owns no records but has names
+    /// with records below it. Such names exist in the DNS and thus must
+    /// not be denied. With NSEC, they are covered implicitly by the NSEC
+    /// record of the preceding name. With NSEC3, they own an NSEC3 record
+    /// of their own, see [`nsec3s`].
+    ///
+    /// Only names within the zone are considered: names at or above the
+    /// apex and names below a zone cut are skipped. Names between the apex
+    /// and a zone cut, however, are included. The names are returned in
+    /// canonical order.
+    ///
+    /// [`nsec3s`]: #method.nsec3s
+    pub fn empty_non_terminals<Octets, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+    ) -> Vec<Dname<Octets>>
     where
-        N: fmt::Display,
-        D: RecordData + fmt::Display,
-        W: io::Write,
+        N: ToDname,
+        D: RecordData,
+        Octets: FromBuilder,
+        Octets::Builder: EmptyBuilder,
+        ApexName: ToDname,
     {
-        for record in &self.records {
-            writeln!(target, "{}", record)?;
-        }
-        Ok(())
-    }
-}
-
-impl<N, D> Default for SortedRecords<N, D> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl<N, D> From<Vec<Record<N, D>>> for SortedRecords<N, D>
-where
-    N: ToDname,
-    D: RecordData + CanonicalOrd,
-{
-    fn from(mut src: Vec<Record<N, D>>) -> Self {
-        src.sort_by(CanonicalOrd::canonical_cmp);
-        SortedRecords { records: src }
-    }
-}
+        let mut res = Vec::new();
+        let mut families = self.families();
+        families.skip_before(apex);
+        let mut cut: Option<&N> = None;
+        let mut prev: Option<&N> = None;
 
-impl<N, D> FromIterator<Record<N, D>> for SortedRecords<N, D>
-where
-    N: ToDname,
-    D: RecordData + CanonicalOrd,
-{
-    fn from_iter<T: IntoIterator<Item = Record<N, D>>>(iter: T) -> Self {
-        let mut res = Self::new();
-        for item in iter {
-            let _ = res.insert(item);
+        for family in families {
+            if !family.is_in_zone(apex) {
+                break;
+            }
+            if let Some(cut) = cut {
+                if family.owner().ends_with(cut) {
+                    continue;
+                }
+            }
+            cut = if family.is_zone_cut(apex) {
+                Some(family.slice[0].owner())
+            } else {
+                None
+            };
+            let _ = for_new_empty_non_terminals(
+                family.owner(),
+                prev,
+                apex.owner(),
+                |name| -> Result<(), Infallible> {
+                    // Domain names are short enough for any builder.
+                    res.push(name.to_dname().unwrap());
+                    Ok(())
+                },
+            );
+            prev = Some(family.slice[0].owner());
         }
         res
     }
-}
 
-impl<N, D> Extend<Record<N, D>> for SortedRecords<N, D>
-where
-    N: ToDname,
-    D: RecordData + CanonicalOrd,
-{
-    fn extend<T: IntoIterator<Item = Record<N, D>>>(&mut self, iter: T) {
-        for item in iter {
-            let _ = self.insert(item);
-        }
+    /// Creates the NSEC3 records for the zone.
+    ///
+    /// The records are created using the hash algorithm, iterations, and
+    /// salt given in `params`. An NSEC3 record is created for every
+    /// authoritative name, every delegation, and every empty non-terminal
+    /// within the zone. The type bitmap of the apex includes the NSEC3PARAM
+    /// record type, the NSEC3PARAM record itself needs to be added
+    /// separately.
+    ///
+    /// The records are returned ordered by their hashed owner name.
+    #[cfg(feature = "ring")]
+    #[allow(clippy::type_complexity)]
+    pub fn nsec3s<Octets, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        ttl: u32,
+        params: &Nsec3param<Octets>,
+    ) -> Result<Vec<Record<Dname<Octets>, Nsec3<Octets>>>, Nsec3Error>
+    where
+        N: ToDname,
+        D: RecordData,
+        Octets: FromBuilder + Clone,
+        Octets::Builder: EmptyBuilder,
+        ApexName: ToDname,
+    {
+        self.nsec3s_with_opt_out(apex, ttl, params, false)
     }
-}
 
-//------------ Family --------------------------------------------------------
+    /// Creates the NSEC3 records using the apex determined from the SOA.
+    ///
+    /// This is the same as [`nsec3s_with_opt_out`] with the apex
+    /// determined via [`find_apex`].
+    ///
+    /// [`nsec3s_with_opt_out`]: #method.nsec3s_with_opt_out
+    /// [`find_apex`]: #method.find_apex
+    #[cfg(feature = "ring")]
+    #[allow(clippy::type_complexity)]
+    pub fn nsec3s_from_soa<Octets>(
+        &self,
+        ttl: u32,
+        params: &Nsec3param<Octets>,
+        opt_out: bool,
+    ) -> Result<
+        Vec<Record<Dname<Octets>, Nsec3<Octets>>>,
+        FromSoaError<Nsec3Error>,
+    >
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        Octets: FromBuilder + Clone,
+        Octets::Builder: EmptyBuilder,
+    {
+        let apex = self.find_apex()?;
+        self.nsec3s_with_opt_out(&apex, ttl, params, opt_out)
+            .map_err(FromSoaError::Inner)
+    }
 
-/// A set of records with the same owner name and class.
-pub struct Family<'a, N, D> {
-    slice: &'a [Record<N, D>],
-}
+    /// Creates the NSEC3 records for the zone, optionally using opt-out.
+    ///
+    /// If `opt_out` is `false`, this is identical to [`nsec3s`].
+    /// Otherwise, the records are created as described in RFC 5155,
+    /// section 7.1, for a zone using opt-out: insecure delegations, i.e.,
+    /// zone cuts without a DS record, as well as empty non-terminals that
+    /// only exist because of insecure delegations are left out of the
+    /// chain and all records have the opt-out flag set. Since the names
+    /// above an insecure delegation remain in the chain, closest encloser
+    /// proofs for names below it are still possible.
+    ///
+    /// [`nsec3s`]: #method.nsec3s
+    #[cfg(feature = "ring")]
+    #[allow(clippy::type_complexity)]
+    pub fn nsec3s_with_opt_out<Octets, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        ttl: u32,
+        params: &Nsec3param<Octets>,
+        opt_out: bool,
+    ) -> Result<Vec<Record<Dname<Octets>, Nsec3<Octets>>>, Nsec3Error>
+    where
+        N: ToDname,
+        D: RecordData,
+        Octets: FromBuilder + Clone,
+        Octets::Builder: EmptyBuilder,
+        ApexName: ToDname,
+    {
+        let algorithm = params.hash_algorithm();
+        let iterations = params.iterations();
+        let salt = params.salt().as_slice();
 
-impl<'a, N, D> Family<'a, N, D> {
-    fn new(slice: &'a [Record<N, D>]) -> Self {
-        Family { slice }
-    }
+        // The hashed owner names with the types present at each name.
+        let mut hashes: BTreeMap<Vec<u8>, Vec<Rtype>> = BTreeMap::new();
 
-    pub fn owner(&self) -> &N {
-        self.slice[0].owner()
-    }
+        let mut families = self.families();
+        families.skip_before(apex);
+        let mut cut: Option<&N> = None;
+        let mut prev: Option<&N> = None;
 
-    pub fn class(&self) -> Class {
-        self.slice[0].class()
-    }
+        for family in families {
+            if !family.is_in_zone(apex) {
+                break;
+            }
+            if let Some(cut) = cut {
+                if family.owner().ends_with(cut) {
+                    continue;
+                }
+            }
+            cut = if family.is_zone_cut(apex) {
+                Some(family.slice[0].owner())
+            } else {
+                None
+            };
 
-    pub fn family_name(&self) -> FamilyName<&N> {
-        FamilyName::new(self.owner(), self.class())
-    }
+            // With opt-out, insecure delegations are skipped. Since `prev`
+            // isn’t updated, neither are empty non-terminals only leading
+            // to them.
+            if opt_out
+                && cut.is_some()
+                && !family.rrsets().any(|rrset| rrset.rtype() == Rtype::Ds)
+            {
+                continue;
+            }
 
-    pub fn rrsets(&self) -> FamilyIter<'a, N, D> {
-        FamilyIter::new(self.slice)
-    }
+            // Empty non-terminals own an NSEC3 record with an empty type
+            // bitmap.
+            for_new_empty_non_terminals(
+                family.owner(),
+                prev,
+                apex.owner(),
+                |name| {
+                    hashes.insert(
+                        nsec3_hash(&name, algorithm, iterations, salt)?,
+                        Vec::new(),
+                    );
+                    Ok(())
+                },
+            )?;
+            prev = Some(family.slice[0].owner());
+
+            let mut types: Vec<_> =
+                family.rrsets().map(|rrset| rrset.rtype()).collect();
+            if cut.is_none() || types.contains(&Rtype::Ds) {
+                types.push(Rtype::Rrsig);
+            }
+            if family.family_name() == *apex {
+                types.push(Rtype::Nsec3param);
+            }
+            hashes.insert(
+                nsec3_hash(family.owner(), algorithm, iterations, salt)?,
+                types,
+            );
+        }
 
-    pub fn records(&self) -> slice::Iter<'a, Record<N, D>> {
-        self.slice.iter()
+        let mut res = Vec::with_capacity(hashes.len());
+        let mut iter = hashes.iter().peekable();
+        let first = match iter.peek() {
+            Some(first) => first.0.clone(),
+            None => return Ok(res),
+        };
+        while let Some((owner_hash, types)) = iter.next() {
+            let next = match iter.peek() {
+                Some(next) => next.0,
+                None => &first,
+            };
+            let mut owner = DnameBuilder::<Octets::Builder>::new();
+            base32::display_hex(owner_hash, &mut LabelWriter(&mut owner))
+                .map_err(|_| Nsec3Error::LongName)?;
+            let owner = owner
+                .append_origin(apex.owner())
+                .map_err(|_| Nsec3Error::LongName)?;
+            let mut bitmap = RtypeBitmap::<Octets>::builder();
+            for &rtype in types {
+                bitmap.add(rtype).map_err(|_| Nsec3Error::LongName)?;
+            }
+            res.push(Record::new(
+                owner,
+                apex.class(),
+                ttl,
+                Nsec3::new(
+                    params.hash_algorithm(),
+                    if opt_out { 1 } else { 0 },
+                    params.iterations(),
+                    params.salt().clone(),
+                    octets_charstr(next)?,
+                    bitmap.finalize(),
+                ),
+            ));
+        }
+        Ok(res)
     }
 
-    pub fn is_zone_cut<NN>(&self, apex: &FamilyName<NN>) -> bool
+    /// Makes the TTLs of all records within each RRset identical.
+    ///
+    /// Following RFC 2181, section 5.2, records of an RRset with differing
+    /// TTLs are all set to the lowest TTL found in the RRset. This needs to
+    /// happen before signing since an RRSIG covers the whole RRset with a
+    /// single TTL.
+    ///
+    /// RRSIG records are grouped by the type they cover. If the covered
+    /// RRset is present, their TTL is set to its TTL as required by
+    /// RFC 4034, section 3. Otherwise, the RRSIG records for each covered
+    /// type are normalized among themselves. Since the original TTL field
+    /// of RRSIG records is left untouched, the signatures of an already
+    /// signed zone remain valid when TTLs are lowered.
+    ///
+    /// Returns a list of all records whose TTL was changed.
+    pub fn normalize_ttls(&mut self) -> Vec<TtlChange<N>>
     where
-        N: ToDname,
-        NN: ToDname,
+        N: ToDname + Clone,
         D: RecordData,
     {
-        self.family_name().ne(apex)
-            && self.records().any(|record| record.rtype() == Rtype::Ns)
-    }
+        let mut res = Vec::new();
+        let mut start = 0;
+        while start < self.records.len() {
+            let end = self.family_end(start);
+            let family = &mut self.records[start..end];
+
+            // The lowest TTL of each RRset, RRSIGs by covered type.
+            let mut ttls: Vec<(Class, Rtype, Option<Rtype>, u32)> =
+                Vec::new();
+            for record in family.iter() {
+                let key = (
+                    record.class(),
+                    record.rtype(),
+                    rrsig_covered(record.data()),
+                );
+                match ttls
+                    .iter_mut()
+                    .find(|item| (item.0, item.1, item.2) == key)
+                {
+                    Some(item) => item.3 = cmp::min(item.3, record.ttl()),
+                    None => ttls.push((key.0, key.1, key.2, record.ttl())),
+                }
+            }
+            let ttl_of = |class, rtype, covered| {
+                ttls.iter().find_map(|item| {
+                    if (item.0, item.1, item.2) == (class, rtype, covered) {
+                        Some(item.3)
+                    } else {
+                        None
+                    }
+                })
+            };
+
+            for record in family {
+                let covered = rrsig_covered(record.data());
+                let ttl = covered
+                    .and_then(|covered| ttl_of(record.class(), covered, None))
+                    .or_else(|| {
+                        ttl_of(record.class(), record.rtype(), covered)
+                    })
+                    .unwrap_or_else(|| record.ttl());
+                if record.ttl() != ttl {
+                    res.push(TtlChange::new(record, ttl));
+                    record.set_ttl(ttl);
+                }
+            }
+            start = end;
+        }
+        res
+    }
+
+    /// Limits the TTLs of all records to the given maximum.
+    ///
+    /// Every record with a TTL larger than `max` gets its TTL set to `max`.
+    /// Since all records of an RRset are treated the same, an RRset with
+    /// identical TTLs will still have identical TTLs afterwards.
+    ///
+    /// Returns a list of all records whose TTL was changed.
+    pub fn cap_ttls(&mut self, max: u32) -> Vec<TtlChange<N>>
+    where
+        N: Clone,
+        D: RecordData,
+    {
+        let mut res = Vec::new();
+        for record in &mut self.records {
+            if record.ttl() > max {
+                res.push(TtlChange::new(record, max));
+                record.set_ttl(max);
+            }
+        }
+        res
+    }
+
+    /// Sets the TTL of all NSEC and NSEC3 records to the negative TTL.
+    ///
+    /// The negative TTL should be determined from the zone’s SOA record
+    /// via [`negative_ttl`].
+    ///
+    /// Returns a list of all records whose TTL was changed.
+    ///
+    /// [`negative_ttl`]: fn.negative_ttl.html
+    pub fn apply_negative_ttl(&mut self, ttl: u32) -> Vec<TtlChange<N>>
+    where
+        N: Clone,
+        D: RecordData,
+    {
+        let mut res = Vec::new();
+        for record in &mut self.records {
+            if (record.rtype() == Rtype::Nsec
+                || record.rtype() == Rtype::Nsec3)
+                && record.ttl() != ttl
+            {
+                res.push(TtlChange::new(record, ttl));
+                record.set_ttl(ttl);
+            }
+        }
+        res
+    }
+
+    /// Returns the index of the end of the family starting at `start`.
+    fn family_end(&self, start: usize) -> usize
+    where
+        N: ToDname,
+    {
+        let first = self.records[start].owner();
+        let mut end = start + 1;
+        while let Some(record) = self.records.get(end) {
+            if !record.owner().name_eq(first) {
+                break;
+            }
+            end += 1;
+        }
+        end
+    }
+
+    pub fn write<W>(&self, target: &mut W) -> Result<(), io::Error>
+    where
+        N: fmt::Display,
+        D: RecordData + fmt::Display,
+        W: io::Write,
+    {
+        write_records(&self.records, target)
+    }
+
+    /// Writes the records split over a number of targets.
+    ///
+    /// The records are split into chunks of at most `max` records each.
+    /// Records of the same family are never split between chunks, so a
+    /// chunk may be longer if a single family has more than `max` records.
+    ///
+    /// For each chunk, the closure `open` is called with the index of the
+    /// chunk and the owner name of its first record and returns the target
+    /// to write the chunk to. This allows, for instance, to name files
+    /// after the range of owner names they contain.
+    pub fn write_split<W, F>(
+        &self,
+        max: usize,
+        mut open: F,
+    ) -> Result<(), io::Error>
+    where
+        N: ToDname + fmt::Display,
+        D: RecordData + fmt::Display,
+        W: io::Write,
+        F: FnMut(usize, &N) -> Result<W, io::Error>,
+    {
+        let mut families = self.families().peekable();
+        let mut idx = 0;
+        while let Some(family) = families.next() {
+            let mut target = open(idx, family.owner())?;
+            let mut len = family.slice.len();
+            write_records(family.slice, &mut target)?;
+            while let Some(family) = families.peek() {
+                if len + family.slice.len() > max {
+                    break;
+                }
+                len += family.slice.len();
+                write_records(family.slice, &mut target)?;
+                families.next();
+            }
+            target.flush()?;
+            idx += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes the records gzip compressed.
+    ///
+    /// Returns the target after the compressed data has been finished.
+    #[cfg(feature = "flate2")]
+    pub fn write_gzip<W>(
+        &self,
+        target: W,
+        level: flate2::Compression,
+    ) -> Result<W, io::Error>
+    where
+        N: fmt::Display,
+        D: RecordData + fmt::Display,
+        W: io::Write,
+    {
+        let mut target = flate2::write::GzEncoder::new(target, level);
+        self.write(&mut target)?;
+        target.finish()
+    }
+
+    /// Writes the records zstd compressed.
+    ///
+    /// The `level` is the zstd compression level with 0 selecting the
+    /// library’s default. Returns the target after the compressed data has
+    /// been finished.
+    #[cfg(feature = "zstd")]
+    pub fn write_zstd<W>(&self, target: W, level: i32) -> Result<W, io::Error>
+    where
+        N: fmt::Display,
+        D: RecordData + fmt::Display,
+        W: io::Write,
+    {
+        let mut target = zstd::stream::write::Encoder::new(target, level)?;
+        self.write(&mut target)?;
+        target.finish()
+    }
+
+    /// Writes the records to an asynchronous target.
+    ///
+    /// The records are formatted into a buffer which is written to the
+    /// target whenever it has reached a size of `chunk_size` octets.
+    #[cfg(feature = "tokio")]
+    pub async fn write_async<W>(
+        &self,
+        target: &mut W,
+        chunk_size: usize,
+    ) -> Result<(), io::Error>
+    where
+        N: fmt::Display,
+        D: RecordData + fmt::Display,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buf = Vec::with_capacity(chunk_size);
+        for record in &self.records {
+            write_records(slice::from_ref(record), &mut buf)?;
+            if buf.len() >= chunk_size {
+                target.write_all(&buf).await?;
+                buf.clear();
+            }
+        }
+        target.write_all(&buf).await?;
+        target.flush().await
+    }
+}
+
+impl<N, D> SortedRecords<N, D> {
+    /// Creates a value from records already in canonical order.
+    ///
+    /// The records must be sorted and must not contain duplicates.
+    #[cfg(feature = "master")]
+    pub(super) fn from_sorted_unchecked(records: Vec<Record<N, D>>) -> Self {
+        SortedRecords { records }
+    }
+}
+
+impl<N, D> Default for SortedRecords<N, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N, D> From<Vec<Record<N, D>>> for SortedRecords<N, D>
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd,
+{
+    /// Sorts the records into canonical order.
+    ///
+    /// Instead of comparing the records directly, which would recompute
+    /// their canonical form over and over again, a sort key is composed
+    /// for each record once and the records are sorted by it.
+    fn from(mut src: Vec<Record<N, D>>) -> Self {
+        src.sort_by_cached_key(canonical_sort_key);
+        SortedRecords { records: src }
+    }
+}
+
+impl<N, D> FromIterator<Record<N, D>> for SortedRecords<N, D>
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd,
+{
+    fn from_iter<T: IntoIterator<Item = Record<N, D>>>(iter: T) -> Self {
+        let mut res = Self::new();
+        res.insert_all_with(iter, DuplicatePolicy::Drop);
+        res
+    }
+}
+
+impl<N, D> Extend<Record<N, D>> for SortedRecords<N, D>
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd,
+{
+    fn extend<T: IntoIterator<Item = Record<N, D>>>(&mut self, iter: T) {
+        let _ = self.insert_all(iter);
+    }
+}
+
+/// Returns a key that sorts records in canonical order.
+///
+/// The key consists of the class, the owner name, the record type, and the
+/// canonical wire format of the record data. Comparing the keys of two
+/// records bytewise results in the same order as `CanonicalOrd`.
+///
+/// In order to achieve this for the owner name, its labels are added
+/// starting with the right-most one, each converted to lowercase and
+/// followed by a zero octet. The octets zero and one within a label are
+/// escaped as the octet one followed by one or two, respectively, so that
+/// the end of a label sorts before any content. The name itself is
+/// terminated by another zero octet so that it sorts before any name that
+/// has it as a suffix.
+pub(super) fn canonical_sort_key<N, D>(record: &Record<N, D>) -> Vec<u8>
+where
+    N: ToDname,
+    D: RecordData,
+{
+    let mut res = Vec::new();
+    res.extend_from_slice(&record.class().to_int().to_be_bytes());
+    for label in record.owner().iter_labels().rev() {
+        for &ch in label.as_slice() {
+            match ch {
+                0 | 1 => res.extend_from_slice(&[1, ch + 1]),
+                _ => res.push(ch.to_ascii_lowercase()),
+            }
+        }
+        if !label.is_root() {
+            res.push(0);
+        }
+    }
+    res.push(0);
+    res.extend_from_slice(&record.rtype().to_int().to_be_bytes());
+    record.data().compose_canonical(&mut res).unwrap();
+    res
+}
+
+/// Writes a slice of records in master file format.
+fn write_records<N, D, W>(
+    records: &[Record<N, D>],
+    target: &mut W,
+) -> Result<(), io::Error>
+where
+    N: fmt::Display,
+    D: RecordData + fmt::Display,
+    W: io::Write,
+{
+    for record in records {
+        writeln!(target, "{}", record)?;
+    }
+    Ok(())
+}
+
+//------------ DuplicatePolicy -----------------------------------------------
+
+/// How to treat a record that is equal to one already present.
+///
+/// Records are considered equal if they are equal in canonical order. They
+/// may still differ in their TTL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Keep the existing record and silently drop the new one.
+    Drop,
+
+    /// Replace the existing record with the new one.
+    ///
+    /// The replaced record is collected.
+    Replace,
+
+    /// Keep the existing record and collect the new one.
+    Collect,
+}
+
+impl DuplicatePolicy {
+    /// Resolves a duplicate, collecting the record not kept if necessary.
+    fn resolve<N, D>(
+        self,
+        existing: &mut Record<N, D>,
+        new: Record<N, D>,
+        collected: &mut Vec<Record<N, D>>,
+    ) {
+        match self {
+            DuplicatePolicy::Drop => {}
+            DuplicatePolicy::Replace => {
+                collected.push(mem::replace(existing, new))
+            }
+            DuplicatePolicy::Collect => collected.push(new),
+        }
+    }
+}
+
+//------------ Dedup ---------------------------------------------------------
+
+/// A report of the changes made by `SortedRecords::dedup`.
+#[derive(Clone, Debug)]
+pub struct Dedup<N, D> {
+    removed: Vec<Record<N, D>>,
+    ttl_changes: Vec<TtlChange<N>>,
+}
+
+impl<N, D> Dedup<N, D> {
+    /// Returns the duplicate records that were removed.
+    pub fn removed(&self) -> &[Record<N, D>] {
+        &self.removed
+    }
+
+    /// Returns the TTL changes made to make RRsets consistent.
+    pub fn ttl_changes(&self) -> &[TtlChange<N>] {
+        &self.ttl_changes
+    }
+
+    /// Returns whether nothing needed to be changed.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.ttl_changes.is_empty()
+    }
+
+    /// Converts the report into the removed records and TTL changes.
+    pub fn into_parts(self) -> (Vec<Record<N, D>>, Vec<TtlChange<N>>) {
+        (self.removed, self.ttl_changes)
+    }
+}
+
+//------------ TtlChange -----------------------------------------------------
+
+/// A change of a record’s TTL made by one of the TTL maintenance methods.
+#[derive(Clone, Debug)]
+pub struct TtlChange<N> {
+    owner: N,
+    class: Class,
+    rtype: Rtype,
+    old_ttl: u32,
+    new_ttl: u32,
+}
+
+impl<N> TtlChange<N> {
+    fn new<D: RecordData>(record: &Record<N, D>, new_ttl: u32) -> Self
+    where
+        N: Clone,
+    {
+        TtlChange {
+            owner: record.owner().clone(),
+            class: record.class(),
+            rtype: record.rtype(),
+            old_ttl: record.ttl(),
+            new_ttl,
+        }
+    }
+
+    /// Returns the owner name of the changed record.
+    pub fn owner(&self) -> &N {
+        &self.owner
+    }
+
+    /// Returns the class of the changed record.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Returns the record type of the changed record.
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+
+    /// Returns the TTL the record had before the change.
+    pub fn old_ttl(&self) -> u32 {
+        self.old_ttl
+    }
+
+    /// Returns the TTL the record has after the change.
+    pub fn new_ttl(&self) -> u32 {
+        self.new_ttl
+    }
+}
+
+impl<N: fmt::Display> fmt::Display for TtlChange<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}: TTL {} -> {}",
+            self.owner, self.class, self.rtype, self.old_ttl, self.new_ttl
+        )
+    }
+}
+
+//------------ octets_from_slice ---------------------------------------------
+
+/// Creates octets from a slice.
+///
+/// This allows creating record data with any octets type rather than
+/// requiring it to be convertible from the types used by a signing key.
+pub(super) fn octets_from_slice<Octets>(slice: &[u8]) -> Octets
+where
+    Octets: FromBuilder,
+    Octets::Builder: EmptyBuilder,
+{
+    let mut builder = Octets::Builder::with_capacity(slice.len());
+    builder.append_slice(slice).unwrap();
+    Octets::from_builder(builder)
+}
+
+//------------ negative_ttl --------------------------------------------------
+
+/// Returns the negative caching TTL for a zone given its SOA record.
+///
+/// As defined in RFC 2308, section 5, this is the lesser of the TTL of the
+/// SOA record itself and its minimum field.
+pub fn negative_ttl<N, NN>(soa: &Record<N, Soa<NN>>) -> u32 {
+    cmp::min(soa.ttl(), soa.data().minimum())
+}
+
+//------------ rrsig_covered -------------------------------------------------
+
+/// Returns the type covered by record data if it is an RRSIG.
+///
+/// Since the record data is generic, the type covered is taken from the
+/// first two octets of its wire format.
+fn rrsig_covered<D: RecordData>(data: &D) -> Option<Rtype> {
+    if data.rtype() != Rtype::Rrsig {
+        return None;
+    }
+    let mut buf = Vec::new();
+    data.compose(&mut buf).ok()?;
+    if buf.len() < 2 {
+        return None;
+    }
+    Some(Rtype::from_int(u16::from_be_bytes([buf[0], buf[1]])))
+}
+
+//------------ nsec3_hash ----------------------------------------------------
+
+/// Calculates the NSEC3 hash of an owner name.
+///
+/// The hash is calculated as described in RFC 5155, section 5, over the
+/// canonical form of `owner`. Currently, only SHA-1 is supported as the hash
+/// algorithm.
+#[cfg(feature = "ring")]
+pub fn nsec3_hash<N: ToDname + ?Sized>(
+    owner: &N,
+    algorithm: Nsec3HashAlg,
+    iterations: u16,
+    salt: &[u8],
+) -> Result<Vec<u8>, Nsec3Error> {
+    use ring::digest;
+
+    if algorithm != Nsec3HashAlg::Sha1 {
+        return Err(Nsec3Error::UnsupportedAlgorithm);
+    }
+    let mut buf = Vec::new();
+    owner.compose_canonical(&mut buf).unwrap();
+    for _ in 0..=iterations {
+        buf.extend_from_slice(salt);
+        let digest = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &buf);
+        buf.clear();
+        buf.extend_from_slice(digest.as_ref());
+    }
+    Ok(buf)
+}
+
+/// Writes base32 characters into a domain name builder as a single label.
+#[cfg(feature = "ring")]
+struct LabelWriter<'a, Builder>(&'a mut DnameBuilder<Builder>);
+
+#[cfg(feature = "ring")]
+impl<'a, Builder: OctetsBuilder> fmt::Write for LabelWriter<'a, Builder> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.bytes() {
+            self.0
+                .push(ch.to_ascii_lowercase())
+                .map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates a character string from an octets slice.
+#[cfg(feature = "ring")]
+fn octets_charstr<Octets>(slice: &[u8]) -> Result<CharStr<Octets>, Nsec3Error>
+where
+    Octets: FromBuilder,
+    Octets::Builder: EmptyBuilder,
+{
+    let mut builder = Octets::Builder::with_capacity(slice.len());
+    builder
+        .append_slice(slice)
+        .map_err(|_| Nsec3Error::LongName)?;
+    CharStr::from_octets(Octets::from_builder(builder))
+        .map_err(|_| Nsec3Error::LongName)
+}
+
+//------------ for_new_empty_non_terminals -----------------------------------
+
+/// Calls `op` for the empty non-terminals above `owner` not seen before.
+///
+/// The empty non-terminals considered are the names strictly between
+/// `apex` and `owner`. `prev` is the previous name of the zone in canonical
+/// order. Since canonical order visits parents before their children, all
+/// names that are ancestors of both `prev` and `owner` have been seen
+/// already, either as an owner name or as an empty non-terminal, and are
+/// skipped. The remaining names are passed to `op` closest to the apex
+/// first, i.e., in canonical order.
+fn for_new_empty_non_terminals<N, A, E, F>(
+    owner: &N,
+    prev: Option<&N>,
+    apex: &A,
+    mut op: F,
+) -> Result<(), E>
+where
+    N: ToDname,
+    A: ToDname,
+    F: FnMut(Dname<&[u8]>) -> Result<(), E>,
+{
+    let owner = owner.to_cow();
+    let mut names = Vec::new();
+    for parent in owner.iter_suffixes().skip(1) {
+        if !parent.ends_with(apex) || parent.name_eq(apex) {
+            break;
+        }
+        if let Some(prev) = prev {
+            if prev.ends_with(&parent) {
+                break;
+            }
+        }
+        names.push(parent);
+    }
+    for name in names.into_iter().rev() {
+        op(name)?
+    }
+    Ok(())
+}
+
+//------------ Family --------------------------------------------------------
+
+/// A set of records with the same owner name and class.
+pub struct Family<'a, N, D> {
+    slice: &'a [Record<N, D>],
+}
+
+impl<'a, N, D> Family<'a, N, D> {
+    fn new(slice: &'a [Record<N, D>]) -> Self {
+        Family { slice }
+    }
+
+    pub fn owner(&self) -> &N {
+        self.slice[0].owner()
+    }
+
+    pub fn class(&self) -> Class {
+        self.slice[0].class()
+    }
+
+    pub fn family_name(&self) -> FamilyName<&N> {
+        FamilyName::new(self.owner(), self.class())
+    }
+
+    pub fn rrsets(&self) -> FamilyIter<'a, N, D> {
+        FamilyIter::new(self.slice)
+    }
+
+    pub fn records(&self) -> slice::Iter<'a, Record<N, D>> {
+        self.slice.iter()
+    }
+
+    pub fn is_zone_cut<NN>(&self, apex: &FamilyName<NN>) -> bool
+    where
+        N: ToDname,
+        NN: ToDname,
+        D: RecordData,
+    {
+        self.family_name().ne(apex)
+            && self.records().any(|record| record.rtype() == Rtype::Ns)
+    }
 
     pub fn is_in_zone<NN: ToDname>(&self, apex: &FamilyName<NN>) -> bool
     where
@@ -355,119 +1542,580 @@ impl<'a, N, D> Family<'a, N, D> {
 //------------ FamilyName ----------------------------------------------------
 
 /// The identifier for a family, i.e., a owner name and class.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct FamilyName<N> {
     owner: N,
     class: Class,
 }
 
 impl<N> FamilyName<N> {
-    fn new(owner: N, class: Class) -> Self {
+    pub fn new(owner: N, class: Class) -> Self {
         FamilyName { owner, class }
     }
 
-    pub fn owner(&self) -> &N {
-        &self.owner
+    pub fn owner(&self) -> &N {
+        &self.owner
+    }
+
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    pub fn into_record<D>(self, ttl: u32, data: D) -> Record<N, D>
+    where
+        N: Clone,
+    {
+        Record::new(self.owner.clone(), self.class, ttl, data)
+    }
+
+    pub fn dnskey<K, Octets>(
+        &self,
+        ttl: u32,
+        key: K,
+    ) -> Result<Record<N, Dnskey<Octets>>, K::Error>
+    where
+        N: Clone,
+        K: SigningKey,
+        Octets: FromBuilder,
+        Octets::Builder: EmptyBuilder,
+    {
+        key.dnskey().map(|dnskey| {
+            self.clone().into_record(
+                ttl,
+                Dnskey::new(
+                    dnskey.flags(),
+                    dnskey.protocol(),
+                    dnskey.algorithm(),
+                    octets_from_slice(dnskey.public_key().as_ref()),
+                ),
+            )
+        })
+    }
+
+    pub fn ds<K: SigningKey>(
+        &self,
+        ttl: u32,
+        key: K,
+    ) -> Result<Record<N, Ds<K::Octets>>, K::Error>
+    where
+        N: ToDname + Clone,
+    {
+        key.ds(&self.owner)
+            .map(|ds| self.clone().into_record(ttl, ds))
+    }
+}
+
+impl<'a, N: Clone> FamilyName<&'a N> {
+    pub fn cloned(&self) -> FamilyName<N> {
+        FamilyName {
+            owner: (*self.owner).clone(),
+            class: self.class,
+        }
+    }
+}
+
+impl<N: ToDname, NN: ToDname> PartialEq<FamilyName<NN>> for FamilyName<N> {
+    fn eq(&self, other: &FamilyName<NN>) -> bool {
+        self.owner.name_eq(&other.owner) && self.class == other.class
+    }
+}
+
+impl<N: ToDname, NN: ToDname, D> PartialEq<Record<NN, D>> for FamilyName<N> {
+    fn eq(&self, other: &Record<NN, D>) -> bool {
+        self.owner.name_eq(other.owner()) && self.class == other.class()
+    }
+}
+
+//------------ Rrset ---------------------------------------------------------
+
+/// A set of records with the same owner name, class, and record type.
+pub struct Rrset<'a, N, D> {
+    slice: &'a [Record<N, D>],
+}
+
+impl<'a, N, D> Rrset<'a, N, D> {
+    fn new(slice: &'a [Record<N, D>]) -> Self {
+        Rrset { slice }
+    }
+
+    pub fn owner(&self) -> &N {
+        self.slice[0].owner()
+    }
+
+    pub fn class(&self) -> Class {
+        self.slice[0].class()
+    }
+
+    pub fn family_name(&self) -> FamilyName<&N> {
+        FamilyName::new(self.owner(), self.class())
+    }
+
+    pub fn rtype(&self) -> Rtype
+    where
+        D: RecordData,
+    {
+        self.slice[0].rtype()
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.slice[0].ttl()
+    }
+
+    pub fn first(&self) -> &Record<N, D> {
+        &self.slice[0]
+    }
+
+    pub fn iter(&self) -> slice::Iter<'a, Record<N, D>> {
+        self.slice.iter()
+    }
+
+    /// Composes the data to be signed for the RRset.
+    ///
+    /// This is the RRSIG record data without the signature followed by the
+    /// records of the RRset in canonical form as described in RFC 4034,
+    /// section 3.1.8.1. The data is appended to `target`.
+    ///
+    /// As required by RFC 4034, section 6.2, the TTL of each record is
+    /// replaced with the original TTL of the RRSIG record so that the
+    /// signature verifies even if the TTLs of the RRset’s records differ.
+    pub fn compose_signed_data<SignerName, Target>(
+        &self,
+        rrsig: &ProtoRrsig<SignerName>,
+        target: &mut Target,
+    ) -> Result<(), ShortBuf>
+    where
+        N: ToDname,
+        D: RecordData,
+        SignerName: ToDname,
+        Target: OctetsBuilder,
+    {
+        target.append_all(|target| {
+            rrsig.compose_canonical(target)?;
+            for record in self.iter() {
+                compose_signed_record(record, rrsig.original_ttl(), target)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Feeds the data to be signed for the RRset piece by piece.
+    ///
+    /// The data is the same as composed by [`compose_signed_data`]. Instead
+    /// of assembling all of it, the RRSIG record data and then each record
+    /// are composed into `buf` one after another and handed to `feed`. The
+    /// buffer is cleared before each piece, so it only ever needs to hold a
+    /// single record. This allows calculating a digest over even very
+    /// large RRsets without materializing them.
+    ///
+    /// [`compose_signed_data`]: #method.compose_signed_data
+    pub fn feed_signed_data<SignerName, Buf, F>(
+        &self,
+        rrsig: &ProtoRrsig<SignerName>,
+        buf: &mut Buf,
+        mut feed: F,
+    ) -> Result<(), ShortBuf>
+    where
+        N: ToDname,
+        D: RecordData,
+        SignerName: ToDname,
+        Buf: OctetsBuilder,
+        F: FnMut(&[u8]),
+    {
+        buf.truncate(0);
+        rrsig.compose_canonical(buf)?;
+        feed(buf.as_ref());
+        for record in self.iter() {
+            buf.truncate(0);
+            compose_signed_record(record, rrsig.original_ttl(), buf)?;
+            feed(buf.as_ref());
+        }
+        Ok(())
+    }
+}
+
+/// Composes a record in canonical form with the given TTL.
+fn compose_signed_record<N, D, Target>(
+    record: &Record<N, D>,
+    ttl: u32,
+    target: &mut Target,
+) -> Result<(), ShortBuf>
+where
+    N: ToDname,
+    D: RecordData,
+    Target: OctetsBuilder,
+{
+    target.append_all(|target| {
+        record.owner().compose_canonical(target)?;
+        record.rtype().compose(target)?;
+        record.class().compose(target)?;
+        ttl.compose(target)?;
+        target.u16_len_prefixed(|target| {
+            record.data().compose_canonical(target)
+        })
+    })
+}
+
+//------------ Classification ------------------------------------------------
+
+/// The role a record plays within a zone.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Classification {
+    /// The record is authoritative data of the zone.
+    ///
+    /// This includes the DS, NSEC, and RRSIG records at a zone cut which
+    /// are owned by the parent.
+    Authoritative,
+
+    /// The record is an NS record at a zone cut below the apex.
+    Delegation,
+
+    /// The record is an address record at or below a zone cut.
+    ///
+    /// Note that the record is considered glue independently of whether
+    /// it is actually referred to by any of the delegation’s NS records.
+    Glue,
+
+    /// The record is hidden by a zone cut or a DNAME record above it.
+    Occluded,
+
+    /// The record’s owner is not within the zone.
+    OutOfZone,
+}
+
+/// The position of a family relative to zone cuts and DNAMEs.
+#[derive(Clone, Copy, Debug)]
+enum FamilyPosition {
+    Authoritative,
+    ZoneCut,
+    BelowZoneCut,
+    BelowDname,
+    OutOfZone,
+}
+
+impl FamilyPosition {
+    fn classify(self, rtype: Rtype) -> Classification {
+        match self {
+            FamilyPosition::Authoritative => Classification::Authoritative,
+            FamilyPosition::ZoneCut => match rtype {
+                Rtype::Ns => Classification::Delegation,
+                Rtype::Ds | Rtype::Nsec | Rtype::Rrsig => {
+                    Classification::Authoritative
+                }
+                Rtype::A | Rtype::Aaaa => Classification::Glue,
+                _ => Classification::Occluded,
+            },
+            FamilyPosition::BelowZoneCut => match rtype {
+                Rtype::A | Rtype::Aaaa => Classification::Glue,
+                _ => Classification::Occluded,
+            },
+            FamilyPosition::BelowDname => Classification::Occluded,
+            FamilyPosition::OutOfZone => Classification::OutOfZone,
+        }
+    }
+}
+
+//------------ ZoneWalker ---------------------------------------------------
+
+/// Keeps track of zone cuts and DNAMEs while walking over families.
+struct ZoneWalker<'a, N, A> {
+    apex: &'a FamilyName<A>,
+
+    /// The owner name of the last zone cut we have encountered.
+    cut: Option<&'a N>,
+
+    /// The owner name of the last DNAME record we have encountered.
+    dname: Option<&'a N>,
+}
+
+impl<'a, N, A> ZoneWalker<'a, N, A> {
+    fn new(apex: &'a FamilyName<A>) -> Self {
+        ZoneWalker {
+            apex,
+            cut: None,
+            dname: None,
+        }
+    }
+}
+
+impl<'a, N: ToDname, A: ToDname> ZoneWalker<'a, N, A> {
+    fn position<D: RecordData>(
+        &mut self,
+        family: &Family<'a, N, D>,
+    ) -> FamilyPosition {
+        if !family.is_in_zone(self.apex) {
+            return FamilyPosition::OutOfZone;
+        }
+
+        // Since records are in canonical order, everything below a name
+        // directly follows that name. So we only need to remember the last
+        // zone cut and DNAME.
+        if let Some(cut) = self.cut {
+            if family.owner().ends_with(cut) {
+                return FamilyPosition::BelowZoneCut;
+            }
+        }
+        if let Some(dname) = self.dname {
+            if family.owner().ends_with(dname) {
+                return FamilyPosition::BelowDname;
+            }
+        }
+
+        let owner = family.slice[0].owner();
+        self.dname = if family
+            .records()
+            .any(|record| record.rtype() == Rtype::Dname)
+        {
+            Some(owner)
+        } else {
+            None
+        };
+        if family.is_zone_cut(self.apex) {
+            self.cut = Some(owner);
+            FamilyPosition::ZoneCut
+        } else {
+            self.cut = None;
+            FamilyPosition::Authoritative
+        }
     }
+}
 
-    pub fn class(&self) -> Class {
-        self.class
-    }
+//------------ ClassifyIter --------------------------------------------------
+
+/// An iterator classifying the records of a zone.
+///
+/// The iterator is created via [`SortedRecords::classify`].
+///
+/// [`SortedRecords::classify`]: struct.SortedRecords.html#method.classify
+pub struct ClassifyIter<'a, N, D, A> {
+    families: RecordsIter<'a, N, D>,
+    walker: ZoneWalker<'a, N, A>,
+    current: Option<(slice::Iter<'a, Record<N, D>>, FamilyPosition)>,
+}
 
-    pub fn into_record<D>(self, ttl: u32, data: D) -> Record<N, D>
-    where
-        N: Clone,
-    {
-        Record::new(self.owner.clone(), self.class, ttl, data)
+impl<'a, N, D, A> ClassifyIter<'a, N, D, A> {
+    fn new(families: RecordsIter<'a, N, D>, apex: &'a FamilyName<A>) -> Self {
+        ClassifyIter {
+            families,
+            walker: ZoneWalker::new(apex),
+            current: None,
+        }
     }
+}
 
-    pub fn dnskey<K: SigningKey, Octets: From<K::Octets>>(
-        &self,
-        ttl: u32,
-        key: K,
-    ) -> Result<Record<N, Dnskey<Octets>>, K::Error>
-    where
-        N: Clone,
-    {
-        key.dnskey()
-            .map(|dnskey| self.clone().into_record(ttl, dnskey.convert()))
-    }
+impl<'a, N, D, A> Iterator for ClassifyIter<'a, N, D, A>
+where
+    N: ToDname + 'a,
+    D: RecordData + 'a,
+    A: ToDname,
+{
+    type Item = (&'a Record<N, D>, Classification);
 
-    pub fn ds<K: SigningKey>(
-        &self,
-        ttl: u32,
-        key: K,
-    ) -> Result<Record<N, Ds<K::Octets>>, K::Error>
-    where
-        N: ToDname + Clone,
-    {
-        key.ds(&self.owner)
-            .map(|ds| self.clone().into_record(ttl, ds))
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((ref mut records, position)) = self.current {
+                if let Some(record) = records.next() {
+                    return Some((record, position.classify(record.rtype())));
+                }
+            }
+            let family = self.families.next()?;
+            let position = self.walker.position(&family);
+            self.current = Some((family.records(), position));
+        }
     }
 }
 
-impl<'a, N: Clone> FamilyName<&'a N> {
-    pub fn cloned(&self) -> FamilyName<N> {
-        FamilyName {
-            owner: (*self.owner).clone(),
-            class: self.class,
-        }
-    }
+//------------ ClassifyRrsetIter ---------------------------------------------
+
+/// An iterator classifying the RRsets of a zone.
+///
+/// The iterator is created via [`SortedRecords::classify_rrsets`].
+///
+/// [`SortedRecords::classify_rrsets`]: struct.SortedRecords.html#method.classify_rrsets
+pub struct ClassifyRrsetIter<'a, N, D, A> {
+    families: RecordsIter<'a, N, D>,
+    walker: ZoneWalker<'a, N, A>,
+    current: Option<(FamilyIter<'a, N, D>, FamilyPosition)>,
 }
 
-impl<N: ToDname, NN: ToDname> PartialEq<FamilyName<NN>> for FamilyName<N> {
-    fn eq(&self, other: &FamilyName<NN>) -> bool {
-        self.owner.name_eq(&other.owner) && self.class == other.class
+impl<'a, N, D, A> ClassifyRrsetIter<'a, N, D, A> {
+    fn new(families: RecordsIter<'a, N, D>, apex: &'a FamilyName<A>) -> Self {
+        ClassifyRrsetIter {
+            families,
+            walker: ZoneWalker::new(apex),
+            current: None,
+        }
     }
 }
 
-impl<N: ToDname, NN: ToDname, D> PartialEq<Record<NN, D>> for FamilyName<N> {
-    fn eq(&self, other: &Record<NN, D>) -> bool {
-        self.owner.name_eq(other.owner()) && self.class == other.class()
+impl<'a, N, D, A> Iterator for ClassifyRrsetIter<'a, N, D, A>
+where
+    N: ToDname + 'a,
+    D: RecordData + 'a,
+    A: ToDname,
+{
+    type Item = (Rrset<'a, N, D>, Classification);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((ref mut rrsets, position)) = self.current {
+                if let Some(rrset) = rrsets.next() {
+                    let class = position.classify(rrset.rtype());
+                    return Some((rrset, class));
+                }
+            }
+            let family = self.families.next()?;
+            let position = self.walker.position(&family);
+            self.current = Some((family.rrsets(), position));
+        }
     }
 }
 
-//------------ Rrset ---------------------------------------------------------
+//------------ ZoneCut -------------------------------------------------------
 
-/// A set of records with the same owner name, class, and record type.
-pub struct Rrset<'a, N, D> {
+/// A delegation point within a zone with all the records at and below it.
+///
+/// Values of this type are produced by [`SortedRecords::zone_cuts`].
+///
+/// [`SortedRecords::zone_cuts`]: struct.SortedRecords.html#method.zone_cuts
+pub struct ZoneCut<'a, N, D> {
+    /// The records at and below the zone cut.
     slice: &'a [Record<N, D>],
-}
 
-impl<'a, N, D> Rrset<'a, N, D> {
-    fn new(slice: &'a [Record<N, D>]) -> Self {
-        Rrset { slice }
-    }
+    /// The number of records at the zone cut itself.
+    family_len: usize,
+}
 
-    pub fn owner(&self) -> &N {
+impl<'a, N, D> ZoneCut<'a, N, D> {
+    /// Returns the owner name of the zone cut.
+    pub fn owner(&self) -> &'a N {
         self.slice[0].owner()
     }
 
+    /// Returns the class of the zone cut.
     pub fn class(&self) -> Class {
         self.slice[0].class()
     }
 
-    pub fn family_name(&self) -> FamilyName<&N> {
-        FamilyName::new(self.owner(), self.class())
+    /// Returns the family of records at the zone cut.
+    pub fn family(&self) -> Family<'a, N, D> {
+        Family::new(&self.slice[..self.family_len])
     }
 
-    pub fn rtype(&self) -> Rtype
+    /// Returns all the records at and below the zone cut.
+    pub fn records(&self) -> slice::Iter<'a, Record<N, D>> {
+        self.slice.iter()
+    }
+
+    /// Returns the families at and below the zone cut.
+    pub fn families(&self) -> RecordsIter<'a, N, D> {
+        RecordsIter::new(self.slice)
+    }
+
+    /// Returns the NS RRset of the zone cut.
+    pub fn ns(&self) -> Option<Rrset<'a, N, D>>
     where
+        N: ToDname,
         D: RecordData,
     {
-        self.slice[0].rtype()
+        self.family()
+            .rrsets()
+            .find(|rrset| rrset.rtype() == Rtype::Ns)
     }
 
-    pub fn ttl(&self) -> u32 {
-        self.slice[0].ttl()
+    /// Returns the DS RRset of the zone cut if there is one.
+    pub fn ds(&self) -> Option<Rrset<'a, N, D>>
+    where
+        N: ToDname,
+        D: RecordData,
+    {
+        self.family()
+            .rrsets()
+            .find(|rrset| rrset.rtype() == Rtype::Ds)
     }
 
-    pub fn first(&self) -> &Record<N, D> {
-        &self.slice[0]
+    /// Returns an iterator over the glue records of the zone cut.
+    ///
+    /// These are all A and AAAA records at and below the zone cut. As with
+    /// [`Classification::Glue`], whether they are actually referred to by
+    /// the NS records is not considered.
+    ///
+    /// [`Classification::Glue`]: enum.Classification.html#variant.Glue
+    pub fn glue(&self) -> GlueIter<'a, N, D> {
+        GlueIter(self.slice.iter())
     }
+}
 
-    pub fn iter(&self) -> slice::Iter<'a, Record<N, D>> {
-        self.slice.iter()
+//------------ ZoneCutIter ---------------------------------------------------
+
+/// An iterator over the zone cuts of a zone.
+///
+/// The iterator is created via [`SortedRecords::zone_cuts`].
+///
+/// [`SortedRecords::zone_cuts`]: struct.SortedRecords.html#method.zone_cuts
+pub struct ZoneCutIter<'a, N, D, A> {
+    families: RecordsIter<'a, N, D>,
+    walker: ZoneWalker<'a, N, A>,
+}
+
+impl<'a, N, D, A> ZoneCutIter<'a, N, D, A> {
+    fn new(families: RecordsIter<'a, N, D>, apex: &'a FamilyName<A>) -> Self {
+        ZoneCutIter {
+            families,
+            walker: ZoneWalker::new(apex),
+        }
+    }
+}
+
+impl<'a, N, D, A> Iterator for ZoneCutIter<'a, N, D, A>
+where
+    N: ToDname + 'a,
+    D: RecordData + 'a,
+    A: ToDname,
+{
+    type Item = ZoneCut<'a, N, D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = self.families.slice;
+            let family = self.families.next()?;
+            if let FamilyPosition::ZoneCut = self.walker.position(&family) {
+                // Everything below the cut directly follows it.
+                let family_len = family.slice.len();
+                let len = family_len
+                    + self
+                        .families
+                        .slice
+                        .iter()
+                        .take_while(|record| {
+                            record.class() == family.class()
+                                && record.owner().ends_with(family.owner())
+                        })
+                        .count();
+                self.families = RecordsIter::new(&rest[len..]);
+                return Some(ZoneCut {
+                    slice: &rest[..len],
+                    family_len,
+                });
+            }
+        }
+    }
+}
+
+//------------ GlueIter ------------------------------------------------------
+
+/// An iterator over the glue records of a zone cut.
+pub struct GlueIter<'a, N, D>(slice::Iter<'a, Record<N, D>>);
+
+impl<'a, N, D> Iterator for GlueIter<'a, N, D>
+where
+    D: RecordData,
+{
+    type Item = &'a Record<N, D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find(|record| {
+            record.rtype() == Rtype::A || record.rtype() == Rtype::Aaaa
+        })
     }
 }
 
@@ -605,3 +2253,931 @@ where
         Some(Rrset::new(res))
     }
 }
+
+//============ Error Types ===================================================
+
+//------------ Nsec3Error ----------------------------------------------------
+
+/// An error happened while creating NSEC3 records.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Nsec3Error {
+    /// The hash algorithm is not supported.
+    UnsupportedAlgorithm,
+
+    /// A hashed owner name would be too long.
+    LongName,
+}
+
+//--- Display and Error
+
+impl fmt::Display for Nsec3Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nsec3Error::UnsupportedAlgorithm => {
+                f.write_str("unsupported hash algorithm")
+            }
+            Nsec3Error::LongName => f.write_str("hashed name too long"),
+        }
+    }
+}
+
+impl error::Error for Nsec3Error {}
+
+//------------ ApexError -----------------------------------------------------
+
+/// The apex of a zone could not be determined.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ApexError {
+    /// The zone doesn’t contain a SOA record.
+    NoSoa,
+
+    /// The zone contains more than one SOA record.
+    MultipleSoa,
+
+    /// The zone contains a record of a different class than the SOA.
+    ClassMismatch(Class),
+}
+
+//--- Display and Error
+
+impl fmt::Display for ApexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ApexError::NoSoa => f.write_str("missing SOA record"),
+            ApexError::MultipleSoa => f.write_str("multiple SOA records"),
+            ApexError::ClassMismatch(class) => {
+                write!(f, "record of class {} differs from SOA", class)
+            }
+        }
+    }
+}
+
+impl error::Error for ApexError {}
+
+//------------ FromSoaError --------------------------------------------------
+
+/// An error happened in an operation determining the apex from the SOA.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FromSoaError<E> {
+    /// The apex could not be determined.
+    Apex(ApexError),
+
+    /// The operation itself failed.
+    Inner(E),
+}
+
+impl<E> From<ApexError> for FromSoaError<E> {
+    fn from(err: ApexError) -> Self {
+        FromSoaError::Apex(err)
+    }
+}
+
+//--- Display and Error
+
+impl<E: fmt::Display> fmt::Display for FromSoaError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FromSoaError::Apex(ref err) => err.fmt(f),
+            FromSoaError::Inner(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for FromSoaError<E> {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use crate::utils::testing::{self, a_record, record, ZoneData};
+    use core::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+    type Data = ZoneData<Vec<u8>>;
+
+    fn name(s: &str) -> Name {
+        testing::name(s)
+    }
+
+    #[test]
+    fn normalize_ttls() {
+        let mut records: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("a.example.", 300, "192.0.2.2"),
+            a_record("b.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let changes = records.normalize_ttls();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].old_ttl(), 3600);
+        assert_eq!(changes[0].new_ttl(), 300);
+        assert!(records.rrsets().all(|rrset| {
+            rrset.iter().all(|record| record.ttl() == rrset.ttl())
+        }));
+    }
+
+    /// Records of several types including RRSIGs for a signed zone.
+    mod signed {
+        use super::*;
+        use crate::base::iana::SecAlg;
+        use crate::rdata::Rrsig;
+
+        pub fn a(addr: &str) -> Data {
+            A::from_str(addr).unwrap().into()
+        }
+
+        pub fn rrsig(covered: Rtype, original_ttl: u32, tag: u16) -> Data {
+            Rrsig::new(
+                covered,
+                SecAlg::Ed25519,
+                2,
+                original_ttl,
+                Time32::from_int(2),
+                Time32::from_int(1),
+                tag,
+                name("example."),
+                vec![0; 64],
+            )
+            .into()
+        }
+    }
+
+    #[test]
+    fn normalize_rrsig_ttls() {
+        use self::signed::{a, rrsig};
+        use crate::rdata::{MasterRecordData, Mx};
+
+        let mx = Mx::new(10, name("mx.example."));
+        let mut records: SortedRecords<Name, Data> = vec![
+            record("a.example.", 3600, a("192.0.2.1")),
+            record("a.example.", 3600, rrsig(Rtype::A, 3600, 1)),
+            record("a.example.", 300, mx.into()),
+            record("a.example.", 300, rrsig(Rtype::Mx, 300, 1)),
+            record("a.example.", 600, rrsig(Rtype::Mx, 300, 2)),
+            // RRSIGs for an RRset that isn’t present.
+            record("a.example.", 900, rrsig(Rtype::Txt, 900, 1)),
+            record("a.example.", 1800, rrsig(Rtype::Txt, 900, 2)),
+        ]
+        .into();
+        let changes = records.normalize_ttls();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|change| change.rtype() == Rtype::Rrsig));
+
+        // Each RRSIG has the TTL of its covered RRset, the original TTL
+        // stays unchanged.
+        for record in &records.records {
+            let expected =
+                match (record.rtype(), rrsig_covered(record.data())) {
+                    (Rtype::A, _) | (_, Some(Rtype::A)) => 3600,
+                    (Rtype::Mx, _) | (_, Some(Rtype::Mx)) => 300,
+                    _ => 900,
+                };
+            assert_eq!(record.ttl(), expected);
+        }
+        let original = records
+            .records
+            .iter()
+            .filter_map(|record| match record.data() {
+                MasterRecordData::Rrsig(rrsig) => Some(rrsig.original_ttl()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(original, [3600, 300, 300, 900, 900]);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn intern_owners() {
+        // Each owner is parsed anew for every record, so no two records
+        // share an allocation before interning.
+        let owners = ["example.", "www.example.", "mail.example."];
+        let mut records: SortedRecords<_, _> = owners
+            .iter()
+            .flat_map(|owner| {
+                (0..10).map(move |idx| {
+                    Record::new(
+                        Dname::<Bytes>::from_str(owner).unwrap(),
+                        Class::In,
+                        3600,
+                        A::from_octets(192, 0, 2, idx),
+                    )
+                })
+            })
+            .collect();
+        let mut pool = NamePool::new();
+        records.intern_owners(&mut pool);
+        assert_eq!(pool.len(), 3);
+        assert_eq!(pool.stats().requests, 30);
+        assert_eq!(pool.stats().saved_octets(), 9 * (9 + 13 + 14));
+
+        let interned = records
+            .families()
+            .flat_map(|family| family.records())
+            .map(|record| record.owner().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(interned.len(), 30);
+        for left in &interned {
+            for right in &interned {
+                assert_eq!(
+                    left == right,
+                    left.as_slice().as_ptr() == right.as_slice().as_ptr()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_sort_key() {
+        use crate::rdata::{Mx, Ns};
+        use std::string::ToString;
+
+        fn a(addr: &str) -> Data {
+            A::from_str(addr).unwrap().into()
+        }
+
+        // RFC 4034, section 6.1 plus some more.
+        let mut records: Vec<Record<Name, Data>> = vec![
+            record("\\200.z.example.", 3600, a("192.0.2.1")),
+            record("Z.a.example.", 3600, a("192.0.2.1")),
+            record("*.z.example.", 3600, a("192.0.2.1")),
+            record("\\001.z.example.", 3600, a("192.0.2.1")),
+            record("a.example.", 3600, a("192.0.2.1")),
+            record("zABC.a.EXAMPLE.", 3600, a("192.0.2.1")),
+            record("yljkjljk.a.example.", 3600, a("192.0.2.1")),
+            record("z.example.", 3600, a("192.0.2.1")),
+            record("example.", 3600, a("192.0.2.1")),
+            record("a\\000.example.", 3600, a("192.0.2.1")),
+            record("a\\001.example.", 3600, a("192.0.2.1")),
+            record("a.example.", 3600, a("192.0.2.2")),
+            record("a.example.", 3600, a("10.0.0.1")),
+            record("a.example.", 3600, Ns::new(name("ns.example.")).into()),
+            record("a.example.", 3600, Ns::new(name("NS.a.example.")).into()),
+            record(
+                "a.example.",
+                3600,
+                Mx::new(10, name("mx.example.")).into(),
+            ),
+            record(
+                "a.example.",
+                3600,
+                Mx::new(5, name("mx.example.")).into(),
+            ),
+            record("org.", 3600, a("192.0.2.1")),
+        ];
+        let mut expected = records.clone();
+        expected.sort_by(CanonicalOrd::canonical_cmp);
+        records.sort_by_cached_key(super::canonical_sort_key);
+        assert_eq!(
+            records.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            expected.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn from_iter_dedups() {
+        let records: SortedRecords<_, _> = vec![
+            a_record("b.example.", 3600, "192.0.2.1"),
+            a_record("A.example.", 3600, "192.0.2.1"),
+            a_record("a.example.", 300, "192.0.2.1"),
+        ]
+        .into_iter()
+        .collect();
+        let records = records.families().next().unwrap();
+        let mut records = records.records();
+        assert_eq!(records.next().unwrap().ttl(), 3600);
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn insert_all() {
+        let mut records: SortedRecords<_, _> = vec![
+            a_record("b.example.", 3600, "192.0.2.1"),
+            a_record("d.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let duplicates = records.insert_all(vec![
+            a_record("e.example.", 3600, "192.0.2.1"),
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("d.example.", 300, "192.0.2.1"),
+            a_record("c.example.", 3600, "192.0.2.1"),
+        ]);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].ttl(), 300);
+        assert_eq!(
+            records
+                .families()
+                .map(|family| format!("{}", family.owner()))
+                .collect::<Vec<_>>(),
+            [
+                "a.example",
+                "b.example",
+                "c.example",
+                "d.example",
+                "e.example"
+            ]
+        );
+        assert!(records.families().all(|family| family
+            .records()
+            .all(|record| record.ttl() == 3600)));
+    }
+
+    #[test]
+    fn insert_all_with() {
+        let mut records: SortedRecords<_, _> =
+            vec![a_record("a.example.", 3600, "192.0.2.1")].into();
+        let batch = || {
+            vec![
+                a_record("a.example.", 300, "192.0.2.1"),
+                a_record("b.example.", 3600, "192.0.2.1"),
+                a_record("b.example.", 60, "192.0.2.1"),
+            ]
+        };
+
+        let mut dropped = records.clone();
+        assert!(dropped
+            .insert_all_with(batch(), DuplicatePolicy::Drop)
+            .is_empty());
+        assert_eq!(
+            dropped.records.iter().map(Record::ttl).collect::<Vec<_>>(),
+            [3600, 3600]
+        );
+
+        let mut replaced = records.clone();
+        let old = replaced.insert_all_with(batch(), DuplicatePolicy::Replace);
+        assert_eq!(
+            old.iter().map(Record::ttl).collect::<Vec<_>>(),
+            [3600, 3600]
+        );
+        assert_eq!(
+            replaced.records.iter().map(Record::ttl).collect::<Vec<_>>(),
+            [300, 60]
+        );
+
+        let new = records.insert_all_with(batch(), DuplicatePolicy::Collect);
+        assert_eq!(
+            new.iter().map(Record::ttl).collect::<Vec<_>>(),
+            [60, 300]
+        );
+        assert_eq!(
+            records.records.iter().map(Record::ttl).collect::<Vec<_>>(),
+            [3600, 3600]
+        );
+    }
+
+    #[test]
+    fn dedup() {
+        let mut records: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("a.example.", 300, "192.0.2.1"),
+            a_record("a.example.", 3600, "192.0.2.2"),
+            a_record("b.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let report = records.dedup();
+        assert_eq!(report.removed().len(), 1);
+        assert_eq!(report.ttl_changes().len(), 2);
+        assert_eq!(records.records.len(), 3);
+        assert!(records.dedup().is_empty());
+    }
+
+    #[test]
+    fn dedup_signed() {
+        use self::signed::{a, rrsig};
+
+        let mut records: SortedRecords<Name, Data> = vec![
+            record("a.example.", 3600, a("192.0.2.1")),
+            record("a.example.", 300, a("192.0.2.1")),
+            record("a.example.", 3600, rrsig(Rtype::A, 3600, 1)),
+            record("a.example.", 3600, rrsig(Rtype::A, 3600, 1)),
+            record("a.example.", 3600, rrsig(Rtype::A, 3600, 2)),
+        ]
+        .into();
+        let report = records.dedup();
+        assert_eq!(report.removed().len(), 2);
+        assert_eq!(records.records.len(), 3);
+        assert!(records.records.iter().all(|record| record.ttl() == 300));
+        assert!(records.dedup().is_empty());
+    }
+
+    #[test]
+    fn classify() {
+        use crate::rdata::{self, Ns};
+
+        fn a() -> Data {
+            A::from_str("192.0.2.1").unwrap().into()
+        }
+
+        let records: SortedRecords<Name, Data> = vec![
+            record("example.", 3600, Ns::new(name("ns.example.")).into()),
+            record("ns.example.", 3600, a()),
+            record(
+                "sub.example.",
+                3600,
+                Ns::new(name("ns.sub.example.")).into(),
+            ),
+            record("sub.example.", 3600, a()),
+            record("ns.sub.example.", 3600, a()),
+            record(
+                "foo.sub.example.",
+                3600,
+                Ns::new(name("bar.example.")).into(),
+            ),
+            record(
+                "d.example.",
+                3600,
+                rdata::Dname::new(name("other.")).into(),
+            ),
+            record("x.d.example.", 3600, a()),
+            record("other.", 3600, a()),
+        ]
+        .into();
+        let apex = FamilyName::new(name("example."), Class::In);
+        let res: Vec<_> = records
+            .classify(&apex)
+            .map(|(record, class)| {
+                (format!("{} {}", record.owner(), record.rtype()), class)
+            })
+            .collect();
+        assert_eq!(
+            res,
+            [
+                ("example NS".into(), Classification::Authoritative),
+                ("d.example DNAME".into(), Classification::Authoritative),
+                ("x.d.example A".into(), Classification::Occluded),
+                ("ns.example A".into(), Classification::Authoritative),
+                ("sub.example A".into(), Classification::Glue),
+                ("sub.example NS".into(), Classification::Delegation),
+                ("foo.sub.example NS".into(), Classification::Occluded),
+                ("ns.sub.example A".into(), Classification::Glue),
+                ("other A".into(), Classification::OutOfZone),
+            ]
+        );
+    }
+
+    #[test]
+    fn zone_cuts_and_subtree() {
+        use crate::rdata::{self, Ns};
+
+        fn a() -> Data {
+            A::from_str("192.0.2.1").unwrap().into()
+        }
+
+        fn ns(target: &str) -> Data {
+            Ns::new(name(target)).into()
+        }
+
+        let records: SortedRecords<Name, Data> = vec![
+            record("example.", 3600, ns("ns.example.")),
+            record("ns.example.", 3600, a()),
+            record("sub.example.", 3600, ns("ns.sub.example.")),
+            record("ns.sub.example.", 3600, a()),
+            record("deep.sub.example.", 3600, ns("ns.deep.sub.example.")),
+            record(
+                "www.sub.example.",
+                3600,
+                rdata::Txt::from_slice(b"x").unwrap().into(),
+            ),
+            record(
+                "d.example.",
+                3600,
+                rdata::Dname::new(name("other.")).into(),
+            ),
+            record("sub.d.example.", 3600, ns("ns.other.")),
+            record("other.example.", 3600, ns("ns.other.")),
+            record("other.", 3600, ns("ns.other.")),
+        ]
+        .into();
+        let apex = FamilyName::new(name("example."), Class::In);
+        let cuts: Vec<_> = records.zone_cuts(&apex).collect();
+        assert_eq!(
+            cuts.iter()
+                .map(|cut| format!("{}", cut.owner()))
+                .collect::<Vec<_>>(),
+            ["other.example", "sub.example"]
+        );
+        assert_eq!(cuts[1].records().count(), 4);
+        assert_eq!(cuts[1].ns().unwrap().iter().count(), 1);
+        assert!(cuts[1].ds().is_none());
+        assert_eq!(
+            cuts[1]
+                .glue()
+                .map(|record| format!("{}", record.owner()))
+                .collect::<Vec<_>>(),
+            ["ns.sub.example"]
+        );
+
+        let subtree = FamilyName::new(name("sub.example."), Class::In);
+        assert_eq!(
+            records
+                .subtree(&subtree)
+                .map(|family| format!("{}", family.owner()))
+                .collect::<Vec<_>>(),
+            [
+                "sub.example",
+                "deep.sub.example",
+                "ns.sub.example",
+                "www.sub.example"
+            ]
+        );
+        let missing = FamilyName::new(name("nope.example."), Class::In);
+        assert_eq!(records.subtree(&missing).count(), 0);
+    }
+
+    #[test]
+    fn write_split() {
+        use core::cell::RefCell;
+        use std::string::String;
+
+        struct Target<'a>(&'a RefCell<Vec<(String, usize)>>);
+
+        impl<'a> io::Write for Target<'a> {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+                let lines = buf.iter().filter(|&&ch| ch == b'\n').count();
+                self.0.borrow_mut().last_mut().unwrap().1 += lines;
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<(), io::Error> {
+                Ok(())
+            }
+        }
+
+        let records: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("a.example.", 3600, "192.0.2.2"),
+            a_record("b.example.", 3600, "192.0.2.1"),
+            a_record("c.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let chunks = RefCell::new(Vec::new());
+        records
+            .write_split(2, |idx, owner| {
+                assert_eq!(idx, chunks.borrow().len());
+                chunks.borrow_mut().push((format!("{}", owner), 0));
+                Ok(Target(&chunks))
+            })
+            .unwrap();
+        assert_eq!(
+            chunks.into_inner(),
+            [("a.example".into(), 2), ("b.example".into(), 2)]
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn write_zstd() {
+        let records: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("b.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let mut plain = Vec::new();
+        records.write(&mut plain).unwrap();
+        let compressed = records.write_zstd(Vec::new(), 0).unwrap();
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), plain);
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn nsec3_hash() {
+        // RFC 5155, appendix A.
+        let hash = super::nsec3_hash(
+            &name("example."),
+            Nsec3HashAlg::Sha1,
+            12,
+            b"\xaa\xbb\xcc\xdd",
+        )
+        .unwrap();
+        let mut hash_str = std::string::String::new();
+        base32::display_hex(&hash, &mut hash_str).unwrap();
+        assert_eq!(
+            hash_str.to_ascii_lowercase(),
+            "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom"
+        );
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn nsec3s_opt_out() {
+        use crate::base::iana::{DigestAlg, SecAlg};
+        use crate::base::serial::Serial;
+        use crate::rdata::{Mx, Ns};
+        use std::string::ToString;
+
+        fn hash(owner: &str) -> Vec<u8> {
+            super::nsec3_hash(
+                &name(owner),
+                Nsec3HashAlg::Sha1,
+                12,
+                b"\xaa\xbb\xcc\xdd",
+            )
+            .unwrap()
+        }
+
+        // The example zone of RFC 5155, appendix A, which was signed with
+        // opt-out. Only the record types matter for the NSEC3 chain.
+        let a = |addr| -> Data { A::from_str(addr).unwrap().into() };
+        let ns = |target| -> Data { Ns::new(name(target)).into() };
+        let mx = |target| -> Data { Mx::new(1, name(target)).into() };
+        let records: SortedRecords<Name, Data> = vec![
+            record(
+                "example.",
+                3600,
+                Soa::new(
+                    name("ns1.example."),
+                    name("bugs.x.w.example."),
+                    Serial(1),
+                    3600,
+                    300,
+                    3_600_000,
+                    3600,
+                )
+                .into(),
+            ),
+            record("example.", 3600, ns("ns1.example.")),
+            record("example.", 3600, ns("ns2.example.")),
+            record("example.", 3600, mx("xx.example.")),
+            record("a.example.", 3600, ns("ns1.a.example.")),
+            record("a.example.", 3600, ns("ns2.a.example.")),
+            record(
+                "a.example.",
+                3600,
+                Ds::new(58470, SecAlg::RsaSha1, DigestAlg::Sha1, vec![0; 20])
+                    .into(),
+            ),
+            record("ns1.a.example.", 3600, a("192.0.2.5")),
+            record("ns2.a.example.", 3600, a("192.0.2.6")),
+            record("ai.example.", 3600, a("192.0.2.9")),
+            record("b.example.", 3600, ns("ns1.b.example.")),
+            record("b.example.", 3600, ns("ns2.b.example.")),
+            record("ns1.b.example.", 3600, a("192.0.2.7")),
+            record("ns2.b.example.", 3600, a("192.0.2.8")),
+            record("ns1.example.", 3600, a("192.0.2.1")),
+            record("ns2.example.", 3600, a("192.0.2.2")),
+            record("*.w.example.", 3600, mx("ai.example.")),
+            record("x.w.example.", 3600, mx("xx.example.")),
+            record("x.y.w.example.", 3600, mx("xx.example.")),
+            record("xx.example.", 3600, a("192.0.2.10")),
+        ]
+        .into();
+        let apex = FamilyName::new(name("example."), Class::In);
+        let params = Nsec3param::new(
+            Nsec3HashAlg::Sha1,
+            0,
+            12,
+            CharStr::from_octets(vec![0xaa, 0xbb, 0xcc, 0xdd]).unwrap(),
+        );
+
+        // Without opt-out, the insecure delegation b.example is included.
+        let nsec3s = records.nsec3s::<Vec<u8>, _>(&apex, 3600, &params);
+        let nsec3s = nsec3s.unwrap();
+        assert_eq!(nsec3s.len(), 12);
+        assert!(nsec3s.iter().all(|nsec3| !nsec3.data().opt_out()));
+
+        // With opt-out, the chain matches the one in the RFC.
+        let nsec3s = records
+            .nsec3s_with_opt_out::<Vec<u8>, _>(&apex, 3600, &params, true)
+            .unwrap();
+        let owners = nsec3s
+            .iter()
+            .map(|nsec3| {
+                nsec3.owner().first().to_string().to_ascii_lowercase()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            owners,
+            [
+                "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom",
+                "2t7b4g4vsa5smi47k61mv5bv1a22bojr",
+                "2vptu5timamqttgl4luu9kg21e0aor3s",
+                "35mthgpgcu1qg68fab165klnsnk3dpvl",
+                "b4um86eghhds6nea196smvmlo4ors995",
+                "gjeqe526plbf1g8mklp59enfd789njgi",
+                "ji6neoaepv8b5o6k4ev33abha8ht9fgc",
+                "k8udemvp1j2f7eg6jebps17vp3n8i58h",
+                "q04jkcevqvmu85r014c7dkba38o0ji5r",
+                "r53bq7cc2uvmubfu5ocmm6pers9tk9en",
+                "t644ebqk9bibcna874givr6joj62mlhv",
+            ]
+        );
+        assert!(nsec3s.iter().all(|nsec3| nsec3.data().opt_out()));
+
+        // The closest encloser proof for a name below b.example: the
+        // closest encloser example. has a record and the next closer name
+        // b.example. is covered by an opt-out record.
+        let next_closer = hash("b.example.");
+        let next_closer = next_closer.as_slice();
+        assert!(nsec3s.iter().enumerate().any(|(idx, nsec3)| {
+            // The owner hash is the next hash of the previous record.
+            let prev = if idx == 0 { nsec3s.len() - 1 } else { idx - 1 };
+            let this = nsec3s[prev].data().next_owner().as_slice();
+            let next = nsec3s[idx].data().next_owner().as_slice();
+            let covered = if this < next {
+                this < next_closer && next_closer < next
+            } else {
+                this < next_closer || next_closer < next
+            };
+            covered && nsec3.data().opt_out()
+        }));
+        assert_eq!(
+            nsec3s.last().unwrap().data().next_owner().as_slice(),
+            hash("example.").as_slice()
+        );
+    }
+
+    #[test]
+    fn cap_ttls() {
+        let mut records: SortedRecords<_, _> = vec![
+            a_record("a.example.", 3600, "192.0.2.1"),
+            a_record("b.example.", 60, "192.0.2.1"),
+        ]
+        .into();
+        let changes = records.cap_ttls(300);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(format!("{}", changes[0].owner()), "a.example");
+        assert!(records.rrsets().all(|rrset| rrset.ttl() <= 300));
+    }
+
+    #[test]
+    fn empty_non_terminals() {
+        use crate::rdata::Ns;
+        use std::string::ToString;
+
+        let a: Data = A::from_str("192.0.2.1").unwrap().into();
+        let ns: Data = Ns::new(name("ns.example.")).into();
+        let records: SortedRecords<Name, Data> = vec![
+            record("example.", 3600, a.clone()),
+            record("a.b.c.example.", 3600, a.clone()),
+            record("d.b.c.example.", 3600, a.clone()),
+            record("c.example.", 3600, a.clone()),
+            record("x.y.example.", 3600, a.clone()),
+            record("sub.z.example.", 3600, ns),
+            record("deep.below.sub.z.example.", 3600, a.clone()),
+            record("w.v.other.", 3600, a),
+        ]
+        .into();
+        let apex = FamilyName::new(name("example."), Class::In);
+        assert_eq!(
+            records
+                .empty_non_terminals::<Vec<u8>, _>(&apex)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["b.c.example", "y.example", "z.example"]
+        );
+    }
+
+    #[test]
+    fn find_apex() {
+        let soa = |serial| -> Data { testing::soa(serial).into() };
+        let a: Data = A::from_str("192.0.2.1").unwrap().into();
+        let mut records: SortedRecords<Name, Data> = vec![
+            record("example.", 3600, soa(1)),
+            record("www.example.", 3600, a.clone()),
+        ]
+        .into();
+        let apex = records.find_apex().unwrap();
+        assert_eq!(apex, FamilyName::new(name("example."), Class::In));
+        assert_eq!(
+            records.nsecs_from_soa::<Vec<u8>>(300).unwrap(),
+            records.nsecs::<Vec<u8>, _>(&apex, 300)
+        );
+
+        let mut other = records.clone();
+        other
+            .insert(Record::new(
+                name("www.example."),
+                Class::Ch,
+                3600,
+                a.clone(),
+            ))
+            .unwrap();
+        assert_eq!(
+            other.find_apex(),
+            Err(ApexError::ClassMismatch(Class::Ch))
+        );
+
+        let mut other = records.clone();
+        other.insert(record("sub.example.", 3600, soa(1))).unwrap();
+        assert_eq!(other.find_apex(), Err(ApexError::MultipleSoa));
+
+        records.insert(record("example.", 3600, soa(2))).unwrap();
+        assert_eq!(records.find_apex(), Err(ApexError::MultipleSoa));
+
+        records.retain(|record| record.rtype() != Rtype::Soa);
+        assert_eq!(records.find_apex(), Err(ApexError::NoSoa));
+        assert_eq!(
+            records.nsecs_from_soa::<Vec<u8>>(300),
+            Err(ApexError::NoSoa)
+        );
+    }
+
+    /// A fake key whose signature is a checksum over the signed data.
+    struct ChecksumKey {
+        incremental: bool,
+    }
+
+    impl ChecksumKey {
+        fn update(sum: &mut u32, data: &[u8]) {
+            for &octet in data {
+                *sum = sum.wrapping_mul(31).wrapping_add(u32::from(octet));
+            }
+        }
+    }
+
+    impl SigningKey for ChecksumKey {
+        type Octets = Vec<u8>;
+        type Signature = Vec<u8>;
+        type Error = ();
+
+        fn dnskey(&self) -> Result<Dnskey<Vec<u8>>, ()> {
+            Ok(Dnskey::new(
+                256,
+                3,
+                crate::base::iana::SecAlg::Ed25519,
+                vec![],
+            ))
+        }
+
+        fn ds<N: ToDname>(&self, _owner: N) -> Result<Ds<Vec<u8>>, ()> {
+            Err(())
+        }
+
+        fn sign(&self, data: &[u8]) -> Result<Vec<u8>, ()> {
+            let mut sum = 0;
+            Self::update(&mut sum, data);
+            Ok(sum.to_be_bytes().to_vec())
+        }
+
+        fn sign_incremental<F>(
+            &self,
+            buf: &mut Vec<u8>,
+            data: F,
+        ) -> Result<Vec<u8>, ()>
+        where
+            F: FnOnce(&mut dyn FnMut(&[u8])),
+        {
+            if !self.incremental {
+                buf.clear();
+                data(&mut |piece| buf.extend_from_slice(piece));
+                return self.sign(buf);
+            }
+            let mut sum = 0;
+            data(&mut |piece| Self::update(&mut sum, piece));
+            Ok(sum.to_be_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn signed_data() {
+        let records: SortedRecords<_, _> = vec![
+            a_record("example.", 3600, "192.0.2.1"),
+            a_record("example.", 3600, "192.0.2.2"),
+            a_record("www.example.", 3600, "192.0.2.1"),
+        ]
+        .into();
+        let rrset = records.rrsets().next().unwrap();
+        let rrsig = ProtoRrsig::new(
+            Rtype::A,
+            crate::base::iana::SecAlg::Ed25519,
+            1,
+            3600,
+            Time32::from_int(2),
+            Time32::from_int(1),
+            0,
+            name("example."),
+        );
+
+        let mut data = Vec::new();
+        rrset.compose_signed_data(&rrsig, &mut data).unwrap();
+        let mut pieces = Vec::new();
+        let mut fed = Vec::new();
+        rrset
+            .feed_signed_data(&rrsig, &mut Vec::new(), |piece| {
+                pieces.push(piece.len());
+                fed.extend_from_slice(piece);
+            })
+            .unwrap();
+        assert_eq!(data, fed);
+        assert_eq!(pieces.len(), 3);
+
+        // Signing with a key hashing incrementally gives the same result.
+        let apex = FamilyName::new(name("example."), Class::In);
+        let sign = |incremental| {
+            records
+                .sign::<Vec<u8>, _, _>(
+                    &apex,
+                    Time32::from_int(2),
+                    Time32::from_int(1),
+                    ChecksumKey { incremental },
+                )
+                .unwrap()
+        };
+        let collected = sign(false);
+        assert_eq!(collected.len(), 2);
+        assert_eq!(collected, sign(true));
+    }
+}
@@ -55,6 +55,36 @@ impl<'a> Key<'a> {
             rng,
         })
     }
+
+    pub fn ed25519_from_seed(
+        flags: u16,
+        seed: &[u8],
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, Unspecified> {
+        let keypair = Ed25519KeyPair::from_seed_unchecked(seed)
+            .map_err(|_| Unspecified)?;
+        let public_key = keypair.public_key().as_ref().into();
+        Ok(Key {
+            dnskey: Dnskey::new(flags, 3, SecAlg::Ed25519, public_key),
+            key: RingKey::Ed25519(keypair),
+            rng,
+        })
+    }
+
+    pub fn throwaway_15(
+        flags: u16,
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, Unspecified> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(rng)?;
+        let keypair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+            .map_err(|_| Unspecified)?;
+        let public_key = keypair.public_key().as_ref().into();
+        Ok(Key {
+            dnskey: Dnskey::new(flags, 3, SecAlg::Ed25519, public_key),
+            key: RingKey::Ed25519(keypair),
+            rng,
+        })
+    }
 }
 
 impl<'a> SigningKey for Key<'a> {
@@ -0,0 +1,1971 @@
+//! Signing complete zones.
+//!
+//! This module provides [`sign_zone`], a single function that turns an
+//! unsigned zone into a signed zone according to a [`SigningPolicy`]. It
+//! takes care of adding the DNSKEY records, creating the records for
+//! authenticated denial of existence via either NSEC or NSEC3, updating the
+//! SOA serial, signing all authoritative RRsets, and, optionally, adding a
+//! ZONEMD record.
+//!
+//! Zones can be signed with keys of several algorithms at once as is
+//! necessary during an algorithm rollover. In this case, every RRset is
+//! signed with every algorithm as required by RFC 4035, section 2.2. The
+//! function [`missing_signatures`] can be used to check that a signed
+//! zone fulfills this requirement. An [`Nsec3Policy`] guards against NSEC3
+//! parameters outside of current best practice, both when signing and for
+//! existing zones.
+//!
+//! After signing, a [`SigningReport`] summarizes what has been done for
+//! audit logs and monitoring.
+//!
+//! [`sign_zone`]: fn.sign_zone.html
+//! [`missing_signatures`]: fn.missing_signatures.html
+//! [`Nsec3Policy`]: struct.Nsec3Policy.html
+//! [`SigningPolicy`]: struct.SigningPolicy.html
+//! [`SigningReport`]: struct.SigningReport.html
+#![cfg(feature = "ring")]
+
+use super::key::{KeyPolicy, SigningKey, WeakKey};
+use super::records::{
+    octets_from_slice, Classification, FamilyName, Nsec3Error, Rrset,
+    SortedRecords,
+};
+use crate::base::clock::{Clock, SystemClock};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Rtype, SecAlg, ZonemdAlg, ZonemdScheme};
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::{Compose, EmptyBuilder, FromBuilder};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::base::time32::{civil_from_days, Time32};
+use crate::rdata::rfc4034::ProtoRrsig;
+use crate::rdata::{MasterRecordData, Nsec3param, Rrsig, Soa, Zonemd};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ring::digest;
+use std::collections::HashSet;
+use std::string::{String, ToString};
+use std::sync::Arc;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ SigningPolicy -------------------------------------------------
+
+/// The policy to follow when signing a zone.
+#[derive(Clone)]
+pub struct SigningPolicy<Octets> {
+    /// The method for authenticated denial of existence.
+    pub denial: Denial<Octets>,
+
+    /// Whether to use opt-out when creating NSEC3 records.
+    ///
+    /// If this is `true` and NSEC3 is used for denial of existence,
+    /// insecure delegations are left out of the NSEC3 chain as described
+    /// in RFC 5155, section 6. This is ignored when NSEC is used.
+    pub nsec3_opt_out: bool,
+
+    /// The validity period of signatures in seconds.
+    pub validity: u32,
+
+    /// The number of seconds the inception time is set into the past.
+    ///
+    /// This allows for validators with slightly wrong clocks.
+    pub inception_offset: u32,
+
+    /// The maximum number of seconds to randomly reduce the validity by.
+    ///
+    /// The jitter is chosen separately for each RRset so that signatures
+    /// don’t all expire at the same time.
+    pub jitter: u32,
+
+    /// How to determine the TTL of RRSIG records.
+    pub rrsig_ttl: RrsigTtl,
+
+    /// How to update the serial of the SOA record.
+    pub serial: SerialPolicy,
+
+    /// The hash algorithm for a ZONEMD record or `None` to not add one.
+    pub zonemd: Option<ZonemdAlg>,
+
+    /// Algorithms whose keys sign the zone without publishing a DNSKEY.
+    ///
+    /// During a conservative algorithm rollover as described in RFC 6781,
+    /// section 4.1.4, the signatures of the new algorithm are added to the
+    /// zone before its DNSKEY records so that validators have them cached
+    /// once the keys appear. Similarly, the signatures of the old algorithm
+    /// are kept for a while after its DNSKEY records have been removed.
+    /// Keys of the algorithms listed here are used for signing but their
+    /// DNSKEY records are not added to the zone.
+    pub unpublished: Vec<SecAlg>,
+
+    /// The source for the current time.
+    ///
+    /// The time is used for the validity period of signatures and for
+    /// updating the SOA serial. This defaults to the system clock.
+    pub clock: Arc<dyn Clock>,
+
+    /// The seed for the random jitter or `None` for a random seed.
+    ///
+    /// Together with a mock clock and keys of a deterministic algorithm
+    /// such as Ed25519, a fixed seed makes signing reproducible which is
+    /// useful for comparing signed zones in tests.
+    pub seed: Option<u64>,
+
+    /// The policy the signing keys are checked against before signing.
+    ///
+    /// This defaults to [`KeyPolicy::strict`]. Use
+    /// [`KeyPolicy::permissive`] to sign with any key.
+    ///
+    /// [`KeyPolicy::strict`]: ../key/struct.KeyPolicy.html#method.strict
+    /// [`KeyPolicy::permissive`]: ../key/struct.KeyPolicy.html#method.permissive
+    pub key_policy: KeyPolicy,
+
+    /// The policy NSEC3 parameters are checked against before signing.
+    ///
+    /// This defaults to [`Nsec3Policy::strict`] which follows RFC 9276.
+    /// Use [`Nsec3Policy::permissive`] to sign with any parameters.
+    ///
+    /// [`Nsec3Policy::strict`]: struct.Nsec3Policy.html#method.strict
+    /// [`Nsec3Policy::permissive`]: struct.Nsec3Policy.html#method.permissive
+    pub nsec3_policy: Nsec3Policy,
+}
+
+impl<Octets> Default for SigningPolicy<Octets> {
+    fn default() -> Self {
+        SigningPolicy {
+            denial: Denial::Nsec,
+            nsec3_opt_out: false,
+            validity: 30 * 24 * 3600,
+            inception_offset: 3600,
+            jitter: 0,
+            rrsig_ttl: RrsigTtl::default(),
+            serial: SerialPolicy::Keep,
+            zonemd: None,
+            unpublished: Vec::new(),
+            clock: Arc::new(SystemClock),
+            seed: None,
+            key_policy: KeyPolicy::default(),
+            nsec3_policy: Nsec3Policy::default(),
+        }
+    }
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for SigningPolicy<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SigningPolicy")
+            .field("denial", &self.denial)
+            .field("nsec3_opt_out", &self.nsec3_opt_out)
+            .field("validity", &self.validity)
+            .field("inception_offset", &self.inception_offset)
+            .field("jitter", &self.jitter)
+            .field("rrsig_ttl", &self.rrsig_ttl)
+            .field("serial", &self.serial)
+            .field("zonemd", &self.zonemd)
+            .field("unpublished", &self.unpublished)
+            .field("clock", &self.clock)
+            .field("seed", &self.seed)
+            .field("key_policy", &self.key_policy)
+            .field("nsec3_policy", &self.nsec3_policy)
+            .finish()
+    }
+}
+
+//------------ Denial --------------------------------------------------------
+
+/// The method for authenticated denial of existence.
+#[derive(Clone)]
+pub enum Denial<Octets> {
+    /// Use NSEC records.
+    Nsec,
+
+    /// Use NSEC3 records with the given parameters.
+    ///
+    /// The parameters are also used for the NSEC3PARAM record added to
+    /// the apex.
+    Nsec3(Nsec3param<Octets>),
+}
+
+//--- Debug
+
+impl<Octets: AsRef<[u8]>> fmt::Debug for Denial<Octets> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Denial::Nsec => f.write_str("Nsec"),
+            Denial::Nsec3(ref params) => {
+                f.debug_tuple("Nsec3").field(params).finish()
+            }
+        }
+    }
+}
+
+//------------ SerialPolicy --------------------------------------------------
+
+/// How to update the serial of the SOA record when signing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SerialPolicy {
+    /// Leave the serial as it is.
+    Keep,
+
+    /// Increase the serial by one.
+    Increment,
+
+    /// Set the serial to the current Unix time.
+    ///
+    /// If the current serial is already equal to or larger than the
+    /// current time, it is increased by one instead.
+    UnixTime,
+
+    /// Set the serial to the current date in the form `YYYYMMDDnn`.
+    ///
+    /// The counter `nn` starts at zero. If the current serial already is
+    /// equal to or larger than that value, it is increased by one instead.
+    DateCounter,
+}
+
+impl SerialPolicy {
+    /// Returns the serial to use given the current serial.
+    pub fn next(self, serial: Serial) -> Serial {
+        self.next_at(serial, Time32::now())
+    }
+
+    /// Returns the serial to use given the current serial and time.
+    pub fn next_at(self, serial: Serial, now: Time32) -> Serial {
+        let candidate = match self {
+            SerialPolicy::Keep => return serial,
+            SerialPolicy::Increment => return serial.add(1),
+            SerialPolicy::UnixTime => now.into(),
+            SerialPolicy::DateCounter => {
+                let (year, month, day) =
+                    civil_from_days(now.into_int() / 86400);
+                Serial((year * 10000 + month * 100 + day) * 100)
+            }
+        };
+        if candidate > serial {
+            candidate
+        } else {
+            serial.add(1)
+        }
+    }
+}
+
+//------------ RrsigTtl ------------------------------------------------------
+
+/// How to determine the TTL of RRSIG records.
+///
+/// The original TTL field of an RRSIG record is always the TTL of the
+/// RRset it covers. Only the TTL of the RRSIG record itself is affected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RrsigTtl {
+    /// Use the TTL of the covered RRset.
+    ///
+    /// This is required by RFC 4034, section 3, and is the default.
+    Rrset,
+
+    /// Use the TTL of the RRset but at most the remaining validity.
+    ///
+    /// The TTL is limited to the number of seconds from the signing time
+    /// until the signature expires. This keeps caches from holding on to
+    /// a signature past its expiration when the zone is served without
+    /// adjusting TTLs, which validators would otherwise have to correct
+    /// as described in RFC 4035, section 5.3.3.
+    ClampToValidity,
+}
+
+impl RrsigTtl {
+    /// Returns the TTL of an RRSIG record.
+    ///
+    /// The RRSIG record covers an RRset with the TTL `ttl` and expires at
+    /// `expiration`. The signing time is `now`.
+    pub fn ttl(self, ttl: u32, now: Time32, expiration: Time32) -> u32 {
+        match self {
+            RrsigTtl::Rrset => ttl,
+            RrsigTtl::ClampToValidity => {
+                if expiration <= now {
+                    0
+                } else {
+                    core::cmp::min(
+                        ttl,
+                        expiration.into_int().wrapping_sub(now.into_int()),
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)] // #[default] needs Rust 1.62.
+impl Default for RrsigTtl {
+    fn default() -> Self {
+        RrsigTtl::Rrset
+    }
+}
+
+//------------ Nsec3Policy ---------------------------------------------------
+
+/// A policy for rejecting NSEC3 parameters outside of best practice.
+///
+/// RFC 9276 recommends an iteration count of zero and an empty salt for
+/// NSEC3 since additional iterations make zone walking hardly more
+/// difficult but cost authoritative servers and validators considerable
+/// effort. Validators may even treat answers with a high iteration count
+/// as insecure or bogus. The policy limits the iteration count and the
+/// length of the salt. There are three presets: [`strict`], which is also
+/// the default, [`relaxed`], and [`permissive`].
+///
+/// The policy is used by [`sign_zone`] to reject NSEC3 parameters before
+/// signing. Its methods [`check_params`] and [`check_zone`] return all
+/// violations instead so that they can be reported as warnings.
+///
+/// [`strict`]: #method.strict
+/// [`relaxed`]: #method.relaxed
+/// [`permissive`]: #method.permissive
+/// [`check_params`]: #method.check_params
+/// [`check_zone`]: #method.check_zone
+/// [`sign_zone`]: fn.sign_zone.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Nsec3Policy {
+    /// The maximum number of additional hash iterations.
+    pub max_iterations: u16,
+
+    /// The maximum length of the salt in octets.
+    pub max_salt_len: usize,
+}
+
+impl Nsec3Policy {
+    /// Returns a strict policy.
+    ///
+    /// This policy follows RFC 9276, section 3.1, and only accepts an
+    /// iteration count of zero and an empty salt.
+    pub fn strict() -> Self {
+        Nsec3Policy {
+            max_iterations: 0,
+            max_salt_len: 0,
+        }
+    }
+
+    /// Returns a relaxed policy.
+    ///
+    /// This policy accepts up to 100 iterations, the lowest limit at which
+    /// RFC 9276, section 3.2, suggests validators may treat answers as
+    /// insecure, and salts of up to eight octets.
+    pub fn relaxed() -> Self {
+        Nsec3Policy {
+            max_iterations: 100,
+            max_salt_len: 8,
+        }
+    }
+
+    /// Returns a policy that accepts all parameters.
+    pub fn permissive() -> Self {
+        Nsec3Policy {
+            max_iterations: u16::MAX,
+            max_salt_len: usize::MAX,
+        }
+    }
+
+    /// Checks NSEC3 parameters against the policy.
+    ///
+    /// Returns all violations or an empty vec if the parameters are fine.
+    pub fn check_params<Octets: AsRef<[u8]>>(
+        &self,
+        params: &Nsec3param<Octets>,
+    ) -> Vec<Nsec3Weakness> {
+        let mut res = Vec::new();
+        self.check_values(
+            params.iterations(),
+            params.salt().as_slice().len(),
+            &mut res,
+        );
+        res
+    }
+
+    /// Checks the NSEC3 parameters used by a zone against the policy.
+    ///
+    /// The parameters of all NSEC3PARAM and NSEC3 records of the zone are
+    /// checked. Each violation is only returned once even if several
+    /// records share it.
+    pub fn check_zone<N, Octets>(
+        &self,
+        records: &SortedRecords<N, MasterRecordData<Octets, N>>,
+    ) -> Vec<Nsec3Weakness>
+    where
+        N: ToDname,
+        Octets: AsRef<[u8]>,
+    {
+        let mut res = Vec::new();
+        for record in records.families().flat_map(|family| family.records()) {
+            match *record.data() {
+                MasterRecordData::Nsec3param(ref params) => self
+                    .check_values(
+                        params.iterations(),
+                        params.salt().as_slice().len(),
+                        &mut res,
+                    ),
+                MasterRecordData::Nsec3(ref nsec3) => self.check_values(
+                    nsec3.iterations(),
+                    nsec3.salt().as_slice().len(),
+                    &mut res,
+                ),
+                _ => {}
+            }
+        }
+        res
+    }
+
+    /// Adds the violations of the given values not yet in `res`.
+    fn check_values(
+        &self,
+        iterations: u16,
+        salt_len: usize,
+        res: &mut Vec<Nsec3Weakness>,
+    ) {
+        let mut add = |weakness| {
+            if !res.contains(&weakness) {
+                res.push(weakness)
+            }
+        };
+        if iterations > self.max_iterations {
+            add(Nsec3Weakness::Iterations(iterations))
+        }
+        if salt_len > self.max_salt_len {
+            add(Nsec3Weakness::LongSalt(salt_len))
+        }
+    }
+}
+
+//--- Default
+
+impl Default for Nsec3Policy {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+//------------ Nsec3Weakness -------------------------------------------------
+
+/// NSEC3 parameters violate an NSEC3 policy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Nsec3Weakness {
+    /// The iteration count is too high.
+    Iterations(u16),
+
+    /// The salt with the given length in octets is too long.
+    LongSalt(usize),
+}
+
+//--- Display and Error
+
+impl fmt::Display for Nsec3Weakness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nsec3Weakness::Iterations(iterations) => {
+                write!(f, "too many NSEC3 iterations ({})", iterations)
+            }
+            Nsec3Weakness::LongSalt(len) => {
+                write!(f, "NSEC3 salt too long ({} octets)", len)
+            }
+        }
+    }
+}
+
+impl error::Error for Nsec3Weakness {}
+
+//------------ sign_zone -----------------------------------------------------
+
+/// Signs a complete zone.
+///
+/// The zone’s apex is determined from the SOA record in `records`. Any
+/// RRSIG, NSEC, NSEC3, NSEC3PARAM, and ZONEMD records already present are
+/// removed and recreated according to `policy`. The DNSKEY records of all
+/// `keys` are added to the apex.
+///
+/// Keys with the secure entry point flag set are used to sign the DNSKEY,
+/// CDS, and CDNSKEY RRsets, all other keys sign the remaining RRsets. If
+/// all keys of an algorithm are of one kind, they are used for everything
+/// so that every RRset is signed with every algorithm. The DNSKEY records
+/// of keys whose algorithm is listed in the policy’s `unpublished` field
+/// are not added.
+///
+/// Before anything else, all keys are checked against the policy’s
+/// `key_policy`. If a key violates it, signing fails with
+/// [`SignError::WeakKey`]. Likewise, NSEC3 parameters are checked against
+/// the policy’s `nsec3_policy` and signing fails with
+/// [`SignError::WeakNsec3`] if they violate it.
+///
+/// Returns the signed zone.
+///
+/// [`SignError::WeakKey`]: enum.SignError.html#variant.WeakKey
+/// [`SignError::WeakNsec3`]: enum.SignError.html#variant.WeakNsec3
+#[allow(clippy::type_complexity)]
+pub fn sign_zone<N, Octets, Key>(
+    mut records: SortedRecords<N, MasterRecordData<Octets, N>>,
+    keys: &[Key],
+    policy: &SigningPolicy<Octets>,
+) -> Result<
+    SortedRecords<N, MasterRecordData<Octets, N>>,
+    SignError<Key::Error>,
+>
+where
+    N: ToDname + CanonicalOrd + Clone + From<Dname<Octets>>,
+    Octets: AsRef<[u8]> + Clone + FromBuilder,
+    Octets::Builder: EmptyBuilder,
+    Key: SigningKey,
+{
+    // Find the apex and SOA.
+    let (apex, soa_ttl, soa) = {
+        let soa = records.find_soa().ok_or(SignError::NoSoa)?;
+        let record = soa.first();
+        match *record.data() {
+            MasterRecordData::Soa(ref soa) => (
+                FamilyName::new(record.owner().clone(), record.class()),
+                record.ttl(),
+                soa.clone(),
+            ),
+            _ => return Err(SignError::NoSoa),
+        }
+    };
+    let negative_ttl = core::cmp::min(soa_ttl, soa.minimum());
+
+    // Check the keys against the key policy.
+    if keys.is_empty() {
+        return Err(SignError::NoKeys);
+    }
+    for key in keys {
+        policy
+            .key_policy
+            .check_key(&key.dnskey().map_err(SignError::Key)?)
+            .map_err(SignError::WeakKey)?;
+    }
+
+    // Check the NSEC3 parameters against the NSEC3 policy.
+    if let Denial::Nsec3(ref params) = policy.denial {
+        if let Some(weakness) =
+            policy.nsec3_policy.check_params(params).into_iter().next()
+        {
+            return Err(SignError::WeakNsec3(weakness));
+        }
+    }
+
+    // Split the keys into those signing the DNSKEY RRset and all others.
+    // This happens separately for each algorithm since every algorithm
+    // has to sign every RRset.
+    let mut algorithms = Vec::new();
+    for key in keys {
+        let algorithm = key.algorithm().map_err(SignError::Key)?;
+        if !algorithms.contains(&algorithm) {
+            algorithms.push(algorithm)
+        }
+    }
+    let mut ksks = Vec::new();
+    let mut zsks = Vec::new();
+    for algorithm in algorithms {
+        let mut alg_ksks = Vec::new();
+        let mut alg_zsks = Vec::new();
+        for key in keys {
+            let dnskey = key.dnskey().map_err(SignError::Key)?;
+            if dnskey.algorithm() != algorithm {
+                continue;
+            }
+            if dnskey.is_secure_entry_point() {
+                alg_ksks.push(key)
+            } else {
+                alg_zsks.push(key)
+            }
+        }
+        if alg_ksks.is_empty() {
+            alg_ksks = alg_zsks.clone()
+        } else if alg_zsks.is_empty() {
+            alg_zsks = alg_ksks.clone()
+        }
+        ksks.extend(alg_ksks);
+        zsks.extend(alg_zsks);
+    }
+
+    // Remove everything we are going to generate.
+    records.retain(|record| {
+        !matches!(
+            record.rtype(),
+            Rtype::Rrsig
+                | Rtype::Nsec
+                | Rtype::Nsec3
+                | Rtype::Nsec3param
+                | Rtype::Zonemd
+        )
+    });
+
+    // Update the serial.
+    let now = policy.clock.time32();
+    let serial = policy.serial.next_at(soa.serial(), now);
+    if serial != soa.serial() {
+        records
+            .retain(|record| record.rtype() != Rtype::Soa || apex != *record);
+        let _ = records.insert(
+            apex.clone().into_record(
+                soa_ttl,
+                Soa::new(
+                    soa.mname().clone(),
+                    soa.rname().clone(),
+                    serial,
+                    soa.refresh(),
+                    soa.retry(),
+                    soa.expire(),
+                    soa.minimum(),
+                )
+                .into(),
+            ),
+        );
+    }
+
+    // Add the DNSKEYs.
+    for key in keys {
+        if policy
+            .unpublished
+            .contains(&key.algorithm().map_err(SignError::Key)?)
+        {
+            continue;
+        }
+        let dnskey = apex
+            .dnskey::<_, Octets>(soa_ttl, key)
+            .map_err(SignError::Key)?;
+        let _ = records.insert(Record::from_record(dnskey));
+    }
+
+    // Add a ZONEMD placeholder so it appears in the type bitmaps.
+    if let Some(algorithm) = policy.zonemd {
+        let len = match algorithm {
+            ZonemdAlg::Sha384 => 48,
+            ZonemdAlg::Sha512 => 64,
+            _ => return Err(SignError::UnsupportedZonemd),
+        };
+        let _ = records.insert(
+            apex.clone().into_record(
+                soa_ttl,
+                Zonemd::new(
+                    serial,
+                    ZonemdScheme::Simple,
+                    algorithm,
+                    octets_from_slice(&[0; 64][..len]),
+                )
+                .into(),
+            ),
+        );
+    }
+
+    // Add NSEC or NSEC3 records.
+    match policy.denial {
+        Denial::Nsec => {
+            let nsecs = records.nsecs::<Octets, _>(&apex, negative_ttl);
+            for nsec in nsecs {
+                let _ = records.insert(Record::from_record(nsec));
+            }
+        }
+        Denial::Nsec3(ref params) => {
+            let nsec3s = records.nsec3s_with_opt_out(
+                &apex,
+                negative_ttl,
+                params,
+                policy.nsec3_opt_out,
+            )?;
+            for nsec3 in nsec3s {
+                let _ = records.insert(Record::from_record(nsec3));
+            }
+            let _ = records.insert(
+                apex.clone()
+                    .into_record(negative_ttl, params.clone().into()),
+            );
+        }
+    }
+
+    // Sign everything.
+    let mut signer = Signer::new(policy, apex.owner().clone(), now);
+    let mut rrsigs = Vec::new();
+    for (rrset, class) in records.classify_rrsets(&apex) {
+        if class != Classification::Authoritative
+            || rrset.rtype() == Rtype::Rrsig
+            || (rrset.rtype() == Rtype::Zonemd && rrset.family_name() == apex)
+        {
+            continue;
+        }
+        let keys = match rrset.rtype() {
+            Rtype::Dnskey | Rtype::Cds | Rtype::Cdnskey => &ksks,
+            _ => &zsks,
+        };
+        signer.sign(&rrset, keys, &mut rrsigs)?;
+    }
+    for rrsig in rrsigs.drain(..) {
+        let _ = records.insert(Record::from_record(rrsig));
+    }
+
+    // Calculate the ZONEMD digest and sign the ZONEMD RRset.
+    if let Some(algorithm) = policy.zonemd {
+        let digest = zonemd_digest(&records, &apex, algorithm);
+        records.retain(|record| {
+            record.rtype() != Rtype::Zonemd || apex != *record
+        });
+        let _ = records.insert(
+            apex.clone().into_record(
+                soa_ttl,
+                Zonemd::new(
+                    serial,
+                    ZonemdScheme::Simple,
+                    algorithm,
+                    octets_from_slice(digest.as_ref()),
+                )
+                .into(),
+            ),
+        );
+        if let Some(rrset) = records.rrsets().find(|rrset| {
+            rrset.rtype() == Rtype::Zonemd && rrset.family_name() == apex
+        }) {
+            signer.sign(&rrset, &zsks, &mut rrsigs)?;
+        }
+        for rrsig in rrsigs {
+            let _ = records.insert(Record::from_record(rrsig));
+        }
+    }
+
+    Ok(records)
+}
+
+/// Calculates the ZONEMD digest using the SIMPLE scheme.
+///
+/// The digest covers all records within the zone in canonical order except
+/// for the ZONEMD RRset at the apex and the RRSIG records covering it as
+/// described in RFC 8976, section 3.3.1.
+fn zonemd_digest<N, Octets>(
+    records: &SortedRecords<N, MasterRecordData<Octets, N>>,
+    apex: &FamilyName<N>,
+    algorithm: ZonemdAlg,
+) -> digest::Digest
+where
+    N: ToDname,
+    Octets: AsRef<[u8]>,
+{
+    let mut ctx = match algorithm {
+        ZonemdAlg::Sha512 => digest::Context::new(&digest::SHA512),
+        _ => digest::Context::new(&digest::SHA384),
+    };
+    let mut buf = Vec::new();
+    for family in records.families() {
+        if !family.is_in_zone(apex) {
+            continue;
+        }
+        let at_apex = family.family_name() == *apex;
+        for record in family.records() {
+            if at_apex {
+                match *record.data() {
+                    MasterRecordData::Zonemd(_) => continue,
+                    MasterRecordData::Rrsig(ref rrsig)
+                        if rrsig.type_covered() == Rtype::Zonemd =>
+                    {
+                        continue
+                    }
+                    _ => {}
+                }
+            }
+            buf.clear();
+            record.compose_canonical(&mut buf).unwrap();
+            ctx.update(&buf);
+        }
+    }
+    ctx.finish()
+}
+
+//------------ missing_signatures --------------------------------------------
+
+/// Returns the RRsets of a signed zone lacking a signature by an algorithm.
+///
+/// According to RFC 4035, section 2.2, every authoritative RRset of a zone
+/// needs to be signed by at least one key of every algorithm present in
+/// the zone’s DNSKEY RRset. This function returns an item for each RRset
+/// and algorithm for which there is no RRSIG record. The zone’s apex is
+/// determined from the SOA record. If there is none, the function returns
+/// an empty list.
+pub fn missing_signatures<N, Octets>(
+    records: &SortedRecords<N, MasterRecordData<Octets, N>>,
+) -> Vec<MissingSignature<N>>
+where
+    N: ToDname + Clone,
+    Octets: AsRef<[u8]>,
+{
+    let apex = match records.find_soa() {
+        Some(soa) => soa.family_name().cloned(),
+        None => return Vec::new(),
+    };
+
+    // Collect the algorithms of the DNSKEY RRset and all signatures.
+    let mut algorithms = Vec::new();
+    let mut signatures = HashSet::new();
+    for record in records.families().flat_map(|family| family.records()) {
+        match *record.data() {
+            MasterRecordData::Dnskey(ref dnskey)
+                if apex == *record
+                    && !algorithms.contains(&dnskey.algorithm()) =>
+            {
+                algorithms.push(dnskey.algorithm())
+            }
+            MasterRecordData::Rrsig(ref rrsig) => {
+                signatures.insert((
+                    record.owner().to_vec(),
+                    record.class(),
+                    rrsig.type_covered(),
+                    rrsig.algorithm(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let mut res = Vec::new();
+    for (rrset, class) in records.classify_rrsets(&apex) {
+        if class != Classification::Authoritative
+            || rrset.rtype() == Rtype::Rrsig
+        {
+            continue;
+        }
+        let owner = rrset.owner().to_vec();
+        for &algorithm in &algorithms {
+            if !signatures.contains(&(
+                owner.clone(),
+                rrset.class(),
+                rrset.rtype(),
+                algorithm,
+            )) {
+                res.push(MissingSignature {
+                    owner: rrset.owner().clone(),
+                    rtype: rrset.rtype(),
+                    algorithm,
+                })
+            }
+        }
+    }
+    res
+}
+
+//------------ MissingSignature ----------------------------------------------
+
+/// An RRset lacks a signature by one of the zone’s algorithms.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MissingSignature<N> {
+    owner: N,
+    rtype: Rtype,
+    algorithm: SecAlg,
+}
+
+impl<N> MissingSignature<N> {
+    /// Returns the owner name of the RRset.
+    pub fn owner(&self) -> &N {
+        &self.owner
+    }
+
+    /// Returns the record type of the RRset.
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+
+    /// Returns the algorithm the RRset isn’t signed with.
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+}
+
+//--- Display
+
+impl<N: fmt::Display> fmt::Display for MissingSignature<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} not signed with algorithm {}",
+            self.owner, self.rtype, self.algorithm
+        )
+    }
+}
+
+//------------ SigningReport -------------------------------------------------
+
+/// A summary of a signed zone for audit logs and monitoring.
+///
+/// The report is created by [`SigningReport::new`] from the unsigned zone
+/// and the zone returned by [`sign_zone`]. It contains the number of RRsets
+/// signed by each key, the length of the NSEC or NSEC3 chain, the range of
+/// signature expiration times, the change in size of the zone, and the
+/// RRsets that have been left unsigned because they aren’t authoritative.
+///
+/// The method [`to_json`] produces a machine-readable form of the report.
+///
+/// [`SigningReport::new`]: #method.new
+/// [`sign_zone`]: fn.sign_zone.html
+/// [`to_json`]: #method.to_json
+#[derive(Clone, Debug)]
+pub struct SigningReport<N> {
+    /// The number of RRsets signed by each key.
+    keys: Vec<KeyUsage>,
+
+    /// The number of NSEC records.
+    nsecs: usize,
+
+    /// The number of NSEC3 records.
+    nsec3s: usize,
+
+    /// The earliest signature expiration time.
+    earliest_expiration: Option<Time32>,
+
+    /// The latest signature expiration time.
+    latest_expiration: Option<Time32>,
+
+    /// The number of records before and after signing.
+    records: (usize, usize),
+
+    /// The size of the zone in octets before and after signing.
+    size: (usize, usize),
+
+    /// The RRsets left unsigned.
+    skipped: Vec<SkippedRrset<N>>,
+}
+
+impl<N> SigningReport<N> {
+    /// Creates the report for a zone from its unsigned and signed versions.
+    ///
+    /// The size of a zone is the sum of the lengths of the canonical wire
+    /// format of all its records. The apex of the signed zone is determined
+    /// from its SOA record. If there is none, no RRsets are considered
+    /// skipped.
+    pub fn new<Octets, NN>(
+        unsigned: &SortedRecords<NN, MasterRecordData<Octets, NN>>,
+        signed: &SortedRecords<N, MasterRecordData<Octets, N>>,
+    ) -> Self
+    where
+        N: ToDname + Clone,
+        NN: ToDname,
+        Octets: AsRef<[u8]>,
+    {
+        let mut buf = Vec::new();
+        let (records_before, size_before) = zone_size(unsigned, &mut buf);
+        let (records_after, size_after) = zone_size(signed, &mut buf);
+        let mut res = SigningReport {
+            keys: Vec::new(),
+            nsecs: 0,
+            nsec3s: 0,
+            earliest_expiration: None,
+            latest_expiration: None,
+            records: (records_before, records_after),
+            size: (size_before, size_after),
+            skipped: Vec::new(),
+        };
+
+        for record in signed.families().flat_map(|family| family.records()) {
+            match *record.data() {
+                MasterRecordData::Rrsig(ref rrsig) => {
+                    res.add_signature(rrsig.algorithm(), rrsig.key_tag());
+                    let expiration = rrsig.expiration();
+                    match res.earliest_expiration {
+                        Some(earliest) if earliest <= expiration => {}
+                        _ => res.earliest_expiration = Some(expiration),
+                    }
+                    match res.latest_expiration {
+                        Some(latest) if latest >= expiration => {}
+                        _ => res.latest_expiration = Some(expiration),
+                    }
+                }
+                MasterRecordData::Nsec(_) => res.nsecs += 1,
+                MasterRecordData::Nsec3(_) => res.nsec3s += 1,
+                _ => {}
+            }
+        }
+
+        if let Some(soa) = signed.find_soa() {
+            let apex = soa.family_name().cloned();
+            for (rrset, class) in signed.classify_rrsets(&apex) {
+                if class != Classification::Authoritative {
+                    res.skipped.push(SkippedRrset {
+                        owner: rrset.owner().clone(),
+                        rtype: rrset.rtype(),
+                        reason: class,
+                    })
+                }
+            }
+        }
+        res
+    }
+
+    /// Counts a signature by the given key.
+    fn add_signature(&mut self, algorithm: SecAlg, key_tag: u16) {
+        match self
+            .keys
+            .iter_mut()
+            .find(|key| key.algorithm == algorithm && key.key_tag == key_tag)
+        {
+            Some(key) => key.rrsets += 1,
+            None => self.keys.push(KeyUsage {
+                algorithm,
+                key_tag,
+                rrsets: 1,
+            }),
+        }
+    }
+
+    /// Returns the number of RRsets signed by each key.
+    ///
+    /// The keys are listed in the order they first appear in the zone.
+    pub fn keys(&self) -> &[KeyUsage] {
+        &self.keys
+    }
+
+    /// Returns the number of RRsets signed with the given algorithm.
+    pub fn signed_with(&self, algorithm: SecAlg) -> usize {
+        self.keys
+            .iter()
+            .filter(|key| key.algorithm == algorithm)
+            .map(|key| key.rrsets)
+            .sum()
+    }
+
+    /// Returns the number of NSEC records in the zone.
+    pub fn nsecs(&self) -> usize {
+        self.nsecs
+    }
+
+    /// Returns the number of NSEC3 records in the zone.
+    pub fn nsec3s(&self) -> usize {
+        self.nsec3s
+    }
+
+    /// Returns the earliest expiration time of all signatures.
+    pub fn earliest_expiration(&self) -> Option<Time32> {
+        self.earliest_expiration
+    }
+
+    /// Returns the latest expiration time of all signatures.
+    pub fn latest_expiration(&self) -> Option<Time32> {
+        self.latest_expiration
+    }
+
+    /// Returns the number of records before signing.
+    pub fn records_before(&self) -> usize {
+        self.records.0
+    }
+
+    /// Returns the number of records after signing.
+    pub fn records_after(&self) -> usize {
+        self.records.1
+    }
+
+    /// Returns the size of the zone in octets before signing.
+    pub fn size_before(&self) -> usize {
+        self.size.0
+    }
+
+    /// Returns the size of the zone in octets after signing.
+    pub fn size_after(&self) -> usize {
+        self.size.1
+    }
+
+    /// Returns the change in size of the zone in octets.
+    pub fn size_delta(&self) -> i64 {
+        self.size.1 as i64 - self.size.0 as i64
+    }
+
+    /// Returns the RRsets that have been left unsigned.
+    pub fn skipped(&self) -> &[SkippedRrset<N>] {
+        &self.skipped
+    }
+
+    /// Returns the report as a JSON object.
+    ///
+    /// Expiration times are given as seconds since the Unix epoch or
+    /// `null` if the zone contains no signatures.
+    pub fn to_json(&self) -> String
+    where
+        N: fmt::Display,
+    {
+        fn opt(value: Option<Time32>) -> String {
+            match value {
+                Some(value) => value.into_int().to_string(),
+                None => String::from("null"),
+            }
+        }
+
+        let mut res = String::from("{\"keys\":[");
+        for (idx, key) in self.keys.iter().enumerate() {
+            if idx > 0 {
+                res.push(',');
+            }
+            res.push_str(&format!(
+                "{{\"algorithm\":{},\"key_tag\":{},\"rrsets\":{}}}",
+                key.algorithm.to_int(),
+                key.key_tag,
+                key.rrsets
+            ));
+        }
+        res.push_str(&format!(
+            "],\"nsec\":{},\"nsec3\":{},\"earliest_expiration\":{},\
+             \"latest_expiration\":{},\"records_before\":{},\
+             \"records_after\":{},\"size_before\":{},\"size_after\":{},\
+             \"size_delta\":{},\"skipped\":[",
+            self.nsecs,
+            self.nsec3s,
+            opt(self.earliest_expiration),
+            opt(self.latest_expiration),
+            self.records.0,
+            self.records.1,
+            self.size.0,
+            self.size.1,
+            self.size_delta(),
+        ));
+        for (idx, skipped) in self.skipped.iter().enumerate() {
+            if idx > 0 {
+                res.push(',');
+            }
+            res.push_str(&format!(
+                "{{\"owner\":\"{}\",\"rtype\":\"{}\",\"reason\":\"{}\"}}",
+                json_escape(&skipped.owner.to_string()),
+                skipped.rtype,
+                skipped.reason_str(),
+            ));
+        }
+        res.push_str("]}");
+        res
+    }
+}
+
+/// Returns the number of records and their canonical size.
+fn zone_size<N, Octets>(
+    records: &SortedRecords<N, MasterRecordData<Octets, N>>,
+    buf: &mut Vec<u8>,
+) -> (usize, usize)
+where
+    N: ToDname,
+    Octets: AsRef<[u8]>,
+{
+    let mut count = 0;
+    let mut size = 0;
+    for record in records.families().flat_map(|family| family.records()) {
+        buf.clear();
+        record.compose_canonical(buf).unwrap();
+        count += 1;
+        size += buf.len();
+    }
+    (count, size)
+}
+
+/// Escapes a string for use within a JSON string.
+fn json_escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            ch if (ch as u32) < 0x20 => {
+                res.push_str(&format!("\\u{:04x}", ch as u32))
+            }
+            ch => res.push(ch),
+        }
+    }
+    res
+}
+
+//------------ KeyUsage ------------------------------------------------------
+
+/// The number of RRsets signed by a key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeyUsage {
+    algorithm: SecAlg,
+    key_tag: u16,
+    rrsets: usize,
+}
+
+impl KeyUsage {
+    /// Returns the algorithm of the key.
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    /// Returns the key tag of the key.
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// Returns the number of RRsets signed by the key.
+    pub fn rrsets(&self) -> usize {
+        self.rrsets
+    }
+}
+
+//------------ SkippedRrset --------------------------------------------------
+
+/// An RRset of a signed zone that has not been signed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkippedRrset<N> {
+    owner: N,
+    rtype: Rtype,
+    reason: Classification,
+}
+
+impl<N> SkippedRrset<N> {
+    /// Returns the owner name of the RRset.
+    pub fn owner(&self) -> &N {
+        &self.owner
+    }
+
+    /// Returns the record type of the RRset.
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+
+    /// Returns why the RRset hasn’t been signed.
+    pub fn reason(&self) -> Classification {
+        self.reason
+    }
+
+    /// Returns the reason as a short string.
+    fn reason_str(&self) -> &'static str {
+        match self.reason {
+            Classification::Authoritative => "authoritative",
+            Classification::Delegation => "delegation",
+            Classification::Glue => "glue",
+            Classification::Occluded => "occluded",
+            Classification::OutOfZone => "out-of-zone",
+        }
+    }
+}
+
+//--- Display
+
+impl<N: fmt::Display> fmt::Display for SkippedRrset<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} not signed ({})",
+            self.owner,
+            self.rtype,
+            self.reason_str()
+        )
+    }
+}
+
+//------------ Signer --------------------------------------------------------
+
+/// Creates the signatures for RRsets.
+struct Signer<N> {
+    signer_name: N,
+    now: Time32,
+    inception: Time32,
+    expiration: Time32,
+    jitter: u32,
+    rrsig_ttl: RrsigTtl,
+    rng: StdRng,
+
+    /// A buffer for collecting the data to be signed.
+    buf: Vec<u8>,
+
+    /// A buffer for composing a single record of the data to be signed.
+    record_buf: Vec<u8>,
+}
+
+impl<N> Signer<N> {
+    fn new<Octets>(
+        policy: &SigningPolicy<Octets>,
+        signer_name: N,
+        now: Time32,
+    ) -> Self {
+        let secs = now.into_int();
+        Signer {
+            signer_name,
+            now,
+            inception: Time32::from_int(
+                secs.wrapping_sub(policy.inception_offset),
+            ),
+            expiration: Time32::from_int(secs.wrapping_add(policy.validity)),
+            jitter: policy.jitter,
+            rrsig_ttl: policy.rrsig_ttl,
+            rng: match policy.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            buf: Vec::new(),
+            record_buf: Vec::new(),
+        }
+    }
+
+    fn expiration(&mut self) -> Time32 {
+        if self.jitter == 0 {
+            return self.expiration;
+        }
+        let jitter = self.rng.gen_range(0, self.jitter.saturating_add(1));
+        Time32::from_int(self.expiration.into_int().wrapping_sub(jitter))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn sign<Octets, Key>(
+        &mut self,
+        rrset: &Rrset<N, MasterRecordData<Octets, N>>,
+        keys: &[&Key],
+        target: &mut Vec<Record<N, Rrsig<Octets, N>>>,
+    ) -> Result<(), SignError<Key::Error>>
+    where
+        N: ToDname + Clone,
+        Octets: AsRef<[u8]> + FromBuilder,
+        Octets::Builder: EmptyBuilder,
+        Key: SigningKey,
+    {
+        let expiration = self.expiration();
+        let ttl = self.rrsig_ttl.ttl(rrset.ttl(), self.now, expiration);
+        for key in keys {
+            let rrsig = ProtoRrsig::new(
+                rrset.rtype(),
+                key.algorithm().map_err(SignError::Key)?,
+                rrset.owner().rrsig_label_count(),
+                rrset.ttl(),
+                expiration,
+                self.inception,
+                key.key_tag().map_err(SignError::Key)?,
+                self.signer_name.clone(),
+            );
+            let record_buf = &mut self.record_buf;
+            let signature = key
+                .sign_incremental(&mut self.buf, |feed| {
+                    rrset.feed_signed_data(&rrsig, record_buf, feed).unwrap()
+                })
+                .map_err(SignError::Key)?;
+            target.push(Record::new(
+                rrset.owner().clone(),
+                rrset.class(),
+                ttl,
+                rrsig.into_rrsig(octets_from_slice(signature.as_ref())),
+            ));
+        }
+        Ok(())
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ SignError -----------------------------------------------------
+
+/// An error happened while signing a zone.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SignError<KeyError> {
+    /// The zone doesn’t contain a SOA record.
+    NoSoa,
+
+    /// No keys were given.
+    NoKeys,
+
+    /// The ZONEMD hash algorithm is not supported.
+    UnsupportedZonemd,
+
+    /// A key violates the key policy.
+    WeakKey(WeakKey),
+
+    /// The NSEC3 parameters violate the NSEC3 policy.
+    WeakNsec3(Nsec3Weakness),
+
+    /// Creating the NSEC3 records failed.
+    Nsec3(Nsec3Error),
+
+    /// Using a key failed.
+    Key(KeyError),
+}
+
+impl<KeyError> From<Nsec3Error> for SignError<KeyError> {
+    fn from(err: Nsec3Error) -> Self {
+        SignError::Nsec3(err)
+    }
+}
+
+//--- Display and Error
+
+impl<KeyError: fmt::Display> fmt::Display for SignError<KeyError> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignError::NoSoa => f.write_str("missing SOA record"),
+            SignError::NoKeys => f.write_str("no signing keys"),
+            SignError::UnsupportedZonemd => {
+                f.write_str("unsupported ZONEMD algorithm")
+            }
+            SignError::WeakKey(ref err) => err.fmt(f),
+            SignError::WeakNsec3(ref err) => err.fmt(f),
+            SignError::Nsec3(ref err) => err.fmt(f),
+            SignError::Key(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl<KeyError: fmt::Debug + fmt::Display> error::Error
+    for SignError<KeyError>
+{
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::clock::MockClock;
+    use crate::base::iana::Class;
+    use crate::sign::ring::Key;
+    use core::str::FromStr;
+    use ring::rand::SystemRandom;
+
+    /// Creates a new ECDSA P-256 key signing everything.
+    fn throwaway_key(rng: &SystemRandom) -> Key {
+        Key::throwaway_13(257, rng).unwrap()
+    }
+
+    #[test]
+    fn serial_policy() {
+        assert_eq!(SerialPolicy::Keep.next(Serial(10)), Serial(10));
+        assert_eq!(SerialPolicy::Increment.next(Serial(10)), Serial(11));
+        assert!(SerialPolicy::UnixTime.next(Serial(10)) > Serial(10));
+        let serial = SerialPolicy::DateCounter.next(Serial(10));
+        assert_eq!(serial.into_int() % 100, 0);
+        assert_eq!(SerialPolicy::DateCounter.next(serial), serial.add(1));
+
+        // 2020-09-13T12:26:40Z
+        let now = Time32::from_int(1_600_000_000);
+        assert_eq!(
+            SerialPolicy::UnixTime.next_at(Serial(10), now),
+            Serial(1_600_000_000)
+        );
+        assert_eq!(
+            SerialPolicy::DateCounter.next_at(Serial(10), now),
+            Serial(2020091300)
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn sign_zone() {
+        use crate::base::charstr::CharStr;
+        use crate::base::iana::Nsec3HashAlg;
+        use crate::sign::key::verify;
+        use crate::utils::testing::example_zone;
+        use bytes::Bytes;
+
+        let records: SortedRecords<_, _> = example_zone::<Bytes>().into();
+        let rng = SystemRandom::new();
+        let keys = [throwaway_key(&rng)];
+        let policy = SigningPolicy {
+            denial: Denial::Nsec3(Nsec3param::new(
+                Nsec3HashAlg::Sha1,
+                0,
+                1,
+                CharStr::from_octets(Bytes::from_static(b"\xab\xcd"))
+                    .unwrap(),
+            )),
+            serial: SerialPolicy::Increment,
+            zonemd: Some(ZonemdAlg::Sha384),
+            nsec3_policy: Nsec3Policy::relaxed(),
+            ..Default::default()
+        };
+        let signed = super::sign_zone(records, &keys, &policy).unwrap();
+
+        let count = |rtype| {
+            signed
+                .families()
+                .flat_map(|family| family.records())
+                .filter(|record| record.rtype() == rtype)
+                .count()
+        };
+        assert_eq!(count(Rtype::Dnskey), 1);
+        assert_eq!(count(Rtype::Nsec3), 2);
+        assert_eq!(count(Rtype::Nsec3param), 1);
+        assert_eq!(count(Rtype::Zonemd), 1);
+        assert_eq!(count(Rtype::Nsec), 0);
+        // SOA, A, DNSKEY, NSEC3PARAM, ZONEMD, and two NSEC3 RRsets.
+        assert_eq!(count(Rtype::Rrsig), 7);
+
+        let soa = signed.find_soa().unwrap();
+        match *soa.first().data() {
+            MasterRecordData::Soa(ref soa) => {
+                assert_eq!(soa.serial(), Serial(11))
+            }
+            _ => panic!("not a SOA record"),
+        }
+
+        // The signature of the A RRset verifies with the key.
+        let rrset = signed
+            .rrsets()
+            .find(|rrset| rrset.rtype() == Rtype::A)
+            .unwrap();
+        let rrsig = signed
+            .families()
+            .flat_map(|family| family.records())
+            .find_map(|record| match *record.data() {
+                MasterRecordData::Rrsig(ref rrsig)
+                    if rrsig.type_covered() == Rtype::A =>
+                {
+                    Some(rrsig.clone())
+                }
+                _ => None,
+            })
+            .unwrap();
+        let proto = ProtoRrsig::new(
+            rrsig.type_covered(),
+            rrsig.algorithm(),
+            rrsig.labels(),
+            rrsig.original_ttl(),
+            rrsig.expiration(),
+            rrsig.inception(),
+            rrsig.key_tag(),
+            rrsig.signer_name().clone(),
+        );
+        let mut data = Vec::new();
+        rrset.compose_signed_data(&proto, &mut data).unwrap();
+        let dnskey = keys[0].dnskey().unwrap();
+        verify(&dnskey, &data, rrsig.signature().as_ref()).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(verify(&dnskey, &data, rrsig.signature().as_ref()).is_err());
+
+        // The ZONEMD digest matches the zone as signed.
+        let apex = signed.find_soa().unwrap().family_name().cloned();
+        let digest = super::zonemd_digest(&signed, &apex, ZonemdAlg::Sha384);
+        let zonemd = signed
+            .families()
+            .flat_map(|family| family.records())
+            .find_map(|record| match *record.data() {
+                MasterRecordData::Zonemd(ref zonemd) => Some(zonemd.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(zonemd.digest().as_ref(), digest.as_ref());
+    }
+
+    #[test]
+    fn zonemd_digest() {
+        use crate::rdata::{Aaaa, Ns, A};
+        use crate::utils::testing::{name, record, ZoneData};
+
+        // The simple example zone from RFC 8976, appendix A.1.
+        let records: SortedRecords<_, ZoneData<Vec<u8>>> = vec![
+            record(
+                "example.",
+                86400,
+                Soa::new(
+                    name::<Vec<u8>>("ns1.example."),
+                    name::<Vec<u8>>("admin.example."),
+                    Serial(2018031900),
+                    1800,
+                    900,
+                    604800,
+                    86400,
+                )
+                .into(),
+            ),
+            record(
+                "example.",
+                86400,
+                Ns::new(name::<Vec<u8>>("ns1.example.")).into(),
+            ),
+            record(
+                "example.",
+                86400,
+                Ns::new(name::<Vec<u8>>("ns2.example.")).into(),
+            ),
+            record(
+                "example.",
+                86400,
+                Zonemd::new(
+                    Serial(2018031900),
+                    ZonemdScheme::Simple,
+                    ZonemdAlg::Sha384,
+                    vec![0; 48],
+                )
+                .into(),
+            ),
+            record(
+                "ns1.example.",
+                3600,
+                A::from_str("203.0.113.63").unwrap().into(),
+            ),
+            record(
+                "ns2.example.",
+                3600,
+                Aaaa::from_str("2001:db8::63").unwrap().into(),
+            ),
+        ]
+        .into();
+        let apex = FamilyName::new(name::<Vec<u8>>("example."), Class::In);
+        let digest = super::zonemd_digest(&records, &apex, ZonemdAlg::Sha384);
+        assert_eq!(
+            digest.as_ref(),
+            &b"\xc6\x80\x90\xd9\x0a\x7a\xed\x71\
+               \x6b\xc4\x59\xf9\x34\x0e\x3d\x7c\
+               \x13\x70\xd4\xd2\x4b\x7e\x2f\xc3\
+               \xa1\xdd\xc0\xb9\xa8\x71\x53\xb9\
+               \xa9\x71\x3b\x3c\x9a\xe5\xcc\x27\
+               \x77\x7f\x98\xb8\xe7\x30\x04\x4c"[..]
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn nsec3_policy() {
+        use crate::base::charstr::CharStr;
+        use crate::base::iana::Nsec3HashAlg;
+        use crate::utils::testing::{record, soa, ZoneData};
+        use bytes::Bytes;
+
+        let params = |iterations, salt: &'static [u8]| {
+            Nsec3param::new(
+                Nsec3HashAlg::Sha1,
+                0,
+                iterations,
+                CharStr::from_octets(Bytes::from_static(salt)).unwrap(),
+            )
+        };
+
+        let strict = Nsec3Policy::strict();
+        let relaxed = Nsec3Policy::relaxed();
+        assert_eq!(strict.check_params(&params(0, b"")), []);
+        assert_eq!(
+            strict.check_params(&params(10, b"\xab\xcd")),
+            [Nsec3Weakness::Iterations(10), Nsec3Weakness::LongSalt(2)]
+        );
+        assert_eq!(relaxed.check_params(&params(10, b"\xab\xcd")), []);
+        assert_eq!(
+            relaxed.check_params(&params(150, b"012345678")),
+            [Nsec3Weakness::Iterations(150), Nsec3Weakness::LongSalt(9)]
+        );
+        assert_eq!(
+            Nsec3Policy::permissive().check_params(&params(2500, b"0123")),
+            []
+        );
+
+        let records: SortedRecords<_, ZoneData<Bytes>> =
+            vec![record("example.", 3600, soa(10).into())].into();
+        let rng = SystemRandom::new();
+        let keys = [throwaway_key(&rng)];
+
+        // Signing rejects weak parameters by default.
+        let policy = SigningPolicy {
+            denial: Denial::Nsec3(params(5, b"")),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::sign_zone(records.clone(), &keys, &policy).err(),
+            Some(SignError::WeakNsec3(Nsec3Weakness::Iterations(5)))
+        );
+
+        // Existing zones are checked for the parameters in use.
+        let policy = SigningPolicy {
+            nsec3_policy: Nsec3Policy::permissive(),
+            ..policy
+        };
+        let signed = super::sign_zone(records, &keys, &policy).unwrap();
+        assert_eq!(
+            strict.check_zone(&signed),
+            [Nsec3Weakness::Iterations(5)]
+        );
+        assert_eq!(relaxed.check_zone(&signed), []);
+    }
+
+    #[test]
+    fn vec_octets() {
+        use crate::utils::testing::example_zone;
+
+        // Signing works with octets types other than the key’s.
+        let records: SortedRecords<_, _> = example_zone::<Vec<u8>>().into();
+
+        let rng = SystemRandom::new();
+        let keys = [throwaway_key(&rng)];
+        let rrsigs = records
+            .sign_from_soa::<Vec<u8>, _>(
+                Time32::from_int(1_600_001_000),
+                Time32::from_int(1_600_000_000),
+                &keys[0],
+            )
+            .unwrap();
+        assert_eq!(rrsigs.len(), 2);
+        assert!(rrsigs
+            .iter()
+            .all(|rrsig| rrsig.data().signature().len() == 64));
+
+        let signed =
+            super::sign_zone(records, &keys, &Default::default()).unwrap();
+        assert!(missing_signatures(&signed).is_empty());
+    }
+
+    #[test]
+    fn rrsig_ttl() {
+        use crate::rdata::A;
+        use crate::utils::testing::{example_zone, record};
+
+        let now = Time32::from_int(1_600_000_000);
+        assert_eq!(RrsigTtl::Rrset.ttl(3600, now, now.add(600)), 3600);
+        assert_eq!(
+            RrsigTtl::ClampToValidity.ttl(3600, now, now.add(600)),
+            600
+        );
+        assert_eq!(
+            RrsigTtl::ClampToValidity.ttl(300, now, now.add(600)),
+            300
+        );
+        assert_eq!(RrsigTtl::ClampToValidity.ttl(300, now, now), 0);
+
+        let mut records = example_zone::<Vec<u8>>();
+        records.push(record(
+            "www.example.",
+            7200,
+            A::from_str("192.0.2.2").unwrap().into(),
+        ));
+        let records: SortedRecords<_, _> = records.into();
+
+        let rng = SystemRandom::new();
+        let keys = [throwaway_key(&rng)];
+        let policy = SigningPolicy {
+            validity: 1000,
+            rrsig_ttl: RrsigTtl::ClampToValidity,
+            clock: Arc::new(MockClock::from_unix_secs(now.into_int().into())),
+            ..Default::default()
+        };
+        let signed = super::sign_zone(records, &keys, &policy).unwrap();
+        let rrsig = signed
+            .families()
+            .flat_map(|family| family.records())
+            .find_map(|record| match *record.data() {
+                MasterRecordData::Rrsig(ref rrsig)
+                    if rrsig.type_covered() == Rtype::A =>
+                {
+                    Some((record.ttl(), rrsig.clone()))
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(rrsig.0, 1000);
+        assert_eq!(rrsig.1.original_ttl(), 3600);
+
+        // The signature verifies with the original TTL even though the
+        // TTLs of the records differ.
+        #[cfg(feature = "validate")]
+        {
+            use crate::validate::RrsigExt;
+
+            let mut rrset = signed
+                .families()
+                .flat_map(|family| family.records())
+                .filter(|record| record.rtype() == Rtype::A)
+                .cloned()
+                .collect::<Vec<_>>();
+            assert_eq!(rrset.len(), 2);
+            let mut data = Vec::new();
+            rrsig.1.signed_data(&mut data, &mut rrset).unwrap();
+            let dnskey = keys[0].dnskey().unwrap();
+            rrsig.1.verify_signed_data(&dnskey, &data).unwrap();
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn algorithm_rollover() {
+        use crate::base::name::Dname;
+        use crate::utils::testing::{example_zone, name, ZoneData};
+        use bytes::Bytes;
+
+        type Name = Dname<Bytes>;
+        type Data = ZoneData<Bytes>;
+
+        let records: SortedRecords<Name, Data> = example_zone().into();
+
+        // The old algorithm has a KSK and a ZSK, the new one a single
+        // combined key which needs to sign everything.
+        let rng = SystemRandom::new();
+        let keys = [
+            Key::throwaway_13(257, &rng).unwrap(),
+            Key::throwaway_13(256, &rng).unwrap(),
+            Key::throwaway_15(257, &rng).unwrap(),
+        ];
+        let policy = SigningPolicy::default();
+        let signed =
+            super::sign_zone(records.clone(), &keys, &policy).unwrap();
+        let count = |records: &SortedRecords<Name, Data>, rtype| {
+            records
+                .families()
+                .flat_map(|family| family.records())
+                .filter(|record| record.rtype() == rtype)
+                .count()
+        };
+        assert_eq!(count(&signed, Rtype::Dnskey), 3);
+        assert_eq!(super::missing_signatures(&signed), []);
+        // SOA, DNSKEY, and NSEC at the apex as well as A and NSEC at www
+        // are signed with both algorithms.
+        assert_eq!(count(&signed, Rtype::Rrsig), 10);
+
+        // Pre-publish the signatures of the new algorithm.
+        let policy = SigningPolicy {
+            unpublished: vec![SecAlg::Ed25519],
+            ..Default::default()
+        };
+        let signed = super::sign_zone(records, &keys, &policy).unwrap();
+        assert_eq!(count(&signed, Rtype::Dnskey), 2);
+        assert_eq!(count(&signed, Rtype::Rrsig), 10);
+        assert_eq!(super::missing_signatures(&signed), []);
+
+        // Dropping the signatures of the old algorithm is detected.
+        let mut signed = signed;
+        signed.retain(|record| match *record.data() {
+            MasterRecordData::Rrsig(ref rrsig) => {
+                rrsig.algorithm() != SecAlg::EcdsaP256Sha256
+                    || rrsig.type_covered() != Rtype::A
+            }
+            _ => true,
+        });
+        let missing = super::missing_signatures(&signed);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].owner(), &name::<Bytes>("www.example."));
+        assert_eq!(missing[0].rtype(), Rtype::A);
+        assert_eq!(missing[0].algorithm(), SecAlg::EcdsaP256Sha256);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn deterministic() {
+        use crate::utils::testing::example_zone;
+        use bytes::Bytes;
+
+        let records: SortedRecords<_, _> = example_zone::<Bytes>().into();
+
+        let rng = SystemRandom::new();
+        let keys = [Key::ed25519_from_seed(257, &[7; 32], &rng).unwrap()];
+        let policy = SigningPolicy {
+            jitter: 3600,
+            serial: SerialPolicy::UnixTime,
+            clock: Arc::new(MockClock::from_unix_secs(1_600_000_000)),
+            seed: Some(42),
+            ..Default::default()
+        };
+        let sign = || {
+            let signed =
+                super::sign_zone(records.clone(), &keys, &policy).unwrap();
+            let mut buf = Vec::new();
+            signed.write(&mut buf).unwrap();
+            buf
+        };
+        let first = sign();
+        assert_eq!(first, sign());
+
+        let signed =
+            super::sign_zone(records.clone(), &keys, &policy).unwrap();
+        for record in signed.families().flat_map(|family| family.records()) {
+            match *record.data() {
+                MasterRecordData::Rrsig(ref rrsig) => {
+                    assert_eq!(
+                        rrsig.inception(),
+                        Time32::from_int(1_599_996_400)
+                    );
+                    assert!(
+                        rrsig.expiration() <= Time32::from_int(1_602_592_000)
+                    );
+                    assert!(
+                        rrsig.expiration() >= Time32::from_int(1_602_588_400)
+                    );
+                }
+                MasterRecordData::Soa(ref soa) => {
+                    assert_eq!(soa.serial(), Serial(1_600_000_000))
+                }
+                _ => {}
+            }
+        }
+
+        // A different seed changes the jitter.
+        let policy = SigningPolicy {
+            seed: Some(43),
+            ..policy
+        };
+        let signed = super::sign_zone(records, &keys, &policy).unwrap();
+        let mut buf = Vec::new();
+        signed.write(&mut buf).unwrap();
+        assert_ne!(first, buf);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn signing_report() {
+        use crate::rdata::{Ns, A};
+        use crate::utils::testing::{example_zone, name, record};
+        use bytes::Bytes;
+
+        let mut records = example_zone::<Bytes>();
+        records.push(record(
+            "sub.example.",
+            3600,
+            Ns::new(name::<Bytes>("ns.sub.example.")).into(),
+        ));
+        records.push(record(
+            "ns.sub.example.",
+            3600,
+            A::from_str("192.0.2.2").unwrap().into(),
+        ));
+        let records: SortedRecords<_, _> = records.into();
+
+        let rng = SystemRandom::new();
+        let keys = [Key::throwaway_13(257, &rng).unwrap()];
+        let key_tag = keys[0].key_tag().unwrap();
+        let policy = SigningPolicy {
+            clock: Arc::new(MockClock::from_unix_secs(1_600_000_000)),
+            validity: 1000,
+            jitter: 0,
+            ..Default::default()
+        };
+        let signed =
+            super::sign_zone(records.clone(), &keys, &policy).unwrap();
+        let report = SigningReport::new(&records, &signed);
+
+        // SOA, A, DNSKEY, and three NSEC RRsets.
+        assert_eq!(
+            report.keys(),
+            &[KeyUsage {
+                algorithm: SecAlg::EcdsaP256Sha256,
+                key_tag,
+                rrsets: 6
+            }]
+        );
+        assert_eq!(report.signed_with(SecAlg::EcdsaP256Sha256), 6);
+        assert_eq!(report.nsecs(), 3);
+        assert_eq!(report.nsec3s(), 0);
+        assert_eq!(
+            report.earliest_expiration(),
+            Some(Time32::from_int(1_600_001_000))
+        );
+        assert_eq!(
+            report.latest_expiration(),
+            Some(Time32::from_int(1_600_001_000))
+        );
+        assert_eq!(report.records_before(), 4);
+        assert_eq!(report.records_after(), 14);
+        assert!(report.size_delta() > 0);
+        assert_eq!(
+            report.size_delta(),
+            report.size_after() as i64 - report.size_before() as i64
+        );
+        let skipped = report
+            .skipped()
+            .iter()
+            .map(|skipped| skipped.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            skipped,
+            [
+                "sub.example NS not signed (delegation)",
+                "ns.sub.example A not signed (glue)",
+            ]
+        );
+
+        let json = report.to_json();
+        assert!(json.starts_with(&format!(
+            "{{\"keys\":[{{\"algorithm\":13,\"key_tag\":{},\"rrsets\":6}}],\
+             \"nsec\":3,\"nsec3\":0,\"earliest_expiration\":1600001000,",
+            key_tag
+        )));
+        assert!(json.ends_with(
+            "\"skipped\":[\
+             {\"owner\":\"sub.example\",\"rtype\":\"NS\",\
+             \"reason\":\"delegation\"},\
+             {\"owner\":\"ns.sub.example\",\"rtype\":\"A\",\
+             \"reason\":\"glue\"}]}"
+        ));
+    }
+}
@@ -4,3 +4,6 @@
 
 pub mod base32;
 pub mod base64;
+pub mod replay;
+#[cfg(test)]
+pub(crate) mod testing;
@@ -0,0 +1,575 @@
+//! Recording and replaying DNS exchanges.
+//!
+//! This module provides a simple file format for storing pairs of DNS
+//! queries and their answers. It allows capturing real traffic once and
+//! replaying it in tests against code built on top of this crate without
+//! requiring network access.
+//!
+//! # File Format
+//!
+//! A replay file starts with the ASCII characters `DNSRPLY` and a zero
+//! octet followed by a single octet with the format version which
+//! currently is always 1. The remainder of the file is a sequence of
+//! exchanges. Each exchange consists of the query message followed by the
+//! answer message. Both are preceded by their length as a 16 bit unsigned
+//! integer in network byte order, the same framing as used for DNS over
+//! TCP. An answer length of zero means that no answer was received for the
+//! query.
+//!
+//! # Usage
+//!
+//! Exchanges are written via a [`Writer`] and read via a [`Reader`], which
+//! is an iterator over the exchanges of a file. For answering queries, all
+//! exchanges can be loaded into a [`Corpus`]. Its [`answer`] method looks
+//! up the answer for a query by the query’s question. If the `resolv`
+//! feature is enabled, a corpus also acts as a [`Resolver`].
+//!
+//! [`Writer`]: struct.Writer.html
+//! [`Reader`]: struct.Reader.html
+//! [`Corpus`]: struct.Corpus.html
+//! [`answer`]: struct.Corpus.html#method.answer
+//! [`Resolver`]: ../../resolv/resolver/trait.Resolver.html
+#![cfg(feature = "std")]
+
+use crate::base::message::Message;
+use crate::base::octets::ShortBuf;
+use std::io::{self, Read, Write};
+use std::iter::FromIterator;
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Constants -----------------------------------------------------
+
+/// The octets a replay file starts with.
+const MAGIC: &[u8; 8] = b"DNSRPLY\0";
+
+/// The version of the file format.
+const VERSION: u8 = 1;
+
+//------------ Exchange ------------------------------------------------------
+
+/// A query and its answer.
+#[derive(Clone)]
+pub struct Exchange {
+    /// The query message.
+    query: Message<Vec<u8>>,
+
+    /// The answer message if one was received.
+    answer: Option<Message<Vec<u8>>>,
+}
+
+impl Exchange {
+    /// Creates a new exchange from a query and an optional answer.
+    pub fn new(
+        query: Message<Vec<u8>>,
+        answer: Option<Message<Vec<u8>>>,
+    ) -> Self {
+        Exchange { query, answer }
+    }
+
+    /// Returns a reference to the query.
+    pub fn query(&self) -> &Message<Vec<u8>> {
+        &self.query
+    }
+
+    /// Returns a reference to the answer if there was one.
+    pub fn answer(&self) -> Option<&Message<Vec<u8>>> {
+        self.answer.as_ref()
+    }
+
+    /// Converts the exchange into the query and answer.
+    pub fn into_parts(self) -> (Message<Vec<u8>>, Option<Message<Vec<u8>>>) {
+        (self.query, self.answer)
+    }
+
+    /// Returns whether the exchange’s query asks the same as `query`.
+    ///
+    /// Two queries are considered to ask the same if their question
+    /// sections are equal. Domain names are compared case-insensitively.
+    pub fn matches<Octets: AsRef<[u8]>>(
+        &self,
+        query: &Message<Octets>,
+    ) -> bool {
+        let mut left = self.query.question();
+        let query = query.for_slice();
+        let mut right = query.question();
+        loop {
+            match (left.next(), right.next()) {
+                (None, None) => return true,
+                (Some(Ok(left)), Some(Ok(right))) => {
+                    if left != right {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+//------------ Writer --------------------------------------------------------
+
+/// Writes exchanges to a replay file.
+#[derive(Debug)]
+pub struct Writer<W> {
+    /// The target to write to.
+    target: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a new writer, writing the file header to `target`.
+    pub fn new(mut target: W) -> Result<Self, io::Error> {
+        target.write_all(MAGIC)?;
+        target.write_all(&[VERSION])?;
+        Ok(Writer { target })
+    }
+
+    /// Writes a query and its answer.
+    ///
+    /// If no answer was received for the query, `answer` should be `None`.
+    pub fn write<Q, A>(
+        &mut self,
+        query: &Message<Q>,
+        answer: Option<&Message<A>>,
+    ) -> Result<(), io::Error>
+    where
+        Q: AsRef<[u8]>,
+        A: AsRef<[u8]>,
+    {
+        write_framed(&mut self.target, query.as_slice())?;
+        write_framed(
+            &mut self.target,
+            answer.map(Message::as_slice).unwrap_or(b""),
+        )
+    }
+
+    /// Writes an exchange.
+    pub fn write_exchange(
+        &mut self,
+        exchange: &Exchange,
+    ) -> Result<(), io::Error> {
+        self.write(exchange.query(), exchange.answer())
+    }
+
+    /// Flushes the underlying target.
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.target.flush()
+    }
+
+    /// Converts the writer into the underlying target.
+    pub fn into_inner(self) -> W {
+        self.target
+    }
+}
+
+//------------ Reader --------------------------------------------------------
+
+/// Reads exchanges from a replay file.
+///
+/// The reader is an iterator over the exchanges in the file. Once an error
+/// has been encountered, the iterator will not return any further items.
+#[derive(Debug)]
+pub struct Reader<R> {
+    /// The source to read from or `None` if we are done.
+    source: Option<R>,
+}
+
+impl<R: Read> Reader<R> {
+    /// Creates a new reader, checking the file header from `source`.
+    pub fn new(mut source: R) -> Result<Self, ReplayError> {
+        let mut header = [0u8; 9];
+        source.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        if header[8] != VERSION {
+            return Err(ReplayError::UnknownVersion(header[8]));
+        }
+        Ok(Reader {
+            source: Some(source),
+        })
+    }
+
+    /// Reads the next exchange.
+    ///
+    /// Returns `Ok(None)` if the end of the file has been reached.
+    fn read_exchange(
+        source: &mut R,
+    ) -> Result<Option<Exchange>, ReplayError> {
+        let query = match read_framed(source, true)? {
+            Some(query) => query,
+            None => return Ok(None),
+        };
+        let query = Message::from_octets(query)?;
+        let answer = match read_framed(source, false)? {
+            Some(answer) if !answer.is_empty() => {
+                Some(Message::from_octets(answer)?)
+            }
+            _ => None,
+        };
+        Ok(Some(Exchange::new(query, answer)))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Exchange, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let res = Self::read_exchange(self.source.as_mut()?);
+        match res {
+            Ok(Some(exchange)) => Some(Ok(exchange)),
+            Ok(None) => {
+                self.source = None;
+                None
+            }
+            Err(err) => {
+                self.source = None;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+//------------ Corpus --------------------------------------------------------
+
+/// A collection of exchanges used to answer queries.
+#[derive(Clone, Default)]
+pub struct Corpus {
+    /// The exchanges in the order they were added.
+    exchanges: Vec<Exchange>,
+}
+
+impl Corpus {
+    /// Creates a new, empty corpus.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a corpus from a replay file.
+    pub fn read<R: Read>(source: R) -> Result<Self, ReplayError> {
+        Reader::new(source)?.collect()
+    }
+
+    /// Writes the corpus as a replay file.
+    pub fn write<W: Write>(&self, target: W) -> Result<(), io::Error> {
+        let mut writer = Writer::new(target)?;
+        for exchange in &self.exchanges {
+            writer.write_exchange(exchange)?;
+        }
+        writer.flush()
+    }
+
+    /// Adds an exchange to the corpus.
+    pub fn push(&mut self, exchange: Exchange) {
+        self.exchanges.push(exchange)
+    }
+
+    /// Returns the number of exchanges in the corpus.
+    pub fn len(&self) -> usize {
+        self.exchanges.len()
+    }
+
+    /// Returns whether the corpus is empty.
+    pub fn is_empty(&self) -> bool {
+        self.exchanges.is_empty()
+    }
+
+    /// Returns an iterator over the exchanges of the corpus.
+    pub fn iter(&self) -> std::slice::Iter<'_, Exchange> {
+        self.exchanges.iter()
+    }
+
+    /// Returns the first exchange whose query matches `query`.
+    ///
+    /// See [`Exchange::matches`] for how queries are compared.
+    ///
+    /// [`Exchange::matches`]: struct.Exchange.html#method.matches
+    pub fn find<Octets: AsRef<[u8]>>(
+        &self,
+        query: &Message<Octets>,
+    ) -> Option<&Exchange> {
+        self.exchanges.iter().find(|item| item.matches(query))
+    }
+
+    /// Returns the recorded answer for `query`.
+    ///
+    /// The answer is taken from the first exchange with a matching query
+    /// that has an answer. The message ID of the returned answer is set to
+    /// that of `query`.
+    pub fn answer<Octets: AsRef<[u8]>>(
+        &self,
+        query: &Message<Octets>,
+    ) -> Option<Message<Vec<u8>>> {
+        let mut answer = self
+            .exchanges
+            .iter()
+            .filter(|item| item.matches(query))
+            .find_map(|item| item.answer())?
+            .clone();
+        answer.header_mut().set_id(query.header().id());
+        Some(answer)
+    }
+}
+
+impl FromIterator<Exchange> for Corpus {
+    fn from_iter<I: IntoIterator<Item = Exchange>>(iter: I) -> Self {
+        Corpus {
+            exchanges: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Exchange> for Corpus {
+    fn extend<I: IntoIterator<Item = Exchange>>(&mut self, iter: I) {
+        self.exchanges.extend(iter)
+    }
+}
+
+impl<'a> IntoIterator for &'a Corpus {
+    type Item = &'a Exchange;
+    type IntoIter = std::slice::Iter<'a, Exchange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.exchanges.iter()
+    }
+}
+
+//--- Resolver
+
+#[cfg(feature = "resolv")]
+impl crate::resolv::resolver::Resolver for Corpus {
+    type Octets = Vec<u8>;
+    type Answer = Answer;
+    type Query = futures::future::Ready<Result<Self::Answer, io::Error>>;
+
+    /// Answers a question from the corpus.
+    ///
+    /// If there is no recorded answer for the question, the query fails
+    /// with an IO error of kind `NotFound`.
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: crate::base::name::ToDname,
+        Q: Into<crate::base::question::Question<N>>,
+    {
+        let mut builder =
+            crate::base::message_builder::MessageBuilder::new_vec();
+        builder.header_mut().set_rd(true);
+        let mut builder = builder.question();
+        let res = match builder.push(question.into()) {
+            Ok(()) => self
+                .answer(&builder.into_message())
+                .map(|message| Answer { message })
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "no recorded answer",
+                    )
+                }),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "question too long",
+            )),
+        };
+        futures::future::ready(res)
+    }
+}
+
+//------------ Answer --------------------------------------------------------
+
+/// An answer replayed from a corpus.
+///
+/// This type is returned by the [`Resolver`] implementation of [`Corpus`].
+///
+/// [`Resolver`]: ../../resolv/resolver/trait.Resolver.html
+/// [`Corpus`]: struct.Corpus.html
+#[derive(Clone)]
+pub struct Answer {
+    /// The answer message.
+    message: Message<Vec<u8>>,
+}
+
+impl Answer {
+    /// Converts the answer into the underlying message.
+    pub fn into_message(self) -> Message<Vec<u8>> {
+        self.message
+    }
+}
+
+impl AsRef<Message<Vec<u8>>> for Answer {
+    fn as_ref(&self) -> &Message<Vec<u8>> {
+        &self.message
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Writes `data` preceded by its length.
+fn write_framed<W: Write>(
+    target: &mut W,
+    data: &[u8],
+) -> Result<(), io::Error> {
+    if data.len() > usize::from(u16::MAX) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "message too long",
+        ));
+    }
+    target.write_all(&(data.len() as u16).to_be_bytes())?;
+    target.write_all(data)
+}
+
+/// Reads data preceded by its length.
+///
+/// If `eof_ok` is true, returns `Ok(None)` if the source ends right away.
+fn read_framed<R: Read>(
+    source: &mut R,
+    eof_ok: bool,
+) -> Result<Option<Vec<u8>>, ReplayError> {
+    let mut len = [0u8; 2];
+    let mut read = 0;
+    while read < 2 {
+        match source.read(&mut len[read..]) {
+            Ok(0) if read == 0 && eof_ok => return Ok(None),
+            Ok(0) => return Err(ReplayError::UnexpectedEof),
+            Ok(n) => read += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let mut data = vec![0u8; usize::from(u16::from_be_bytes(len))];
+    source.read_exact(&mut data).map_err(|err| {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            ReplayError::UnexpectedEof
+        } else {
+            err.into()
+        }
+    })?;
+    Ok(Some(data))
+}
+
+//------------ ReplayError ---------------------------------------------------
+
+/// An error happened while reading a replay file.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The file did not start with the expected magic octets.
+    BadMagic,
+
+    /// The file uses a format version that is not supported.
+    UnknownVersion(u8),
+
+    /// The file ended in the middle of an exchange.
+    UnexpectedEof,
+
+    /// A message was too short to be a DNS message.
+    ShortMessage,
+
+    /// Reading from the source failed.
+    Io(io::Error),
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<ShortBuf> for ReplayError {
+    fn from(_: ShortBuf) -> Self {
+        ReplayError::ShortMessage
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::BadMagic => f.write_str("not a replay file"),
+            ReplayError::UnknownVersion(version) => {
+                write!(f, "unknown replay file version {}", version)
+            }
+            ReplayError::UnexpectedEof => {
+                f.write_str("unexpected end of replay file")
+            }
+            ReplayError::ShortMessage => f.write_str("short message"),
+            ReplayError::Io(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ReplayError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::Dname;
+    use crate::rdata::A;
+    use core::str::FromStr;
+
+    fn query(qname: &str, qtype: Rtype, id: u16) -> Message<Vec<u8>> {
+        let mut builder = MessageBuilder::new_vec();
+        builder.header_mut().set_id(id);
+        let mut builder = builder.question();
+        builder
+            .push((Dname::<Vec<u8>>::from_str(qname).unwrap(), qtype))
+            .unwrap();
+        builder.into_message()
+    }
+
+    fn answer(query: &Message<Vec<u8>>, addr: &str) -> Message<Vec<u8>> {
+        let mut builder = MessageBuilder::new_vec()
+            .start_answer(query, crate::base::iana::Rcode::NoError)
+            .unwrap();
+        let question = query.first_question().unwrap();
+        builder
+            .push((*question.qname(), 3600, A::from_str(addr).unwrap()))
+            .unwrap();
+        builder.into_message()
+    }
+
+    #[test]
+    fn write_read_answer() {
+        let q1 = query("www.example.com", Rtype::A, 1);
+        let a1 = answer(&q1, "192.0.2.1");
+        let q2 = query("example.com", Rtype::Aaaa, 2);
+
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        writer.write(&q1, Some(&a1)).unwrap();
+        writer.write::<_, Vec<u8>>(&q2, None).unwrap();
+        let data = writer.into_inner();
+
+        let corpus = Corpus::read(data.as_slice()).unwrap();
+        assert_eq!(corpus.len(), 2);
+        assert_eq!(
+            corpus.iter().next().unwrap().query().as_slice(),
+            q1.as_slice()
+        );
+        assert!(corpus.iter().nth(1).unwrap().answer().is_none());
+
+        let mut out = Vec::new();
+        corpus.write(&mut out).unwrap();
+        assert_eq!(out, data);
+
+        let replayed = corpus
+            .answer(&query("WWW.Example.COM", Rtype::A, 17))
+            .unwrap();
+        assert_eq!(replayed.header().id(), 17);
+        assert_eq!(replayed.header_counts().ancount(), 1);
+        assert!(corpus.answer(&q2).is_none());
+        assert!(corpus
+            .answer(&query("www.example.com", Rtype::Mx, 3))
+            .is_none());
+
+        assert!(matches!(
+            Corpus::read(&data[..data.len() - 1]),
+            Err(ReplayError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            Corpus::read(&b"DNSREPLY\x01"[..]),
+            Err(ReplayError::BadMagic)
+        ));
+    }
+}
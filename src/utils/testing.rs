@@ -0,0 +1,97 @@
+//! Helpers shared by the tests of several modules.
+//!
+//! Most tests need a few records or a small zone to work with. This module
+//! provides the ones that are used all over the place so they don’t have
+//! to be defined again in every test module.
+#![cfg(all(test, feature = "std"))]
+// Not every helper is used with every set of features.
+#![allow(dead_code)]
+
+use crate::base::iana::Class;
+use crate::base::name::Dname;
+use crate::base::octets::{EmptyBuilder, FromBuilder};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::{MasterRecordData, Soa, A};
+use core::future::Future;
+use core::str::FromStr;
+use std::vec::Vec;
+
+/// The record data used for zones.
+pub type ZoneData<Octets> = MasterRecordData<Octets, Dname<Octets>>;
+
+/// Creates an absolute domain name from its presentation format.
+pub fn name<Octets>(s: &str) -> Dname<Octets>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    Dname::from_str(s).unwrap()
+}
+
+/// Creates a record of class IN.
+pub fn record<Octets, D>(
+    owner: &str,
+    ttl: u32,
+    data: D,
+) -> Record<Dname<Octets>, D>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    Record::new(name(owner), Class::In, ttl, data)
+}
+
+/// Creates an A record with an owner name atop a vec.
+pub fn a_record(
+    owner: &str,
+    ttl: u32,
+    addr: &str,
+) -> Record<Dname<Vec<u8>>, A> {
+    record(owner, ttl, A::from_str(addr).unwrap())
+}
+
+/// Creates the SOA record data of the example zone.
+pub fn soa<Octets>(serial: u32) -> Soa<Dname<Octets>>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    Soa::new(
+        name("ns.example."),
+        name("hostmaster.example."),
+        Serial(serial),
+        3600,
+        600,
+        86400,
+        300,
+    )
+}
+
+/// Returns the records of a minimal example zone.
+///
+/// The zone `example.` contains its SOA record with serial 10 and an A
+/// record for `www.example.`. All records have a TTL of 3600 seconds.
+pub fn example_zone<Octets>() -> Vec<Record<Dname<Octets>, ZoneData<Octets>>>
+where
+    Octets: FromBuilder,
+    <Octets as FromBuilder>::Builder: EmptyBuilder,
+{
+    vec![
+        record("example.", 3600, soa(10).into()),
+        record(
+            "www.example.",
+            3600,
+            A::from_str("192.0.2.1").unwrap().into(),
+        ),
+    ]
+}
+
+/// Runs a future to completion on a single-threaded Tokio runtime.
+pub fn run<F: Future>(fut: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(fut)
+}
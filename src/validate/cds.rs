@@ -0,0 +1,716 @@
+//! Parent-side processing of CDS and CDNSKEY records.
+//!
+//! With [RFC 7344] and [RFC 8078], a child zone can signal the DS RRset it
+//! would like its parent to publish by placing CDS and CDNSKEY records at
+//! its apex. This module implements the parent side of this mechanism: the
+//! records found at the child are collected in [`ChildKeys`] and then
+//! checked against the DS RRset currently published by the parent via
+//! [`ChildKeys::process`]. The result is a [`DsUpdate`] describing what
+//! the parent should do.
+//!
+//! The checks follow the acceptance rules of the two RFCs. The CDS and
+//! CDNSKEY RRsets have to be signed by a key that is referred to by the
+//! current DS RRset, both RRsets have to be consistent with each other if
+//! present, and the new DS RRset must not break the chain of trust. A
+//! request to delete the DS RRset is only accepted if it is the only
+//! record in its RRsets.
+//!
+//! Bootstrapping the DS RRset for a zone that isn’t yet secure needs some
+//! other way of establishing trust and is not covered by this module.
+//!
+//! [RFC 7344]: https://tools.ietf.org/html/rfc7344
+//! [RFC 8078]: https://tools.ietf.org/html/rfc8078
+//! [`ChildKeys`]: struct.ChildKeys.html
+//! [`ChildKeys::process`]: struct.ChildKeys.html#method.process
+//! [`DsUpdate`]: enum.DsUpdate.html
+
+use super::{DnskeyExt, RrsigExt};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Class, DigestAlg, Rtype, SecAlg};
+use crate::base::name::ToDname;
+use crate::base::octets::Compose;
+use crate::base::rdata::RecordData;
+use crate::base::record::Record;
+use crate::base::time32::Time32;
+use crate::rdata::{Cdnskey, Cds, Dnskey, Ds, Rrsig};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ ChildKeys -----------------------------------------------------
+
+/// The key related records found at the apex of a child zone.
+#[derive(Clone)]
+pub struct ChildKeys<N, Octets, SignerName> {
+    /// The apex of the child zone.
+    apex: N,
+
+    /// The DNSKEY RRset.
+    dnskeys: Vec<Dnskey<Octets>>,
+
+    /// The CDS RRset.
+    cds: Vec<Cds<Octets>>,
+
+    /// The CDNSKEY RRset.
+    cdnskeys: Vec<Cdnskey<Octets>>,
+
+    /// The RRSIG records covering any of the above.
+    rrsigs: Vec<Rrsig<Octets, SignerName>>,
+
+    /// The digest type used for DS records created from CDNSKEY records.
+    digest_type: DigestAlg,
+}
+
+impl<N, Octets, SignerName> ChildKeys<N, Octets, SignerName> {
+    /// Creates a new, empty value for the child zone with the given apex.
+    pub fn new(apex: N) -> Self {
+        ChildKeys {
+            apex,
+            dnskeys: Vec::new(),
+            cds: Vec::new(),
+            cdnskeys: Vec::new(),
+            rrsigs: Vec::new(),
+            digest_type: DigestAlg::Sha256,
+        }
+    }
+
+    /// Returns the apex of the child zone.
+    pub fn apex(&self) -> &N {
+        &self.apex
+    }
+
+    /// Adds a record to the DNSKEY RRset.
+    pub fn push_dnskey(&mut self, dnskey: Dnskey<Octets>) {
+        self.dnskeys.push(dnskey)
+    }
+
+    /// Adds a record to the CDS RRset.
+    pub fn push_cds(&mut self, cds: Cds<Octets>) {
+        self.cds.push(cds)
+    }
+
+    /// Adds a record to the CDNSKEY RRset.
+    pub fn push_cdnskey(&mut self, cdnskey: Cdnskey<Octets>) {
+        self.cdnskeys.push(cdnskey)
+    }
+
+    /// Adds a signature for one of the RRsets.
+    pub fn push_rrsig(&mut self, rrsig: Rrsig<Octets, SignerName>) {
+        self.rrsigs.push(rrsig)
+    }
+
+    /// Returns the digest type for DS records created from CDNSKEY records.
+    ///
+    /// This digest type is only used if the child only publishes CDNSKEY
+    /// records. It defaults to SHA-256.
+    pub fn digest_type(&self) -> DigestAlg {
+        self.digest_type
+    }
+
+    /// Sets the digest type for DS records created from CDNSKEY records.
+    pub fn set_digest_type(&mut self, digest_type: DigestAlg) {
+        self.digest_type = digest_type
+    }
+}
+
+impl<N, Octets, SignerName> ChildKeys<N, Octets, SignerName>
+where
+    N: ToDname + Clone,
+    Octets: AsRef<[u8]> + Clone,
+    SignerName: ToDname,
+{
+    /// Checks the records against the current DS RRset of the child.
+    ///
+    /// The signatures need to be valid at the time `now`.
+    pub fn process<DsOctets: AsRef<[u8]>>(
+        &self,
+        current: &[Ds<DsOctets>],
+        now: Time32,
+    ) -> Result<DsUpdate, CdsError> {
+        if self.cds.is_empty() && self.cdnskeys.is_empty() {
+            return Ok(DsUpdate::Unchanged);
+        }
+        if current.is_empty() {
+            return Err(CdsError::NoChain);
+        }
+
+        // The keys referred to by the current DS RRset. These must have
+        // signed the DNSKEY, CDS, and CDNSKEY RRsets.
+        let trusted: Vec<_> = self
+            .dnskeys
+            .iter()
+            .filter(|key| {
+                current.iter().any(|ds| ds_matches(ds, key, &self.apex))
+            })
+            .collect();
+        if trusted.is_empty() {
+            return Err(CdsError::NoTrustedKey);
+        }
+        self.verify(Rtype::Dnskey, &self.dnskeys, &trusted, now)?;
+        if !self.cds.is_empty() {
+            self.verify(Rtype::Cds, &self.cds, &trusted, now)?;
+        }
+        if !self.cdnskeys.is_empty() {
+            self.verify(Rtype::Cdnskey, &self.cdnskeys, &trusted, now)?;
+        }
+
+        // A delete request must be the only record in its RRset.
+        let cds_delete =
+            self.cds.iter().any(|cds| cds.algorithm().to_int() == 0);
+        let cdnskey_delete = self
+            .cdnskeys
+            .iter()
+            .any(|key| key.algorithm().to_int() == 0);
+        if cds_delete || cdnskey_delete {
+            if (cds_delete && self.cds.len() > 1)
+                || (cdnskey_delete && self.cdnskeys.len() > 1)
+                || (!self.cds.is_empty() && !cds_delete)
+                || (!self.cdnskeys.is_empty() && !cdnskey_delete)
+            {
+                return Err(CdsError::MixedDelete);
+            }
+            return Ok(DsUpdate::Delete);
+        }
+
+        // Each CDNSKEY must have a matching CDS and vice versa.
+        if !self.cds.is_empty() && !self.cdnskeys.is_empty() {
+            let consistent = self.cdnskeys.iter().all(|key| {
+                let key = cdnskey_to_dnskey(key);
+                self.cds
+                    .iter()
+                    .any(|cds| ds_matches(cds_to_ds(cds), &key, &self.apex))
+            }) && self.cds.iter().all(|cds| {
+                self.cdnskeys.iter().any(|key| {
+                    ds_matches(
+                        cds_to_ds(cds),
+                        &cdnskey_to_dnskey(key),
+                        &self.apex,
+                    )
+                })
+            });
+            if !consistent {
+                return Err(CdsError::Inconsistent);
+            }
+        }
+
+        // Determine the new DS RRset.
+        let mut new: Vec<Ds<Vec<u8>>> = if !self.cds.is_empty() {
+            self.cds
+                .iter()
+                .map(|cds| {
+                    Ds::new(
+                        cds.key_tag(),
+                        cds.algorithm(),
+                        cds.digest_type(),
+                        cds.digest().as_ref().into(),
+                    )
+                })
+                .collect()
+        } else {
+            let mut res = Vec::new();
+            for key in &self.cdnskeys {
+                let key = cdnskey_to_dnskey(key);
+                let digest = key
+                    .digest(&self.apex, self.digest_type)
+                    .map_err(|_| CdsError::UnsupportedDigest)?;
+                res.push(Ds::new(
+                    key.key_tag(),
+                    key.algorithm(),
+                    self.digest_type,
+                    digest.as_ref().into(),
+                ))
+            }
+            res
+        };
+        new.sort();
+        new.dedup();
+
+        // The new DS RRset needs to refer to a key that signs the DNSKEY
+        // RRset or validation of the child breaks.
+        let entry: Vec<_> = self
+            .dnskeys
+            .iter()
+            .filter(|key| {
+                new.iter().any(|ds| ds_matches(ds, key, &self.apex))
+            })
+            .collect();
+        if entry.is_empty()
+            || self
+                .verify(Rtype::Dnskey, &self.dnskeys, &entry, now)
+                .is_err()
+        {
+            return Err(CdsError::BreaksChain);
+        }
+
+        let mut current: Vec<_> = current
+            .iter()
+            .map(|ds| {
+                Ds::new(
+                    ds.key_tag(),
+                    ds.algorithm(),
+                    ds.digest_type(),
+                    Vec::from(ds.digest().as_ref()),
+                )
+            })
+            .collect();
+        current.sort();
+        current.dedup();
+        if current == new {
+            Ok(DsUpdate::Unchanged)
+        } else {
+            Ok(DsUpdate::Replace(new))
+        }
+    }
+
+    /// Verifies that an RRset is signed by one of the given keys.
+    fn verify<D>(
+        &self,
+        rtype: Rtype,
+        rrset: &[D],
+        keys: &[&Dnskey<Octets>],
+        now: Time32,
+    ) -> Result<(), CdsError>
+    where
+        D: RecordData + CanonicalOrd + Compose + Clone,
+    {
+        let mut records: Vec<_> = rrset
+            .iter()
+            .map(|data| {
+                Record::new(self.apex.clone(), Class::In, 0, data.clone())
+            })
+            .collect();
+        for rrsig in &self.rrsigs {
+            if rrsig.type_covered() != rtype
+                || !rrsig.signer_name().name_eq(&self.apex)
+                || rrsig.inception() > now
+                || rrsig.expiration() < now
+            {
+                continue;
+            }
+            let key = match keys.iter().find(|key| {
+                key.key_tag() == rrsig.key_tag()
+                    && key.algorithm() == rrsig.algorithm()
+            }) {
+                Some(key) => key,
+                None => continue,
+            };
+            let mut signed_data = Vec::new();
+            if rrsig.signed_data(&mut signed_data, &mut records).is_err() {
+                continue;
+            }
+            if rrsig.verify_signed_data(key, &signed_data).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(CdsError::Unsigned(rtype))
+    }
+}
+
+//--- Debug
+
+impl<N, Octets, SignerName> fmt::Debug for ChildKeys<N, Octets, SignerName>
+where
+    N: fmt::Debug,
+    Octets: AsRef<[u8]>,
+    SignerName: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChildKeys")
+            .field("apex", &self.apex)
+            .field("dnskeys", &self.dnskeys)
+            .field("cds", &self.cds)
+            .field("cdnskeys", &self.cdnskeys)
+            .field("rrsigs", &self.rrsigs)
+            .field("digest_type", &self.digest_type)
+            .finish()
+    }
+}
+
+//------------ Helpers -------------------------------------------------------
+
+/// A view of a DS or CDS record.
+trait DsData {
+    fn key_tag(&self) -> u16;
+    fn algorithm(&self) -> SecAlg;
+    fn digest_type(&self) -> DigestAlg;
+    fn digest(&self) -> &[u8];
+}
+
+impl<Octets: AsRef<[u8]>> DsData for Ds<Octets> {
+    fn key_tag(&self) -> u16 {
+        Ds::key_tag(self)
+    }
+
+    fn algorithm(&self) -> SecAlg {
+        Ds::algorithm(self)
+    }
+
+    fn digest_type(&self) -> DigestAlg {
+        Ds::digest_type(self)
+    }
+
+    fn digest(&self) -> &[u8] {
+        Ds::digest(self).as_ref()
+    }
+}
+
+impl<T: DsData> DsData for &T {
+    fn key_tag(&self) -> u16 {
+        (*self).key_tag()
+    }
+
+    fn algorithm(&self) -> SecAlg {
+        (*self).algorithm()
+    }
+
+    fn digest_type(&self) -> DigestAlg {
+        (*self).digest_type()
+    }
+
+    fn digest(&self) -> &[u8] {
+        (*self).digest()
+    }
+}
+
+/// Converts a CDS record into a DS record borrowing its digest.
+fn cds_to_ds<Octets: AsRef<[u8]>>(cds: &Cds<Octets>) -> Ds<&[u8]> {
+    Ds::new(
+        cds.key_tag(),
+        cds.algorithm(),
+        cds.digest_type(),
+        cds.digest().as_ref(),
+    )
+}
+
+/// Converts a CDNSKEY record into a DNSKEY record.
+fn cdnskey_to_dnskey<Octets: Clone>(key: &Cdnskey<Octets>) -> Dnskey<Octets> {
+    Dnskey::new(
+        key.flags(),
+        key.protocol(),
+        key.algorithm(),
+        key.public_key().clone(),
+    )
+}
+
+/// Returns whether a DS record refers to a DNSKEY record.
+///
+/// Keys that aren’t zone keys or have been revoked never match.
+fn ds_matches<D, Octets, N>(ds: D, key: &Dnskey<Octets>, apex: &N) -> bool
+where
+    D: DsData,
+    Octets: AsRef<[u8]>,
+    N: ToDname,
+{
+    if !key.is_zsk()
+        || key.is_revoked()
+        || ds.algorithm() != key.algorithm()
+        || ds.key_tag() != key.key_tag()
+    {
+        return false;
+    }
+    match key.digest(apex, ds.digest_type()) {
+        Ok(digest) => digest.as_ref() == ds.digest(),
+        Err(_) => false,
+    }
+}
+
+//------------ DsUpdate ------------------------------------------------------
+
+/// The action a parent should take for the DS RRset of a child.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DsUpdate {
+    /// The DS RRset should be kept as it is.
+    Unchanged,
+
+    /// The DS RRset should be replaced with the given records.
+    Replace(Vec<Ds<Vec<u8>>>),
+
+    /// The DS RRset should be removed, turning the child insecure.
+    Delete,
+}
+
+//============ Error Types ===================================================
+
+//------------ CdsError ------------------------------------------------------
+
+/// The CDS and CDNSKEY records of a child were not acceptable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CdsError {
+    /// The child has no DS RRset to validate against.
+    NoChain,
+
+    /// None of the child’s keys is referred to by the current DS RRset.
+    NoTrustedKey,
+
+    /// The RRset of the given type is not signed by a trusted key.
+    Unsigned(Rtype),
+
+    /// A delete request was combined with other records.
+    MixedDelete,
+
+    /// The CDS and CDNSKEY RRsets don’t match.
+    Inconsistent,
+
+    /// The digest type for DS records created from CDNSKEY records is not
+    /// supported.
+    UnsupportedDigest,
+
+    /// The new DS RRset would break the chain of trust.
+    BreaksChain,
+}
+
+//--- Display and Error
+
+impl fmt::Display for CdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CdsError::NoChain => f.write_str("child zone is not secure"),
+            CdsError::NoTrustedKey => {
+                f.write_str("no key referred to by the current DS RRset")
+            }
+            CdsError::Unsigned(rtype) => {
+                write!(f, "{} RRset not signed by a trusted key", rtype)
+            }
+            CdsError::MixedDelete => {
+                f.write_str("delete request combined with other records")
+            }
+            CdsError::Inconsistent => {
+                f.write_str("CDS and CDNSKEY RRsets don’t match")
+            }
+            CdsError::UnsupportedDigest => {
+                f.write_str("unsupported digest type")
+            }
+            CdsError::BreaksChain => {
+                f.write_str("new DS RRset would break the chain of trust")
+            }
+        }
+    }
+}
+
+impl error::Error for CdsError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::name::Dname;
+    use core::str::FromStr;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    type Name = Dname<Vec<u8>>;
+    type Keys = ChildKeys<Name, Vec<u8>, Name>;
+
+    struct Key {
+        pair: Ed25519KeyPair,
+        dnskey: Dnskey<Vec<u8>>,
+    }
+
+    impl Key {
+        fn new(seed: u8) -> Self {
+            let pair =
+                Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap();
+            let dnskey = Dnskey::new(
+                257,
+                3,
+                SecAlg::Ed25519,
+                pair.public_key().as_ref().into(),
+            );
+            Key { pair, dnskey }
+        }
+
+        fn ds(&self, apex: &Name) -> Ds<Vec<u8>> {
+            Ds::new(
+                self.dnskey.key_tag(),
+                SecAlg::Ed25519,
+                DigestAlg::Sha256,
+                self.dnskey
+                    .digest(apex, DigestAlg::Sha256)
+                    .unwrap()
+                    .as_ref()
+                    .into(),
+            )
+        }
+
+        fn cds(&self, apex: &Name) -> Cds<Vec<u8>> {
+            let ds = self.ds(apex);
+            Cds::new(
+                ds.key_tag(),
+                ds.algorithm(),
+                ds.digest_type(),
+                ds.into_digest(),
+            )
+        }
+
+        fn cdnskey(&self) -> Cdnskey<Vec<u8>> {
+            Cdnskey::new(
+                self.dnskey.flags(),
+                self.dnskey.protocol(),
+                self.dnskey.algorithm(),
+                self.dnskey.public_key().clone(),
+            )
+        }
+
+        fn sign<D>(
+            &self,
+            apex: &Name,
+            rtype: Rtype,
+            rrset: &[D],
+        ) -> Rrsig<Vec<u8>, Name>
+        where
+            D: RecordData + CanonicalOrd + Compose + Clone,
+        {
+            let rrsig = |signature| {
+                Rrsig::new(
+                    rtype,
+                    SecAlg::Ed25519,
+                    1,
+                    3600,
+                    2000.into(),
+                    1000.into(),
+                    self.dnskey.key_tag(),
+                    apex.clone(),
+                    signature,
+                )
+            };
+            let mut records: Vec<_> = rrset
+                .iter()
+                .map(|data| {
+                    Record::new(apex.clone(), Class::In, 0, data.clone())
+                })
+                .collect();
+            let mut data = Vec::new();
+            rrsig(Vec::new())
+                .signed_data(&mut data, &mut records)
+                .unwrap();
+            rrsig(self.pair.sign(&data).as_ref().into())
+        }
+    }
+
+    fn child(
+        apex: &Name,
+        dnskeys: &[&Key],
+        signers: &[&Key],
+        cds: &[Cds<Vec<u8>>],
+        cdnskeys: &[Cdnskey<Vec<u8>>],
+    ) -> Keys {
+        let mut res = ChildKeys::new(apex.clone());
+        let dnskeys: Vec<_> =
+            dnskeys.iter().map(|key| key.dnskey.clone()).collect();
+        for key in signers {
+            res.push_rrsig(key.sign(apex, Rtype::Dnskey, &dnskeys));
+            if !cds.is_empty() {
+                res.push_rrsig(key.sign(apex, Rtype::Cds, cds));
+            }
+            if !cdnskeys.is_empty() {
+                res.push_rrsig(key.sign(apex, Rtype::Cdnskey, cdnskeys));
+            }
+        }
+        dnskeys.into_iter().for_each(|key| res.push_dnskey(key));
+        cds.iter().cloned().for_each(|cds| res.push_cds(cds));
+        cdnskeys
+            .iter()
+            .cloned()
+            .for_each(|key| res.push_cdnskey(key));
+        res
+    }
+
+    #[test]
+    fn rollover() {
+        let apex = Name::from_str("child.example.").unwrap();
+        let (old, new) = (Key::new(1), Key::new(2));
+        let current = [old.ds(&apex)];
+        let now = Time32::from(1500);
+
+        // Nothing published, nothing to do.
+        let keys = child(&apex, &[&old], &[&old], &[], &[]);
+        assert_eq!(keys.process(&current, now), Ok(DsUpdate::Unchanged));
+
+        // Roll to the new key, signed by both keys.
+        let keys = child(
+            &apex,
+            &[&old, &new],
+            &[&old, &new],
+            &[new.cds(&apex)],
+            &[new.cdnskey()],
+        );
+        assert_eq!(
+            keys.process(&current, now),
+            Ok(DsUpdate::Replace(vec![new.ds(&apex)]))
+        );
+        assert_eq!(
+            keys.process(&[new.ds(&apex)], now),
+            Ok(DsUpdate::Unchanged)
+        );
+
+        // CDNSKEY only.
+        let keys =
+            child(&apex, &[&old, &new], &[&old, &new], &[], &[new.cdnskey()]);
+        assert_eq!(
+            keys.process(&current, now),
+            Ok(DsUpdate::Replace(vec![new.ds(&apex)]))
+        );
+
+        // Signatures must be valid now.
+        assert_eq!(
+            keys.process(&current, 3000.into()),
+            Err(CdsError::Unsigned(Rtype::Dnskey))
+        );
+
+        // Insecure children need bootstrapping.
+        assert_eq!(keys.process::<Vec<u8>>(&[], now), Err(CdsError::NoChain));
+    }
+
+    #[test]
+    fn reject() {
+        let apex = Name::from_str("child.example.").unwrap();
+        let (old, new, other) = (Key::new(1), Key::new(2), Key::new(3));
+        let current = [old.ds(&apex)];
+        let now = Time32::from(1500);
+
+        // Signed by the new key only.
+        let keys =
+            child(&apex, &[&old, &new], &[&new], &[new.cds(&apex)], &[]);
+        assert_eq!(
+            keys.process(&current, now),
+            Err(CdsError::Unsigned(Rtype::Dnskey))
+        );
+
+        // New key doesn’t sign the DNSKEY RRset.
+        let keys =
+            child(&apex, &[&old, &new], &[&old], &[new.cds(&apex)], &[]);
+        assert_eq!(keys.process(&current, now), Err(CdsError::BreaksChain));
+
+        // CDS and CDNSKEY disagree.
+        let keys = child(
+            &apex,
+            &[&old, &new],
+            &[&old, &new],
+            &[new.cds(&apex)],
+            &[other.cdnskey()],
+        );
+        assert_eq!(keys.process(&current, now), Err(CdsError::Inconsistent));
+    }
+
+    #[test]
+    fn delete() {
+        let apex = Name::from_str("child.example.").unwrap();
+        let key = Key::new(1);
+        let current = [key.ds(&apex)];
+        let now = Time32::from(1500);
+        let delete_cds = Cds::new(0, 0.into(), 0.into(), vec![0]);
+        let delete_cdnskey = Cdnskey::new(0, 3, 0.into(), vec![0]);
+
+        let keys = child(
+            &apex,
+            &[&key],
+            &[&key],
+            &[delete_cds.clone()],
+            &[delete_cdnskey],
+        );
+        assert_eq!(keys.process(&current, now), Ok(DsUpdate::Delete));
+
+        let keys =
+            child(&apex, &[&key], &[&key], &[delete_cds], &[key.cdnskey()]);
+        assert_eq!(keys.process(&current, now), Err(CdsError::MixedDelete));
+    }
+}
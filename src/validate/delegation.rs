@@ -0,0 +1,738 @@
+//! Checking the consistency of a delegation.
+//!
+//! A delegation consists of data on both sides of a zone cut. The parent
+//! zone publishes the NS RRset of the child zone, glue addresses for name
+//! servers within the child zone, and, if the child is signed, the DS
+//! RRset. The child zone publishes the authoritative copy of its NS RRset,
+//! the addresses of its name servers, and its DNSKEY RRset. If the two
+//! sides disagree, resolution may fail in hard to debug ways.
+//!
+//! The function [`check_delegation`] compares the records of a child zone
+//! against the data published by the parent and returns a
+//! [`DelegationReport`] describing what has been found. The parent side
+//! data is retrieved via a [`Resolver`] which should send its queries
+//! directly to the authoritative servers of the parent zone so that it
+//! receives the parent’s referral rather than the child’s NS RRset.
+//!
+//! [`check_delegation`]: fn.check_delegation.html
+//! [`DelegationReport`]: struct.DelegationReport.html
+//! [`Resolver`]: ../../resolv/resolver/trait.Resolver.html
+#![cfg(feature = "resolv")]
+
+use super::DnskeyExt;
+use crate::base::iana::{DigestAlg, Rtype, SecAlg};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::record::Record;
+use crate::rdata::{Aaaa, Dnskey, Ds, MasterRecordData, Ns, A};
+use crate::resolv::resolver::Resolver;
+use std::net::IpAddr;
+use std::vec::Vec;
+use std::{fmt, io};
+
+//------------ check_delegation ----------------------------------------------
+
+/// Checks the delegation of a zone against its parent.
+///
+/// The child zone is given by its apex and its records. Only the NS and
+/// DNSKEY records at the apex and the address records of name servers
+/// within the zone are considered, so `records` may contain the complete
+/// zone or just these records.
+///
+/// The NS and DS records for the apex are requested from `resolver`. NS
+/// records are taken from the answer and authority sections and glue from
+/// the additional section of the response so that both a referral and an
+/// authoritative answer are understood.
+///
+/// The function fails if any of the queries fails.
+pub async fn check_delegation<'a, R, N, Octets, I>(
+    resolver: &R,
+    apex: &N,
+    records: I,
+) -> Result<DelegationReport, io::Error>
+where
+    R: Resolver,
+    N: ToDname + 'a,
+    Octets: AsRef<[u8]> + 'a,
+    I: IntoIterator<Item = &'a Record<N, MasterRecordData<Octets, N>>>,
+{
+    let apex = apex.to_vec();
+    let child = ChildData::collect(&apex, records);
+    let (ns, ds) = tokio::join!(
+        resolver.query((&apex, Rtype::Ns)),
+        resolver.query((&apex, Rtype::Ds)),
+    );
+    let ns = ns?;
+    let ds = ds?;
+    let parent = ParentData::collect(&apex, ns.as_ref(), ds.as_ref());
+    Ok(DelegationReport::new(apex, child, parent))
+}
+
+//------------ ChildData and ParentData --------------------------------------
+
+/// The data of the child side of the delegation.
+struct ChildData {
+    ns: Vec<Dname<Vec<u8>>>,
+    dnskeys: Vec<Dnskey<Vec<u8>>>,
+    addrs: Vec<(Dname<Vec<u8>>, IpAddr)>,
+}
+
+impl ChildData {
+    fn collect<'a, N, Octets, I>(apex: &Dname<Vec<u8>>, records: I) -> Self
+    where
+        N: ToDname + 'a,
+        Octets: AsRef<[u8]> + 'a,
+        I: IntoIterator<Item = &'a Record<N, MasterRecordData<Octets, N>>>,
+    {
+        let mut res = ChildData {
+            ns: Vec::new(),
+            dnskeys: Vec::new(),
+            addrs: Vec::new(),
+        };
+        for record in records {
+            let at_apex = record.owner().name_eq(apex);
+            match *record.data() {
+                MasterRecordData::Ns(ref ns) if at_apex => {
+                    push_name(&mut res.ns, ns.nsdname().to_vec())
+                }
+                MasterRecordData::Dnskey(ref dnskey) if at_apex => {
+                    res.dnskeys.push(Dnskey::new(
+                        dnskey.flags(),
+                        dnskey.protocol(),
+                        dnskey.algorithm(),
+                        dnskey.public_key().as_ref().to_vec(),
+                    ))
+                }
+                MasterRecordData::A(ref a) => {
+                    res.addrs.push((record.owner().to_vec(), a.addr().into()))
+                }
+                MasterRecordData::Aaaa(ref aaaa) => res
+                    .addrs
+                    .push((record.owner().to_vec(), aaaa.addr().into())),
+                _ => {}
+            }
+        }
+        res
+    }
+}
+
+/// The data of the parent side of the delegation.
+struct ParentData {
+    ns: Vec<Dname<Vec<u8>>>,
+    ds: Vec<Ds<Vec<u8>>>,
+    glue: Vec<(Dname<Vec<u8>>, IpAddr)>,
+}
+
+impl ParentData {
+    fn collect<O1, O2>(
+        apex: &Dname<Vec<u8>>,
+        ns: &Message<O1>,
+        ds: &Message<O2>,
+    ) -> Self
+    where
+        O1: AsRef<[u8]>,
+        O2: AsRef<[u8]>,
+    {
+        let mut res = ParentData {
+            ns: Vec::new(),
+            ds: Vec::new(),
+            glue: Vec::new(),
+        };
+
+        let ns = ns.for_slice();
+        if let Ok((_, answer, authority, additional)) = ns.sections() {
+            let ns_records = answer
+                .limit_to::<Ns<ParsedDname<&&[u8]>>>()
+                .chain(authority.limit_to::<Ns<ParsedDname<&&[u8]>>>());
+            for record in ns_records.flatten() {
+                if record.owner().name_eq(apex) {
+                    push_name(&mut res.ns, record.data().nsdname().to_vec())
+                }
+            }
+            for record in additional.limit_to::<A>().flatten() {
+                res.glue.push((
+                    record.owner().to_vec(),
+                    record.data().addr().into(),
+                ))
+            }
+            for record in additional.limit_to::<Aaaa>().flatten() {
+                res.glue.push((
+                    record.owner().to_vec(),
+                    record.data().addr().into(),
+                ))
+            }
+        }
+
+        let ds = ds.for_slice();
+        if let Ok(answer) = ds.answer() {
+            for record in answer.limit_to::<Ds<&[u8]>>().flatten() {
+                if record.owner().name_eq(apex) {
+                    let ds = record.data();
+                    res.ds.push(Ds::new(
+                        ds.key_tag(),
+                        ds.algorithm(),
+                        ds.digest_type(),
+                        ds.digest().to_vec(),
+                    ))
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Adds a name to a list unless it is already present.
+fn push_name(names: &mut Vec<Dname<Vec<u8>>>, name: Dname<Vec<u8>>) {
+    if !names.iter().any(|item| item.name_eq(&name)) {
+        names.push(name)
+    }
+}
+
+/// Returns whether a name is in a list of names.
+fn contains_name(names: &[Dname<Vec<u8>>], name: &Dname<Vec<u8>>) -> bool {
+    names.iter().any(|item| item.name_eq(name))
+}
+
+/// Returns the sorted addresses for a name.
+fn addrs_for(
+    addrs: &[(Dname<Vec<u8>>, IpAddr)],
+    name: &Dname<Vec<u8>>,
+) -> Vec<IpAddr> {
+    let mut res: Vec<_> = addrs
+        .iter()
+        .filter(|(owner, _)| owner.name_eq(name))
+        .map(|(_, addr)| *addr)
+        .collect();
+    res.sort();
+    res.dedup();
+    res
+}
+
+//------------ DelegationReport ----------------------------------------------
+
+/// The result of checking a delegation.
+///
+/// The report contains the NS RRsets found on both sides, the result of
+/// checking each DS record of the parent against the child’s DNSKEY
+/// records, and the result of comparing the glue for each name server
+/// within the child zone against the child’s address records.
+///
+/// The method [`is_consistent`] summarizes whether everything is fine. The
+/// `Display` implementation lists all problems, one per line.
+///
+/// [`is_consistent`]: #method.is_consistent
+#[derive(Clone, Debug)]
+pub struct DelegationReport {
+    apex: Dname<Vec<u8>>,
+    parent_ns: Vec<Dname<Vec<u8>>>,
+    child_ns: Vec<Dname<Vec<u8>>>,
+    ds: Vec<DsCheck>,
+    glue: Vec<GlueCheck>,
+}
+
+impl DelegationReport {
+    fn new(
+        apex: Dname<Vec<u8>>,
+        child: ChildData,
+        parent: ParentData,
+    ) -> Self {
+        let ds = parent
+            .ds
+            .iter()
+            .map(|ds| DsCheck::new(&apex, ds, &child.dnskeys))
+            .collect();
+
+        // Glue is needed for all name servers within the child zone listed
+        // on either side.
+        let mut glue = Vec::new();
+        for name in parent.ns.iter().chain(child.ns.iter()) {
+            if !name.ends_with(&apex)
+                || glue.iter().any(|item: &GlueCheck| item.name.name_eq(name))
+            {
+                continue;
+            }
+            glue.push(GlueCheck {
+                name: name.clone(),
+                parent: addrs_for(&parent.glue, name),
+                child: addrs_for(&child.addrs, name),
+            })
+        }
+
+        DelegationReport {
+            apex,
+            parent_ns: parent.ns,
+            child_ns: child.ns,
+            ds,
+            glue,
+        }
+    }
+
+    /// Returns the apex of the child zone.
+    pub fn apex(&self) -> &Dname<Vec<u8>> {
+        &self.apex
+    }
+
+    /// Returns the NS RRset published by the parent.
+    pub fn parent_ns(&self) -> &[Dname<Vec<u8>>] {
+        &self.parent_ns
+    }
+
+    /// Returns the NS RRset published by the child.
+    pub fn child_ns(&self) -> &[Dname<Vec<u8>>] {
+        &self.child_ns
+    }
+
+    /// Returns the name servers listed by the child but not the parent.
+    pub fn missing_at_parent(&self) -> Vec<&Dname<Vec<u8>>> {
+        self.child_ns
+            .iter()
+            .filter(|name| !contains_name(&self.parent_ns, name))
+            .collect()
+    }
+
+    /// Returns the name servers listed by the parent but not the child.
+    pub fn missing_at_child(&self) -> Vec<&Dname<Vec<u8>>> {
+        self.parent_ns
+            .iter()
+            .filter(|name| !contains_name(&self.child_ns, name))
+            .collect()
+    }
+
+    /// Returns whether both sides list the same name servers.
+    pub fn ns_match(&self) -> bool {
+        self.missing_at_parent().is_empty()
+            && self.missing_at_child().is_empty()
+    }
+
+    /// Returns the results of checking the parent’s DS records.
+    pub fn ds(&self) -> &[DsCheck] {
+        &self.ds
+    }
+
+    /// Returns whether the delegation is secure.
+    ///
+    /// This is the case if at least one DS record matches a DNSKEY record
+    /// of the child.
+    pub fn is_secure(&self) -> bool {
+        self.ds.iter().any(|ds| ds.status == DsStatus::Matches)
+    }
+
+    /// Returns the results of checking the glue.
+    pub fn glue(&self) -> &[GlueCheck] {
+        &self.glue
+    }
+
+    /// Returns whether no problems have been found.
+    ///
+    /// The delegation is consistent if the NS RRsets match, all glue is
+    /// correct, and either there are no DS records or all of them match a
+    /// DNSKEY record of the child.
+    pub fn is_consistent(&self) -> bool {
+        !self.parent_ns.is_empty()
+            && self.ns_match()
+            && self.ds.iter().all(|ds| ds.status == DsStatus::Matches)
+            && self
+                .glue
+                .iter()
+                .all(|glue| glue.status() == GlueStatus::Matches)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for DelegationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.parent_ns.is_empty() {
+            writeln!(f, "{}: no NS records at parent", self.apex)?;
+        }
+        for name in self.missing_at_parent() {
+            writeln!(f, "{}: NS {} missing at parent", self.apex, name)?;
+        }
+        for name in self.missing_at_child() {
+            writeln!(f, "{}: NS {} missing at child", self.apex, name)?;
+        }
+        for ds in &self.ds {
+            if ds.status != DsStatus::Matches {
+                writeln!(f, "{}: {}", self.apex, ds)?;
+            }
+        }
+        for glue in &self.glue {
+            if glue.status() != GlueStatus::Matches {
+                writeln!(f, "{}: {}", self.apex, glue)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//------------ DsCheck -------------------------------------------------------
+
+/// The result of checking a DS record against the child’s DNSKEY records.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DsCheck {
+    key_tag: u16,
+    algorithm: SecAlg,
+    digest_type: DigestAlg,
+    status: DsStatus,
+}
+
+impl DsCheck {
+    fn new(
+        apex: &Dname<Vec<u8>>,
+        ds: &Ds<Vec<u8>>,
+        dnskeys: &[Dnskey<Vec<u8>>],
+    ) -> Self {
+        let mut status = DsStatus::NoKey;
+        for dnskey in dnskeys {
+            if dnskey.key_tag() != ds.key_tag()
+                || dnskey.algorithm() != ds.algorithm()
+            {
+                continue;
+            }
+            match dnskey.digest(apex, ds.digest_type()) {
+                Ok(digest) if digest.as_ref() == ds.digest().as_slice() => {
+                    status = DsStatus::Matches;
+                    break;
+                }
+                Ok(_) => status = DsStatus::DigestMismatch,
+                Err(_) => status = DsStatus::UnsupportedDigest,
+            }
+        }
+        DsCheck {
+            key_tag: ds.key_tag(),
+            algorithm: ds.algorithm(),
+            digest_type: ds.digest_type(),
+            status,
+        }
+    }
+
+    /// Returns the key tag of the DS record.
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+
+    /// Returns the algorithm of the DS record.
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    /// Returns the digest type of the DS record.
+    pub fn digest_type(&self) -> DigestAlg {
+        self.digest_type
+    }
+
+    /// Returns the result of the check.
+    pub fn status(&self) -> DsStatus {
+        self.status
+    }
+}
+
+//--- Display
+
+impl fmt::Display for DsCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DS {} {} {}: ",
+            self.key_tag, self.algorithm, self.digest_type
+        )?;
+        match self.status {
+            DsStatus::Matches => f.write_str("matches a DNSKEY"),
+            DsStatus::NoKey => f.write_str("no matching DNSKEY"),
+            DsStatus::DigestMismatch => f.write_str("digest mismatch"),
+            DsStatus::UnsupportedDigest => {
+                f.write_str("unsupported digest type")
+            }
+        }
+    }
+}
+
+//------------ DsStatus ------------------------------------------------------
+
+/// The result of checking a DS record.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DsStatus {
+    /// The DS record matches a DNSKEY record of the child.
+    Matches,
+
+    /// The child has no DNSKEY record with the key tag and algorithm.
+    NoKey,
+
+    /// The digest doesn’t match the DNSKEY records with the key tag.
+    DigestMismatch,
+
+    /// The digest type is not supported, so the digest wasn’t checked.
+    UnsupportedDigest,
+}
+
+//------------ GlueCheck -----------------------------------------------------
+
+/// The result of comparing the glue for a name server.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GlueCheck {
+    name: Dname<Vec<u8>>,
+    parent: Vec<IpAddr>,
+    child: Vec<IpAddr>,
+}
+
+impl GlueCheck {
+    /// Returns the name of the name server.
+    pub fn name(&self) -> &Dname<Vec<u8>> {
+        &self.name
+    }
+
+    /// Returns the glue addresses published by the parent.
+    pub fn parent(&self) -> &[IpAddr] {
+        &self.parent
+    }
+
+    /// Returns the addresses published by the child.
+    pub fn child(&self) -> &[IpAddr] {
+        &self.child
+    }
+
+    /// Returns the result of the comparison.
+    pub fn status(&self) -> GlueStatus {
+        if self.parent.is_empty() {
+            GlueStatus::Missing
+        } else if self.parent != self.child {
+            GlueStatus::Mismatch
+        } else {
+            GlueStatus::Matches
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for GlueCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "glue for {}: ", self.name)?;
+        match self.status() {
+            GlueStatus::Matches => f.write_str("matches"),
+            GlueStatus::Missing => f.write_str("missing at parent"),
+            GlueStatus::Mismatch => {
+                f.write_str("parent has")?;
+                for addr in &self.parent {
+                    write!(f, " {}", addr)?;
+                }
+                f.write_str(", child has")?;
+                for addr in &self.child {
+                    write!(f, " {}", addr)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+//------------ GlueStatus ----------------------------------------------------
+
+/// The result of comparing the glue for a name server.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GlueStatus {
+    /// The parent’s glue matches the child’s addresses.
+    Matches,
+
+    /// The parent has no glue for the name server.
+    Missing,
+
+    /// The parent’s glue differs from the child’s addresses.
+    Mismatch,
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Rcode};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::octets::OctetsBuilder;
+    use crate::utils::replay::{Corpus, Exchange};
+    use std::str::FromStr;
+    use std::string::ToString;
+
+    type Name = Dname<Vec<u8>>;
+    type ChildRecord = Record<Name, MasterRecordData<Vec<u8>, Name>>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn query(rtype: Rtype) -> Message<Vec<u8>> {
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((name("child.example"), rtype)).unwrap();
+        query.into_message()
+    }
+
+    fn referral(glue: [u8; 4]) -> Exchange {
+        let query = query(Rtype::Ns);
+        let answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        let mut authority = answer.authority();
+        for ns in &["ns1.child.example", "ns.other.example"] {
+            authority
+                .push((name("child.example"), 3600, Ns::new(name(ns))))
+                .unwrap();
+        }
+        let mut additional = authority.additional();
+        additional
+            .push((
+                name("ns1.child.example"),
+                3600,
+                A::from_octets(glue[0], glue[1], glue[2], glue[3]),
+            ))
+            .unwrap();
+        Exchange::new(query, Some(additional.into_message()))
+    }
+
+    fn ds(records: &[Ds<Vec<u8>>]) -> Exchange {
+        let query = query(Rtype::Ds);
+        let mut answer = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        for ds in records {
+            answer
+                .push((name("child.example"), 3600, ds.clone()))
+                .unwrap();
+        }
+        Exchange::new(query, Some(answer.into_message()))
+    }
+
+    fn child(dnskey: &Dnskey<Vec<u8>>) -> Vec<ChildRecord> {
+        let apex = name("child.example");
+        vec![
+            Record::new(
+                apex.clone(),
+                Class::In,
+                3600,
+                Ns::new(name("ns1.child.example")).into(),
+            ),
+            Record::new(
+                apex.clone(),
+                Class::In,
+                3600,
+                Ns::new(name("ns2.child.example")).into(),
+            ),
+            Record::new(apex, Class::In, 3600, dnskey.clone().into()),
+            Record::new(
+                name("ns1.child.example"),
+                Class::In,
+                3600,
+                A::from_octets(192, 0, 2, 1).into(),
+            ),
+            Record::new(
+                name("ns2.child.example"),
+                Class::In,
+                3600,
+                A::from_octets(192, 0, 2, 2).into(),
+            ),
+        ]
+    }
+
+    fn check(corpus: Corpus, records: &[ChildRecord]) -> DelegationReport {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(check_delegation(
+                &corpus,
+                &name("child.example"),
+                records,
+            ))
+            .unwrap()
+    }
+
+    #[test]
+    fn delegation() {
+        let dnskey = Dnskey::new(257, 3, SecAlg::EcdsaP256Sha256, {
+            let mut key = Vec::new();
+            key.append_slice(&[0x55; 64]).unwrap();
+            key
+        });
+        let digest = dnskey
+            .digest(&name("child.example"), DigestAlg::Sha256)
+            .unwrap();
+        let good = Ds::new(
+            dnskey.key_tag(),
+            dnskey.algorithm(),
+            DigestAlg::Sha256,
+            digest.as_ref().to_vec(),
+        );
+        let bad = Ds::new(
+            dnskey.key_tag(),
+            dnskey.algorithm(),
+            DigestAlg::Sha256,
+            vec![0; 32],
+        );
+        let unknown = Ds::new(
+            dnskey.key_tag().wrapping_add(1),
+            dnskey.algorithm(),
+            DigestAlg::Sha256,
+            vec![0; 32],
+        );
+        let records = child(&dnskey);
+
+        let report = check(
+            vec![referral([192, 0, 2, 3]), ds(&[good.clone(), bad, unknown])]
+                .into_iter()
+                .collect(),
+            &records,
+        );
+        assert_eq!(report.parent_ns().len(), 2);
+        assert_eq!(report.child_ns().len(), 2);
+        assert_eq!(report.missing_at_parent(), [&name("ns2.child.example")]);
+        assert_eq!(report.missing_at_child(), [&name("ns.other.example")]);
+        assert!(!report.ns_match());
+        let status: Vec<_> =
+            report.ds().iter().map(DsCheck::status).collect();
+        assert_eq!(
+            status,
+            [DsStatus::Matches, DsStatus::DigestMismatch, DsStatus::NoKey]
+        );
+        assert!(report.is_secure());
+        let glue: Vec<_> = report
+            .glue()
+            .iter()
+            .map(|glue| (glue.name().to_string(), glue.status()))
+            .collect();
+        assert_eq!(
+            glue,
+            [
+                ("ns1.child.example".into(), GlueStatus::Mismatch),
+                ("ns2.child.example".into(), GlueStatus::Missing),
+            ]
+        );
+        assert!(!report.is_consistent());
+        assert_eq!(report.to_string().lines().count(), 6);
+
+        // Fix the child’s NS RRset and the parent’s glue and DS RRset.
+        let records: Vec<_> = records
+            .into_iter()
+            .filter(|record| !record.owner().to_string().starts_with("ns2"))
+            .map(|record| match *record.data() {
+                MasterRecordData::Ns(ref ns)
+                    if ns.nsdname() == &name("ns2.child.example") =>
+                {
+                    Record::new(
+                        record.owner().clone(),
+                        record.class(),
+                        record.ttl(),
+                        Ns::new(name("ns.other.example")).into(),
+                    )
+                }
+                _ => record,
+            })
+            .collect();
+        let report = check(
+            vec![referral([192, 0, 2, 1]), ds(&[good])]
+                .into_iter()
+                .collect(),
+            &records,
+        );
+        assert!(report.ns_match());
+        assert!(report.is_consistent(), "{}", report);
+        assert_eq!(report.to_string(), "");
+    }
+}
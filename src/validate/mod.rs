@@ -1,18 +1,36 @@
 //! DNSSEC validation.
 //!
 //! **This module is experimental and likely to change significantly.**
+//!
+//! Trust anchors for the root zone as well as for zones not chained from
+//! the root can be collected in [`TrustAnchors`] which finds the anchor
+//! responsible for a name and verifies a zone’s DNSKEY RRset.
+//!
+//! The [cds] module helps a parent zone maintain the DS RRset of a child
+//! zone based on the child’s CDS and CDNSKEY RRsets.
+//!
+//! The [delegation] module checks that the data published by a parent zone
+//! for a delegation is consistent with the child zone.
+//!
+//! [`TrustAnchors`]: struct.TrustAnchors.html
+//! [cds]: cds/index.html
+//! [delegation]: delegation/index.html
 #![cfg(feature = "validate")]
 
+pub mod cds;
+pub mod delegation;
+
 use crate::base::cmp::CanonicalOrd;
-use crate::base::iana::{DigestAlg, SecAlg};
-use crate::base::name::ToDname;
+use crate::base::iana::{DigestAlg, Rtype, SecAlg};
+use crate::base::name::{Dname, ToDname};
 use crate::base::octets::{Compose, OctetsBuilder, ShortBuf};
 use crate::base::rdata::RecordData;
 use crate::base::record::Record;
-use crate::rdata::{Dnskey, Rrsig};
+use crate::base::time32::Time32;
+use crate::rdata::{Dnskey, Ds, Rrsig};
 use ring::{digest, signature};
 use std::vec::Vec;
-use std::{error, fmt};
+use std::{error, fmt, slice};
 
 //------------ Dnskey --------------------------------------------------------
 
@@ -300,6 +318,337 @@ fn rsa_exponent_modulus(
     Ok(public_key[pos..].split_at(exp_len))
 }
 
+//------------ TrustAnchor ---------------------------------------------------
+
+/// A trust anchor for a zone.
+///
+/// A trust anchor is a piece of information about a zone’s keys that is
+/// configured rather than learned through a chain of trust from the root.
+/// It can either be a DS record or a DNSKEY record. Anchors for zones that
+/// aren’t delegated from a signed parent – such as internal zones in a
+/// split-horizon deployment – allow validating these zones as islands of
+/// security.
+///
+/// Each anchor carries its own algorithm, so a zone can have anchors for
+/// several algorithms, e.g., during an algorithm rollover.
+#[derive(Clone, Debug)]
+pub struct TrustAnchor {
+    /// The apex of the zone this anchor is for.
+    owner: Dname<Vec<u8>>,
+
+    /// The anchor data.
+    data: TrustAnchorData,
+}
+
+/// The data of a trust anchor.
+#[derive(Clone, Debug)]
+enum TrustAnchorData {
+    Ds(Ds<Vec<u8>>),
+    Dnskey(Dnskey<Vec<u8>>),
+}
+
+impl TrustAnchor {
+    /// Creates a trust anchor from a DS record for the zone `owner`.
+    pub fn from_ds<N: ToDname, O: AsRef<[u8]>>(
+        owner: &N,
+        ds: &Ds<O>,
+    ) -> Self {
+        TrustAnchor {
+            owner: owner.to_vec(),
+            data: TrustAnchorData::Ds(Ds::new(
+                ds.key_tag(),
+                ds.algorithm(),
+                ds.digest_type(),
+                ds.digest().as_ref().into(),
+            )),
+        }
+    }
+
+    /// Creates a trust anchor from a DNSKEY record for the zone `owner`.
+    pub fn from_dnskey<N: ToDname, O: AsRef<[u8]>>(
+        owner: &N,
+        dnskey: &Dnskey<O>,
+    ) -> Self {
+        TrustAnchor {
+            owner: owner.to_vec(),
+            data: TrustAnchorData::Dnskey(Dnskey::new(
+                dnskey.flags(),
+                dnskey.protocol(),
+                dnskey.algorithm(),
+                dnskey.public_key().as_ref().into(),
+            )),
+        }
+    }
+
+    /// Returns the apex of the zone this anchor is for.
+    pub fn owner(&self) -> &Dname<Vec<u8>> {
+        &self.owner
+    }
+
+    /// Returns the key tag of the anchored key.
+    pub fn key_tag(&self) -> u16 {
+        match self.data {
+            TrustAnchorData::Ds(ref ds) => ds.key_tag(),
+            TrustAnchorData::Dnskey(ref key) => key.key_tag(),
+        }
+    }
+
+    /// Returns the algorithm of the anchored key.
+    pub fn algorithm(&self) -> SecAlg {
+        match self.data {
+            TrustAnchorData::Ds(ref ds) => ds.algorithm(),
+            TrustAnchorData::Dnskey(ref key) => key.algorithm(),
+        }
+    }
+
+    /// Returns the DS record if this anchor is one.
+    pub fn as_ds(&self) -> Option<&Ds<Vec<u8>>> {
+        match self.data {
+            TrustAnchorData::Ds(ref ds) => Some(ds),
+            _ => None,
+        }
+    }
+
+    /// Returns the DNSKEY record if this anchor is one.
+    pub fn as_dnskey(&self) -> Option<&Dnskey<Vec<u8>>> {
+        match self.data {
+            TrustAnchorData::Dnskey(ref key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the anchor can be used by this module.
+    ///
+    /// An anchor is supported if both its algorithm and, for DS anchors,
+    /// its digest type are supported. According to [RFC 4035, Section 5],
+    /// a zone that only has unsupported anchors is to be treated as if it
+    /// had no anchors at all.
+    ///
+    /// [RFC 4035, Section 5]: https://tools.ietf.org/html/rfc4035#section-5
+    pub fn is_supported(&self) -> bool {
+        let alg = matches!(
+            self.algorithm(),
+            SecAlg::RsaSha1
+                | SecAlg::RsaSha1Nsec3Sha1
+                | SecAlg::RsaSha256
+                | SecAlg::RsaSha512
+                | SecAlg::EcdsaP256Sha256
+                | SecAlg::EcdsaP384Sha384
+                | SecAlg::Ed25519
+        );
+        let digest = match self.data {
+            TrustAnchorData::Ds(ref ds) => matches!(
+                ds.digest_type(),
+                DigestAlg::Sha1 | DigestAlg::Sha256 | DigestAlg::Sha384
+            ),
+            TrustAnchorData::Dnskey(_) => true,
+        };
+        alg && digest
+    }
+
+    /// Returns whether a DNSKEY of the anchored zone matches the anchor.
+    ///
+    /// The key must be a zone key that hasn’t been revoked and must have
+    /// the anchor’s key tag and algorithm. For a DS anchor, the digest of
+    /// the key must match the anchor’s digest. For a DNSKEY anchor, the
+    /// public keys must be identical.
+    pub fn matches_dnskey<O: AsRef<[u8]>>(&self, dnskey: &Dnskey<O>) -> bool {
+        if !dnskey.is_zsk()
+            || dnskey.is_revoked()
+            || dnskey.algorithm() != self.algorithm()
+            || dnskey.key_tag() != self.key_tag()
+        {
+            return false;
+        }
+        match self.data {
+            TrustAnchorData::Ds(ref ds) => {
+                match dnskey.digest(&self.owner, ds.digest_type()) {
+                    Ok(digest) => digest.as_ref() == ds.digest().as_slice(),
+                    Err(_) => false,
+                }
+            }
+            TrustAnchorData::Dnskey(ref key) => {
+                key.public_key().as_slice() == dnskey.public_key().as_ref()
+            }
+        }
+    }
+}
+
+//------------ TrustAnchors --------------------------------------------------
+
+/// A set of trust anchors.
+///
+/// The set can hold anchors for any number of zones, including the root
+/// zone and any islands of security. Names are validated starting from the
+/// closest enclosing zone that has an anchor.
+#[derive(Clone, Debug, Default)]
+pub struct TrustAnchors {
+    anchors: Vec<TrustAnchor>,
+}
+
+impl TrustAnchors {
+    /// Creates a new, empty set of trust anchors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a trust anchor to the set.
+    pub fn push(&mut self, anchor: TrustAnchor) {
+        self.anchors.push(anchor)
+    }
+
+    /// Returns the number of anchors in the set.
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Returns an iterator over all anchors in the set.
+    pub fn iter(&self) -> slice::Iter<'_, TrustAnchor> {
+        self.anchors.iter()
+    }
+
+    /// Returns an iterator over the anchors for the zone `apex`.
+    pub fn anchors_for<'a, N: ToDname>(
+        &'a self,
+        apex: &'a N,
+    ) -> impl Iterator<Item = &'a TrustAnchor> + 'a {
+        self.anchors
+            .iter()
+            .filter(move |anchor| anchor.owner.name_eq(apex))
+    }
+
+    /// Returns the apex of the closest zone with a supported anchor.
+    ///
+    /// This is the longest owner name of a supported anchor that `qname`
+    /// is equal to or a subdomain of. Validation of `qname` needs to start
+    /// at this zone. If there is no such zone, the name is insecure.
+    pub fn closest<N: ToDname>(&self, qname: &N) -> Option<&Dname<Vec<u8>>> {
+        self.anchors
+            .iter()
+            .filter(|anchor| {
+                anchor.is_supported() && qname.ends_with(&anchor.owner)
+            })
+            .map(|anchor| &anchor.owner)
+            .max_by_key(|owner| owner.label_count())
+    }
+
+    /// Returns the keys of a DNSKEY RRset that match an anchor.
+    ///
+    /// Only supported anchors for the zone `apex` are considered.
+    pub fn matching_keys<'a, N, O, I>(
+        &self,
+        apex: &N,
+        keys: I,
+    ) -> Vec<&'a Dnskey<O>>
+    where
+        N: ToDname,
+        O: AsRef<[u8]> + 'a,
+        I: IntoIterator<Item = &'a Dnskey<O>>,
+    {
+        keys.into_iter()
+            .filter(|key| {
+                self.anchors_for(apex).any(|anchor| {
+                    anchor.is_supported() && anchor.matches_dnskey(key)
+                })
+            })
+            .collect()
+    }
+
+    /// Verifies the DNSKEY RRset of the zone `apex`.
+    ///
+    /// The RRset is considered secure if one of the `rrsigs` is valid at
+    /// the time `now`, was created by a key in the RRset that matches one
+    /// of the anchors of the zone, and verifies correctly.
+    pub fn verify_dnskeys<N, K, O, S>(
+        &self,
+        apex: &N,
+        records: &mut [Record<K, Dnskey<O>>],
+        rrsigs: &[Rrsig<S, K>],
+        now: Time32,
+    ) -> Result<(), TrustError>
+    where
+        N: ToDname,
+        K: ToDname,
+        O: AsRef<[u8]>,
+        S: AsRef<[u8]>,
+    {
+        if !self.anchors_for(apex).any(TrustAnchor::is_supported) {
+            return Err(TrustError::NoAnchor);
+        }
+        let keys: Vec<_> = records
+            .iter()
+            .filter(|record| record.owner().name_eq(apex))
+            .map(|record| record.data())
+            .collect();
+        // Copy the keys since creating the signed data reorders `records`.
+        let keys: Vec<_> = self
+            .matching_keys(apex, keys)
+            .into_iter()
+            .map(|key| {
+                Dnskey::new(
+                    key.flags(),
+                    key.protocol(),
+                    key.algorithm(),
+                    Vec::from(key.public_key().as_ref()),
+                )
+            })
+            .collect();
+        if keys.is_empty() {
+            return Err(TrustError::NoMatchingKey);
+        }
+        let mut res = Err(TrustError::NoSignature);
+        for rrsig in rrsigs {
+            if rrsig.type_covered() != Rtype::Dnskey
+                || !rrsig.signer_name().name_eq(apex)
+            {
+                continue;
+            }
+            if rrsig.inception() > now || rrsig.expiration() < now {
+                res = Err(TrustError::Expired);
+                continue;
+            }
+            let key = match keys.iter().find(|key| {
+                key.key_tag() == rrsig.key_tag()
+                    && key.algorithm() == rrsig.algorithm()
+            }) {
+                Some(key) => key,
+                None => continue,
+            };
+            let mut signed_data = Vec::new();
+            if rrsig.signed_data(&mut signed_data, records).is_err() {
+                continue;
+            }
+            match rrsig.verify_signed_data(key, &signed_data) {
+                Ok(()) => return Ok(()),
+                Err(err) => res = Err(TrustError::Algorithm(err)),
+            }
+        }
+        res
+    }
+}
+
+//--- Extend and IntoIterator
+
+impl Extend<TrustAnchor> for TrustAnchors {
+    fn extend<I: IntoIterator<Item = TrustAnchor>>(&mut self, iter: I) {
+        self.anchors.extend(iter)
+    }
+}
+
+impl<'a> IntoIterator for &'a TrustAnchors {
+    type Item = &'a TrustAnchor;
+    type IntoIter = slice::Iter<'a, TrustAnchor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 //============ Error Types ===================================================
 
 //------------ AlgorithmError ------------------------------------------------
@@ -328,14 +677,61 @@ impl fmt::Display for AlgorithmError {
 
 impl error::Error for AlgorithmError {}
 
+//------------ TrustError ----------------------------------------------------
+
+/// A DNSKEY RRset could not be verified against the trust anchors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TrustError {
+    /// There are no supported anchors for the zone.
+    NoAnchor,
+
+    /// None of the keys matches an anchor.
+    NoMatchingKey,
+
+    /// There is no signature by a matching key.
+    NoSignature,
+
+    /// The signature is not valid at the current time.
+    Expired,
+
+    /// Verifying the signature failed.
+    Algorithm(AlgorithmError),
+}
+
+//--- From
+
+impl From<AlgorithmError> for TrustError {
+    fn from(err: AlgorithmError) -> Self {
+        TrustError::Algorithm(err)
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for TrustError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrustError::NoAnchor => f.write_str("no trust anchor for zone"),
+            TrustError::NoMatchingKey => {
+                f.write_str("no key matching a trust anchor")
+            }
+            TrustError::NoSignature => {
+                f.write_str("no signature by a trusted key")
+            }
+            TrustError::Expired => f.write_str("signature expired"),
+            TrustError::Algorithm(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for TrustError {}
+
 //============ Test ==========================================================
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::base::iana::{Class, Rtype, SecAlg};
-    use crate::base::serial::Serial;
-    use crate::master::scan::Scanner;
     use crate::rdata::{MasterRecordData, Mx};
     use crate::utils::base64;
     use bytes::Bytes;
@@ -598,10 +994,9 @@ mod test {
         assert!(rrsig.verify_signed_data(&ksk, &signed_data).is_ok());
     }
 
-    // Parse RRSIG serial from text.
-    fn rrsig_serial(x: &str) -> Serial {
-        let mut s = Scanner::new(x);
-        Serial::scan_rrsig(&mut s).unwrap()
+    // Parse RRSIG time from text.
+    fn rrsig_time(x: &str) -> Time32 {
+        x.parse().unwrap()
     }
 
     #[test]
@@ -624,8 +1019,8 @@ mod test {
             SecAlg::RsaSha1,
             2,
             3600,
-            rrsig_serial("20040509183619"),
-            rrsig_serial("20040409183619"),
+            rrsig_time("20040509183619"),
+            rrsig_time("20040409183619"),
             38519,
             Dname::from_str("example.").unwrap(),
             base64::decode(
@@ -655,4 +1050,170 @@ mod test {
         // Test verifier
         assert_eq!(rrsig.verify_signed_data(&key, &signed_data), Ok(()));
     }
+
+    #[test]
+    fn trust_anchor_matching() {
+        let (ksk, zsk) = root_pubkey();
+        let root = Dname::root();
+        let ds = TrustAnchor::from_ds(
+            &root,
+            &Ds::new(
+                20326,
+                SecAlg::RsaSha256,
+                DigestAlg::Sha256,
+                base64::decode(
+                    "4G1EuAuPHTmpXAsNfGXQhFjogECbvGg0VxBCN8f47I0=",
+                )
+                .unwrap()
+                .into(),
+            ),
+        );
+        assert!(ds.is_supported());
+        assert!(ds.matches_dnskey(&ksk));
+        assert!(!ds.matches_dnskey(&zsk));
+
+        let key = TrustAnchor::from_dnskey(&root, &zsk);
+        assert!(key.matches_dnskey(&zsk));
+        assert!(!key.matches_dnskey(&ksk));
+
+        // A revoked key must not match anymore.
+        let revoked = Dnskey::new(
+            ksk.flags() | 0x80,
+            ksk.protocol(),
+            ksk.algorithm(),
+            ksk.public_key().clone(),
+        );
+        assert!(!TrustAnchor::from_dnskey(&root, &revoked)
+            .matches_dnskey(&revoked));
+    }
+
+    #[test]
+    fn trust_anchors_closest() {
+        let (ksk, _) = root_pubkey();
+        let mut anchors = TrustAnchors::new();
+        anchors.push(TrustAnchor::from_dnskey(&Dname::root(), &ksk));
+        anchors.push(TrustAnchor::from_dnskey(
+            &Dname::from_str("corp.example.").unwrap(),
+            &ksk,
+        ));
+        anchors.push(TrustAnchor::from_dnskey(
+            &Dname::from_str("old.corp.example.").unwrap(),
+            &Dnskey::new(257, 3, SecAlg::RsaMd5, Bytes::from_static(b"key")),
+        ));
+
+        let closest = |name| {
+            anchors
+                .closest(&Dname::from_str(name).unwrap())
+                .map(|apex| format!("{}", apex))
+        };
+        assert_eq!(closest("www.corp.example."), Some("corp.example".into()));
+        assert_eq!(closest("CORP.example."), Some("corp.example".into()));
+        assert_eq!(
+            closest("www.old.corp.example."),
+            Some("corp.example".into())
+        );
+        assert_eq!(closest("www.example."), Some("".into()));
+    }
+
+    #[test]
+    fn trust_anchors_verify_island() {
+        let (ksk, zsk) = (
+            Dnskey::new(
+                257,
+                3,
+                SecAlg::Ed25519,
+                base64::decode(
+                    "m1NELLVVQKl4fHVn/KKdeNO0PrYKGT3IGbYseT8XcKo=",
+                )
+                .unwrap()
+                .into(),
+            ),
+            Dnskey::new(
+                256,
+                3,
+                SecAlg::Ed25519,
+                base64::decode(
+                    "2tstZAjgmlDTePn0NVXrAHBJmg84LoaFVxzLl1anjGI=",
+                )
+                .unwrap()
+                .into(),
+            ),
+        );
+        let apex = Dname::from_str("ed25519.nl.").unwrap();
+        let rrsig = Rrsig::new(
+            Rtype::Dnskey,
+            SecAlg::Ed25519,
+            2,
+            3600,
+            1559174400.into(),
+            1557360000.into(),
+            45515,
+            Dname::from_str("ED25519.nl.").unwrap(),
+            base64::decode(
+                "hvPSS3E9Mx7lMARqtv6IGiw0NE0uz0mZewndJCHTkhwSYqlasUq7KfO5\
+                QdtgPXja7YkTaqzrYUbYk01J8ICsAA==",
+            )
+            .unwrap()
+            .into(),
+        );
+        let owner =
+            Dname::from_octets(Bytes::from_static(b"\x07ED25519\x02nl\x00"))
+                .unwrap();
+        let mut records: Vec<_> = [&ksk, &zsk]
+            .iter()
+            .map(|key| {
+                Record::new(owner.clone(), Class::In, 3600, (*key).clone())
+            })
+            .collect();
+        let rrsigs = [rrsig];
+
+        let mut anchors = TrustAnchors::new();
+        assert_eq!(
+            anchors.verify_dnskeys(
+                &apex,
+                &mut records,
+                &rrsigs,
+                1558000000.into()
+            ),
+            Err(TrustError::NoAnchor)
+        );
+
+        // Anchoring the ZSK doesn’t help since it didn’t sign the RRset.
+        anchors.push(TrustAnchor::from_dnskey(&apex, &zsk));
+        assert_eq!(
+            anchors.verify_dnskeys(
+                &apex,
+                &mut records,
+                &rrsigs,
+                1558000000.into()
+            ),
+            Err(TrustError::NoSignature)
+        );
+
+        anchors.push(TrustAnchor::from_dnskey(&apex, &ksk));
+        assert_eq!(
+            anchors
+                .matching_keys(&apex, records.iter().map(Record::data))
+                .len(),
+            2
+        );
+        assert_eq!(
+            anchors.verify_dnskeys(
+                &apex,
+                &mut records,
+                &rrsigs,
+                1558000000.into()
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            anchors.verify_dnskeys(
+                &apex,
+                &mut records,
+                &rrsigs,
+                1560000000.into()
+            ),
+            Err(TrustError::Expired)
+        );
+    }
 }